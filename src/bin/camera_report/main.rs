@@ -0,0 +1,396 @@
+//! Generates a camera profiling report from bracketed exposures, for
+//! documenting and sharing camera profiles with a team.
+//!
+//! The report includes the estimated transfer function, the sensor
+//! floor and ceiling, how well the estimate fits the images, and how it
+//! compares to the built-in transfer functions.
+
+use std::path::{Path, PathBuf};
+
+use sensor_analysis::{emor, utils::lerp_slice, ExposureMapping};
+
+use lib::{
+    bracket_analysis::{
+        bracket_floor_ceiling, bracket_images_to_histogram_sets, compensate_bracket_set,
+        dark_floor, exposure_mappings, load_image_set, reject_bracket_images,
+    },
+    chroma_space::ChromaSpace,
+    transfer_function::{TransferFunction, TransferFunctionID, TRANSFER_FUNCTION_IDS},
+};
+
+mod report;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Resolution of the estimated curves in the report.
+const CURVE_RESOLUTION: usize = 1024;
+
+fn main() {
+    use clap::Arg;
+
+    let args = clap::App::new("ETF Camera Report")
+        .version(VERSION)
+        .author("Nathan Vegdahl, Ian Hubert")
+        .about("Generates a camera profiling report from bracketed exposures")
+        .arg(
+            Arg::with_name("brackets")
+                .long("brackets")
+                .value_name("DIR")
+                .takes_value(true)
+                .required(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("A directory of bracketed exposure images, used as one image set.  Can be given multiple times"),
+        )
+        .arg(
+            Arg::with_name("reject_frames")
+                .long("reject-frames")
+                .help("Excludes bracketed images that duplicate another image of their set, or whose brightness doesn't fit their exposure (e.g. the lighting changed).  Without this they're only warned about"),
+        )
+        .arg(
+            Arg::with_name("dark")
+                .long("dark")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("A directory of lens cap (dark) images, for estimating the sensor noise floor"),
+        )
+        .arg(
+            Arg::with_name("name")
+                .long("name")
+                .value_name("CAMERA")
+                .takes_value(true)
+                .default_value("Camera")
+                .help("Camera name, used as the report title"),
+        )
+        .arg(
+            Arg::with_name("rounds")
+                .long("rounds")
+                .value_name("N")
+                .takes_value(true)
+                .default_value("4000")
+                .help("Number of transfer function estimation rounds"),
+        )
+        .arg(
+            Arg::with_name("smoothness")
+                .long("smoothness")
+                .value_name("AMOUNT")
+                .takes_value(true)
+                .default_value("0")
+                .help("Smoothness regularization of the estimated curve"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .possible_values(&["html", "md"])
+                .default_value("html")
+                .help("Report format.  Markdown reports write the curve plot to a separate .svg file next to the report"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .takes_value(true)
+                .required(true)
+                .help("File to write the report to"),
+        )
+        .get_matches();
+
+    std::process::exit(match run(&args) {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            1
+        }
+    });
+}
+
+fn run(args: &clap::ArgMatches) -> Result<(), String> {
+    // Options.
+    let output_path = PathBuf::from(args.value_of("output").unwrap());
+    let rounds: usize = args
+        .value_of("rounds")
+        .unwrap()
+        .parse()
+        .map_err(|_| "--rounds must be a whole number")?;
+    let smoothness: f32 = args
+        .value_of("smoothness")
+        .unwrap()
+        .parse()
+        .map_err(|_| "--smoothness must be a number")?;
+
+    let mut report = Report::new(args.value_of("name").unwrap());
+    report.rounds = rounds;
+    let mut note = |line: String| eprintln!("{}", line);
+
+    // Load images.  Float images all share the encoding of the first
+    // one loaded, so that their histograms are comparable.
+    let mut float_encoding = None;
+    let mut bracket_sets = Vec::new();
+    for dir in args.values_of("brackets").into_iter().flatten() {
+        let mut set = load_image_set(Path::new(dir), &mut float_encoding, &mut note)?;
+        compensate_bracket_set(&mut set, &mut note);
+        reject_bracket_images(&mut set, args.is_present("reject_frames"), &mut note);
+        report.image_sets.push((dir.to_string(), set.len()));
+        bracket_sets.push(set);
+    }
+    let dark_images = match args.value_of("dark") {
+        Some(dir) => load_image_set(Path::new(dir), &mut float_encoding, &mut note)?,
+        None => Vec::new(),
+    };
+    report.dark_image_count = dark_images.len();
+    report.exif_camera = bracket_sets
+        .iter()
+        .flatten()
+        .chain(dark_images.iter())
+        .find_map(|(_, info)| info.camera.clone());
+
+    let histogram_sets = bracket_images_to_histogram_sets(&bracket_sets);
+    if !histogram_sets
+        .iter()
+        .any(|hs| hs.iter().all(|h| h.len() >= 2))
+    {
+        return Err(
+            "estimation needs at least two bracketed images with exposure Exif data".into(),
+        );
+    }
+
+    // Floor and ceiling.
+    let (bracket_floor, ceiling) = bracket_floor_ceiling(&histogram_sets);
+    report.floor = if dark_images.is_empty() {
+        bracket_floor
+    } else {
+        report.floor_from_dark_images = true;
+        dark_floor(&dark_images, |n| n, |n| n)
+    };
+    report.ceiling = ceiling;
+    if let Some((make, model)) = &report.exif_camera {
+        report.known_camera = camera_db::find_camera(make, model).copied();
+    }
+
+    // Estimate the transfer function.
+    let channel_mappings = exposure_mappings(&histogram_sets, report.floor, report.ceiling);
+    let mappings: Vec<ExposureMapping> = channel_mappings.iter().flatten().cloned().collect();
+    if mappings.is_empty() {
+        return Err("not enough valid images to estimate the transfer function".into());
+    }
+    let mut estimator = emor::EmorEstimator::new(&mappings).with_smoothness(smoothness);
+    let mut rounds_done = 0;
+    while rounds_done < rounds {
+        eprint!(
+            "\rEstimating transfer function, round {}/{}",
+            rounds_done, rounds
+        );
+        rounds_done +=
+            estimator.do_rounds_for(rounds - rounds_done, std::time::Duration::from_millis(100));
+    }
+    eprintln!();
+
+    let (inv_emor_factors, err) = estimator.current_estimate();
+    report.fit_error = err;
+    let divergence = emor::channel_errors(
+        &[
+            &channel_mappings[0][..],
+            &channel_mappings[1][..],
+            &channel_mappings[2][..],
+        ],
+        &inv_emor_factors,
+        None,
+    );
+    report.channel_divergence = [divergence[0], divergence[1], divergence[2]];
+
+    // Normalize each channel's curve to its floor and ceiling.
+    let curve = emor::inv_emor_factors_to_curve(&inv_emor_factors, 0.0, 1.0);
+    for chan in 0..3 {
+        let out_floor = lerp_slice(&curve, report.floor[chan]);
+        let out_ceil = lerp_slice(&curve, report.ceiling[chan]);
+        let norm = 1.0 / (out_ceil - out_floor);
+        report.curves[chan] = (0..CURVE_RESOLUTION)
+            .map(|i| {
+                let x = i as f32 / (CURVE_RESOLUTION - 1) as f32;
+                (lerp_slice(&curve, x) - out_floor) * norm
+            })
+            .collect();
+    }
+
+    // Compare to the built-in transfer functions.
+    report.matches = TRANSFER_FUNCTION_IDS
+        .iter()
+        .filter_map(|id| {
+            let function = TransferFunction {
+                id: *id,
+                ..TransferFunction::default()
+            };
+            compare_to_function(&report.curves, report.floor, report.ceiling, function)
+        })
+        .collect();
+    report
+        .matches
+        .sort_unstable_by(|a, b| a.rms_deviation.partial_cmp(&b.rms_deviation).unwrap());
+
+    // Write the report.
+    let write_result = match args.value_of("format").unwrap() {
+        "md" => report.write_markdown(&output_path),
+        _ => report.write_html(&output_path),
+    };
+    write_result.map_err(|e| {
+        format!(
+            "unable to write report \"{}\": {}",
+            output_path.to_string_lossy(),
+            e
+        )
+    })?;
+    println!("Wrote: {}", output_path.to_string_lossy());
+
+    Ok(())
+}
+
+/// Everything that goes into the report.
+pub struct Report {
+    pub camera_name: String,
+    pub exif_camera: Option<(String, String)>, // (make, model)
+    pub known_camera: Option<camera_db::CameraInfo>,
+
+    pub image_sets: Vec<(String, usize)>, // (directory, image count)
+    pub dark_image_count: usize,
+
+    pub floor: [f32; 3],
+    pub floor_from_dark_images: bool,
+    pub ceiling: [f32; 3],
+
+    pub rounds: usize,
+    pub fit_error: f32,
+    pub channel_divergence: [f32; 3],
+
+    /// The estimated to-linear curve of each channel, normalized to
+    /// its floor and ceiling, across the encoded range [0.0, 1.0].
+    pub curves: [Vec<f32>; 3],
+
+    /// The built-in transfer functions, closest match first.
+    pub matches: Vec<CurveMatch>,
+}
+
+impl Report {
+    fn new(camera_name: &str) -> Report {
+        Report {
+            camera_name: camera_name.into(),
+            exif_camera: None,
+            known_camera: None,
+
+            image_sets: Vec::new(),
+            dark_image_count: 0,
+
+            floor: [0.0; 3],
+            floor_from_dark_images: false,
+            ceiling: [1.0; 3],
+
+            rounds: 0,
+            fit_error: 0.0,
+            channel_divergence: [0.0; 3],
+
+            curves: [Vec::new(), Vec::new(), Vec::new()],
+
+            matches: Vec::new(),
+        }
+    }
+
+    /// The chromaticities suggested for the camera's color space in
+    /// an OCIO config: the camera's own gamut if it's a known camera,
+    /// and otherwise based on the closest built-in transfer function.
+    pub fn suggested_chroma_space(
+        &self,
+    ) -> Option<(ChromaSpace, colorbox::chroma::Chromaticities)> {
+        let chroma_space = match self.known_camera {
+            Some(camera) => camera.gamut.into(),
+            None => native_chroma_space(self.matches.first()?.function.id),
+        };
+        let chroma = chroma_space.chromaticities(colorbox::chroma::REC709)?;
+        Some((chroma_space, chroma))
+    }
+}
+
+/// How closely the estimated curves match a built-in transfer function.
+pub struct CurveMatch {
+    pub function: TransferFunction,
+
+    /// Average exposure difference, in stops.  This is removed before
+    /// measuring the deviations, since the estimated curves are
+    /// normalized to their ceilings rather than calibrated to an
+    /// absolute exposure.
+    pub exposure_offset: f32,
+
+    /// Root mean square and maximum deviation, in stops.
+    pub rms_deviation: f32,
+    pub max_deviation: f32,
+}
+
+/// Compares the estimated curves to a built-in transfer function, in
+/// stops, between the sensor floor and ceiling.
+///
+/// Returns `None` if there's no overlap to compare.
+fn compare_to_function(
+    curves: &[Vec<f32>; 3],
+    floor: [f32; 3],
+    ceiling: [f32; 3],
+    function: TransferFunction,
+) -> Option<CurveMatch> {
+    const SAMPLES: usize = 256;
+    const MIN_LINEAR: f32 = 1.0 / 4096.0;
+
+    let mut errors = Vec::new();
+    for chan in 0..3 {
+        for i in 0..SAMPLES {
+            let x = floor[chan] + (ceiling[chan] - floor[chan]) * (i as f32 / (SAMPLES - 1) as f32);
+            let estimated = lerp_slice(&curves[chan], x);
+            let reference = function.to_linear_fc(x, None, None, true);
+            if estimated > MIN_LINEAR && reference > MIN_LINEAR {
+                errors.push((estimated / reference).log2());
+            }
+        }
+    }
+    if errors.is_empty() {
+        return None;
+    }
+
+    let offset = errors.iter().sum::<f32>() / errors.len() as f32;
+    let mut sum_squared = 0.0f32;
+    let mut max_deviation = 0.0f32;
+    for error in errors.iter() {
+        let deviation = error - offset;
+        sum_squared += deviation * deviation;
+        max_deviation = max_deviation.max(deviation.abs());
+    }
+
+    Some(CurveMatch {
+        function: function,
+        exposure_offset: offset,
+        rms_deviation: (sum_squared / errors.len() as f32).sqrt(),
+        max_deviation: max_deviation,
+    })
+}
+
+/// The color gamut that cameras using a transfer function usually
+/// record in.
+fn native_chroma_space(id: TransferFunctionID) -> ChromaSpace {
+    use TransferFunctionID::*;
+    match id {
+        Linear | Custom => ChromaSpace::None,
+
+        ARRILogC3 => ChromaSpace::ARRIWideGamut3,
+        ARRILogC4 => ChromaSpace::ARRIWideGamut4,
+        BlackmagicFilmGen5 => ChromaSpace::BlackmagicWideGamutGen4,
+        DavinciIntermediate => ChromaSpace::DavinciWideGamut,
+        CanonLog1 | CanonLog2 | CanonLog3 => ChromaSpace::CanonCinemaGamut,
+        DJIDlog => ChromaSpace::DJIDGamut,
+        FujifilmFlog => ChromaSpace::Rec2020, // F-Gamut.
+        HLG | PQ => ChromaSpace::Rec2020,
+        NikonNlog => ChromaSpace::Rec2020, // N-Gamut.
+        PanasonicVlog => ChromaSpace::PanasonicVGamut,
+        Rec709 | sRGB => ChromaSpace::Rec709,
+        RedLog3G10 => ChromaSpace::RedWideGamutRGB,
+        SonySlog1 | SonySlog2 => ChromaSpace::SGamut,
+        SonySlog3 => ChromaSpace::SGamut3Cine,
+    }
+}
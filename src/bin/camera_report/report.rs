@@ -0,0 +1,347 @@
+//! Writing the report as HTML or Markdown.
+
+use std::{fmt::Write as _, path::Path};
+
+use crate::{Report, VERSION};
+
+/// How many of the closest built-in transfer functions to list.
+const MATCH_COUNT: usize = 5;
+
+const CHANNEL_NAMES: [&str; 3] = ["Red", "Green", "Blue"];
+const CHANNEL_COLORS: [&str; 3] = ["#dc1414", "#14b414", "#1414dc"];
+
+impl Report {
+    pub fn write_html(&self, path: &Path) -> std::io::Result<()> {
+        let mut html = String::new();
+        let w = &mut html;
+
+        writeln!(w, "<!DOCTYPE html>").unwrap();
+        writeln!(w, "<html>\n<head>\n<meta charset=\"utf-8\">").unwrap();
+        writeln!(
+            w,
+            "<title>Camera Report: {}</title>",
+            escape(&self.camera_name)
+        )
+        .unwrap();
+        writeln!(
+            w,
+            "<style>body {{ font-family: sans-serif; max-width: 50em; margin: auto; }} \
+             table {{ border-collapse: collapse; }} \
+             th, td {{ border: 1px solid #aaa; padding: 0.2em 0.6em; text-align: left; }}</style>"
+        )
+        .unwrap();
+        writeln!(w, "</head>\n<body>").unwrap();
+        writeln!(w, "<h1>Camera Report: {}</h1>", escape(&self.camera_name)).unwrap();
+        writeln!(w, "<p><em>{}</em></p>", escape(&self.generated_by())).unwrap();
+
+        for (title, rows) in self.sections() {
+            writeln!(w, "<h2>{}</h2>", title).unwrap();
+            if title == "Estimated Curve" {
+                w.push_str(&self.curve_svg());
+                w.push('\n');
+            }
+            html_table(w, &rows);
+        }
+        if let Some(note) = self.suggestion_note() {
+            writeln!(w, "<p>{}</p>", escape(&note)).unwrap();
+        }
+
+        writeln!(w, "</body>\n</html>").unwrap();
+
+        std::fs::write(path, html)
+    }
+
+    /// Writes the report as Markdown, with the curve plot as an .svg
+    /// file next to it.
+    pub fn write_markdown(&self, path: &Path) -> std::io::Result<()> {
+        let svg_path = path.with_file_name(format!(
+            "{}_curve.svg",
+            path.file_stem().unwrap_or_default().to_string_lossy()
+        ));
+        std::fs::write(&svg_path, self.curve_svg())?;
+
+        let mut md = String::new();
+        let w = &mut md;
+
+        writeln!(w, "# Camera Report: {}\n", self.camera_name).unwrap();
+        writeln!(w, "*{}*\n", self.generated_by()).unwrap();
+
+        for (title, rows) in self.sections() {
+            writeln!(w, "## {}\n", title).unwrap();
+            if title == "Estimated Curve" {
+                writeln!(
+                    w,
+                    "![Estimated curve]({})\n",
+                    svg_path.file_name().unwrap().to_string_lossy()
+                )
+                .unwrap();
+            }
+            markdown_table(w, &rows);
+            w.push('\n');
+        }
+        if let Some(note) = self.suggestion_note() {
+            writeln!(w, "{}", note).unwrap();
+        }
+
+        std::fs::write(path, md)
+    }
+
+    fn generated_by(&self) -> String {
+        format!("Generated by ETF Camera Report v{}", VERSION)
+    }
+
+    /// The report contents, as titled tables.  The first row of each
+    /// table is its header.
+    fn sections(&self) -> Vec<(&'static str, Vec<Vec<String>>)> {
+        let rgb = |v: [f32; 3]| -> Vec<String> { v.iter().map(|n| format!("{:.5}", n)).collect() };
+        let mut sections = Vec::new();
+
+        // Images.
+        let mut rows = vec![vec!["".into(), "Value".into()]];
+        if let Some((make, model)) = &self.exif_camera {
+            rows.push(vec!["Camera (Exif)".into(), format!("{} {}", make, model)]);
+        }
+        for (dir, count) in self.image_sets.iter() {
+            rows.push(vec![
+                format!("Bracketed set \"{}\"", dir),
+                format!("{} images", count),
+            ]);
+        }
+        rows.push(vec![
+            "Dark images".into(),
+            format!("{} images", self.dark_image_count),
+        ]);
+        sections.push(("Source Images", rows));
+
+        // Floor and ceiling.
+        let mut rows = vec![vec![
+            String::new(),
+            CHANNEL_NAMES[0].into(),
+            CHANNEL_NAMES[1].into(),
+            CHANNEL_NAMES[2].into(),
+        ]];
+        let floor_source = if self.floor_from_dark_images {
+            "Floor (from dark images)"
+        } else {
+            "Floor (from brackets)"
+        };
+        rows.push([vec![floor_source.into()], rgb(self.floor)].concat());
+        rows.push([vec!["Ceiling".into()], rgb(self.ceiling)].concat());
+        if let Some(camera) = &self.known_camera {
+            let expected = camera.black_level_normalized();
+            rows.push(
+                [
+                    vec![format!(
+                        "Known black level ({} {})",
+                        camera.make, camera.model
+                    )],
+                    self.floor
+                        .iter()
+                        .map(|floor| {
+                            if camera.floor_diverges(*floor) {
+                                format!("{:.5} (diverges)", expected)
+                            } else {
+                                format!("{:.5}", expected)
+                            }
+                        })
+                        .collect(),
+                ]
+                .concat(),
+            );
+        }
+        sections.push(("Sensor Floor and Ceiling", rows));
+
+        // Estimate.
+        let rows = vec![
+            vec!["".into(), "Value".into()],
+            vec!["Estimation rounds".into(), self.rounds.to_string()],
+            vec!["Average fit error".into(), format!("{:.6}", self.fit_error)],
+            vec![
+                "Per-channel divergence (R, G, B)".into(),
+                format!(
+                    "{:.4}, {:.4}, {:.4}",
+                    self.channel_divergence[0],
+                    self.channel_divergence[1],
+                    self.channel_divergence[2]
+                ),
+            ],
+        ];
+        sections.push(("Estimated Curve", rows));
+
+        // Comparison.
+        let mut rows = vec![vec![
+            "Transfer function".into(),
+            "RMS deviation (stops)".into(),
+            "Max deviation (stops)".into(),
+            "Exposure offset (stops)".into(),
+        ]];
+        for m in self.matches.iter().take(MATCH_COUNT) {
+            rows.push(vec![
+                m.function.id.ui_text().into(),
+                format!("{:.3}", m.rms_deviation),
+                format!("{:.3}", m.max_deviation),
+                format!("{:+.2}", m.exposure_offset),
+            ]);
+        }
+        sections.push(("Closest Built-in Transfer Functions", rows));
+
+        // Suggested chromaticities.
+        if let Some((chroma_space, chroma)) = self.suggested_chroma_space() {
+            let xy = |p: (f64, f64)| format!("{:.4}, {:.4}", p.0, p.1);
+            let rows = vec![
+                vec![chroma_space.ui_text().into(), "x, y".into()],
+                vec!["Red".into(), xy(chroma.r)],
+                vec!["Green".into(), xy(chroma.g)],
+                vec!["Blue".into(), xy(chroma.b)],
+                vec!["White point".into(), xy(chroma.w)],
+            ];
+            sections.push(("Suggested OCIO Chromaticities", rows));
+        }
+
+        sections
+    }
+
+    fn suggestion_note(&self) -> Option<String> {
+        let function = self.matches.first()?.function;
+        self.suggested_chroma_space()?;
+        Some(format!(
+            "The chromaticities are suggested because the estimated curve is closest to {}, \
+             and are the gamut that cameras using it usually record in.  Check them against \
+             the camera's documentation before using them in a config.",
+            function.id.ui_text()
+        ))
+    }
+
+    /// A plot of the estimated curves, with the closest built-in
+    /// transfer function dashed.
+    fn curve_svg(&self) -> String {
+        const WIDTH: f32 = 480.0;
+        const HEIGHT: f32 = 360.0;
+        const MARGIN: f32 = 40.0;
+        let to_svg = |x: f32, y: f32| -> (f32, f32) {
+            (
+                MARGIN + x * (WIDTH - MARGIN * 2.0),
+                HEIGHT - MARGIN - y * (HEIGHT - MARGIN * 2.0),
+            )
+        };
+        let polyline = |points: &mut dyn Iterator<Item = (f32, f32)>| -> String {
+            let mut text = String::new();
+            for (x, y) in points {
+                let (x, y) = to_svg(x, y.max(-0.05).min(1.05));
+                write!(text, "{:.1},{:.1} ", x, y).unwrap();
+            }
+            text
+        };
+
+        let mut svg = String::new();
+        let w = &mut svg;
+        writeln!(
+            w,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">",
+            WIDTH, HEIGHT
+        )
+        .unwrap();
+        writeln!(
+            w,
+            "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+            WIDTH, HEIGHT
+        )
+        .unwrap();
+
+        // Axes.
+        let (x0, y0) = to_svg(0.0, 0.0);
+        let (x1, y1) = to_svg(1.0, 1.0);
+        writeln!(
+            w,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#888\"/>",
+            x0,
+            y1,
+            x1 - x0,
+            y0 - y1
+        )
+        .unwrap();
+        writeln!(
+            w,
+            "<text x=\"{}\" y=\"{}\" font-size=\"12\" text-anchor=\"middle\">Encoded value</text>",
+            (x0 + x1) / 2.0,
+            HEIGHT - MARGIN / 3.0
+        )
+        .unwrap();
+        writeln!(
+            w,
+            "<text x=\"{0}\" y=\"{1}\" font-size=\"12\" text-anchor=\"middle\" transform=\"rotate(-90 {0} {1})\">Linear value</text>",
+            MARGIN / 2.0,
+            (y0 + y1) / 2.0
+        )
+        .unwrap();
+
+        // The closest built-in function, at the estimate's exposure.
+        if let Some(m) = self.matches.first() {
+            let scale = 2.0f32.powf(m.exposure_offset);
+            let mut points = (0..=256).map(|i| {
+                let x = i as f32 / 256.0;
+                (x, m.function.to_linear_fc(x, None, None, true) * scale)
+            });
+            writeln!(
+                w,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"#666\" stroke-dasharray=\"6 4\"/>",
+                polyline(&mut points)
+            )
+            .unwrap();
+            writeln!(
+                w,
+                "<text x=\"{}\" y=\"{}\" font-size=\"12\" fill=\"#666\">- - {}</text>",
+                x0 + 8.0,
+                y1 + 16.0,
+                escape(m.function.id.ui_text())
+            )
+            .unwrap();
+        }
+
+        // The estimated curves.
+        for (curve, color) in self.curves.iter().zip(CHANNEL_COLORS.iter()) {
+            let norm = 1.0 / (curve.len() - 1) as f32;
+            let mut points = curve.iter().enumerate().map(|(i, y)| (i as f32 * norm, *y));
+            writeln!(
+                w,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"/>",
+                polyline(&mut points),
+                color
+            )
+            .unwrap();
+        }
+
+        writeln!(w, "</svg>").unwrap();
+        svg
+    }
+}
+
+fn html_table(w: &mut String, rows: &[Vec<String>]) {
+    w.push_str("<table>\n");
+    for (i, row) in rows.iter().enumerate() {
+        let tag = if i == 0 { "th" } else { "td" };
+        w.push_str("<tr>");
+        for cell in row.iter() {
+            write!(w, "<{0}>{1}</{0}>", tag, escape(cell)).unwrap();
+        }
+        w.push_str("</tr>\n");
+    }
+    w.push_str("</table>\n");
+}
+
+fn markdown_table(w: &mut String, rows: &[Vec<String>]) {
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        writeln!(w, "| {} |", cells.join(" | ")).unwrap();
+        if i == 0 {
+            writeln!(w, "|{}", " --- |".repeat(row.len())).unwrap();
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,376 @@
+//! Applies a LUT, or a color space conversion of an OCIO config, to
+//! images and image sequences.  Meant for render pipelines and other
+//! scripted use, so it has no GUI.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use image_fmt::{Image, ImageBuf, WriteFormat};
+use ocio_gen::{
+    config::{Interpolation, OCIOConfig, OutputFile, Transform},
+    eval::Evaluator,
+    lut_extrapolation::Extrapolation,
+};
+use rayon::prelude::*;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The image formats that can be read.
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "tif", "tiff", "png"];
+
+const WRITE_FORMATS: &[WriteFormat] = &[WriteFormat::Png, WriteFormat::Tiff, WriteFormat::Exr];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum BitDepth {
+    Eight,
+    Sixteen,
+    Float,
+}
+
+fn main() {
+    use clap::Arg;
+
+    let args = clap::App::new("ETF Apply")
+        .version(VERSION)
+        .author("Nathan Vegdahl, Ian Hubert")
+        .about("Applies a LUT or an OCIO color space conversion to images")
+        .arg(
+            Arg::with_name("input")
+                .value_name("INPUT")
+                .required(true)
+                .multiple(true)
+                .help("Image files, or directories of images such as image sequences"),
+        )
+        .arg(
+            Arg::with_name("lut")
+                .long("lut")
+                .value_name("LUT_FILE")
+                .takes_value(true)
+                .required_unless("config")
+                .conflicts_with("config")
+                .help("A 1D or 3D LUT to apply (.cube or .spi1d)"),
+        )
+        .arg(
+            Arg::with_name("inverse")
+                .long("inverse")
+                .requires("lut")
+                .help("Applies the inverse of the LUT.  Only 1D LUTs can be inverted"),
+        )
+        .arg(
+            Arg::with_name("extrapolation")
+                .long("extrapolation")
+                .value_name("POLICY")
+                .takes_value(true)
+                .possible_values(&["clamp", "linear", "slope"])
+                .default_value("clamp")
+                .help("What 1D LUTs do with input outside of their domain: clamp to the ends of the table, extend the end segments as straight lines, or extend with the table's average slope"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("CONFIG_FILE")
+                .takes_value(true)
+                .requires("from")
+                .help("An OCIO config, such as one exported by OCIO Maker, to convert between color spaces of"),
+        )
+        .arg(
+            Arg::with_name("from")
+                .long("from")
+                .value_name("COLOR_SPACE")
+                .takes_value(true)
+                .requires("config")
+                .help("The color space of the input images"),
+        )
+        .arg(
+            Arg::with_name("to")
+                .long("to")
+                .value_name("COLOR_SPACE")
+                .takes_value(true)
+                .requires("config")
+                .required_unless_one(&["lut", "display"])
+                .conflicts_with("display")
+                .help("The color space to convert to"),
+        )
+        .arg(
+            Arg::with_name("display")
+                .long("display")
+                .value_name("DISPLAY")
+                .takes_value(true)
+                .requires_all(&["config", "view"])
+                .help("A display to convert to, instead of a color space.  Requires --view"),
+        )
+        .arg(
+            Arg::with_name("view")
+                .long("view")
+                .value_name("VIEW")
+                .takes_value(true)
+                .requires("display")
+                .help("The view of the display to convert to"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("DIR")
+                .takes_value(true)
+                .required(true)
+                .help("Directory to write the output images to.  They keep their file names, with the extension of the output format"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .possible_values(&["png", "tiff", "exr"])
+                .default_value("exr")
+                .help("Output image format"),
+        )
+        .arg(
+            Arg::with_name("depth")
+                .long("depth")
+                .value_name("BITS")
+                .takes_value(true)
+                .possible_values(&["8", "16", "32"])
+                .help("Output bit depth, where 32 is float.  Defaults to 16 for PNG and TIFF, and is always 32 for EXR"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .short("j")
+                .long("threads")
+                .value_name("N")
+                .takes_value(true)
+                .help("Number of threads to use.  Defaults to the number of CPU cores"),
+        )
+        .get_matches();
+
+    std::process::exit(run(&args));
+}
+
+/// Returns the process exit code.
+fn run(args: &clap::ArgMatches) -> i32 {
+    match run_inner(args) {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            1
+        }
+    }
+}
+
+fn run_inner(args: &clap::ArgMatches) -> Result<(), String> {
+    // Options.
+    let output_dir = PathBuf::from(args.value_of("output").unwrap());
+    let format = {
+        let ext = args.value_of("format").unwrap();
+        *WRITE_FORMATS.iter().find(|f| f.ext() == ext).unwrap()
+    };
+    let depth = match (format, args.value_of("depth")) {
+        (WriteFormat::Exr, None | Some("32")) => BitDepth::Float,
+        (WriteFormat::Exr, Some(_)) => return Err("EXR output is always 32-bit float".into()),
+        (WriteFormat::Png, Some("32")) => {
+            return Err("PNG output can't be 32-bit float.  Use TIFF or EXR instead".into())
+        }
+        (_, Some("8")) => BitDepth::Eight,
+        (_, Some("32")) => BitDepth::Float,
+        (_, _) => BitDepth::Sixteen,
+    };
+    if let Some(threads) = args.value_of("threads") {
+        let threads: usize = threads
+            .parse()
+            .map_err(|_| "--threads must be a whole number")?;
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|e| format!("unable to start threads: {}", e))?;
+    }
+
+    // The transform.
+    let (config, transforms) = if let Some(lut_path) = args.value_of("lut") {
+        lut_transform(Path::new(lut_path), args.is_present("inverse"))?
+    } else {
+        let config_path = Path::new(args.value_of("config").unwrap());
+        let (config, warnings) = ocio_gen::import::read_config(config_path).map_err(|e| {
+            format!(
+                "unable to load OCIO config \"{}\": {}",
+                config_path.to_string_lossy(),
+                e
+            )
+        })?;
+        for warning in warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        let from = args.value_of("from").unwrap();
+        let transforms = {
+            let evaluator = Evaluator::new(&config);
+            match (args.value_of("to"), args.value_of("display")) {
+                (Some(to), _) => evaluator.processor(from, to)?,
+                (None, Some(display)) => {
+                    evaluator.view_processor(from, display, args.value_of("view").unwrap())?
+                }
+                (None, None) => unreachable!(),
+            }
+        };
+        (config, transforms)
+    };
+    let mut evaluator = Evaluator::new(&config);
+    evaluator.extrapolation =
+        Extrapolation::from_str(args.value_of("extrapolation").unwrap()).unwrap();
+    if let (true, Some(path)) = (args.is_present("lut"), evaluator.unreadable_files.first()) {
+        return Err(format!(
+            "unable to read LUT \"{}\".  Only .cube and .spi1d LUTs are supported",
+            path.to_string_lossy()
+        ));
+    }
+    // Catches unsupported transforms before any images are processed.
+    evaluator.eval(&transforms, [0.18; 3])?;
+
+    // Inputs.
+    let mut inputs = Vec::new();
+    for input in args.values_of("input").unwrap() {
+        let path = Path::new(input);
+        if path.is_dir() {
+            inputs.extend(images_in_dir(path)?);
+        } else {
+            inputs.push(path.to_path_buf());
+        }
+    }
+    if inputs.is_empty() {
+        return Err("no input images found".into());
+    }
+
+    std::fs::create_dir_all(&output_dir).map_err(|e| {
+        format!(
+            "unable to create output directory \"{}\": {}",
+            output_dir.to_string_lossy(),
+            e
+        )
+    })?;
+
+    // Process the images, a few at a time.
+    let failed_count = AtomicUsize::new(0);
+    inputs.par_iter().for_each(|input| {
+        let output = output_dir
+            .join(input.file_stem().unwrap_or_default())
+            .with_extension(format.ext());
+        match apply_to_file(input, &output, &evaluator, &transforms, format, depth) {
+            Ok(()) => println!("Wrote: {}", output.to_string_lossy()),
+            Err(e) => {
+                eprintln!("Error: \"{}\": {}", input.to_string_lossy(), e);
+                failed_count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+
+    match failed_count.into_inner() {
+        0 => Ok(()),
+        n => Err(format!("{} of {} images failed", n, inputs.len())),
+    }
+}
+
+/// A config and transform that applies a single LUT file.
+fn lut_transform(path: &Path, inverse: bool) -> Result<(OCIOConfig, Vec<Transform>), String> {
+    let data = std::fs::read(path)
+        .map_err(|e| format!("unable to read LUT \"{}\": {}", path.to_string_lossy(), e))?;
+    let src = path.to_string_lossy().to_string();
+
+    let mut config = OCIOConfig::default();
+    config
+        .external_files
+        .insert(src.clone().into(), OutputFile::Raw(data));
+    let transforms = vec![Transform::FileTransform {
+        src: src,
+        interpolation: Interpolation::Linear,
+        direction_inverse: inverse,
+    }];
+
+    Ok((config, transforms))
+}
+
+/// All readable images in a directory, sorted by file name so that
+/// image sequences stay in order.
+fn images_in_dir(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| {
+            format!(
+                "unable to read directory \"{}\": {}",
+                dir.to_string_lossy(),
+                e
+            )
+        })?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .map(|ext| IMAGE_EXTENSIONS.contains(&&*ext.to_string_lossy().to_lowercase()))
+                .unwrap_or(false)
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Loads an image, applies the transforms to it, and writes it to
+/// `output`.
+///
+/// Integer images are normalized to [0.0, 1.0] before the transforms
+/// are applied.  Alpha is kept as-is.
+fn apply_to_file(
+    input: &Path,
+    output: &Path,
+    evaluator: &Evaluator,
+    transforms: &[Transform],
+    format: WriteFormat,
+    depth: BitDepth,
+) -> Result<(), String> {
+    if let (Ok(a), Ok(b)) = (input.canonicalize(), output.canonicalize()) {
+        if a == b {
+            return Err("the output would overwrite the input".into());
+        }
+    }
+
+    let image = File::open(input)
+        .map_err(image_fmt::ReadError::from)
+        .and_then(|file| image_fmt::load(BufReader::new(file)))
+        .map_err(|e| format!("unable to load image: {}", e))?;
+
+    let (mut pixels, channel_count) = match image.data.to_f32() {
+        ImageBuf::RgbF32(pixels) => (pixels, 3),
+        ImageBuf::RgbaF32(pixels) => (pixels, 4),
+        _ => unreachable!(),
+    };
+    pixels
+        .par_chunks_mut(channel_count)
+        .try_for_each(|pixel| -> Result<(), String> {
+            let rgb = [pixel[0] as f64, pixel[1] as f64, pixel[2] as f64];
+            let out = evaluator.eval(transforms, rgb)?;
+            for chan in 0..3 {
+                pixel[chan] = out[chan] as f32;
+            }
+            Ok(())
+        })?;
+
+    let data = if channel_count == 4 {
+        ImageBuf::RgbaF32(pixels)
+    } else {
+        ImageBuf::RgbF32(pixels)
+    };
+    let image = Image {
+        dimensions: image.dimensions,
+        data: match depth {
+            BitDepth::Eight => data.to_8_bit(),
+            BitDepth::Sixteen => data.to_16_bit(),
+            BitDepth::Float => data,
+        },
+    };
+
+    let write = || -> Result<(), image_fmt::WriteError> {
+        let mut writer = BufWriter::new(File::create(output)?);
+        image_fmt::write(&mut writer, &image, format)?;
+        writer.flush()?;
+        Ok(())
+    };
+    write().map_err(|e| format!("unable to write image: {}", e))
+}
@@ -0,0 +1,226 @@
+//! Alignment of hand-held bracketed exposures, using median threshold
+//! bitmaps (Ward, 2003).
+//!
+//! Median threshold bitmaps are mostly independent of exposure, so
+//! images of different exposures can be compared directly.  Only
+//! translation is corrected, not rotation.
+
+use image_fmt::{Image, ImageBuf};
+
+/// Values this close to the median are ignored, since they're too
+/// noisy to be reliable.
+const NOISE_TOLERANCE: f32 = 4.0 / 255.0;
+
+/// The smallest resolution, in pixels, that the image pyramid is
+/// reduced to.
+const MIN_LEVEL_SIZE: usize = 32;
+
+/// Estimates the `(x, y)` pixel offset of each image relative to the
+/// reference image, searching at most `max_shift` pixels in each
+/// direction.
+///
+/// The offsets are how far each image needs to be shifted to line up
+/// with the reference image.
+pub fn estimate_offsets(
+    images: &[&Image],
+    reference: usize,
+    max_shift: usize,
+) -> Vec<(isize, isize)> {
+    // Enough levels to reach `max_shift`, as long as the smallest
+    // level is still big enough to be meaningful.
+    let levels = {
+        let min_dimension = images
+            .iter()
+            .map(|img| img.width().min(img.height()))
+            .min()
+            .unwrap_or(0);
+        let for_shift = usize::BITS - max_shift.max(1).leading_zeros();
+        let for_size = usize::BITS - (min_dimension / MIN_LEVEL_SIZE).max(1).leading_zeros();
+        for_shift.min(for_size) as usize
+    };
+    let pyramids: Vec<Vec<Bitmaps>> = images
+        .iter()
+        .map(|img| bitmap_pyramid(img, levels))
+        .collect();
+
+    (0..images.len())
+        .map(|i| {
+            if i == reference {
+                return (0, 0);
+            }
+
+            // Coarse to fine, refining the offset by up to one pixel
+            // at each level.
+            let mut offset = (0isize, 0isize);
+            for level in (0..levels).rev() {
+                let a = &pyramids[reference][level];
+                let b = &pyramids[i][level];
+                let center = (offset.0 * 2, offset.1 * 2);
+                let mut best = (center, std::f32::INFINITY);
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let candidate = (center.0 + dx, center.1 + dy);
+                        let error = a.difference(b, candidate);
+                        if error < best.1 {
+                            best = (candidate, error);
+                        }
+                    }
+                }
+                offset = best.0;
+            }
+
+            let max_shift = max_shift as isize;
+            (
+                offset.0.max(-max_shift).min(max_shift),
+                offset.1.max(-max_shift).min(max_shift),
+            )
+        })
+        .collect()
+}
+
+/// Returns a copy of the image shifted by `offset` pixels.  Pixels
+/// shifted in from outside the image are copied from the nearest edge.
+pub fn shift_image(img: &Image, offset: (isize, isize)) -> Image {
+    fn shift<T: Copy>(
+        data: &[T],
+        width: usize,
+        height: usize,
+        channels: usize,
+        offset: (isize, isize),
+    ) -> Vec<T> {
+        let mut out = Vec::with_capacity(data.len());
+        for y in 0..height {
+            let src_y = (y as isize - offset.1).max(0).min(height as isize - 1) as usize;
+            for x in 0..width {
+                let src_x = (x as isize - offset.0).max(0).min(width as isize - 1) as usize;
+                let i = (src_y * width + src_x) * channels;
+                out.extend_from_slice(&data[i..(i + channels)]);
+            }
+        }
+        out
+    }
+
+    let (w, h) = img.dimensions;
+    Image {
+        dimensions: img.dimensions,
+        data: match img.data {
+            ImageBuf::Rgb8(ref d) => ImageBuf::Rgb8(shift(d, w, h, 3, offset)),
+            ImageBuf::Rgb16(ref d) => ImageBuf::Rgb16(shift(d, w, h, 3, offset)),
+            ImageBuf::Rgba8(ref d) => ImageBuf::Rgba8(shift(d, w, h, 4, offset)),
+            ImageBuf::Rgba16(ref d) => ImageBuf::Rgba16(shift(d, w, h, 4, offset)),
+            ImageBuf::RgbF32(ref d) => ImageBuf::RgbF32(shift(d, w, h, 3, offset)),
+            ImageBuf::RgbaF32(ref d) => ImageBuf::RgbaF32(shift(d, w, h, 4, offset)),
+        },
+    }
+}
+
+/// The median threshold bitmap of an image, and a mask of the pixels
+/// that aren't too close to the median.
+struct Bitmaps {
+    width: usize,
+    height: usize,
+    threshold: Vec<bool>,
+    mask: Vec<bool>,
+}
+
+impl Bitmaps {
+    fn new(luma: &[f32], width: usize, height: usize) -> Bitmaps {
+        let median = {
+            let mut sorted = luma.to_vec();
+            sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+            sorted[sorted.len() / 2]
+        };
+        Bitmaps {
+            width: width,
+            height: height,
+            threshold: luma.iter().map(|n| *n > median).collect(),
+            mask: luma
+                .iter()
+                .map(|n| (n - median).abs() > NOISE_TOLERANCE)
+                .collect(),
+        }
+    }
+
+    /// The fraction of differing pixels between `self` and `other`
+    /// shifted by `offset`, ignoring pixels near either's median.
+    ///
+    /// It's a fraction rather than a count so that larger shifts, with
+    /// less overlap, aren't favored.
+    fn difference(&self, other: &Bitmaps, offset: (isize, isize)) -> f32 {
+        let mut count = 0usize;
+        let mut total = 0usize;
+        for y in 0..self.height {
+            let other_y = y as isize - offset.1;
+            if other_y < 0 || other_y >= other.height as isize {
+                continue;
+            }
+            for x in 0..self.width {
+                let other_x = x as isize - offset.0;
+                if other_x < 0 || other_x >= other.width as isize {
+                    continue;
+                }
+                let i = y * self.width + x;
+                let j = other_y as usize * other.width + other_x as usize;
+                if self.mask[i] && other.mask[j] {
+                    total += 1;
+                    if self.threshold[i] != other.threshold[j] {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        if total == 0 {
+            1.0
+        } else {
+            count as f32 / total as f32
+        }
+    }
+}
+
+/// Bitmaps of the image at successively halved resolutions, starting
+/// with full resolution.
+fn bitmap_pyramid(img: &Image, levels: usize) -> Vec<Bitmaps> {
+    let (mut width, mut height) = img.dimensions;
+    let mut luma = image_luma(img);
+
+    let mut pyramid = Vec::new();
+    for level in 0..levels {
+        if level > 0 {
+            let (w, h) = ((width / 2).max(1), (height / 2).max(1));
+            luma = (0..(w * h))
+                .map(|i| {
+                    let (x, y) = ((i % w) * 2, (i / w) * 2);
+                    let x2 = (x + 1).min(width - 1);
+                    let y2 = (y + 1).min(height - 1);
+                    (luma[y * width + x]
+                        + luma[y * width + x2]
+                        + luma[y2 * width + x]
+                        + luma[y2 * width + x2])
+                        * 0.25
+                })
+                .collect();
+            width = w;
+            height = h;
+        }
+        pyramid.push(Bitmaps::new(&luma, width, height));
+    }
+    pyramid
+}
+
+/// The luma of each pixel, normalized to [0.0, 1.0] for integer images.
+fn image_luma(img: &Image) -> Vec<f32> {
+    fn luma<T: Copy, F: Fn(T) -> f32>(data: &[T], channels: usize, to_f32: F) -> Vec<f32> {
+        data.chunks(channels)
+            .map(|p| to_f32(p[0]) * 0.2126 + to_f32(p[1]) * 0.7152 + to_f32(p[2]) * 0.0722)
+            .collect()
+    }
+
+    match img.data {
+        ImageBuf::Rgb8(ref d) => luma(d, 3, |n| n as f32 / 255.0),
+        ImageBuf::Rgb16(ref d) => luma(d, 3, |n| n as f32 / 65535.0),
+        ImageBuf::Rgba8(ref d) => luma(d, 4, |n| n as f32 / 255.0),
+        ImageBuf::Rgba16(ref d) => luma(d, 4, |n| n as f32 / 65535.0),
+        ImageBuf::RgbF32(ref d) => luma(d, 3, |n| n),
+        ImageBuf::RgbaF32(ref d) => luma(d, 4, |n| n),
+    }
+}
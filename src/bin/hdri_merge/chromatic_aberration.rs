@@ -0,0 +1,214 @@
+//! Estimation and correction of lateral chromatic aberration.
+//!
+//! Lateral chromatic aberration makes the lens magnify each color
+//! channel slightly differently, which shows up as colored fringes
+//! that grow towards the edges of the image.  It's modeled here as a
+//! radial scale of the red and blue channels about the image center,
+//! relative to green.
+//!
+//! Both estimation and correction work on linear (merged) pixels.
+//! Merging is a weighted average of the linearized images, so scaling
+//! the merged result is the same as scaling each image before merging.
+
+use rayon::prelude::*;
+
+/// The largest scale difference searched for, in either direction.
+/// Even wide lenses rarely exceed a few pixels of fringing at the
+/// corners, which is well within this.
+const MAX_SCALE_OFFSET: f32 = 0.004;
+
+/// The scale resolution of the search.
+const SCALE_STEP: f32 = 0.0001;
+
+/// The number of edge samples that estimation is limited to, spread
+/// evenly across the image.
+const MAX_SAMPLES: usize = 1 << 16;
+
+/// Samples closer to the center than this fraction of the center to
+/// corner distance are ignored, since aberration is too small there to
+/// tell anything.
+const MIN_RADIUS: f32 = 0.25;
+
+/// Lowest value considered when taking logarithms, to keep noise in
+/// near-black areas from dominating.
+const LOG_FLOOR: f32 = 1.0e-5;
+
+/// Estimates the radial scales of the red and blue channels relative to
+/// green, as `[red, blue]`.
+///
+/// A channel's scale is how much farther from the center its image of
+/// a point is than green's.  Returns `[1.0, 1.0]` if the image doesn't
+/// have enough edges to estimate from.
+pub fn estimate_scales(pixels: &[[f32; 3]], width: usize, height: usize) -> [f32; 2] {
+    let image = Channels {
+        pixels: pixels,
+        width: width,
+        height: height,
+    };
+    let center = image.center();
+    let max_radius = (center.0 * center.0 + center.1 * center.1).sqrt();
+    if max_radius < 8.0 {
+        return [1.0, 1.0];
+    }
+
+    // Gather points on strong edges in green, along with their radial
+    // direction and green's log gradient in that direction.
+    //
+    // The log gradient is used because it's independent of the local
+    // color: where red is a constant multiple of green, their log
+    // gradients are the same, and aberration is the only thing that
+    // makes them differ.
+    let stride = (((width * height) as f32 / (MAX_SAMPLES * 4) as f32).sqrt() as usize).max(1);
+    let mut samples: Vec<Sample> = (0..(height / stride))
+        .into_par_iter()
+        .flat_map_iter(|row| {
+            let image = &image;
+            let y = (row * stride) as f32;
+            (0..(width / stride)).filter_map(move |col| {
+                let x = (col * stride) as f32;
+                let offset = (x - center.0, y - center.1);
+                let radius = (offset.0 * offset.0 + offset.1 * offset.1).sqrt();
+                if radius < max_radius * MIN_RADIUS {
+                    return None;
+                }
+                let direction = (offset.0 / radius, offset.1 / radius);
+                let green = image.radial_gradient(1, (x, y), direction)?;
+                Some(Sample {
+                    position: (x, y),
+                    direction: direction,
+                    green: green,
+                })
+            })
+        })
+        .collect();
+    if samples.len() < 64 {
+        return [1.0, 1.0];
+    }
+
+    // Keep only the strongest edges, which are the ones where a
+    // misalignment between channels actually shows.
+    samples.sort_unstable_by(|a, b| b.green.abs().partial_cmp(&a.green.abs()).unwrap());
+    samples.truncate((samples.len() / 4).max(64).min(MAX_SAMPLES));
+
+    let steps = (MAX_SCALE_OFFSET / SCALE_STEP).round() as isize;
+    let mut scales = [1.0; 2];
+    for (i, chan) in [0, 2].iter().enumerate() {
+        let mut best = (1.0f32, std::f32::INFINITY);
+        for step in -steps..=steps {
+            let scale = 1.0 + step as f32 * SCALE_STEP;
+            let error = samples
+                .par_iter()
+                .map(|sample| {
+                    let position = (
+                        center.0 + (sample.position.0 - center.0) * scale,
+                        center.1 + (sample.position.1 - center.1) * scale,
+                    );
+                    match image.radial_gradient(*chan, position, sample.direction) {
+                        Some(gradient) => {
+                            let diff = gradient - sample.green;
+                            diff * diff
+                        }
+                        // Treat samples outside the image as
+                        // mismatched, so that large scales aren't
+                        // favored for having fewer samples.
+                        None => sample.green * sample.green,
+                    }
+                })
+                .sum::<f32>();
+            // Prefer the smallest correction among equally good ones.
+            if error < best.1 || (error == best.1 && (scale - 1.0).abs() < (best.0 - 1.0).abs()) {
+                best = (scale, error);
+            }
+        }
+        scales[i] = best.0;
+    }
+
+    scales
+}
+
+/// Scales the red and blue channels radially about the image center by
+/// the given `[red, blue]` scales, bringing them in line with green.
+pub fn correct(pixels: &mut [[f32; 3]], width: usize, height: usize, scales: [f32; 2]) {
+    if scales == [1.0, 1.0] {
+        return;
+    }
+
+    let source: Vec<[f32; 3]> = pixels.to_vec();
+    let image = Channels {
+        pixels: &source,
+        width: width,
+        height: height,
+    };
+    let center = image.center();
+    pixels
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                for (i, chan) in [0, 2].iter().enumerate() {
+                    let position = (
+                        center.0 + (x as f32 - center.0) * scales[i],
+                        center.1 + (y as f32 - center.1) * scales[i],
+                    );
+                    pixel[*chan] = image.sample(*chan, position);
+                }
+            }
+        });
+}
+
+/// A point on an edge, used for estimation.
+struct Sample {
+    position: (f32, f32),
+    direction: (f32, f32), // Unit vector pointing away from the center.
+    green: f32,            // Green's log gradient along `direction`.
+}
+
+/// Bilinear access to the channels of an image.
+struct Channels<'a> {
+    pixels: &'a [[f32; 3]],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Channels<'a> {
+    fn center(&self) -> (f32, f32) {
+        (
+            (self.width as f32 - 1.0) * 0.5,
+            (self.height as f32 - 1.0) * 0.5,
+        )
+    }
+
+    /// Bilinearly interpolated value of a channel.  Positions outside
+    /// the image are clamped to its edges.
+    fn sample(&self, chan: usize, position: (f32, f32)) -> f32 {
+        let x = position.0.max(0.0).min((self.width - 1) as f32);
+        let y = position.1.max(0.0).min((self.height - 1) as f32);
+        let (x1, y1) = (x.floor() as usize, y.floor() as usize);
+        let (x2, y2) = ((x1 + 1).min(self.width - 1), (y1 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x1 as f32, y - y1 as f32);
+
+        let p = |x: usize, y: usize| self.pixels[y * self.width + x][chan];
+        let top = p(x1, y1) + (p(x2, y1) - p(x1, y1)) * fx;
+        let bottom = p(x1, y2) + (p(x2, y2) - p(x1, y2)) * fx;
+        top + (bottom - top) * fy
+    }
+
+    /// The gradient of a channel's logarithm along `direction`, or
+    /// `None` if it's too close to the image's edges.
+    fn radial_gradient(
+        &self,
+        chan: usize,
+        position: (f32, f32),
+        direction: (f32, f32),
+    ) -> Option<f32> {
+        let a = (position.0 - direction.0, position.1 - direction.1);
+        let b = (position.0 + direction.0, position.1 + direction.1);
+        let max = ((self.width - 1) as f32, (self.height - 1) as f32);
+        if a.0.min(a.1).min(b.0).min(b.1) < 0.0 || a.0.max(b.0) > max.0 || a.1.max(b.1) > max.1 {
+            return None;
+        }
+
+        let log = |p| self.sample(chan, p).max(LOG_FLOOR).log2();
+        Some((log(b) - log(a)) * 0.5)
+    }
+}
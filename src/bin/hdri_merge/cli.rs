@@ -0,0 +1,462 @@
+//! Headless HDRI merging, for scripted pipelines.
+
+use std::{
+    borrow::Cow,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use image_fmt::ExrCompression;
+use lib::{lens_projection::LensProjection, SourceImage};
+use sensor_analysis::{frame_rejection::Rejection, Histogram};
+
+use crate::{
+    align, camera_name, chromatic_aberration, chromatic_aberration_message, known_camera_curves,
+    load_images_ahead, merge_incrementally, projection_tag,
+    save_format::{self, SaveFormat, SaveOptions},
+    HDRIMerger, VERSION,
+};
+
+/// Adds the command line arguments for headless mode.
+pub fn add_args<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+    use clap::Arg;
+
+    app.arg(
+        Arg::with_name("cli")
+            .long("cli")
+            .requires("output")
+            .help("Runs without the GUI, merging the given images into an HDRI"),
+    )
+    .arg(
+        Arg::with_name("images")
+            .value_name("IMAGES")
+            .multiple(true)
+            .help("Bracketed exposure images, or directories of them"),
+    )
+    .arg(
+        Arg::with_name("output")
+            .long("output")
+            .short("o")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("File to write the HDRI to.  The format is chosen by the extension: .hdr, .exr, .tif, or .pfm"),
+    )
+    .arg(
+        Arg::with_name("exposure")
+            .long("exposure")
+            .value_name("STOPS")
+            .takes_value(true)
+            .allow_hyphen_values(true)
+            .default_value("0")
+            .help("Exposure adjustment of the written HDRI, in stops"),
+    )
+    .arg(
+        Arg::with_name("exr_half")
+            .long("exr-half")
+            .help("Writes .exr files with 16-bit (half) floats instead of 32-bit floats"),
+    )
+    .arg(
+        Arg::with_name("exr_compression")
+            .long("exr-compression")
+            .value_name("METHOD")
+            .takes_value(true)
+            .possible_values(&["none", "rle", "zip"])
+            .default_value("zip")
+            .help("The compression of .exr files"),
+    )
+    .arg(
+        Arg::with_name("align")
+            .long("align")
+            .help("Aligns hand-held brackets before merging.  Only corrects translation"),
+    )
+    .arg(
+        Arg::with_name("max_shift")
+            .long("max-shift")
+            .value_name("PIXELS")
+            .takes_value(true)
+            .requires("align")
+            .default_value("64")
+            .help("The largest misalignment to correct, in pixels"),
+    )
+    .arg(
+        Arg::with_name("reject_frames")
+            .long("reject-frames")
+            .help("Excludes images that duplicate another image, or whose brightness doesn't fit their exposure (e.g. the lighting changed).  Without this they're only warned about"),
+    )
+    .arg(
+        Arg::with_name("camera_curve")
+            .long("camera-curve")
+            .help("For images shot in the log profile of a camera in the camera database: uses that camera's log curve and black level instead of estimating the transfer function"),
+    )
+    .arg(
+        Arg::with_name("correct_ca")
+            .long("correct-ca")
+            .help("Estimates and corrects lateral chromatic aberration (colored fringes towards the edges of the image, common with wide lenses)"),
+    )
+    .arg(
+        Arg::with_name("projection")
+            .long("projection")
+            .value_name("PROJECTION")
+            .takes_value(true)
+            .possible_values(&[
+                "rectilinear",
+                "fisheye_equidistant",
+                "fisheye_equisolid",
+                "fisheye_stereographic",
+                "fisheye_orthographic",
+            ])
+            .help("The projection of the lens the images were taken with.  Fisheye projections are recorded in written .hdr and .exr files"),
+    )
+    .arg(
+        Arg::with_name("export_lut")
+            .long("export-lut")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("Also writes the estimated to-linear transfer function as a .cube or .spi1d LUT"),
+    )
+}
+
+/// Runs headless mode, returning the process exit code.
+pub fn run(args: &clap::ArgMatches) -> i32 {
+    match run_inner(args) {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            1
+        }
+    }
+}
+
+fn run_inner(args: &clap::ArgMatches) -> Result<(), String> {
+    // Options.
+    let output_path = PathBuf::from(args.value_of("output").unwrap());
+    let save_options = SaveOptions {
+        format: SaveFormat::from_path(&output_path)
+            .ok_or("the output file must have an .hdr, .exr, .tif, or .pfm extension")?,
+        exr_half_float: args.is_present("exr_half"),
+        exr_compression: match args.value_of("exr_compression").unwrap() {
+            "none" => ExrCompression::None,
+            "rle" => ExrCompression::Rle,
+            _ => ExrCompression::Zip,
+        },
+        ..SaveOptions::new()
+    };
+    let exposure: f32 = args
+        .value_of("exposure")
+        .unwrap()
+        .parse()
+        .map_err(|_| "--exposure must be a number")?;
+    let max_shift: Option<usize> = if args.is_present("align") {
+        Some(
+            args.value_of("max_shift")
+                .unwrap()
+                .parse()
+                .map_err(|_| "--max-shift must be a whole number")?,
+        )
+    } else {
+        None
+    };
+    let projection = args
+        .value_of("projection")
+        .and_then(LensProjection::from_str)
+        .unwrap_or(LensProjection::Rectilinear);
+    let lut_path = args.value_of("export_lut").map(PathBuf::from);
+    if let Some(path) = &lut_path {
+        let ext = extension(path);
+        if ext != "cube" && ext != "spi1d" {
+            return Err("the exported LUT must have a .cube or .spi1d extension".into());
+        }
+    }
+
+    println!("ETF HDRI Merge v{}", VERSION);
+
+    // Load the images.
+    let mut paths = Vec::new();
+    for input in args.values_of("images").into_iter().flatten() {
+        let path = Path::new(input);
+        if path.is_dir() {
+            paths.extend(images_in_dir(path)?);
+        } else {
+            paths.push(path.to_path_buf());
+        }
+    }
+    if paths.len() < 2 {
+        return Err("at least two bracketed images are needed".into());
+    }
+
+    // When the transfer function is known up front, merge the images as
+    // they load.  Alignment and frame rejection need all of the images
+    // first, so they rule that out.
+    let mut merge = None;
+    let mut merging =
+        args.is_present("camera_curve") && max_shift.is_none() && !args.is_present("reject_frames");
+
+    let mut images: Vec<SourceImage> = Vec::new();
+    let mut missing_exposure = Vec::new();
+    for (path, img) in load_images_ahead(paths) {
+        let img =
+            img.map_err(|e| format!("unable to load image \"{}\": {}", path.to_string_lossy(), e))?;
+        if img.image.data.is_float() {
+            return Err(format!(
+                "floating point images can't be merged: \"{}\"",
+                path.to_string_lossy()
+            ));
+        }
+        if let Some(first) = images.first() {
+            if img.image.dimensions != first.image.dimensions {
+                return Err(format!(
+                    "image has a different resolution than the others: \"{}\"",
+                    path.to_string_lossy()
+                ));
+            }
+        }
+        if img.info.exposure.is_none() {
+            missing_exposure.push(path.to_string_lossy().to_string());
+        }
+        println!("Loaded: {}", path.to_string_lossy());
+        if merging {
+            merging = merge_incrementally(&mut merge, &img);
+        }
+        images.push(img);
+    }
+    if !missing_exposure.is_empty() {
+        return Err(format!(
+            "images lack the Exif data needed to compute their exposure: \"{}\"",
+            missing_exposure.join("\", \"")
+        ));
+    }
+
+    // Compensate for ISO and aperture changes.
+    let (mismatches, exposures) = {
+        let infos: Vec<&lib::ImageInfo> = images.iter().map(|img| &img.info).collect();
+        (
+            lib::bracket_analysis::find_settings_mismatches(&infos),
+            lib::bracket_analysis::bracket_exposures(&infos, true),
+        )
+    };
+    for ((img, mismatch), exposure) in images.iter_mut().zip(mismatches).zip(exposures) {
+        if mismatch.any() {
+            eprintln!(
+                "Warning: image's ISO or aperture differs from the others, which may throw off merging: \"{}\"",
+                img.info.full_filepath
+            );
+        }
+        if exposure.is_some() {
+            img.info.exposure = exposure;
+        }
+    }
+    images.sort_unstable_by(|a, b| a.info.exposure.partial_cmp(&b.info.exposure).unwrap());
+
+    let mut image_histograms: Vec<[Histogram; 3]> = images
+        .iter()
+        .map(|img| lib::job_helpers::compute_image_histograms(img, None, None))
+        .collect();
+
+    // Check for duplicate and outlier images.
+    let rejections = {
+        let set: Vec<_> = image_histograms
+            .iter()
+            .zip(images.iter())
+            .map(|(histograms, img)| (histograms, &img.info))
+            .collect();
+        lib::bracket_analysis::find_rejected_images(&set)
+    };
+    let reject = args.is_present("reject_frames");
+    for (i, rejection) in rejections.iter().enumerate() {
+        let reason = match rejection {
+            Some(Rejection::Duplicate(j)) => {
+                format!("is a duplicate of \"{}\"", images[*j].info.full_filepath)
+            }
+            Some(Rejection::Outlier) => "doesn't fit its exposure (did the scene change?)".into(),
+            None => continue,
+        };
+        eprintln!(
+            "Warning: image {}{}: \"{}\"",
+            reason,
+            if reject { ", so it was excluded" } else { "" },
+            images[i].info.full_filepath
+        );
+    }
+    if reject {
+        for i in (0..images.len()).rev() {
+            if rejections[i].is_some() {
+                images.remove(i);
+                image_histograms.remove(i);
+            }
+        }
+    }
+    if images.len() < 2 {
+        return Err("at least two bracketed images are needed".into());
+    }
+
+    // Estimate the linearizing curves.
+    let mut histograms = [Vec::new(), Vec::new(), Vec::new()];
+    for (img_hists, img) in image_histograms.into_iter().zip(images.iter()) {
+        for (chan, hist) in std::iter::IntoIterator::into_iter(img_hists).enumerate() {
+            histograms[chan].push((hist, img.info.exposure.unwrap()));
+        }
+    }
+    let camera_curves = if args.is_present("camera_curve") {
+        known_camera_curves(&images)
+    } else {
+        None
+    };
+    let (inv_mapping, floor_ceil_pairs) = match camera_curves {
+        Some((camera, inv_mapping, floor_ceil_pairs)) => {
+            println!("Using the known curve of the {}", camera.ui_text());
+            (inv_mapping, floor_ceil_pairs)
+        }
+        None => {
+            if args.is_present("camera_curve") {
+                println!("The images aren't from a known camera, estimating the curve instead");
+            }
+            let (inv_mapping, floor_ceil_pairs, err) =
+                sensor_analysis::estimate_transfer_function(&[
+                    &histograms[0],
+                    &histograms[1],
+                    &histograms[2],
+                ]);
+            println!("Transfer function estimation error: {}", err);
+            (inv_mapping, floor_ceil_pairs)
+        }
+    };
+
+    // Align.
+    let offsets = match max_shift {
+        Some(max_shift) => {
+            let image_refs: Vec<_> = images.iter().map(|img| &img.image).collect();
+            let offsets = align::estimate_offsets(&image_refs, images.len() / 2, max_shift);
+            for (img, offset) in images.iter().zip(offsets.iter()) {
+                println!(
+                    "Alignment offset of \"{}\": {}, {}",
+                    img.info.filename, offset.0, offset.1
+                );
+            }
+            offsets
+        }
+        None => vec![(0, 0); images.len()],
+    };
+
+    // Merge, unless the images were already merged as they loaded and
+    // their exposures haven't changed since.
+    let aligned_image = |img_i: usize| {
+        if offsets[img_i] == (0, 0) {
+            Cow::Borrowed(&images[img_i].image)
+        } else {
+            Cow::Owned(align::shift_image(&images[img_i].image, offsets[img_i]))
+        }
+    };
+    let mut hdri = match merge.filter(|merge| merge.matches(&images)) {
+        Some(merge) => merge.merger,
+        None => {
+            let mut hdri = HDRIMerger::new(images[0].image.width(), images[0].image.height());
+            for (img_i, img) in images.iter().enumerate() {
+                eprint!("\rMerging image {}/{}", img_i + 1, images.len());
+                hdri.add_image(
+                    &aligned_image(img_i),
+                    img.info.exposure.unwrap(),
+                    &floor_ceil_pairs,
+                    &inv_mapping,
+                );
+            }
+            eprintln!();
+            hdri
+        }
+    };
+    let last = images.len() - 1;
+    hdri.add_bracket_ends(
+        (&aligned_image(0), images[0].info.exposure.unwrap()),
+        (&aligned_image(last), images[last].info.exposure.unwrap()),
+        &floor_ceil_pairs,
+        &inv_mapping,
+    );
+    hdri.finish();
+    if args.is_present("correct_ca") {
+        let scales = chromatic_aberration::estimate_scales(&hdri.pixels, hdri.width, hdri.height);
+        chromatic_aberration::correct(&mut hdri.pixels, hdri.width, hdri.height, scales);
+        println!("{}", chromatic_aberration_message(scales));
+    }
+    for (is_warning, message) in hdri.coverage_messages() {
+        if is_warning {
+            eprintln!("Warning: {}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+    print!(
+        "{}",
+        crate::stats::HdriStats::compute(&hdri.pixels).summary()
+    );
+
+    // Write.
+    let scale = 2.0f32.powf(exposure);
+    let hdr_options = hdr::HdrOptions {
+        exposure: scale,
+        software: Some(format!("ETF HDRI Merge v{}", VERSION)),
+        camera: camera_name(&images),
+        projection: projection_tag(projection),
+        ..hdr::HdrOptions::default()
+    };
+    save_format::write_hdri(
+        &output_path,
+        &hdri.pixels,
+        hdri.width,
+        hdri.height,
+        &save_options,
+        &hdr_options,
+    )
+    .map_err(|e| {
+        format!(
+            "unable to write HDRI \"{}\": {}",
+            output_path.to_string_lossy(),
+            e
+        )
+    })?;
+    println!("Wrote: {}", output_path.to_string_lossy());
+
+    // Export the transfer function.
+    if let Some(path) = lut_path {
+        let tables = [
+            &inv_mapping[0][..],
+            &inv_mapping[1][..],
+            &inv_mapping[2][..],
+        ];
+        let write = || -> std::io::Result<()> {
+            let mut file = BufWriter::new(std::fs::File::create(&path)?);
+            if extension(&path) == "cube" {
+                colorbox::formats::cube_iridas::write_1d(&mut file, [(0.0, 1.0); 3], tables)?;
+            } else {
+                colorbox::formats::spi1d::write(&mut file, 0.0, 1.0, &tables)?;
+            }
+            file.flush()
+        };
+        write()
+            .map_err(|e| format!("unable to write LUT \"{}\": {}", path.to_string_lossy(), e))?;
+        println!("Wrote: {}", path.to_string_lossy());
+    }
+
+    Ok(())
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// All images in a directory, sorted by file name.
+fn images_in_dir(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| {
+            format!(
+                "unable to read directory \"{}\": {}",
+                dir.to_string_lossy(),
+                e
+            )
+        })?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| lib::bracket_analysis::IMAGE_EXTENSIONS.contains(&&*extension(path)))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
@@ -1,8 +1,10 @@
 use crate::egui::{self, Context, RichText, Ui};
+use egui_custom::{ImageBadge, ImageListEdit, ImageListItem, ImageListWidget};
 
-pub fn image_list(ctx: &Context, ui: &mut Ui, app: &mut crate::AppMain, job_count: usize) {
-    let mut remove_i = None; // Temp to store index of an image to remove.
+use lib::{bracket_analysis::SettingsMismatch, ImageInfo};
+use sensor_analysis::frame_rejection::Rejection;
 
+pub fn image_list(ctx: &Context, ui: &mut Ui, app: &mut crate::AppMain, job_count: usize) {
     // Selected image info.
     // (Extra scope to contain ui_data's mutex guard.)
     {
@@ -11,8 +13,9 @@ pub fn image_list(ctx: &Context, ui: &mut Ui, app: &mut crate::AppMain, job_coun
         let spacing = 4.0;
 
         ui.add_space(spacing + 4.0);
-        if ui_data.selected_image_index < ui_data.thumbnails.len() {
-            let info = &ui_data.thumbnails[ui_data.selected_image_index].3;
+        let selected_image_index = ui_data.selection.current.1;
+        if selected_image_index < ui_data.thumbnails.len() {
+            let info = &ui_data.thumbnails[selected_image_index].3;
             ui.add(Label::new(RichText::new("Filename:").strong()));
             ui.indent("", |ui| ui.label(format!("{}", info.filename)));
 
@@ -83,37 +86,195 @@ pub fn image_list(ctx: &Context, ui: &mut Ui, app: &mut crate::AppMain, job_coun
 
     ui.add(egui::widgets::Separator::default().spacing(16.0));
 
+    // Manual exposures.
+    let missing_exposures = app
+        .ui_data
+        .lock()
+        .thumbnails
+        .iter()
+        .any(|(_, _, _, info)| info.exposure.is_none());
+    if ui
+        .add_enabled(
+            job_count == 0 && missing_exposures,
+            egui::widgets::Button::new("Enter Exposures..."),
+        )
+        .on_hover_text("Enter the exposures of the images that lack exposure metadata.")
+        .on_disabled_hover_text("All images have exposure metadata.")
+        .clicked()
+    {
+        app.open_exposure_editor();
+    }
+    exposure_editor(ctx, app);
+
+    // ISO/aperture compensation, if the images mix them.
+    let mismatches = {
+        let ui_data = app.ui_data.lock();
+        let infos: Vec<&ImageInfo> = ui_data
+            .thumbnails
+            .iter()
+            .map(|(_, _, _, info)| info)
+            .collect();
+        lib::bracket_analysis::find_settings_mismatches(&infos)
+    };
+    if mismatches.iter().any(|m| m.any()) {
+        let mut compensate = app.ui_data.lock().compensate_settings;
+        if ui
+            .add_enabled(
+                job_count == 0,
+                egui::widgets::Checkbox::new(&mut compensate, "Compensate for ISO/aperture changes"),
+            )
+            .on_hover_text("The images mix ISO or aperture changes with shutter speed bracketing.  When checked, exposures account for the ISO and aperture of each image, with missing values taken to be the most common ones.  When unchecked, exposures are computed from shutter speed alone.")
+            .changed()
+        {
+            app.set_compensate_settings(compensate, ctx);
+        }
+    }
+
+    // Duplicate and outlier images.
+    let rejected: Vec<usize> = app
+        .ui_data
+        .lock()
+        .rejections
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| r.is_some())
+        .map(|(img_i, _)| img_i)
+        .collect();
+    if !rejected.is_empty()
+        && ui
+            .add_enabled(
+                job_count == 0,
+                egui::widgets::Button::new(format!(
+                    "🗙 Remove {} Duplicate/Outlier Images",
+                    rejected.len()
+                )),
+            )
+            .on_hover_text("Remove the images that duplicate another image, or whose brightness doesn't fit their exposure (e.g. because the lighting changed between shots).  They can throw off merging.")
+            .clicked()
+    {
+        for img_i in rejected.iter().rev() {
+            app.remove_image(*img_i, ctx);
+        }
+    }
+    ui.add_space(4.0);
+
     // Image thumbnails.
-    egui::containers::ScrollArea::vertical()
-        .auto_shrink([false, false])
-        .show(ui, |ui| {
-            let ui_data = &mut *app.ui_data.lock_mut();
-            let thumbnails = &ui_data.thumbnails;
-            let selected_image_index = &mut ui_data.selected_image_index;
-
-            for (img_i, (ref tex_handle, _, _, _)) in thumbnails.iter().enumerate() {
-                ui.horizontal(|ui| {
-                    if ui
-                        .add(
-                            egui::widgets::ImageButton::new(tex_handle)
-                                .selected(img_i == *selected_image_index),
-                        )
-                        .clicked()
-                    {
-                        *selected_image_index = img_i;
-                        app.compute_image_preview(img_i, ctx);
-                    }
-                    if ui
-                        .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
-                        .clicked()
-                    {
-                        remove_i = Some(img_i);
-                    }
-                });
-            }
-        });
+    let response = {
+        let ui_data = &mut *app.ui_data.lock_mut();
+        let items: Vec<Vec<ImageListItem>> = vec![ui_data
+            .thumbnails
+            .iter()
+            .enumerate()
+            .map(|(img_i, (tex_handle, _, _, info))| ImageListItem {
+                thumbnail: tex_handle,
+                badges: image_badges(
+                    info,
+                    &ui_data.thumbnails[0].3,
+                    mismatches.get(img_i).copied().unwrap_or_default(),
+                    ui_data
+                        .rejections
+                        .get(img_i)
+                        .copied()
+                        .flatten()
+                        .map(|r| match r {
+                            Rejection::Duplicate(j) => (
+                                r,
+                                ui_data
+                                    .thumbnails
+                                    .get(j)
+                                    .map(|t| t.3.filename.as_str())
+                                    .unwrap_or(""),
+                            ),
+                            r => (r, ""),
+                        }),
+                ),
+            })
+            .collect()];
+        ImageListWidget::new(&items)
+            .enabled(job_count == 0)
+            .show(ui, &mut ui_data.selection)
+    };
 
-    if let Some(img_i) = remove_i {
-        app.remove_image(img_i, ctx);
+    if response.selection_changed {
+        let selected_image_index = app.ui_data.lock().selection.current.1;
+        app.compute_image_preview(selected_image_index, ctx);
+    }
+    if let Some(ImageListEdit::RemoveImages(images)) = response.edit {
+        for (_, img_i) in images {
+            app.remove_image(img_i, ctx);
+        }
+    }
+}
+
+/// Shows the exposure editor if it's open, and applies the entered
+/// exposures.
+fn exposure_editor(ctx: &Context, app: &mut crate::AppMain) {
+    let exposures: Vec<((String, String), f32)> = {
+        let mut ui_data = app.ui_data.lock_mut();
+        let (editor, keys) = match ui_data.exposure_editor {
+            Some(ref mut editor) => editor,
+            None => return,
+        };
+        let evs = match editor.show(ctx) {
+            Some(evs) => evs,
+            None => return,
+        };
+        let exposures = evs
+            .iter()
+            .map(|(i, ev)| (keys[*i].clone(), 2.0f32.powf(*ev)))
+            .collect();
+        ui_data.exposure_editor = None;
+        exposures
+    };
+
+    if !exposures.is_empty() {
+        app.set_exposures(&exposures, ctx);
+    }
+}
+
+/// Notes about problems with an image, for the image list.  `first` is
+/// the first image of the list.
+fn image_badges(
+    info: &ImageInfo,
+    first: &ImageInfo,
+    mismatch: SettingsMismatch,
+    rejection: Option<(Rejection, &str)>, // (rejection, filename of the image it duplicates)
+) -> Vec<ImageBadge> {
+    let mut badges = Vec::new();
+    if info.exposure.is_none() {
+        badges.push(ImageBadge::warning(
+            "Exif",
+            "The image lacks the Exif data needed to compute its exposure, so HDRI merging will not work correctly.  Its exposure can be entered with \"Enter Exposures...\".",
+        ));
+    }
+    if info.width != first.width || info.height != first.height {
+        badges.push(ImageBadge::warning(
+            "Resolution",
+            "The image's resolution differs from the other images.",
+        ));
+    }
+    if mismatch.iso {
+        badges.push(ImageBadge::warning(
+            "ISO",
+            "The image's ISO differs from most of the other images, or is missing.  Bracketing should only vary shutter speed, since ISO changes may not scale the image by exactly the nominal amount.",
+        ));
+    }
+    if mismatch.aperture {
+        badges.push(ImageBadge::warning(
+            "Aperture",
+            "The image's aperture differs from most of the other images, or is missing.  Bracketing should only vary shutter speed, since aperture changes also change depth of field and vignetting.",
+        ));
+    }
+    match rejection {
+        Some((Rejection::Duplicate(_), original)) => badges.push(ImageBadge::warning(
+            "Duplicate",
+            &format!("The image is nearly identical to \"{}\", so it adds nothing to the merge.  If their exposures differ, one of them is also wrong.", original),
+        )),
+        Some((Rejection::Outlier, _)) => badges.push(ImageBadge::warning(
+            "Outlier",
+            "The image's brightness doesn't fit its exposure relative to the other images.  The lighting may have changed between shots, or its exposure may be wrong.",
+        )),
+        None => {}
     }
+    badges
 }
@@ -1,5 +1,5 @@
 use crate::egui::{self, Align, Context, Ui};
-use crate::ShowImage;
+use crate::{Coverage, ShowImage};
 
 pub fn image_view(
     ctx: &Context,
@@ -26,9 +26,11 @@ pub fn image_view(
             {
                 *show_image = ShowImage::SelectedImage;
             }
+            // The HDRI preview can also be of a merge in progress, before
+            // the HDRI is built.
             if ui
                 .add_enabled(
-                    have_hdri,
+                    have_hdri || have_hdri_preview_tex,
                     egui::widgets::RadioButton::new(*show_image == ShowImage::HDRI, "Show HDRI"),
                 )
                 .clicked()
@@ -51,6 +53,30 @@ pub fn image_view(
             {
                 app.compute_hdri_preview(ctx);
             }
+
+            ui.add_space(spacing);
+            if ui
+                .checkbox(
+                    &mut app.ui_data.lock_mut().clipping_overlay,
+                    "Show Clipping",
+                )
+                .on_hover_text("Tints pixels that the source images don't cover well, to check whether more exposures are needed.")
+                .changed()
+            {
+                app.compute_hdri_preview(ctx);
+            }
+            if app.ui_data.lock().clipping_overlay {
+                for (coverage, text) in [
+                    (Coverage::ClippedHighlight, "Clipped in all"),
+                    (Coverage::ClippedShadow, "Below floor in all"),
+                    (Coverage::SingleImage, "From one image"),
+                ]
+                .iter()
+                {
+                    let [r, g, b] = crate::coverage_overlay_color(*coverage).unwrap();
+                    ui.colored_label(egui::Color32::from_rgb(r, g, b), format!("■ {}", text));
+                }
+            }
         }
 
         ui.with_layout(egui::Layout::right_to_left(Align::Max), |ui| {
@@ -76,10 +102,13 @@ pub fn image_view(
         .auto_shrink([false, false])
         .show(ui, |ui| {
             if show_image == ShowImage::HDRI && have_hdri_preview_tex {
-                if let Some((ref tex_handle, _, _)) = app.ui_data.lock().hdri_preview_tex {
+                // The texture may be downscaled, so it's sized by the
+                // HDRI's full resolution instead of its own.
+                if let Some((ref tex_handle, width, height)) = app.ui_data.lock().hdri_preview_tex {
                     ui.add(
-                        egui::widgets::Image::from_texture(tex_handle)
-                            .fit_to_original_size(image_zoom),
+                        egui::widgets::Image::from_texture(tex_handle).fit_to_exact_size(
+                            egui::vec2(width as f32, height as f32) * image_zoom,
+                        ),
                     );
                 }
             } else if show_image == ShowImage::SelectedImage && image_count > 0 {
@@ -1,79 +1,144 @@
 #![windows_subsystem = "windows"] // Don't go through console on Windows.
 
+mod align;
+mod chromatic_aberration;
+mod cli;
 mod image_list;
 mod image_view;
 mod menu;
+mod save_format;
+mod stats;
 
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::mpsc};
 
 use eframe::egui;
+use egui_custom::{ExposureEditor, ExposureEditorItem, ImageListSelection};
 use rayon::prelude::*;
 
-use sensor_analysis::eval_transfer_function_lut;
+use job_queue::Priority;
+use sensor_analysis::{eval_transfer_function_lut, frame_rejection::Rejection, Histogram};
 use shared_data::Shared;
 
+use lib::display_profile::DisplayTransform;
+use lib::lens_projection::{LensProjection, LENS_PROJECTIONS};
+use lib::preferences::Preferences;
 use lib::{ImageBuf, ImageInfo, SourceImage};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const APP_NAME: &str = "hdri_merge";
+
+// The second worker lets previews load while longer jobs are running.
+const DEFAULT_WORKER_COUNT: usize = 2;
 
 pub fn main() {
-    clap::App::new("ETF HDRI Merge")
-        .version(VERSION)
-        .author("Nathan Vegdahl, Ian Hubert")
-        .about("Merges LDR images into an HDRI")
-        .get_matches();
+    let args = cli::add_args(
+        clap::App::new("ETF HDRI Merge")
+            .version(VERSION)
+            .author("Nathan Vegdahl, Ian Hubert")
+            .about("Merges LDR images into an HDRI"),
+    )
+    .get_matches();
+
+    if args.is_present("cli") {
+        std::process::exit(cli::run(&args));
+    }
+
+    let preferences = Preferences::load(APP_NAME);
+    let mut viewport = egui::ViewportBuilder::default().with_drag_and_drop(true); // Enable drag-and-dropping files on Windows.
+    if let Ok(preferences) = &preferences {
+        viewport = preferences.apply_to_viewport(viewport);
+    }
 
     eframe::run_native(
         "HDRI Merge",
         eframe::NativeOptions {
-            viewport: egui::ViewportBuilder::default().with_drag_and_drop(true), // Enable drag-and-dropping files on Windows.
+            viewport: viewport,
             ..eframe::NativeOptions::default()
         },
-        Box::new(|cc| Box::new(AppMain::new(cc))),
+        Box::new(|cc| Box::new(AppMain::new(cc, preferences))),
     )
     .expect("Couldn't start application.");
 }
 
 pub struct AppMain {
     job_queue: job_queue::JobQueue,
+    preferences: Preferences,
+    display: DisplayTransform, // For previews.  Updated from the preferences.
 
     images: Shared<Vec<SourceImage>>,
     hdri_merger: Shared<Option<HDRIMerger>>,
+    hdri_preview: Shared<Option<HDRIPreview>>, // Downscaled copy of the HDRI, for the preview.
+    incremental_merge: Shared<Option<IncrementalMerge>>,
 
     ui_data: Shared<UIData>,
 }
 
 impl AppMain {
-    fn new(cc: &eframe::CreationContext) -> AppMain {
+    fn new(cc: &eframe::CreationContext, preferences: std::io::Result<Preferences>) -> AppMain {
         // Dark mode.
         cc.egui_ctx.set_visuals(egui::style::Visuals {
             dark_mode: true,
             ..egui::style::Visuals::default()
         });
 
+        let (preferences, preferences_error) = match preferences {
+            Ok(preferences) => (preferences, None),
+            Err(e) => (Preferences::default(), Some(e)),
+        };
+
         // Update callback for jobs.
-        let mut job_queue = job_queue::JobQueue::new();
+        let mut job_queue =
+            job_queue::JobQueue::with_worker_count(preferences.worker_count(DEFAULT_WORKER_COUNT));
         let ctx_clone = cc.egui_ctx.clone();
         job_queue.set_update_fn(move || {
             ctx_clone.request_repaint();
         });
 
+        // Keep a log of the session on disk, for bug reports.
+        if let Some(path) = lib::user_dirs::log_path(APP_NAME) {
+            if let Err(e) = job_queue.set_log_file(&path) {
+                job_queue.log_warning(format!(
+                    "Unable to open log file \"{}\": {}",
+                    path.to_string_lossy(),
+                    e
+                ));
+            }
+        }
+        if let Some(e) = preferences_error {
+            job_queue.log_warning(format!("Unable to load preferences: {}", e));
+        }
+
         AppMain {
             job_queue: job_queue,
+            preferences: preferences,
+            display: DisplayTransform::srgb(),
 
             images: Shared::new(Vec::new()),
             hdri_merger: Shared::new(None),
+            hdri_preview: Shared::new(None),
+            incremental_merge: Shared::new(None),
 
             ui_data: Shared::new(UIData {
                 preview_exposure: 0.0,
-                selected_image_index: 0,
+                selection: ImageListSelection::default(),
                 image_zoom: 1.0,
                 show_image: ShowImage::SelectedImage,
                 save_with_preview_exposure: false,
+                save_options: save_format::SaveOptions::new(),
+                show_save_window: false,
+                clipping_overlay: false,
+                use_camera_curve: false,
+                correct_chromatic_aberration: false,
+                lens_projection: LensProjection::Rectilinear,
 
                 thumbnails: Vec::new(),
                 image_preview_tex: None,
                 hdri_preview_tex: None,
+                hdri_stats: None,
+                exposure_editor: None,
+                compensate_settings: true,
+                histograms: Vec::new(),
+                rejections: Vec::new(),
             }),
         }
     }
@@ -83,15 +148,36 @@ impl AppMain {
 pub struct UIData {
     // Widgets.
     preview_exposure: f32,
-    selected_image_index: usize,
+    selection: ImageListSelection,
     image_zoom: f32,
     show_image: ShowImage,
     save_with_preview_exposure: bool,
+    save_options: save_format::SaveOptions,
+    show_save_window: bool,
+    clipping_overlay: bool, // Highlight pixels that are poorly covered by the source images.
+    use_camera_curve: bool, // Use the camera database's curve for known cameras instead of estimating.
+    correct_chromatic_aberration: bool,
+    lens_projection: LensProjection, // Of the source images, recorded in saved HDRIs.
 
     // Others.
     thumbnails: Vec<(egui::TextureHandle, usize, usize, ImageInfo)>, // (GPU texture, width, height, info)
     image_preview_tex: Option<(egui::TextureHandle, usize, usize)>,
     hdri_preview_tex: Option<(egui::TextureHandle, usize, usize)>,
+    hdri_stats: Option<stats::HdriStats>,
+
+    // Manual entry of the exposures of images without exposure metadata.
+    exposure_editor: Option<(ExposureEditor, Vec<(String, String)>)>, // (editor, (full path, filename) of each image)
+
+    // Whether exposures account for ISO and aperture changes between
+    // images, or are from shutter speed alone.
+    compensate_settings: bool,
+
+    // Histograms of the images, for finding duplicate and outlier
+    // images.
+    histograms: Vec<((String, String), [Histogram; 3])>, // ((full path, filename), histograms)
+
+    // Duplicate and outlier images, parallel to `thumbnails`.
+    rejections: Vec<Option<Rejection>>,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -106,7 +192,18 @@ impl eframe::App for AppMain {
         // Don't need to do anything.
     }
 
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Err(e) = self.preferences.save(APP_NAME) {
+            self.job_queue
+                .log_error(format!("Unable to save preferences: {}", e));
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.preferences.update_window(ctx);
+        if self.display.profile() != &self.preferences.display_profile {
+            self.update_display_transform(ctx);
+        }
         // Some simple queries we use in drawing the UI.
         let image_count = self.ui_data.lock().thumbnails.len();
         let have_hdri = match self.hdri_merger.try_lock() {
@@ -117,7 +214,7 @@ impl eframe::App for AppMain {
         let job_count = self.job_queue.job_count();
 
         // File dialogs used in the UI.
-        let add_images_dialog = rfd::FileDialog::new()
+        let mut add_images_dialog = rfd::FileDialog::new()
             .set_title("Add Images")
             .add_filter(
                 "All Images",
@@ -130,22 +227,15 @@ impl eframe::App for AppMain {
             .add_filter("tiff", &["tiff", "TIFF", "tif", "TIF"])
             .add_filter("webp", &["webp", "WEBP"])
             .add_filter("png", &["png", "PNG"]);
-        let save_hdri_dialog = rfd::FileDialog::new()
-            .set_title("Save HDRI")
-            .add_filter(".hdr", &["hdr", "HDR"]);
+        if let Some(dir) = &self.preferences.last_directory {
+            add_images_dialog = add_images_dialog.set_directory(dir);
+        }
 
         //----------------
         // GUI.
 
         // Menu bar.
-        menu::menu_bar(
-            ctx,
-            self,
-            &add_images_dialog,
-            &save_hdri_dialog,
-            have_hdri,
-            job_count,
-        );
+        menu::menu_bar(ctx, self, &add_images_dialog, have_hdri, job_count);
 
         // Status bar and log (footer).
         egui_custom::status_bar(ctx, &self.job_queue);
@@ -158,6 +248,18 @@ impl eframe::App for AppMain {
                 image_list::image_list(ctx, ui, self, job_count);
             });
 
+        // HDRI statistics (right-side panel).
+        if self.ui_data.lock().show_image == ShowImage::HDRI {
+            if let Some(ref stats) = self.ui_data.lock().hdri_stats {
+                egui::containers::panel::SidePanel::right("hdri_stats")
+                    .min_width(240.0)
+                    .resizable(false)
+                    .show(ctx, |ui| {
+                        stats::stats_panel(ui, stats);
+                    });
+            }
+        }
+
         // Main area.
         egui::containers::panel::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal_top(|ui| {
@@ -186,7 +288,7 @@ impl eframe::App for AppMain {
 
                 ui.label(" ➡ ");
 
-                // Save .hdr button.
+                // Save button.
                 if ui
                     .add_enabled(
                         have_hdri && job_count == 0,
@@ -194,21 +296,47 @@ impl eframe::App for AppMain {
                     )
                     .clicked()
                 {
-                    if let Some(path) = save_hdri_dialog.clone().save_file() {
-                        self.save_hdri(path);
-                    }
+                    self.ui_data.lock_mut().show_save_window = true;
                 }
 
                 ui.add_space(20.0);
 
-                // Save with preview exposure or not.
                 ui.add_enabled(
                     job_count == 0,
                     egui::widgets::Checkbox::new(
-                        &mut self.ui_data.lock_mut().save_with_preview_exposure,
-                        "Save with preview exposure",
+                        &mut self.ui_data.lock_mut().use_camera_curve,
+                        "Use known camera curve",
                     ),
-                );
+                )
+                .on_hover_text("For images shot in the log profile of a camera in the camera database: use that camera's log curve and black level instead of estimating the transfer function.");
+
+                ui.add_space(20.0);
+
+                ui.add_enabled(
+                    job_count == 0,
+                    egui::widgets::Checkbox::new(
+                        &mut self.ui_data.lock_mut().correct_chromatic_aberration,
+                        "Correct chromatic aberration",
+                    ),
+                )
+                .on_hover_text("Estimates and removes the colored fringes that lateral chromatic aberration (common with wide lenses) causes towards the edges of the image.");
+
+                ui.add_space(20.0);
+
+                ui.label("Lens:");
+                ui.add_enabled_ui(job_count == 0, |ui| {
+                    let projection = &mut self.ui_data.lock_mut().lens_projection;
+                    egui::ComboBox::from_id_source("lens_projection")
+                        .width(180.0)
+                        .selected_text(projection.ui_text())
+                        .show_ui(ui, |ui| {
+                            for p in LENS_PROJECTIONS.iter() {
+                                ui.selectable_value(projection, *p, p.ui_text());
+                            }
+                        })
+                        .response
+                        .on_hover_text("The projection of the lens the images were taken with.  Fisheye projections are recorded in saved HDRIs, for tools that unwrap or stitch them.");
+                });
             });
 
             ui.add(egui::widgets::Separator::default().spacing(12.0));
@@ -217,6 +345,9 @@ impl eframe::App for AppMain {
             image_view::image_view(ctx, ui, self, image_count, have_hdri, have_hdri_preview_tex);
         });
 
+        // Save window.
+        save_format::save_window(ctx, self, job_count);
+
         //----------------
         // Processing.
 
@@ -237,15 +368,49 @@ impl eframe::App for AppMain {
 }
 
 impl AppMain {
-    fn add_image_files(&mut self, mut image_paths: Vec<PathBuf>, ctx: &egui::Context) {
+    /// Rebuilds the display transform from the preferences, and updates
+    /// the previews to use it.
+    fn update_display_transform(&mut self, ctx: &egui::Context) {
+        let (display, error) = DisplayTransform::new(&self.preferences.display_profile);
+        if let Some(e) = error {
+            self.job_queue
+                .log_error(format!("Unable to use display profile: {}", e));
+        }
+        self.display = display;
+
+        let selected_image_index = self.ui_data.lock().selection.current.1;
+        self.compute_image_preview(selected_image_index, ctx);
+        self.compute_hdri_preview(ctx);
+    }
+
+    fn add_image_files(&mut self, image_paths: Vec<PathBuf>, ctx: &egui::Context) {
         let images = self.images.clone_ref();
+        let hdri = self.hdri_merger.clone_ref();
+        let hdri_preview = self.hdri_preview.clone_ref();
+        let incremental_merge = self.incremental_merge.clone_ref();
         let ui_data = self.ui_data.clone_ref();
+        let display = self.display.clone();
         let ctx1 = ctx.clone();
         let ctx2 = ctx.clone();
 
-        self.job_queue.add_job("Add Image(s)", move |status| {
+        self.job_queue.add_job("Add Image(s)", Priority::Normal, move |status| {
+            // Thumbnails are added out of step with the rejections, so
+            // clear them until they're updated.
+            ui_data.lock_mut().rejections.clear();
+
+            // When the transfer function is known up front, merge the
+            // images as they load.  That's only possible when continuing
+            // a merge of all of the images already loaded.
+            let mut merge = incremental_merge.lock_mut().take();
+            let mut merging = ui_data.lock().use_camera_curve
+                && match merge {
+                    Some(ref merge) => merge.matches(&images.lock()),
+                    None => images.lock().is_empty(),
+                };
+
             let len = image_paths.len() as f32;
-            for (img_i, path) in image_paths.drain(..).enumerate() {
+            let loaded_images = load_images_ahead(image_paths);
+            for (img_i, (path, img)) in loaded_images.iter().enumerate() {
                 if status.lock().is_canceled() {
                     break;
                 }
@@ -256,7 +421,7 @@ impl AppMain {
                 );
 
                 // Load image.
-                let img = match lib::job_helpers::load_image(&path) {
+                let img = match img {
                     Ok(img) => img,
                     Err(image_fmt::ReadError::IO(e)) => {
                         status.lock_mut().log_error(format!(
@@ -282,6 +447,16 @@ impl AppMain {
                     }
                 };
 
+                // Float images are already linear, so there's nothing to
+                // merge them with.
+                if img.image.data.is_float() {
+                    status.lock_mut().log_error(format!(
+                        "Floating point images can't be merged: \"{}\".  Not loading.",
+                        path.to_string_lossy()
+                    ));
+                    continue;
+                }
+
                 // Ensure it has the same resolution as the other images.
                 if !images.lock().is_empty() {
                     let needed_width = images.lock()[0].image.width();
@@ -298,18 +473,19 @@ impl AppMain {
                 // Check if we got exposure data from it.
                 if img.info.exposure.is_none() {
                     status.lock_mut().log_warning(format!(
-                        "Image file lacks Exif data needed to compute exposure value: \"{}\".  HDRI merging will not work correctly.",
+                        "Image file lacks Exif data needed to compute exposure value: \"{}\".  HDRI merging will not work correctly unless its exposure is entered with \"Enter Exposures...\".",
                         path.to_string_lossy()
                     ));
                 }
 
                 // Make a thumbnail texture.
                 let (thumbnail_tex_handle, thumbnail_width, thumbnail_height) = {
-                    let (pixels, width, height) = lib::job_helpers::make_image_preview(
+                    let (mut pixels, width, height) = lib::job_helpers::make_image_preview(
                         &img,
                         Some(128),
                         None,
                     );
+                    display.apply(&mut pixels, 4);
                     (
                         make_texture((&pixels, width, height), &ctx1),
                         width,
@@ -317,9 +493,31 @@ impl AppMain {
                     )
                 };
 
+                let histograms = lib::job_helpers::compute_image_histograms(&img, None, None);
+
+                // Merge it, and show the merge so far as a rough preview.
+                // That replaces the previously built HDRI, if any, which
+                // is out of date now anyway.
+                if merging {
+                    merging = merge_incrementally(&mut merge, &img);
+                    if let Some(ref merge) = merge {
+                        let preview = HDRIPreview::new(&merge.merger, PREVIEW_MAX_SIZE);
+                        let tex_info = make_hdri_preview_tex(&preview, &ui_data, &display, &ctx1);
+                        *hdri.lock_mut() = None;
+                        *hdri_preview.lock_mut() = Some(preview);
+                        let mut ui_data = ui_data.lock_mut();
+                        ui_data.hdri_stats = None;
+                        ui_data.hdri_preview_tex = Some(tex_info);
+                    }
+                }
+
                 // Add image and thumbnail to our lists.
                 {
                     let mut ui_data = ui_data.lock_mut();
+                    ui_data.histograms.push((
+                        (img.info.full_filepath.clone(), img.info.filename.clone()),
+                        histograms,
+                    ));
                     ui_data.thumbnails
                         .push((thumbnail_tex_handle, thumbnail_width, thumbnail_height, img.info.clone()));
                     ui_data.thumbnails
@@ -332,108 +530,270 @@ impl AppMain {
                         .sort_unstable_by(|a, b| a.info.exposure.partial_cmp(&b.info.exposure).unwrap());
                 }
             }
+            *incremental_merge.lock_mut() = merge;
+
+            // Check for ISO and aperture changes between the images.
+            update_bracket_exposures(&images, &ui_data);
+            let images = images.lock();
+            let infos: Vec<&ImageInfo> = images.iter().map(|img| &img.info).collect();
+            let mismatches = lib::bracket_analysis::find_settings_mismatches(&infos);
+            if mismatches.iter().any(|m| m.any()) {
+                status.lock_mut().log_warning(format!(
+                    "The images mix ISO or aperture changes with shutter speed bracketing, which may throw off merging: {}.",
+                    infos
+                        .iter()
+                        .zip(mismatches.iter())
+                        .filter(|(_, m)| m.any())
+                        .map(|(info, _)| format!("\"{}\"", info.filename))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+
+            // Check for duplicate and outlier images.
+            let rejected: Vec<String> = {
+                let ui_data = ui_data.lock();
+                ui_data
+                    .thumbnails
+                    .iter()
+                    .zip(ui_data.rejections.iter())
+                    .filter(|(_, r)| r.is_some())
+                    .map(|((_, _, _, info), _)| format!("\"{}\"", info.filename))
+                    .collect()
+            };
+            if !rejected.is_empty() {
+                status.lock_mut().log_warning(format!(
+                    "Some images duplicate other images, or their brightness doesn't fit their exposure, which may throw off merging: {}.",
+                    rejected.join(", ")
+                ));
+            }
         });
 
-        let selected_image_index = self.ui_data.lock().selected_image_index;
+        let selected_image_index = self.ui_data.lock().selection.current.1;
         self.compute_image_preview(selected_image_index, &ctx2);
     }
 
+    fn open_exposure_editor(&mut self) {
+        let (items, keys): (Vec<_>, Vec<_>) = self
+            .images
+            .lock()
+            .iter()
+            .map(|img| {
+                (
+                    ExposureEditorItem {
+                        name: img.info.filename.clone(),
+                        known_ev: img.info.exposure.map(|e| e.log2()),
+                        brightness: lib::job_helpers::estimate_brightness(img),
+                    },
+                    (img.info.full_filepath.clone(), img.info.filename.clone()),
+                )
+            })
+            .unzip();
+        self.ui_data.lock_mut().exposure_editor =
+            Some((ExposureEditor::new("Enter Exposures", items), keys));
+    }
+
+    /// Sets the exposures of the images with the given (full path,
+    /// filename).
+    fn set_exposures(&mut self, exposures: &[((String, String), f32)], ctx: &egui::Context) {
+        apply_exposures(&self.images, &self.ui_data, exposures);
+        self.ui_data.lock_mut().selection.select((0, 0));
+        self.compute_image_preview(0, ctx);
+    }
+
+    /// Sets whether exposures account for ISO and aperture changes, and
+    /// recomputes them.
+    fn set_compensate_settings(&mut self, compensate: bool, ctx: &egui::Context) {
+        self.ui_data.lock_mut().compensate_settings = compensate;
+        update_bracket_exposures(&self.images, &self.ui_data);
+        self.ui_data.lock_mut().selection.select((0, 0));
+        self.compute_image_preview(0, ctx);
+    }
+
     fn remove_image(&self, image_index: usize, ctx: &egui::Context) {
         let images = self.images.clone_ref();
         let ui_data = self.ui_data.clone_ref();
 
-        self.job_queue.add_job("Remove Image", move |status| {
-            status
-                .lock_mut()
-                .set_progress(format!("Removing image..."), 0.0);
+        self.job_queue
+            .add_job("Remove Image", Priority::Normal, move |status| {
+                status
+                    .lock_mut()
+                    .set_progress(format!("Removing image..."), 0.0);
 
-            {
-                images.lock_mut().remove(image_index);
+                {
+                    images.lock_mut().remove(image_index);
 
-                let mut ui_data = ui_data.lock_mut();
-                let _ = ui_data.thumbnails.remove(image_index);
-                if ui_data.selected_image_index > image_index {
-                    ui_data.selected_image_index -= 1;
+                    let mut ui_data = ui_data.lock_mut();
+                    let (_, _, _, info) = ui_data.thumbnails.remove(image_index);
+                    ui_data
+                        .histograms
+                        .retain(|(key, _)| key.0 != info.full_filepath || key.1 != info.filename);
+                    ui_data.selection.image_removed((0, image_index));
                 }
-            }
-        });
+                update_rejections(&ui_data);
+            });
 
-        let selected_image_index = self.ui_data.lock().selected_image_index;
+        let selected_image_index = self.ui_data.lock().selection.current.1;
         self.compute_image_preview(selected_image_index, ctx);
     }
 
     fn build_hdri(&mut self, ctx: &egui::Context) {
         let images = self.images.clone_ref();
         let hdri = self.hdri_merger.clone_ref();
+        let hdri_preview = self.hdri_preview.clone_ref();
+        let incremental_merge = self.incremental_merge.clone_ref();
         let ui_data = self.ui_data.clone_ref();
 
-        self.job_queue.add_job("Build HDRI", move |status| {
-            let img_len = images.lock().len();
-            let width = images.lock()[0].image.width();
-            let height = images.lock()[0].image.height();
+        self.job_queue
+            .add_job("Build HDRI", Priority::Normal, move |status| {
+                let img_len = images.lock().len();
+                let width = images.lock()[0].image.width();
+                let height = images.lock()[0].image.height();
+
+                // Reports progress as the `done`th of the job's steps: a
+                // histogram and a merge per image, the transfer function
+                // estimate, and finalizing.
+                let total_steps = (img_len * 2 + 2) as f32;
+                let set_step = |done: usize, sub_step: &str, items: Option<(usize, usize)>| {
+                    let mut status = status.lock_mut();
+                    status.set_progress("Building HDRI".into(), done as f32 / total_steps);
+                    let sub_ratio = items.map(|(i, len)| i as f32 / len as f32).unwrap_or(0.0);
+                    status.set_sub_progress(sub_step.into(), sub_ratio);
+                    if let Some((i, len)) = items {
+                        status.set_item_count(i, len);
+                    }
+                };
 
-            status
-                .lock_mut()
-                .set_progress(format!("Estimating transfer function"), 0.0);
+                // If the images were merged as they loaded, and are still
+                // the same, there's nothing left to merge.
+                let incremental = incremental_merge.lock_mut().take().filter(|merge| {
+                    ui_data.lock().use_camera_curve && merge.matches(&images.lock())
+                });
+                let (mut hdri_merger, inv_mapping, floor_ceil_pairs) =
+                    if let Some(merge) = incremental {
+                        status.lock_mut().log_note(format!(
+                            "Using the known curve of the {} instead of estimating one.",
+                            merge.camera.ui_text()
+                        ));
+                        (merge.merger, merge.inv_mapping, merge.floor_ceil_pairs)
+                    } else {
+                        // Calculate histograms.
+                        let mut histograms = [Vec::new(), Vec::new(), Vec::new()];
+                        for img_i in 0..img_len {
+                            if status.lock().is_canceled() {
+                                return;
+                            }
+                            set_step(img_i, "Computing histograms", Some((img_i, img_len)));
+                            let src_img = &images.lock()[img_i];
+                            if let Some(exposure) = src_img.info.exposure {
+                                let img_hists =
+                                    lib::job_helpers::compute_image_histograms(src_img, None, None);
+                                for (chan, hist) in
+                                    std::iter::IntoIterator::into_iter(img_hists).enumerate()
+                                {
+                                    histograms[chan].push((hist, exposure));
+                                }
+                            }
+                        }
+
+                        // Estimate linearizating curve, unless it's known.
+                        set_step(img_len, "Estimating transfer function", None);
+                        let camera_curves = if ui_data.lock().use_camera_curve {
+                            known_camera_curves(&images.lock())
+                        } else {
+                            None
+                        };
+                        let (inv_mapping, floor_ceil_pairs) =
+                            if let Some((camera, inv_mapping, floor_ceil_pairs)) = camera_curves {
+                                status.lock_mut().log_note(format!(
+                                    "Using the known curve of the {} instead of estimating one.",
+                                    camera.ui_text()
+                                ));
+                                (inv_mapping, floor_ceil_pairs)
+                            } else {
+                                let (inv_mapping, floor_ceil_pairs, _) =
+                                    sensor_analysis::estimate_transfer_function(&[
+                                        &histograms[0],
+                                        &histograms[1],
+                                        &histograms[2],
+                                    ]);
+                                (inv_mapping, floor_ceil_pairs)
+                            };
+
+                        // Merge images.
+                        let mut hdri_merger = HDRIMerger::new(width, height);
+                        for img_i in 0..img_len {
+                            if status.lock().is_canceled() {
+                                return;
+                            }
+                            set_step(
+                                img_len + 1 + img_i,
+                                "Merging images",
+                                Some((img_i, img_len)),
+                            );
+
+                            let src_img = &images.lock()[img_i];
+                            hdri_merger.add_image(
+                                &src_img.image,
+                                src_img.info.exposure.unwrap_or(1.0),
+                                &floor_ceil_pairs,
+                                &inv_mapping,
+                            );
+                        }
+                        (hdri_merger, inv_mapping, floor_ceil_pairs)
+                    };
 
-            // Calculate histograms.
-            let mut histograms = [Vec::new(), Vec::new(), Vec::new()];
-            for img_i in 0..img_len {
-                if status.lock().is_canceled() {
-                    return;
-                }
-                let src_img = &images.lock()[img_i];
-                if let Some(exposure) = src_img.info.exposure {
-                    let img_hists = lib::job_helpers::compute_image_histograms(src_img);
-                    for (chan, hist) in std::iter::IntoIterator::into_iter(img_hists).enumerate() {
-                        histograms[chan].push((hist, exposure));
-                    }
+                {
+                    let images = images.lock();
+                    let (lowest, highest) = (&images[0], &images[img_len - 1]);
+                    hdri_merger.add_bracket_ends(
+                        (&lowest.image, lowest.info.exposure.unwrap_or(1.0)),
+                        (&highest.image, highest.info.exposure.unwrap_or(1.0)),
+                        &floor_ceil_pairs,
+                        &inv_mapping,
+                    );
                 }
-            }
 
-            // Estimate linearizating curve.
-            let (inv_mapping, floor_ceil_pairs, _) =
-                sensor_analysis::estimate_transfer_function(&[
-                    &histograms[0],
-                    &histograms[1],
-                    &histograms[2],
-                ]);
-
-            // Merge images.
-            let mut hdri_merger = HDRIMerger::new(width, height);
-            for img_i in 0..img_len {
+                // Finalize.
                 if status.lock().is_canceled() {
                     return;
                 }
-                status.lock_mut().set_progress(
-                    format!("Merging image {}", img_i + 1),
-                    (img_i + 1) as f32 / (img_len + 2) as f32,
-                );
-
-                let src_img = &images.lock()[img_i];
-                hdri_merger.add_image(
-                    &src_img.image,
-                    src_img.info.exposure.unwrap_or(1.0),
-                    &floor_ceil_pairs,
-                    &inv_mapping,
-                    img_i == 0,
-                    img_i == img_len - 1,
-                );
-            }
+                set_step(img_len * 2 + 1, "Finalizing", None);
+                hdri_merger.finish();
+                if ui_data.lock().correct_chromatic_aberration {
+                    status
+                        .lock_mut()
+                        .set_sub_progress("Correcting chromatic aberration".into(), 0.0);
+                    let scales = chromatic_aberration::estimate_scales(
+                        &hdri_merger.pixels,
+                        hdri_merger.width,
+                        hdri_merger.height,
+                    );
+                    chromatic_aberration::correct(
+                        &mut hdri_merger.pixels,
+                        hdri_merger.width,
+                        hdri_merger.height,
+                        scales,
+                    );
+                    status
+                        .lock_mut()
+                        .log_note(chromatic_aberration_message(scales));
+                }
+                for (is_warning, message) in hdri_merger.coverage_messages() {
+                    if is_warning {
+                        status.lock_mut().log_warning(message);
+                    } else {
+                        status.lock_mut().log_note(message);
+                    }
+                }
+                let stats = stats::HdriStats::compute(&hdri_merger.pixels);
+                let preview = HDRIPreview::new(&hdri_merger, PREVIEW_MAX_SIZE);
 
-            // Finalize.
-            if status.lock().is_canceled() {
-                return;
-            }
-            status.lock_mut().set_progress(
-                format!("Finalizing"),
-                (img_len + 1) as f32 / (img_len + 2) as f32,
-            );
-            hdri_merger.finish();
-
-            *hdri.lock_mut() = Some(hdri_merger);
-            ui_data.lock_mut().show_image = ShowImage::HDRI;
-        });
+                *hdri.lock_mut() = Some(hdri_merger);
+                *hdri_preview.lock_mut() = Some(preview);
+                ui_data.lock_mut().hdri_stats = Some(stats);
+                ui_data.lock_mut().show_image = ShowImage::HDRI;
+            });
 
         self.compute_hdri_preview(ctx);
     }
@@ -445,90 +805,62 @@ impl AppMain {
         } else {
             1.0
         };
+        let options = hdr::HdrOptions {
+            exposure: exposure,
+            software: Some(format!("ETF HDRI Merge v{}", VERSION)),
+            camera: camera_name(&self.images.lock()),
+            projection: projection_tag(self.ui_data.lock().lens_projection),
+            ..hdr::HdrOptions::default()
+        };
+        let save_options = self.ui_data.lock().save_options.clone();
 
-        self.job_queue.add_job("Save HDRI", move |status| {
-            status
-                .lock_mut()
-                .set_progress(format!("Saving: {}", path.to_string_lossy()), 0.0);
-            if let Some(ref hdri) = *hdri.lock() {
-                hdr::write_hdr(
-                    &mut std::io::BufWriter::new(std::fs::File::create(path).unwrap()),
-                    &hdri.pixels,
-                    hdri.width,
-                    hdri.height,
-                    exposure,
-                )
-                .unwrap();
-            }
-        });
+        self.job_queue
+            .add_job("Save HDRI", Priority::High, move |status| {
+                status
+                    .lock_mut()
+                    .set_progress(format!("Saving: {}", path.to_string_lossy()), 0.0);
+                if let Some(ref hdri) = *hdri.lock() {
+                    if let Err(e) = save_format::write_hdri(
+                        &path,
+                        &hdri.pixels,
+                        hdri.width,
+                        hdri.height,
+                        &save_options,
+                        &options,
+                    ) {
+                        status.lock_mut().log_error(format!(
+                            "Unable to save HDRI \"{}\": {}",
+                            path.to_string_lossy(),
+                            e
+                        ));
+                    }
+                }
+            });
     }
 
     fn compute_hdri_preview(&mut self, ctx: &egui::Context) {
-        let hdri = self.hdri_merger.clone_ref();
+        let hdri_preview = self.hdri_preview.clone_ref();
         let ui_data = self.ui_data.clone_ref();
+        let display = self.display.clone();
         let ctx = ctx.clone();
 
         self.job_queue
-            .cancel_pending_jobs_with_name("Update HDRI preview");
-        self.job_queue
-            .add_job("Update HDRI preview", move |status| {
+            .add_job("Update HDRI preview", Priority::Low, move |status| {
                 status
                     .lock_mut()
                     .set_progress("Updating HDRI preview".to_string(), 0.0);
 
-                let exposure = 2.0f32.powf(ui_data.lock().preview_exposure);
-                let srgb_table: Vec<f32> = (0..256)
-                    .map(|n| colorbox::transfer_functions::srgb::from_linear(n as f32 / 255.0))
-                    .collect();
-                let map_val = |n: f32| {
-                    (eval_transfer_function_lut(&srgb_table, (n * exposure).max(0.0).min(1.0))
-                        * 255.0)
-                        .round() as u8
-                };
-
-                let preview: Option<(Vec<u8>, usize, usize)> = hdri.lock().as_ref().map(|hdri| {
-                    (
-                        hdri.pixels
-                            .par_iter()
-                            .map(|[r, g, b]| {
-                                let r = map_val(*r);
-                                let g = map_val(*g);
-                                let b = map_val(*b);
-                                [r, g, b, 255]
-                            })
-                            .flatten_iter()
-                            .collect(),
-                        hdri.width,
-                        hdri.height,
-                    )
-                });
+                let tex_info = hdri_preview
+                    .lock()
+                    .as_ref()
+                    .map(|preview| make_hdri_preview_tex(preview, &ui_data, &display, &ctx));
 
                 if status.lock().is_canceled() {
                     return;
                 }
 
-                if preview.is_some() {
-                    // Update the HDRI preview texture.
-                    let tex_info = preview.as_ref().map(|(pixels, width, height)| {
-                        (
-                            ctx.load_texture(
-                                "",
-                                egui::ColorImage::from_rgba_unmultiplied([*width, *height], pixels),
-                                egui::TextureOptions {
-                                    magnification: egui::TextureFilter::Linear,
-                                    minification: egui::TextureFilter::Linear,
-                                    wrap_mode: egui::TextureWrapMode::ClampToEdge,
-                                },
-                            ),
-                            *width,
-                            *height,
-                        )
-                    });
-
-                    if let Some((tex_handle, width, height)) = tex_info {
-                        let mut ui_data = ui_data.lock_mut();
-                        ui_data.hdri_preview_tex = Some((tex_handle, width, height));
-                    }
+                if let Some(tex_info) = tex_info {
+                    ui_data.lock_mut().hdri_preview_tex = Some(tex_info);
                 }
             });
     }
@@ -536,11 +868,11 @@ impl AppMain {
     fn compute_image_preview(&self, image_index: usize, ctx: &egui::Context) {
         let images = self.images.clone_ref();
         let ui_data = self.ui_data.clone_ref();
+        let display = self.display.clone();
         let ctx = ctx.clone();
 
-        self.job_queue.cancel_jobs_with_name("Update image preview");
         self.job_queue
-            .add_job("Update image preview", move |status| {
+            .add_parallel_job("Update image preview", Priority::Low, move |status| {
                 status
                     .lock_mut()
                     .set_progress("Updating image preview".to_string(), 0.0);
@@ -554,7 +886,9 @@ impl AppMain {
                     return;
                 }
 
-                if let Some((pixels, width, height)) = preview {
+                if let Some((mut pixels, width, height)) = preview {
+                    display.apply(&mut pixels, 4);
+
                     // Update the image preview texture.
                     let tex_handle = ctx.load_texture(
                         "",
@@ -579,6 +913,32 @@ struct HDRIMerger {
     pixel_weights: Vec<f32>,
     width: usize,
     height: usize,
+
+    // Per pixel, how many of the source images it was usable in
+    // (neither clipped nor below the floor), and how many it was
+    // clipped in.
+    usable_counts: Vec<u16>,
+    clipped_counts: Vec<u16>,
+    image_count: u16,
+
+    is_finished: bool,
+}
+
+/// The lowest or highest exposed image of a bracket set, whose
+/// weighting doesn't taper off at one end.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum BracketEnd {
+    Lowest,  // Nothing covers its highlights better.
+    Highest, // Nothing covers its shadows better.
+}
+
+/// How well a pixel of the HDRI is covered by the source images.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Coverage {
+    Good,
+    SingleImage,      // Only usable in one image, so it's noisier.
+    ClippedHighlight, // Clipped in every image that wasn't below the floor.
+    ClippedShadow,    // Below the floor in every image.
 }
 
 impl HDRIMerger {
@@ -588,18 +948,128 @@ impl HDRIMerger {
             pixel_weights: vec![0.0; width * height],
             width: width,
             height: height,
+
+            usable_counts: vec![0; width * height],
+            clipped_counts: vec![0; width * height],
+            image_count: 0,
+
+            is_finished: false,
+        }
+    }
+
+    /// The merged value of a pixel.  Before `finish()` it's merged from
+    /// the images added so far, which is only good for a rough preview.
+    fn pixel(&self, pixel_index: usize) -> [f32; 3] {
+        let pixel = self.pixels[pixel_index];
+        let weight = self.pixel_weights[pixel_index];
+        if self.is_finished || weight <= 0.0 {
+            pixel
+        } else {
+            [pixel[0] / weight, pixel[1] / weight, pixel[2] / weight]
         }
     }
 
+    fn coverage(&self, pixel_index: usize) -> Coverage {
+        match self.usable_counts[pixel_index] {
+            0 if self.clipped_counts[pixel_index] > 0 => Coverage::ClippedHighlight,
+            0 => Coverage::ClippedShadow,
+            1 if self.image_count > 1 => Coverage::SingleImage,
+            _ => Coverage::Good,
+        }
+    }
+
+    /// The fraction of pixels with the given coverage.
+    fn coverage_fraction(&self, coverage: Coverage) -> f32 {
+        let count = (0..self.pixels.len())
+            .filter(|i| self.coverage(*i) == coverage)
+            .count();
+        count as f32 / self.pixels.len().max(1) as f32
+    }
+
+    /// Messages about pixels that couldn't be recovered from any of the
+    /// source images, as `(is_warning, message)`.
+    fn coverage_messages(&self) -> Vec<(bool, String)> {
+        const THRESHOLD: f32 = 0.0001;
+
+        let mut messages = Vec::new();
+        let highlights = self.coverage_fraction(Coverage::ClippedHighlight);
+        if highlights > THRESHOLD {
+            messages.push((
+                true,
+                format!(
+                    "{:.2}% of the HDRI's pixels are clipped in every image.  A darker exposure is needed to recover them.",
+                    highlights * 100.0
+                ),
+            ));
+        }
+        let shadows = self.coverage_fraction(Coverage::ClippedShadow);
+        if shadows > THRESHOLD {
+            messages.push((
+                false,
+                format!(
+                    "{:.2}% of the HDRI's pixels are below the sensor floor in every image.  A brighter exposure is needed to recover them.",
+                    shadows * 100.0
+                ),
+            ));
+        }
+        messages
+    }
+
+    /// Adds an image to the merge.
+    ///
+    /// Images can be added in any order, e.g. as they load.  Once they're
+    /// all added, the lowest and highest exposed ones also need to be
+    /// passed to `add_bracket_ends()`.
     fn add_image(
         &mut self,
         img: &image_fmt::Image,
         exposure: f32,
         floor_ceil: &[(f32, f32)],
         linearizing_curves: &[Vec<f32>],
-        is_lowest_exposed: bool,
-        is_highest_exposed: bool,
     ) {
+        self.image_count += 1;
+        self.accumulate(img, exposure, floor_ceil, linearizing_curves, None);
+    }
+
+    /// Adds the rest of the weight of the lowest and highest exposed
+    /// images, as `(image, exposure)`.  They're weighted like the other
+    /// images by `add_image()`, which tapers off towards both clipping and
+    /// the floor, but there are no other images to cover the lowest's
+    /// highlights or the highest's shadows.
+    fn add_bracket_ends(
+        &mut self,
+        lowest: (&image_fmt::Image, f32),
+        highest: (&image_fmt::Image, f32),
+        floor_ceil: &[(f32, f32)],
+        linearizing_curves: &[Vec<f32>],
+    ) {
+        self.accumulate(
+            lowest.0,
+            lowest.1,
+            floor_ceil,
+            linearizing_curves,
+            Some(BracketEnd::Lowest),
+        );
+        self.accumulate(
+            highest.0,
+            highest.1,
+            floor_ceil,
+            linearizing_curves,
+            Some(BracketEnd::Highest),
+        );
+    }
+
+    /// Accumulates an image's weighted linear values.  With a bracket
+    /// end, only the weight that its end adds is accumulated.
+    fn accumulate(
+        &mut self,
+        img: &image_fmt::Image,
+        exposure: f32,
+        floor_ceil: &[(f32, f32)],
+        linearizing_curves: &[Vec<f32>],
+        bracket_end: Option<BracketEnd>,
+    ) {
+        debug_assert!(!self.is_finished);
         debug_assert_eq!(self.width, img.width());
         debug_assert_eq!(self.height, img.height());
 
@@ -630,18 +1100,29 @@ impl HDRIMerger {
             };
 
             // Triangle weight.
-            let tri = if (is_lowest_exposed && n > 0.5) || (is_highest_exposed && n < 0.5) {
-                // For highest/lowest exposed image, make the appropriate
-                // half a constant 1.0 instead of sloping down to zero.
-                1.0
-            } else {
-                ((0.5 - (n - 0.5).abs()) * 4.0).min(1.0)
-            };
+            let tri = ((0.5 - (n - 0.5).abs()) * 4.0).min(1.0);
 
             // Triangle -> smooth step weight.
             let smooth = tri * tri * (3.0 - 2.0 * tri);
+            let weight = smooth * smooth * smooth;
+
+            // For highest/lowest exposed image, the appropriate half is
+            // a constant 1.0 instead of sloping down to zero.
+            match bracket_end {
+                None => weight,
+                Some(BracketEnd::Lowest) if n > 0.5 => 1.0 - weight,
+                Some(BracketEnd::Highest) if n < 0.5 => 1.0 - weight,
+                Some(_) => 0.0,
+            }
+        };
 
-            smooth * smooth * smooth
+        // Returns whether an encoded color is (usable, clipped).
+        let classify = |encoded_rgb: (f32, f32, f32)| -> (bool, bool) {
+            let r = (encoded_rgb.0 - r_floor) * r_norm;
+            let g = (encoded_rgb.1 - g_floor) * g_norm;
+            let b = (encoded_rgb.2 - b_floor) * b_norm;
+            let clipped = r.max(g).max(b) >= 1.0;
+            (!clipped && r.min(g).min(b) > 0.0, clipped)
         };
 
         let inv_exposure = 1.0 / exposure;
@@ -663,6 +1144,12 @@ impl HDRIMerger {
                     self.pixels[i][1] += g_linear * inv_exposure * weight;
                     self.pixels[i][2] += b_linear * inv_exposure * weight;
                     self.pixel_weights[i] += weight;
+
+                    if bracket_end.is_none() {
+                        let (usable, clipped) = classify((r, g, b));
+                        self.usable_counts[i] += usable as u16;
+                        self.clipped_counts[i] += clipped as u16;
+                    }
                 }
             }
 
@@ -683,6 +1170,12 @@ impl HDRIMerger {
                     self.pixels[i][1] += g_linear * inv_exposure * weight;
                     self.pixels[i][2] += b_linear * inv_exposure * weight;
                     self.pixel_weights[i] += weight;
+
+                    if bracket_end.is_none() {
+                        let (usable, clipped) = classify((r, g, b));
+                        self.usable_counts[i] += usable as u16;
+                        self.clipped_counts[i] += clipped as u16;
+                    }
                 }
             }
 
@@ -698,7 +1191,410 @@ impl HDRIMerger {
                 pixel[2] /= weight;
             }
         }
+        self.is_finished = true;
+    }
+}
+
+/// An HDRI merged as its images load, which is possible when the
+/// transfer function is known up front from the camera database.
+/// Building the HDRI finishes it instead of merging from scratch, as long
+/// as it's still of the same images.
+struct IncrementalMerge {
+    merger: HDRIMerger,
+    camera: &'static camera_db::CameraInfo,
+    inv_mapping: Vec<Vec<f32>>,
+    floor_ceil_pairs: Vec<(f32, f32)>,
+    merged: Vec<(String, String, f32)>, // (full path, filename, exposure) of each merged image.
+}
+
+impl IncrementalMerge {
+    /// Starts a merge with an image, if it's from a known camera and has
+    /// an exposure.
+    fn new(img: &SourceImage) -> Option<IncrementalMerge> {
+        let (camera, inv_mapping, floor_ceil_pairs) =
+            known_camera_curves(std::slice::from_ref(img))?;
+        let mut merge = IncrementalMerge {
+            merger: HDRIMerger::new(img.image.width(), img.image.height()),
+            camera: camera,
+            inv_mapping: inv_mapping,
+            floor_ceil_pairs: floor_ceil_pairs,
+            merged: Vec::new(),
+        };
+        if merge.add_image(img) {
+            Some(merge)
+        } else {
+            None
+        }
+    }
+
+    /// Merges an image.  Returns false, without merging it, if it lacks
+    /// an exposure, is from a different camera, or has a different
+    /// resolution.
+    fn add_image(&mut self, img: &SourceImage) -> bool {
+        let exposure = match img.info.exposure {
+            Some(exposure) => exposure,
+            None => return false,
+        };
+        let same_camera = img
+            .info
+            .camera
+            .as_ref()
+            .and_then(|(make, model)| camera_db::find_camera(make, model))
+            .map(|camera| std::ptr::eq(camera, self.camera))
+            .unwrap_or(false);
+        if !same_camera || img.image.dimensions != (self.merger.width, self.merger.height) {
+            return false;
+        }
+
+        self.merger.add_image(
+            &img.image,
+            exposure,
+            &self.floor_ceil_pairs,
+            &self.inv_mapping,
+        );
+        self.merged.push((
+            img.info.full_filepath.clone(),
+            img.info.filename.clone(),
+            exposure,
+        ));
+        true
+    }
+
+    /// Whether exactly these images have been merged, with their current
+    /// exposures.
+    fn matches(&self, images: &[SourceImage]) -> bool {
+        self.merged.len() == images.len()
+            && images.iter().all(|img| {
+                self.merged.iter().any(|(path, filename, exposure)| {
+                    img.info.full_filepath == *path
+                        && img.info.filename == *filename
+                        && img.info.exposure == Some(*exposure)
+                })
+            })
+    }
+}
+
+/// Adds a just-loaded image to an incremental merge, starting the merge
+/// if there isn't one yet.  If the image can't be merged incrementally,
+/// drops the merge and returns false, and the HDRI is instead merged from
+/// scratch when it's built.
+fn merge_incrementally(merge: &mut Option<IncrementalMerge>, img: &SourceImage) -> bool {
+    let merged = match merge {
+        Some(merge) => merge.add_image(img),
+        None => {
+            *merge = IncrementalMerge::new(img);
+            merge.is_some()
+        }
+    };
+    if !merged {
+        *merge = None;
+    }
+    merged
+}
+
+/// Loads images on a separate thread, staying one image ahead of the
+/// receiver, so that processing each image overlaps with loading the
+/// next.  Loading stops early if the receiver is dropped.
+fn load_images_ahead(
+    paths: Vec<PathBuf>,
+) -> mpsc::Receiver<(PathBuf, Result<SourceImage, image_fmt::ReadError>)> {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    std::thread::spawn(move || {
+        for path in paths {
+            let img = lib::job_helpers::load_image(&path);
+            if sender.send((path, img)).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// The projection to record in saved HDRIs.  Rectilinear is the
+/// default assumption, so it isn't recorded.
+fn projection_tag(projection: LensProjection) -> Option<String> {
+    if projection.is_fisheye() {
+        Some(projection.as_str().into())
+    } else {
+        None
+    }
+}
+
+/// Describes the chromatic aberration that was corrected.
+fn chromatic_aberration_message(scales: [f32; 2]) -> String {
+    if scales == [1.0, 1.0] {
+        "No chromatic aberration was found to correct.".into()
+    } else {
+        format!(
+            "Corrected chromatic aberration: red scaled by {:.4}, blue scaled by {:.4}.",
+            scales[0], scales[1]
+        )
+    }
+}
+
+/// The largest width or height of the HDRI preview.  Larger HDRIs are
+/// downscaled for the preview, to keep it responsive.
+const PREVIEW_MAX_SIZE: usize = 2048;
+
+/// A downscaled copy of an HDRI, for computing previews from.  Saving
+/// always uses the full-resolution HDRI.
+#[derive(Debug)]
+struct HDRIPreview {
+    pixels: Vec<[f32; 3]>,
+    coverage: Vec<Coverage>,
+    width: usize,
+    height: usize,
+    full_size: (usize, usize), // The full-resolution HDRI's (width, height).
+}
+
+impl HDRIPreview {
+    /// Downscales by a whole-number factor to fit within `max_size`,
+    /// averaging blocks of pixels.
+    ///
+    /// Each preview pixel's coverage is the worst of its block's, so
+    /// that small problem areas (e.g. a clipped sun) still show up in the
+    /// clipping overlay.
+    fn new(hdri: &HDRIMerger, max_size: usize) -> HDRIPreview {
+        let factor = ((hdri.width.max(hdri.height) + max_size - 1) / max_size).max(1);
+        let width = (hdri.width + factor - 1) / factor;
+        let height = (hdri.height + factor - 1) / factor;
+
+        let severity = |coverage: Coverage| match coverage {
+            Coverage::Good => 0,
+            Coverage::SingleImage => 1,
+            Coverage::ClippedShadow => 2,
+            Coverage::ClippedHighlight => 3,
+        };
+
+        let (pixels, coverage) = (0..(width * height))
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = ((i % width) * factor, (i / width) * factor);
+                let mut sum = [0.0f32; 3];
+                let mut count = 0;
+                let mut worst = Coverage::Good;
+                for src_y in y..(y + factor).min(hdri.height) {
+                    for src_x in x..(x + factor).min(hdri.width) {
+                        let src_i = src_y * hdri.width + src_x;
+                        let pixel = hdri.pixel(src_i);
+                        sum[0] += pixel[0];
+                        sum[1] += pixel[1];
+                        sum[2] += pixel[2];
+                        count += 1;
+
+                        let coverage = hdri.coverage(src_i);
+                        if severity(coverage) > severity(worst) {
+                            worst = coverage;
+                        }
+                    }
+                }
+                let norm = 1.0 / count.max(1) as f32;
+                ([sum[0] * norm, sum[1] * norm, sum[2] * norm], worst)
+            })
+            .unzip();
+
+        HDRIPreview {
+            pixels: pixels,
+            coverage: coverage,
+            width: width,
+            height: height,
+            full_size: (hdri.width, hdri.height),
+        }
+    }
+}
+
+/// Makes the HDRI preview's texture, with the preview exposure, clipping
+/// overlay, and display transform applied.  Returns the texture and the
+/// size to show it at, which is the full-resolution HDRI's.
+fn make_hdri_preview_tex(
+    preview: &HDRIPreview,
+    ui_data: &Shared<UIData>,
+    display: &DisplayTransform,
+    ctx: &egui::Context,
+) -> (egui::TextureHandle, usize, usize) {
+    let exposure = 2.0f32.powf(ui_data.lock().preview_exposure);
+    let clipping_overlay = ui_data.lock().clipping_overlay;
+    let srgb_table: Vec<f32> = (0..256)
+        .map(|n| colorbox::transfer_functions::srgb::from_linear(n as f32 / 255.0))
+        .collect();
+    let map_val = |n: f32| {
+        (eval_transfer_function_lut(&srgb_table, (n * exposure).max(0.0).min(1.0)) * 255.0).round()
+            as u8
+    };
+
+    let pixels: Vec<u8> = preview
+        .pixels
+        .par_iter()
+        .zip(preview.coverage.par_iter())
+        .map(|([r, g, b], coverage)| {
+            let r = map_val(*r);
+            let g = map_val(*g);
+            let b = map_val(*b);
+            let mut pixel = [r, g, b, 255];
+            display.apply(&mut pixel, 4);
+            if clipping_overlay {
+                if let Some(color) = coverage_overlay_color(*coverage) {
+                    for (n, c) in pixel.iter_mut().zip(color.iter()).take(3) {
+                        *n = ((*n as u16 + *c as u16) / 2) as u8;
+                    }
+                }
+            }
+            pixel
+        })
+        .flatten_iter()
+        .collect();
+
+    let tex_handle = ctx.load_texture(
+        "",
+        egui::ColorImage::from_rgba_unmultiplied([preview.width, preview.height], &pixels),
+        egui::TextureOptions {
+            magnification: egui::TextureFilter::Linear,
+            minification: egui::TextureFilter::Linear,
+            wrap_mode: egui::TextureWrapMode::ClampToEdge,
+        },
+    );
+    (tex_handle, preview.full_size.0, preview.full_size.1)
+}
+
+/// The color that pixels with the given coverage are tinted with in the
+/// HDRI preview's clipping overlay, if any.
+fn coverage_overlay_color(coverage: Coverage) -> Option<[u8; 3]> {
+    match coverage {
+        Coverage::Good => None,
+        Coverage::SingleImage => Some([255, 220, 0]),
+        Coverage::ClippedHighlight => Some([255, 0, 0]),
+        Coverage::ClippedShadow => Some([0, 64, 255]),
+    }
+}
+
+/// Looks up the images' camera in the camera database, and returns it
+/// along with linearizing curves and floor/ceiling pairs (in the same
+/// form as `sensor_analysis::estimate_transfer_function()`) built from
+/// its log curve and black level.
+///
+/// Returns `None` if none of the images are from a known camera.
+fn known_camera_curves(
+    images: &[SourceImage],
+) -> Option<(
+    &'static camera_db::CameraInfo,
+    Vec<Vec<f32>>,
+    Vec<(f32, f32)>,
+)> {
+    const RESOLUTION: usize = 4096;
+
+    let camera = images
+        .iter()
+        .filter_map(|img| img.info.camera.as_ref())
+        .find_map(|(make, model)| camera_db::find_camera(make, model))?;
+    let function = lib::transfer_function::TransferFunction {
+        id: camera.transfer_function.into(),
+        ..Default::default()
+    };
+    let floor = camera.black_level_normalized();
+
+    let norm = 1.0 / (RESOLUTION - 1) as f32;
+    let curve: Vec<f32> = (0..RESOLUTION)
+        .map(|i| {
+            function
+                .to_linear_fc(i as f32 * norm, Some(floor), None, true)
+                .max(0.0)
+        })
+        .collect();
+
+    Some((camera, vec![curve; 3], vec![(floor, 1.0); 3]))
+}
+
+/// The camera make and model of the images, from their Exif data, for
+/// recording in saved files.
+fn camera_name(images: &[SourceImage]) -> Option<String> {
+    let (make, model) = images.iter().find_map(|img| img.info.camera.as_ref())?;
+    // Some makers repeat the make in the model string.
+    if model.to_lowercase().starts_with(&make.to_lowercase()) {
+        Some(model.trim().into())
+    } else {
+        Some(format!("{} {}", make.trim(), model.trim()))
+    }
+}
+
+/// Sets the exposures of the images with the given (full path,
+/// filename), and re-sorts the images.
+fn apply_exposures(
+    images: &Shared<Vec<SourceImage>>,
+    ui_data: &Shared<UIData>,
+    exposures: &[((String, String), f32)],
+) {
+    let exposure_of = |info: &ImageInfo| {
+        exposures
+            .iter()
+            .find(|(key, _)| info.full_filepath == key.0 && info.filename == key.1)
+            .map(|(_, exposure)| *exposure)
+    };
+
+    // Stable sorts, so that images with equal exposures stay in the same
+    // order as their thumbnails.
+    {
+        let mut images = images.lock_mut();
+        for img in images.iter_mut() {
+            if let Some(exposure) = exposure_of(&img.info) {
+                img.info.exposure = Some(exposure);
+            }
+        }
+        images.sort_by(|a, b| a.info.exposure.partial_cmp(&b.info.exposure).unwrap());
+    }
+    {
+        let mut ui_data = ui_data.lock_mut();
+        for (_, _, _, info) in ui_data.thumbnails.iter_mut() {
+            if let Some(exposure) = exposure_of(info) {
+                info.exposure = Some(exposure);
+            }
+        }
+        ui_data
+            .thumbnails
+            .sort_by(|a, b| a.3.exposure.partial_cmp(&b.3.exposure).unwrap());
     }
+    update_rejections(ui_data);
+}
+
+/// Recomputes the images' exposures from their metadata, with or
+/// without ISO/aperture compensation as set in the UI.
+fn update_bracket_exposures(images: &Shared<Vec<SourceImage>>, ui_data: &Shared<UIData>) {
+    let compensate = ui_data.lock().compensate_settings;
+    let exposures: Vec<((String, String), f32)> = {
+        let images = images.lock();
+        let infos: Vec<&ImageInfo> = images.iter().map(|img| &img.info).collect();
+        lib::bracket_analysis::bracket_exposures(&infos, compensate)
+            .iter()
+            .zip(infos.iter())
+            .filter_map(|(exposure, info)| {
+                exposure.map(|e| ((info.full_filepath.clone(), info.filename.clone()), e))
+            })
+            .collect()
+    };
+    apply_exposures(images, ui_data, &exposures);
+}
+
+/// Finds the duplicate and outlier images, from their histograms.
+fn update_rejections(ui_data: &Shared<UIData>) {
+    let mut ui_data = ui_data.lock_mut();
+    let rejections = {
+        let images: Vec<(&[Histogram; 3], &ImageInfo)> = ui_data
+            .thumbnails
+            .iter()
+            .filter_map(|(_, _, _, info)| {
+                ui_data
+                    .histograms
+                    .iter()
+                    .find(|(key, _)| info.full_filepath == key.0 && info.filename == key.1)
+                    .map(|(_, histograms)| (histograms, info))
+            })
+            .collect();
+        if images.len() == ui_data.thumbnails.len() {
+            lib::bracket_analysis::find_rejected_images(&images)
+        } else {
+            Vec::new()
+        }
+    };
+    ui_data.rejections = rejections;
 }
 
 fn make_texture(img: (&[u8], usize, usize), ctx: &egui::Context) -> egui::TextureHandle {
@@ -4,7 +4,6 @@ pub fn menu_bar(
     ctx: &Context,
     app: &mut crate::AppMain,
     add_images_dialog: &rfd::FileDialog,
-    save_hdri_dialog: &rfd::FileDialog,
     have_hdri: bool,
     job_count: usize,
 ) {
@@ -16,6 +15,9 @@ pub fn menu_bar(
                     .clicked()
                 {
                     if let Some(paths) = add_images_dialog.clone().pick_files() {
+                        if let Some(dir) = paths.first().and_then(|p| p.parent()) {
+                            app.preferences.last_directory = Some(dir.into());
+                        }
                         app.add_image_files(paths, ctx);
                     }
                 }
@@ -27,11 +29,15 @@ pub fn menu_bar(
                     )
                     .clicked()
                 {
-                    if let Some(path) = save_hdri_dialog.clone().save_file() {
-                        app.save_hdri(path);
-                    }
+                    app.ui_data.lock_mut().show_save_window = true;
+                    ui.close_menu();
                 }
 
+                ui.separator();
+                ui.menu_button("Preferences", |ui| {
+                    app.preferences.ui(ui, crate::DEFAULT_WORKER_COUNT);
+                    lib::display_profile::profile_ui(ui, &mut app.preferences.display_profile);
+                });
                 ui.separator();
                 if ui.add(egui::widgets::Button::new("Quit")).clicked() {
                     ctx.send_viewport_cmd(egui::viewport::ViewportCommand::Close);
@@ -0,0 +1,268 @@
+//! The file formats that HDRIs can be saved in, and the window for
+//! choosing one along with its options.
+
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use image_fmt::{ExrCompression, ExrOptions, ImageBuf, WriteFormat, EXR_COMPRESSIONS};
+
+use crate::egui::{self, Context};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SaveFormat {
+    Hdr,
+    Exr,
+    Tiff,
+    Pfm,
+}
+
+pub const SAVE_FORMATS: &[SaveFormat] = &[
+    SaveFormat::Hdr,
+    SaveFormat::Exr,
+    SaveFormat::Tiff,
+    SaveFormat::Pfm,
+];
+
+impl SaveFormat {
+    pub fn ui_text(&self) -> &'static str {
+        match *self {
+            SaveFormat::Hdr => "Radiance HDR (.hdr)",
+            SaveFormat::Exr => "OpenEXR (.exr)",
+            SaveFormat::Tiff => "TIFF, 32-bit float (.tif)",
+            SaveFormat::Pfm => "Portable Float Map (.pfm)",
+        }
+    }
+
+    /// The file extensions of the format, preferred one first.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match *self {
+            SaveFormat::Hdr => &["hdr"],
+            SaveFormat::Exr => &["exr"],
+            SaveFormat::Tiff => &["tif", "tiff"],
+            SaveFormat::Pfm => &["pfm"],
+        }
+    }
+
+    /// The format with the path's extension, if any.
+    pub fn from_path(path: &Path) -> Option<SaveFormat> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        SAVE_FORMATS
+            .iter()
+            .copied()
+            .find(|format| format.extensions().contains(&&*ext))
+    }
+
+    /// Whether the format records metadata (camera, lens projection,
+    /// etc.).
+    pub fn has_metadata(&self) -> bool {
+        match *self {
+            SaveFormat::Hdr | SaveFormat::Exr => true,
+            SaveFormat::Tiff | SaveFormat::Pfm => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveOptions {
+    pub format: SaveFormat,
+    pub hdr_run_length_encoding: bool,
+    pub exr_half_float: bool,
+    pub exr_compression: ExrCompression,
+}
+
+impl SaveOptions {
+    pub fn new() -> SaveOptions {
+        SaveOptions {
+            format: SaveFormat::Hdr,
+            hdr_run_length_encoding: true,
+            exr_half_float: false,
+            exr_compression: ExrCompression::Zip,
+        }
+    }
+}
+
+/// Writes an HDRI in the format and with the options in `options`.
+///
+/// `hdr_options` has the exposure to apply, and the metadata that's
+/// recorded in formats that support it.
+pub fn write_hdri(
+    path: &Path,
+    pixels: &[[f32; 3]],
+    width: usize,
+    height: usize,
+    options: &SaveOptions,
+    hdr_options: &hdr::HdrOptions,
+) -> Result<(), String> {
+    let mut file = BufWriter::new(std::fs::File::create(path).map_err(|e| e.to_string())?);
+
+    if options.format == SaveFormat::Hdr {
+        hdr::write_hdr_with_options(
+            &mut file,
+            pixels,
+            width,
+            height,
+            &hdr::HdrOptions {
+                run_length_encoding: options.hdr_run_length_encoding,
+                ..hdr_options.clone()
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    } else {
+        let scale = hdr_options.exposure;
+        let image = image_fmt::Image {
+            dimensions: (width, height),
+            data: ImageBuf::RgbF32(
+                pixels
+                    .iter()
+                    .flat_map(|p| [p[0] * scale, p[1] * scale, p[2] * scale])
+                    .collect(),
+            ),
+        };
+        match options.format {
+            SaveFormat::Exr => image_fmt::write_exr(
+                &mut file,
+                &image,
+                &ExrOptions {
+                    half_float: options.exr_half_float,
+                    compression: options.exr_compression,
+                    attributes: exr_attributes(hdr_options),
+                },
+            ),
+            SaveFormat::Tiff => image_fmt::write(&mut file, &image, WriteFormat::Tiff),
+            SaveFormat::Pfm => image_fmt::write(&mut file, &image, WriteFormat::Pfm),
+            SaveFormat::Hdr => unreachable!(),
+        }
+        .map_err(|e| e.to_string())?;
+    }
+
+    file.flush().map_err(|e| e.to_string())
+}
+
+/// The same metadata as in .hdr headers, as EXR string attributes.
+fn exr_attributes(hdr_options: &hdr::HdrOptions) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    for (name, value) in [
+        ("software", &hdr_options.software),
+        ("camera", &hdr_options.camera),
+        ("projection", &hdr_options.projection),
+    ]
+    .iter()
+    {
+        if let Some(value) = value {
+            attributes.push((name.to_string(), value.clone()));
+        }
+    }
+    attributes
+}
+
+/// Window for choosing the format and options to save the HDRI with.
+pub fn save_window(ctx: &Context, app: &mut crate::AppMain, job_count: usize) {
+    if !app.ui_data.lock().show_save_window {
+        return;
+    }
+
+    let mut open = true;
+    let mut save = false;
+    egui::Window::new("Save HDRI")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let ui_data = &mut *app.ui_data.lock_mut();
+            let options = &mut ui_data.save_options;
+
+            egui::Grid::new("save_hdri_options")
+                .min_col_width(4.0)
+                .show(ui, |ui| {
+                    ui.label("Format: ");
+                    egui::ComboBox::from_id_source("save_hdri_format")
+                        .width(200.0)
+                        .selected_text(options.format.ui_text())
+                        .show_ui(ui, |ui| {
+                            for format in SAVE_FORMATS.iter() {
+                                ui.selectable_value(&mut options.format, *format, format.ui_text());
+                            }
+                        });
+                    ui.end_row();
+
+                    match options.format {
+                        SaveFormat::Hdr => {
+                            ui.label("");
+                            ui.checkbox(&mut options.hdr_run_length_encoding, "Run-length encoding")
+                                .on_hover_text("Makes the file smaller.  Nearly all software supports it.");
+                            ui.end_row();
+                        }
+                        SaveFormat::Exr => {
+                            ui.label("Precision: ");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut options.exr_half_float, true, "Half (16-bit)");
+                                ui.radio_value(&mut options.exr_half_float, false, "Full (32-bit)");
+                            })
+                            .response
+                            .on_hover_text("Half floats are half the size, with a range of about 40 stops and precision that's ample for most HDRIs.");
+                            ui.end_row();
+
+                            ui.label("Compression: ");
+                            egui::ComboBox::from_id_source("save_hdri_exr_compression")
+                                .width(200.0)
+                                .selected_text(options.exr_compression.ui_text())
+                                .show_ui(ui, |ui| {
+                                    for compression in EXR_COMPRESSIONS.iter() {
+                                        ui.selectable_value(
+                                            &mut options.exr_compression,
+                                            *compression,
+                                            compression.ui_text(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+                        }
+                        SaveFormat::Tiff | SaveFormat::Pfm => {}
+                    }
+                });
+            if !options.format.has_metadata() {
+                ui.label("This format doesn't record metadata such as the camera and lens projection.");
+            }
+            ui.add_space(8.0);
+
+            ui.checkbox(
+                &mut ui_data.save_with_preview_exposure,
+                "Save with preview exposure",
+            );
+            ui.add_space(8.0);
+
+            if ui
+                .add_enabled(job_count == 0, egui::widgets::Button::new("Save..."))
+                .clicked()
+            {
+                save = true;
+            }
+        });
+
+    if save {
+        let format = app.ui_data.lock().save_options.format;
+        let mut dialog = rfd::FileDialog::new()
+            .set_title("Save HDRI")
+            .add_filter(format.ui_text(), format.extensions());
+        if let Some(dir) = &app.preferences.last_directory {
+            dialog = dialog.set_directory(dir);
+        }
+        if let Some(mut path) = dialog.save_file() {
+            if SaveFormat::from_path(&path) != Some(format) {
+                let mut name = path.into_os_string();
+                name.push(".");
+                name.push(format.extensions()[0]);
+                path = PathBuf::from(name);
+            }
+            if let Some(dir) = path.parent() {
+                app.preferences.last_directory = Some(dir.into());
+            }
+            app.save_hdri(path);
+            open = false;
+        }
+    }
+
+    app.ui_data.lock_mut().show_save_window = open;
+}
@@ -0,0 +1,225 @@
+//! Statistics of a merged HDRI, for validating captures and documenting
+//! the resulting assets.
+
+use crate::egui::{self, Ui};
+
+/// Resolution of the luminance histogram.
+const BINS_PER_STOP: f32 = 8.0;
+
+/// The fraction of pixels ignored at each end of the luminance range
+/// when measuring dynamic range, so that a handful of hot or dead
+/// pixels don't dominate it.
+const OUTLIER_FRACTION: f32 = 0.0001;
+
+#[derive(Debug, Clone)]
+pub struct HdriStats {
+    pub channel_min: [f32; 3],
+    pub channel_max: [f32; 3],
+
+    // Histogram of log2 luminance, with `BINS_PER_STOP` bins per stop
+    // starting at `histogram_start` stops.  Pixels without a positive
+    // luminance aren't included, and are counted in `black_count`
+    // instead.
+    histogram: Vec<usize>,
+    histogram_start: f32,
+    black_count: usize,
+
+    // The log2 luminance of the darkest and brightest pixels, excluding
+    // outliers.
+    stops_range: (f32, f32),
+}
+
+impl HdriStats {
+    pub fn compute(pixels: &[[f32; 3]]) -> HdriStats {
+        let mut channel_min = [f32::INFINITY; 3];
+        let mut channel_max = [f32::NEG_INFINITY; 3];
+        let mut stops_min = f32::INFINITY;
+        let mut stops_max = f32::NEG_INFINITY;
+        for pixel in pixels.iter() {
+            for chan in 0..3 {
+                channel_min[chan] = channel_min[chan].min(pixel[chan]);
+                channel_max[chan] = channel_max[chan].max(pixel[chan]);
+            }
+            let lum = luminance(*pixel);
+            if lum > 0.0 {
+                stops_min = stops_min.min(lum.log2());
+                stops_max = stops_max.max(lum.log2());
+            }
+        }
+        if pixels.is_empty() {
+            channel_min = [0.0; 3];
+            channel_max = [0.0; 3];
+        }
+
+        // Build the histogram.
+        let mut histogram = Vec::new();
+        let mut histogram_start = 0.0;
+        let mut black_count = 0;
+        if stops_min <= stops_max {
+            let first_bin = (stops_min * BINS_PER_STOP).floor();
+            let bin_count = (stops_max * BINS_PER_STOP).floor() - first_bin + 1.0;
+            histogram = vec![0; bin_count as usize];
+            histogram_start = first_bin / BINS_PER_STOP;
+            for pixel in pixels.iter() {
+                let lum = luminance(*pixel);
+                if lum > 0.0 {
+                    let bin = (lum.log2() * BINS_PER_STOP).floor() - first_bin;
+                    histogram[(bin.max(0.0) as usize).min(bin_count as usize - 1)] += 1;
+                } else {
+                    black_count += 1;
+                }
+            }
+        } else {
+            black_count = pixels.len();
+        }
+
+        // Find the range without outliers, to the resolution of the
+        // histogram.
+        let lit_count = pixels.len() - black_count;
+        let outlier_count = (lit_count as f32 * OUTLIER_FRACTION) as usize;
+        let bin_width = 1.0 / BINS_PER_STOP;
+        let mut stops_range = (0.0, 0.0);
+        if lit_count > 0 {
+            let mut low = histogram.len() - 1;
+            let mut total = 0;
+            for (i, count) in histogram.iter().enumerate() {
+                total += count;
+                if total > outlier_count {
+                    low = i;
+                    break;
+                }
+            }
+            let mut high = 0;
+            let mut total = 0;
+            for (i, count) in histogram.iter().enumerate().rev() {
+                total += count;
+                if total > outlier_count {
+                    high = i;
+                    break;
+                }
+            }
+            stops_range = (
+                (histogram_start + low as f32 * bin_width).max(stops_min),
+                (histogram_start + (high + 1) as f32 * bin_width).min(stops_max),
+            );
+        }
+
+        HdriStats {
+            channel_min: channel_min,
+            channel_max: channel_max,
+            histogram: histogram,
+            histogram_start: histogram_start,
+            black_count: black_count,
+            stops_range: stops_range,
+        }
+    }
+
+    /// The measured dynamic range, in stops.
+    pub fn dynamic_range(&self) -> f32 {
+        (self.stops_range.1 - self.stops_range.0).max(0.0)
+    }
+
+    /// A plain-text summary, for printing or copying into an asset's
+    /// documentation.
+    pub fn summary(&self) -> String {
+        let mut text = format!("Dynamic range: {:.1} stops\n", self.dynamic_range());
+        text.push_str(&format!(
+            "Luminance: {:.4e} to {:.4e}\n",
+            self.stops_range.0.exp2(),
+            self.stops_range.1.exp2()
+        ));
+        for (chan, name) in ["Red", "Green", "Blue"].iter().enumerate() {
+            text.push_str(&format!(
+                "{}: {:.4e} to {:.4e}\n",
+                name, self.channel_min[chan], self.channel_max[chan]
+            ));
+        }
+        if self.black_count > 0 {
+            text.push_str(&format!("Black pixels: {}\n", self.black_count));
+        }
+        text
+    }
+}
+
+/// Rec.709 luminance.  The HDRI is in the camera's color space, so this
+/// is only approximate, but it's close enough for measuring range.
+fn luminance(pixel: [f32; 3]) -> f32 {
+    pixel[0] * 0.2126 + pixel[1] * 0.7152 + pixel[2] * 0.0722
+}
+
+pub fn stats_panel(ui: &mut Ui, stats: &HdriStats) {
+    use egui_plot::{Bar, BarChart, Plot, VLine};
+
+    ui.horizontal(|ui| {
+        ui.strong("HDRI Statistics");
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui
+                .button("Copy")
+                .on_hover_text("Copy the statistics as text.")
+                .clicked()
+            {
+                let text = stats.summary();
+                ui.output_mut(|output| output.copied_text = text);
+            }
+        });
+    });
+    ui.add_space(4.0);
+
+    egui::Grid::new("hdri_stats_grid")
+        .min_col_width(4.0)
+        .show(ui, |ui| {
+            ui.label("Dynamic range: ");
+            ui.label(format!("{:.1} stops", stats.dynamic_range()))
+                .on_hover_text("Between the darkest and brightest pixels, ignoring the most extreme 0.01% at each end.");
+            ui.end_row();
+
+            ui.label("Luminance: ");
+            ui.label(format!(
+                "{:.3e} to {:.3e}",
+                stats.stops_range.0.exp2(),
+                stats.stops_range.1.exp2()
+            ));
+            ui.end_row();
+
+            for (chan, name) in ["Red", "Green", "Blue"].iter().enumerate() {
+                ui.label(format!("{}: ", name));
+                ui.label(format!(
+                    "{:.3e} to {:.3e}",
+                    stats.channel_min[chan], stats.channel_max[chan]
+                ));
+                ui.end_row();
+            }
+
+            if stats.black_count > 0 {
+                ui.label("Black pixels: ");
+                ui.label(format!("{}", stats.black_count));
+                ui.end_row();
+            }
+        });
+    ui.add_space(8.0);
+
+    // Log-luminance histogram.
+    let bin_width = 1.0 / BINS_PER_STOP;
+    let max_count = stats.histogram.iter().copied().max().unwrap_or(0).max(1);
+    let bars = stats
+        .histogram
+        .iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let stops = stats.histogram_start + (i as f32 + 0.5) * bin_width;
+            Bar::new(stops as f64, *count as f64 / max_count as f64).width(bin_width as f64)
+        })
+        .collect();
+    Plot::new("hdri_stats_histogram")
+        .height(160.0)
+        .x_axis_label("Luminance (stops)")
+        .show_axes([true, false])
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .show(ui, |plot| {
+            plot.bar_chart(BarChart::new(bars).color(lib::colors::GRAY));
+            plot.vline(VLine::new(stats.stops_range.0 as f64).color(lib::colors::YELLOW));
+            plot.vline(VLine::new(stats.stops_range.1 as f64).color(lib::colors::YELLOW));
+        });
+}
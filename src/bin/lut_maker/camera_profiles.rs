@@ -0,0 +1,168 @@
+use sensor_analysis::ExposureMapping;
+
+use crate::egui::{self, Ui};
+use crate::{
+    estimated_tf::EstimatedTF, exposure_calibration::ExposureCalibration,
+    generated_tf::GeneratedTF, image_list::ImageList, modified_tf::ModifiedTF,
+};
+
+/// A set of named camera profiles, each with its own images and
+/// transfer function data.
+///
+/// The data of the active profile lives directly in `AppMain`, and
+/// is swapped in and out when switching profiles.  That way the rest
+/// of the app doesn't need to know about profiles at all.
+pub struct CameraProfiles {
+    active: usize,
+    names: Vec<String>,
+    stored: Vec<Option<StoredProfile>>, // `None` for the active profile.
+}
+
+impl CameraProfiles {
+    pub fn new() -> CameraProfiles {
+        CameraProfiles {
+            active: 0,
+            names: vec!["Camera 1".into()],
+            stored: vec![None],
+        }
+    }
+
+    pub fn active_name(&self) -> &str {
+        &self.names[self.active]
+    }
+
+    pub fn active_name_mut(&mut self) -> &mut String {
+        &mut self.names[self.active]
+    }
+}
+
+/// The per-camera data of an inactive profile.
+struct StoredProfile {
+    bracket_image_sets: ImageList,
+    dark_images: ImageList,
+    transfer_function_tables: Option<([Vec<f32>; 3], f32, f32)>,
+
+    generated: GeneratedTF,
+    estimated: EstimatedTF,
+    modified: ModifiedTF,
+    calibration: ExposureCalibration,
+    exposure_mappings: [Vec<ExposureMapping>; 3],
+}
+
+impl StoredProfile {
+    fn new() -> StoredProfile {
+        let bracket_image_sets = ImageList::new(true, true);
+        let mut dark_images = ImageList::new(false, false);
+        dark_images.share_float_encoding(&bracket_image_sets);
+
+        StoredProfile {
+            bracket_image_sets: bracket_image_sets,
+            dark_images: dark_images,
+            transfer_function_tables: None,
+
+            generated: GeneratedTF::new(),
+            estimated: EstimatedTF::new(),
+            modified: ModifiedTF::new(),
+            calibration: ExposureCalibration::new(),
+            exposure_mappings: [Vec::new(), Vec::new(), Vec::new()],
+        }
+    }
+
+    fn swap_with_app(&mut self, app: &mut crate::AppMain) {
+        use std::mem::swap;
+
+        swap(&mut self.bracket_image_sets, &mut app.bracket_image_sets);
+        swap(&mut self.dark_images, &mut app.dark_images);
+        swap(
+            &mut self.transfer_function_tables,
+            &mut *app.transfer_function_tables.lock_mut(),
+        );
+
+        let mut ui_data = app.ui_data.lock_mut();
+        swap(&mut self.generated, &mut ui_data.generated);
+        swap(&mut self.estimated, &mut ui_data.estimated);
+        swap(&mut self.modified, &mut ui_data.modified);
+        swap(&mut self.calibration, &mut ui_data.calibration);
+        swap(&mut self.exposure_mappings, &mut ui_data.exposure_mappings);
+    }
+}
+
+fn switch_to(app: &mut crate::AppMain, index: usize) {
+    let active = app.camera_profiles.active;
+    if index == active {
+        return;
+    }
+
+    let mut profile = app.camera_profiles.stored[index].take().unwrap();
+    profile.swap_with_app(app);
+    app.camera_profiles.stored[active] = Some(profile);
+    app.camera_profiles.active = index;
+
+    // The history refers to the previous profile's data.
+    app.undo_stack.clear();
+}
+
+/// Profile selector, with buttons to add, rename, and remove profiles.
+///
+/// Profiles can't be changed while jobs are running, since running
+/// jobs write their results into the active profile's data.
+pub fn profile_ui(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize) {
+    let enabled = job_count == 0;
+
+    ui.horizontal(|ui| {
+        let mut selected = app.camera_profiles.active;
+        ui.add_enabled_ui(enabled, |ui| {
+            egui::ComboBox::from_id_source("Camera Profile Selector")
+                .width(140.0)
+                .selected_text(app.camera_profiles.active_name())
+                .show_ui(ui, |ui| {
+                    for (i, name) in app.camera_profiles.names.iter().enumerate() {
+                        ui.selectable_value(&mut selected, i, name);
+                    }
+                });
+        });
+        if selected != app.camera_profiles.active {
+            switch_to(app, selected);
+        }
+
+        if ui
+            .add_enabled(enabled, egui::widgets::Button::new("+"))
+            .on_hover_text("Add a new camera profile")
+            .clicked()
+        {
+            let profiles = &mut app.camera_profiles;
+            profiles
+                .names
+                .push(format!("Camera {}", profiles.names.len() + 1));
+            profiles.stored.push(Some(StoredProfile::new()));
+            let index = profiles.names.len() - 1;
+            switch_to(app, index);
+        }
+
+        if ui
+            .add_enabled(
+                enabled && app.camera_profiles.names.len() > 1,
+                egui::widgets::Button::new("−"),
+            )
+            .on_hover_text("Remove the current camera profile")
+            .clicked()
+        {
+            let removed = app.camera_profiles.active;
+            switch_to(app, if removed == 0 { 1 } else { removed - 1 });
+            app.camera_profiles.names.remove(removed);
+            app.camera_profiles.stored.remove(removed);
+            if app.camera_profiles.active > removed {
+                app.camera_profiles.active -= 1;
+            }
+        }
+    });
+
+    ui.add_enabled(
+        enabled,
+        egui::widgets::TextEdit::singleline(
+            &mut app.camera_profiles.names[app.camera_profiles.active],
+        )
+        .desired_width(200.0),
+    )
+    .on_hover_text("Profile name");
+}
@@ -0,0 +1,612 @@
+//! Headless LUT estimation and generation, for scripted camera
+//! profiling pipelines.
+
+use std::path::{Path, PathBuf};
+
+use sensor_analysis::{emor, ExposureMapping, Histogram};
+
+use lib::bracket_analysis::{
+    bracket_floor_ceiling, bracket_images_to_histogram_sets, compensate_bracket_set, dark_floor,
+    exposure_mappings, image_sets_are_monochrome, load_image_set, reject_bracket_images,
+};
+use lib::chroma_space::ChromaSpace;
+use lib::formula::Formula;
+use lib::lut_formats::{LutMetadata, Quantization};
+use ocio_gen::lut_extrapolation::Extrapolation;
+
+use crate::{
+    estimated_tf::{EstimatedTF, MAX_ESTIMATION_ROUNDS},
+    generated_tf::{HdrIntent, TransferFunction, TransferFunctionID, TRANSFER_FUNCTION_IDS},
+    lut_pair, ExportFormat, ExportOptions, EXPORT_FORMATS, VERSION,
+};
+
+/// Adds the command line arguments for headless mode.
+pub fn add_args<'a, 'b>(app: clap::App<'a, 'b>) -> clap::App<'a, 'b> {
+    use clap::Arg;
+
+    app.arg(
+        Arg::with_name("cli")
+            .long("cli")
+            .help("Runs without the GUI, estimating or generating a LUT pair from the given images"),
+    )
+    .arg(
+        Arg::with_name("brackets")
+            .long("brackets")
+            .value_name("DIR")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("A directory of bracketed exposure images, used as one image set.  Can be given multiple times"),
+    )
+    .arg(
+        Arg::with_name("reject_frames")
+            .long("reject-frames")
+            .help("Excludes bracketed images that duplicate another image of their set, or whose brightness doesn't fit their exposure (e.g. the lighting changed).  Without this they're only warned about"),
+    )
+    .arg(
+        Arg::with_name("dark")
+            .long("dark")
+            .value_name("DIR")
+            .takes_value(true)
+            .help("A directory of lens cap (dark) images, for estimating the sensor noise floor"),
+    )
+    .arg(
+        Arg::with_name("convergence")
+            .long("convergence")
+            .value_name("FRACTION")
+            .takes_value(true)
+            .default_value("0.001")
+            .help("Transfer function estimation stops once the error improves by less than this fraction over a thousand rounds"),
+    )
+    .arg(
+        Arg::with_name("smoothness")
+            .long("smoothness")
+            .value_name("AMOUNT")
+            .takes_value(true)
+            .default_value("0")
+            .help("Smoothness regularization of the estimated curve"),
+    )
+    .arg(
+        Arg::with_name("shared_curve")
+            .long("shared-curve")
+            .help("Estimates a single curve for all channels, with per-channel gain"),
+    )
+    .arg(
+        Arg::with_name("detect_knee")
+            .long("detect-knee")
+            .help("Looks for a knee in the estimated curve, as produced by dual gain sensors, and fits a piecewise curve if one is found"),
+    )
+    .arg(
+        Arg::with_name("generate")
+            .long("generate")
+            .value_name("FUNCTION")
+            .takes_value(true)
+            .help("Generates a LUT pair for a built-in transfer function (e.g. \"slog3\") instead of estimating one.  Any given images are used to estimate the floor and ceiling"),
+    )
+    .arg(
+        Arg::with_name("formula")
+            .long("formula")
+            .value_name("EXPRESSION")
+            .takes_value(true)
+            .conflicts_with("generate")
+            .help("Like --generate, but for a custom encoded-to-linear formula in terms of x, e.g. \"x^2.4\" or \"if(x < 0.1, x / 4.5, ((x + 0.099) / 1.099)^(1 / 0.45))\""),
+    )
+    .arg(
+        Arg::with_name("hdr_intent")
+            .long("hdr-intent")
+            .value_name("INTENT")
+            .takes_value(true)
+            .possible_values(&["scene", "display"])
+            .help("Whether generated HLG and PQ LUTs decode to scene light or display light, i.e. whether the Rec.2100 OOTF is included.  Defaults to scene light for HLG and display light for PQ"),
+    )
+    .arg(
+        Arg::with_name("peak_luminance")
+            .long("peak-luminance")
+            .value_name("CD/M2")
+            .takes_value(true)
+            .default_value("1000")
+            .help("Nominal peak luminance of the display for generated HLG LUTs in display light, which sets the OOTF's system gamma"),
+    )
+    .arg(
+        Arg::with_name("format")
+            .long("format")
+            .value_name("FORMAT")
+            .takes_value(true)
+            .possible_values(&["cube", "spi1d", "clf", "csp", "3dl", "vlt", "sony-cube", "icc"])
+            .default_value("cube")
+            .help("Output LUT format"),
+    )
+    .arg(
+        Arg::with_name("resolution")
+            .long("resolution")
+            .value_name("N")
+            .takes_value(true)
+            .default_value("0")
+            .help("Table length of 1D LUTs in the cube, clf, and csp formats (e.g. 4096 for a 12-bit domain).  Zero keeps the native resolution"),
+    )
+    .arg(
+        Arg::with_name("quantize")
+            .long("quantize")
+            .value_name("PRECISION")
+            .takes_value(true)
+            .possible_values(&["none", "half", "8", "10", "12", "16"])
+            .default_value("none")
+            .help("Rounds the LUT's output values to half floats or to integer code values of the given bit depth, for cameras and monitors that load LUTs at a fixed precision"),
+    )
+    .arg(
+        Arg::with_name("no_dither")
+            .long("no-dither")
+            .help("Disables dithering of quantized 1D tables"),
+    )
+    .arg(
+        Arg::with_name("icc_primaries")
+            .long("icc-primaries")
+            .value_name("GAMUT")
+            .takes_value(true)
+            .default_value("Rec709")
+            .help("Primaries of the linear color space of icc profiles, by name (e.g. Rec709, Rec2020, or AcesAP1)"),
+    )
+    .arg(
+        Arg::with_name("extrapolation")
+            .long("extrapolation")
+            .value_name("POLICY")
+            .takes_value(true)
+            .possible_values(&["clamp", "linear", "slope"])
+            .default_value("clamp")
+            .help("What 1D LUTs give for input outside of their range: clamp to the ends of the table, extend the end segments as straight lines, or extend with the table's average slope.  Other than clamp, the tables are extended past their range"),
+    )
+    .arg(
+        Arg::with_name("name")
+            .long("name")
+            .value_name("CAMERA")
+            .takes_value(true)
+            .default_value("Camera")
+            .help("Camera name, used for the output file names"),
+    )
+    .arg(
+        Arg::with_name("output")
+            .long("output")
+            .short("o")
+            .value_name("DIR")
+            .takes_value(true)
+            .help("Directory to write the LUTs to"),
+    )
+    .arg(
+        Arg::with_name("report")
+            .long("report")
+            .value_name("FILE")
+            .takes_value(true)
+            .help("Also writes the text report to the given file"),
+    )
+}
+
+/// Runs headless mode, returning the process exit code.
+pub fn run(args: &clap::ArgMatches) -> i32 {
+    let mut report = Vec::new();
+    let result = run_with_report(args, &mut report);
+
+    if let Some(path) = args.value_of("report") {
+        if let Err(e) = std::fs::write(path, report.join("\n") + "\n") {
+            eprintln!("Error: unable to write report \"{}\": {}", path, e);
+            return 1;
+        }
+    }
+
+    match result {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            1
+        }
+    }
+}
+
+fn run_with_report(args: &clap::ArgMatches, report: &mut Vec<String>) -> Result<(), String> {
+    let mut note = |line: String| {
+        println!("{}", line);
+        report.push(line);
+    };
+
+    // Options.
+    let output_dir = PathBuf::from(
+        args.value_of("output")
+            .ok_or("an output directory must be given with --output")?,
+    );
+    let camera_name = args.value_of("name").unwrap();
+    let format = {
+        let id = args.value_of("format").unwrap();
+        *EXPORT_FORMATS.iter().find(|f| f.id() == id).unwrap()
+    };
+    let mut export_options = ExportOptions::new();
+    export_options.resolution = args
+        .value_of("resolution")
+        .unwrap()
+        .parse()
+        .map_err(|_| "--resolution must be a whole number")?;
+    export_options.quantization = match args.value_of("quantize").unwrap() {
+        "none" => Quantization::None,
+        "half" => Quantization::Half,
+        bits => Quantization::Integer(bits.parse().unwrap()),
+    };
+    export_options.dither = !args.is_present("no_dither");
+    export_options.extrapolation =
+        Extrapolation::from_str(args.value_of("extrapolation").unwrap()).unwrap();
+    export_options.icc_primaries = ChromaSpace::from_str(args.value_of("icc_primaries").unwrap())
+        .filter(|space| !matches!(space, ChromaSpace::None | ChromaSpace::Custom))
+        .ok_or("unknown --icc-primaries gamut")?;
+    let convergence: f32 = args
+        .value_of("convergence")
+        .unwrap()
+        .parse()
+        .ok()
+        .filter(|n: &f32| *n > 0.0)
+        .ok_or("--convergence must be a positive number")?;
+    let smoothness: f32 = args
+        .value_of("smoothness")
+        .unwrap()
+        .parse()
+        .map_err(|_| "--smoothness must be a number")?;
+    let generate = match (args.value_of("generate"), args.value_of("formula")) {
+        (Some(name), _) => Some(parse_transfer_function(name)?),
+        (None, Some(text)) => Some(TransferFunction {
+            id: TransferFunctionID::Custom,
+            custom_formula: Formula::parse(text).map_err(|e| format!("--formula: {}", e))?,
+            ..TransferFunction::default()
+        }),
+        (None, None) => None,
+    };
+    let generate = match generate {
+        Some(mut function) => {
+            match args.value_of("hdr_intent") {
+                Some("scene") => {
+                    function.hlg_intent = HdrIntent::Scene;
+                    function.pq_intent = HdrIntent::Scene;
+                }
+                Some("display") => {
+                    function.hlg_intent = HdrIntent::Display;
+                    function.pq_intent = HdrIntent::Display;
+                }
+                _ => {}
+            }
+            function.hlg_peak_luminance = args
+                .value_of("peak_luminance")
+                .unwrap()
+                .parse()
+                .ok()
+                .filter(|n: &u32| *n > 0)
+                .ok_or("--peak-luminance must be a positive whole number")?;
+            Some(function)
+        }
+        None => None,
+    };
+
+    note(format!("ETF LUT Maker v{}", VERSION));
+    note(format!("Camera: {}", camera_name));
+
+    // Load images.  Float images all share the encoding of the first
+    // one loaded, so that their histograms are comparable.
+    let mut float_encoding = None;
+    let mut bracket_sets = Vec::new();
+    for dir in args.values_of("brackets").into_iter().flatten() {
+        let mut set = load_image_set(Path::new(dir), &mut float_encoding, &mut note)?;
+        compensate_bracket_set(&mut set, &mut note);
+        reject_bracket_images(&mut set, args.is_present("reject_frames"), &mut note);
+        note(format!(
+            "Bracketed image set \"{}\": {} images",
+            dir,
+            set.len()
+        ));
+        bracket_sets.push(set);
+    }
+    let dark_images = match args.value_of("dark") {
+        Some(dir) => {
+            let set = load_image_set(Path::new(dir), &mut float_encoding, &mut note)?;
+            note(format!("Dark images \"{}\": {} images", dir, set.len()));
+            set
+        }
+        None => Vec::new(),
+    };
+    let histogram_sets = bracket_images_to_histogram_sets(&bracket_sets);
+    let have_brackets = histogram_sets
+        .iter()
+        .any(|hs| hs.iter().all(|h| h.len() >= 2));
+    if generate.is_none() && !have_brackets {
+        return Err(
+            "estimation needs at least two bracketed images with exposure Exif data".into(),
+        );
+    }
+
+    // Floor and ceiling.
+    let to_linear = |n: f32| generate.as_ref().map(|f| f.to_linear(n)).unwrap_or(n);
+    let from_linear = |n: f32| generate.as_ref().map(|f| f.from_linear(n)).unwrap_or(n);
+    let (bracket_floor, bracket_ceiling) = bracket_floor_ceiling(&histogram_sets);
+    let floor = if !dark_images.is_empty() {
+        Some(dark_floor(&dark_images, to_linear, from_linear))
+    } else if have_brackets {
+        Some(bracket_floor)
+    } else {
+        None
+    };
+    let ceiling = if have_brackets {
+        Some(bracket_ceiling)
+    } else {
+        None
+    };
+    if let Some(floor) = floor {
+        note(format!(
+            "Sensor floor: [{:.5}, {:.5}, {:.5}]",
+            floor[0], floor[1], floor[2]
+        ));
+    }
+    if let Some(ceiling) = ceiling {
+        note(format!(
+            "Sensor ceiling: [{:.5}, {:.5}, {:.5}]",
+            ceiling[0], ceiling[1], ceiling[2]
+        ));
+    }
+
+    // Build the to-linear LUT.
+    let to_linear_lut = if let Some(ref function) = generate {
+        if function.id == TransferFunctionID::Custom {
+            note(format!(
+                "Generated from: {}",
+                function.custom_formula.text()
+            ));
+        } else {
+            note(format!("Generated from: {}", function.id.ui_text()));
+        }
+        if function.id == TransferFunctionID::HLG && function.hlg_intent == HdrIntent::Display {
+            note(format!(
+                "HLG display light, nominal peak {} cd/m² (system gamma {:.3})",
+                function.hlg_peak_luminance,
+                function.hlg_system_gamma()
+            ));
+        } else if function.id == TransferFunctionID::PQ && function.pq_intent == HdrIntent::Scene {
+            note("PQ decoded to scene light (inverse OOTF)".into());
+        }
+        generated_lut(function, floor, ceiling)
+    } else {
+        let mut estimated = EstimatedTF::new();
+        estimated.convergence = convergence;
+        estimated.smoothness = smoothness;
+        estimated.shared_curve = args.is_present("shared_curve");
+        estimated.detect_knee = args.is_present("detect_knee");
+        estimated.sensor_floor = floor.unwrap_or([0.0; 3]);
+        estimated.sensor_ceiling = ceiling.unwrap_or([1.0; 3]);
+        estimated.monochrome = image_sets_are_monochrome(&bracket_sets);
+        if estimated.monochrome {
+            note("Monochrome images: estimating a single channel".into());
+        }
+        estimated_lut(&estimated, &histogram_sets, &mut note)?
+    };
+    let from_linear_lut = to_linear_lut.resample_inverted(4096);
+
+    // Write the LUTs.
+    std::fs::create_dir_all(&output_dir).map_err(|e| {
+        format!(
+            "unable to create output directory \"{}\": {}",
+            output_dir.to_string_lossy(),
+            e
+        )
+    })?;
+    let (to_linear_name, from_linear_name) = lut_pair::file_names(camera_name, format.ext());
+    let luts = vec![
+        (to_linear_lut, true, &to_linear_name, &from_linear_name),
+        (from_linear_lut, false, &from_linear_name, &to_linear_name),
+    ];
+    for (lut, is_to_linear, name, pair_name) in luts.into_iter() {
+        let path = output_dir.join(name);
+        let metadata = LutMetadata {
+            name: String::new(),
+            software: format!("ETF LUT Maker v{}", VERSION),
+            camera: Some(camera_name.to_string()),
+            notes: vec![
+                format!(
+                    "Transform: {}",
+                    if is_to_linear {
+                        "to linear"
+                    } else {
+                        "from linear"
+                    }
+                ),
+                format!(
+                    "Mode: {}",
+                    if generate.is_some() {
+                        "Generate"
+                    } else {
+                        "Estimate"
+                    }
+                ),
+                format!("Inverse: {}", pair_name),
+            ],
+        };
+        let warnings = crate::write_lut_file(
+            &path,
+            lut,
+            format,
+            export_options,
+            None,
+            is_to_linear,
+            &metadata,
+        )
+        .map_err(|e| format!("unable to write \"{}\": {}", path.to_string_lossy(), e))?;
+        for warning in warnings {
+            note(format!("Warning: {}", warning));
+        }
+        note(format!("Wrote: {}", path.to_string_lossy()));
+    }
+
+    Ok(())
+}
+
+/// Looks up a built-in transfer function by a simplified name, e.g.
+/// "srgb" or "sonyslog3".
+fn parse_transfer_function(name: &str) -> Result<TransferFunction, String> {
+    let simplify = |text: &str| -> String {
+        text.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(|c| c.to_lowercase())
+            .collect()
+    };
+
+    let mut function = TransferFunction::default();
+    match TRANSFER_FUNCTION_IDS
+        .iter()
+        .find(|id| simplify(&format!("{:?}", id)) == simplify(name))
+    {
+        Some(id) => {
+            function.id = *id;
+            Ok(function)
+        }
+        None => {
+            let names: Vec<String> = TRANSFER_FUNCTION_IDS
+                .iter()
+                .map(|id| simplify(&format!("{:?}", id)))
+                .collect();
+            Err(format!(
+                "unknown transfer function \"{}\".  Available functions: {}",
+                name,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+fn generated_lut(
+    function: &TransferFunction,
+    floor: Option<[f32; 3]>,
+    ceiling: Option<[f32; 3]>,
+) -> colorbox::lut::Lut1D {
+    const RESOLUTION: usize = 4096;
+    let norm = 1.0 / (RESOLUTION - 1) as f32;
+    colorbox::lut::Lut1D {
+        ranges: vec![(0.0, 1.0)],
+        tables: (0..3)
+            .map(|chan| {
+                (0..RESOLUTION)
+                    .map(|i| {
+                        function.to_linear_fc(
+                            i as f32 * norm,
+                            floor.map(|f| f[chan]),
+                            ceiling.map(|c| c[chan]),
+                            false,
+                        )
+                    })
+                    .collect()
+            })
+            .collect(),
+    }
+}
+
+fn estimated_lut<F: FnMut(String)>(
+    estimated: &EstimatedTF,
+    histogram_sets: &[[Vec<(Histogram, f32)>; 3]],
+    note: &mut F,
+) -> Result<colorbox::lut::Lut1D, String> {
+    let channel_mappings = exposure_mappings(
+        histogram_sets,
+        estimated.sensor_floor,
+        estimated.sensor_ceiling,
+    );
+    // The channels of monochrome images are all the same, so only the
+    // first is used.
+    let mappings: Vec<ExposureMapping> = channel_mappings
+        .iter()
+        .take(if estimated.monochrome { 1 } else { 3 })
+        .flatten()
+        .cloned()
+        .collect();
+    if mappings.is_empty() {
+        return Err("not enough valid images to estimate the transfer function".into());
+    }
+
+    // Estimate until the estimate converges, reporting progress on
+    // stderr.  When detecting a knee, estimation continues with it once
+    // it's found, until it converges again.
+    let mut estimator = emor::EmorEstimator::new(&mappings).with_smoothness(estimated.smoothness);
+    let mut knee = None;
+    while estimator.rounds_done() < MAX_ESTIMATION_ROUNDS {
+        eprint!(
+            "\rEstimating transfer function, round {}, error {:.6}",
+            estimator.rounds_done(),
+            estimator.current_estimate().1
+        );
+        estimator.do_rounds_for(
+            MAX_ESTIMATION_ROUNDS - estimator.rounds_done(),
+            std::time::Duration::from_millis(100),
+        );
+        if !estimator.has_converged(estimated.convergence) {
+            continue;
+        }
+
+        if estimated.detect_knee && knee.is_none() {
+            let (inv_emor_factors, _) = estimator.current_estimate();
+            if let Some((found, _)) = emor::find_knee(&mappings, &inv_emor_factors) {
+                knee = Some(found);
+                estimator.set_knee(knee);
+                continue;
+            }
+        }
+        break;
+    }
+    eprintln!();
+    if !estimator.has_converged(estimated.convergence) {
+        note(format!(
+            "The estimate didn't converge within {} rounds",
+            MAX_ESTIMATION_ROUNDS
+        ));
+    }
+    if estimated.detect_knee {
+        note(match knee {
+            Some(knee) => format!(
+                "Knee at encoded value {:.4}, with the to-linear slope multiplied by {:.3} above it",
+                knee.position, knee.slope,
+            ),
+            None => "No knee found".into(),
+        });
+    }
+
+    let (inv_emor_factors, err) = estimator.current_estimate();
+    let mut curve = emor::inv_emor_factors_to_curve(&inv_emor_factors, 0.0, 1.0);
+    if let Some(knee) = knee {
+        knee.apply(&mut curve);
+    }
+    let tables = [curve.clone(), curve.clone(), curve];
+    note(format!("Average estimation error: {}", err));
+
+    if !estimated.monochrome {
+        let divergence = emor::channel_errors(
+            &[
+                &channel_mappings[0][..],
+                &channel_mappings[1][..],
+                &channel_mappings[2][..],
+            ],
+            &inv_emor_factors,
+            knee,
+        );
+        note(format!(
+            "Per-channel divergence: R {:.4}, G {:.4}, B {:.4}",
+            divergence[0], divergence[1], divergence[2],
+        ));
+    }
+
+    // Apply the floor and ceiling.
+    let mut lut = colorbox::lut::Lut1D {
+        ranges: vec![(0.0, 1.0)],
+        tables: tables.to_vec(),
+    };
+    let norms = estimated.floor_ceiling_norms(&tables);
+    for chan in 0..3 {
+        let (floor, norm) = norms[chan];
+        for n in lut.tables[chan].iter_mut() {
+            *n = (*n - floor) * norm;
+        }
+    }
+    estimated.reduce_channels(&mut lut);
+
+    for line in EstimatedTF::log_curve_notes(&EstimatedTF::log_curve_fits(&lut)) {
+        note(line);
+    }
+
+    Ok(lut)
+}
@@ -0,0 +1,52 @@
+use crate::egui::{self, Ui};
+
+/// Bit depths available for entering values as integer code values.
+pub const BIT_DEPTHS: &[u32] = &[8, 10, 12, 14, 16];
+
+/// Draws a slider for each of the RGB channel values, along with a
+/// field for entering the value exactly as an N-bit code value
+/// (e.g. 95/1023 for a 10-bit black level of 95).
+pub fn channel_rows(ui: &mut Ui, values: &mut [f32; 3], bit_depth: u32, enabled: bool) {
+    let max_code = ((1u32 << bit_depth) - 1) as f32;
+
+    for (label, value) in ["R: ", "G: ", "B: "].iter().zip(values.iter_mut()) {
+        ui.horizontal(|ui| {
+            ui.label(*label);
+            ui.add_enabled(
+                enabled,
+                egui::widgets::Slider::new(value, 0.0..=1.0).show_value(false),
+            );
+
+            let mut code = *value * max_code;
+            if ui
+                .add_enabled(
+                    enabled,
+                    egui::widgets::DragValue::new(&mut code)
+                        .clamp_range(0.0..=max_code)
+                        .max_decimals(2)
+                        .suffix(format!("/{}", max_code)),
+                )
+                .changed()
+            {
+                *value = code / max_code;
+            }
+        });
+    }
+}
+
+/// Combo box for choosing the bit depth of code value entry.
+pub fn bit_depth_selector(ui: &mut Ui, id: &str, bit_depth: &mut u32, enabled: bool) {
+    ui.add_enabled_ui(enabled, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Code values:");
+            egui::ComboBox::from_id_source(id)
+                .width(70.0)
+                .selected_text(format!("{}-bit", bit_depth))
+                .show_ui(ui, |ui| {
+                    for depth in BIT_DEPTHS.iter() {
+                        ui.selectable_value(bit_depth, *depth, format!("{}-bit", depth));
+                    }
+                });
+        });
+    });
+}
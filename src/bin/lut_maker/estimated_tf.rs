@@ -1,23 +1,221 @@
+use sensor_analysis::{log_fit::LogCurve, utils::lerp_slice};
+
 use crate::egui::{self, Ui};
+use crate::generated_tf::TransferFunction;
+
+/// Estimation stops after this many rounds even if it hasn't converged.
+pub const MAX_ESTIMATION_ROUNDS: usize = 200000;
 
 pub struct EstimatedTF {
-    pub rounds: usize,
+    // Estimation stops once the error improves by less than this
+    // fraction over `emor::CONVERGENCE_WINDOW` rounds.
+    pub convergence: f32,
+    pub smoothness: f32,
     pub transfer_function_preview: Option<([Vec<f32>; 3], f32)>, // (lut, error)
+    pub error_history: Vec<(usize, f32)>, // (round, error) of the last estimation.
     pub sensor_floor: [f32; 3],
     pub sensor_ceiling: [f32; 3],
+
+    // Whether to apply the floor and ceiling to the exported LUT.
+    // They're used for estimation either way.
+    pub adjust_floor: bool,
+    pub adjust_ceiling: bool,
+
+    // When true, all channels share a single floor and curve, and only
+    // differ by a gain.
+    pub shared_curve: bool,
+    pub channel_divergence: Option<[f32; 3]>,
+
+    // When true, the bracketed images are all monochrome, so only one
+    // channel is estimated and exported.  Set from the images.
+    pub monochrome: bool,
+
+    // When true, estimation looks for a knee in the curve, as produced
+    // by dual gain sensors, and fits a piecewise curve if it finds one.
+    pub detect_knee: bool,
+    pub knee: Option<sensor_analysis::emor::Knee>,
+
+    // (encoded, linear) pairs that the curve's exposure is pinned to.
+    pub anchors: Vec<(f32, f32)>,
+
+    // Built-in function to compare the estimate against.
+    pub validation_tf: TransferFunction,
 }
 
 impl EstimatedTF {
     pub fn new() -> EstimatedTF {
         EstimatedTF {
-            rounds: 4000,
+            convergence: 1.0e-3,
+            smoothness: 0.0,
             transfer_function_preview: None,
+            error_history: Vec::new(),
             sensor_floor: [0.0; 3],
             sensor_ceiling: [1.0; 3],
+
+            adjust_floor: true,
+            adjust_ceiling: true,
+
+            shared_curve: false,
+            channel_divergence: None,
+            monochrome: false,
+
+            detect_knee: false,
+            knee: None,
+
+            anchors: Vec::new(),
+
+            validation_tf: TransferFunction::default(),
+        }
+    }
+
+    /// Reduces a to-linear or from-linear LUT made from the estimate to
+    /// a single channel, if the images are monochrome.
+    pub fn reduce_channels(&self, lut: &mut colorbox::lut::Lut1D) {
+        if self.monochrome {
+            lut.tables.truncate(1);
+            lut.ranges.truncate(1);
+        }
+    }
+
+    /// Fits log curves to the channels of a to-linear LUT made from the
+    /// estimate, so the transfer function can be implemented without
+    /// a table.
+    pub fn log_curve_fits(lut: &colorbox::lut::Lut1D) -> Vec<Option<(LogCurve, f32)>> {
+        lut.tables
+            .iter()
+            .map(|table| sensor_analysis::log_fit::fit_log_curve(table))
+            .collect()
+    }
+
+    /// Describes log curve fits from `log_curve_fits()`, for reporting
+    /// alongside exported LUTs.
+    pub fn log_curve_notes(fits: &[Option<(LogCurve, f32)>]) -> Vec<String> {
+        let mut notes = vec!["Log curve fit: encoded = a * log10(b * linear + c) + d".into()];
+        for (i, fit) in fits.iter().enumerate() {
+            let channel = if fits.len() == 1 {
+                "All"
+            } else {
+                ["R", "G", "B"][i.min(2)]
+            };
+            notes.push(match fit {
+                Some((curve, err)) => format!(
+                    "  {}: a = {}, b = {}, c = {}, d = {} (RMS error {:.5})",
+                    channel, curve.a, curve.b, curve.c, curve.d, err
+                ),
+                None => format!("  {}: no fit", channel),
+            });
+        }
+        notes
+    }
+
+    /// The floor used for all channels in shared curve mode.
+    pub fn shared_floor(&self) -> f32 {
+        self.sensor_floor.iter().sum::<f32>() / 3.0
+    }
+
+    /// The floor and ceiling that are actually applied to the curve,
+    /// taking into account whether they're enabled and whether the
+    /// curve is shared between channels.
+    pub fn applied_floor_ceiling(&self) -> ([f32; 3], [f32; 3]) {
+        let floor = if !self.adjust_floor {
+            [0.0; 3]
+        } else if self.shared_curve {
+            [self.shared_floor(); 3]
+        } else {
+            self.sensor_floor
+        };
+        let ceiling = if self.adjust_ceiling {
+            self.sensor_ceiling
+        } else {
+            [1.0; 3]
+        };
+        (floor, ceiling)
+    }
+
+    /// Computes the `(offset, scale)` pair for each channel that
+    /// applies the floor, ceiling, and anchor points to the given
+    /// to-linear luts.
+    ///
+    /// The normalized value is `(lut_value - offset) * scale`.
+    pub fn floor_ceiling_norms(&self, luts: &[Vec<f32>; 3]) -> [(f32, f32); 3] {
+        let (floor, ceiling) = self.applied_floor_ceiling();
+        let mut norms = [(0.0, 1.0); 3];
+        if self.shared_curve {
+            let out_floor = lerp_slice(&luts[1], floor[1]);
+            let gains = sensor_analysis::shared_curve_channel_gains(&luts[1], floor[1], &ceiling);
+            for chan in 0..3 {
+                norms[chan] = (out_floor, gains[chan]);
+            }
+        } else {
+            for chan in 0..3 {
+                let out_floor = lerp_slice(&luts[chan], floor[chan]);
+                let out_ceil = lerp_slice(&luts[chan], ceiling[chan]);
+                norms[chan] = (out_floor, 1.0 / (out_ceil - out_floor));
+            }
+        }
+
+        // Scale all channels equally to match the anchors, so that
+        // color balance is preserved.
+        if !self.anchors.is_empty() {
+            let anchor_scale = sensor_analysis::anchor_exposure_scale(
+                |n| {
+                    (0..3)
+                        .map(|chan| (lerp_slice(&luts[chan], n) - norms[chan].0) * norms[chan].1)
+                        .sum::<f32>()
+                        / 3.0
+                },
+                &self.anchors,
+            );
+            for norm in norms.iter_mut() {
+                norm.1 *= anchor_scale;
+            }
         }
+
+        norms
     }
 }
 
+/// Draws a small graph of the error over the rounds of the last
+/// estimation, on a log scale.
+fn error_sparkline(ui: &mut Ui, history: &[(usize, f32)]) {
+    let size = egui::Vec2::new(120.0, ui.spacing().interact_size.y);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let points: Vec<(usize, f32)> = history
+        .iter()
+        .filter(|(_, err)| err.is_finite() && *err > 0.0)
+        .map(|(round, err)| (*round, err.log10()))
+        .collect();
+    if points.len() < 2 {
+        return;
+    }
+
+    let last_round = points.last().unwrap().0.max(1) as f32;
+    let (min, max) = points.iter().fold(
+        (std::f32::INFINITY, -std::f32::INFINITY),
+        |(min, max), (_, n)| (min.min(*n), max.max(*n)),
+    );
+    let height = (max - min).max(1.0e-6);
+    let line: Vec<egui::Pos2> = points
+        .iter()
+        .map(|(round, n)| {
+            egui::Pos2::new(
+                rect.left() + (rect.width() * *round as f32 / last_round),
+                rect.bottom() - (rect.height() * (*n - min) / height),
+            )
+        })
+        .collect();
+
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    painter.add(egui::Shape::line(
+        line,
+        egui::Stroke::new(1.0, ui.visuals().text_color()),
+    ));
+
+    let (rounds, err) = history.last().unwrap();
+    response.on_hover_text(format!("Error after {} rounds: {:.6}", rounds, err));
+}
+
 pub fn estimated_mode_ui(
     ui: &mut Ui,
     app: &mut crate::AppMain,
@@ -28,6 +226,7 @@ pub fn estimated_mode_ui(
     // Transfer function controls.
     let area_width = ui.available_width();
     let sub_area_width = (area_width / 3.0).min(230.0);
+    let bit_depth = app.ui_data.lock().code_value_bits;
     ui.horizontal(|ui| {
         // Transfer curve controls.
         ui.vertical(|ui| {
@@ -36,26 +235,84 @@ pub fn estimated_mode_ui(
             ui.label("Transfer Function");
             ui.add_space(4.0);
             ui.add_enabled_ui(job_count == 0, |ui| {
-                // Rounds slider.
-                ui.add_enabled(
-                    job_count == 0,
-                    egui::widgets::DragValue::new(&mut app.ui_data.lock_mut().estimated.rounds)
-                        .clamp_range(100..=200000)
-                        .max_decimals(0)
-                        .prefix("Estimation rounds: "),
-                );
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            job_count == 0 && total_bracket_images > 0,
+                            egui::widgets::Button::new("Estimate"),
+                        )
+                        .clicked()
+                    {
+                        app.estimate_everything();
+                    }
+                    error_sparkline(ui, &app.ui_data.lock().estimated.error_history);
+                });
 
-                if ui
-                    .add_enabled(
-                        job_count == 0 && total_bracket_images > 0,
-                        egui::widgets::Button::new("Estimate"),
+                egui::containers::CollapsingHeader::new("Advanced").show(ui, |ui| {
+                    ui.add_enabled(
+                        job_count == 0,
+                        egui::widgets::Slider::new(
+                            &mut app.ui_data.lock_mut().estimated.convergence,
+                            1.0e-5..=1.0e-1,
+                        )
+                        .logarithmic(true)
+                        .max_decimals(5)
+                        .text("Convergence"),
                     )
-                    .clicked()
-                {
-                    app.estimate_everything();
+                    .on_hover_text(
+                        "Estimation stops once the error improves by less than this fraction over a thousand rounds.  Lower values take longer, but fit more closely.",
+                    );
+                    ui.add_enabled(
+                        job_count == 0,
+                        egui::widgets::Slider::new(
+                            &mut app.ui_data.lock_mut().estimated.smoothness,
+                            0.0..=100.0,
+                        )
+                        .logarithmic(true)
+                        .max_decimals(3)
+                        .text("Smoothness"),
+                    )
+                    .on_hover_text(
+                        "Penalizes oscillations in the curve during estimation.  Increase this if noisy bracket sets produce wavy curves.",
+                    );
+                    ui.add_enabled(
+                        job_count == 0,
+                        egui::widgets::Checkbox::new(
+                            &mut app.ui_data.lock_mut().estimated.detect_knee,
+                            "Detect knee",
+                        ),
+                    )
+                    .on_hover_text(
+                        "Look for a sudden change in slope in the curve, as produced by sensors with dual gain or HDR modes, and fit a piecewise curve if one is found.",
+                    );
+                });
+
+                if app.ui_data.lock().estimated.monochrome {
+                    ui.label("Monochrome images")
+                        .on_hover_text("All the bracketed images are monochrome, so a single channel is estimated.  LUTs are exported with a single channel in formats that support it (e.g. spi1d), and with the channel replicated otherwise.");
+                } else {
+                    ui.checkbox(
+                        &mut app.ui_data.lock_mut().estimated.shared_curve,
+                        "Shared curve with per-channel gain",
+                    )
+                    .on_hover_text(
+                        "Use a single floor and curve for all channels, differing only by a gain.  Common for Bayer sensors, and reduces color casts.",
+                    );
                 }
             });
             ui.add_space(4.0);
+            if let Some(divergence) = app.ui_data.lock().estimated.channel_divergence {
+                ui.label(format!(
+                    "Channel divergence:  R {:.4}  G {:.4}  B {:.4}",
+                    divergence[0], divergence[1], divergence[2]
+                ));
+            }
+            if let Some(knee) = app.ui_data.lock().estimated.knee {
+                ui.label(format!(
+                    "Knee at {:.4}, slope ×{:.3} above",
+                    knee.position, knee.slope
+                ));
+            }
         });
 
         ui.add_space(8.0);
@@ -65,7 +322,13 @@ pub fn estimated_mode_ui(
             ui.set_width(sub_area_width);
 
             ui.horizontal(|ui| {
-                ui.label("Sensor Noise Floor");
+                ui.checkbox(
+                    &mut app.ui_data.lock_mut().estimated.adjust_floor,
+                    "Adjust Noise Floor",
+                )
+                .on_hover_text(
+                    "When disabled, the floor is still used for estimation, but isn't applied to the exported LUT.",
+                );
                 ui.add_space(4.0);
                 if ui
                     .add_enabled(
@@ -78,20 +341,19 @@ pub fn estimated_mode_ui(
                 }
             });
             ui.add_space(4.0);
-            for (label, value) in ["R: ", "G: ", "B: "]
-                .iter()
-                .zip(app.ui_data.lock_mut().estimated.sensor_floor.iter_mut())
-            {
-                ui.horizontal(|ui| {
-                    ui.label(*label);
-                    ui.add_enabled(
-                        job_count == 0,
-                        egui::widgets::Slider::new(value, 0.0..=1.0)
-                            .max_decimals(5)
-                            .min_decimals(5),
-                    );
-                });
-            }
+            crate::code_value::channel_rows(
+                ui,
+                &mut app.ui_data.lock_mut().estimated.sensor_floor,
+                bit_depth,
+                job_count == 0,
+            );
+            ui.add_space(4.0);
+            crate::code_value::bit_depth_selector(
+                ui,
+                "estimated code value bits",
+                &mut app.ui_data.lock_mut().code_value_bits,
+                job_count == 0,
+            );
         });
 
         ui.add_space(8.0);
@@ -101,7 +363,13 @@ pub fn estimated_mode_ui(
             ui.set_width(sub_area_width);
 
             ui.horizontal(|ui| {
-                ui.label("Sensor Ceiling");
+                ui.checkbox(
+                    &mut app.ui_data.lock_mut().estimated.adjust_ceiling,
+                    "Adjust Ceiling",
+                )
+                .on_hover_text(
+                    "When disabled, the ceiling is still used for estimation, but isn't applied to the exported LUT.",
+                );
                 ui.add_space(4.0);
                 if ui
                     .add_enabled(
@@ -114,20 +382,66 @@ pub fn estimated_mode_ui(
                 }
             });
             ui.add_space(4.0);
-            for (label, value) in ["R: ", "G: ", "B: "]
-                .iter()
-                .zip(app.ui_data.lock_mut().estimated.sensor_ceiling.iter_mut())
+            crate::code_value::channel_rows(
+                ui,
+                &mut app.ui_data.lock_mut().estimated.sensor_ceiling,
+                bit_depth,
+                job_count == 0,
+            );
+        });
+    });
+
+    ui.add_space(8.0);
+
+    // Reference point controls.
+    ui.horizontal(|ui| {
+        ui.label("Reference Points");
+        ui.add_space(4.0);
+        if ui
+            .add_enabled(job_count == 0, egui::widgets::Button::new("Add"))
+            .on_hover_text(
+                "Pin the curve's exposure so that an encoded value maps to a known linear value.  For example, 18% gray to the code value documented for the camera's log encoding.",
+            )
+            .clicked()
+        {
+            app.ui_data.lock_mut().estimated.anchors.push((0.5, 0.18));
+        }
+    });
+    let mut remove_i = None;
+    for (i, (encoded, linear)) in app
+        .ui_data
+        .lock_mut()
+        .estimated
+        .anchors
+        .iter_mut()
+        .enumerate()
+    {
+        ui.horizontal(|ui| {
+            ui.add_enabled(
+                job_count == 0,
+                egui::widgets::DragValue::new(encoded)
+                    .clamp_range(0.0..=1.0)
+                    .speed(0.0005)
+                    .max_decimals(5)
+                    .prefix("Encoded: "),
+            );
+            ui.add_enabled(
+                job_count == 0,
+                egui::widgets::DragValue::new(linear)
+                    .clamp_range(0.0..=1000.0)
+                    .speed(0.0005)
+                    .max_decimals(5)
+                    .prefix("Linear: "),
+            );
+            if ui
+                .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
+                .clicked()
             {
-                ui.horizontal(|ui| {
-                    ui.label(*label);
-                    ui.add_enabled(
-                        job_count == 0,
-                        egui::widgets::Slider::new(value, 0.0..=1.0)
-                            .max_decimals(5)
-                            .min_decimals(5),
-                    );
-                });
+                remove_i = Some(i);
             }
         });
-    });
+    }
+    if let Some(i) = remove_i {
+        app.ui_data.lock_mut().estimated.anchors.remove(i);
+    }
 }
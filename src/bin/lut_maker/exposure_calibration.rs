@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+
+use sensor_analysis::utils::lerp_slice;
+
+use crate::egui::{self, Ui};
+
+/// Calibrates the exposure of exported to-linear LUTs against a gray
+/// card, so that 18% gray lands at 0.18.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExposureCalibration {
+    // Exposure of the gray card shot relative to the metered exposure.
+    pub stops_offset: f32,
+    pub gray_card: Option<(String, [f32; 3])>, // (filename, encoded gray values)
+}
+
+impl ExposureCalibration {
+    pub fn new() -> ExposureCalibration {
+        ExposureCalibration {
+            stops_offset: 0.0,
+            gray_card: None,
+        }
+    }
+
+    /// Computes the scale to apply to the output of the given to-linear
+    /// luts so that the gray card maps to 18% gray.
+    ///
+    /// The luts are `(table, input_min, input_max)` for each channel.
+    /// Returns 1.0 if there is no gray card.
+    pub fn scale(&self, luts: &[(Vec<f32>, f32, f32); 3]) -> f32 {
+        if let Some((_, gray)) = &self.gray_card {
+            let linear = (0..3)
+                .map(|chan| {
+                    let (ref table, min, max) = luts[chan];
+                    lerp_slice(table, ((gray[chan] - min) / (max - min)).max(0.0).min(1.0))
+                })
+                .sum::<f32>()
+                / 3.0;
+            let target = 0.18 * 2.0f32.powf(self.stops_offset);
+
+            if linear > 0.0 {
+                target / linear
+            } else {
+                1.0
+            }
+        } else {
+            1.0
+        }
+    }
+}
+
+pub fn calibration_ui(
+    ui: &mut Ui,
+    app: &mut crate::AppMain,
+    job_count: usize,
+    working_dir: &mut PathBuf,
+) {
+    let load_gray_card_dialog = {
+        let mut d = rfd::FileDialog::new()
+            .set_title("Load Gray Card Image")
+            .add_filter(
+                "All Images",
+                &[
+                    "jpg", "JPG", "jpeg", "JPEG", "tiff", "TIFF", "tif", "TIF", "png", "PNG",
+                ],
+            );
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
+
+    ui.horizontal(|ui| {
+        ui.label("Exposure Calibration");
+        ui.add_space(4.0);
+        if ui
+            .add_enabled(job_count == 0, egui::widgets::Button::new("Load Gray Card..."))
+            .on_hover_text(
+                "Load an image of an 18% gray card filling the center of the frame, shot at the metered exposure.  Exported 'to linear' LUTs are then scaled so that the gray card lands at 0.18.",
+            )
+            .clicked()
+        {
+            if let Some(path) = load_gray_card_dialog.pick_file() {
+                if let Some(parent) = path.parent().map(|p| p.into()) {
+                    *working_dir = parent;
+                }
+                app.load_gray_card(&path);
+            }
+        }
+
+        ui.add_enabled(
+            job_count == 0,
+            egui::widgets::DragValue::new(&mut app.ui_data.lock_mut().calibration.stops_offset)
+                .clamp_range(-10.0..=10.0)
+                .speed(0.01)
+                .max_decimals(2)
+                .prefix("Gray card exposure: ")
+                .suffix(" stops"),
+        );
+
+        let scale = {
+            let ui_data = app.ui_data.lock();
+            ui_data.calibration.scale(&ui_data.to_linear_luts(false))
+        };
+        let gray_card_name = app
+            .ui_data
+            .lock()
+            .calibration
+            .gray_card
+            .as_ref()
+            .map(|(name, _)| name.clone());
+        if let Some(name) = gray_card_name {
+            ui.label(format!(
+                "{}:  scale {:.4} ({:+.2} stops)",
+                name,
+                scale,
+                scale.log2()
+            ));
+            if ui
+                .add_enabled(job_count == 0, egui::widgets::Button::new("Clear"))
+                .clicked()
+            {
+                app.ui_data.lock_mut().calibration.gray_card = None;
+            }
+        } else {
+            ui.label("Uncalibrated");
+        }
+    });
+}
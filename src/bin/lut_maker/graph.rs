@@ -2,10 +2,14 @@ use std::iter::FromIterator;
 
 use sensor_analysis::{utils::lerp_slice, ExposureMapping};
 
-use crate::egui::Ui;
+use crate::egui::{self, Ui};
 use egui_plot::{Line, Plot, PlotPoint, PlotPoints, Points};
 
-use crate::AppMode;
+use crate::{
+    generated_tf::{TransferFunction, TRANSFER_FUNCTION_IDS},
+    modified_tf::{CurveEdit, CURVE_EDIT_POINTS},
+    AppMode,
+};
 
 #[allow(non_camel_case_types)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -13,6 +17,94 @@ pub enum PreviewMode {
     ToLinear,
     FromLinear,
     ExposureMappings,
+    Validation,
+}
+
+/// How an axis of the transfer function graph is scaled.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AxisScale {
+    Linear,
+    Log,   // Base 10.
+    Stops, // Relative to 18% gray.
+}
+
+const AXIS_SCALES: &[AxisScale] = &[AxisScale::Linear, AxisScale::Log, AxisScale::Stops];
+
+impl AxisScale {
+    fn ui_text(&self) -> &'static str {
+        match *self {
+            AxisScale::Linear => "Linear",
+            AxisScale::Log => "Log",
+            AxisScale::Stops => "Stops (from 18% gray)",
+        }
+    }
+
+    /// Maps a value to its position on the axis.  Returns `None` for
+    /// values that can't be shown, such as non-positive values on a
+    /// log axis.
+    fn apply(&self, n: f32) -> Option<f32> {
+        match *self {
+            AxisScale::Linear => Some(n),
+            AxisScale::Log if n > 0.0 => Some(n.log10()),
+            AxisScale::Stops if n > 0.0 => Some((n / 0.18).log2()),
+            _ => None,
+        }
+    }
+
+    /// Inverse of `apply()`.
+    fn invert(&self, n: f32) -> f32 {
+        match *self {
+            AxisScale::Linear => n,
+            AxisScale::Log => 10.0f32.powf(n),
+            AxisScale::Stops => 2.0f32.powf(n) * 0.18,
+        }
+    }
+
+    fn axis_label(&self, name: &str) -> String {
+        match *self {
+            AxisScale::Linear => name.into(),
+            AxisScale::Log => format!("{} (log10)", name),
+            AxisScale::Stops => format!("{} (stops from 18% gray)", name),
+        }
+    }
+}
+
+/// The axis scales of a transfer function graph, along with what each
+/// axis represents.
+struct GraphAxes {
+    scales: [AxisScale; 2],
+    names: [&'static str; 2],
+}
+
+impl GraphAxes {
+    fn new(scales: [AxisScale; 2], from_linear: bool) -> GraphAxes {
+        GraphAxes {
+            scales: scales,
+            names: if from_linear {
+                ["Linear", "Encoded"]
+            } else {
+                ["Encoded", "Linear"]
+            },
+        }
+    }
+
+    /// Maps a point to its position in the plot, if it can be shown.
+    fn point(&self, x: f32, y: f32) -> Option<[f64; 2]> {
+        Some([
+            self.scales[0].apply(x)? as f64,
+            self.scales[1].apply(y)? as f64,
+        ])
+    }
+
+    fn plot(&self, id: &str) -> Plot {
+        let plot = Plot::new(id);
+        if self.scales == [AxisScale::Linear; 2] {
+            plot.data_aspect(1.0)
+        } else {
+            plot.x_axis_label(self.scales[0].axis_label(self.names[0]))
+                .y_axis_label(self.scales[1].axis_label(self.names[1]))
+        }
+    }
 }
 
 pub fn graph_ui(ui: &mut Ui, app: &mut crate::AppMain) {
@@ -28,119 +120,116 @@ pub fn graph_ui(ui: &mut Ui, app: &mut crate::AppMain) {
             PreviewMode::ExposureMappings,
             "Bracketed Exposures Plot",
         );
+        ui.radio_value(mode, PreviewMode::Validation, "Compare to Built-in");
     });
 
-    let ui_data = app.ui_data.lock();
-
-    match (ui_data.preview_mode, ui_data.mode) {
-        (PreviewMode::ExposureMappings, AppMode::Generate) => {
-            let floor = if ui_data.generated.sensor_floor.0 {
-                Some(ui_data.generated.sensor_floor.1)
-            } else {
-                None
-            };
-            let ceiling = if ui_data.generated.sensor_ceiling.0 {
-                Some(ui_data.generated.sensor_ceiling.1)
-            } else {
-                None
-            };
+    // Built-in function to compare against.
+    if app.ui_data.lock().preview_mode == PreviewMode::Validation {
+        ui.horizontal(|ui| {
+            ui.label("Compare with: ");
+            let validation_tf = &mut app.ui_data.lock_mut().estimated.validation_tf;
+            egui::ComboBox::from_id_source("Validation Transfer Function")
+                .width(200.0)
+                .selected_text(validation_tf.id.ui_text())
+                .show_ui(ui, |ui| {
+                    for tf in TRANSFER_FUNCTION_IDS.iter() {
+                        ui.selectable_value(&mut validation_tf.id, *tf, tf.ui_text());
+                    }
+                });
+        });
+    }
 
-            // Normalized to-linear luts.
-            let luts: Vec<(Vec<f32>, f32, f32)> = {
-                let res = ui_data.generated.transfer_function_resolution;
-                let res_norm = 1.0 / (res - 1) as f32;
-                (0..3)
-                    .map(|chan| {
-                        (
-                            (0..res)
-                                .map(|i| {
-                                    let x = i as f32 * res_norm;
-                                    ui_data.generated.transfer_function.to_linear_fc(
-                                        x,
-                                        floor.map(|f| f[chan]),
-                                        ceiling.map(|c| c[chan]),
-                                        true,
-                                    )
-                                })
-                                .collect(),
-                            0.0,
-                            1.0,
-                        )
-                    })
-                    .collect()
-            };
+    // Axis scales of the transfer function graphs.
+    if matches!(
+        app.ui_data.lock().preview_mode,
+        PreviewMode::ToLinear | PreviewMode::FromLinear
+    ) {
+        ui.horizontal(|ui| {
+            let axes = &mut app.ui_data.lock_mut().graph_axes;
+            for (axis, name) in axes.iter_mut().zip(["X axis", "Y axis"]) {
+                egui::ComboBox::from_label(name)
+                    .width(160.0)
+                    .selected_text(axis.ui_text())
+                    .show_ui(ui, |ui| {
+                        for scale in AXIS_SCALES.iter() {
+                            ui.selectable_value(axis, *scale, scale.ui_text());
+                        }
+                    });
+                ui.add_space(8.0);
+            }
+        });
+    }
 
-            exposure_mappings_graph(ui, &ui_data.exposure_mappings, &luts[..]);
-        }
+    // The curve editor modifies the curve, so it's handled separately.
+    let editing_curve = {
+        let ui_data = app.ui_data.lock();
+        ui_data.mode == AppMode::Modify
+            && ui_data.preview_mode == PreviewMode::ToLinear
+            && ui_data.modified.curve_edit.enabled
+            && ui_data.modified.loaded_lut.is_some()
+    };
+    if editing_curve {
+        curve_editor_graph(ui, app);
+        return;
+    }
 
-        (PreviewMode::ExposureMappings, AppMode::Estimate) => {
-            let floor = ui_data.estimated.sensor_floor;
-            let ceiling = ui_data.estimated.sensor_ceiling;
-
-            // Normalized to-linear luts.
-            let luts: Vec<(Vec<f32>, f32, f32)> = {
-                let simple = [vec![0.0, 1.0], vec![0.0, 1.0], vec![0.0, 1.0]];
-                let luts = if let Some((luts, _)) = &ui_data.estimated.transfer_function_preview {
-                    luts
-                } else {
-                    &simple
-                };
-
-                (0..3)
-                    .map(|chan| {
-                        let out_floor = lerp_slice(&luts[chan], floor[chan]);
-                        let out_ceil = lerp_slice(&luts[chan], ceiling[chan]);
-                        let out_norm = 1.0 / (out_ceil - out_floor);
-                        (
-                            luts[chan]
-                                .iter()
-                                .map(|y| (y - out_floor) * out_norm)
-                                .collect(),
-                            0.0,
-                            1.0,
-                        )
-                    })
-                    .collect()
-            };
+    let ui_data = app.ui_data.lock();
+    let axes = GraphAxes::new(
+        ui_data.graph_axes,
+        ui_data.preview_mode == PreviewMode::FromLinear,
+    );
 
-            exposure_mappings_graph(ui, &ui_data.exposure_mappings, &luts[..]);
+    match (ui_data.preview_mode, ui_data.mode) {
+        (PreviewMode::ExposureMappings, _) => {
+            let luts = ui_data.to_linear_luts(true);
+            exposure_mappings_graph(ui, &ui_data.exposure_mappings, &luts);
         }
 
-        (PreviewMode::ExposureMappings, AppMode::Modify) => {
-            let luts = if let Some(luts) = ui_data.modified.adjusted_lut(true) {
-                luts
+        (PreviewMode::Validation, AppMode::Estimate) => {
+            if ui_data.estimated.transfer_function_preview.is_some() {
+                let luts = ui_data.to_linear_luts(true);
+                validation_graph(ui, &luts, &ui_data.estimated.validation_tf);
             } else {
-                [
-                    (vec![0.0, 1.0], 0.0, 1.0),
-                    (vec![0.0, 1.0], 0.0, 1.0),
-                    (vec![0.0, 1.0], 0.0, 1.0),
-                ]
-            };
+                Plot::new("Validation Graph").show(ui, |plot| {
+                    plot.text(egui_plot::Text::new(
+                        PlotPoint::new(0.5, 0.0),
+                        "No estimated transfer function.",
+                    ));
+                });
+            }
+        }
 
-            exposure_mappings_graph(ui, &ui_data.exposure_mappings, &luts);
+        (PreviewMode::Validation, _) => {
+            Plot::new("Validation Graph").show(ui, |plot| {
+                plot.text(egui_plot::Text::new(
+                    PlotPoint::new(0.5, 0.0),
+                    "Comparing to built-in functions is only available in Estimate mode.",
+                ));
+            });
         }
 
         (PreviewMode::FromLinear, AppMode::Estimate)
         | (PreviewMode::ToLinear, AppMode::Estimate) => {
-            let floor = ui_data.estimated.sensor_floor;
-            let ceiling = ui_data.estimated.sensor_ceiling;
-
             if let Some((luts, err)) = &ui_data.estimated.transfer_function_preview {
                 let show_from_linear_graph = ui_data.preview_mode == PreviewMode::FromLinear;
-                transfer_function_graph(ui, Some(&format!("Average error: {}", err)), |chan| {
-                    let out_floor = lerp_slice(&luts[chan], floor[chan]);
-                    let out_ceil = lerp_slice(&luts[chan], ceiling[chan]);
-                    let out_norm = 1.0 / (out_ceil - out_floor);
-                    let x_norm = 1.0 / (luts[chan].len() - 1) as f32;
-
-                    luts[chan].iter().enumerate().map(move |(idx, y)| {
-                        if show_from_linear_graph {
-                            ((y - out_floor) * out_norm, idx as f32 * x_norm)
-                        } else {
-                            (idx as f32 * x_norm, (y - out_floor) * out_norm)
-                        }
-                    })
-                });
+                let norms = ui_data.estimated.floor_ceiling_norms(luts);
+                transfer_function_graph(
+                    ui,
+                    &axes,
+                    Some(&format!("Average error: {}", err)),
+                    |chan| {
+                        let (out_floor, out_norm) = norms[chan];
+                        let x_norm = 1.0 / (luts[chan].len() - 1) as f32;
+
+                        luts[chan].iter().enumerate().map(move |(idx, y)| {
+                            if show_from_linear_graph {
+                                ((y - out_floor) * out_norm, idx as f32 * x_norm)
+                            } else {
+                                (idx as f32 * x_norm, (y - out_floor) * out_norm)
+                            }
+                        })
+                    },
+                );
             } else {
                 Plot::new("Transfer Function Graph")
                     .data_aspect(1.0)
@@ -167,7 +256,7 @@ pub fn graph_ui(ui: &mut Ui, app: &mut crate::AppMain) {
 
             let res = ui_data.generated.transfer_function_resolution;
             let res_norm = 1.0 / (res - 1) as f32;
-            let function = ui_data.generated.transfer_function;
+            let function = &ui_data.generated.transfer_function;
 
             let range_min = (0..3).fold(std::f32::INFINITY, |a, i| {
                 a.min(function.to_linear_fc(0.0, floor.map(|f| f[i]), ceiling.map(|c| c[i]), false))
@@ -176,7 +265,7 @@ pub fn graph_ui(ui: &mut Ui, app: &mut crate::AppMain) {
                 a.max(function.to_linear_fc(1.0, floor.map(|f| f[i]), ceiling.map(|c| c[i]), false))
             });
             let extent = range_max - range_min;
-            transfer_function_graph(ui, None, |chan| {
+            transfer_function_graph(ui, &axes, None, |chan| {
                 (0..res).map(move |i| {
                     let x = range_min + (i as f32 * res_norm * extent);
                     (
@@ -209,9 +298,9 @@ pub fn graph_ui(ui: &mut Ui, app: &mut crate::AppMain) {
 
             let res = ui_data.generated.transfer_function_resolution;
             let res_norm = 1.0 / (res - 1) as f32;
-            let function = ui_data.generated.transfer_function;
+            let function = &ui_data.generated.transfer_function;
 
-            transfer_function_graph(ui, None, |chan| {
+            transfer_function_graph(ui, &axes, None, |chan| {
                 (0..res).map(move |i| {
                     let x = i as f32 * res_norm;
                     (
@@ -228,11 +317,15 @@ pub fn graph_ui(ui: &mut Ui, app: &mut crate::AppMain) {
         }
 
         (PreviewMode::FromLinear, AppMode::Modify) | (PreviewMode::ToLinear, AppMode::Modify) => {
-            if let Some(luts) = ui_data
-                .modified
-                .adjusted_lut(ui_data.preview_mode == PreviewMode::ToLinear)
-            {
-                transfer_function_graph(ui, None, |chan| {
+            // A 3D LUT only has one direction, so its neutral axis is
+            // shown for both.
+            let luts = ui_data.modified.neutral_axis_lut().or_else(|| {
+                ui_data
+                    .modified
+                    .adjusted_lut(ui_data.preview_mode == PreviewMode::ToLinear)
+            });
+            if let Some(luts) = luts {
+                transfer_function_graph(ui, &axes, None, |chan| {
                     let range = (luts[chan].1, luts[chan].2);
                     let range_norm = range.1 - range.0;
                     let res_norm = 1.0 / (luts[chan].0.len() - 1) as f32;
@@ -295,27 +388,195 @@ fn exposure_mappings_graph(
         });
 }
 
-fn transfer_function_graph<I: Iterator<Item = (f32, f32)>, F: Fn(usize) -> I>(
-    ui: &mut Ui,
-    label: Option<&str>,
-    channel_points: F,
-) {
+/// Plots the difference in stops between the given to-linear luts and
+/// a built-in transfer function, across the encoded range.
+///
+/// The average exposure offset between the two is removed, since the
+/// luts are normalized to their ceilings rather than calibrated to an
+/// absolute exposure.
+fn validation_graph(ui: &mut Ui, luts: &[(Vec<f32>, f32, f32); 3], function: &TransferFunction) {
+    const SAMPLES: usize = 512;
+    const MIN_LINEAR: f32 = 1.0 / 4096.0;
+
     let colors = &[lib::colors::RED, lib::colors::GREEN, lib::colors::BLUE];
 
-    // Draw the graph.
-    Plot::new("Transfer Function Graph")
-        .data_aspect(1.0)
-        .show(ui, |plot| {
-            if let Some(text) = label {
-                plot.text(egui_plot::Text::new(PlotPoint::new(0.5, -0.05), text));
+    // Compute the error in stops at each sample.
+    let mut errors: [Vec<[f64; 2]>; 3] = [Vec::new(), Vec::new(), Vec::new()];
+    for chan in 0..3 {
+        let (ref table, min, max) = luts[chan];
+        for i in 0..SAMPLES {
+            let x = i as f32 / (SAMPLES - 1) as f32;
+            let estimated = lerp_slice(table, (x - min) / (max - min));
+            let reference = function.to_linear_fc(x, None, None, true);
+            if estimated > MIN_LINEAR && reference > MIN_LINEAR {
+                errors[chan].push([x as f64, (estimated / reference).log2() as f64]);
             }
+        }
+    }
+
+    // Remove the average exposure offset.
+    let count = errors.iter().map(|e| e.len()).sum::<usize>().max(1);
+    let offset = errors.iter().flatten().map(|p| p[1]).sum::<f64>() / count as f64;
+    let mut max_error = 0.0f64;
+    for p in errors.iter_mut().flatten() {
+        p[1] -= offset;
+        max_error = max_error.max(p[1].abs());
+    }
+
+    Plot::new("Validation Graph").show(ui, |plot| {
+        plot.text(egui_plot::Text::new(
+            PlotPoint::new(0.5, -max_error.max(0.5) - 0.25),
+            format!(
+                "Exposure offset: {:+.2} stops,  max deviation: {:.2} stops",
+                offset, max_error
+            ),
+        ));
+        plot.line(Line::new(vec![[0.0f64, 0.0], [1.0, 0.0]]).color(lib::colors::GRAY));
+        for chan in 0..3 {
+            plot.line(
+                Line::new(PlotPoints::from(std::mem::take(&mut errors[chan]))).color(colors[chan]),
+            );
+        }
+    });
+}
+
+/// The "to linear" graph of Modify mode, with draggable control points
+/// for hand-editing the curve.
+///
+/// The control points sit on the average of the channels' curves, and
+/// are kept between their neighbors so the curve stays monotonic.
+/// Control points that can't be shown with the current axis scales
+/// (e.g. at zero on a log axis) can't be dragged.
+fn curve_editor_graph(ui: &mut Ui, app: &mut crate::AppMain) {
+    const GRAB_RADIUS: f32 = 10.0;
+    let colors = &[lib::colors::RED, lib::colors::GREEN, lib::colors::BLUE];
+
+    let (axes, luts, unedited_luts) = {
+        let ui_data = app.ui_data.lock();
+        let axes = GraphAxes::new(ui_data.graph_axes, false);
+        match (
+            ui_data.modified.adjusted_lut(true),
+            ui_data.modified.unedited_lut(true),
+        ) {
+            (Some(a), Some(b)) => (axes, a, b),
+            _ => return,
+        }
+    };
+    let average = |luts: &[(Vec<f32>, f32, f32); 3], t: f32| -> f32 {
+        luts.iter().map(|lut| lerp_slice(&lut.0, t)).sum::<f32>() / 3.0
+    };
+    let to_x = |t: f32| luts[0].1 + ((luts[0].2 - luts[0].1) * t);
+    let values: Vec<f32> = (0..CURVE_EDIT_POINTS)
+        .map(|i| average(&luts, CurveEdit::point_position(i)))
+        .collect();
+    let points: Vec<Option<[f64; 2]>> = values
+        .iter()
+        .enumerate()
+        .map(|(i, y)| axes.point(to_x(CurveEdit::point_position(i)), *y))
+        .collect();
+
+    let drag_id = ui.id().with("Curve Editor Drag");
+    let mut dragging: Option<usize> = ui.data(|data| data.get_temp(drag_id)).flatten();
+
+    let plot_response = axes
+        .plot("Transfer Function Graph")
+        .allow_drag(false)
+        .show(ui, |plot| {
             for chan in 0..3 {
+                let range = (luts[chan].1, luts[chan].2);
+                let res_norm = 1.0 / (luts[chan].0.len() - 1) as f32;
                 plot.line(
                     Line::new(PlotPoints::from_iter(
-                        channel_points(chan).map(|(x, y)| [x as f64, y as f64]),
+                        luts[chan].0.iter().enumerate().filter_map(|(i, y)| {
+                            let x = range.0 + (i as f32 * res_norm * (range.1 - range.0));
+                            axes.point(x, *y)
+                        }),
                     ))
                     .color(colors[chan]),
                 );
             }
+            plot.points(
+                Points::new(PlotPoints::from_iter(points.iter().flatten().copied()))
+                    .radius(4.0)
+                    .color(lib::colors::GRAY),
+            );
+
+            // Find the control point under the pointer, if any.
+            let pointer = plot.pointer_coordinate();
+            let hovered = pointer.and_then(|pointer| {
+                let pointer_pos = plot.screen_from_plot(pointer);
+                points
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, p)| {
+                        let p = (*p)?;
+                        let d = plot
+                            .screen_from_plot(PlotPoint::new(p[0], p[1]))
+                            .distance(pointer_pos);
+                        Some((i, d))
+                    })
+                    .filter(|(_, d)| *d <= GRAB_RADIUS)
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                    .map(|(i, _)| i)
+            });
+
+            (pointer, hovered)
         });
+
+    let (pointer, hovered) = plot_response.inner;
+    let response = &plot_response.response;
+    if response.drag_started() {
+        dragging = hovered;
+    }
+    if response.drag_released() {
+        dragging = None;
+    }
+    if let (Some(i), Some(pointer)) = (dragging, pointer) {
+        let lower = if i > 0 {
+            values[i - 1]
+        } else {
+            std::f32::NEG_INFINITY
+        };
+        let upper = if i + 1 < values.len() {
+            values[i + 1]
+        } else {
+            std::f32::INFINITY
+        };
+        let y = axes.scales[1]
+            .invert(pointer.y as f32)
+            .max(lower)
+            .min(upper);
+        let t = CurveEdit::point_position(i);
+        app.ui_data.lock_mut().modified.curve_edit.offsets[i] = y - average(&unedited_luts, t);
+    }
+
+    ui.data_mut(|data| data.insert_temp(drag_id, dragging));
+}
+
+fn transfer_function_graph<I: Iterator<Item = (f32, f32)>, F: Fn(usize) -> I>(
+    ui: &mut Ui,
+    axes: &GraphAxes,
+    label: Option<&str>,
+    channel_points: F,
+) {
+    let colors = &[lib::colors::RED, lib::colors::GREEN, lib::colors::BLUE];
+
+    // Draw the graph.
+    axes.plot("Transfer Function Graph").show(ui, |plot| {
+        if let Some(text) = label {
+            let position = axes.point(0.5, 0.0).unwrap_or([0.0, 0.0]);
+            plot.text(egui_plot::Text::new(
+                PlotPoint::new(position[0], position[1] - 0.05),
+                text,
+            ));
+        }
+        for chan in 0..3 {
+            plot.line(
+                Line::new(PlotPoints::from_iter(
+                    channel_points(chan).filter_map(|(x, y)| axes.point(x, y)),
+                ))
+                .color(colors[chan]),
+            );
+        }
+    });
 }
@@ -1,33 +1,109 @@
-use std::{path::PathBuf, sync::atomic::AtomicBool};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+};
 
-use job_queue::JobQueue;
-use sensor_analysis::Histogram;
-use shared_data::Shared;
+use job_queue::{JobQueue, Priority};
+use sensor_analysis::{frame_rejection::Rejection, Histogram};
+use shared_data::{Shared, Watch};
 
-use lib::ImageInfo;
+use lib::bracket_analysis::SettingsMismatch;
+use lib::display_profile::DisplayTransform;
+use lib::{FloatEncoding, ImageInfo, SampleMask};
 
 use crate::egui::{self, Context, Ui};
+use egui_custom::{
+    ExposureEditor, ExposureEditorItem, ImageBadge, ImageListEdit, ImageListItem,
+    ImageListSelection, ImageListWidget,
+};
 
 pub struct ImageList {
     pub histogram_sets: Shared<Vec<Vec<([Histogram; 3], ImageInfo)>>>,
+    histogram_sets_watch: Watch<Vec<Vec<([Histogram; 3], ImageInfo)>>>,
     ui_data: Shared<UiData>,
     multiple_sets: AtomicBool,
     uses_exif_data: AtomicBool,
+
+    // How float images' values are mapped to histogram buckets.  It's
+    // determined by the first float image added, and is shared by all
+    // images in the list (and any lists it's shared with) so that their
+    // histograms are comparable.
+    float_encoding: Shared<Option<FloatEncoding>>,
+
+    // Converts thumbnails for the user's monitor.
+    display: DisplayTransform,
 }
 
 struct UiData {
     thumbnail_sets: Vec<Vec<(egui::TextureHandle, usize, usize, ImageInfo)>>, // (tex_handle, width, height, ImageInfo)
-    selected_idx: (usize, usize), // (set index, image index)
+    set_names: Vec<String>, // User-given names of the sets.  Empty means unnamed.
+    selection: ImageListSelection,
+    move_error: Option<String>, // Why the last drag-and-drop move failed.
+    video_frame_interval: f32,  // In seconds.
+
+    // Whether exposures account for ISO and aperture changes within a
+    // set, or are from shutter speed alone.
+    compensate_settings: bool,
+
+    // Duplicate and outlier images of each set, parallel to
+    // `thumbnail_sets`.  Updated when the histograms change.
+    rejections: Vec<Vec<Option<Rejection>>>,
+
+    // Restricts histogram computation to part of an image, keyed by
+    // the image's (full path, filename), since frames of the same video
+    // share a path.  Images without one are sampled whole.  Entries are
+    // kept when images are removed, so that undo and re-adding an image
+    // restore its region.
+    sample_masks: HashMap<(String, String), SampleMask>,
+    mask_drag_start: Option<(f32, f32)>,
+
+    // Manual entry of the exposures of images without exposure
+    // metadata, for one set.
+    exposure_editor: Option<(ExposureEditor, usize, Vec<(String, String)>)>, // (editor, set index, (full path, filename) of each image)
+}
+
+/// A snapshot of an image list's contents, for undo/redo.
+///
+/// Snapshots compare equal when they contain the same images, with the
+/// same exposures, and set names.
+#[derive(Clone)]
+pub struct Snapshot {
+    files: Vec<Vec<(String, String)>>, // (full path, filename)
+    exposures: Vec<Vec<Option<f32>>>,
+    names: Vec<String>,
+    data: Arc<(
+        Vec<Vec<([Histogram; 3], ImageInfo)>>,
+        Vec<Vec<(egui::TextureHandle, usize, usize, ImageInfo)>>,
+    )>,
+}
+
+impl PartialEq for Snapshot {
+    fn eq(&self, other: &Self) -> bool {
+        self.files == other.files && self.exposures == other.exposures && self.names == other.names
+    }
 }
 
 impl ImageList {
     pub fn new(multiple_sets: bool, uses_exif_data: bool) -> ImageList {
+        let histogram_sets = Shared::new(Vec::new());
         ImageList {
-            histogram_sets: Shared::new(Vec::new()),
+            histogram_sets_watch: histogram_sets.watch(),
+            histogram_sets: histogram_sets,
             ui_data: Shared::new(UiData {
                 thumbnail_sets: Vec::new(),
-                selected_idx: (0, 0),
+                set_names: Vec::new(),
+                selection: ImageListSelection::default(),
+                move_error: None,
+                video_frame_interval: 1.0,
+                compensate_settings: true,
+                rejections: Vec::new(),
+                sample_masks: HashMap::new(),
+                mask_drag_start: None,
+                exposure_editor: None,
             }),
+            float_encoding: Shared::new(None),
+            display: DisplayTransform::srgb(),
             multiple_sets: AtomicBool::new(multiple_sets),
             uses_exif_data: AtomicBool::new(uses_exif_data),
         }
@@ -42,6 +118,93 @@ impl ImageList {
             .sum()
     }
 
+    /// Takes a snapshot of the list's contents.
+    ///
+    /// If the contents are the same as `previous`, its data is shared
+    /// rather than copied.
+    pub fn snapshot(&self, previous: Option<&Snapshot>) -> Snapshot {
+        let files: Vec<Vec<(String, String)>> = self
+            .ui_data
+            .lock()
+            .thumbnail_sets
+            .iter()
+            .map(|set| {
+                set.iter()
+                    .map(|(_, _, _, info)| (info.full_filepath.clone(), info.filename.clone()))
+                    .collect()
+            })
+            .collect();
+        let exposures: Vec<Vec<Option<f32>>> = self
+            .ui_data
+            .lock()
+            .thumbnail_sets
+            .iter()
+            .map(|set| set.iter().map(|(_, _, _, info)| info.exposure).collect())
+            .collect();
+        let names = self.ui_data.lock().set_names.clone();
+
+        match previous {
+            Some(previous)
+                if previous.files == files
+                    && previous.exposures == exposures
+                    && previous.names == names =>
+            {
+                previous.clone()
+            }
+            _ => Snapshot {
+                files: files,
+                exposures: exposures,
+                names: names,
+                data: Arc::new((
+                    self.histogram_sets.lock().clone(),
+                    self.ui_data.lock().thumbnail_sets.clone(),
+                )),
+            },
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        // Unchanged histograms are left alone, so that watchers don't
+        // see a change.
+        if self.snapshot(Some(snapshot)) != *snapshot {
+            *self.histogram_sets.lock_mut() = snapshot.data.0.clone();
+        }
+        let mut ui_data = self.ui_data.lock_mut();
+        ui_data.thumbnail_sets = snapshot.data.1.clone();
+        ui_data.set_names = snapshot.names.clone();
+        ui_data.selection = ImageListSelection::default();
+        ui_data.exposure_editor = None;
+    }
+
+    /// Info about the currently selected image, if any.
+    pub fn selected_image(&self) -> Option<ImageInfo> {
+        let ui_data = self.ui_data.lock();
+        let (set_i, img_i) = ui_data.selection.current;
+        ui_data
+            .thumbnail_sets
+            .get(set_i)
+            .and_then(|set| set.get(img_i))
+            .map(|(_, _, _, info)| info.clone())
+    }
+
+    /// The encoding used for the histograms of float images in the list,
+    /// if it has any.
+    pub fn float_encoding(&self) -> Option<FloatEncoding> {
+        *self.float_encoding.lock()
+    }
+
+    /// Makes this list use the same float image encoding as `other`, so
+    /// that their histograms can be used together.
+    pub fn share_float_encoding(&mut self, other: &ImageList) {
+        self.float_encoding = other.float_encoding.clone_ref();
+    }
+
+    /// Sets the display transform for the thumbnails of images added
+    /// from now on.
+    pub fn set_display(&mut self, display: DisplayTransform) {
+        self.display = display;
+    }
+
     // Returns whether any data was changed or not.
     pub fn draw(
         &mut self,
@@ -55,19 +218,23 @@ impl ImageList {
         let use_sets = self.uses_sets();
 
         let add_images_dialog = {
+            let mut all_extensions = vec![
+                "jpg", "JPG", "jpeg", "JPEG", "tiff", "TIFF", "tif", "TIF", "webp", "WEBP", "png",
+                "PNG",
+            ];
+            if cfg!(feature = "ffmpeg") {
+                all_extensions.extend_from_slice(lib::job_helpers::VIDEO_EXTENSIONS);
+            }
             let mut d = rfd::FileDialog::new()
                 .set_title("Add Images")
-                .add_filter(
-                    "All Images",
-                    &[
-                        "jpg", "JPG", "jpeg", "JPEG", "tiff", "TIFF", "tif", "TIF", "webp", "WEBP",
-                        "png", "PNG",
-                    ],
-                )
+                .add_filter("All Images", &all_extensions[..])
                 .add_filter("jpeg", &["jpg", "JPG", "jpeg", "JPEG"])
                 .add_filter("tiff", &["tiff", "TIFF", "tif", "TIF"])
                 .add_filter("webp", &["webp", "WEBP"])
                 .add_filter("png", &["png", "PNG"]);
+            if cfg!(feature = "ffmpeg") {
+                d = d.add_filter("video", lib::job_helpers::VIDEO_EXTENSIONS);
+            }
             if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
                 d = d.set_directory(&working_dir);
             }
@@ -95,71 +262,302 @@ impl ImageList {
             }
         }
 
-        // Image thumbnails.
-        let mut remove_i = (None, None); // (set index, image index)
-        egui::containers::ScrollArea::vertical()
-            .auto_shrink([false, false])
-            .show(ui, |ui| {
-                let ui_data = &mut *self.ui_data.lock_mut();
-                let thumbnail_sets = &ui_data.thumbnail_sets;
-                let (ref mut set_index, ref mut image_index) = &mut ui_data.selected_idx;
-
-                for set_i in 0..thumbnail_sets.len() {
-                    ui.add_space(16.0);
-                    if use_sets {
-                        ui.horizontal(|ui| {
-                            ui.label(format!("Image Set {}", set_i + 1));
-                            if ui
-                                .add_enabled(enable_changes, egui::widgets::Button::new("🗙"))
-                                .clicked()
-                            {
-                                remove_i = (Some(set_i), None);
-                            }
-                        });
-                        ui.add_space(4.0);
-                    }
-                    let set = &thumbnail_sets[set_i];
-                    for (img_i, (ref tex_handle, _, _, _)) in set.iter().enumerate() {
-                        ui.horizontal(|ui| {
-                            if ui
-                                .add(
-                                    egui::widgets::ImageButton::new(
-                                        tex_handle,
-                                        // egui::Vec2::new(display_width, display_height),
-                                    )
-                                    .selected(set_i == *set_index && img_i == *image_index),
-                                )
-                                .clicked()
-                            {
-                                *set_index = set_i;
-                                *image_index = img_i;
-                            }
-                            if ui
-                                .add_enabled(enable_changes, egui::widgets::Button::new("🗙"))
-                                .clicked()
-                            {
-                                remove_i = (Some(set_i), Some(img_i));
-                            }
-                        });
-                    }
+        // Video frame extraction interval.
+        if cfg!(feature = "ffmpeg") {
+            ui.add_enabled(
+                enable_changes,
+                egui::widgets::DragValue::new(&mut self.ui_data.lock_mut().video_frame_interval)
+                    .clamp_range(0.01..=3600.0)
+                    .speed(0.05)
+                    .prefix("Video frame interval: ")
+                    .suffix(" s"),
+            );
+        }
+
+        // Float image encoding.
+        if let Some(encoding) = self.float_encoding() {
+            if self.total_image_count() > 0 {
+                ui.label(format!("Float image histograms: {}", encoding.ui_text()))
+                    .on_hover_text("Float images are mapped into this range before analysis, and estimated curves take the mapped values as input.  Log2 mapping is used for high dynamic range images, in which case the estimated curve acts as a shaper.");
+            }
+        }
+
+        // Sampling region.
+        if self.sampling_region_ui(ui, enable_changes, working_dir, jq) {
+            was_changed = true;
+        }
+
+        // Manual exposures.
+        if self.uses_exif() {
+            let set_i = self.ui_data.lock().selection.current.0;
+            let missing_exposures = self
+                .histogram_sets
+                .lock()
+                .get(set_i)
+                .map(|set| set.iter().any(|(_, info)| info.exposure.is_none()))
+                .unwrap_or(false);
+            if ui
+                .add_enabled(
+                    enable_changes && missing_exposures,
+                    egui::widgets::Button::new("Enter Exposures..."),
+                )
+                .on_hover_text("Enter the exposures of the images in the selected set that lack exposure metadata.")
+                .on_disabled_hover_text("All images in the selected set have exposure metadata.")
+                .clicked()
+            {
+                self.open_exposure_editor(set_i);
+            }
+            if self.exposure_editor_ui(ctx) {
+                was_changed = true;
+            }
+        }
+
+        // ISO/aperture compensation, for sets that mix them.
+        let mismatches: Vec<Vec<SettingsMismatch>> = if self.uses_exif() {
+            self.ui_data
+                .lock()
+                .thumbnail_sets
+                .iter()
+                .map(|set| {
+                    let infos: Vec<&ImageInfo> = set.iter().map(|(_, _, _, info)| info).collect();
+                    lib::bracket_analysis::find_settings_mismatches(&infos)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        if mismatches.iter().flatten().any(|m| m.any()) {
+            let mut compensate = self.ui_data.lock().compensate_settings;
+            if ui
+                .add_enabled(
+                    enable_changes,
+                    egui::widgets::Checkbox::new(&mut compensate, "Compensate for ISO/aperture changes"),
+                )
+                .on_hover_text("Some sets mix ISO or aperture changes with shutter speed bracketing.  When checked, exposures account for the ISO and aperture of each image, with missing values taken to be the set's most common ones.  When unchecked, exposures are computed from shutter speed alone.")
+                .changed()
+            {
+                self.ui_data.lock_mut().compensate_settings = compensate;
+                let set_count = self.histogram_sets.lock().len();
+                for set_i in 0..set_count {
+                    update_bracket_exposures(&self.histogram_sets, &self.ui_data, set_i);
                 }
-            });
-        match remove_i {
-            (Some(set_i), Some(img_i)) => {
-                self.remove_image(set_i, img_i);
                 was_changed = true;
             }
+        }
 
-            (Some(set_i), None) => {
+        // Duplicate and outlier images.  Only checked once jobs are
+        // done, so that sets aren't re-checked for every image added.
+        if self.uses_exif() && enable_changes && self.histogram_sets_watch.changed() {
+            self.ui_data.lock_mut().rejections = self
+                .histogram_sets
+                .lock()
+                .iter()
+                .map(|set| {
+                    let images: Vec<_> = set.iter().map(|(h, info)| (h, info)).collect();
+                    lib::bracket_analysis::find_rejected_images(&images)
+                })
+                .collect();
+        }
+        let rejected: Vec<(usize, usize)> = self
+            .ui_data
+            .lock()
+            .rejections
+            .iter()
+            .enumerate()
+            .flat_map(|(set_i, set)| {
+                set.iter()
+                    .enumerate()
+                    .filter(|(_, r)| r.is_some())
+                    .map(move |(img_i, _)| (set_i, img_i))
+            })
+            .collect();
+        if !rejected.is_empty()
+            && ui
+                .add_enabled(
+                    enable_changes,
+                    egui::widgets::Button::new(format!(
+                        "🗙 Remove {} Duplicate/Outlier Images",
+                        rejected.len()
+                    )),
+                )
+                .on_hover_text("Remove the images that duplicate another image of their set, or whose brightness doesn't fit their exposure (e.g. because the lighting changed between shots).  They can throw off estimation.")
+                .clicked()
+        {
+            for (set_i, img_i) in rejected.iter().rev() {
+                self.remove_image(*set_i, *img_i);
+            }
+            self.ui_data.lock_mut().rejections.clear();
+            was_changed = true;
+        }
+
+        if let Some(message) = &self.ui_data.lock().move_error {
+            ui.colored_label(lib::colors::YELLOW, message);
+        }
+
+        // Image thumbnails.
+        let uses_exif = self.uses_exif();
+        let response = {
+            let ui_data = &mut *self.ui_data.lock_mut();
+            let rejections = &ui_data.rejections;
+            let items: Vec<Vec<ImageListItem>> = ui_data
+                .thumbnail_sets
+                .iter()
+                .enumerate()
+                .map(|(set_i, set)| {
+                    set.iter()
+                        .enumerate()
+                        .map(|(img_i, (tex_handle, _, _, info))| ImageListItem {
+                            thumbnail: tex_handle,
+                            badges: image_badges(
+                                info,
+                                &set[0].3,
+                                use_sets,
+                                uses_exif,
+                                mismatches
+                                    .get(set_i)
+                                    .and_then(|m| m.get(img_i))
+                                    .copied()
+                                    .unwrap_or_default(),
+                                rejections
+                                    .get(set_i)
+                                    .and_then(|r| r.get(img_i))
+                                    .copied()
+                                    .flatten()
+                                    .map(|r| match r {
+                                        Rejection::Duplicate(j) => (
+                                            r,
+                                            set.get(j).map(|t| t.3.filename.as_str()).unwrap_or(""),
+                                        ),
+                                        r => (r, ""),
+                                    }),
+                            ),
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut list = ImageListWidget::new(&items).enabled(enable_changes);
+            if use_sets {
+                list = list.sets(&mut ui_data.set_names);
+            }
+            list.show(ui, &mut ui_data.selection)
+        };
+        match response.edit {
+            Some(ImageListEdit::RemoveImages(images)) => {
+                for (set_i, img_i) in images {
+                    self.remove_image(set_i, img_i);
+                }
+                was_changed = true;
+            }
+            Some(ImageListEdit::RemoveSet(set_i)) => {
                 self.remove_image_set(set_i);
                 was_changed = true;
             }
-            _ => {}
+            Some(ImageListEdit::MoveSet { from, to }) => {
+                self.move_image_set(from, to);
+                was_changed = true;
+            }
+            Some(ImageListEdit::MoveImage { from, to }) => {
+                was_changed = self.move_image(from, to);
+            }
+            None => {}
         }
 
         was_changed
     }
 
+    fn open_exposure_editor(&mut self, set_index: usize) {
+        let (items, keys): (Vec<_>, Vec<_>) = match self.histogram_sets.lock().get(set_index) {
+            Some(set) => set
+                .iter()
+                .map(|(histograms, info)| {
+                    (
+                        ExposureEditorItem {
+                            name: info.filename.clone(),
+                            known_ev: info.exposure.map(|e| e.log2()),
+                            brightness: histograms[1]
+                                .find_sum_lerp(histograms[1].total_samples / 2),
+                        },
+                        (info.full_filepath.clone(), info.filename.clone()),
+                    )
+                })
+                .unzip(),
+            None => return,
+        };
+
+        let title = if self.uses_sets() {
+            let name = self.ui_data.lock().set_names[set_index].clone();
+            if name.is_empty() {
+                format!("Enter Exposures: Image Set {}", set_index + 1)
+            } else {
+                format!("Enter Exposures: {}", name)
+            }
+        } else {
+            "Enter Exposures".into()
+        };
+        self.ui_data.lock_mut().exposure_editor =
+            Some((ExposureEditor::new(&title, items), set_index, keys));
+    }
+
+    // Returns whether any exposures were changed or not.
+    fn exposure_editor_ui(&mut self, ctx: &Context) -> bool {
+        let (set_index, exposures) = {
+            let mut ui_data = self.ui_data.lock_mut();
+            let (editor, set_index, keys) = match ui_data.exposure_editor {
+                Some(ref mut editor) => editor,
+                None => return false,
+            };
+            let evs = match editor.show(ctx) {
+                Some(evs) => evs,
+                None => return false,
+            };
+            let exposures: Vec<((String, String), f32)> = evs
+                .iter()
+                .map(|(i, ev)| (keys[*i].clone(), 2.0f32.powf(*ev)))
+                .collect();
+            let set_index = *set_index;
+            ui_data.exposure_editor = None;
+            (set_index, exposures)
+        };
+        if exposures.is_empty() {
+            return false;
+        }
+
+        let is_image = |info: &ImageInfo, key: &(String, String)| {
+            info.full_filepath == key.0 && info.filename == key.1
+        };
+        {
+            let mut histogram_sets = self.histogram_sets.lock_mut();
+            if let Some(set) = histogram_sets.get_mut(set_index) {
+                for (_, info) in set.iter_mut() {
+                    if let Some((_, exposure)) =
+                        exposures.iter().find(|(key, _)| is_image(info, key))
+                    {
+                        info.exposure = Some(*exposure);
+                    }
+                }
+                // A stable sort, so that images with equal exposures stay
+                // in the same order as their thumbnails.
+                set.sort_by(|a, b| a.1.exposure.partial_cmp(&b.1.exposure).unwrap());
+            }
+        }
+        {
+            let mut ui_data = self.ui_data.lock_mut();
+            if let Some(set) = ui_data.thumbnail_sets.get_mut(set_index) {
+                for (_, _, _, info) in set.iter_mut() {
+                    if let Some((_, exposure)) =
+                        exposures.iter().find(|(key, _)| is_image(info, key))
+                    {
+                        info.exposure = Some(*exposure);
+                    }
+                }
+                set.sort_by(|a, b| a.3.exposure.partial_cmp(&b.3.exposure).unwrap());
+            }
+            ui_data.selection.select((set_index, 0));
+        }
+
+        true
+    }
+
     fn uses_sets(&self) -> bool {
         use std::sync::atomic::Ordering;
         self.multiple_sets.load(Ordering::Acquire)
@@ -181,31 +579,99 @@ impl ImageList {
 
         let histogram_sets = self.histogram_sets.clone_ref();
         let ui_data = self.ui_data.clone_ref();
+        let float_encoding = self.float_encoding.clone_ref();
+        let display = self.display.clone();
         let ctx = ctx.clone();
+        let video_frame_interval = self.ui_data.lock().video_frame_interval;
+
+        job_queue.add_job("Add Image(s)", Priority::Normal, move |status| {
+            // Expand video files into their extracted frames.  The
+            // temporary frame directories are cleaned up when the
+            // job finishes.
+            let mut frame_dirs = Vec::new();
+            let mut image_paths: Vec<(PathBuf, Option<VideoFrame>)> = {
+                let mut paths = Vec::new();
+                for path in image_paths.drain(..) {
+                    if !lib::job_helpers::is_video_file(&path) {
+                        paths.push((path, None));
+                        continue;
+                    }
+                    if status.lock().is_canceled() {
+                        return;
+                    }
+                    status.lock_mut().set_progress(
+                        format!("Extracting video frames: {}", path.to_string_lossy()),
+                        0.0,
+                    );
+                    match extract_video_frames(&path, video_frame_interval) {
+                        Ok((frame_dir, frame_paths)) => {
+                            let exposures = lib::job_helpers::read_video_exposure_sidecar(&path);
+                            let video_name = path
+                                .file_name()
+                                .map(|p| p.to_string_lossy().into())
+                                .unwrap_or_else(|| "".into());
+                            for (frame_i, frame_path) in frame_paths.into_iter().enumerate() {
+                                let exposure = exposures
+                                    .as_ref()
+                                    .and_then(|e| e.get(frame_i).copied().flatten());
+                                paths.push((
+                                    frame_path,
+                                    Some(VideoFrame {
+                                        name: format!("{} [frame {}]", video_name, frame_i + 1),
+                                        full_path: path.to_string_lossy().into(),
+                                        exposure: exposure,
+                                    }),
+                                ));
+                            }
+                            frame_dirs.push(frame_dir);
+                        }
+                        Err(e) => {
+                            status.lock_mut().log_error(format!(
+                                "Unable to extract frames from video \"{}\": {}",
+                                path.to_string_lossy(),
+                                e
+                            ));
+                        }
+                    }
+                }
+                paths
+            };
 
-        job_queue.add_job("Add Image(s)", move |status| {
             let len = image_paths.len() as f32;
 
             // Create a new image and thumbnail set.
             if use_sets || histogram_sets.lock().is_empty() {
                 histogram_sets.lock_mut().push(Vec::new());
-                ui_data.lock_mut().thumbnail_sets.push(Vec::new());
+                let mut ui_data = ui_data.lock_mut();
+                ui_data.thumbnail_sets.push(Vec::new());
+                ui_data.set_names.push(String::new());
             }
 
             // Load and add images.
-            for (img_i, path) in image_paths.drain(..).enumerate() {
+            for (img_i, (path, video_frame)) in image_paths.drain(..).enumerate() {
                 if status.lock().is_canceled() {
                     break;
                 }
 
-                status.lock_mut().set_progress(
-                    format!("Loading: {}", path.to_string_lossy()),
-                    (img_i + 1) as f32 / len,
-                );
+                {
+                    let mut status = status.lock_mut();
+                    status.set_progress(
+                        format!("Loading: {}", path.to_string_lossy()),
+                        img_i as f32 / len,
+                    );
+                    status.set_item_count(img_i, len as usize);
+                }
 
                 // Load image.
                 let img = match lib::job_helpers::load_image(&path) {
-                    Ok(img) => img,
+                    Ok(mut img) => {
+                        if let Some(frame) = video_frame {
+                            img.info.filename = frame.name;
+                            img.info.full_filepath = frame.full_path;
+                            img.info.exposure = frame.exposure;
+                        }
+                        img
+                    }
                     Err(image_fmt::ReadError::IO(e)) => {
                         status.lock_mut().log_error(format!(
                             "Unable to read file \"{}\": {:?}.",
@@ -246,14 +712,15 @@ impl ImageList {
                 // Check if we got exposure data from it.
                 if use_exif && img.info.exposure.is_none() {
                     status.lock_mut().log_warning(format!(
-                        "Image file lacks Exif data needed to compute exposure value: \"{}\".  This image will be excluded from transfer function, floor, and ceiling estimation.",
+                        "Image file lacks Exif data needed to compute exposure value: \"{}\".  This image will be excluded from transfer function, floor, and ceiling estimation unless its exposure is entered with \"Enter Exposures...\".",
                         path.to_string_lossy()
                     ));
                 }
 
                 // Make a thumbnail texture.
                 let (thumbnail_tex_handle, thumbnail_width, thumbnail_height) = {
-                    let (pixels, width, height) = lib::job_helpers::make_image_preview(&img, Some(128), None);
+                    let (mut pixels, width, height) = lib::job_helpers::make_image_preview(&img, Some(128), None);
+                    display.apply(&mut pixels, 4);
                     let tex_handle = ctx.load_texture("",
                             egui::ColorImage::from_rgba_unmultiplied(
                                 [width, height],
@@ -268,8 +735,40 @@ impl ImageList {
                     (tex_handle, width, height)
                 };
 
+                // Determine how to map float images into histograms, if
+                // this is the first float image in the list.
+                if img.image.data.is_float() && float_encoding.lock().is_none() {
+                    let encoding = FloatEncoding::auto(&img.image);
+                    if let Some(encoding) = encoding {
+                        status.lock_mut().log_note(format!(
+                            "Float image histograms: {}.",
+                            encoding.ui_text()
+                        ));
+                    }
+                    *float_encoding.lock_mut() = encoding;
+                }
+
                 // Compute histograms.
-                let histograms = lib::job_helpers::compute_image_histograms(&img);
+                let sample_mask = ui_data
+                    .lock()
+                    .sample_masks
+                    .get(&(img.info.full_filepath.clone(), img.info.filename.clone()))
+                    .cloned();
+                let histograms = match lib::job_helpers::compute_image_histograms_with_progress(
+                    &img,
+                    sample_mask.as_ref(),
+                    float_encoding.lock().as_ref(),
+                    |fraction| {
+                        status.lock_mut().set_progress(
+                            format!("Computing histograms: {}", path.to_string_lossy()),
+                            (img_i as f32 + fraction) / len,
+                        );
+                        !status.lock().is_canceled()
+                    },
+                ) {
+                    Some(histograms) => histograms,
+                    None => break, // Canceled.
+                };
 
                 // Add image and thumbnail to our lists.
                 {
@@ -285,10 +784,361 @@ impl ImageList {
                     set.sort_unstable_by(|a, b| a.1.exposure.partial_cmp(&b.1.exposure).unwrap());
                 }
             }
+
+            // Check for ISO and aperture changes within the set.
+            if use_exif {
+                let set_index = histogram_sets.lock().len().saturating_sub(1);
+                update_bracket_exposures(&histogram_sets, &ui_data, set_index);
+
+                let histogram_sets = histogram_sets.lock();
+                let infos: Vec<&ImageInfo> = histogram_sets
+                    .last()
+                    .map(|set| set.iter().map(|(_, info)| info).collect())
+                    .unwrap_or_default();
+                let mismatches = lib::bracket_analysis::find_settings_mismatches(&infos);
+                if mismatches.iter().any(|m| m.any()) {
+                    status.lock_mut().log_warning(format!(
+                        "Image set mixes ISO or aperture changes with shutter speed bracketing, which may throw off estimation: {}.",
+                        infos
+                            .iter()
+                            .zip(mismatches.iter())
+                            .filter(|(_, m)| m.any())
+                            .map(|(info, _)| format!("\"{}\"", info.filename))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+            }
+        });
+    }
+
+    // Edits the sampling region of the selected image.  Returns whether
+    // any image's sampling region was changed or not.
+    fn sampling_region_ui(
+        &mut self,
+        ui: &mut Ui,
+        enable_changes: bool,
+        working_dir: &mut PathBuf,
+        jq: &JobQueue,
+    ) -> bool {
+        // (full path, filename) of the images whose region changed.
+        let mut changed_images: Vec<(String, String)> = Vec::new();
+
+        let (selected_set, selected_image) = {
+            let ui_data = self.ui_data.lock();
+            let (set_i, img_i) = ui_data.selection.current;
+            (
+                set_i,
+                ui_data
+                    .thumbnail_sets
+                    .get(set_i)
+                    .and_then(|set| set.get(img_i))
+                    .map(|(_, _, _, info)| (info.full_filepath.clone(), info.filename.clone())),
+            )
+        };
+
+        egui::containers::CollapsingHeader::new("Sampling Region").show(ui, |ui| {
+            ui.label(
+                match selected_image
+                    .as_ref()
+                    .and_then(|key| self.ui_data.lock().sample_masks.get(key).cloned())
+                {
+                    None => "Whole image",
+                    Some(SampleMask::Rect(..)) => "Rectangle",
+                    Some(SampleMask::Image(..)) => "Mask image",
+                },
+            );
+            ui.add_space(4.0);
+
+            // Draw a rectangle on the selected image.
+            {
+                let ui_data = &mut *self.ui_data.lock_mut();
+                let (set_i, img_i) = ui_data.selection.current;
+                if let Some((tex_handle, width, height, info)) = ui_data
+                    .thumbnail_sets
+                    .get(set_i)
+                    .and_then(|set| set.get(img_i))
+                {
+                    let key = (info.full_filepath.clone(), info.filename.clone());
+                    let response = ui.add(
+                        egui::Image::new(tex_handle)
+                            .fit_to_exact_size(egui::Vec2::new(*width as f32, *height as f32))
+                            .sense(if enable_changes {
+                                egui::Sense::drag()
+                            } else {
+                                egui::Sense::hover()
+                            }),
+                    );
+                    let rect = response.rect;
+                    let to_normalized = |pos: egui::Pos2| -> (f32, f32) {
+                        (
+                            ((pos.x - rect.min.x) / rect.width()).max(0.0).min(1.0),
+                            ((pos.y - rect.min.y) / rect.height()).max(0.0).min(1.0),
+                        )
+                    };
+
+                    if response.drag_started() {
+                        ui_data.mask_drag_start =
+                            response.interact_pointer_pos().map(to_normalized);
+                    }
+                    if response.dragged() {
+                        if let (Some(start), Some(pos)) =
+                            (ui_data.mask_drag_start, response.interact_pointer_pos())
+                        {
+                            let end = to_normalized(pos);
+                            ui_data.sample_masks.insert(
+                                key.clone(),
+                                SampleMask::Rect(
+                                    start.0.min(end.0),
+                                    start.1.min(end.1),
+                                    start.0.max(end.0),
+                                    start.1.max(end.1),
+                                ),
+                            );
+                        }
+                    }
+                    if response.drag_released() && ui_data.mask_drag_start.is_some() {
+                        ui_data.mask_drag_start = None;
+
+                        // Ignore degenerate rectangles (e.g. from a simple click).
+                        let degenerate = match ui_data.sample_masks.get(&key) {
+                            Some(SampleMask::Rect(min_x, min_y, max_x, max_y)) => {
+                                (max_x - min_x) < 0.01 || (max_y - min_y) < 0.01
+                            }
+                            _ => false,
+                        };
+                        if degenerate {
+                            ui_data.sample_masks.remove(&key);
+                        }
+                        changed_images.push(key.clone());
+                    }
+
+                    // Show the current rectangle.
+                    if let Some(SampleMask::Rect(min_x, min_y, max_x, max_y)) =
+                        ui_data.sample_masks.get(&key).cloned()
+                    {
+                        ui.painter().rect_stroke(
+                            egui::Rect::from_min_max(
+                                rect.lerp_inside(egui::Vec2::new(min_x, min_y)),
+                                rect.lerp_inside(egui::Vec2::new(max_x, max_y)),
+                            ),
+                            0.0,
+                            egui::Stroke::new(1.5, lib::colors::YELLOW),
+                        );
+                    }
+                } else {
+                    ui.label("Add an image to draw a sampling rectangle on it.");
+                }
+            }
+
+            ui.add_space(4.0);
+            let enable_changes = enable_changes && selected_image.is_some();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(enable_changes, egui::widgets::Button::new("Load Mask..."))
+                    .clicked()
+                {
+                    let mut dialog = rfd::FileDialog::new()
+                        .set_title("Load Mask Image")
+                        .add_filter(
+                            "All Images",
+                            &[
+                                "jpg", "JPG", "jpeg", "JPEG", "tiff", "TIFF", "tif", "TIF", "png",
+                                "PNG",
+                            ],
+                        );
+                    if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+                        dialog = dialog.set_directory(&working_dir);
+                    }
+                    if let Some(path) = dialog.pick_file() {
+                        if let Some(parent) = path.parent().map(|p| p.into()) {
+                            *working_dir = parent;
+                        }
+                        let image = selected_image.clone().unwrap();
+                        self.load_sample_mask(path, image.clone(), jq);
+                        changed_images.push(image);
+                    }
+                }
+                let has_mask = selected_image
+                    .as_ref()
+                    .map(|key| self.ui_data.lock().sample_masks.contains_key(key))
+                    .unwrap_or(false);
+                if ui
+                    .add_enabled(
+                        enable_changes && has_mask,
+                        egui::widgets::Button::new("Clear"),
+                    )
+                    .clicked()
+                {
+                    let image = selected_image.clone().unwrap();
+                    self.ui_data.lock_mut().sample_masks.remove(&image);
+                    changed_images.push(image);
+                }
+
+                // Brackets are usually shot with the same framing, so
+                // let one image's region be used for its whole set.
+                if ui
+                    .add_enabled(enable_changes, egui::widgets::Button::new("Apply to Set"))
+                    .on_hover_text(
+                        "Use the selected image's sampling region for all images in its set.",
+                    )
+                    .clicked()
+                {
+                    let ui_data = &mut *self.ui_data.lock_mut();
+                    let image = selected_image.clone().unwrap();
+                    let mask = ui_data.sample_masks.get(&image).cloned();
+                    let set_images: Vec<(String, String)> = ui_data
+                        .thumbnail_sets
+                        .get(selected_set)
+                        .map(|set| {
+                            set.iter()
+                                .map(|(_, _, _, info)| {
+                                    (info.full_filepath.clone(), info.filename.clone())
+                                })
+                                .filter(|key| *key != image)
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    for key in set_images.into_iter() {
+                        match mask {
+                            Some(ref mask) => {
+                                ui_data.sample_masks.insert(key.clone(), mask.clone());
+                            }
+                            None => {
+                                ui_data.sample_masks.remove(&key);
+                            }
+                        }
+                        changed_images.push(key);
+                    }
+                }
+            });
         });
 
-        // // Update the exposure mappings.
-        // self.compute_exposure_mappings();
+        if !changed_images.is_empty() {
+            self.recompute_histograms(changed_images, jq);
+            return true;
+        }
+
+        false
+    }
+
+    /// Loads a mask image as the sampling region of `image`, given as
+    /// (full path, filename).
+    fn load_sample_mask(&mut self, path: PathBuf, image: (String, String), job_queue: &JobQueue) {
+        let ui_data = self.ui_data.clone_ref();
+
+        job_queue.add_job("Load Sample Mask", Priority::Normal, move |status| {
+            status.lock_mut().set_progress(
+                format!("Loading sample mask: {}", path.to_string_lossy()),
+                0.0,
+            );
+
+            match lib::job_helpers::load_sample_mask(&path) {
+                Ok(mask) => {
+                    ui_data.lock_mut().sample_masks.insert(image, mask);
+                }
+                Err(_) => {
+                    status.lock_mut().log_error(format!(
+                        "Unable to load mask image \"{}\".",
+                        path.to_string_lossy()
+                    ));
+                }
+            }
+        });
+    }
+
+    /// Reloads the given images, as (full path, filename), and recomputes
+    /// their histograms with their current sampling regions.
+    fn recompute_histograms(&mut self, images: Vec<(String, String)>, job_queue: &JobQueue) {
+        let histogram_sets = self.histogram_sets.clone_ref();
+        let ui_data = self.ui_data.clone_ref();
+        let float_encoding = self.float_encoding();
+
+        job_queue.add_job("Recompute Histograms", Priority::Normal, move |status| {
+            let masks = ui_data.lock().sample_masks.clone();
+            let image_infos: Vec<Vec<ImageInfo>> = histogram_sets
+                .lock()
+                .iter()
+                .map(|set| set.iter().map(|(_, info)| info.clone()).collect())
+                .collect();
+            let is_changed = |info: &ImageInfo| {
+                images
+                    .iter()
+                    .any(|key| info.full_filepath == key.0 && info.filename == key.1)
+            };
+            let len = image_infos
+                .iter()
+                .flatten()
+                .filter(|info| is_changed(info))
+                .count() as f32;
+
+            let mut img_count = 0;
+            for (set_i, set) in image_infos.iter().enumerate() {
+                for (img_i, info) in set.iter().enumerate() {
+                    if !is_changed(info) {
+                        continue;
+                    }
+                    if status.lock().is_canceled() {
+                        return;
+                    }
+                    img_count += 1;
+                    status.lock_mut().set_progress(
+                        format!("Recomputing histograms: {}", info.filename),
+                        img_count as f32 / len,
+                    );
+
+                    let path = PathBuf::from(&info.full_filepath);
+                    if lib::job_helpers::is_video_file(&path) {
+                        status.lock_mut().log_warning(format!(
+                            "Can't apply the sampling region to already-added video frames: \"{}\".  Re-add the video to apply it.",
+                            info.filename
+                        ));
+                        continue;
+                    }
+                    let img = match lib::job_helpers::load_image(&path) {
+                        Ok(img) => img,
+                        Err(_) => {
+                            status.lock_mut().log_error(format!(
+                                "Unable to reload image \"{}\" to apply the sampling region.",
+                                info.full_filepath
+                            ));
+                            continue;
+                        }
+                    };
+
+                    let histograms =
+                        match lib::job_helpers::compute_image_histograms_with_progress(
+                            &img,
+                            masks.get(&(info.full_filepath.clone(), info.filename.clone())),
+                            float_encoding.as_ref(),
+                            |fraction| {
+                                status.lock_mut().set_progress(
+                                    format!("Recomputing histograms: {}", info.filename),
+                                    ((img_count - 1) as f32 + fraction) / len,
+                                );
+                                !status.lock().is_canceled()
+                            },
+                        ) {
+                            Some(histograms) => histograms,
+                            None => return, // Canceled.
+                        };
+                    if histograms[0].total_samples == 0 {
+                        status.lock_mut().log_warning(format!(
+                            "Sampling region contains no pixels of image \"{}\".",
+                            info.filename
+                        ));
+                    }
+
+                    if let Some(entry) = histogram_sets
+                        .lock_mut()
+                        .get_mut(set_i)
+                        .and_then(|set| set.get_mut(img_i))
+                    {
+                        entry.0 = histograms;
+                    }
+                }
+            }
+        });
     }
 
     fn remove_image(&mut self, set_index: usize, image_index: usize) {
@@ -317,13 +1167,7 @@ impl ImageList {
             let _ = thumbnail_sets[set_index].remove(image_index);
         }
 
-        // Adjust the selected image index appropriately.
-        if ui_data.selected_idx.0 == set_index && ui_data.selected_idx.1 > image_index {
-            ui_data.selected_idx.1 -= 1;
-        }
-
-        // // Update the exposure mappings.
-        // self.compute_exposure_mappings();
+        ui_data.selection.image_removed((set_index, image_index));
     }
 
     fn remove_image_set(&mut self, set_index: usize) {
@@ -337,6 +1181,9 @@ impl ImageList {
         {
             // Remove the thumbnail set.
             let mut ui_data = self.ui_data.lock_mut();
+            if set_index < ui_data.set_names.len() {
+                ui_data.set_names.remove(set_index);
+            }
             let thumbnail_sets = &mut ui_data.thumbnail_sets;
             if set_index < thumbnail_sets.len() {
                 thumbnail_sets.remove(set_index);
@@ -349,15 +1196,244 @@ impl ImageList {
                     .get(new_set_index)
                     .map(|s| s.len().saturating_sub(1))
                     .unwrap_or(0);
-                ui_data.selected_idx = (new_set_index, new_image_index);
-            } else if set_index == ui_data.selected_idx.0 {
-                ui_data.selected_idx.1 = 0;
-            } else if set_index < ui_data.selected_idx.0 {
-                ui_data.selected_idx.0 -= 1;
+                ui_data.selection.select((new_set_index, new_image_index));
+            } else if set_index == ui_data.selection.current.0 {
+                ui_data.selection.select((set_index, 0));
+            } else if set_index < ui_data.selection.current.0 {
+                let (set_i, img_i) = ui_data.selection.current;
+                ui_data.selection.select((set_i - 1, img_i));
+            }
+        }
+    }
+
+    /// Moves an image set to a new position in the list.
+    fn move_image_set(&mut self, from: usize, to: usize) {
+        fn move_item<T>(list: &mut Vec<T>, from: usize, to: usize) {
+            if from < list.len() && to < list.len() {
+                let item = list.remove(from);
+                list.insert(to, item);
+            }
+        }
+
+        move_item(&mut *self.histogram_sets.lock_mut(), from, to);
+        let mut ui_data = self.ui_data.lock_mut();
+        move_item(&mut ui_data.thumbnail_sets, from, to);
+        move_item(&mut ui_data.set_names, from, to);
+        ui_data.selection.select((to, 0));
+        ui_data.move_error = None;
+    }
+
+    /// Moves an image to another place in its set or into another set,
+    /// keeping the set sorted by exposure.  Returns whether the image
+    /// was moved.
+    ///
+    /// Images can only be moved into sets of the same resolution.
+    fn move_image(
+        &mut self,
+        (set_index, image_index): (usize, usize),
+        (to_set, to_index): (usize, usize),
+    ) -> bool {
+        {
+            let mut histogram_sets = self.histogram_sets.lock_mut();
+            let mut ui_data = self.ui_data.lock_mut();
+            if set_index >= histogram_sets.len()
+                || to_set >= histogram_sets.len()
+                || image_index >= histogram_sets[set_index].len()
+            {
+                return false;
+            }
+
+            let info = &histogram_sets[set_index][image_index].1;
+            if let Some((_, target_info)) = histogram_sets[to_set]
+                .first()
+                .filter(|_| to_set != set_index)
+            {
+                if info.width != target_info.width || info.height != target_info.height {
+                    ui_data.move_error = Some(format!(
+                        "Can't move \"{}\": all images in a set must have the same resolution.",
+                        info.filename
+                    ));
+                    return false;
+                }
+            }
+            ui_data.move_error = None;
+
+            // Sets stay sorted by exposure, so the drop position only
+            // orders images whose exposures are equal or unknown.
+            let image = histogram_sets[set_index].remove(image_index);
+            let key = (image.1.full_filepath.clone(), image.1.filename.clone());
+            let set = &mut histogram_sets[to_set];
+            set.insert(to_index.min(set.len()), image);
+            set.sort_by(|a, b| a.1.exposure.partial_cmp(&b.1.exposure).unwrap());
+
+            let thumbnail = ui_data.thumbnail_sets[set_index].remove(image_index);
+            let set = &mut ui_data.thumbnail_sets[to_set];
+            set.insert(to_index.min(set.len()), thumbnail);
+            set.sort_by(|a, b| a.3.exposure.partial_cmp(&b.3.exposure).unwrap());
+
+            let new_index = set
+                .iter()
+                .position(|t| t.3.full_filepath == key.0 && t.3.filename == key.1)
+                .unwrap_or(0);
+            ui_data.selection.select((to_set, new_index));
+        }
+
+        // The sets' most common ISO and aperture may have changed.
+        if self.uses_exif() {
+            update_bracket_exposures(&self.histogram_sets, &self.ui_data, set_index);
+            update_bracket_exposures(&self.histogram_sets, &self.ui_data, to_set);
+        }
+
+        // Don't leave empty sets behind.
+        if self.histogram_sets.lock()[set_index].is_empty() {
+            self.remove_image_set(set_index);
+        }
+
+        true
+    }
+}
+
+/// Recomputes the exposures of a set's images from their metadata, with
+/// or without ISO/aperture compensation as set in the UI, and re-sorts
+/// the set.
+fn update_bracket_exposures(
+    histogram_sets: &Shared<Vec<Vec<([Histogram; 3], ImageInfo)>>>,
+    ui_data: &Shared<UiData>,
+    set_index: usize,
+) {
+    let compensate = ui_data.lock().compensate_settings;
+    let exposures: Vec<((String, String), f32)> = {
+        let histogram_sets = histogram_sets.lock();
+        let set = match histogram_sets.get(set_index) {
+            Some(set) => set,
+            None => return,
+        };
+        let infos: Vec<&ImageInfo> = set.iter().map(|(_, info)| info).collect();
+        lib::bracket_analysis::bracket_exposures(&infos, compensate)
+            .iter()
+            .zip(infos.iter())
+            .filter_map(|(exposure, info)| {
+                exposure.map(|e| ((info.full_filepath.clone(), info.filename.clone()), e))
+            })
+            .collect()
+    };
+    let exposure_of = |info: &ImageInfo| {
+        exposures
+            .iter()
+            .find(|(key, _)| info.full_filepath == key.0 && info.filename == key.1)
+            .map(|(_, exposure)| *exposure)
+    };
+
+    // Stable sorts, so that images with equal exposures stay in the same
+    // order as their thumbnails.
+    if let Some(set) = histogram_sets.lock_mut().get_mut(set_index) {
+        for (_, info) in set.iter_mut() {
+            if let Some(exposure) = exposure_of(info) {
+                info.exposure = Some(exposure);
             }
         }
+        set.sort_by(|a, b| a.1.exposure.partial_cmp(&b.1.exposure).unwrap());
+    }
+    if let Some(set) = ui_data.lock_mut().thumbnail_sets.get_mut(set_index) {
+        for (_, _, _, info) in set.iter_mut() {
+            if let Some(exposure) = exposure_of(info) {
+                info.exposure = Some(exposure);
+            }
+        }
+        set.sort_by(|a, b| a.3.exposure.partial_cmp(&b.3.exposure).unwrap());
+    }
+}
 
-        // // Update the exposure mappings.
-        // self.compute_exposure_mappings();
+/// Notes about problems with an image, for the image list.  `first`
+/// is the first image of its set.
+fn image_badges(
+    info: &ImageInfo,
+    first: &ImageInfo,
+    use_sets: bool,
+    uses_exif: bool,
+    mismatch: SettingsMismatch,
+    rejection: Option<(Rejection, &str)>, // (rejection, filename of the image it duplicates)
+) -> Vec<ImageBadge> {
+    let mut badges = Vec::new();
+    if uses_exif && info.exposure.is_none() {
+        badges.push(ImageBadge::warning(
+            "Exif",
+            "The image lacks the Exif data needed to compute its exposure, so it's excluded from transfer function, floor, and ceiling estimation.  Its exposure can be entered with \"Enter Exposures...\".",
+        ));
+    }
+    if use_sets && (info.width != first.width || info.height != first.height) {
+        badges.push(ImageBadge::warning(
+            "Resolution",
+            "The image's resolution differs from the other images in its set.",
+        ));
+    }
+    if mismatch.iso {
+        badges.push(ImageBadge::warning(
+            "ISO",
+            "The image's ISO differs from most of the other images in its set, or is missing.  Bracketing should only vary shutter speed, since ISO changes may not scale the image by exactly the nominal amount.",
+        ));
+    }
+    if mismatch.aperture {
+        badges.push(ImageBadge::warning(
+            "Aperture",
+            "The image's aperture differs from most of the other images in its set, or is missing.  Bracketing should only vary shutter speed, since aperture changes also change depth of field and vignetting.",
+        ));
+    }
+    match rejection {
+        Some((Rejection::Duplicate(_), original)) => badges.push(ImageBadge::warning(
+            "Duplicate",
+            &format!("The image is nearly identical to \"{}\", so it adds nothing to estimation but extra weight.  If their exposures differ, one of them is also wrong.", original),
+        )),
+        Some((Rejection::Outlier, _)) => badges.push(ImageBadge::warning(
+            "Outlier",
+            "The image's brightness doesn't fit its exposure relative to the other images in its set.  The lighting may have changed between shots, or its exposure may be wrong.",
+        )),
+        None => {}
     }
+    badges
+}
+
+/// A video frame that was extracted to a temporary image file.
+struct VideoFrame {
+    name: String,
+    full_path: String,
+    exposure: Option<f32>,
+}
+
+/// A temporary directory that is deleted when dropped.
+struct FrameDir(PathBuf);
+
+impl Drop for FrameDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(feature = "ffmpeg")]
+fn extract_video_frames(
+    path: &std::path::Path,
+    interval: f32,
+) -> std::io::Result<(FrameDir, Vec<PathBuf>)> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let frame_dir = FrameDir(std::env::temp_dir().join(format!(
+        "etf_lut_maker_{}_{}",
+        std::process::id(),
+        DIR_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )));
+    let frame_paths = lib::job_helpers::extract_video_frames(path, interval, &frame_dir.0)?;
+
+    Ok((frame_dir, frame_paths))
+}
+
+#[cfg(not(feature = "ffmpeg"))]
+fn extract_video_frames(
+    _path: &std::path::Path,
+    _interval: f32,
+) -> std::io::Result<(FrameDir, Vec<PathBuf>)> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "video support requires building with the \"ffmpeg\" feature",
+    ))
 }
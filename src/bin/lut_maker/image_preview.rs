@@ -0,0 +1,393 @@
+use std::path::{Path, PathBuf};
+
+use job_queue::{JobQueue, Priority};
+use sensor_analysis::utils::lerp_slice;
+use shared_data::Shared;
+
+use lib::display_profile::DisplayTransform;
+use lib::lut_modify::{Lut3DStack, Trims};
+use lib::{FloatEncoding, ImageInfo};
+
+use crate::egui::{self, Ui};
+use crate::scopes::{self, ScopeKind};
+
+const PREVIEW_WIDTH: usize = 512;
+const PREVIEW_MAX_DISPLAY_HEIGHT: f32 = 300.0;
+
+/// Shows a bracketed image with the current to-linear transform and a
+/// view transform applied, so that the linearization can be checked
+/// visually.  Scopes of the image before and after linearization are
+/// shown alongside it.
+pub struct ImagePreview {
+    source: Shared<Option<PreviewSource>>,
+    requested: Option<String>, // Key of the last requested image.
+    failed: Shared<Option<(String, String)>>, // (key, error) of the last image that failed to load.
+
+    view_transform: ViewTransform,
+    exposure: f32, // In stops.
+    scope_kind: ScopeKind,
+
+    texture: Option<egui::TextureHandle>,
+    scope_textures: Option<[egui::TextureHandle; 2]>, // (before, after)
+    last_settings: Option<PreviewSettings>,
+}
+
+/// The downscaled, still-encoded image that the preview is made from.
+struct PreviewSource {
+    key: String,
+    pixels: Vec<[f32; 3]>,
+    width: usize,
+    height: usize,
+}
+
+/// Everything the preview texture depends on, for detecting when it
+/// needs to be rebuilt.
+#[derive(Clone, PartialEq)]
+struct PreviewSettings {
+    key: String,
+    luts: [(Vec<f32>, f32, f32); 3],
+    lut_3d: Option<(PathBuf, Option<PathBuf>, Option<PathBuf>, Trims)>, // (lut, pre curve, post curve, trims)
+    scale: f32,
+    view_transform: ViewTransform,
+    exposure: f32,
+    scope_kind: ScopeKind,
+    display: DisplayTransform,
+}
+
+impl ImagePreview {
+    pub fn new() -> ImagePreview {
+        ImagePreview {
+            source: Shared::new(None),
+            requested: None,
+            failed: Shared::new(None),
+
+            view_transform: ViewTransform::Srgb,
+            exposure: 0.0,
+            scope_kind: ScopeKind::Waveform,
+
+            texture: None,
+            scope_textures: None,
+            last_settings: None,
+        }
+    }
+
+    fn load_image(
+        &mut self,
+        key: String,
+        path: PathBuf,
+        float_encoding: Option<FloatEncoding>,
+        job_queue: &JobQueue,
+    ) {
+        self.requested = Some(key.clone());
+        let source = self.source.clone_ref();
+        let failed = self.failed.clone_ref();
+
+        job_queue.add_parallel_job("Load Preview Image", Priority::Low, move |status| {
+            status
+                .lock_mut()
+                .set_progress(format!("Loading preview: {}", path.to_string_lossy()), 0.0);
+
+            match lib::job_helpers::load_image(&path) {
+                Ok(_) if status.lock().is_canceled() => {
+                    // Superseded by a newer preview.
+                }
+                Ok(img) => {
+                    let (pixels, width, height) = lib::job_helpers::make_float_preview(
+                        &img,
+                        PREVIEW_WIDTH,
+                        float_encoding.as_ref(),
+                    );
+                    *source.lock_mut() = Some(PreviewSource {
+                        key: key,
+                        pixels: pixels,
+                        width: width,
+                        height: height,
+                    });
+                }
+                Err(e) => {
+                    status.lock_mut().log_error(format!(
+                        "Unable to load image for preview: \"{}\".",
+                        path.to_string_lossy(),
+                    ));
+                    // Recorded so the panel can show the error instead
+                    // of waiting on a preview that will never come.
+                    *failed.lock_mut() = Some((key, e.to_string()));
+                }
+            }
+        });
+    }
+
+    /// Rebuilds the preview texture if anything it depends on has
+    /// changed.
+    ///
+    /// If given, `lut_3d` is used instead of the LUTs in the settings.
+    fn update_texture(
+        &mut self,
+        ctx: &egui::Context,
+        settings: PreviewSettings,
+        lut_3d: Option<(&Lut3DStack, &Trims)>,
+    ) {
+        if self.last_settings.as_ref() == Some(&settings) {
+            return;
+        }
+
+        let source_guard = self.source.lock();
+        let source = match &*source_guard {
+            Some(source) if source.key == settings.key => source,
+            _ => return,
+        };
+
+        let exposure = 2.0f32.powf(settings.exposure) * settings.scale;
+        let linear: Vec<[f32; 3]> = source
+            .pixels
+            .iter()
+            .map(|pixel| {
+                if let Some((stack, trims)) = lut_3d {
+                    let out = stack.eval(*pixel, trims);
+                    return [out[0] * exposure, out[1] * exposure, out[2] * exposure];
+                }
+
+                let mut out = [0.0f32; 3];
+                for chan in 0..3 {
+                    let (ref table, min, max) = settings.luts[chan];
+                    let n = ((pixel[chan] - min) / (max - min)).max(0.0).min(1.0);
+                    out[chan] = lerp_slice(table, n) * exposure;
+                }
+                out
+            })
+            .collect();
+
+        let mut bytes = Vec::with_capacity(linear.len() * 3);
+        for pixel in linear.iter() {
+            for chan in 0..3 {
+                let display = settings.view_transform.apply(pixel[chan]);
+                bytes.push((display.max(0.0).min(1.0) * 255.0).round() as u8);
+            }
+        }
+
+        settings.display.apply(&mut bytes, 3);
+
+        let image = egui::ColorImage::from_rgb([source.width, source.height], &bytes);
+        set_texture(&mut self.texture, ctx, "Image Preview", image);
+
+        // Scopes.
+        let scope_images = [
+            scopes::render_scope(
+                settings.scope_kind,
+                &source.pixels,
+                source.width,
+                source.height,
+            ),
+            scopes::render_scope(settings.scope_kind, &linear, source.width, source.height),
+        ];
+        match self.scope_textures {
+            Some(ref mut textures) => {
+                for (texture, image) in textures.iter_mut().zip(scope_images) {
+                    texture.set(image, egui::TextureOptions::NEAREST);
+                }
+            }
+            None => {
+                let [before, after] = scope_images;
+                self.scope_textures = Some([
+                    ctx.load_texture("Scope Before", before, egui::TextureOptions::NEAREST),
+                    ctx.load_texture("Scope After", after, egui::TextureOptions::NEAREST),
+                ]);
+            }
+        }
+
+        self.last_settings = Some(settings);
+    }
+}
+
+pub fn preview_ui(ui: &mut Ui, app: &mut crate::AppMain) {
+    egui::containers::CollapsingHeader::new("Image Preview").show(ui, |ui| {
+        let info = match app.bracket_image_sets.selected_image() {
+            Some(info) => info,
+            None => {
+                ui.label("Select a bracketed image to preview it.");
+                return;
+            }
+        };
+        if lib::job_helpers::is_video_file(Path::new(&info.full_filepath)) {
+            ui.label("Previews aren't available for video frames.");
+            return;
+        }
+
+        // Load the image if it's newly selected.
+        let key = image_key(&info);
+        if app.image_preview.requested.as_ref() != Some(&key) {
+            app.image_preview.load_image(
+                key.clone(),
+                info.full_filepath.clone().into(),
+                app.bracket_image_sets.float_encoding(),
+                &app.job_queue,
+            );
+        }
+
+        // Controls.
+        ui.horizontal(|ui| {
+            let preview = &mut app.image_preview;
+            egui::ComboBox::from_label("View transform")
+                .selected_text(preview.view_transform.ui_text())
+                .show_ui(ui, |ui| {
+                    for vt in VIEW_TRANSFORMS.iter() {
+                        ui.selectable_value(&mut preview.view_transform, *vt, vt.ui_text());
+                    }
+                });
+            ui.add_space(16.0);
+            ui.add(
+                egui::widgets::Slider::new(&mut preview.exposure, -8.0..=8.0)
+                    .max_decimals(1)
+                    .text("Exposure (stops)"),
+            );
+            ui.add_space(16.0);
+            egui::ComboBox::from_label("Scope")
+                .selected_text(preview.scope_kind.ui_text())
+                .show_ui(ui, |ui| {
+                    for kind in scopes::SCOPE_KINDS.iter() {
+                        ui.selectable_value(&mut preview.scope_kind, *kind, kind.ui_text());
+                    }
+                });
+        });
+        ui.add_space(4.0);
+
+        // Update and draw the preview.
+        {
+            let ui_data = app.ui_data.lock();
+            let luts = ui_data.to_linear_luts(true);
+            let scale = ui_data.calibration.scale(&luts);
+            let lut_3d = if ui_data.mode == crate::AppMode::Modify {
+                ui_data
+                    .modified
+                    .loaded_lut_3d
+                    .as_ref()
+                    .map(|stack| (stack, &ui_data.modified.trims))
+            } else {
+                None
+            };
+            let settings = PreviewSettings {
+                key: key,
+                luts: luts,
+                lut_3d: lut_3d.map(|(stack, trims)| {
+                    (
+                        stack.path.clone(),
+                        stack.pre_curve.as_ref().map(|(_, path)| path.clone()),
+                        stack.post_curve.as_ref().map(|(_, path)| path.clone()),
+                        *trims,
+                    )
+                }),
+                scale: scale,
+                view_transform: app.image_preview.view_transform,
+                exposure: app.image_preview.exposure,
+                scope_kind: app.image_preview.scope_kind,
+                display: app.display.clone(),
+            };
+            app.image_preview.update_texture(ui.ctx(), settings, lut_3d);
+        }
+
+        let preview = &app.image_preview;
+        let is_current =
+            preview.last_settings.as_ref().map(|s| &s.key) == preview.requested.as_ref();
+        if let Some((ref key, ref error)) = *preview.failed.lock() {
+            if preview.requested.as_ref() == Some(key) {
+                ui.colored_label(
+                    lib::colors::RED,
+                    format!("Unable to load the image for preview: {}", error),
+                );
+                return;
+            }
+        }
+        match (&preview.texture, &preview.scope_textures) {
+            (Some(texture), Some([scope_before, scope_after])) if is_current => {
+                // Leave room for the scopes to the right.
+                let [width, height] = texture.size();
+                let display_scale = ((ui.available_width() - scopes::SCOPE_WIDTH as f32 - 16.0)
+                    / width as f32)
+                    .min(PREVIEW_MAX_DISPLAY_HEIGHT / height as f32)
+                    .max(0.1);
+
+                ui.horizontal_top(|ui| {
+                    ui.add(egui::Image::new(texture).fit_to_exact_size(egui::Vec2::new(
+                        width as f32 * display_scale,
+                        height as f32 * display_scale,
+                    )));
+                    ui.add_space(16.0);
+                    ui.vertical(|ui| {
+                        let scope_size = egui::Vec2::new(
+                            scopes::SCOPE_WIDTH as f32,
+                            scopes::SCOPE_HEIGHT as f32,
+                        );
+                        ui.label("Before linearization");
+                        ui.add(egui::Image::new(scope_before).fit_to_exact_size(scope_size));
+                        ui.add_space(4.0);
+                        ui.label("After linearization");
+                        ui.add(egui::Image::new(scope_after).fit_to_exact_size(scope_size));
+                    });
+                });
+            }
+            _ => {
+                ui.label("Loading preview...");
+            }
+        }
+    });
+}
+
+fn set_texture(
+    texture: &mut Option<egui::TextureHandle>,
+    ctx: &egui::Context,
+    name: &str,
+    image: egui::ColorImage,
+) {
+    match texture {
+        Some(ref mut texture) => texture.set(image, egui::TextureOptions::LINEAR),
+        None => *texture = Some(ctx.load_texture(name, image, egui::TextureOptions::LINEAR)),
+    }
+}
+
+fn image_key(info: &ImageInfo) -> String {
+    format!("{}\n{}", info.full_filepath, info.filename)
+}
+
+//-------------------------------------------------------------
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ViewTransform {
+    Srgb,
+    Log,
+    Linear,
+}
+
+const VIEW_TRANSFORMS: &[ViewTransform] = &[
+    ViewTransform::Srgb,
+    ViewTransform::Log,
+    ViewTransform::Linear,
+];
+
+impl ViewTransform {
+    fn ui_text(&self) -> &'static str {
+        match *self {
+            ViewTransform::Srgb => "sRGB",
+            ViewTransform::Log => "Log (-8 to +4 stops)",
+            ViewTransform::Linear => "None (linear)",
+        }
+    }
+
+    /// Maps a linear scene value to a display value.
+    fn apply(&self, n: f32) -> f32 {
+        match *self {
+            ViewTransform::Srgb => {
+                if n <= 0.0031308 {
+                    n * 12.92
+                } else {
+                    (1.055 * n.powf(1.0 / 2.4)) - 0.055
+                }
+            }
+            ViewTransform::Log => {
+                // Maps 18% gray to two thirds of the way up.
+                let stops = (n.max(1.0e-6) / 0.18).log2();
+                (stops + 8.0) / 12.0
+            }
+            ViewTransform::Linear => n,
+        }
+    }
+}
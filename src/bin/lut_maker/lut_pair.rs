@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use lib::chroma_space::ChromaSpace;
+
+/// File names for a matched pair of LUTs, as `(to_linear, from_linear)`,
+/// following the `<camera>_to_linear.<ext>` and `linear_to_<camera>.<ext>`
+/// convention.
+pub fn file_names(camera_name: &str, ext: &str) -> (String, String) {
+    let camera = file_name_safe(camera_name);
+    (
+        format!("{}_to_linear.{}", camera, ext),
+        format!("linear_to_{}.{}", camera, ext),
+    )
+}
+
+/// File name of the camera space sidecar file that describes a LUT
+/// pair, e.g. `<camera>.camera_space.json`.
+pub fn sidecar_file_name(camera_name: &str) -> String {
+    format!(
+        "{}.{}",
+        file_name_safe(camera_name),
+        lib::camera_space::SIDECAR_EXTENSION
+    )
+}
+
+/// Lowercases the name and replaces anything that isn't alphanumeric
+/// with underscores.
+fn file_name_safe(name: &str) -> String {
+    let mut safe = String::new();
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            safe.extend(c.to_lowercase());
+        } else if !safe.ends_with('_') {
+            safe.push('_');
+        }
+    }
+    let safe = safe.trim_matches('_');
+    if safe.is_empty() {
+        "camera".into()
+    } else {
+        safe.into()
+    }
+}
+
+/// Adds an input color space that uses the given to-linear LUT to a
+/// config made by OCIO Maker, optionally with the camera's gamut.
+///
+/// The color space is added to the settings that OCIO Maker stores in
+/// the config's header comment, so it shows up the next time the config
+/// is loaded in OCIO Maker.  The config needs to be re-exported from OCIO
+/// Maker for the color space to actually be usable.
+///
+/// The config is replaced via a staged write, so it's never left
+/// half-written, and keeps its line endings.
+pub fn register_in_ocio_config(
+    config_path: &Path,
+    space_name: &str,
+    to_linear_lut_path: &Path,
+    chroma_space: Option<ChromaSpace>,
+) -> Result<(), String> {
+    let text = std::fs::read_to_string(config_path).map_err(|e| e.to_string())?;
+    if !text.starts_with("# Generated by ETF OCIO Maker") {
+        return Err("not an ETF OCIO Maker-generated config".into());
+    }
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+
+    // OCIO Maker stores LUT paths relative to the config's directory.
+    let base: PathBuf = config_path
+        .parent()
+        .map(|p| p.into())
+        .unwrap_or_else(|| ".".into());
+    let base = base.canonicalize().unwrap_or(base);
+    let lut_path = to_linear_lut_path
+        .canonicalize()
+        .unwrap_or_else(|_| to_linear_lut_path.into());
+    let lut_path = pathdiff::diff_paths(&lut_path, &base).unwrap_or(lut_path);
+
+    // Insert after the last separator line of the header comment.
+    let mut insert_at = None;
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        if !line.starts_with('#') {
+            break;
+        }
+        line_start += line.len();
+        if line.trim_end() == "# ----" {
+            insert_at = Some(line_start);
+        }
+    }
+    let insert_at = insert_at.ok_or("config header is malformed")?;
+
+    let mut lines = vec![format!("# color_space: {}", space_name.trim())];
+    lines.push("# include_as_display: false".into());
+    if let Some(chroma_space) = chroma_space {
+        lines.push(format!("# chroma_space: {}", chroma_space.as_str()));
+    }
+    lines.push(format!(
+        "# transfer_lut_path: {}",
+        lut_path.to_string_lossy()
+    ));
+    lines.push("# transfer_lut_use_inverse: false".into());
+    lines.push("# ----".into());
+
+    let mut new_text = String::new();
+    new_text.push_str(&text[..insert_at]);
+    for line in lines.iter() {
+        new_text.push_str(line);
+        new_text.push_str(newline);
+    }
+    new_text.push_str(&text[insert_at..]);
+
+    ocio_gen::config::write_file_staged(config_path, new_text.as_bytes()).map_err(|e| e.to_string())
+}
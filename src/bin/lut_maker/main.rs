@@ -5,35 +5,73 @@ use std::path::PathBuf;
 use eframe::egui;
 use egui::containers::Frame;
 
+use job_queue::{JobId, Priority};
+use ocio_gen::lut_extrapolation::{ExtrapolatedLut, Extrapolation};
 use sensor_analysis::{utils::lerp_slice, ExposureMapping, Histogram};
 use shared_data::Shared;
 
+use lib::camera_space::{CameraSpace, LutDirection};
+use lib::chroma_space::ChromaSpace;
+use lib::display_profile::DisplayTransform;
+use lib::lut_formats::{LutMetadata, Quantization};
+use lib::preferences::Preferences;
+use lib::undo::{HistoryAction, UndoStack};
 use lib::ImageInfo;
 
+mod camera_profiles;
+mod cli;
+mod code_value;
 mod estimated_tf;
+mod exposure_calibration;
 mod generated_tf;
 mod graph;
 mod image_list;
+mod image_preview;
+mod lut_pair;
 mod menu;
 mod mode_and_export_bar;
 mod modified_tf;
+mod profile_wizard;
+mod scopes;
+
+use estimated_tf::MAX_ESTIMATION_ROUNDS;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const APP_NAME: &str = "lut_maker";
+
+// The second worker lets previews load while longer jobs are running.
+const DEFAULT_WORKER_COUNT: usize = 2;
+
+/// How far beyond their domain 1D LUTs are extended when exporting with
+/// an extrapolation other than clamping, as a fraction of the domain.
+const EXTRAPOLATION_MARGIN: f32 = 0.25;
 
 fn main() {
-    clap::App::new("ETF LUT Maker")
-        .version(VERSION)
-        .author("Nathan Vegdahl, Ian Hubert")
-        .about("Does all things color space")
-        .get_matches();
+    let args = cli::add_args(
+        clap::App::new("ETF LUT Maker")
+            .version(VERSION)
+            .author("Nathan Vegdahl, Ian Hubert")
+            .about("Does all things color space"),
+    )
+    .get_matches();
+
+    if args.is_present("cli") {
+        std::process::exit(cli::run(&args));
+    }
+
+    let preferences = Preferences::load(APP_NAME);
+    let mut viewport = egui::ViewportBuilder::default().with_drag_and_drop(true); // Enable drag-and-dropping files on Windows.
+    if let Ok(preferences) = &preferences {
+        viewport = preferences.apply_to_viewport(viewport);
+    }
 
     eframe::run_native(
         "LUT Maker",
         eframe::NativeOptions {
-            viewport: egui::ViewportBuilder::default().with_drag_and_drop(true), // Enable drag-and-dropping files on Windows.
+            viewport: viewport,
             ..eframe::NativeOptions::default()
         },
-        Box::new(|cc| Box::new(AppMain::new(cc))),
+        Box::new(|cc| Box::new(AppMain::new(cc, preferences))),
     )
     .expect("Couldn't start application.");
 }
@@ -41,47 +79,101 @@ fn main() {
 pub struct AppMain {
     job_queue: job_queue::JobQueue,
     last_opened_directory: Option<PathBuf>,
+    preferences: Preferences,
+    display: DisplayTransform, // For previews.  Updated from the preferences.
 
     bracket_image_sets: image_list::ImageList,
+    bracket_images_watch: shared_data::Watch<Vec<Vec<([Histogram; 3], ImageInfo)>>>,
+    preselected_camera: Option<&'static camera_db::CameraInfo>, // Last camera whose curve was pre-selected.
     dark_images: image_list::ImageList,
     transfer_function_tables: Shared<Option<([Vec<f32>; 3], f32, f32)>>, // (table, x_min, x_max)
+    camera_profiles: camera_profiles::CameraProfiles,
+    image_preview: image_preview::ImagePreview,
+    profile_wizard: profile_wizard::ProfileWizard,
+    undo_stack: UndoStack<EditState>,
 
     ui_data: Shared<UIData>,
 }
 
 impl AppMain {
-    fn new(cc: &eframe::CreationContext) -> AppMain {
+    fn new(cc: &eframe::CreationContext, preferences: std::io::Result<Preferences>) -> AppMain {
         // Dark mode.
         cc.egui_ctx.set_visuals(egui::style::Visuals {
             dark_mode: true,
             ..egui::style::Visuals::default()
         });
 
+        let (preferences, preferences_error) = match preferences {
+            Ok(preferences) => (preferences, None),
+            Err(e) => (Preferences::default(), Some(e)),
+        };
+
         // Update callback for jobs.
-        let mut job_queue = job_queue::JobQueue::new();
+        let mut job_queue =
+            job_queue::JobQueue::with_worker_count(preferences.worker_count(DEFAULT_WORKER_COUNT));
         let ctx_clone = cc.egui_ctx.clone();
         job_queue.set_update_fn(move || {
             ctx_clone.request_repaint();
         });
 
+        // Keep a log of the session on disk, for bug reports.
+        if let Some(path) = lib::user_dirs::log_path(APP_NAME) {
+            if let Err(e) = job_queue.set_log_file(&path) {
+                job_queue.log_warning(format!(
+                    "Unable to open log file \"{}\": {}",
+                    path.to_string_lossy(),
+                    e
+                ));
+            }
+        }
+        if let Some(e) = preferences_error {
+            job_queue.log_warning(format!("Unable to load preferences: {}", e));
+        }
+
+        let export_format = EXPORT_FORMATS
+            .iter()
+            .copied()
+            .find(|f| Some(f.id()) == preferences.export_format.as_deref())
+            .unwrap_or(ExportFormat::Cube);
+
+        // Dark images need to be analyzed in the same way as the
+        // bracketed images for the noise floor estimate to apply.
+        let bracket_image_sets = image_list::ImageList::new(true, true);
+        let mut dark_images = image_list::ImageList::new(false, false);
+        dark_images.share_float_encoding(&bracket_image_sets);
+
         AppMain {
             job_queue: job_queue,
-            last_opened_directory: None,
-
-            bracket_image_sets: image_list::ImageList::new(true, true),
-            dark_images: image_list::ImageList::new(false, false),
+            last_opened_directory: preferences.last_directory.clone(),
+            preferences: preferences,
+            display: DisplayTransform::srgb(),
+
+            bracket_images_watch: bracket_image_sets.histogram_sets.watch(),
+            preselected_camera: None,
+            bracket_image_sets: bracket_image_sets,
+            dark_images: dark_images,
             transfer_function_tables: Shared::new(None),
+            camera_profiles: camera_profiles::CameraProfiles::new(),
+            image_preview: image_preview::ImagePreview::new(),
+            profile_wizard: profile_wizard::ProfileWizard::new(),
+            undo_stack: UndoStack::new(100),
 
             ui_data: Shared::new(UIData {
                 image_view: ImageViewID::Dark,
                 mode: AppMode::Generate,
-                export_format: ExportFormat::Cube,
+                export_format: export_format,
+                export_options: ExportOptions::new(),
+                show_export_options: false,
                 preview_mode: graph::PreviewMode::ToLinear,
+                graph_axes: [graph::AxisScale::Linear; 2],
+                code_value_bits: 10,
+                register_lut_pair: false,
 
                 generated: generated_tf::GeneratedTF::new(),
                 estimated: estimated_tf::EstimatedTF::new(),
                 modified: modified_tf::ModifiedTF::new(),
 
+                calibration: exposure_calibration::ExposureCalibration::new(),
                 exposure_mappings: [Vec::new(), Vec::new(), Vec::new()],
             }),
         }
@@ -96,7 +188,12 @@ pub struct UIData {
     image_view: ImageViewID,
     mode: AppMode,
     export_format: ExportFormat,
+    export_options: ExportOptions,
+    show_export_options: bool,
     preview_mode: graph::PreviewMode,
+    graph_axes: [graph::AxisScale; 2], // (x, y)
+    code_value_bits: u32,              // Bit depth for entering floor/ceiling code values.
+    register_lut_pair: bool,           // Whether to add exported LUT pairs to an OCIO Maker config.
 
     // Mode-specific data.
     generated: generated_tf::GeneratedTF,
@@ -104,9 +201,186 @@ pub struct UIData {
     modified: modified_tf::ModifiedTF,
 
     // Data that's shared between the modes.
+    calibration: exposure_calibration::ExposureCalibration,
     pub exposure_mappings: [Vec<ExposureMapping>; 3],
 }
 
+impl UIData {
+    /// Builds the to-linear luts of the current mode, with the floor
+    /// and ceiling applied, as `(table, input_min, input_max)` for each
+    /// channel.
+    ///
+    /// `normalize` determines whether the output is normalized to
+    /// [0.0, 1.0] for fixed functions with a larger native range.
+    fn to_linear_luts(&self, normalize: bool) -> [(Vec<f32>, f32, f32); 3] {
+        match self.mode {
+            AppMode::Generate => {
+                let floor = if self.generated.sensor_floor.0 {
+                    Some(self.generated.sensor_floor.1)
+                } else {
+                    None
+                };
+                let ceiling = if self.generated.sensor_ceiling.0 {
+                    Some(self.generated.sensor_ceiling.1)
+                } else {
+                    None
+                };
+
+                let res = self.generated.transfer_function_resolution;
+                let res_norm = 1.0 / (res - 1) as f32;
+                let table = |chan: usize| -> Vec<f32> {
+                    (0..res)
+                        .map(|i| {
+                            self.generated.transfer_function.to_linear_fc(
+                                i as f32 * res_norm,
+                                floor.map(|f| f[chan]),
+                                ceiling.map(|c| c[chan]),
+                                normalize,
+                            )
+                        })
+                        .collect()
+                };
+
+                [
+                    (table(0), 0.0, 1.0),
+                    (table(1), 0.0, 1.0),
+                    (table(2), 0.0, 1.0),
+                ]
+            }
+
+            AppMode::Estimate => {
+                let simple = [vec![0.0, 1.0], vec![0.0, 1.0], vec![0.0, 1.0]];
+                let luts = if let Some((luts, _)) = &self.estimated.transfer_function_preview {
+                    luts
+                } else {
+                    &simple
+                };
+                let norms = self.estimated.floor_ceiling_norms(luts);
+                let table = |chan: usize| -> Vec<f32> {
+                    let (out_floor, out_norm) = norms[chan];
+                    luts[chan]
+                        .iter()
+                        .map(|y| (y - out_floor) * out_norm)
+                        .collect()
+                };
+
+                [
+                    (table(0), 0.0, 1.0),
+                    (table(1), 0.0, 1.0),
+                    (table(2), 0.0, 1.0),
+                ]
+            }
+
+            AppMode::Modify => {
+                if let Some(luts) = self.modified.neutral_axis_lut() {
+                    luts
+                } else if let Some(luts) = self.modified.adjusted_lut(true) {
+                    luts
+                } else {
+                    [
+                        (vec![0.0, 1.0], 0.0, 1.0),
+                        (vec![0.0, 1.0], 0.0, 1.0),
+                        (vec![0.0, 1.0], 0.0, 1.0),
+                    ]
+                }
+            }
+        }
+    }
+}
+
+/// The user-editable state covered by undo/redo.
+///
+/// Derived data like estimated curves and exposure mappings is left
+/// out, since it's recomputed from this.
+#[derive(Clone, PartialEq)]
+struct EditState {
+    bracket_images: image_list::Snapshot,
+    dark_images: image_list::Snapshot,
+
+    generated: generated_tf::GeneratedTF,
+    estimated_floor: [f32; 3],
+    estimated_ceiling: [f32; 3],
+    estimated_convergence: f32,
+    estimated_smoothness: f32,
+    estimated_shared_curve: bool,
+    estimated_detect_knee: bool,
+    estimated_adjust: (bool, bool), // (floor, ceiling)
+    estimated_anchors: Vec<(f32, f32)>,
+    modified_floor: (bool, [f32; 3]),
+    modified_ceiling: (bool, [f32; 3]),
+    modified_gamut: modified_tf::GamutStage,
+    modified_curve_edit: modified_tf::CurveEdit,
+    modified_trims: lib::lut_modify::Trims,
+    calibration: exposure_calibration::ExposureCalibration,
+}
+
+impl AppMain {
+    fn edit_state(&self) -> EditState {
+        let previous = self.undo_stack.current();
+        let ui_data = self.ui_data.lock();
+        EditState {
+            bracket_images: self
+                .bracket_image_sets
+                .snapshot(previous.map(|s| &s.bracket_images)),
+            dark_images: self.dark_images.snapshot(previous.map(|s| &s.dark_images)),
+
+            generated: ui_data.generated.clone(),
+            estimated_floor: ui_data.estimated.sensor_floor,
+            estimated_ceiling: ui_data.estimated.sensor_ceiling,
+            estimated_convergence: ui_data.estimated.convergence,
+            estimated_smoothness: ui_data.estimated.smoothness,
+            estimated_shared_curve: ui_data.estimated.shared_curve,
+            estimated_detect_knee: ui_data.estimated.detect_knee,
+            estimated_adjust: (
+                ui_data.estimated.adjust_floor,
+                ui_data.estimated.adjust_ceiling,
+            ),
+            estimated_anchors: ui_data.estimated.anchors.clone(),
+            modified_floor: ui_data.modified.sensor_floor,
+            modified_ceiling: ui_data.modified.sensor_ceiling,
+            modified_gamut: ui_data.modified.gamut,
+            modified_curve_edit: ui_data.modified.curve_edit.clone(),
+            modified_trims: ui_data.modified.trims,
+            calibration: ui_data.calibration.clone(),
+        }
+    }
+
+    fn apply_history_action(&mut self, action: HistoryAction) {
+        let state = match action {
+            HistoryAction::Undo => self.undo_stack.undo(),
+            HistoryAction::Redo => self.undo_stack.redo(),
+        };
+        let state = if let Some(state) = state {
+            state
+        } else {
+            return;
+        };
+
+        self.bracket_image_sets.restore(&state.bracket_images);
+        self.dark_images.restore(&state.dark_images);
+
+        {
+            let mut ui_data = self.ui_data.lock_mut();
+            ui_data.generated = state.generated;
+            ui_data.estimated.sensor_floor = state.estimated_floor;
+            ui_data.estimated.sensor_ceiling = state.estimated_ceiling;
+            ui_data.estimated.convergence = state.estimated_convergence;
+            ui_data.estimated.smoothness = state.estimated_smoothness;
+            ui_data.estimated.shared_curve = state.estimated_shared_curve;
+            ui_data.estimated.detect_knee = state.estimated_detect_knee;
+            ui_data.estimated.adjust_floor = state.estimated_adjust.0;
+            ui_data.estimated.adjust_ceiling = state.estimated_adjust.1;
+            ui_data.estimated.anchors = state.estimated_anchors;
+            ui_data.modified.sensor_floor = state.modified_floor;
+            ui_data.modified.sensor_ceiling = state.modified_ceiling;
+            ui_data.modified.gamut = state.modified_gamut;
+            ui_data.modified.curve_edit = state.modified_curve_edit;
+            ui_data.modified.trims = state.modified_trims;
+            ui_data.calibration = state.calibration;
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum ImageViewID {
     Dark,
@@ -128,7 +402,20 @@ impl eframe::App for AppMain {
         // Don't need to do anything.
     }
 
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.preferences.last_directory = self.last_opened_directory.clone();
+        self.preferences.export_format = Some(self.ui_data.lock().export_format.id().into());
+        if let Err(e) = self.preferences.save(APP_NAME) {
+            self.job_queue
+                .log_error(format!("Unable to save preferences: {}", e));
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.preferences.update_window(ctx);
+        if self.display.profile() != &self.preferences.display_profile {
+            self.update_display_transform();
+        }
         let job_count = self.job_queue.job_count();
         let total_bracket_images = self.bracket_image_sets.total_image_count();
         let total_dark_images = self.dark_images.total_image_count();
@@ -141,7 +428,15 @@ impl eframe::App for AppMain {
         //----------------
         // GUI.
 
-        menu::menu_bar(ctx);
+        menu::menu_bar(ctx, self, job_count);
+        profile_wizard::wizard_window(
+            ctx,
+            self,
+            job_count,
+            total_bracket_images,
+            total_dark_images,
+            &mut working_dir,
+        );
 
         // Status bar and log (footer).
         egui_custom::status_bar(ctx, &self.job_queue);
@@ -151,8 +446,13 @@ impl eframe::App for AppMain {
             .min_width(200.0)
             .resizable(false)
             .show(ctx, |ui| {
-                // View selector.
+                // Camera profile selector.
                 ui.add_space(8.0);
+                camera_profiles::profile_ui(ui, self, job_count);
+
+                ui.add(egui::widgets::Separator::default().spacing(16.0));
+
+                // View selector.
                 {
                     let image_view = &mut self.ui_data.lock_mut().image_view;
                     egui::ComboBox::from_id_source("Image View Selector")
@@ -187,15 +487,13 @@ impl eframe::App for AppMain {
                         );
                     }
                     ImageViewID::Bracketed => {
-                        if self.bracket_image_sets.draw(
+                        self.bracket_image_sets.draw(
                             ctx,
                             ui,
                             &self.job_queue,
                             job_count == 0,
                             &mut working_dir,
-                        ) {
-                            self.compute_exposure_mappings();
-                        }
+                        );
                     }
                 }
             });
@@ -247,6 +545,12 @@ impl eframe::App for AppMain {
                     }
                 }
 
+                ui.add_space(8.0);
+                exposure_calibration::calibration_ui(ui, self, job_count, &mut working_dir);
+
+                ui.add_space(8.0);
+                image_preview::preview_ui(ui, self);
+
                 ui.add_space(18.0);
 
                 // Graph view.
@@ -280,13 +584,93 @@ impl eframe::App for AppMain {
                         .add_image_files(dropped_file_list, ctx, &self.job_queue)
                 }
             }
-            self.compute_exposure_mappings();
+        }
+
+        // Keep the exposure mappings up-to-date with the bracketed
+        // images.  This waits for jobs to finish, so that loading many
+        // images only updates them once.
+        if job_count == 0 && self.bracket_images_watch.changed() {
+            self.compute_exposure_mappings(&[]);
+            self.preselect_camera_curve();
+        }
+
+        //----------------
+        // Undo/redo.
+
+        if job_count == 0 {
+            if let Some(action) = lib::undo::consume_shortcuts(ctx) {
+                self.apply_history_action(action);
+            }
+            if !lib::undo::is_interacting(ctx) {
+                let edit_state = self.edit_state();
+                self.undo_stack.update(edit_state);
+            }
         }
     }
 }
 
 impl AppMain {
-    fn estimate_sensor_floor(&self) {
+    /// Rebuilds the display transform from the preferences.  The image
+    /// preview picks it up automatically.
+    fn update_display_transform(&mut self) {
+        let (display, error) = DisplayTransform::new(&self.preferences.display_profile);
+        if let Some(e) = error {
+            self.job_queue
+                .log_error(format!("Unable to use display profile: {}", e));
+        }
+        self.bracket_image_sets.set_display(display.clone());
+        self.dark_images.set_display(display.clone());
+        self.display = display;
+    }
+
+    /// The camera make and model from the Exif data of the loaded
+    /// images, if any.
+    fn exif_camera(&self) -> Option<(String, String)> {
+        let bracket_image_sets = self.bracket_image_sets.histogram_sets.lock();
+        let dark_images = self.dark_images.histogram_sets.lock();
+        bracket_image_sets
+            .iter()
+            .chain(dark_images.iter())
+            .flatten()
+            .find_map(|(_, info)| info.camera.clone())
+    }
+
+    /// The camera of the loaded images, if it's in the camera database.
+    fn known_camera(&self) -> Option<&'static camera_db::CameraInfo> {
+        let bracket_image_sets = self.bracket_image_sets.histogram_sets.lock();
+        let dark_images = self.dark_images.histogram_sets.lock();
+        bracket_image_sets
+            .iter()
+            .chain(dark_images.iter())
+            .flatten()
+            .filter_map(|(_, info)| info.camera.as_ref())
+            .find_map(|(make, model)| camera_db::find_camera(make, model))
+    }
+
+    /// Pre-selects the transfer function of Generate mode when the
+    /// bracketed images are from a camera in the camera database.
+    ///
+    /// This only happens when a different camera is detected, so that
+    /// it doesn't override the user's choice on every image change.
+    fn preselect_camera_curve(&mut self) {
+        let camera = self.known_camera();
+        if camera.map(|c| c.model) == self.preselected_camera.map(|c| c.model) {
+            return;
+        }
+        self.preselected_camera = camera;
+
+        if let Some(camera) = camera {
+            let id = generated_tf::TransferFunctionID::from(camera.transfer_function);
+            self.ui_data.lock_mut().generated.transfer_function.id = id;
+            self.job_queue.log_note(format!(
+                "Images are from a {}.  Selected {} in Generate mode.",
+                camera.ui_text(),
+                id.ui_text()
+            ));
+        }
+    }
+
+    fn estimate_sensor_floor(&self) -> JobId {
         use sensor_analysis::estimate_sensor_floor_ceiling;
 
         let bracket_image_sets = self.bracket_image_sets.histogram_sets.clone_ref();
@@ -294,13 +678,23 @@ impl AppMain {
         let ui_data = self.ui_data.clone_ref();
 
         self.job_queue
-            .add_job("Estimate Sensor Noise Floor", move |status| {
+            .add_job("Estimate Sensor Noise Floor", Priority::Normal, move |status| {
                 status
                     .lock_mut()
                     .set_progress(format!("Estimating sensor noise floor"), 0.0);
 
                 let mode = ui_data.lock().mode;
 
+                // Look up the camera in our database of known black
+                // levels, for initializing and sanity-checking the floor.
+                let known_camera = dark_images
+                    .lock()
+                    .iter()
+                    .chain(bracket_image_sets.lock().iter())
+                    .flatten()
+                    .filter_map(|(_, info)| info.camera.as_ref())
+                    .find_map(|(make, model)| camera_db::find_camera(make, model));
+
                 let loaded_lut_is_monotonic =
                     if let Some((lut, _, _)) = &ui_data.lock().modified.loaded_lut {
                         lut.is_monotonic()
@@ -354,7 +748,14 @@ impl AppMain {
                     let mut sum = [0.0f64; 3];
                     let mut sample_count = [0usize; 3];
                     if let Some(set) = dark_images.lock().get(0) {
-                        for (histograms, _) in set.iter() {
+                        for (img_i, (histograms, _)) in set.iter().enumerate() {
+                            status.lock_mut().set_progress(
+                                format!("Estimating sensor noise floor"),
+                                img_i as f32 / set.len() as f32,
+                            );
+                            if status.lock().is_canceled() {
+                                return;
+                            }
                             for chan in 0..3 {
                                 let norm = 1.0 / (histograms[chan].buckets.len() - 1) as f32;
                                 for (i, bucket_population) in
@@ -380,7 +781,7 @@ impl AppMain {
                     floor
                 } else {
                     let histogram_sets =
-                        bracket_images_to_histogram_sets(&*bracket_image_sets.lock());
+                        lib::bracket_analysis::bracket_images_to_histogram_sets(&*bracket_image_sets.lock());
 
                     if histogram_sets.iter().all(|hs| hs[0].len() < 2 || hs[1].len() < 2 || hs[2].len() < 2) {
                         status.lock_mut().log_warning(format!(
@@ -391,7 +792,11 @@ impl AppMain {
 
                     // Estimate sensor floor for each channel.
                     let mut floor: [Option<f32>; 3] = [None; 3];
-                    for histograms in histogram_sets.iter() {
+                    for (set_i, histograms) in histogram_sets.iter().enumerate() {
+                        status.lock_mut().set_progress(
+                            format!("Estimating sensor noise floor"),
+                            set_i as f32 / histogram_sets.len() as f32,
+                        );
                         if status.lock().is_canceled() {
                             return;
                         }
@@ -412,11 +817,30 @@ impl AppMain {
 
                     let mut floor_2 = [0.0f32; 3];
                     for i in 0..3 {
-                        floor_2[i] = floor[i].unwrap_or(0.0);
+                        floor_2[i] = floor[i].unwrap_or_else(|| {
+                            known_camera
+                                .map(|c| c.black_level_normalized())
+                                .unwrap_or(0.0)
+                        });
                     }
                     floor_2
                 };
 
+                if let Some(camera) = known_camera {
+                    if floor.iter().any(|f| camera.floor_diverges(*f)) {
+                        status.lock_mut().log_warning(format!(
+                            "Estimated sensor floor [{:.5}, {:.5}, {:.5}] differs significantly from the known black level of the {} {} ({}/{}).  Check that the images are correct, or consider using the known value.",
+                            floor[0],
+                            floor[1],
+                            floor[2],
+                            camera.make,
+                            camera.model,
+                            camera.black_level,
+                            (1u32 << camera.bit_depth) - 1,
+                        ));
+                    }
+                }
+
                 let mut ui_data = ui_data.lock_mut();
                 match mode {
                     AppMode::Generate => {
@@ -431,22 +855,22 @@ impl AppMain {
                         ui_data.estimated.sensor_floor = floor;
                     }
                 }
-            });
+            })
     }
 
-    fn estimate_sensor_ceiling(&self) {
+    fn estimate_sensor_ceiling(&self) -> JobId {
         use sensor_analysis::estimate_sensor_floor_ceiling;
 
         let bracket_image_sets = self.bracket_image_sets.histogram_sets.clone_ref();
         let ui_data = self.ui_data.clone_ref();
 
         self.job_queue
-            .add_job("Estimate Sensor Ceiling", move |status| {
+            .add_job("Estimate Sensor Ceiling", Priority::Normal, move |status| {
                 status
                     .lock_mut()
                     .set_progress(format!("Estimating sensor ceiling"), 0.0);
 
-                let histogram_sets = bracket_images_to_histogram_sets(&*bracket_image_sets.lock());
+                let histogram_sets = lib::bracket_analysis::bracket_images_to_histogram_sets(&*bracket_image_sets.lock());
                 if histogram_sets.iter().all(|hs| hs[0].len() < 2 || hs[1].len() < 2 || hs[2].len() < 2) {
                     status.lock_mut().log_warning(format!(
                         "Not enough valid images to estimate ceiling.  Check that you have at least two bracketed exposure images with exposure Exif data.",
@@ -456,7 +880,11 @@ impl AppMain {
 
                 // Estimate sensor floor for each channel.
                 let mut ceiling: [Option<f32>; 3] = [None; 3];
-                for histograms in histogram_sets.iter() {
+                for (set_i, histograms) in histogram_sets.iter().enumerate() {
+                    status.lock_mut().set_progress(
+                        format!("Estimating sensor ceiling"),
+                        set_i as f32 / histogram_sets.len() as f32,
+                    );
                     if status.lock().is_canceled() {
                         return;
                     }
@@ -495,49 +923,71 @@ impl AppMain {
                         }
                     }
                 }
-            });
+            })
     }
 
-    fn compute_exposure_mappings(&self) {
+    /// Computes the exposure mappings once the jobs in `after` finish.
+    fn compute_exposure_mappings(&self, after: &[JobId]) -> JobId {
         let bracket_image_sets = self.bracket_image_sets.histogram_sets.clone_ref();
         let ui_data = self.ui_data.clone_ref();
 
-        self.job_queue
-            .add_job("Compute Exposure Mappings", move |status| {
+        self.job_queue.add_job_after(
+            after,
+            "Compute Exposure Mappings",
+            Priority::Normal,
+            move |status| {
                 status
                     .lock_mut()
                     .set_progress("Computing exposure mappings".into(), 0.0);
 
-                let histogram_sets = bracket_images_to_histogram_sets(&*bracket_image_sets.lock());
+                let histogram_sets = lib::bracket_analysis::bracket_images_to_histogram_sets(
+                    &*bracket_image_sets.lock(),
+                );
+                ui_data.lock_mut().estimated.monochrome =
+                    lib::bracket_analysis::image_sets_are_monochrome(&*bracket_image_sets.lock());
 
                 // We use the estimated curve's floor and ceiling because
                 // that data is only used for estimation, and doesn't actually
                 // affect the points of the exposure mappings.
                 let floor = ui_data.lock().estimated.sensor_floor;
                 let ceiling = ui_data.lock().estimated.sensor_ceiling;
-                let mappings = exposure_mappings(&histogram_sets, floor, ceiling);
+                let mappings =
+                    lib::bracket_analysis::exposure_mappings(&histogram_sets, floor, ceiling);
                 ui_data.lock_mut().exposure_mappings = mappings;
-            });
+            },
+        )
     }
 
-    fn estimate_transfer_curve(&self) {
+    /// Estimates the transfer curve once the jobs in `after` finish.
+    fn estimate_transfer_curve(&self, after: &[JobId]) -> JobId {
         use sensor_analysis::emor;
 
         // Make sure the exposure mappings are up-to-date.
-        self.compute_exposure_mappings();
+        let mappings = self.compute_exposure_mappings(after);
 
         let transfer_function_tables = self.transfer_function_tables.clone_ref();
         let ui_data = self.ui_data.clone_ref();
 
-        self.job_queue
-            .add_job("Estimate Transfer Function", move |status| {
-                let total_rounds = ui_data.lock().estimated.rounds;
-
+        self.job_queue.add_job_after(
+            &[mappings],
+            "Estimate Transfer Function",
+            Priority::Normal,
+            move |status| {
+                let convergence = ui_data.lock().estimated.convergence;
+                let smoothness = ui_data.lock().estimated.smoothness;
+                let monochrome = ui_data.lock().estimated.monochrome;
+                let detect_knee = ui_data.lock().estimated.detect_knee;
+                ui_data.lock_mut().estimated.knee = None;
+                ui_data.lock_mut().estimated.error_history.clear();
+
+                // The channels of monochrome images are all the same, so
+                // only the first is used.
                 let mappings: Vec<ExposureMapping> = ui_data
                     .lock()
                     .exposure_mappings
                     .clone()
                     .iter()
+                    .take(if monochrome { 1 } else { 3 })
                     .map(|m| m.clone())
                     .flatten()
                     .collect();
@@ -548,61 +998,237 @@ impl AppMain {
                     return;
                 }
 
-                // Estimate transfer function.
-                let rounds_per_update = (1000 / mappings.len()).max(1);
-                let mut estimator = emor::EmorEstimator::new(&mappings);
-                for round_i in 0..(total_rounds / rounds_per_update) {
+                // Estimate transfer function until it converges.  Rounds
+                // are done in time-limited chunks, so that progress
+                // updates and cancellation stay responsive however many
+                // mappings there are.
+                //
+                // When detecting a knee, it's searched for once the
+                // estimate has converged, and if one is found estimation
+                // continues with it until it converges again.
+                let mut estimator = emor::EmorEstimator::new(&mappings).with_smoothness(smoothness);
+                let mut knee = None;
+                loop {
+                    // Progress is how close the improvement of the error is
+                    // to the convergence threshold, on a log scale.
+                    let progress = estimator
+                        .last_improvement()
+                        .map(|n| (n.max(1.0e-12).ln() / convergence.ln()).max(0.0).min(1.0))
+                        .unwrap_or(0.0);
                     status.lock_mut().set_progress(
                         format!(
-                            "Estimating transfer function, round {}/{}",
-                            round_i * rounds_per_update,
-                            total_rounds
+                            "Estimating transfer function, round {}",
+                            estimator.rounds_done()
                         ),
-                        (round_i * rounds_per_update) as f32 / total_rounds as f32,
+                        progress,
                     );
                     if status.lock().is_canceled() {
                         return;
                     }
 
-                    estimator.do_rounds(rounds_per_update);
+                    estimator.do_rounds_for(
+                        MAX_ESTIMATION_ROUNDS - estimator.rounds_done(),
+                        std::time::Duration::from_millis(100),
+                    );
                     let (inv_emor_factors, err) = estimator.current_estimate();
                     let mut curves: [Vec<f32>; 3] = [Vec::new(), Vec::new(), Vec::new()];
                     for i in 0..3 {
                         // The (0.0, 1.0) floor/ceil here is because we handle the
                         // floor/ceil adjustment dynamically when previewing and exporting.
                         curves[i] = emor::inv_emor_factors_to_curve(&inv_emor_factors, 0.0, 1.0);
+                        if let Some(knee) = knee {
+                            knee.apply(&mut curves[i]);
+                        }
                     }
 
                     // Store the curve and the preview.
                     *transfer_function_tables.lock_mut() = Some((curves.clone(), 0.0, 1.0));
-                    ui_data.lock_mut().estimated.transfer_function_preview = Some((curves, err));
+                    {
+                        let mut ui_data = ui_data.lock_mut();
+                        ui_data.estimated.transfer_function_preview = Some((curves, err));
+                        ui_data
+                            .estimated
+                            .error_history
+                            .push((estimator.rounds_done(), err));
+                    }
+
+                    if !estimator.has_converged(convergence) {
+                        if estimator.rounds_done() >= MAX_ESTIMATION_ROUNDS {
+                            status.lock_mut().log_warning(format!(
+                                "The transfer function estimate didn't converge within {} rounds.",
+                                MAX_ESTIMATION_ROUNDS
+                            ));
+                            break;
+                        }
+                        continue;
+                    }
+
+                    // Look for a knee.
+                    if detect_knee && knee.is_none() {
+                        status
+                            .lock_mut()
+                            .set_progress("Detecting knee".into(), 1.0);
+                        match emor::find_knee(&mappings, &inv_emor_factors) {
+                            Some((found, _)) => {
+                                status.lock_mut().log_note(format!(
+                                    "Found a knee at encoded value {:.4}, with the to-linear slope multiplied by {:.3} above it.",
+                                    found.position, found.slope,
+                                ));
+                                knee = Some(found);
+                                estimator.set_knee(knee);
+                                continue;
+                            }
+                            None => {
+                                status
+                                    .lock_mut()
+                                    .log_note("No knee found in the transfer function.".into());
+                            }
+                        }
+                    }
+                    break;
                 }
-            });
+                ui_data.lock_mut().estimated.knee = knee;
+
+                // Report how far each channel diverges from the shared fit.
+                if monochrome {
+                    ui_data.lock_mut().estimated.channel_divergence = None;
+                    return;
+                }
+                let (inv_emor_factors, _) = estimator.current_estimate();
+                let channel_errors = {
+                    let channel_mappings = ui_data.lock().exposure_mappings.clone();
+                    emor::channel_errors(
+                        &[
+                            &channel_mappings[0][..],
+                            &channel_mappings[1][..],
+                            &channel_mappings[2][..],
+                        ],
+                        &inv_emor_factors,
+                        knee,
+                    )
+                };
+                let divergence = [channel_errors[0], channel_errors[1], channel_errors[2]];
+                ui_data.lock_mut().estimated.channel_divergence = Some(divergence);
+                if ui_data.lock().estimated.shared_curve {
+                    status.lock_mut().log_note(format!(
+                        "Per-channel divergence from the shared curve: R {:.4}, G {:.4}, B {:.4}",
+                        divergence[0], divergence[1], divergence[2],
+                    ));
+                }
+            },
+        )
     }
 
     fn estimate_everything(&self) {
-        self.estimate_sensor_floor();
-        self.estimate_sensor_ceiling();
-        self.estimate_transfer_curve();
+        let floor = self.estimate_sensor_floor();
+        let ceiling = self.estimate_sensor_ceiling();
+        self.estimate_transfer_curve(&[floor, ceiling]);
     }
 
-    fn export_lut(&self, path: &std::path::Path, to_linear: bool) {
+    /// Exports a LUT of the current transfer function.
+    ///
+    /// The LUT's metadata records the camera, direction, and mode, plus
+    /// any extra `notes`, in whatever way the format supports.
+    fn export_lut(&self, path: &std::path::Path, to_linear: bool, notes: Vec<String>) {
         let transfer_function_tables = self.transfer_function_tables.clone_ref();
         let ui_data = self.ui_data.clone_ref();
         let exp_fmt = ui_data.lock().export_format;
+        let exp_options = ui_data.lock().export_options;
         let path = path.to_path_buf();
         let mode = ui_data.lock().mode;
 
-        self.job_queue.add_job("Export LUT", move |status| {
+        let mut metadata = LutMetadata {
+            name: String::new(),
+            software: format!("ETF LUT Maker v{}", VERSION),
+            camera: Some(self.camera_profiles.active_name().to_string()),
+            notes: vec![
+                format!(
+                    "Transform: {}",
+                    if to_linear {
+                        "to linear"
+                    } else {
+                        "from linear"
+                    }
+                ),
+                format!("Mode: {:?}", mode),
+            ],
+        };
+        metadata.notes.extend(notes);
+
+        self.job_queue.add_job("Export LUT", Priority::High, move |status| {
             status
                 .lock_mut()
                 .set_progress(format!("Exporting LUT: {}", path.to_string_lossy(),), 0.0);
 
-            // Compute the LUT.
-            let lut = match mode {
+            // A loaded 3D LUT is baked as a whole, along with its curves
+            // and trims.
+            let lut_3d = if mode == AppMode::Modify {
+                ui_data.lock().modified.loaded_lut_3d.clone()
+            } else {
+                None
+            };
+            if let Some(stack) = lut_3d {
+                if !to_linear {
+                    status.lock_mut().log_error(
+                        "a modified 3D LUT can't be inverted, so it can only be \
+                         exported as a \"to linear\" LUT."
+                            .into(),
+                    );
+                    return;
+                }
+                if !exp_fmt.supports_3d() {
+                    status.lock_mut().log_error(format!(
+                        "the {} format can't hold a 3D LUT.  Please choose a \
+                         different export format.",
+                        exp_fmt.ui_text(),
+                    ));
+                    return;
+                }
+
+                let (trims, exposure_scale) = {
+                    let ui_data = ui_data.lock();
+                    (
+                        ui_data.modified.trims,
+                        ui_data.calibration.scale(&ui_data.to_linear_luts(false)),
+                    )
+                };
+                let f = |rgb| {
+                    let out = stack.eval(rgb, &trims);
+                    [
+                        out[0] * exposure_scale,
+                        out[1] * exposure_scale,
+                        out[2] * exposure_scale,
+                    ]
+                };
+                match write_lut_3d_file(
+                    &path,
+                    stack.input_range(),
+                    f,
+                    exp_fmt,
+                    exp_options,
+                    &metadata,
+                ) {
+                    Ok(warnings) => {
+                        for warning in warnings {
+                            status.lock_mut().log_warning(warning);
+                        }
+                    }
+                    Err(_) => {
+                        status.lock_mut().log_error(format!(
+                            "couldn't write to {}.  Please make sure the selected file path is writable.",
+                            path.to_string_lossy()
+                        ));
+                    }
+                }
+                return;
+            }
+
+            // Compute the LUT, and for estimated functions also fit log
+            // curves to report alongside it.
+            let mut log_curve_fits = Vec::new();
+            let mut lut = match mode {
                 AppMode::Estimate => {
-                    let floor = ui_data.lock().estimated.sensor_floor;
-                    let ceiling = ui_data.lock().estimated.sensor_ceiling;
+                    let (floor, ceiling) = ui_data.lock().estimated.applied_floor_ceiling();
 
                     if floor.iter().zip(ceiling.iter()).any(|(a, b)| *a >= *b) {
                         status.lock_mut().log_error(
@@ -623,14 +1249,18 @@ impl AppMain {
                     };
 
                     // Apply the floor and ceiling.
+                    let norms = ui_data.lock().estimated.floor_ceiling_norms(&tables);
                     for i in 0..3 {
-                        let floor = lerp_slice(&to_linear_lut.tables[i], floor[i]);
-                        let ceil = lerp_slice(&to_linear_lut.tables[i], ceiling[i]);
-                        let norm = 1.0 / (ceil - floor);
+                        let (floor, norm) = norms[i];
                         for n in to_linear_lut.tables[i].iter_mut() {
                             *n = (*n - floor) * norm;
                         }
                     }
+                    ui_data
+                        .lock()
+                        .estimated
+                        .reduce_channels(&mut to_linear_lut);
+                    log_curve_fits = estimated_tf::EstimatedTF::log_curve_fits(&to_linear_lut);
 
                     // Invert if needed.
                     if to_linear {
@@ -644,7 +1274,7 @@ impl AppMain {
                     let (function, floor, ceiling, resolution) = {
                         let ui_data = ui_data.lock();
                         (
-                            ui_data.generated.transfer_function,
+                            ui_data.generated.transfer_function.clone(),
                             if ui_data.generated.sensor_floor.0 {
                                 Some(ui_data.generated.sensor_floor.1)
                             } else {
@@ -739,161 +1369,717 @@ impl AppMain {
                 }
             };
 
-            // Write out the LUT.
-            let write_result = (|| -> std::io::Result<()> {
-                match exp_fmt {
-                    ExportFormat::Cube => colorbox::formats::cube_iridas::write_1d(
-                        &mut std::io::BufWriter::new(std::fs::File::create(&path)?),
-                        if lut.ranges.len() < 3 {
-                            [(lut.ranges[0].0, lut.ranges[0].1); 3]
-                        } else {
-                            [
-                                (lut.ranges[0].0, lut.ranges[0].1),
-                                (lut.ranges[1].0, lut.ranges[1].1),
-                                (lut.ranges[2].0, lut.ranges[2].1),
-                            ]
-                        },
-                        [&lut.tables[0], &lut.tables[1], &lut.tables[2]],
-                    )?,
-
-                    ExportFormat::Spi1D => {
-                        let ranges_are_equal = lut
-                            .ranges
-                            .iter()
-                            .fold((lut.ranges[0], true), |a, b| (a.0, a.1 && a.0 == *b))
-                            .1;
-                        let lut = if ranges_are_equal {
-                            lut
-                        } else {
-                            lut.resample_to_single_range(
-                                lut.tables[0]
-                                    .len()
-                                    .max((lut.tables[0].len() * 4).min(1 << 12)),
-                            )
-                        };
-                        colorbox::formats::spi1d::write(
-                            &mut std::io::BufWriter::new(std::fs::File::create(&path)?),
-                            lut.ranges[0].0,
-                            lut.ranges[0].1,
-                            &[&lut.tables[0], &lut.tables[1], &lut.tables[2]],
-                        )?
+            // Apply the exposure calibration.
+            let exposure_scale = {
+                let ui_data = ui_data.lock();
+                ui_data.calibration.scale(&ui_data.to_linear_luts(false))
+            };
+            if to_linear {
+                for table in lut.tables.iter_mut() {
+                    for n in table.iter_mut() {
+                        *n *= exposure_scale;
                     }
                 }
-                Ok(())
-            })();
+            } else {
+                for range in lut.ranges.iter_mut() {
+                    range.0 *= exposure_scale;
+                    range.1 *= exposure_scale;
+                }
+            }
+            if !log_curve_fits.is_empty() {
+                for (curve, _) in log_curve_fits.iter_mut().flatten() {
+                    curve.b /= exposure_scale;
+                }
+                metadata
+                    .notes
+                    .extend(estimated_tf::EstimatedTF::log_curve_notes(&log_curve_fits));
+            }
 
-            if let Err(_) = write_result {
+            // A gamut stage requires a format that can represent it.
+            let gamut_matrix = if mode == AppMode::Modify {
+                ui_data.lock().modified.gamut.directional_matrix(to_linear)
+            } else {
+                None
+            };
+            if gamut_matrix.is_some() && !exp_fmt.supports_gamut() {
                 status.lock_mut().log_error(format!(
-                    "couldn't write to {}.  Please make sure the selected file path is writable.",
-                    path.to_string_lossy()
+                    "the {} format can't include a gamut conversion.  Please choose \
+                     a different export format or disable the gamut conversion.",
+                    exp_fmt.ui_text(),
                 ));
+                return;
+            }
+
+            // Write out the LUT.
+            match write_lut_file(
+                &path,
+                lut,
+                exp_fmt,
+                exp_options,
+                gamut_matrix,
+                to_linear,
+                &metadata,
+            ) {
+                Ok(warnings) => {
+                    for warning in warnings {
+                        status.lock_mut().log_warning(warning);
+                    }
+                }
+                Err(_) => {
+                    status.lock_mut().log_error(format!(
+                        "couldn't write to {}.  Please make sure the selected file path is writable.",
+                        path.to_string_lossy()
+                    ));
+                }
             }
         });
     }
 
+    /// Exports both the to-linear and from-linear LUTs into `dir`,
+    /// named after the current camera profile.
+    ///
+    /// A camera space sidecar file describing the to-linear LUT is
+    /// written alongside them, for dropping into OCIO Maker.  If
+    /// `ocio_config` is given, the to-linear LUT is also registered as
+    /// an input color space in that OCIO Maker config.
+    fn export_lut_pair(&self, dir: &std::path::Path, ocio_config: Option<PathBuf>) {
+        let camera_name = self.camera_profiles.active_name().to_string();
+        let exp_fmt = self.ui_data.lock().export_format;
+        let mode = self.ui_data.lock().mode;
+        let (to_linear_name, from_linear_name) = lut_pair::file_names(&camera_name, exp_fmt.ext());
+        let to_linear_path = dir.join(&to_linear_name);
+        let from_linear_path = dir.join(&from_linear_name);
+
+        self.export_lut(
+            &to_linear_path,
+            true,
+            vec![format!("Inverse: {}", from_linear_name)],
+        );
+        self.export_lut(
+            &from_linear_path,
+            false,
+            vec![format!("Inverse: {}", to_linear_name)],
+        );
+
+        let chroma_space = self
+            .known_camera()
+            .map(|camera| lib::chroma_space::ChromaSpace::from(camera.gamut));
+
+        // OCIO Maker can only load 1D .cube and .spi1d LUTs, so the
+        // sidecar is skipped for other formats and for LUTs with a
+        // gamut stage.
+        let has_gamut_stage = mode == AppMode::Modify
+            && self
+                .ui_data
+                .lock()
+                .modified
+                .gamut
+                .directional_matrix(true)
+                .is_some();
+        if matches!(exp_fmt, ExportFormat::Cube | ExportFormat::Spi1D) && !has_gamut_stage {
+            let sidecar_path = dir.join(lut_pair::sidecar_file_name(&camera_name));
+            let mut camera_space =
+                CameraSpace::new(&camera_name, &to_linear_path, LutDirection::ToLinear);
+            if let Some(chroma_space) = chroma_space {
+                camera_space.set_chroma_space(chroma_space, colorbox::chroma::REC709);
+            }
+            self.job_queue
+                .add_job("Write Camera Space", Priority::Normal, move |status| {
+                    if let Err(e) = camera_space.write(&sidecar_path) {
+                        status.lock_mut().log_error(format!(
+                            "Unable to write camera space file \"{}\": {}.",
+                            sidecar_path.to_string_lossy(),
+                            e
+                        ));
+                    }
+                });
+        }
+
+        if let Some(config_path) = ocio_config {
+            self.job_queue
+                .add_job("Register in OCIO Config", Priority::Normal, move |status| {
+                    if let Err(e) = lut_pair::register_in_ocio_config(
+                        &config_path,
+                        &camera_name,
+                        &to_linear_path,
+                        chroma_space,
+                    ) {
+                        status.lock_mut().log_error(format!(
+                            "Unable to register LUT in OCIO config \"{}\": {}.",
+                            config_path.to_string_lossy(),
+                            e
+                        ));
+                    } else {
+                        status.lock_mut().log_note(format!(
+                            "Added color space \"{}\" to \"{}\".  Load and re-export the config in OCIO Maker to use it.",
+                            camera_name,
+                            config_path.to_string_lossy()
+                        ));
+                    }
+                });
+        }
+    }
+
+    /// Load a gray card image for exposure calibration.
+    fn load_gray_card(&self, path: &std::path::Path) {
+        let ui_data = self.ui_data.clone_ref();
+        let path = path.to_path_buf();
+        let float_encoding = self.bracket_image_sets.float_encoding();
+
+        self.job_queue
+            .add_job("Load Gray Card", Priority::Normal, move |status| {
+                status.lock_mut().set_progress(
+                    format!("Loading gray card: {}", path.to_string_lossy()),
+                    0.0,
+                );
+
+                let img = match lib::job_helpers::load_image(&path) {
+                    Ok(img) => img,
+                    Err(_) => {
+                        status.lock_mut().log_error(format!(
+                            "Unable to load gray card image \"{}\".",
+                            path.to_string_lossy()
+                        ));
+                        return;
+                    }
+                };
+
+                // Average the center of the image.
+                let mask = lib::SampleMask::Rect(0.4, 0.4, 0.6, 0.6);
+                let histograms = lib::job_helpers::compute_image_histograms(
+                    &img,
+                    Some(&mask),
+                    float_encoding.as_ref(),
+                );
+                let mut gray = [0.0f32; 3];
+                for chan in 0..3 {
+                    let norm = 1.0 / (histograms[chan].buckets.len() - 1) as f64;
+                    let sum: f64 = histograms[chan]
+                        .buckets
+                        .iter()
+                        .enumerate()
+                        .map(|(i, n)| i as f64 * norm * *n as f64)
+                        .sum();
+                    gray[chan] = (sum / histograms[chan].total_samples.max(1) as f64) as f32;
+                }
+
+                ui_data.lock_mut().calibration.gray_card = Some((img.info.filename.clone(), gray));
+            });
+    }
+
     /// Load a LUT for subsequent modification by the user.
     fn load_lut(&self, lut_path: &std::path::Path) {
         let ui_data = self.ui_data.clone_ref();
         let path = lut_path.to_path_buf();
 
-        self.job_queue.add_job("Load LUT", move |status| {
-            status
-                .lock_mut()
-                .set_progress(format!("Loading LUT: {}", path.to_string_lossy(),), 0.0);
+        self.job_queue
+            .add_job("Load LUT", Priority::Normal, move |status| {
+                status
+                    .lock_mut()
+                    .set_progress(format!("Loading LUT: {}", path.to_string_lossy(),), 0.0);
+
+                // Load lut.
+                let lut = match lib::job_helpers::load_1d_lut(&path) {
+                    Ok(lut) => lut,
+                    Err(colorbox::formats::ReadError::IoErr(_)) => {
+                        status.lock_mut().log_error(format!(
+                            "Unable to access file \"{}\".",
+                            path.to_string_lossy()
+                        ));
+                        return;
+                    }
+                    Err(colorbox::formats::ReadError::FormatErr) => {
+                        status.lock_mut().log_error(format!(
+                            "Not a 1D LUT file: \"{}\".",
+                            path.to_string_lossy()
+                        ));
+                        return;
+                    }
+                };
 
-            // Load lut.
-            let lut = match lib::job_helpers::load_1d_lut(&path) {
-                Ok(lut) => lut,
-                Err(colorbox::formats::ReadError::IoErr(_)) => {
-                    status.lock_mut().log_error(format!(
-                        "Unable to access file \"{}\".",
-                        path.to_string_lossy()
-                    ));
-                    return;
+                let res = lut
+                    .tables
+                    .get(0)
+                    .map(|t| (t.len() * 4).min(1 << 14))
+                    .unwrap_or(4096);
+                let reversed_lut = lut.resample_inverted(res);
+
+                // Set this as the lut for the passed color space index.
+                let mut ui_data = ui_data.lock_mut();
+                ui_data.modified.loaded_lut = Some((lut, reversed_lut, path));
+                ui_data.modified.loaded_lut_3d = None;
+            });
+    }
+
+    /// Load a 3D LUT for subsequent modification by the user.
+    fn load_lut_3d(&self, lut_path: &std::path::Path) {
+        let ui_data = self.ui_data.clone_ref();
+        let path = lut_path.to_path_buf();
+
+        self.job_queue
+            .add_job("Load 3D LUT", Priority::Normal, move |status| {
+                status
+                    .lock_mut()
+                    .set_progress(format!("Loading 3D LUT: {}", path.to_string_lossy(),), 0.0);
+
+                let (lut, shaper) = match lib::job_helpers::load_3d_lut(&path) {
+                    Ok(lut) => lut,
+                    Err(colorbox::formats::ReadError::IoErr(_)) => {
+                        status.lock_mut().log_error(format!(
+                            "Unable to access file \"{}\".",
+                            path.to_string_lossy()
+                        ));
+                        return;
+                    }
+                    Err(colorbox::formats::ReadError::FormatErr) => {
+                        status.lock_mut().log_error(format!(
+                            "Not a 3D LUT file: \"{}\".",
+                            path.to_string_lossy()
+                        ));
+                        return;
+                    }
+                };
+
+                let mut ui_data = ui_data.lock_mut();
+                ui_data.modified.loaded_lut_3d =
+                    Some(lib::lut_modify::Lut3DStack::new(lut, shaper, path));
+                ui_data.modified.loaded_lut = None;
+            });
+    }
+
+    /// Load a 1D LUT to apply before (or, if `post` is true, after) the
+    /// loaded 3D LUT.
+    fn load_lut_3d_curve(&self, lut_path: &std::path::Path, post: bool) {
+        let ui_data = self.ui_data.clone_ref();
+        let path = lut_path.to_path_buf();
+
+        self.job_queue
+            .add_job("Load Curve", Priority::Normal, move |status| {
+                status
+                    .lock_mut()
+                    .set_progress(format!("Loading curve: {}", path.to_string_lossy(),), 0.0);
+
+                let lut = match lib::job_helpers::load_1d_lut(&path) {
+                    Ok(lut) => lut,
+                    Err(colorbox::formats::ReadError::IoErr(_)) => {
+                        status.lock_mut().log_error(format!(
+                            "Unable to access file \"{}\".",
+                            path.to_string_lossy()
+                        ));
+                        return;
+                    }
+                    Err(colorbox::formats::ReadError::FormatErr) => {
+                        status.lock_mut().log_error(format!(
+                            "Not a 1D LUT file: \"{}\".",
+                            path.to_string_lossy()
+                        ));
+                        return;
+                    }
+                };
+
+                if let Some(ref mut stack) = ui_data.lock_mut().modified.loaded_lut_3d {
+                    if post {
+                        stack.post_curve = Some((lut, path));
+                    } else {
+                        stack.pre_curve = Some((lut, path));
+                    }
                 }
-                Err(colorbox::formats::ReadError::FormatErr) => {
-                    status.lock_mut().log_error(format!(
-                        "Not a 1D LUT file: \"{}\".",
-                        path.to_string_lossy()
-                    ));
+            });
+    }
+
+    /// Loads the curve from Estimate mode into Modify mode, with the
+    /// estimated floor and ceiling applied.
+    fn use_estimated_curve(&self) {
+        let transfer_function_tables = self.transfer_function_tables.clone_ref();
+        let ui_data = self.ui_data.clone_ref();
+
+        self.job_queue
+            .add_job("Use Estimated Curve", Priority::Normal, move |status| {
+                status
+                    .lock_mut()
+                    .set_progress("Preparing estimated curve".into(), 0.0);
+
+                let tables = if let Some((tables, _, _)) = transfer_function_tables.lock().clone() {
+                    tables
+                } else {
                     return;
-                }
-            };
+                };
 
-            let res = lut
-                .tables
-                .get(0)
-                .map(|t| (t.len() * 4).min(1 << 14))
-                .unwrap_or(4096);
-            let reversed_lut = lut.resample_inverted(res);
+                let mut lut = colorbox::lut::Lut1D {
+                    ranges: vec![(0.0, 1.0)],
+                    tables: tables.to_vec(),
+                };
+                let norms = ui_data.lock().estimated.floor_ceiling_norms(&tables);
+                for i in 0..3 {
+                    let (floor, norm) = norms[i];
+                    for n in lut.tables[i].iter_mut() {
+                        *n = (*n - floor) * norm;
+                    }
+                }
+                let reversed_lut = lut.resample_inverted(4096);
 
-            // Set this as the lut for the passed color space index.
-            ui_data.lock_mut().modified.loaded_lut = Some((lut, reversed_lut, path));
-        });
+                let mut ui_data = ui_data.lock_mut();
+                ui_data.modified.loaded_lut =
+                    Some((lut, reversed_lut, PathBuf::from("Estimated Curve")));
+                ui_data.modified.loaded_lut_3d = None;
+            });
     }
 }
 
-/// Utility function to get histograms into the right order for processing.
+/// Writes a LUT to a file in the given format, returning any warnings.
 ///
-/// Excludes the histograms of images without exposure data.
-fn bracket_images_to_histogram_sets(
-    image_sets: &[Vec<([Histogram; 3], ImageInfo)>],
-) -> Vec<[Vec<(Histogram, f32)>; 3]> {
-    let mut histogram_sets: Vec<[Vec<(Histogram, f32)>; 3]> = Vec::new();
-    for images in image_sets.iter() {
-        let mut histograms = [Vec::new(), Vec::new(), Vec::new()];
-        for src_img in images.iter() {
-            for chan in 0..3 {
-                if let Some(exposure) = src_img.1.exposure {
-                    histograms[chan].push((src_img.0[chan].clone(), exposure));
-                }
+/// Single-channel LUTs are written as such in formats that support it,
+/// and with the channel replicated to all three otherwise.
+///
+/// If given, `gamut_matrix` is applied after the LUT when `to_linear`
+/// is true, and before it otherwise.  The format must support it.
+fn write_lut_file(
+    path: &std::path::Path,
+    lut: colorbox::lut::Lut1D,
+    exp_fmt: ExportFormat,
+    exp_options: ExportOptions,
+    gamut_matrix: Option<colorbox::matrix::Matrix>,
+    to_linear: bool,
+    metadata: &LutMetadata,
+) -> std::io::Result<Vec<String>> {
+    let mut warnings = Vec::new();
+
+    // Formats that are written as 1D tables get the extrapolation baked
+    // in (or, for CLF, described), and recorded in their metadata.  The
+    // others clamp.
+    let is_1d = !matches!(
+        exp_fmt,
+        ExportFormat::Lustre3dl
+            | ExportFormat::PanasonicVlt
+            | ExportFormat::SonyCube
+            | ExportFormat::Icc
+    ) && !(exp_fmt == ExportFormat::Cube && gamut_matrix.is_some());
+    let mut metadata = named_metadata(metadata, path);
+    if is_1d && exp_options.extrapolation != Extrapolation::Clamp {
+        metadata.notes.push(format!(
+            "Extrapolation: {}",
+            exp_options.extrapolation.as_str()
+        ));
+    }
+    let metadata = &metadata;
+
+    let lut = if lut.tables.len() == 1 && exp_fmt != ExportFormat::Spi1D {
+        colorbox::lut::Lut1D {
+            ranges: vec![lut.ranges[0]; 3],
+            tables: vec![
+                lut.tables[0].clone(),
+                lut.tables[0].clone(),
+                lut.tables[0].clone(),
+            ],
+        }
+    } else {
+        lut
+    };
+
+    match exp_fmt {
+        ExportFormat::Cube if gamut_matrix.is_some() => {
+            let lut = single_range_lut(lut, 0);
+            lib::lut_formats::write_cube_3d(
+                &mut std::io::BufWriter::new(std::fs::File::create(path)?),
+                lut.ranges[0],
+                exp_options.cube_size,
+                |rgb| {
+                    quantize_rgb(
+                        eval_with_matrix(&lut, rgb, gamut_matrix.unwrap(), to_linear),
+                        exp_options,
+                    )
+                },
+                metadata,
+            )?
+        }
+
+        ExportFormat::Cube => {
+            let lut = if exp_options.resolution >= 2 {
+                single_range_lut(lut, exp_options.resolution)
+            } else {
+                lut
+            };
+            let lut = quantized_lut(extended_lut(lut, exp_options), exp_options);
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            lib::lut_formats::write_cube_header(&mut file, metadata)?;
+            colorbox::formats::cube_iridas::write_1d(
+                &mut file,
+                if lut.ranges.len() < 3 {
+                    [(lut.ranges[0].0, lut.ranges[0].1); 3]
+                } else {
+                    [
+                        (lut.ranges[0].0, lut.ranges[0].1),
+                        (lut.ranges[1].0, lut.ranges[1].1),
+                        (lut.ranges[2].0, lut.ranges[2].1),
+                    ]
+                },
+                [&lut.tables[0], &lut.tables[1], &lut.tables[2]],
+            )?
+        }
+
+        ExportFormat::Spi1D => {
+            let lut = quantized_lut(
+                extended_lut(single_range_lut(lut, 0), exp_options),
+                exp_options,
+            );
+            colorbox::formats::spi1d::write(
+                &mut std::io::BufWriter::new(std::fs::File::create(path)?),
+                lut.ranges[0].0,
+                lut.ranges[0].1,
+                &lut.tables.iter().map(|t| &t[..]).collect::<Vec<_>>(),
+            )?
+        }
+
+        ExportFormat::Clf => {
+            let lut = single_range_lut(lut, exp_options.resolution);
+            let lut =
+                ExtrapolatedLut::new(quantized_lut(lut, exp_options), exp_options.extrapolation);
+            lib::lut_formats::write_clf_1d(
+                &mut std::io::BufWriter::new(std::fs::File::create(path)?),
+                &lut,
+                if to_linear { None } else { gamut_matrix },
+                if to_linear { gamut_matrix } else { None },
+                metadata,
+            )?
+        }
+
+        ExportFormat::Csp => {
+            let lut = single_range_lut(lut, exp_options.resolution);
+            let lut = quantized_lut(extended_lut(lut, exp_options), exp_options);
+            lib::lut_formats::write_csp_1d(
+                &mut std::io::BufWriter::new(std::fs::File::create(path)?),
+                lut.ranges[0],
+                [&lut.tables[0], &lut.tables[1], &lut.tables[2]],
+                exp_options.shaper_points,
+                metadata,
+            )?
+        }
+
+        ExportFormat::Lustre3dl => {
+            let lut = single_range_lut(lut, 0);
+            if lut.ranges[0] != (0.0, 1.0) {
+                warnings.push(
+                    "The .3dl format only supports an input range of [0.0, 1.0], \
+                     so the LUT's input will be clipped to that range."
+                        .into(),
+                );
+            }
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            if let Some(matrix) = gamut_matrix {
+                lib::lut_formats::write_3dl(
+                    &mut file,
+                    exp_options.cube_size,
+                    |rgb| quantize_rgb(eval_with_matrix(&lut, rgb, matrix, to_linear), exp_options),
+                    metadata,
+                )?
+            } else {
+                let lut = quantized_lut(lut, exp_options);
+                lib::lut_formats::write_3dl_from_1d(
+                    &mut file,
+                    lut.ranges[0],
+                    [&lut.tables[0], &lut.tables[1], &lut.tables[2]],
+                    exp_options.cube_size,
+                    metadata,
+                )?
             }
         }
 
-        histogram_sets.push(histograms);
+        ExportFormat::PanasonicVlt | ExportFormat::SonyCube => {
+            let lut = single_range_lut(lut, 0);
+            if lut.ranges[0] != (0.0, 1.0) {
+                warnings.push(format!(
+                    "The {} format only supports an input range of [0.0, 1.0], \
+                     so the LUT's input will be clipped to that range.",
+                    exp_fmt.ui_text()
+                ));
+            }
+            let matrix =
+                gamut_matrix.unwrap_or([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+            let f = |rgb| quantize_rgb(eval_with_matrix(&lut, rgb, matrix, to_linear), exp_options);
+            let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+            if exp_fmt == ExportFormat::PanasonicVlt {
+                lib::lut_formats::write_vlt(&mut file, f, metadata)?
+            } else {
+                let cube_size = if exp_options.cube_size <= 17 { 17 } else { 33 };
+                lib::lut_formats::write_sony_cube(&mut file, cube_size, f, metadata)?
+            }
+        }
+
+        ExportFormat::Icc => {
+            // ICC profiles only describe the to-linear direction, and
+            // applications invert them as needed.  So from-linear LUTs
+            // are inverted back to that direction, along with their
+            // gamut stage.
+            let (lut, gamut_matrix) = if to_linear {
+                (single_range_lut(lut, exp_options.resolution), gamut_matrix)
+            } else {
+                (
+                    single_range_lut(
+                        single_range_lut(lut, 0).resample_inverted(4096),
+                        exp_options.resolution,
+                    ),
+                    gamut_matrix.and_then(colorbox::matrix::invert),
+                )
+            };
+            let lut = quantized_lut(lut, exp_options);
+            if lut.ranges[0] != (0.0, 1.0) {
+                warnings.push(
+                    "The .icc format only supports an input range of [0.0, 1.0], \
+                     so the LUT's input will be clipped to that range."
+                        .into(),
+                );
+            }
+            lib::lut_formats::write_icc(
+                &mut std::io::BufWriter::new(std::fs::File::create(path)?),
+                lut.ranges[0],
+                [&lut.tables[0], &lut.tables[1], &lut.tables[2]],
+                exp_options
+                    .icc_primaries
+                    .chromaticities(colorbox::chroma::REC709)
+                    .unwrap_or(colorbox::chroma::REC709),
+                gamut_matrix,
+                metadata,
+            )?
+        }
     }
-    histogram_sets
+
+    Ok(warnings)
 }
 
-fn exposure_mappings(
-    histogram_sets: &[[Vec<(Histogram, f32)>; 3]],
-    floor: [f32; 3],
-    ceiling: [f32; 3],
-) -> [Vec<ExposureMapping>; 3] {
-    let mut mappings = [Vec::new(), Vec::new(), Vec::new()];
-
-    for histograms in histogram_sets.iter() {
-        for chan in 0..histograms.len() {
-            for i in 0..histograms[chan].len() {
-                // Find the histogram with closest to 2x the exposure of this one.
-                const TARGET_RATIO: f32 = 2.0;
-                let mut other_hist_i = i;
-                let mut best_ratio: f32 = -std::f32::INFINITY;
-                for j in (i + 1)..histograms[chan].len() {
-                    let ratio = histograms[chan][j].1 / histograms[chan][i].1;
-                    if (ratio - TARGET_RATIO).abs() > (best_ratio - TARGET_RATIO).abs() {
-                        break;
-                    }
-                    other_hist_i = j;
-                    best_ratio = ratio;
-                }
+/// Writes a 3D LUT file in the given format, sampling `f` over `range`
+/// on all axes, and returns any warnings.
+///
+/// The format must support 3D LUTs.
+fn write_lut_3d_file<F: Fn([f32; 3]) -> [f32; 3]>(
+    path: &std::path::Path,
+    range: (f32, f32),
+    f: F,
+    exp_fmt: ExportFormat,
+    exp_options: ExportOptions,
+    metadata: &LutMetadata,
+) -> std::io::Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    if exp_fmt != ExportFormat::Cube && range != (0.0, 1.0) {
+        warnings.push(format!(
+            "The {} format only supports an input range of [0.0, 1.0], \
+             so the LUT's input will be clipped to that range.",
+            exp_fmt.ui_text()
+        ));
+    }
 
-                // Compute and add the exposure mapping.
-                if other_hist_i > i {
-                    mappings[chan].push(ExposureMapping::from_histograms(
-                        &histograms[chan][i].0,
-                        &histograms[chan][other_hist_i].0,
-                        histograms[chan][i].1,
-                        histograms[chan][other_hist_i].1,
-                        floor[chan],
-                        ceiling[chan],
-                    ));
-                }
-            }
+    let metadata = &named_metadata(metadata, path);
+    let f = |rgb| quantize_rgb(f(rgb), exp_options);
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    match exp_fmt {
+        ExportFormat::Cube => {
+            lib::lut_formats::write_cube_3d(&mut file, range, exp_options.cube_size, f, metadata)?
+        }
+        ExportFormat::Lustre3dl => {
+            lib::lut_formats::write_3dl(&mut file, exp_options.cube_size, f, metadata)?
+        }
+        ExportFormat::PanasonicVlt => lib::lut_formats::write_vlt(&mut file, f, metadata)?,
+        ExportFormat::SonyCube => {
+            let cube_size = if exp_options.cube_size <= 17 { 17 } else { 33 };
+            lib::lut_formats::write_sony_cube(&mut file, cube_size, f, metadata)?
+        }
+        ExportFormat::Spi1D | ExportFormat::Clf | ExportFormat::Csp | ExportFormat::Icc => {
+            unreachable!()
         }
     }
 
-    mappings
+    Ok(warnings)
+}
+
+/// A copy of `metadata`, named after the file at `path` if it isn't
+/// already named.
+fn named_metadata(metadata: &LutMetadata, path: &std::path::Path) -> LutMetadata {
+    let mut metadata = metadata.clone();
+    if metadata.name.is_empty() {
+        metadata.name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into())
+            .unwrap_or_else(|| String::from("LUT"));
+    }
+    metadata
+}
+
+/// Resamples a LUT so that all channels share a single input range,
+/// and optionally to a specific table length.
+fn single_range_lut(lut: colorbox::lut::Lut1D, resolution: usize) -> colorbox::lut::Lut1D {
+    let ranges_are_equal = lut
+        .ranges
+        .iter()
+        .fold((lut.ranges[0], true), |a, b| (a.0, a.1 && a.0 == *b))
+        .1;
+    let mut lut = if ranges_are_equal {
+        lut
+    } else {
+        lut.resample_to_single_range(
+            lut.tables[0]
+                .len()
+                .max((lut.tables[0].len() * 4).min(1 << 12)),
+        )
+    };
+
+    if resolution >= 2 {
+        for table in lut.tables.iter_mut() {
+            let norm = 1.0 / (resolution - 1) as f32;
+            *table = (0..resolution)
+                .map(|i| lerp_slice(table, i as f32 * norm))
+                .collect();
+        }
+    }
+
+    lut
+}
+
+/// Extends the tables of a LUT beyond its domain as set in the export
+/// options, so that applications which clamp at the domain follow the
+/// chosen extrapolation.
+fn extended_lut(lut: colorbox::lut::Lut1D, exp_options: ExportOptions) -> colorbox::lut::Lut1D {
+    ExtrapolatedLut::new(lut, exp_options.extrapolation).extended(EXTRAPOLATION_MARGIN)
+}
+
+/// Quantizes the tables of a LUT as set in the export options.
+fn quantized_lut(
+    mut lut: colorbox::lut::Lut1D,
+    exp_options: ExportOptions,
+) -> colorbox::lut::Lut1D {
+    for table in lut.tables.iter_mut() {
+        lib::lut_formats::quantize_table(table, exp_options.quantization, exp_options.dither);
+    }
+    lut
+}
+
+/// Quantizes an RGB value as set in the export options.
+fn quantize_rgb(rgb: [f32; 3], exp_options: ExportOptions) -> [f32; 3] {
+    let q = exp_options.quantization;
+    [q.quantize(rgb[0]), q.quantize(rgb[1]), q.quantize(rgb[2])]
+}
+
+/// Evaluates a single-range LUT along with a matrix, which is applied
+/// after the LUT when going to linear and before it otherwise.
+fn eval_with_matrix(
+    lut: &colorbox::lut::Lut1D,
+    rgb: [f32; 3],
+    matrix: colorbox::matrix::Matrix,
+    to_linear: bool,
+) -> [f32; 3] {
+    let eval_1d = |rgb: [f32; 3]| {
+        let (min, max) = lut.ranges[0];
+        let mut out = [0.0f32; 3];
+        for chan in 0..3 {
+            let n = ((rgb[chan] - min) / (max - min)).max(0.0).min(1.0);
+            out[chan] = lerp_slice(&lut.tables[chan], n);
+        }
+        out
+    };
+
+    if to_linear {
+        modified_tf::apply_matrix(eval_1d(rgb), matrix)
+    } else {
+        eval_1d(modified_tf::apply_matrix(rgb, matrix))
+    }
 }
 
 //-------------------------------------------------------------
@@ -909,22 +2095,113 @@ enum AppMode {
 enum ExportFormat {
     Cube,
     Spi1D,
+    Clf,
+    Csp,
+    Lustre3dl,
+    PanasonicVlt,
+    SonyCube,
+    Icc,
 }
 
+const EXPORT_FORMATS: &[ExportFormat] = &[
+    ExportFormat::Cube,
+    ExportFormat::Spi1D,
+    ExportFormat::Clf,
+    ExportFormat::Csp,
+    ExportFormat::Lustre3dl,
+    ExportFormat::PanasonicVlt,
+    ExportFormat::SonyCube,
+    ExportFormat::Icc,
+];
+
 impl ExportFormat {
     fn ui_text(&self) -> &'static str {
         use ExportFormat::*;
         match *self {
             Cube => ".cube",
             Spi1D => ".spi1d",
+            Clf => ".clf",
+            Csp => ".csp",
+            Lustre3dl => ".3dl",
+            PanasonicVlt => "Panasonic .vlt",
+            SonyCube => "Sony camera .cube",
+            Icc => ".icc",
         }
     }
 
     fn ext(&self) -> &'static str {
         use ExportFormat::*;
         match *self {
-            Cube => "cube",
+            Cube | SonyCube => "cube",
             Spi1D => "spi1d",
+            Clf => "clf",
+            Csp => "csp",
+            Lustre3dl => "3dl",
+            PanasonicVlt => "vlt",
+            Icc => "icc",
+        }
+    }
+
+    /// Unique name of the format, for preferences and the command line.
+    fn id(&self) -> &'static str {
+        use ExportFormat::*;
+        match *self {
+            SonyCube => "sony-cube",
+            _ => self.ext(),
+        }
+    }
+
+    /// Whether the format can represent a gamut (matrix) stage.
+    fn supports_gamut(&self) -> bool {
+        use ExportFormat::*;
+        match *self {
+            Spi1D | Csp => false,
+            Cube | Clf | Lustre3dl | PanasonicVlt | SonyCube | Icc => true,
+        }
+    }
+
+    /// Whether the format can hold a full 3D LUT.
+    fn supports_3d(&self) -> bool {
+        use ExportFormat::*;
+        match *self {
+            Spi1D | Clf | Csp | Icc => false,
+            Cube | Lustre3dl | PanasonicVlt | SonyCube => true,
+        }
+    }
+}
+
+/// Output quantizations offered for export.
+const QUANTIZATIONS: &[Quantization] = &[
+    Quantization::None,
+    Quantization::Half,
+    Quantization::Integer(8),
+    Quantization::Integer(10),
+    Quantization::Integer(12),
+    Quantization::Integer(16),
+];
+
+/// Format-specific export settings.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ExportOptions {
+    resolution: usize,    // 1D .cube, CLF, and CSP table length.  Zero means unchanged.
+    shaper_points: usize, // CSP pre-LUT points.  Two or fewer means a linear shaper.
+    cube_size: usize,     // Resolution of 3D LUTs.
+    quantization: Quantization, // Precision of the output values.
+    dither: bool,         // Whether to dither quantized 1D tables.
+    extrapolation: Extrapolation, // Out-of-domain behavior baked into 1D tables.
+    icc_primaries: ChromaSpace, // Primaries of the linear side of ICC profiles.
+}
+
+impl ExportOptions {
+    fn new() -> ExportOptions {
+        ExportOptions {
+            resolution: 0,
+            shaper_points: 2,
+            cube_size: 33,
+            quantization: Quantization::None,
+            dither: true,
+            extrapolation: Extrapolation::Clamp,
+            icc_primaries: ChromaSpace::Rec709,
         }
     }
 }
@@ -1,15 +1,52 @@
+use lib::undo::HistoryAction;
+
 use crate::egui::{self, Context};
 
 /// The top menu bar of the UI.
-pub fn menu_bar(ctx: &Context) {
+pub fn menu_bar(ctx: &Context, app: &mut crate::AppMain, job_count: usize) {
     egui::containers::panel::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
         egui::menu::bar(ui, |ui| {
             egui::menu::menu_button(ui, "File", |ui| {
+                if ui
+                    .add(egui::widgets::Button::new("Profile This Camera..."))
+                    .on_hover_text("A guided walk through profiling a camera, from loading images to exporting its LUTs.")
+                    .clicked()
+                {
+                    app.profile_wizard.open = true;
+                    ui.close_menu();
+                }
+                ui.separator();
+                ui.menu_button("Preferences", |ui| {
+                    app.preferences.ui(ui, crate::DEFAULT_WORKER_COUNT);
+                    lib::display_profile::profile_ui(ui, &mut app.preferences.display_profile);
+                });
                 ui.separator();
                 if ui.add(egui::widgets::Button::new("Quit")).clicked() {
                     ctx.send_viewport_cmd(egui::viewport::ViewportCommand::Close);
                 }
             });
+            egui::menu::menu_button(ui, "Edit", |ui| {
+                if ui
+                    .add_enabled(
+                        job_count == 0 && app.undo_stack.can_undo(),
+                        egui::widgets::Button::new("Undo").shortcut_text("Ctrl+Z"),
+                    )
+                    .clicked()
+                {
+                    app.apply_history_action(HistoryAction::Undo);
+                    ui.close_menu();
+                }
+                if ui
+                    .add_enabled(
+                        job_count == 0 && app.undo_stack.can_redo(),
+                        egui::widgets::Button::new("Redo").shortcut_text("Ctrl+Shift+Z"),
+                    )
+                    .clicked()
+                {
+                    app.apply_history_action(HistoryAction::Redo);
+                    ui.close_menu();
+                }
+            });
         });
     });
 }
@@ -2,10 +2,15 @@ use std::path::PathBuf;
 
 use crate::egui::{self, Align, Ui};
 
-use crate::{AppMode, ExportFormat};
+use lib::lut_formats::Quantization;
+use ocio_gen::lut_extrapolation::EXTRAPOLATIONS;
+
+use crate::{AppMode, ExportFormat, EXPORT_FORMATS, QUANTIZATIONS};
 
 /// Mode tabs and export buttons.
 pub fn bar(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize, working_dir: &mut PathBuf) {
+    export_options_window(ui.ctx(), app);
+
     let export_lut_dialog = {
         let exp_fmt = app.ui_data.lock().export_format;
         let mut d = rfd::FileDialog::new()
@@ -21,6 +26,24 @@ pub fn bar(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize, working_dir:
         d
     };
 
+    let export_pair_dialog = {
+        let mut d = rfd::FileDialog::new().set_title("Export LUT Pair To Directory");
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
+
+    let register_config_dialog = {
+        let mut d = rfd::FileDialog::new()
+            .set_title("Select OCIO Maker Config")
+            .add_filter("OCIO config", &["ocio"]);
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
+
     ui.with_layout(egui::Layout::right_to_left(Align::Max), |ui| {
         // Export buttons.
         let export_enabled = {
@@ -28,10 +51,37 @@ pub fn bar(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize, working_dir:
             job_count == 0
                 && ((mode == AppMode::Estimate && app.transfer_function_tables.lock().is_some())
                     || (mode == AppMode::Modify
-                        && app.ui_data.lock().modified.loaded_lut.is_some())
+                        && (app.ui_data.lock().modified.loaded_lut.is_some()
+                            || app.ui_data.lock().modified.loaded_lut_3d.is_some()))
                     || mode == AppMode::Generate)
         };
         ui.add_space(8.0);
+        if ui
+            .add_enabled(export_enabled, egui::widgets::Button::new("Export LUT Pair..."))
+            .on_hover_text(
+                "Exports both the 'to linear' and 'from linear' LUTs, named after the camera profile.",
+            )
+            .clicked()
+        {
+            if let Some(dir) = export_pair_dialog.clone().pick_folder() {
+                let register = app.ui_data.lock().register_lut_pair;
+                let config = if register {
+                    register_config_dialog.clone().pick_file()
+                } else {
+                    None
+                };
+                if !register || config.is_some() {
+                    app.export_lut_pair(&dir, config);
+                }
+                *working_dir = dir;
+            }
+        }
+        ui.checkbox(
+            &mut app.ui_data.lock_mut().register_lut_pair,
+            "Add to OCIO Maker config",
+        )
+        .on_hover_text("After exporting a LUT pair, add the camera as an input color space to a config made with OCIO Maker.");
+        ui.add_space(8.0);
         if ui
             .add_enabled(
                 export_enabled,
@@ -40,7 +90,7 @@ pub fn bar(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize, working_dir:
             .clicked()
         {
             if let Some(path) = export_lut_dialog.clone().save_file() {
-                app.export_lut(&path, false);
+                app.export_lut(&path, false, Vec::new());
                 if let Some(parent) = path.parent().map(|p| p.into()) {
                     *working_dir = parent;
                 }
@@ -54,7 +104,7 @@ pub fn bar(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize, working_dir:
             .clicked()
         {
             if let Some(path) = export_lut_dialog.clone().save_file() {
-                app.export_lut(&path, true);
+                app.export_lut(&path, true, Vec::new());
                 if let Some(parent) = path.parent().map(|p| p.into()) {
                     *working_dir = parent;
                 }
@@ -62,16 +112,17 @@ pub fn bar(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize, working_dir:
         }
         ui.add_space(8.0);
         {
-            let exp_fmt = &mut app.ui_data.lock_mut().export_format;
+            let ui_data = &mut *app.ui_data.lock_mut();
+            if ui.button("Options...").clicked() {
+                ui_data.show_export_options = true;
+            }
+            let exp_fmt = &mut ui_data.export_format;
             egui::ComboBox::from_label("Export format:")
                 .selected_text(exp_fmt.ui_text())
                 .show_ui(ui, |ui| {
-                    ui.selectable_value(exp_fmt, ExportFormat::Cube, ExportFormat::Cube.ui_text());
-                    ui.selectable_value(
-                        exp_fmt,
-                        ExportFormat::Spi1D,
-                        ExportFormat::Spi1D.ui_text(),
-                    );
+                    for format in EXPORT_FORMATS.iter() {
+                        ui.selectable_value(exp_fmt, *format, format.ui_text());
+                    }
                 });
         }
 
@@ -103,3 +154,120 @@ pub fn bar(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize, working_dir:
         });
     });
 }
+
+/// Window with the options of the selected export format.
+fn export_options_window(ctx: &egui::Context, app: &mut crate::AppMain) {
+    let ui_data = &mut *app.ui_data.lock_mut();
+    let format = ui_data.export_format;
+    let options = &mut ui_data.export_options;
+
+    egui::Window::new(format!("{} Export Options", format.ui_text()))
+        .open(&mut ui_data.show_export_options)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            match format {
+                ExportFormat::Clf | ExportFormat::Csp | ExportFormat::Cube | ExportFormat::Icc => {
+                    ui.horizontal(|ui| {
+                        ui.label("Resolution:");
+                        ui.add(egui::widgets::DragValue::new(&mut options.resolution).clamp_range(0..=65536));
+                    })
+                    .response
+                    .on_hover_text("Number of entries in the LUT's table (e.g. 4096 for a 12-bit domain).  Zero keeps the native resolution.");
+                    if format == ExportFormat::Csp {
+                        let mut use_shaper = options.shaper_points > 2;
+                        ui.checkbox(&mut use_shaper, "Logarithmic shaper")
+                            .on_hover_text("Use a logarithmically spaced pre-LUT, for better shadow precision in LUTs with a wide input range (e.g. 'from linear' LUTs).");
+                        if use_shaper {
+                            if options.shaper_points <= 2 {
+                                options.shaper_points = 64;
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label("Shaper points:");
+                                ui.add(
+                                    egui::widgets::DragValue::new(&mut options.shaper_points)
+                                        .clamp_range(3..=4096),
+                                );
+                            });
+                        } else {
+                            options.shaper_points = 2;
+                        }
+                    }
+                    if format == ExportFormat::Cube {
+                        ui.horizontal(|ui| {
+                            ui.label("Cube size:");
+                            ui.add(egui::widgets::DragValue::new(&mut options.cube_size).clamp_range(2..=65));
+                        })
+                        .response
+                        .on_hover_text("Only used when a gamut conversion is baked into the LUT, in which case a 3D LUT is written.");
+                    }
+                    if format == ExportFormat::Icc {
+                        ui.horizontal(|ui| {
+                            crate::modified_tf::chroma_space_selector(
+                                ui,
+                                "ICC Primaries",
+                                "Primaries:",
+                                &mut options.icc_primaries,
+                            );
+                        })
+                        .response
+                        .on_hover_text("The primaries of the linear color space, which the profile's colorants are computed from.  When a gamut conversion is enabled, this should be its target.");
+                    }
+                }
+                ExportFormat::Lustre3dl => {
+                    ui.horizontal(|ui| {
+                        ui.label("Cube size:");
+                        ui.add(egui::widgets::DragValue::new(&mut options.cube_size).clamp_range(2..=65));
+                    });
+                }
+                ExportFormat::SonyCube => {
+                    ui.horizontal(|ui| {
+                        ui.label("Cube size:");
+                        for size in [17, 33].iter() {
+                            if ui.selectable_label(options.cube_size == *size, format!("{}", size)).clicked() {
+                                options.cube_size = *size;
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Sony cameras load 17 and 33-point user LUTs.");
+                }
+                ExportFormat::Spi1D | ExportFormat::PanasonicVlt => {}
+            }
+
+            // Out-of-domain behavior, for formats written as 1D tables.
+            if !matches!(
+                format,
+                ExportFormat::Lustre3dl
+                    | ExportFormat::PanasonicVlt
+                    | ExportFormat::SonyCube
+                    | ExportFormat::Icc
+            ) {
+                egui::ComboBox::from_label("Extrapolation")
+                    .selected_text(options.extrapolation.ui_text())
+                    .show_ui(ui, |ui| {
+                        for extrapolation in EXTRAPOLATIONS.iter() {
+                            ui.selectable_value(&mut options.extrapolation, *extrapolation, extrapolation.ui_text());
+                        }
+                    })
+                    .response
+                    .on_hover_text("What the LUT gives for input outside of its range.  Other than clamping, the table is extended past its range, so that applications which clamp agree on the result.  CLF files instead get a table over the whole half float range.");
+            }
+
+            // Output quantization, for all formats.
+            egui::ComboBox::from_label("Quantization")
+                .selected_text(options.quantization.ui_text())
+                .show_ui(ui, |ui| {
+                    for quantization in QUANTIZATIONS.iter() {
+                        ui.selectable_value(&mut options.quantization, *quantization, quantization.ui_text());
+                    }
+                })
+                .response
+                .on_hover_text("Round the LUT's output values to a fixed precision, for cameras and monitors that load LUTs at that precision (e.g. 33-point cubes with 10 or 12-bit values).");
+            ui.add_enabled(
+                options.quantization != Quantization::None,
+                egui::widgets::Checkbox::new(&mut options.dither, "Dither"),
+            )
+            .on_hover_text("Carry the rounding error of each 1D table entry over to the next, so the curve's average slope is preserved instead of running in flat steps.");
+        });
+}
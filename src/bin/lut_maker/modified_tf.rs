@@ -1,13 +1,23 @@
 use std::path::PathBuf;
 
+use colorbox::matrix::{self, AdaptationMethod, Matrix};
 use sensor_analysis::utils::lerp_slice;
 
+use lib::chroma_space::{ChromaSpace, CHROMA_SPACES};
+use lib::lut_modify::{Lut3DStack, Trims};
+
 use crate::egui::{self, Ui};
 
 pub struct ModifiedTF {
     pub loaded_lut: Option<(colorbox::lut::Lut1D, colorbox::lut::Lut1D, PathBuf)>, // (to linear, from linear, path)
     pub sensor_floor: (bool, [f32; 3]), // The bool is whether to do an adjustment at all.
     pub sensor_ceiling: (bool, [f32; 3]),
+    pub gamut: GamutStage,
+    pub curve_edit: CurveEdit,
+
+    // Used instead of `loaded_lut` when a 3D LUT is loaded.
+    pub loaded_lut_3d: Option<Lut3DStack>,
+    pub trims: Trims,
 }
 
 impl ModifiedTF {
@@ -16,14 +26,79 @@ impl ModifiedTF {
             loaded_lut: None,
             sensor_floor: (false, [0.0; 3]),
             sensor_ceiling: (false, [1.0; 3]),
+            gamut: GamutStage::new(),
+            curve_edit: CurveEdit::new(),
+
+            loaded_lut_3d: None,
+            trims: Trims::new(),
         }
     }
 
-    /// Returns the LUT with the adjustments made from the modified settings.
+    /// The response of the loaded 3D LUT stack along its neutral axis,
+    /// in the same form as `adjusted_lut()`.
+    pub fn neutral_axis_lut(&self) -> Option<[(Vec<f32>, f32, f32); 3]> {
+        const SAMPLES: usize = 256;
+
+        let stack = self.loaded_lut_3d.as_ref()?;
+        let range = stack.input_range();
+        let samples: Vec<[f32; 3]> = (0..SAMPLES)
+            .map(|i| {
+                let x = range.0 + ((range.1 - range.0) * i as f32 / (SAMPLES - 1) as f32);
+                stack.eval([x; 3], &self.trims)
+            })
+            .collect();
+        let table = |chan: usize| -> Vec<f32> { samples.iter().map(|rgb| rgb[chan]).collect() };
+
+        Some([
+            (table(0), range.0, range.1),
+            (table(1), range.0, range.1),
+            (table(2), range.0, range.1),
+        ])
+    }
+
+    /// Returns the LUT with the adjustments made from the modified settings,
+    /// including the trims and any hand edits to the curve.
     ///
     /// The returned value is an array of (lut, range start, range end) tuples,
     /// one for each channel.
     pub fn adjusted_lut(&self, to_linear: bool) -> Option<[(Vec<f32>, f32, f32); 3]> {
+        if !self.curve_edit.is_active() {
+            return self.unedited_lut(to_linear);
+        }
+
+        let mut luts = self.unedited_lut(true)?;
+        for lut in luts.iter_mut() {
+            self.curve_edit.apply(&mut lut.0);
+        }
+        if to_linear {
+            Some(luts)
+        } else {
+            // The from-linear LUT has to be recomputed from the edited curve.
+            Some(invert_luts(&luts))
+        }
+    }
+
+    /// Like `adjusted_lut()`, but without the hand edits to the curve.
+    pub fn unedited_lut(&self, to_linear: bool) -> Option<[(Vec<f32>, f32, f32); 3]> {
+        if self.trims.is_identity() {
+            return self.floor_ceiling_lut(to_linear);
+        }
+
+        let mut luts = self.floor_ceiling_lut(true)?;
+        for lut in luts.iter_mut() {
+            for n in lut.0.iter_mut() {
+                *n = self.trims.apply(*n);
+            }
+        }
+        if to_linear {
+            Some(luts)
+        } else {
+            Some(invert_luts(&luts))
+        }
+    }
+
+    /// The loaded LUT with just the sensor floor and ceiling adjustments.
+    fn floor_ceiling_lut(&self, to_linear: bool) -> Option<[(Vec<f32>, f32, f32); 3]> {
         let floor = self.sensor_floor.1;
         let ceiling = self.sensor_ceiling.1;
 
@@ -125,6 +200,138 @@ impl ModifiedTF {
     }
 }
 
+/// Inverts to-linear LUTs in the form returned by `adjusted_lut()`.
+fn invert_luts(luts: &[(Vec<f32>, f32, f32); 3]) -> [(Vec<f32>, f32, f32); 3] {
+    let inverted = colorbox::lut::Lut1D {
+        ranges: luts.iter().map(|lut| (lut.1, lut.2)).collect(),
+        tables: luts.iter().map(|lut| lut.0.clone()).collect(),
+    }
+    .resample_inverted(4096);
+    let table = |chan: usize| {
+        let range = inverted.ranges[chan.min(inverted.ranges.len() - 1)];
+        (inverted.tables[chan].clone(), range.0, range.1)
+    };
+    [table(0), table(1), table(2)]
+}
+
+/// Number of control points in the curve editor, spread evenly across
+/// the input range.
+pub const CURVE_EDIT_POINTS: usize = 9;
+
+/// Hand edits to the to-linear curve, stored as offsets of the output
+/// at each control point.  The offsets are interpolated with a spline
+/// between the control points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurveEdit {
+    pub enabled: bool,
+    pub offsets: Vec<f32>,
+}
+
+impl CurveEdit {
+    pub fn new() -> CurveEdit {
+        CurveEdit {
+            enabled: false,
+            offsets: vec![0.0; CURVE_EDIT_POINTS],
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.offsets.iter().any(|n| *n != 0.0)
+    }
+
+    /// The position of the given control point in the normalized
+    /// input range.
+    pub fn point_position(index: usize) -> f32 {
+        index as f32 / (CURVE_EDIT_POINTS - 1) as f32
+    }
+
+    /// The spline-interpolated offset at `t` in the normalized input
+    /// range.
+    pub fn offset_at(&self, t: f32) -> f32 {
+        let last = self.offsets.len() - 1;
+        let x = t.max(0.0).min(1.0) * last as f32;
+        let i = (x as usize).min(last - 1);
+        let f = x - i as f32;
+
+        // Catmull-Rom, with the end points duplicated.
+        let p0 = self.offsets[i.saturating_sub(1)];
+        let p1 = self.offsets[i];
+        let p2 = self.offsets[i + 1];
+        let p3 = self.offsets[(i + 2).min(last)];
+        0.5 * ((2.0 * p1)
+            + ((p2 - p0) * f)
+            + (((2.0 * p0) - (5.0 * p1) + (4.0 * p2) - p3) * f * f)
+            + (((3.0 * p1) - p0 - (3.0 * p2) + p3) * f * f * f))
+    }
+
+    /// Applies the edits to a to-linear table, keeping it monotonic.
+    pub fn apply(&self, table: &mut [f32]) {
+        let norm = 1.0 / (table.len() - 1).max(1) as f32;
+        let mut prev = -std::f32::INFINITY;
+        for (i, y) in table.iter_mut().enumerate() {
+            *y = (*y + self.offset_at(i as f32 * norm)).max(prev);
+            prev = *y;
+        }
+    }
+}
+
+/// An optional 3x3 matrix applied in linear space, after the to-linear
+/// curve (or before the from-linear curve).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GamutStage {
+    pub enabled: bool,
+    pub use_custom_matrix: bool,
+    pub source: ChromaSpace,
+    pub target: ChromaSpace,
+    pub custom_matrix: Matrix,
+}
+
+impl GamutStage {
+    pub fn new() -> GamutStage {
+        GamutStage {
+            enabled: false,
+            use_custom_matrix: false,
+            source: ChromaSpace::Rec709,
+            target: ChromaSpace::Rec709,
+            custom_matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+
+    /// The to-linear direction matrix, or `None` if the stage is disabled
+    /// or doesn't have a valid matrix.
+    pub fn matrix(&self) -> Option<Matrix> {
+        if !self.enabled {
+            return None;
+        }
+        if self.use_custom_matrix {
+            return Some(self.custom_matrix);
+        }
+
+        let source = self.source.chromaticities(colorbox::chroma::REC709)?;
+        let target = self.target.chromaticities(colorbox::chroma::REC709)?;
+        Some(matrix::compose(&[
+            matrix::rgb_to_xyz_matrix(source),
+            matrix::xyz_chromatic_adaptation_matrix(source.w, target.w, AdaptationMethod::Bradford),
+            matrix::xyz_to_rgb_matrix(target),
+        ]))
+    }
+
+    /// Like `matrix()`, but for the given direction.
+    pub fn directional_matrix(&self, to_linear: bool) -> Option<Matrix> {
+        if to_linear {
+            self.matrix()
+        } else {
+            self.matrix().and_then(matrix::invert)
+        }
+    }
+}
+
+/// Applies a matrix to an RGB color.
+pub fn apply_matrix(rgb: [f32; 3], mat: Matrix) -> [f32; 3] {
+    let out = matrix::transform_color([rgb[0] as f64, rgb[1] as f64, rgb[2] as f64], mat);
+    [out[0] as f32, out[1] as f32, out[2] as f32]
+}
+
 pub fn modified_mode_ui(
     ui: &mut Ui,
     app: &mut crate::AppMain,
@@ -144,10 +351,20 @@ pub fn modified_mode_ui(
         }
         d
     };
+    let load_3d_lut_dialog = {
+        let mut d = rfd::FileDialog::new()
+            .set_title("Load 3D LUT")
+            .add_filter("cube", &["cube"]);
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
 
     // Transfer function controls.
     let area_width = ui.available_width();
     let sub_area_width = (area_width / 3.0).min(230.0);
+    let bit_depth = app.ui_data.lock().code_value_bits;
     ui.horizontal(|ui| {
         ui.vertical(|ui| {
             ui.set_width(sub_area_width);
@@ -189,6 +406,79 @@ pub fn modified_mode_ui(
                         std::mem::swap(lut1, lut2);
                     }
                 }
+            } else if app.ui_data.lock().modified.loaded_lut_3d.is_some() {
+                let (name, has_shaper, pre_curve, post_curve) = {
+                    let ui_data = app.ui_data.lock();
+                    let stack = ui_data.modified.loaded_lut_3d.as_ref().unwrap();
+                    let file_name = |path: &PathBuf| -> String {
+                        path.file_name()
+                            .map(|name| name.to_string_lossy().into())
+                            .unwrap_or_else(|| "Unnamed LUT".into())
+                    };
+                    (
+                        file_name(&stack.path),
+                        stack.shaper.is_some(),
+                        stack.pre_curve.as_ref().map(|(_, path)| file_name(path)),
+                        stack.post_curve.as_ref().map(|(_, path)| file_name(path)),
+                    )
+                };
+                ui.horizontal(|ui| {
+                    ui.strong(name);
+                    if ui
+                        .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
+                        .clicked()
+                    {
+                        app.ui_data.lock_mut().modified.loaded_lut_3d = None;
+                    }
+                });
+                ui.label(if has_shaper {
+                    "(3D LUT with shaper)"
+                } else {
+                    "(3D LUT)"
+                });
+                ui.add_space(4.0);
+
+                // Pre and post curves.
+                for (label, curve, post) in [
+                    ("Pre curve", pre_curve, false),
+                    ("Post curve", post_curve, true),
+                ] {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}:", label));
+                        if let Some(curve_name) = curve {
+                            ui.strong(curve_name);
+                            if ui
+                                .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
+                                .clicked()
+                            {
+                                if let Some(ref mut stack) =
+                                    app.ui_data.lock_mut().modified.loaded_lut_3d
+                                {
+                                    if post {
+                                        stack.post_curve = None;
+                                    } else {
+                                        stack.pre_curve = None;
+                                    }
+                                }
+                            }
+                        } else if ui
+                            .add_enabled(job_count == 0, egui::widgets::Button::new("Load..."))
+                            .on_hover_text(if post {
+                                "A 1D LUT applied after the 3D LUT and trims."
+                            } else {
+                                "A 1D LUT applied before the 3D LUT."
+                            })
+                            .clicked()
+                        {
+                            if let Some(path) = load_1d_lut_dialog.clone().pick_file() {
+                                app.load_lut_3d_curve(&path, post);
+                                if let Some(parent) = path.parent().map(|p| p.into()) {
+                                    *working_dir = parent;
+                                }
+                            }
+                        }
+                    });
+                }
             } else {
                 ui.horizontal(|ui| {
                     if ui
@@ -202,12 +492,47 @@ pub fn modified_mode_ui(
                             }
                         }
                     }
+                    if ui
+                        .add_enabled(job_count == 0, egui::widgets::Button::new("Load 3D LUT..."))
+                        .clicked()
+                    {
+                        if let Some(path) = load_3d_lut_dialog.clone().pick_file() {
+                            app.load_lut_3d(&path);
+                            if let Some(parent) = path.parent().map(|p| p.into()) {
+                                *working_dir = parent;
+                            }
+                        }
+                    }
                 });
+                let have_estimate = app.transfer_function_tables.lock().is_some();
+                if ui
+                    .add_enabled(
+                        job_count == 0 && have_estimate,
+                        egui::widgets::Button::new("Use Estimated Curve"),
+                    )
+                    .on_hover_text("Use the curve from Estimate mode, e.g. to hand-tweak it.")
+                    .clicked()
+                {
+                    app.use_estimated_curve();
+                }
             }
         });
 
         ui.add_space(8.0);
 
+        // A 3D LUT gets trims instead of the floor and ceiling controls.
+        if app.ui_data.lock().modified.loaded_lut_3d.is_some() {
+            ui.vertical(|ui| {
+                ui.set_width(sub_area_width);
+                trims_ui(
+                    ui,
+                    &mut app.ui_data.lock_mut().modified.trims,
+                    job_count == 0,
+                );
+            });
+            return;
+        }
+
         let have_lut = app.ui_data.lock().modified.loaded_lut.is_some();
 
         ui.add_enabled_ui(have_lut, |ui| {
@@ -235,20 +560,19 @@ pub fn modified_mode_ui(
                     }
                 });
                 ui.add_space(4.0);
-                for (label, value) in ["R: ", "G: ", "B: "]
-                    .iter()
-                    .zip(app.ui_data.lock_mut().modified.sensor_floor.1.iter_mut())
-                {
-                    ui.horizontal(|ui| {
-                        ui.label(*label);
-                        ui.add_enabled(
-                            job_count == 0 && adjust_floor,
-                            egui::widgets::Slider::new(value, 0.0..=1.0)
-                                .max_decimals(5)
-                                .min_decimals(5),
-                        );
-                    });
-                }
+                crate::code_value::channel_rows(
+                    ui,
+                    &mut app.ui_data.lock_mut().modified.sensor_floor.1,
+                    bit_depth,
+                    job_count == 0 && adjust_floor,
+                );
+                ui.add_space(4.0);
+                crate::code_value::bit_depth_selector(
+                    ui,
+                    "modified code value bits",
+                    &mut app.ui_data.lock_mut().code_value_bits,
+                    job_count == 0,
+                );
             });
 
             ui.add_space(8.0);
@@ -275,21 +599,176 @@ pub fn modified_mode_ui(
                     }
                 });
                 ui.add_space(4.0);
-                for (label, value) in ["R: ", "G: ", "B: "]
-                    .iter()
-                    .zip(app.ui_data.lock_mut().modified.sensor_ceiling.1.iter_mut())
-                {
-                    ui.horizontal(|ui| {
-                        ui.label(*label);
-                        ui.add_enabled(
-                            job_count == 0 && adjust_ceiling,
-                            egui::widgets::Slider::new(value, 0.0..=1.0)
-                                .max_decimals(5)
-                                .min_decimals(5),
-                        );
-                    });
-                }
+                crate::code_value::channel_rows(
+                    ui,
+                    &mut app.ui_data.lock_mut().modified.sensor_ceiling.1,
+                    bit_depth,
+                    job_count == 0 && adjust_ceiling,
+                );
             });
         });
     });
+
+    ui.add_space(8.0);
+
+    // Curve editing controls.
+    let have_lut = app.ui_data.lock().modified.loaded_lut.is_some();
+    ui.add_enabled_ui(have_lut && job_count == 0, |ui| {
+        ui.horizontal(|ui| {
+            let curve_edit = &mut app.ui_data.lock_mut().modified.curve_edit;
+            ui.checkbox(&mut curve_edit.enabled, "Edit Curve")
+                .on_hover_text(
+                    "Drag the control points in the \"To Linear\" graph to hand-tweak the curve.",
+                );
+            if ui
+                .add_enabled(curve_edit.enabled, egui::widgets::Button::new("Reset"))
+                .clicked()
+            {
+                *curve_edit = CurveEdit {
+                    enabled: true,
+                    ..CurveEdit::new()
+                };
+            }
+        });
+    });
+
+    ui.add_space(8.0);
+
+    // Trims.  These are shown next to the LUT for 3D LUTs.
+    if app.ui_data.lock().modified.loaded_lut_3d.is_none() {
+        ui.add_enabled_ui(have_lut, |ui| {
+            trims_ui(
+                ui,
+                &mut app.ui_data.lock_mut().modified.trims,
+                job_count == 0,
+            );
+        });
+        ui.add_space(8.0);
+    }
+
+    // Gamut stage controls.
+    ui.add_enabled_ui(have_lut && job_count == 0, |ui| {
+        let gamut = &mut app.ui_data.lock_mut().modified.gamut;
+        ui.checkbox(&mut gamut.enabled, "Gamut Conversion")
+            .on_hover_text("Applies a 3x3 matrix in linear space.  Exports with a gamut conversion are baked into a 3D LUT.");
+        if !gamut.enabled {
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut gamut.use_custom_matrix, false, "Chromaticities");
+            ui.radio_value(&mut gamut.use_custom_matrix, true, "Custom Matrix");
+        });
+        ui.add_space(4.0);
+
+        if gamut.use_custom_matrix {
+            for row in gamut.custom_matrix.iter_mut() {
+                ui.horizontal(|ui| {
+                    for n in row.iter_mut() {
+                        ui.add(egui::widgets::DragValue::new(n).speed(0.001).max_decimals(6));
+                    }
+                });
+            }
+        } else {
+            ui.horizontal(|ui| {
+                chroma_space_selector(ui, "gamut source", "Source", &mut gamut.source);
+                ui.add_space(8.0);
+                chroma_space_selector(ui, "gamut target", "Target", &mut gamut.target);
+            });
+        }
+    });
+}
+
+fn trims_ui(ui: &mut Ui, trims: &mut Trims, enabled: bool) {
+    ui.add_enabled_ui(enabled, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Trims");
+            ui.add_space(4.0);
+            if ui
+                .add_enabled(!trims.is_identity(), egui::widgets::Button::new("Reset"))
+                .clicked()
+            {
+                *trims = Trims::new();
+            }
+        });
+        ui.add_space(4.0);
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::widgets::DragValue::new(&mut trims.exposure)
+                    .clamp_range(-8.0..=8.0)
+                    .speed(0.01)
+                    .max_decimals(2)
+                    .prefix("Exposure (stops): "),
+            );
+            ui.add(
+                egui::widgets::DragValue::new(&mut trims.offset)
+                    .clamp_range(-1.0..=1.0)
+                    .speed(0.0005)
+                    .max_decimals(4)
+                    .prefix("Offset: "),
+            );
+        });
+        ui.add(
+            egui::widgets::DragValue::new(&mut trims.gamma)
+                .clamp_range(0.1..=10.0)
+                .speed(0.005)
+                .max_decimals(3)
+                .prefix("Gamma: "),
+        )
+        .on_hover_text("Applied as a power of 1/gamma, so higher values brighten.");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::widgets::DragValue::new(&mut trims.contrast)
+                    .clamp_range(0.1..=10.0)
+                    .speed(0.005)
+                    .max_decimals(3)
+                    .prefix("Contrast: "),
+            );
+            ui.add(
+                egui::widgets::DragValue::new(&mut trims.pivot)
+                    .clamp_range(0.001..=1.0)
+                    .speed(0.001)
+                    .max_decimals(3)
+                    .prefix("Pivot: "),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::widgets::DragValue::new(&mut trims.floor)
+                    .clamp_range(-1.0..=1.0)
+                    .speed(0.001)
+                    .max_decimals(4)
+                    .prefix("Floor: "),
+            )
+            .on_hover_text("The output value that 0.0 is mapped to.");
+            ui.add(
+                egui::widgets::DragValue::new(&mut trims.ceiling)
+                    .clamp_range(0.0..=16.0)
+                    .speed(0.001)
+                    .max_decimals(4)
+                    .prefix("Ceiling: "),
+            )
+            .on_hover_text("The output value that 1.0 is mapped to.");
+        });
+
+        // Keep the trims invertible.
+        trims.ceiling = trims.ceiling.max(trims.floor + 0.001);
+    });
+}
+
+pub fn chroma_space_selector(ui: &mut Ui, id: &str, label: &str, space: &mut ChromaSpace) {
+    ui.label(label);
+    egui::ComboBox::from_id_source(id)
+        .width(200.0)
+        .selected_text(space.ui_text())
+        .show_ui(ui, |ui| {
+            // Custom chromaticities aren't supported here, since a
+            // custom matrix can be entered instead.
+            for cs in CHROMA_SPACES
+                .iter()
+                .filter(|cs| **cs != ChromaSpace::None && **cs != ChromaSpace::Custom)
+            {
+                ui.selectable_value(space, *cs, cs.ui_text());
+            }
+        });
 }
@@ -0,0 +1,286 @@
+//! A guided "Profile This Camera" window, which walks through the steps
+//! of profiling a camera: loading images, estimating and validating the
+//! transfer function, naming the camera, and exporting the LUTs (and
+//! optionally adding the camera to an OCIO Maker config).
+
+use std::path::PathBuf;
+
+use crate::egui::{self, Context, Ui};
+
+use crate::{graph::PreviewMode, AppMode, ImageViewID};
+
+pub struct ProfileWizard {
+    pub open: bool,
+    exported_to: Option<PathBuf>,
+}
+
+impl ProfileWizard {
+    pub fn new() -> ProfileWizard {
+        ProfileWizard {
+            open: false,
+            exported_to: None,
+        }
+    }
+}
+
+pub fn wizard_window(
+    ctx: &Context,
+    app: &mut crate::AppMain,
+    job_count: usize,
+    total_bracket_images: usize,
+    total_dark_images: usize,
+    working_dir: &mut PathBuf,
+) {
+    if !app.profile_wizard.open {
+        return;
+    }
+
+    let add_images_dialog = {
+        let mut d = rfd::FileDialog::new().set_title("Add Images").add_filter(
+            "All Images",
+            &[
+                "jpg", "JPG", "jpeg", "JPEG", "tiff", "TIFF", "tif", "TIF", "exr", "EXR", "png",
+                "PNG", "dng", "DNG",
+            ],
+        );
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
+    let export_pair_dialog = {
+        let mut d = rfd::FileDialog::new().set_title("Export LUT Pair To Directory");
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
+    let register_config_dialog = {
+        let mut d = rfd::FileDialog::new()
+            .set_title("Select OCIO Maker Config")
+            .add_filter("OCIO config", &["ocio"]);
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
+
+    let have_brackets = total_bracket_images >= 2;
+    let have_estimate = app.transfer_function_tables.lock().is_some();
+    let idle = job_count == 0;
+
+    let mut open = true;
+    egui::Window::new("Profile This Camera")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.set_width(380.0);
+
+            // 1. Bracketed images.
+            step_header(ui, 1, "Bracketed exposures", have_brackets);
+            ui.horizontal(|ui| {
+                ui.label(format!("{} image(s).", total_bracket_images));
+                if ui
+                    .add_enabled(idle, egui::widgets::Button::new("Add Images..."))
+                    .clicked()
+                {
+                    if let Some(paths) = add_images_dialog.clone().pick_files() {
+                        if let Some(parent) = paths[0].parent().map(|p| p.into()) {
+                            *working_dir = parent;
+                        }
+                        app.ui_data.lock_mut().image_view = ImageViewID::Bracketed;
+                        app.bracket_image_sets
+                            .add_image_files(paths, ctx, &app.job_queue);
+                    }
+                }
+            });
+            ui.add_space(8.0);
+
+            // 2. Lens cap images.
+            step_header(ui, 2, "Lens cap images (optional)", total_dark_images > 0);
+            ui.horizontal(|ui| {
+                ui.label(format!("{} image(s).", total_dark_images));
+                if ui
+                    .add_enabled(idle, egui::widgets::Button::new("Add Images..."))
+                    .on_hover_text("Images taken with the lens cap on, for a more accurate noise floor.")
+                    .clicked()
+                {
+                    if let Some(paths) = add_images_dialog.clone().pick_files() {
+                        if let Some(parent) = paths[0].parent().map(|p| p.into()) {
+                            *working_dir = parent;
+                        }
+                        app.ui_data.lock_mut().image_view = ImageViewID::Dark;
+                        app.dark_images.add_image_files(paths, ctx, &app.job_queue);
+                    }
+                }
+            });
+            ui.add_space(8.0);
+
+            // 3. Estimate.
+            step_header(ui, 3, "Estimate the transfer function", have_estimate);
+            if ui
+                .add_enabled(idle && have_brackets, egui::widgets::Button::new("Estimate"))
+                .on_hover_text("Estimates the sensor floor, ceiling, and transfer function in Estimate mode.")
+                .clicked()
+            {
+                app.ui_data.lock_mut().mode = AppMode::Estimate;
+                app.estimate_everything();
+            }
+            ui.add_space(8.0);
+
+            // 4. Validate.
+            let known_camera = app.known_camera();
+            step_header(ui, 4, "Validate", false);
+            validate_ui(ui, app, known_camera, have_estimate);
+            ui.add_space(8.0);
+
+            // 5. Name.
+            let name_done = {
+                let name = app.camera_profiles.active_name();
+                !name.trim().is_empty() && name != "Camera 1"
+            };
+            step_header(ui, 5, "Name the camera", name_done);
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::widgets::TextEdit::singleline(app.camera_profiles.active_name_mut())
+                        .desired_width(200.0),
+                );
+                let exif_name = known_camera
+                    .map(|camera| camera.ui_text())
+                    .or_else(|| app.exif_camera().map(|(make, model)| exif_camera_name(&make, &model)));
+                if let Some(exif_name) = exif_name {
+                    if ui
+                        .button("From Exif")
+                        .on_hover_text(format!("Use \"{}\", from the images' Exif data.", exif_name))
+                        .clicked()
+                    {
+                        *app.camera_profiles.active_name_mut() = exif_name;
+                    }
+                }
+            });
+            ui.add_space(8.0);
+
+            // 6. Export.
+            let exported = app.profile_wizard.exported_to.is_some();
+            step_header(ui, 6, "Export", exported);
+            ui.checkbox(
+                &mut app.ui_data.lock_mut().register_lut_pair,
+                "Add to an OCIO Maker config",
+            )
+            .on_hover_text("After exporting, add the camera as an input color space to a config made with OCIO Maker, with its gamut if it's a known camera.");
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        idle && have_estimate,
+                        egui::widgets::Button::new("Export LUT Pair..."),
+                    )
+                    .on_hover_text(format!(
+                        "Exports both the 'to linear' and 'from linear' LUTs in the {} format.",
+                        app.ui_data.lock().export_format.ui_text()
+                    ))
+                    .clicked()
+                {
+                    if let Some(dir) = export_pair_dialog.clone().pick_folder() {
+                        let register = app.ui_data.lock().register_lut_pair;
+                        let config = if register {
+                            register_config_dialog.clone().pick_file()
+                        } else {
+                            None
+                        };
+                        if !register || config.is_some() {
+                            app.ui_data.lock_mut().mode = AppMode::Estimate;
+                            app.export_lut_pair(&dir, config);
+                            app.profile_wizard.exported_to = Some(dir.clone());
+                        }
+                        *working_dir = dir;
+                    }
+                }
+                if let Some(dir) = &app.profile_wizard.exported_to {
+                    ui.label(format!("Exported to \"{}\".", dir.to_string_lossy()));
+                }
+            });
+        });
+
+    app.profile_wizard.open = open;
+}
+
+/// The numbered title of a step, with a check mark once it's done.
+fn step_header(ui: &mut Ui, number: usize, title: &str, done: bool) {
+    ui.horizontal(|ui| {
+        ui.strong(format!("{}. {}", number, title));
+        if done {
+            ui.colored_label(lib::colors::GREEN, "✔");
+        }
+    });
+}
+
+/// Shows how well the estimate fits, and points to the comparison
+/// graph.
+fn validate_ui(
+    ui: &mut Ui,
+    app: &mut crate::AppMain,
+    known_camera: Option<&'static camera_db::CameraInfo>,
+    have_estimate: bool,
+) {
+    if !have_estimate {
+        ui.label("Estimate the transfer function first.");
+        return;
+    }
+
+    let mut ui_data = app.ui_data.lock_mut();
+    if let Some((_, error)) = ui_data.estimated.error_history.last() {
+        ui.label(format!("Fit error: {:.5}", error));
+    }
+    if let Some(camera) = known_camera {
+        let diverges = ui_data
+            .estimated
+            .sensor_floor
+            .iter()
+            .any(|floor| camera.floor_diverges(*floor));
+        if diverges {
+            ui.colored_label(
+                lib::colors::YELLOW,
+                format!(
+                    "The estimated floor differs from the {}'s known black level.  Adding lens cap images may help.",
+                    camera.ui_text()
+                ),
+            );
+        } else {
+            ui.label(format!(
+                "The estimated floor matches the {}'s known black level.",
+                camera.ui_text()
+            ));
+        }
+    }
+
+    let compare_text = match known_camera {
+        Some(camera) => format!(
+            "Compare to {}",
+            crate::generated_tf::TransferFunctionID::from(camera.transfer_function).ui_text()
+        ),
+        None => "Compare to Built-in".into(),
+    };
+    if ui
+        .button(compare_text)
+        .on_hover_text("Shows the graph comparing the estimate to a built-in transfer function.")
+        .clicked()
+    {
+        ui_data.mode = AppMode::Estimate;
+        ui_data.preview_mode = PreviewMode::Validation;
+        if let Some(camera) = known_camera {
+            ui_data.estimated.validation_tf.id = camera.transfer_function.into();
+        }
+    }
+}
+
+/// A camera name from Exif make and model strings, without repeating
+/// the make if the model already starts with it.
+fn exif_camera_name(make: &str, model: &str) -> String {
+    let (make, model) = (make.trim(), model.trim());
+    if model.to_lowercase().starts_with(&make.to_lowercase()) {
+        model.into()
+    } else {
+        format!("{} {}", make, model)
+    }
+}
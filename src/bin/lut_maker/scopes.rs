@@ -0,0 +1,123 @@
+use crate::egui::{self, Color32};
+
+pub const SCOPE_WIDTH: usize = 256;
+pub const SCOPE_HEIGHT: usize = 128;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ScopeKind {
+    Waveform,
+    Parade,
+    Histogram,
+}
+
+pub const SCOPE_KINDS: &[ScopeKind] =
+    &[ScopeKind::Waveform, ScopeKind::Parade, ScopeKind::Histogram];
+
+impl ScopeKind {
+    pub fn ui_text(&self) -> &'static str {
+        match *self {
+            ScopeKind::Waveform => "Luma Waveform",
+            ScopeKind::Parade => "RGB Parade",
+            ScopeKind::Histogram => "Histogram",
+        }
+    }
+}
+
+/// Renders a scope of the given image, whose values are expected to be
+/// in [0.0, 1.0].  Values outside that range pile up at the edges.
+pub fn render_scope(
+    kind: ScopeKind,
+    pixels: &[[f32; 3]],
+    width: usize,
+    height: usize,
+) -> egui::ColorImage {
+    let mut image = egui::ColorImage::new([SCOPE_WIDTH, SCOPE_HEIGHT], Color32::BLACK);
+    if pixels.is_empty() || width == 0 || height == 0 {
+        return image;
+    }
+
+    let to_row = |n: f32| -> usize {
+        ((1.0 - n.max(0.0).min(1.0)) * (SCOPE_HEIGHT - 1) as f32).round() as usize
+    };
+    let channel_colors = [lib::colors::RED, lib::colors::GREEN, lib::colors::BLUE];
+
+    match kind {
+        ScopeKind::Waveform | ScopeKind::Parade => {
+            // Accumulate counts for each channel in the scope's grid.
+            let mut counts = vec![[0u32; 3]; SCOPE_WIDTH * SCOPE_HEIGHT];
+            for (i, pixel) in pixels.iter().enumerate() {
+                let x = i % width;
+                if kind == ScopeKind::Waveform {
+                    let col = x * SCOPE_WIDTH / width;
+                    let luma = (pixel[0] * 0.2126) + (pixel[1] * 0.7152) + (pixel[2] * 0.0722);
+                    counts[to_row(luma) * SCOPE_WIDTH + col][1] += 1;
+                } else {
+                    let section_width = SCOPE_WIDTH / 3;
+                    for chan in 0..3 {
+                        let col = (chan * section_width) + (x * section_width / width);
+                        counts[to_row(pixel[chan]) * SCOPE_WIDTH + col][chan] += 1;
+                    }
+                }
+            }
+
+            // Scale so that an evenly spread image would be mid-brightness.
+            let columns = if kind == ScopeKind::Waveform {
+                SCOPE_WIDTH
+            } else {
+                SCOPE_WIDTH / 3
+            };
+            let gain = (columns * SCOPE_HEIGHT) as f32 / pixels.len() as f32 * 0.25;
+            for (cell, pixel) in counts.iter().zip(image.pixels.iter_mut()) {
+                let level =
+                    |count: u32| -> u8 { ((count as f32 * gain).sqrt().min(1.0) * 255.0) as u8 };
+                *pixel = if kind == ScopeKind::Waveform {
+                    let v = level(cell[1]);
+                    Color32::from_rgb(v, v, v)
+                } else {
+                    Color32::from_rgb(level(cell[0]), level(cell[1]), level(cell[2]))
+                };
+            }
+        }
+
+        ScopeKind::Histogram => {
+            let mut bins = [[0u32; SCOPE_WIDTH]; 3];
+            for pixel in pixels.iter() {
+                for chan in 0..3 {
+                    let bin =
+                        (pixel[chan].max(0.0).min(1.0) * (SCOPE_WIDTH - 1) as f32).round() as usize;
+                    bins[chan][bin] += 1;
+                }
+            }
+
+            // Leave out the end bins when normalizing, since clipped
+            // values tend to spike there.
+            let max_count = bins
+                .iter()
+                .flat_map(|b| b[1..(SCOPE_WIDTH - 1)].iter())
+                .copied()
+                .max()
+                .unwrap_or(0)
+                .max(1) as f32;
+            for col in 0..SCOPE_WIDTH {
+                for chan in 0..3 {
+                    if bins[chan][col] == 0 {
+                        continue;
+                    }
+                    let bar_height = (bins[chan][col] as f32 / max_count).min(1.0);
+                    let top = to_row(bar_height);
+                    for row in top..SCOPE_HEIGHT {
+                        let pixel = &mut image.pixels[row * SCOPE_WIDTH + col];
+                        let color = channel_colors[chan];
+                        *pixel = Color32::from_rgb(
+                            pixel.r().saturating_add(color.r()),
+                            pixel.g().saturating_add(color.g()),
+                            pixel.b().saturating_add(color.b()),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    image
+}
@@ -0,0 +1,202 @@
+use std::path::PathBuf;
+
+use ocio_gen::{
+    config::{OCIOConfig, OutputFile},
+    eval::Evaluator,
+};
+
+use crate::egui::{self, Context};
+
+/// Default number of samples per axis of baked LUTs.
+const DEFAULT_CUBE_SIZE: usize = 33;
+
+/// A generated config along with the settings for baking one of its
+/// (input space -> display/view) chains into a 3D LUT.
+#[derive(Clone)]
+pub struct BakeState {
+    pub config: OCIOConfig,
+    pub source: String,
+    pub display: String,
+    pub view: String,
+    pub cube_size: usize,
+    pub range: (f32, f32),
+}
+
+impl BakeState {
+    pub fn new(config: OCIOConfig) -> BakeState {
+        let source = config
+            .colorspaces
+            .first()
+            .map(|s| s.name.clone())
+            .unwrap_or_else(String::new);
+        let (display, view) = config
+            .displays
+            .first()
+            .map(|d| {
+                (
+                    d.name.clone(),
+                    d.views
+                        .first()
+                        .map(|v| v.0.clone())
+                        .unwrap_or_else(String::new),
+                )
+            })
+            .unwrap_or_else(|| (String::new(), String::new()));
+
+        BakeState {
+            config: config,
+            source: source,
+            display: display,
+            view: view,
+            cube_size: DEFAULT_CUBE_SIZE,
+            range: (0.0, 1.0),
+        }
+    }
+}
+
+/// Window for picking the chain to bake and the LUT's settings.
+pub fn window(ctx: &Context, app: &mut crate::AppMain, working_dir: &mut PathBuf) {
+    let mut bake_path = None;
+    let mut close = false;
+
+    {
+        let mut ui_data = app.ui_data.lock_mut();
+        let state = match ui_data.bake_lut {
+            Some(ref mut state) => state,
+            None => return,
+        };
+
+        egui::Window::new("Bake LUT")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Bake the transform from an input color space to a display/view into a 3D LUT, for use with monitors, cameras, and applications that can't load OCIO configs.");
+                ui.add_space(8.0);
+
+                egui::Grid::new("bake_lut_settings").show(ui, |ui| {
+                    ui.label("Input Color Space:");
+                    egui::ComboBox::from_id_source("bake_lut_source")
+                        .selected_text(state.source.as_str())
+                        .width(256.0)
+                        .show_ui(ui, |ui| {
+                            for space in state.config.colorspaces.iter() {
+                                ui.selectable_value(
+                                    &mut state.source,
+                                    space.name.clone(),
+                                    space.name.as_str(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Display:");
+                    let old_display = state.display.clone();
+                    egui::ComboBox::from_id_source("bake_lut_display")
+                        .selected_text(state.display.as_str())
+                        .width(256.0)
+                        .show_ui(ui, |ui| {
+                            for display in state.config.displays.iter() {
+                                ui.selectable_value(
+                                    &mut state.display,
+                                    display.name.clone(),
+                                    display.name.as_str(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    // Reset the view when the display changes, since
+                    // each display has its own views.
+                    let views: Vec<String> = state
+                        .config
+                        .displays
+                        .iter()
+                        .find(|d| d.name == state.display)
+                        .map(|d| d.views.iter().map(|v| v.0.clone()).collect())
+                        .unwrap_or_else(Vec::new);
+                    if state.display != old_display {
+                        state.view = views.first().cloned().unwrap_or_else(String::new);
+                    }
+
+                    ui.label("View:");
+                    egui::ComboBox::from_id_source("bake_lut_view")
+                        .selected_text(state.view.as_str())
+                        .width(256.0)
+                        .show_ui(ui, |ui| {
+                            for view in views.iter() {
+                                ui.selectable_value(&mut state.view, view.clone(), view.as_str());
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Cube Size:");
+                    ui.add(egui::widgets::DragValue::new(&mut state.cube_size).clamp_range(2..=65));
+                    ui.end_row();
+
+                    ui.label("Input Range:");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::widgets::DragValue::new(&mut state.range.0)
+                                .speed(0.01)
+                                .max_decimals(4),
+                        );
+                        ui.label("to");
+                        ui.add(
+                            egui::widgets::DragValue::new(&mut state.range.1)
+                                .speed(0.01)
+                                .max_decimals(4),
+                        );
+                    });
+                    ui.end_row();
+                });
+
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    let bake_enabled = !state.source.is_empty()
+                        && !state.view.is_empty()
+                        && state.range.0 < state.range.1;
+                    if ui
+                        .add_enabled(bake_enabled, egui::widgets::Button::new("Bake..."))
+                        .clicked()
+                    {
+                        let mut d = rfd::FileDialog::new()
+                            .set_title("Save Baked LUT")
+                            .add_filter("cube", &["cube"])
+                            .set_file_name(format!("{}__{}.cube", state.display, state.view));
+                        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+                            d = d.set_directory(&working_dir);
+                        }
+                        bake_path = d.save_file();
+                    }
+                    if ui.button("Close").clicked() {
+                        close = true;
+                    }
+                });
+            });
+    }
+
+    if let Some(path) = bake_path {
+        if let Some(parent) = path.parent().map(|p| p.into()) {
+            *working_dir = parent;
+        }
+        app.bake_lut(path);
+    } else if close {
+        app.ui_data.lock_mut().bake_lut = None;
+    }
+}
+
+/// Bakes the chain selected in `state` and writes it to `path` as a
+/// .cube file.
+pub fn bake(state: &BakeState, path: &std::path::Path) -> Result<(), String> {
+    let evaluator = Evaluator::new(&state.config);
+    let transforms = evaluator.view_processor(&state.source, &state.display, &state.view)?;
+    let lut = evaluator.bake_lut_3d(&transforms, state.cube_size, state.range)?;
+
+    let write = || -> std::io::Result<()> {
+        use std::io::Write;
+        let mut f = std::io::BufWriter::new(std::fs::File::create(path)?);
+        OutputFile::Lut3D(lut).write(path, &mut f)?;
+        f.flush()
+    };
+    write().map_err(|e| e.to_string())
+}
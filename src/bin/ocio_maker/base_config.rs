@@ -1,8 +1,16 @@
+use std::ops::RangeInclusive;
+
 use crate::egui::{self, Context};
 
+/// Ranges of the view transform adjustments.
+pub const CONTRAST_RANGE: RangeInclusive<f64> = 0.5..=2.0;
+pub const SHOULDER_RANGE: RangeInclusive<f64> = 0.25..=4.0;
+pub const SATURATION_RANGE: RangeInclusive<f64> = 0.0..=2.0;
+
 pub fn ui(ctx: &Context, app: &mut crate::AppMain) {
     egui::containers::panel::CentralPanel::default().show(ctx, |ui| {
         // Base config preset.
+        let mut preset_changed = false;
         ui.horizontal(|ui| {
             let mut ui_data = app.ui_data.lock_mut();
             ui.label("Configuration base: ");
@@ -11,10 +19,17 @@ pub fn ui(ctx: &Context, app: &mut crate::AppMain) {
                 .selected_text(String::from(ui_data.base_preset.ui_text()))
                 .show_ui(ui, |ui| {
                     for bp in super::BASE_PRESETS {
-                        ui.selectable_value(&mut ui_data.base_preset, *bp, bp.ui_text());
+                        preset_changed |= ui
+                            .selectable_value(&mut ui_data.base_preset, *bp, bp.ui_text())
+                            .changed();
                     }
                 });
         });
+        if preset_changed {
+            // Catch e.g. looks and views that referred to color spaces
+            // of the previous base.
+            app.check_base_preset();
+        }
         ui.add_space(8.0);
 
         let base_preset = app.ui_data.lock().base_preset;
@@ -23,17 +38,63 @@ pub fn ui(ctx: &Context, app: &mut crate::AppMain) {
                 ui.label("The working color space is what rendering, compositing, and RGB color math are done in.  It is always a linear color space, defined only by a gamut.  It does not have to match the final target display color space, although that's usually a good choice.");
                 ui.add_space(8.0);
 
-                let space = &mut app.ui_data.lock_mut().working_color_space;
+                let ui_data = &mut *app.ui_data.lock_mut();
                 crate::colorspace_editor::chromaticity_editor(
                     ui,
                     "Working color space: ",
-                    space,
+                    &mut ui_data.working_color_space,
                 );
                 ui.add_space(8.0);
-                crate::gamut_graph::graph(ui, space);
+                crate::gamut_graph::graph(
+                    ui,
+                    &ui_data.working_color_space,
+                    &ui_data.color_spaces,
+                    &mut ui_data.gamut_graph,
+                );
+            }
+
+            crate::BasePreset::Imported => {
+                ui.label(format!(
+                    "Imported from: {}",
+                    app.ui_data.lock().imported_config_path
+                ));
+                ui.add_space(8.0);
+                ui.label("Color spaces, looks, displays, and roles added in OCIO Maker are merged into the imported config on export.  Use File > Import Base Config... to import a different config.");
             }
 
             _ => {}
         }
+
+        if base_preset.has_adjustable_view_transforms() {
+            ui.add_space(16.0);
+            ui.label("View transform adjustments.  These tune the look of the Toney and AgX view transforms, relative to their stock settings.  The view transforms' LUTs are regenerated with them on export.");
+            ui.add_space(8.0);
+
+            let params = &mut app.ui_data.lock_mut().tone_map_params;
+            egui::Grid::new("tone_map_params").show(ui, |ui| {
+                ui.label("Contrast:");
+                ui.add(egui::widgets::Slider::new(&mut params.contrast, CONTRAST_RANGE));
+                ui.end_row();
+
+                ui.label("Shoulder:");
+                ui.add(egui::widgets::Slider::new(&mut params.shoulder, SHOULDER_RANGE))
+                    .on_hover_text("Softness of the highlight roll-off.  Higher values keep more highlight detail.");
+                ui.end_row();
+
+                ui.label("Saturation:");
+                ui.add(egui::widgets::Slider::new(&mut params.saturation, SATURATION_RANGE));
+                ui.end_row();
+            });
+            ui.add_space(4.0);
+            if ui
+                .add_enabled(
+                    *params != ocio_gen::ToneMapParams::default(),
+                    egui::widgets::Button::new("Reset"),
+                )
+                .clicked()
+            {
+                *params = ocio_gen::ToneMapParams::default();
+            }
+        }
     });
 }
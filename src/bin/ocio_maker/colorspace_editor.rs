@@ -4,7 +4,26 @@ use colorbox::formats;
 
 use crate::egui::{self, Ui};
 
-use crate::ChromaSpace;
+use lib::chroma_space::{ChromaSpace, CHROMA_SPACES};
+use lib::white_balance::{WhiteBalance, ILLUMINANTS};
+
+use ocio_gen::{config::Encoding, gamut_map::GamutMapping};
+
+const ENCODINGS: &[Encoding] = &[
+    Encoding::SceneLinear,
+    Encoding::DisplayLinear,
+    Encoding::Log,
+    Encoding::SDRVideo,
+    Encoding::HDRVideo,
+    Encoding::Data,
+];
+
+const GAMUT_MAPPINGS: &[GamutMapping] = &[
+    GamutMapping::None,
+    GamutMapping::Clip,
+    GamutMapping::AcesCompress,
+    GamutMapping::SoftCompress,
+];
 
 pub fn editor(
     ui: &mut Ui,
@@ -24,6 +43,15 @@ pub fn editor(
         }
         d
     };
+    let load_3d_lut_dialog = {
+        let mut d = rfd::FileDialog::new()
+            .set_title("Load 3D LUT")
+            .add_filter("cube", &["cube"]);
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
 
     // Name and Misc.
     ui.horizontal(|ui| {
@@ -33,18 +61,151 @@ pub fn editor(
         ui.add_space(16.0);
 
         ui.checkbox(&mut space.include_as_display, "Include as Display");
+        ui.checkbox(&mut space.inactive, "Inactive").on_hover_text(
+            "Inactive color spaces are hidden from applications' color space menus, but remain usable by name.",
+        );
     });
 
     ui.add_space(8.0);
 
-    // Chromaticity space.
-    chromaticity_editor(ui, "Chromaticities / Gamut: ", space);
+    // Metadata.
+    egui::CollapsingHeader::new("Metadata")
+        .id_source(format!("{}_metadata", space_id))
+        .show(ui, |ui| {
+            egui::Grid::new("metadata").min_col_width(4.0).show(ui, |ui| {
+                ui.label("Family: ");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::widgets::TextEdit::singleline(&mut space.family)
+                            .hint_text(crate::DEFAULT_FAMILY)
+                            .id(egui::Id::new(format!("{}_family", space_id))),
+                    )
+                    .on_hover_text("Used by applications to group color spaces in menus.  Slashes create sub-menus in some applications, e.g. \"Input/Cameras\".");
+                    ui.menu_button("⏷", |ui| {
+                        for family in crate::FAMILY_PRESETS.iter() {
+                            if ui.button(*family).clicked() {
+                                space.family = (*family).into();
+                                ui.close_menu();
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Common families");
+                });
+                ui.end_row();
+
+                ui.label("Aliases: ");
+                ui.add(
+                    egui::widgets::TextEdit::singleline(&mut space.aliases)
+                        .hint_text("e.g. lin_srgb, srgb_linear")
+                        .id(egui::Id::new(format!("{}_aliases", space_id))),
+                )
+                .on_hover_text("Comma-separated alternate names, which can be used anywhere the color space's name can.");
+                ui.end_row();
+
+                ui.label("Encoding: ");
+                egui::ComboBox::from_id_source(format!("{}_encoding", space_id))
+                    .selected_text(space.encoding.map(|e| e.as_str()).unwrap_or("None"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut space.encoding, None, "None");
+                        for encoding in ENCODINGS.iter() {
+                            ui.selectable_value(
+                                &mut space.encoding,
+                                Some(*encoding),
+                                encoding.as_str(),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text("A hint about how the color space encodes values, used by OCIO for optimizations and by some applications to pick sensible defaults.");
+                ui.end_row();
+
+                ui.label("Description: ");
+                ui.add(
+                    egui::widgets::TextEdit::multiline(&mut space.description)
+                        .desired_rows(2)
+                        .id(egui::Id::new(format!("{}_description", space_id))),
+                );
+                ui.end_row();
+            });
+        });
+
+    ui.add_space(8.0);
+
+    // Gamut definition.
+    ui.horizontal(|ui| {
+        ui.label("Gamut Defined By: ");
+        let mut use_matrix = space.input_matrix.is_some();
+        ui.radio_value(&mut use_matrix, false, "Chromaticities");
+        ui.radio_value(&mut use_matrix, true, "Matrix");
+        if use_matrix != space.input_matrix.is_some() {
+            space.input_matrix = if use_matrix {
+                Some(IDENTITY_MATRIX_3X4)
+            } else {
+                None
+            };
+        }
+    });
+    if space.input_matrix.is_some() {
+        matrix_editor(ui, space);
+        ui.add_space(4.0);
+        chromaticity_editor(ui, "Matrix Output Space: ", space);
+    } else {
+        chromaticity_editor(ui, "Chromaticities / Gamut: ", space);
+        camera_gamut_menu(ui, space);
+    }
+    ui.horizontal(|ui| {
+        ui.label("Gamut Mapping: ");
+        egui::ComboBox::from_id_source(format!("{}_gamut_mapping", space_id))
+            .selected_text(space.gamut_mapping.ui_text())
+            .show_ui(ui, |ui| {
+                for mapping in GAMUT_MAPPINGS.iter() {
+                    ui.selectable_value(&mut space.gamut_mapping, *mapping, mapping.ui_text());
+                }
+            })
+            .response
+            .on_hover_text("How colors outside the config's reference gamut are brought into it (and vice versa), when this color space's gamut isn't contained by it.  Clip is the simplest, while the compression options keep more detail in saturated colors.");
+    });
+    white_balance_editor(ui, space, space_id);
     ui.add_space(8.0);
 
     // Transfer function.
     let transfer_lut_label = "Transfer Function (to linear): ";
     let mut remove_lut = false;
-    if let Some((_, ref filepath, ref mut inverse)) = space.transfer_lut {
+    if let Some((ref lut, ref shaper, ref filepath)) = space.transfer_lut_3d {
+        ui.horizontal(|ui| {
+            ui.label(transfer_lut_label);
+            ui.strong(if let Some(name) = filepath.file_name() {
+                let tmp: String = name.to_string_lossy().into();
+                tmp
+            } else {
+                "Unnamed LUT".into()
+            });
+            ui.label(if shaper.is_some() {
+                "(3D LUT with shaper)"
+            } else {
+                "(3D LUT)"
+            });
+            if ui
+                .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
+                .clicked()
+            {
+                remove_lut = true;
+            }
+        });
+        ui.indent(0, |ui| {
+            egui::CollapsingHeader::new("Inspect")
+                .id_source(format!("{}_inspect_lut_3d", space_id))
+                .show(ui, |ui| {
+                    egui_custom::Lut3DView::new(
+                        format!("{}_lut_3d_view", space_id),
+                        lut.resolution,
+                        [&lut.tables[0], &lut.tables[1], &lut.tables[2]],
+                    )
+                    .show(ui);
+                });
+        });
+    } else if let Some((_, ref filepath, ref mut inverse)) = space.transfer_lut {
         ui.horizontal(|ui| {
             ui.label(transfer_lut_label);
             ui.strong(if let Some(name) = filepath.file_name() {
@@ -95,6 +256,35 @@ pub fn editor(
                     }
                 }
             }
+            if ui
+                .add_enabled(job_count == 0, egui::widgets::Button::new("Load 3D LUT..."))
+                .on_hover_text("For camera vendors that only publish 3D LUTs.  A 1D shaper in the .cube file is detected and used automatically.")
+                .clicked()
+            {
+                if let Some(path) = load_3d_lut_dialog.clone().pick_file() {
+                    if let Some(parent) = path.parent().map(|p| p.into()) {
+                        *working_dir = parent;
+                    }
+
+                    match lib::job_helpers::load_3d_lut(&path) {
+                        Ok((lut, shaper)) => {
+                            space.transfer_lut_3d = Some((lut, shaper, path.clone()))
+                        }
+                        Err(formats::ReadError::IoErr(_)) => {
+                            return Err(format!(
+                                "Unable to access file \"{}\".",
+                                path.to_string_lossy()
+                            ));
+                        }
+                        Err(formats::ReadError::FormatErr) => {
+                            return Err(format!(
+                                "Not a 3D LUT file: \"{}\".",
+                                path.to_string_lossy()
+                            ));
+                        }
+                    }
+                }
+            }
             Ok(())
         })
         .inner?;
@@ -102,11 +292,159 @@ pub fn editor(
 
     if remove_lut {
         space.transfer_lut = None;
+        space.transfer_lut_3d = None;
     }
 
     Ok(())
 }
 
+const IDENTITY_MATRIX_3X4: [[f64; 4]; 3] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+];
+
+/// Editor for an explicit 3x4 matrix (the fourth column being offsets)
+/// that maps linear input RGB to the selected chromaticity space.
+fn matrix_editor(ui: &mut Ui, space: &mut crate::ColorSpaceSpec) {
+    let matrix = match space.input_matrix {
+        Some(ref mut m) => m,
+        None => return,
+    };
+
+    ui.indent("input_matrix_container", |ui| {
+        egui::Grid::new("input_matrix")
+            .min_col_width(4.0)
+            .show(ui, |ui| {
+                let precision = 0.0001;
+
+                ui.label("");
+                ui.label("R");
+                ui.label("G");
+                ui.label("B");
+                ui.label("Offset");
+                ui.end_row();
+
+                for (row, label) in matrix.iter_mut().zip(["R", "G", "B"].iter()) {
+                    ui.label(*label);
+                    for n in row.iter_mut() {
+                        ui.add(
+                            egui::widgets::DragValue::new(n)
+                                .speed(precision)
+                                .max_decimals(6),
+                        );
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+    ui.add_space(8.0);
+}
+
+fn white_balance_editor(ui: &mut Ui, space: &mut crate::ColorSpaceSpec, space_id: &str) {
+    let mut enabled = space.white_balance.is_some();
+    ui.checkbox(&mut enabled, "White Balance")
+        .on_hover_text("For footage shot under lighting other than the color space's white point.  Maps the light's color to white.");
+    if enabled != space.white_balance.is_some() {
+        space.white_balance = if enabled {
+            Some(WhiteBalance::Temperature(6504.0, 0.0))
+        } else {
+            None
+        };
+    }
+
+    let white_balance = match space.white_balance {
+        Some(ref mut white_balance) => white_balance,
+        None => return,
+    };
+    ui.indent(format!("{}_white_balance", space_id), |ui| {
+        ui.horizontal(|ui| {
+            let mut use_temperature = matches!(white_balance, WhiteBalance::Temperature(..));
+            ui.radio_value(&mut use_temperature, true, "Temperature");
+            ui.radio_value(&mut use_temperature, false, "Illuminant");
+            match (use_temperature, *white_balance) {
+                (true, WhiteBalance::Illuminant(..)) => {
+                    *white_balance = WhiteBalance::Temperature(6504.0, 0.0);
+                }
+                (false, WhiteBalance::Temperature(..)) => {
+                    let (x, y) = white_balance.source_white();
+                    *white_balance = WhiteBalance::Illuminant(x, y);
+                }
+                _ => {}
+            }
+        });
+
+        match *white_balance {
+            WhiteBalance::Temperature(ref mut temperature, ref mut tint) => {
+                ui.horizontal(|ui| {
+                    ui.label("Temperature (K): ");
+                    ui.add(
+                        egui::widgets::DragValue::new(temperature)
+                            .clamp_range(1667.0..=25000.0)
+                            .speed(10.0)
+                            .max_decimals(0),
+                    );
+                    ui.add_space(8.0);
+                    ui.label("Tint: ");
+                    ui.add(
+                        egui::widgets::DragValue::new(tint)
+                            .clamp_range(-100.0..=100.0)
+                            .speed(0.1)
+                            .max_decimals(1),
+                    )
+                    .on_hover_text("Positive values are toward magenta, negative toward green.");
+                });
+            }
+            WhiteBalance::Illuminant(ref mut x, ref mut y) => {
+                ui.horizontal(|ui| {
+                    ui.label("x: ");
+                    ui.add(
+                        egui::widgets::DragValue::new(x)
+                            .clamp_range(0.0..=1.0)
+                            .speed(0.0001),
+                    );
+                    ui.label("y: ");
+                    ui.add(
+                        egui::widgets::DragValue::new(y)
+                            .clamp_range(0.001..=1.0)
+                            .speed(0.0001),
+                    );
+                    egui::ComboBox::from_id_source(format!("{}_illuminant", space_id))
+                        .selected_text("Presets")
+                        .show_ui(ui, |ui| {
+                            for (name, (ix, iy)) in ILLUMINANTS.iter() {
+                                if ui.selectable_label(false, *name).clicked() {
+                                    *x = *ix;
+                                    *y = *iy;
+                                }
+                            }
+                        });
+                });
+            }
+        }
+    });
+}
+
+/// Menu of the cameras in the camera database, which sets the
+/// chromaticities to the selected camera's gamut.
+fn camera_gamut_menu(ui: &mut Ui, space: &mut crate::ColorSpaceSpec) {
+    ui.menu_button("Gamut from camera...", |ui| {
+        for camera in camera_db::CAMERAS.iter() {
+            let chroma_space: ChromaSpace = camera.gamut.into();
+            if ui
+                .button(camera.ui_text())
+                .on_hover_text(chroma_space.ui_text())
+                .clicked()
+            {
+                space.chroma_space = chroma_space;
+                ui.close_menu();
+            }
+        }
+    })
+    .response
+    .on_hover_text("Set the chromaticities to the native gamut of a known camera.");
+}
+
 pub fn chromaticity_editor(ui: &mut Ui, label: &str, space: &mut crate::ColorSpaceSpec) {
     ui.horizontal(|ui| {
         ui.label(label);
@@ -114,7 +452,7 @@ pub fn chromaticity_editor(ui: &mut Ui, label: &str, space: &mut crate::ColorSpa
             .width(256.0)
             .selected_text(format!("{}", space.chroma_space.ui_text()))
             .show_ui(ui, |ui| {
-                for cs in super::CHROMA_SPACES {
+                for cs in CHROMA_SPACES {
                     ui.selectable_value(&mut space.chroma_space, *cs, cs.ui_text());
                 }
             });
@@ -1,25 +1,144 @@
+use std::path::PathBuf;
+
 use crate::egui::{self, Ui};
 
-pub fn list(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize) {
+/// Drag-and-drop payload for reordering the list: the index of the
+/// dragged color space.
+struct DraggedColorSpace(usize);
+
+pub fn list(ui: &mut Ui, app: &mut crate::AppMain, working_dir: &mut PathBuf, job_count: usize) {
     let mut remove_i = None;
     let mut add_input_space = false;
+    let mut duplicate_i = None;
+    let mut add_to_library = false;
+    let mut add_from_library_i = None;
+    let mut remove_from_library_i = None;
+    let mut lut_drop_target = None;
+    let mut move_from_to = None;
 
     ui.add_space(4.0);
     ui.horizontal(|ui| {
         add_input_space |= ui.button("New  ➕").clicked();
+        let (has_selected, selected_i) = {
+            let ui_data = app.ui_data.lock();
+            (
+                ui_data.selected_space_index < ui_data.color_spaces.len(),
+                ui_data.selected_space_index,
+            )
+        };
+        if ui
+            .add_enabled(has_selected, egui::widgets::Button::new("Duplicate"))
+            .on_hover_text("Adds a copy of the selected color space")
+            .clicked()
+        {
+            duplicate_i = Some(selected_i);
+        }
+        ui.menu_button("Library", |ui| {
+            if ui
+                .add_enabled(
+                    has_selected && job_count == 0,
+                    egui::widgets::Button::new("Add Selected to Library"),
+                )
+                .on_hover_text("Replaces any library color space with the same name.")
+                .clicked()
+            {
+                add_to_library = true;
+                ui.close_menu();
+            }
+            ui.separator();
+
+            let library = app.library.lock();
+            if library.is_empty() {
+                ui.label("The library is empty.");
+            }
+            for (i, space) in library.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
+                        .on_hover_text("Remove from library")
+                        .clicked()
+                    {
+                        remove_from_library_i = Some(i);
+                    }
+                    if ui
+                        .add_enabled(job_count == 0, egui::widgets::Button::new(&space.name))
+                        .on_hover_text("Add to config")
+                        .clicked()
+                    {
+                        add_from_library_i = Some(i);
+                        ui.close_menu();
+                    }
+                });
+            }
+        });
     });
+    if ui
+        .add_enabled(
+            job_count == 0,
+            egui::widgets::Button::new("Import LUT Folder..."),
+        )
+        .on_hover_text("Adds an input color space for each LUT in a folder.")
+        .clicked()
+    {
+        let mut dialog = rfd::FileDialog::new().set_title("Import LUT Folder");
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            dialog = dialog.set_directory(&working_dir);
+        }
+        if let Some(dir) = dialog.pick_folder() {
+            match crate::lut_folder_import::LutFolderImport::new(&dir) {
+                Ok(import) => app.lut_folder_import = Some(import),
+                Err(e) => app.job_queue.log_error(format!(
+                    "Unable to read folder \"{}\": {}.",
+                    dir.to_string_lossy(),
+                    e
+                )),
+            }
+            *working_dir = dir;
+        }
+    }
+    ui.checkbox(&mut app.ui_data.lock_mut().group_color_spaces, "Group by family")
+        .on_hover_text("Keeps color spaces of the same family together, under headers.  The exported config lists them in the same order, which organizes applications' color space menus.");
+    if app.ui_data.lock().group_color_spaces {
+        app.group_color_spaces_by_family();
+    }
     ui.add_space(4.0);
 
     egui::containers::ScrollArea::vertical()
         .auto_shrink([true, false])
         .show(ui, |ui| {
             let ui_data = &mut *app.ui_data.lock_mut();
+            let group = ui_data.group_color_spaces;
 
             let mut space_i = 0;
             let mut selected_i = ui_data.selected_space_index;
 
+            // For dropping LUT files onto a specific color space.
+            let (hovering_files, dropping_files, pointer_pos) = ui.input(|input| {
+                (
+                    !input.raw.hovered_files.is_empty(),
+                    !input.raw.dropped_files.is_empty(),
+                    input.pointer.hover_pos(),
+                )
+            });
+
+            let mut prev_family = None;
             for input_space in ui_data.color_spaces.iter() {
-                ui.horizontal(|ui| {
+                if group && prev_family != Some(input_space.family_or_default()) {
+                    if prev_family.is_some() {
+                        ui.add_space(4.0);
+                    }
+                    ui.strong(input_space.family_or_default());
+                }
+                prev_family = Some(input_space.family_or_default());
+
+                let row = ui.horizontal(|ui| {
+                    ui.dnd_drag_source(
+                        egui::Id::new(("color_space_drag", space_i)),
+                        DraggedColorSpace(space_i),
+                        |ui| ui.label("☰"),
+                    )
+                    .response
+                    .on_hover_text("Drag to reorder");
                     if ui
                         .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
                         .clicked()
@@ -37,16 +156,59 @@ pub fn list(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize) {
                     }
                 });
 
+                // Reordering.
+                let row_rect = row.response.rect;
+                if let Some(dragged) = row.response.dnd_hover_payload::<DraggedColorSpace>() {
+                    if dragged.0 != space_i {
+                        let y = if dragged.0 < space_i {
+                            row_rect.bottom()
+                        } else {
+                            row_rect.top()
+                        };
+                        ui.painter()
+                            .hline(row_rect.x_range(), y, ui.visuals().selection.stroke);
+                    }
+                }
+                if let Some(dragged) = row.response.dnd_release_payload::<DraggedColorSpace>() {
+                    move_from_to = Some((dragged.0, space_i));
+                }
+
+                if (hovering_files || dropping_files)
+                    && pointer_pos.map(|pos| row_rect.contains(pos)) == Some(true)
+                {
+                    lut_drop_target = Some(space_i);
+                    ui.painter().rect_stroke(
+                        row_rect.expand(2.0),
+                        2.0,
+                        ui.visuals().selection.stroke,
+                    );
+                }
+
                 space_i += 1;
             }
 
             ui_data.selected_space_index = selected_i;
         });
+    app.lut_drop_target = lut_drop_target;
+    if let Some((from, to)) = move_from_to {
+        app.move_color_space(from, to);
+    }
 
     if add_input_space {
         app.add_input_color_space();
     }
+    if let Some(space_i) = duplicate_i {
+        app.duplicate_color_space(space_i);
+    }
     if let Some(space_i) = remove_i {
         app.remove_color_space(space_i);
     }
+    if add_to_library {
+        app.add_selected_to_library();
+    }
+    if let Some(library_i) = add_from_library_i {
+        app.add_library_color_space(library_i);
+    } else if let Some(library_i) = remove_from_library_i {
+        app.remove_library_color_space(library_i);
+    }
 }
@@ -0,0 +1,97 @@
+use crate::egui::{self, Ui};
+use crate::ViewSpec;
+
+pub fn editor(
+    ui: &mut Ui,
+    display: &mut crate::DisplaySpec,
+    display_id: &str,
+    space_names: &[String],
+) {
+    // Name and Misc.
+    ui.horizontal(|ui| {
+        ui.label("Name: ");
+        ui.add(
+            egui::widgets::TextEdit::singleline(&mut display.name)
+                .id(egui::Id::new(display_id)),
+        )
+        .on_hover_text("If this matches the name of a display in the base config, these views are merged into it.");
+
+        ui.add_space(16.0);
+
+        ui.checkbox(&mut display.active, "Active");
+    });
+    ui.add_space(8.0);
+
+    // Views.
+    ui.label("Views:");
+    let mut move_up = None;
+    let mut remove = None;
+    let view_count = display.views.len();
+    ui.indent("views_container", |ui| {
+        egui::Grid::new("views").min_col_width(4.0).show(ui, |ui| {
+            ui.label("");
+            ui.label("");
+            ui.label("");
+            ui.label("Name");
+            ui.label("Color Space");
+            ui.label("");
+            ui.label("Active")
+                .on_hover_text("Active views are matched by name across all displays.");
+            ui.end_row();
+
+            for (i, view) in display.views.iter_mut().enumerate() {
+                if ui.button("🗙").clicked() {
+                    remove = Some(i);
+                }
+                if ui
+                    .add_enabled(i > 0, egui::widgets::Button::new("⏶"))
+                    .clicked()
+                {
+                    move_up = Some(i);
+                }
+                if ui
+                    .add_enabled(i + 1 < view_count, egui::widgets::Button::new("⏷"))
+                    .clicked()
+                {
+                    move_up = Some(i + 1);
+                }
+                ui.add(
+                    egui::widgets::TextEdit::singleline(&mut view.name)
+                        .id(egui::Id::new(format!("{}_view{}", display_id, i))),
+                );
+                ui.add(
+                    egui::widgets::TextEdit::singleline(&mut view.color_space)
+                        .id(egui::Id::new(format!("{}_view{}_space", display_id, i))),
+                );
+                if !space_names.is_empty() {
+                    egui::ComboBox::from_id_source(format!("view_space{}", i))
+                        .width(32.0)
+                        .selected_text("")
+                        .show_ui(ui, |ui| {
+                            for name in space_names.iter() {
+                                ui.selectable_value(&mut view.color_space, name.clone(), name);
+                            }
+                        });
+                } else {
+                    ui.label("");
+                }
+                ui.checkbox(&mut view.active, "");
+                ui.end_row();
+            }
+        });
+    });
+    if let Some(i) = remove {
+        display.views.remove(i);
+    } else if let Some(i) = move_up {
+        display.views.swap(i - 1, i);
+    }
+    ui.add_space(4.0);
+
+    if ui.button("Add View").clicked() {
+        display.views.push(ViewSpec {
+            name: format!("View {}", display.views.len() + 1),
+            color_space: String::new(),
+            active: true,
+        });
+    }
+}
@@ -0,0 +1,52 @@
+use crate::egui::{self, Ui};
+
+pub fn list(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize) {
+    let mut remove_i = None;
+    let mut add_display = false;
+
+    ui.add_space(4.0);
+    ui.horizontal(|ui| {
+        add_display |= ui.button("New  ➕").clicked();
+    });
+    ui.add_space(4.0);
+
+    egui::containers::ScrollArea::vertical()
+        .auto_shrink([true, false])
+        .show(ui, |ui| {
+            let ui_data = &mut *app.ui_data.lock_mut();
+
+            let mut display_i = 0;
+            let mut selected_i = ui_data.selected_display_index;
+
+            for display in ui_data.displays.iter() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
+                        .clicked()
+                    {
+                        remove_i = Some(display_i);
+                    }
+                    if ui
+                        .add(egui::widgets::SelectableLabel::new(
+                            display_i == ui_data.selected_display_index,
+                            &display.name,
+                        ))
+                        .clicked()
+                    {
+                        selected_i = display_i;
+                    }
+                });
+
+                display_i += 1;
+            }
+
+            ui_data.selected_display_index = selected_i;
+        });
+
+    if add_display {
+        app.add_display();
+    }
+    if let Some(display_i) = remove_i {
+        app.remove_display(display_i);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::egui::{self, Context};
+
+pub fn ui(ctx: &Context, app: &mut crate::AppMain, job_count: usize) {
+    // Display list (left-side panel).
+    egui::containers::panel::SidePanel::left("display_list")
+        .resizable(false)
+        .show(ctx, |ui| {
+            crate::display_list::list(ui, app, job_count);
+        });
+
+    // Main area.
+    egui::containers::panel::CentralPanel::default().show(ctx, |ui| {
+        // Main UI area.
+        let selected_display_index = app.ui_data.lock().selected_display_index;
+        if selected_display_index < app.ui_data.lock().displays.len() {
+            let mut ui_data = app.ui_data.lock_mut();
+            let space_names: Vec<String> = ui_data
+                .color_spaces
+                .iter()
+                .map(|s| s.name.trim().into())
+                .collect();
+            let display = &mut ui_data.displays[selected_display_index];
+
+            crate::display_editor::editor(
+                ui,
+                display,
+                &format!("display{}", selected_display_index),
+                &space_names,
+            );
+        }
+    });
+}
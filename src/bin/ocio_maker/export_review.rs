@@ -0,0 +1,133 @@
+use ocio_gen::{config::OCIOConfig, diff::ConfigDiff};
+
+use crate::egui::{self, Color32, Context, Ui};
+
+/// A generated config waiting for the user to confirm overwriting the
+/// previously exported one.
+pub struct PendingExport {
+    pub config: OCIOConfig,
+    pub diff: ConfigDiff,
+    pub export_path: String,
+}
+
+const ADDED_COLOR: Color32 = Color32::from_rgb(0x6c, 0xc6, 0x6c);
+const REMOVED_COLOR: Color32 = Color32::from_rgb(0xe0, 0x6c, 0x6c);
+const CHANGED_COLOR: Color32 = Color32::from_rgb(0xe0, 0xc0, 0x5a);
+
+/// Window listing what re-exporting will change, with buttons to go
+/// ahead or cancel.
+pub fn window(ctx: &Context, app: &mut crate::AppMain) {
+    let mut overwrite = false;
+    let mut cancel = false;
+
+    {
+        let ui_data = app.ui_data.lock();
+        let pending = match ui_data.pending_export {
+            Some(ref pending) => pending,
+            None => return,
+        };
+        let diff = &pending.diff;
+
+        egui::Window::new("Review Changes")
+            .collapsible(false)
+            .resizable(true)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "There is already a config in \"{}\".  Exporting will make the following changes to it:",
+                    pending.export_path
+                ));
+                ui.add_space(8.0);
+
+                egui::containers::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        if !diff.added_colorspaces.is_empty()
+                            || !diff.removed_colorspaces.is_empty()
+                            || !diff.changed_colorspaces.is_empty()
+                        {
+                            ui.strong("Color Spaces");
+                            for name in diff.added_colorspaces.iter() {
+                                entry(ui, "+", ADDED_COLOR, name);
+                            }
+                            for name in diff.removed_colorspaces.iter() {
+                                entry(ui, "−", REMOVED_COLOR, name);
+                            }
+                            for name in diff.changed_colorspaces.iter() {
+                                entry(ui, "~", CHANGED_COLOR, name);
+                            }
+                            ui.add_space(8.0);
+                        }
+
+                        if !diff.role_changes.is_empty() {
+                            ui.strong("Roles");
+                            for (role, old, new) in diff.role_changes.iter() {
+                                let text = format!(
+                                    "{}:  {}  →  {}",
+                                    role,
+                                    old.as_deref().unwrap_or("(none)"),
+                                    new.as_deref().unwrap_or("(none)"),
+                                );
+                                let (symbol, color) = match (old, new) {
+                                    (None, _) => ("+", ADDED_COLOR),
+                                    (_, None) => ("−", REMOVED_COLOR),
+                                    _ => ("~", CHANGED_COLOR),
+                                };
+                                entry(ui, symbol, color, &text);
+                            }
+                            ui.add_space(8.0);
+                        }
+
+                        if !diff.added_files.is_empty()
+                            || !diff.removed_files.is_empty()
+                            || !diff.changed_files.is_empty()
+                        {
+                            ui.strong("LUT Files");
+                            for path in diff.added_files.iter() {
+                                entry(ui, "+", ADDED_COLOR, &path.to_string_lossy());
+                            }
+                            for path in diff.removed_files.iter() {
+                                entry(ui, "−", REMOVED_COLOR, &path.to_string_lossy());
+                            }
+                            for (path, old, new) in diff.changed_files.iter() {
+                                let text = format!(
+                                    "{}  (checksum {:016x} → {:016x})",
+                                    path.to_string_lossy(),
+                                    old,
+                                    new
+                                );
+                                entry(ui, "~", CHANGED_COLOR, &text);
+                            }
+                            ui.add_space(8.0);
+                        }
+                    });
+
+                ui.label("Removed LUT files are no longer referenced by the config, but are left on disk.");
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Overwrite").clicked() {
+                        overwrite = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+    }
+
+    if overwrite {
+        if let Some(pending) = app.ui_data.lock_mut().pending_export.take() {
+            app.write_config(pending.config, pending.export_path);
+        }
+    } else if cancel {
+        app.ui_data.lock_mut().pending_export = None;
+    }
+}
+
+fn entry(ui: &mut Ui, symbol: &str, color: Color32, text: &str) {
+    ui.horizontal(|ui| {
+        ui.colored_label(color, symbol);
+        ui.label(text);
+    });
+}
@@ -0,0 +1,91 @@
+use crate::egui::{self, Context};
+
+pub fn ui(ctx: &Context, app: &mut crate::AppMain) {
+    egui::containers::panel::CentralPanel::default().show(ctx, |ui| {
+        ui.label("File rules let applications automatically assign color spaces to files, based on their paths.  They're tried in order, after any file rules of the base config.  The pattern matches the file path without its extension, and both the pattern and extension can use * and ? wildcards.");
+        ui.add_space(8.0);
+
+        let ui_data = &mut *app.ui_data.lock_mut();
+        let space_names: Vec<String> = ui_data
+            .color_spaces
+            .iter()
+            .map(|s| s.name.trim().into())
+            .collect();
+
+        let mut remove = None;
+        let mut move_up = None;
+        let rule_count = ui_data.file_rules.len();
+        egui::Grid::new("file_rules")
+            .min_col_width(4.0)
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label("");
+                ui.label("Name");
+                ui.label("Pattern");
+                ui.label("Extension");
+                ui.label("Color Space");
+                ui.label("");
+                ui.end_row();
+
+                for (i, rule) in ui_data.file_rules.iter_mut().enumerate() {
+                    if ui.button("🗙").clicked() {
+                        remove = Some(i);
+                    }
+                    if ui
+                        .add_enabled(i > 0 && rule_count > 1, egui::widgets::Button::new("⏶"))
+                        .clicked()
+                    {
+                        move_up = Some(i);
+                    }
+
+                    ui.add(
+                        egui::widgets::TextEdit::singleline(&mut rule.name)
+                            .id(egui::Id::new(format!("file_rule{}", i))),
+                    );
+                    ui.add(
+                        egui::widgets::TextEdit::singleline(&mut rule.pattern)
+                            .id(egui::Id::new(format!("file_rule{}_pattern", i)))
+                            .desired_width(96.0),
+                    );
+                    ui.add(
+                        egui::widgets::TextEdit::singleline(&mut rule.extension)
+                            .id(egui::Id::new(format!("file_rule{}_extension", i)))
+                            .desired_width(64.0),
+                    );
+
+                    ui.add(
+                        egui::widgets::TextEdit::singleline(&mut rule.color_space)
+                            .id(egui::Id::new(format!("file_rule{}_space", i))),
+                    );
+                    if !space_names.is_empty() {
+                        egui::ComboBox::from_id_source(format!("file_rule_space{}", i))
+                            .width(32.0)
+                            .selected_text("")
+                            .show_ui(ui, |ui| {
+                                for name in space_names.iter() {
+                                    ui.selectable_value(&mut rule.color_space, name.clone(), name);
+                                }
+                            });
+                    } else {
+                        ui.label("");
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = remove {
+            ui_data.file_rules.remove(i);
+        } else if let Some(i) = move_up {
+            ui_data.file_rules.swap(i - 1, i);
+        }
+        ui.add_space(4.0);
+
+        if ui.button("Add File Rule").clicked() {
+            ui_data.file_rules.push(crate::FileRuleSpec {
+                name: format!("Rule {}", rule_count + 1),
+                pattern: "*".into(),
+                extension: "*".into(),
+                color_space: String::new(),
+            });
+        }
+    });
+}
@@ -1,23 +1,167 @@
 use std::iter::FromIterator;
 
-use crate::egui::{Color32, Ui};
+use colorbox::chroma::Chromaticities;
 
-use lib::colors::*;
+use crate::egui::{self, Color32, Ui};
 
-pub fn graph(ui: &mut Ui, space: &mut crate::ColorSpaceSpec) {
+use lib::{
+    chroma_space::{ChromaSpace, CHROMA_SPACES},
+    colors::*,
+};
+
+/// Settings of the gamut graph.  They're shared between color spaces,
+/// so that a set of comparison gamuts can be checked against each of
+/// them in turn.
+#[derive(Debug, Clone, Default)]
+pub struct GraphSettings {
+    pub pointers_gamut: bool,
+    pub compare_gamuts: Vec<ChromaSpace>,
+    pub compare_spaces: Vec<String>, // Names of the config's color spaces.
+}
+
+/// The boundary of Pointer's gamut of real surface colors, in CIE xy
+/// (illuminant C).
+const POINTERS_GAMUT: &[[f64; 2]] = &[
+    [0.508, 0.226],
+    [0.538, 0.258],
+    [0.588, 0.280],
+    [0.637, 0.298],
+    [0.659, 0.316],
+    [0.634, 0.351],
+    [0.594, 0.391],
+    [0.557, 0.427],
+    [0.523, 0.462],
+    [0.482, 0.491],
+    [0.444, 0.515],
+    [0.409, 0.546],
+    [0.371, 0.558],
+    [0.332, 0.573],
+    [0.288, 0.584],
+    [0.242, 0.576],
+    [0.202, 0.530],
+    [0.177, 0.454],
+    [0.151, 0.389],
+    [0.151, 0.330],
+    [0.162, 0.295],
+    [0.157, 0.266],
+    [0.159, 0.245],
+    [0.142, 0.214],
+    [0.141, 0.195],
+    [0.129, 0.168],
+    [0.138, 0.141],
+    [0.145, 0.129],
+    [0.145, 0.106],
+    [0.161, 0.094],
+    [0.188, 0.084],
+    [0.252, 0.104],
+    [0.324, 0.127],
+    [0.393, 0.165],
+    [0.451, 0.199],
+    [0.508, 0.226],
+];
+
+/// Colors that comparison gamuts are drawn in, in order.
+const COMPARE_COLORS: &[Color32] = &[
+    WHITE,
+    Color32::from_rgb(255, 160, 60),
+    Color32::from_rgb(110, 170, 255),
+    Color32::from_rgb(150, 255, 110),
+    Color32::from_rgb(255, 110, 200),
+    Color32::from_rgb(180, 130, 255),
+];
+
+/// Draws the gamut of `space`, along with the comparison gamuts chosen
+/// in `settings`.  `config_spaces` are the config's color spaces that
+/// can be chosen for comparison.
+pub fn graph(
+    ui: &mut Ui,
+    space: &crate::ColorSpaceSpec,
+    config_spaces: &[crate::ColorSpaceSpec],
+    settings: &mut GraphSettings,
+) {
     // Visualize chromaticities / gamut.
     if let Some(chroma) = space.chroma_space.chromaticities(space.custom_chroma) {
-        use egui_plot::{HLine, Line, LineStyle, Plot, PlotPoints, VLine};
+        use egui_plot::{HLine, Line, LineStyle, Plot, PlotPoint, PlotPoints, Text, VLine};
+
+        let config_spaces: Vec<(&str, Chromaticities)> = config_spaces
+            .iter()
+            .filter(|s| s.name != space.name)
+            .filter_map(|s| {
+                s.chroma_space
+                    .chromaticities(s.custom_chroma)
+                    .map(|c| (s.name.as_str(), c))
+            })
+            .collect();
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut settings.pointers_gamut, "Pointer's Gamut")
+                .on_hover_text("The gamut of real-world surface colors.");
+            ui.menu_button("Compare With  ⏷", |ui| {
+                if !config_spaces.is_empty() {
+                    for (name, _) in config_spaces.iter() {
+                        let mut checked = settings.compare_spaces.iter().any(|n| n == name);
+                        if ui.checkbox(&mut checked, *name).changed() {
+                            if checked {
+                                settings.compare_spaces.push(name.to_string());
+                            } else {
+                                settings.compare_spaces.retain(|n| n != name);
+                            }
+                        }
+                    }
+                    ui.separator();
+                }
+                for gamut in CHROMA_SPACES.iter() {
+                    if *gamut == ChromaSpace::None || *gamut == ChromaSpace::Custom {
+                        continue;
+                    }
+                    let mut checked = settings.compare_gamuts.contains(gamut);
+                    if ui.checkbox(&mut checked, gamut.ui_text()).changed() {
+                        if checked {
+                            settings.compare_gamuts.push(*gamut);
+                        } else {
+                            settings.compare_gamuts.retain(|g| g != gamut);
+                        }
+                    }
+                }
+                ui.separator();
+                if ui.button("Clear").clicked() {
+                    settings.compare_gamuts.clear();
+                    settings.compare_spaces.clear();
+                    ui.close_menu();
+                }
+            });
+        });
+
+        // Gather the comparison gamuts, in the order they were chosen.
+        // Color spaces that no longer exist or have no gamut are
+        // silently skipped.
+        let mut comparisons: Vec<(&str, Chromaticities)> = Vec::new();
+        for name in settings.compare_spaces.iter() {
+            if let Some(&(name, chroma)) = config_spaces.iter().find(|(n, _)| n == name) {
+                comparisons.push((name, chroma));
+            }
+        }
+        for gamut in settings.compare_gamuts.iter() {
+            if let Some(chroma) = gamut.chromaticities(chroma) {
+                comparisons.push((gamut.ui_text(), chroma));
+            }
+        }
+
         let wp_style = LineStyle::Dashed { length: 10.0 };
         let r = [chroma.r.0, chroma.r.1];
         let g = [chroma.g.0, chroma.g.1];
         let b = [chroma.b.0, chroma.b.1];
         let w = [chroma.w.0, chroma.w.1];
 
+        let size = if comparisons.is_empty() && !settings.pointers_gamut {
+            250.0
+        } else {
+            400.0
+        };
         Plot::new("chromaticities_plot")
             .data_aspect(1.0)
-            .height(250.0)
-            .width(250.0)
+            .height(size)
+            .width(size)
             .include_x(-0.12)
             .include_x(1.12)
             .include_y(-0.12)
@@ -44,6 +188,34 @@ pub fn graph(ui: &mut Ui, space: &mut crate::ColorSpaceSpec) {
                 plot.hline(HLine::new(0.0).color(Color32::from_rgb(50, 50, 50)));
                 plot.vline(VLine::new(0.0).color(Color32::from_rgb(50, 50, 50)));
 
+                if settings.pointers_gamut {
+                    plot.line(
+                        Line::new(PlotPoints::from_iter(POINTERS_GAMUT.iter().copied()))
+                            .color(GRAY)
+                            .style(LineStyle::Dotted { spacing: 5.0 }),
+                    );
+                    plot.text(
+                        Text::new(PlotPoint::new(0.29, 0.6), "Pointer's")
+                            .color(GRAY)
+                            .anchor(egui::Align2::CENTER_BOTTOM),
+                    );
+                }
+
+                // Comparison gamuts.
+                for (i, (name, chroma)) in comparisons.iter().enumerate() {
+                    let color = COMPARE_COLORS[i % COMPARE_COLORS.len()];
+                    let points = [chroma.r, chroma.g, chroma.b, chroma.r];
+                    plot.line(
+                        Line::new(PlotPoints::from_iter(points.iter().map(|p| [p.0, p.1])))
+                            .color(color),
+                    );
+                    plot.text(
+                        Text::new(PlotPoint::new(chroma.r.0, chroma.r.1), *name)
+                            .color(color)
+                            .anchor(egui::Align2::LEFT_BOTTOM),
+                    );
+                }
+
                 // Color space
                 plot.line(Line::new(PlotPoints::from_iter([r, g].iter().copied())).color(YELLOW));
                 plot.line(Line::new(PlotPoints::from_iter([g, b].iter().copied())).color(CYAN));
@@ -63,6 +235,13 @@ pub fn graph(ui: &mut Ui, space: &mut crate::ColorSpaceSpec) {
                         .color(BLUE)
                         .style(wp_style),
                 );
+                if !comparisons.is_empty() {
+                    plot.text(
+                        Text::new(PlotPoint::new(g[0], g[1]), space.name.as_str())
+                            .color(YELLOW)
+                            .anchor(egui::Align2::LEFT_BOTTOM),
+                    );
+                }
             });
 
         ui.add_space(8.0);
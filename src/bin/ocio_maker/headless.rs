@@ -0,0 +1,74 @@
+//! Exporting configs from the command line, without the GUI, so that
+//! e.g. configs can be regenerated automatically when their LUTs change.
+
+use std::path::{Path, PathBuf};
+
+use job_queue::{JobQueue, LogLevel, Priority};
+use shared_data::Shared;
+
+use crate::UIData;
+
+pub struct ExportOptions {
+    /// Defaults to the directory of the project spec file.
+    pub export_path: Option<PathBuf>,
+    pub v1_compatible: bool,
+    pub p3_displays: bool,
+    pub verify: bool,
+}
+
+/// Loads the project spec file at `spec_path` and exports its config,
+/// printing any errors, warnings, and notes.
+///
+/// Returns whether it was successful.
+pub fn export(spec_path: &Path, options: ExportOptions) -> bool {
+    let job_queue = JobQueue::new();
+    let ui_data = Shared::new(UIData::new());
+
+    {
+        // Made absolute so that the default export directory isn't empty.
+        let spec_path = spec_path
+            .canonicalize()
+            .unwrap_or_else(|_| spec_path.into());
+        let ui_data = ui_data.clone_ref();
+        job_queue.add_job("Export Config", Priority::High, move |status| {
+            if !crate::load_project(status, &ui_data, &spec_path) {
+                return;
+            }
+            {
+                let mut ui_data = ui_data.lock_mut();
+                if let Some(path) = options.export_path {
+                    ui_data.export_path = path.to_string_lossy().into();
+                }
+                ui_data.v1_compatible = options.v1_compatible;
+                ui_data.p3_displays = options.p3_displays;
+            }
+
+            let (config, export_path) = match crate::prepare_export(status, &ui_data) {
+                Some(export) => export,
+                None => return,
+            };
+            if crate::write_config(status, &config, &export_path) && options.verify {
+                let colorspaces = ui_data.lock().color_space_names();
+                crate::verify_export(status, &export_path, &colorspaces);
+            }
+        });
+    }
+    job_queue.wait_for_all_jobs();
+
+    // Index zero is the most recent.
+    let mut success = true;
+    for i in (0..job_queue.log_count()).rev() {
+        if let Some((message, level)) = job_queue.get_log(i) {
+            match level {
+                LogLevel::Error => {
+                    success = false;
+                    eprintln!("Error: {}", message);
+                }
+                LogLevel::Warning => eprintln!("Warning: {}", message),
+                LogLevel::Note => println!("{}", message),
+            }
+        }
+    }
+
+    success
+}
@@ -7,7 +7,7 @@ pub fn ui(ctx: &Context, app: &mut crate::AppMain, working_dir: &mut PathBuf, jo
     egui::containers::panel::SidePanel::left("color_space_list")
         .resizable(false)
         .show(ctx, |ui| {
-            crate::colorspace_list::list(ui, app, job_count);
+            crate::colorspace_list::list(ui, app, working_dir, job_count);
         });
 
     // Main area.
@@ -15,7 +15,7 @@ pub fn ui(ctx: &Context, app: &mut crate::AppMain, working_dir: &mut PathBuf, jo
         // Main UI area.
         let selected_space_index = app.ui_data.lock().selected_space_index;
         if selected_space_index < app.ui_data.lock().color_spaces.len() {
-            let mut ui_data = app.ui_data.lock_mut();
+            let ui_data = &mut *app.ui_data.lock_mut();
             let space = &mut ui_data.color_spaces[selected_space_index];
 
             if let Err(message) = crate::colorspace_editor::editor(
@@ -30,8 +30,9 @@ pub fn ui(ctx: &Context, app: &mut crate::AppMain, working_dir: &mut PathBuf, jo
 
             ui.add_space(8.0);
 
-            crate::gamut_graph::graph(ui, space);
-            crate::transfer_function_graph::graph(ui, space);
+            let space = &ui_data.color_spaces[selected_space_index];
+            crate::gamut_graph::graph(ui, space, &ui_data.color_spaces, &mut ui_data.gamut_graph);
+            crate::transfer_function_graph::graph(ui, space, &mut ui_data.transfer_graph);
         }
     });
 }
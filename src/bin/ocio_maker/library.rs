@@ -0,0 +1,136 @@
+//! A library of color spaces (e.g. cameras) that's shared between all
+//! configs, so they can be defined once and then added to each show's
+//! config, rather than redefined every time.
+//!
+//! The library is stored in a per-user file, in the same text format
+//! that color spaces are stored in the header of exported configs.  LUT
+//! paths are stored as absolute paths.
+
+use std::path::{Path, PathBuf};
+
+use job_queue::{JobQueue, Priority};
+use shared_data::Shared;
+
+use crate::{ColorSpaceSpec, ConfigLoadErr};
+
+const LIBRARY_FILE_NAME: &str = "color_space_library.txt";
+
+/// Path of the library file, or `None` if there's no suitable
+/// per-user directory for it.
+pub fn library_path() -> Option<PathBuf> {
+    lib::user_dirs::config_path("ocio_maker", LIBRARY_FILE_NAME)
+}
+
+/// Loads the library in the background, replacing the contents of
+/// `library` once done.
+pub fn load(library: &Shared<Vec<ColorSpaceSpec>>, job_queue: &JobQueue) {
+    let path = match library_path() {
+        Some(path) if path.is_file() => path,
+        _ => return,
+    };
+    let library = library.clone_ref();
+
+    job_queue.add_job(
+        "Load Color Space Library",
+        Priority::Normal,
+        move |status| {
+            status
+                .lock_mut()
+                .set_progress("Loading color space library".into(), 0.0);
+
+            let text = match std::fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) => {
+                    status.lock_mut().log_error(format!(
+                        "Unable to read color space library \"{}\": {}",
+                        path.to_string_lossy(),
+                        e
+                    ));
+                    return;
+                }
+            };
+
+            let mut color_spaces = Vec::new();
+            for chunk in text.split("----\n") {
+                if !chunk.trim_start().starts_with("color_space:") {
+                    continue;
+                }
+                let (color_space, result) = ColorSpaceSpec::from_str::<&Path>(chunk, None);
+                if let Err(ConfigLoadErr::FileUnloadable(s)) = result {
+                    status.lock_mut().log_error(format!(
+                    "Unable to load LUT file: \"{}\".  Library color space \"{}\" is incomplete.",
+                    s, color_space.name
+                ));
+                }
+                color_spaces.push(color_space);
+            }
+
+            *library.lock_mut() = color_spaces;
+        },
+    );
+}
+
+/// Writes the library to disk in the background.
+pub fn save(library: &Shared<Vec<ColorSpaceSpec>>, job_queue: &JobQueue) {
+    let text = {
+        let mut text = String::new();
+        for space in library.lock().iter() {
+            text.push_str(&space.to_string::<&Path>(None, false));
+            text.push_str("----\n");
+        }
+        text
+    };
+
+    job_queue.add_job(
+        "Save Color Space Library",
+        Priority::Normal,
+        move |status| {
+            let path = match library_path() {
+                Some(path) => path,
+                None => {
+                    status.lock_mut().log_error(
+                        "Unable to save color space library: no user directory to save it in."
+                            .into(),
+                    );
+                    return;
+                }
+            };
+
+            let write = || -> std::io::Result<()> {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(&path, &text)
+            };
+            if let Err(e) = write() {
+                status.lock_mut().log_error(format!(
+                    "Unable to save color space library \"{}\": {}",
+                    path.to_string_lossy(),
+                    e
+                ));
+            }
+        },
+    );
+}
+
+/// A copy of `space` for the library, with its LUT paths made absolute.
+/// Relative paths are taken to be relative to `base_dir`.
+pub fn library_copy(space: &ColorSpaceSpec, base_dir: &Path) -> ColorSpaceSpec {
+    let absolute = |path: &PathBuf| -> PathBuf {
+        let path = if path.is_relative() {
+            base_dir.join(path)
+        } else {
+            path.clone()
+        };
+        path.canonicalize().unwrap_or(path)
+    };
+
+    let mut space = space.clone();
+    if let Some((_, ref mut path, _)) = space.transfer_lut {
+        *path = absolute(path);
+    }
+    if let Some((_, _, ref mut path)) = space.transfer_lut_3d {
+        *path = absolute(path);
+    }
+    space
+}
@@ -0,0 +1,242 @@
+use std::path::PathBuf;
+
+use ocio_gen::config::GradingStyle;
+
+use crate::egui::{self, Ui};
+use crate::LookTransformSpec;
+
+const GRADING_STYLES: &[GradingStyle] =
+    &[GradingStyle::Log, GradingStyle::Linear, GradingStyle::Video];
+
+pub fn editor(
+    ui: &mut Ui,
+    look: &mut crate::LookSpec,
+    look_id: &str,
+    space_names: &[String],
+    working_dir: &mut PathBuf,
+) -> Result<(), String> {
+    let load_lut_dialog = {
+        let mut d = rfd::FileDialog::new()
+            .set_title("Load LUT")
+            .add_filter("All Supported LUTs", &["spi1d", "cube"])
+            .add_filter("cube", &["cube"])
+            .add_filter("spi1d", &["spi1d"]);
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
+
+    // Name.
+    ui.horizontal(|ui| {
+        ui.label("Name: ");
+        ui.add(egui::widgets::TextEdit::singleline(&mut look.name).id(egui::Id::new(look_id)));
+    });
+    ui.add_space(8.0);
+
+    // Process space.
+    ui.horizontal(|ui| {
+        ui.label("Process Space: ");
+        ui.add(
+            egui::widgets::TextEdit::singleline(&mut look.process_space)
+                .id(egui::Id::new(format!("{}_process_space", look_id))),
+        )
+        .on_hover_text("The color space the look's transforms are applied in.  Can be any color space in the exported config, including those of the base config.");
+        if !space_names.is_empty() {
+            egui::ComboBox::from_id_source("Process Space")
+                .width(32.0)
+                .selected_text("")
+                .show_ui(ui, |ui| {
+                    for name in space_names.iter() {
+                        ui.selectable_value(&mut look.process_space, name.clone(), name);
+                    }
+                });
+        }
+    });
+    ui.add_space(8.0);
+
+    // Transform chain.
+    ui.label("Transforms (applied in order):");
+    let mut move_up = None;
+    let mut remove = None;
+    let transform_count = look.transforms.len();
+    ui.indent("look_transforms_container", |ui| {
+        for (i, transform) in look.transforms.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.button("🗙").clicked() {
+                    remove = Some(i);
+                }
+                if ui
+                    .add_enabled(i > 0, egui::widgets::Button::new("⏶"))
+                    .clicked()
+                {
+                    move_up = Some(i);
+                }
+                if ui
+                    .add_enabled(i + 1 < transform_count, egui::widgets::Button::new("⏷"))
+                    .clicked()
+                {
+                    move_up = Some(i + 1);
+                }
+                ui.strong(transform.ui_text());
+            });
+            ui.indent(i, |ui| transform_editor(ui, transform, i));
+            ui.add_space(4.0);
+        }
+    });
+    if let Some(i) = remove {
+        look.transforms.remove(i);
+    } else if let Some(i) = move_up {
+        look.transforms.swap(i - 1, i);
+    }
+    ui.add_space(4.0);
+
+    // Add transforms.
+    let mut result = Ok(());
+    ui.horizontal(|ui| {
+        if ui.button("Add CDL").clicked() {
+            look.transforms.push(LookTransformSpec::default_cdl());
+        }
+        if ui.button("Add Grading Primary").clicked() {
+            look.transforms
+                .push(LookTransformSpec::default_grading_primary());
+        }
+        if ui.button("Add LUT File...").clicked() {
+            if let Some(path) = load_lut_dialog.clone().pick_file() {
+                if let Some(parent) = path.parent().map(|p| p.into()) {
+                    *working_dir = parent;
+                }
+
+                match LookTransformSpec::load_file(&path) {
+                    Some(transform) => look.transforms.push(transform),
+                    None => {
+                        result = Err(format!(
+                            "Unable to load LUT file \"{}\".",
+                            path.to_string_lossy()
+                        ));
+                    }
+                }
+            }
+        }
+    });
+
+    result
+}
+
+fn transform_editor(ui: &mut Ui, transform: &mut LookTransformSpec, index: usize) {
+    let precision = 0.001;
+
+    match *transform {
+        LookTransformSpec::CDL {
+            ref mut slope,
+            ref mut offset,
+            ref mut power,
+            ref mut saturation,
+        } => {
+            egui::Grid::new(format!("cdl{}", index))
+                .min_col_width(4.0)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label("R");
+                    ui.label("G");
+                    ui.label("B");
+                    ui.end_row();
+
+                    for (label, values) in
+                        vec![("Slope", slope), ("Offset", offset), ("Power", power)].into_iter()
+                    {
+                        ui.label(label);
+                        for n in values.iter_mut() {
+                            ui.add(egui::widgets::DragValue::new(n).speed(precision));
+                        }
+                        ui.end_row();
+                    }
+
+                    ui.label("Saturation");
+                    ui.add(
+                        egui::widgets::DragValue::new(saturation)
+                            .clamp_range(0.0..=4.0)
+                            .speed(precision),
+                    );
+                    ui.end_row();
+                });
+        }
+
+        LookTransformSpec::GradingPrimary {
+            ref mut style,
+            ref mut contrast,
+            ref mut saturation,
+            ref mut pivot,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label("Style: ");
+                egui::ComboBox::from_id_source(format!("grading_style{}", index))
+                    .selected_text(style.as_str())
+                    .show_ui(ui, |ui| {
+                        for gs in GRADING_STYLES {
+                            ui.selectable_value(style, *gs, gs.as_str());
+                        }
+                    });
+            });
+            egui::Grid::new(format!("grading_primary{}", index))
+                .min_col_width(4.0)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label("R");
+                    ui.label("G");
+                    ui.label("B");
+                    ui.end_row();
+
+                    ui.label("Contrast");
+                    for n in contrast.iter_mut() {
+                        ui.add(egui::widgets::DragValue::new(n).speed(precision));
+                    }
+                    ui.end_row();
+
+                    ui.label("Pivot");
+                    ui.add(egui::widgets::DragValue::new(pivot).speed(precision));
+                    ui.end_row();
+
+                    ui.label("Saturation");
+                    ui.add(
+                        egui::widgets::DragValue::new(saturation)
+                            .clamp_range(0.0..=4.0)
+                            .speed(precision),
+                    );
+                    ui.end_row();
+                });
+        }
+
+        LookTransformSpec::File {
+            ref lut_1d,
+            ref lut_3d,
+            ref path,
+        } => {
+            ui.horizontal(|ui| {
+                ui.strong(if let Some(name) = path.file_name() {
+                    let tmp: String = name.to_string_lossy().into();
+                    tmp
+                } else {
+                    "Unnamed LUT".into()
+                });
+                ui.label(match (lut_1d.is_some(), lut_3d.is_some()) {
+                    (true, true) => "(3D LUT with shaper)",
+                    (false, true) => "(3D LUT)",
+                    _ => "(1D LUT)",
+                });
+            });
+            if let Some(lut_3d) = lut_3d {
+                egui::CollapsingHeader::new("Inspect")
+                    .id_source(format!("look_inspect_lut_3d{}", index))
+                    .show(ui, |ui| {
+                        egui_custom::Lut3DView::new(
+                            format!("look_lut_3d_view{}", index),
+                            lut_3d.resolution,
+                            [&lut_3d.tables[0], &lut_3d.tables[1], &lut_3d.tables[2]],
+                        )
+                        .show(ui);
+                    });
+            }
+        }
+    }
+}
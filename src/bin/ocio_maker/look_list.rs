@@ -0,0 +1,52 @@
+use crate::egui::{self, Ui};
+
+pub fn list(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize) {
+    let mut remove_i = None;
+    let mut add_look = false;
+
+    ui.add_space(4.0);
+    ui.horizontal(|ui| {
+        add_look |= ui.button("New  ➕").clicked();
+    });
+    ui.add_space(4.0);
+
+    egui::containers::ScrollArea::vertical()
+        .auto_shrink([true, false])
+        .show(ui, |ui| {
+            let ui_data = &mut *app.ui_data.lock_mut();
+
+            let mut look_i = 0;
+            let mut selected_i = ui_data.selected_look_index;
+
+            for look in ui_data.looks.iter() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
+                        .clicked()
+                    {
+                        remove_i = Some(look_i);
+                    }
+                    if ui
+                        .add(egui::widgets::SelectableLabel::new(
+                            look_i == ui_data.selected_look_index,
+                            &look.name,
+                        ))
+                        .clicked()
+                    {
+                        selected_i = look_i;
+                    }
+                });
+
+                look_i += 1;
+            }
+
+            ui_data.selected_look_index = selected_i;
+        });
+
+    if add_look {
+        app.add_look();
+    }
+    if let Some(look_i) = remove_i {
+        app.remove_look(look_i);
+    }
+}
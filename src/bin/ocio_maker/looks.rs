@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use crate::egui::{self, Context};
+
+pub fn ui(ctx: &Context, app: &mut crate::AppMain, working_dir: &mut PathBuf, job_count: usize) {
+    // Look list (left-side panel).
+    egui::containers::panel::SidePanel::left("look_list")
+        .resizable(false)
+        .show(ctx, |ui| {
+            crate::look_list::list(ui, app, job_count);
+        });
+
+    // Main area.
+    egui::containers::panel::CentralPanel::default().show(ctx, |ui| {
+        // Main UI area.
+        let selected_look_index = app.ui_data.lock().selected_look_index;
+        if selected_look_index < app.ui_data.lock().looks.len() {
+            let mut ui_data = app.ui_data.lock_mut();
+            let space_names: Vec<String> = ui_data
+                .color_spaces
+                .iter()
+                .map(|s| s.name.trim().into())
+                .collect();
+            let look = &mut ui_data.looks[selected_look_index];
+
+            if let Err(message) = crate::look_editor::editor(
+                ui,
+                look,
+                &format!("look{}", selected_look_index),
+                &space_names,
+                working_dir,
+            ) {
+                app.job_queue.log_error(message);
+            };
+        }
+    });
+}
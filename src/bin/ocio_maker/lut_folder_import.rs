@@ -0,0 +1,264 @@
+use std::path::{Path, PathBuf};
+
+use colorbox::formats;
+use job_queue::Priority;
+
+use lib::chroma_space::{ChromaSpace, CHROMA_SPACES};
+
+use crate::egui::{self, Context};
+
+/// A folder of LUTs waiting to be imported as input color spaces, one
+/// per LUT, along with the settings that apply to all of them.
+pub struct LutFolderImport {
+    dir: PathBuf,
+    lut_paths: Vec<PathBuf>, // Sorted by file name.
+    chroma_space: ChromaSpace,
+    inverse: bool, // Whether the LUTs are from linear, unless their names say otherwise.
+}
+
+impl LutFolderImport {
+    /// Finds the LUT files in `dir`.  Subdirectories aren't searched.
+    pub fn new(dir: &Path) -> std::io::Result<LutFolderImport> {
+        let mut lut_paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() && crate::is_transfer_lut_path(&path) {
+                lut_paths.push(path);
+            }
+        }
+        lut_paths.sort();
+
+        Ok(LutFolderImport {
+            dir: dir.into(),
+            lut_paths: lut_paths,
+            chroma_space: ChromaSpace::None,
+            inverse: false,
+        })
+    }
+}
+
+/// Window for choosing the gamut and direction of a folder of LUTs
+/// before importing them.
+pub fn window(ctx: &Context, app: &mut crate::AppMain, job_count: usize) {
+    let mut import = false;
+    let mut cancel = false;
+
+    {
+        let state = match app.lut_folder_import {
+            Some(ref mut state) => state,
+            None => return,
+        };
+
+        egui::Window::new("Import LUT Folder")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} LUT(s) in \"{}\".  Each becomes an input color space named after its file.",
+                    state.lut_paths.len(),
+                    state.dir.to_string_lossy()
+                ));
+                ui.add_space(8.0);
+
+                egui::Grid::new("lut_folder_import_settings")
+                    .min_col_width(4.0)
+                    .show(ui, |ui| {
+                        ui.label("Gamut: ");
+                        egui::ComboBox::from_id_source("lut_folder_import_gamut")
+                            .width(256.0)
+                            .selected_text(state.chroma_space.ui_text())
+                            .show_ui(ui, |ui| {
+                                for cs in CHROMA_SPACES {
+                                    // There's nowhere to enter custom chromaticities here.
+                                    if *cs != ChromaSpace::Custom {
+                                        ui.selectable_value(
+                                            &mut state.chroma_space,
+                                            *cs,
+                                            cs.ui_text(),
+                                        );
+                                    }
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Direction: ");
+                        ui.horizontal(|ui| {
+                            ui.radio_value(&mut state.inverse, false, "To linear");
+                            ui.radio_value(&mut state.inverse, true, "From linear");
+                        })
+                        .response
+                        .on_hover_text("Files named like \"linear_to_<name>\" or \"<name>_to_linear\" use the direction their name implies instead.");
+                        ui.end_row();
+                    });
+                ui.add_space(8.0);
+
+                egui::containers::ScrollArea::vertical()
+                    .max_height(240.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for path in state.lut_paths.iter() {
+                            let (name, _) = guess_name_and_direction(path);
+                            ui.label(format!(
+                                "{}  ←  {}",
+                                name,
+                                path.file_name().unwrap_or_default().to_string_lossy()
+                            ));
+                        }
+                    });
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            job_count == 0 && !state.lut_paths.is_empty(),
+                            egui::widgets::Button::new("Import"),
+                        )
+                        .clicked()
+                    {
+                        import = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+    }
+
+    if import {
+        if let Some(state) = app.lut_folder_import.take() {
+            import_luts(app, state);
+        }
+    } else if cancel {
+        app.lut_folder_import = None;
+    }
+}
+
+/// Loads the LUTs in a job, and adds their color spaces all at once.
+fn import_luts(app: &crate::AppMain, state: LutFolderImport) {
+    let ui_data = app.ui_data.clone_ref();
+
+    app.job_queue
+        .add_job("Import LUT Folder", Priority::Normal, move |status| {
+            let mut spaces = Vec::new();
+            let lut_count = state.lut_paths.len();
+            for (i, path) in state.lut_paths.iter().enumerate() {
+                status.lock_mut().set_progress(
+                    format!("Loading LUT: {}", path.to_string_lossy()),
+                    i as f32 / lut_count as f32,
+                );
+
+                let (name, inverse) = guess_name_and_direction(path);
+                let mut space = crate::ColorSpaceSpec::with_name(&name);
+                space.chroma_space = state.chroma_space;
+
+                // .cube files can be either 1D or 3D.
+                match lib::job_helpers::load_1d_lut(path) {
+                    Ok(lut) => {
+                        space.transfer_lut =
+                            Some((lut, path.clone(), inverse.unwrap_or(state.inverse)))
+                    }
+                    Err(formats::ReadError::FormatErr) => {
+                        match lib::job_helpers::load_3d_lut(path) {
+                            Ok((lut, shaper)) => {
+                                space.transfer_lut_3d = Some((lut, shaper, path.clone()))
+                            }
+                            Err(_) => {
+                                status.lock_mut().log_warning(format!(
+                                    "Skipped \"{}\", which isn't a LUT that can be loaded.",
+                                    path.to_string_lossy()
+                                ));
+                                continue;
+                            }
+                        }
+                    }
+                    Err(formats::ReadError::IoErr(_)) => {
+                        status.lock_mut().log_warning(format!(
+                            "Skipped \"{}\", which couldn't be read.",
+                            path.to_string_lossy()
+                        ));
+                        continue;
+                    }
+                }
+                spaces.push(space);
+            }
+
+            let ui_data = &mut *ui_data.lock_mut();
+            let added_count = spaces.len();
+            for mut space in spaces {
+                // Keep names unique.
+                let base_name = space.name.clone();
+                for i in 2..200 {
+                    if !ui_data.color_spaces.iter().any(|s| s.name == space.name) {
+                        break;
+                    }
+                    space.name = format!("{} {}", base_name, i);
+                }
+                ui_data.color_spaces.push(space);
+            }
+            if added_count > 0 {
+                ui_data.selected_space_index = ui_data.color_spaces.len() - added_count;
+            }
+            status.lock_mut().log_note(format!(
+                "Imported {} of {} LUT(s) from \"{}\" as color spaces.",
+                added_count,
+                lut_count,
+                state.dir.to_string_lossy()
+            ));
+        });
+}
+
+/// Guesses a color space name from a LUT's file name, along with its
+/// direction (`Some(true)` for from-linear) if the name gives it away.
+///
+/// For example, "linear_to_slog3.cube" becomes `("Slog3", Some(true))`
+/// and "Arri-LogC4.spi1d" becomes `("Arri LogC4", None)`.
+fn guess_name_and_direction(path: &Path) -> (String, Option<bool>) {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let strip_prefix = |prefix: &str| {
+        stem.get(..prefix.len())
+            .filter(|s| s.eq_ignore_ascii_case(prefix))
+            .map(|_| &stem[prefix.len()..])
+    };
+    let strip_suffix = |suffix: &str| {
+        let i = stem.len().checked_sub(suffix.len())?;
+        stem.get(i..)
+            .filter(|s| s.eq_ignore_ascii_case(suffix))
+            .map(|_| &stem[..i])
+    };
+    let (name, inverse) =
+        if let Some(name) = strip_prefix("linear_to_").or_else(|| strip_prefix("lin_to_")) {
+            (name, Some(true))
+        } else if let Some(name) = strip_suffix("_to_linear").or_else(|| strip_suffix("_to_lin")) {
+            (name, Some(false))
+        } else {
+            (&stem[..], None)
+        };
+
+    // Underscores and dashes to spaces, and capitalize all-lowercase
+    // words.
+    let words: Vec<String> = name
+        .split(|c: char| c == '_' || c == '-' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            if w.chars().all(|c| !c.is_uppercase()) {
+                let mut chars = w.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            } else {
+                w.into()
+            }
+        })
+        .collect();
+
+    if words.is_empty() {
+        (stem, inverse)
+    } else {
+        (words.join(" "), inverse)
+    }
+}
@@ -1,40 +1,130 @@
 #![windows_subsystem = "windows"] // Don't go through console on Windows.
 
+mod bake_lut;
 mod base_config;
 mod colorspace_editor;
 mod colorspace_list;
+mod display_editor;
+mod display_list;
+mod displays;
+mod export_review;
+mod file_rules;
 mod gamut_graph;
+mod headless;
 mod input_transforms;
+mod library;
+mod look_editor;
+mod look_list;
+mod looks;
+mod lut_folder_import;
 mod menu;
+mod named_transform_editor;
+mod named_transform_list;
+mod named_transforms;
+mod roles;
+mod soft_proof;
 mod top_bar;
 mod transfer_function_graph;
+mod validation_panel;
 
 use std::{
-    io::{BufRead, BufReader},
+    collections::HashMap,
     path::{Path, PathBuf},
 };
 
 use eframe::egui;
 
-use colorbox::lut::Lut1D;
+use colorbox::lut::{Lut1D, Lut3D};
+use job_queue::Priority;
+use ocio_gen::{
+    config::{Encoding, GradingStyle},
+    gamut_map::GamutMapping,
+};
 use shared_data::Shared;
 
+use lib::chroma_space::ChromaSpace;
+use lib::preferences::Preferences;
+use lib::undo::{HistoryAction, UndoStack};
+use lib::white_balance::WhiteBalance;
+
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const APP_NAME: &str = "ocio_maker";
+const DEFAULT_WORKER_COUNT: usize = 1;
+
+/// The family of exported color spaces and named transforms that don't
+/// specify one.
+const DEFAULT_FAMILY: &str = "Custom (OCIO Maker)";
+
+/// Common families, offered as presets in the color space editor.
+const FAMILY_PRESETS: &[&str] = &["Camera", "Display", "Utility"];
 
 fn main() {
-    clap::App::new("ETF OCIO Maker")
+    let args = clap::App::new("ETF OCIO Maker")
         .version(VERSION)
         .author("Nathan Vegdahl, Ian Hubert")
         .about("Make OCIO configurations easily")
+        .arg(
+            clap::Arg::with_name("export")
+                .long("export")
+                .value_name("SPEC_FILE")
+                .takes_value(true)
+                .help("Exports the config of a project spec file without opening the GUI.  A config previously exported by OCIO Maker can also be used as the spec file."),
+        )
+        .arg(
+            clap::Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("DIR")
+                .takes_value(true)
+                .requires("export")
+                .help("The directory to export the config to.  Defaults to the directory of the spec file."),
+        )
+        .arg(
+            clap::Arg::with_name("v1_compatible")
+                .long("v1-compatible")
+                .requires("export")
+                .help("Exports an OCIO v1 compatible config."),
+        )
+        .arg(
+            clap::Arg::with_name("p3_displays")
+                .long("p3-displays")
+                .requires("export")
+                .help("Adds DCI-P3 and P3-D65 displays to the config."),
+        )
+        .arg(
+            clap::Arg::with_name("verify")
+                .long("verify")
+                .requires("export")
+                .help("Verifies the exported config, and fails if its color spaces don't round-trip accurately."),
+        )
         .get_matches();
 
+    if let Some(spec_path) = args.value_of("export") {
+        let success = headless::export(
+            Path::new(spec_path),
+            headless::ExportOptions {
+                export_path: args.value_of("output").map(|p| p.into()),
+                v1_compatible: args.is_present("v1_compatible"),
+                p3_displays: args.is_present("p3_displays"),
+                verify: args.is_present("verify"),
+            },
+        );
+        std::process::exit(if success { 0 } else { 1 });
+    }
+
+    let preferences = Preferences::load(APP_NAME);
+    let mut viewport = egui::ViewportBuilder::default().with_drag_and_drop(true); // Enable drag-and-dropping files on Windows.
+    if let Ok(preferences) = &preferences {
+        viewport = preferences.apply_to_viewport(viewport);
+    }
+
     eframe::run_native(
         "OCIO Maker",
         eframe::NativeOptions {
-            viewport: egui::ViewportBuilder::default().with_drag_and_drop(true), // Enable drag-and-dropping files on Windows.
+            viewport: viewport,
             ..eframe::NativeOptions::default()
         },
-        Box::new(|cc| Box::new(AppMain::new(cc))),
+        Box::new(|cc| Box::new(AppMain::new(cc, preferences))),
     )
     .expect("Couldn't start application.");
 }
@@ -42,48 +132,1143 @@ fn main() {
 pub struct AppMain {
     job_queue: job_queue::JobQueue,
     last_opened_directory: Option<PathBuf>,
+    preferences: Preferences,
+    undo_stack: UndoStack<EditState>,
+    soft_proof: soft_proof::SoftProof,
+    lut_drop_target: Option<usize>, // Color space row that files are being dragged over.
+    lut_folder_import: Option<lut_folder_import::LutFolderImport>,
 
     ui_data: Shared<UIData>,
+
+    // Color spaces shared between configs.  Not part of the undo
+    // history, since it's saved separately from any config.
+    library: Shared<Vec<ColorSpaceSpec>>,
 }
 
 impl AppMain {
-    fn new(cc: &eframe::CreationContext) -> AppMain {
+    fn new(cc: &eframe::CreationContext, preferences: std::io::Result<Preferences>) -> AppMain {
         // Dark mode.
         cc.egui_ctx.set_visuals(egui::style::Visuals {
             dark_mode: true,
             ..egui::style::Visuals::default()
         });
 
+        let (preferences, preferences_error) = match preferences {
+            Ok(preferences) => (preferences, None),
+            Err(e) => (Preferences::default(), Some(e)),
+        };
+
         // Update callback for jobs.
-        let mut job_queue = job_queue::JobQueue::new();
+        let mut job_queue =
+            job_queue::JobQueue::with_worker_count(preferences.worker_count(DEFAULT_WORKER_COUNT));
         let ctx_clone = cc.egui_ctx.clone();
         job_queue.set_update_fn(move || {
             ctx_clone.request_repaint();
         });
 
-        AppMain {
+        // Keep a log of the session on disk, for bug reports.
+        if let Some(path) = lib::user_dirs::log_path(APP_NAME) {
+            if let Err(e) = job_queue.set_log_file(&path) {
+                job_queue.log_warning(format!(
+                    "Unable to open log file \"{}\": {}",
+                    path.to_string_lossy(),
+                    e
+                ));
+            }
+        }
+        if let Some(e) = preferences_error {
+            job_queue.log_warning(format!("Unable to load preferences: {}", e));
+        }
+
+        let app = AppMain {
             job_queue: job_queue,
-            last_opened_directory: std::env::current_dir().ok(),
-
-            ui_data: Shared::new(UIData {
-                selected_tab: Tabs::BaseConfig,
-
-                base_preset: BasePreset::Blender4_0,
-                working_color_space: ColorSpaceSpec {
-                    // Only the `chroma_space` and `custom_chroma` fields are
-                    // actually used to define the working color space.
-                    name: "".into(),
-                    transfer_lut: None,
-                    chroma_space: ChromaSpace::Rec709,
-                    custom_chroma: colorbox::chroma::REC709,
-                    include_as_display: false,
-                },
-                color_spaces: Vec::new(),
-                selected_space_index: 0,
-                export_path: String::new(),
-            }),
+            last_opened_directory: preferences
+                .last_directory
+                .clone()
+                .or_else(|| std::env::current_dir().ok()),
+            preferences: preferences,
+            undo_stack: UndoStack::new(100),
+            soft_proof: soft_proof::SoftProof::new(),
+            lut_drop_target: None,
+            lut_folder_import: None,
+
+            ui_data: Shared::new(UIData::new()),
+            library: Shared::new(Vec::new()),
+        };
+
+        // Fetch the base config's color space names for validation.
+        app.check_base_preset();
+
+        library::load(&app.library, &app.job_queue);
+
+        app
+    }
+}
+
+/// Generates the OCIO config described by the UI data.
+///
+/// `export_path` is only used for making the paths of source LUT files
+/// in the header comment relative.
+fn generate_config(
+    ui_data: &Shared<UIData>,
+    export_path: &str,
+) -> Result<ocio_gen::config::OCIOConfig, String> {
+    use colorbox::matrix;
+    use ocio_gen::config::*;
+
+    let base_preset = ui_data.lock().base_preset;
+    let imported_config_path = ui_data.lock().imported_config_path.clone();
+
+    // Template config.
+    let working_color_space = ui_data.lock().working_color_space.clone();
+    let tone_map_params = ui_data.lock().tone_map_params;
+    let (mut config, working_space_chroma) =
+        base_preset.make_config(&working_color_space, tone_map_params, &imported_config_path)?;
+    if ui_data.lock().p3_displays {
+        config.add_p3_displays(matrix::AdaptationMethod::Hunt);
+    }
+
+    // Initial header comment.
+    config
+        .header_comment
+        .push_str(&format!("Generated by ETF OCIO Maker v{}\n\n", VERSION));
+    config
+        .header_comment
+        .push_str("Hand edits to anything generated by ETF OCIO Maker will be lost when\n");
+    config
+        .header_comment
+        .push_str("it's exported again, but hand-added color spaces, looks, etc. are kept.\n\n");
+    config.header_comment.push_str("----\n");
+    config
+        .header_comment
+        .push_str(&format!("base: {}\n", base_preset.as_str()));
+    match base_preset {
+        BasePreset::Custom => {
+            config.header_comment.push_str(
+                &ui_data
+                    .lock()
+                    .working_color_space
+                    .to_string::<&str>(None, true),
+            );
+        }
+        BasePreset::Imported => {
+            let path = relative_path(Path::new(&imported_config_path), Some(&export_path));
+            config
+                .header_comment
+                .push_str(&format!("imported_path: {}\n", path.to_string_lossy()));
+        }
+        _ => {}
+    }
+    if base_preset.has_adjustable_view_transforms()
+        && tone_map_params != ocio_gen::ToneMapParams::default()
+    {
+        config.header_comment.push_str(&format!(
+            "tone_map: {} {} {}\n",
+            tone_map_params.contrast, tone_map_params.shoulder, tone_map_params.saturation
+        ));
+    }
+    let lut_paths = ui_data.lock().lut_paths.clone();
+    if lut_paths != LutPaths::Copy {
+        config
+            .header_comment
+            .push_str(&format!("lut_paths: {}\n", lut_paths.to_string()));
+    }
+    config.header_comment.push_str("----\n");
+
+    // Prep to add our own stuff.
+    let output_dir: &Path = "ocio_maker".as_ref();
+    if lut_paths == LutPaths::Copy {
+        config.add_search_path(&output_dir.to_string_lossy());
+    }
+    let mut source_luts = SourceLuts::new(&lut_paths, export_path);
+    let space_count = ui_data.lock().color_spaces.len();
+
+    // Add color spaces.
+    for i in 0..space_count {
+        if let Some(space) = ui_data.lock().color_spaces.get(i).map(|s| s.clone()) {
+            // Add text version to header comment.
+            config
+                .header_comment
+                .push_str(&space.to_string(Some(&export_path), false));
+            config.header_comment.push_str("----\n");
+
+            // Actual export.
+            let space_name = escape_name(&space.name);
+
+            let mut to_linear_transforms = Vec::new();
+            if let Some((ref lut_3d, ref shaper, ref path)) = space.transfer_lut_3d {
+                if let Some(src) = source_luts.place(&mut config, path)? {
+                    to_linear_transforms.push(Transform::FileTransform {
+                        src: src,
+                        interpolation: Interpolation::Tetrahedral,
+                        direction_inverse: false,
+                    });
+                } else {
+                    let file_name = path
+                        .file_name()
+                        .map(|f| f.to_str())
+                        .flatten()
+                        .unwrap_or("lut.cube");
+
+                    // Shaper.
+                    if let Some(shaper) = shaper {
+                        let shaper_path =
+                            output_dir.join(format!("omkr_{}__shaper__{}", i, file_name));
+                        config
+                            .output_files
+                            .entry(shaper_path.clone())
+                            .or_insert(OutputFile::Lut1D(shaper.clone()));
+                        to_linear_transforms.push(Transform::FileTransform {
+                            src: shaper_path.file_name().unwrap().to_string_lossy().into(),
+                            interpolation: Interpolation::Linear,
+                            direction_inverse: false,
+                        });
+                    }
+
+                    // 3D LUT.
+                    let lut_path = output_dir.join(format!("omkr_{}__{}", i, file_name));
+                    config
+                        .output_files
+                        .entry(lut_path.clone())
+                        .or_insert(OutputFile::Lut3D(lut_3d.clone()));
+                    to_linear_transforms.push(Transform::FileTransform {
+                        src: lut_path.file_name().unwrap().to_string_lossy().into(),
+                        interpolation: Interpolation::Tetrahedral,
+                        direction_inverse: false,
+                    });
+                }
+            } else if let Some((ref lut, ref path, inverse)) = space.transfer_lut {
+                if let Some(src) = source_luts.place(&mut config, path)? {
+                    to_linear_transforms.push(Transform::FileTransform {
+                        src: src,
+                        interpolation: Interpolation::Linear,
+                        direction_inverse: inverse,
+                    });
+                } else {
+                    // Compute output path.
+                    let lut_path = output_dir.join(format!(
+                        "omkr_{}__{}",
+                        i,
+                        path.file_name()
+                            .map(|f| f.to_str())
+                            .flatten()
+                            .unwrap_or("lut.cube")
+                    ));
+
+                    // Add LUT file to config if it's not already there.
+                    config
+                        .output_files
+                        .entry(lut_path.clone())
+                        .or_insert(OutputFile::Lut1D(lut.clone()));
+
+                    to_linear_transforms.push(Transform::FileTransform {
+                        src: lut_path.file_name().unwrap().to_string_lossy().into(),
+                        interpolation: Interpolation::Linear,
+                        direction_inverse: inverse,
+                    });
+                }
+            }
+
+            if let Some(m) = space.input_matrix {
+                to_linear_transforms.push(Transform::MatrixTransform([
+                    m[0][0] as f32,
+                    m[0][1] as f32,
+                    m[0][2] as f32,
+                    m[0][3] as f32,
+                    m[1][0] as f32,
+                    m[1][1] as f32,
+                    m[1][2] as f32,
+                    m[1][3] as f32,
+                    m[2][0] as f32,
+                    m[2][1] as f32,
+                    m[2][2] as f32,
+                    m[2][3] as f32,
+                    0.0,
+                    0.0,
+                    0.0,
+                    1.0,
+                ]));
+            }
+
+            let chroma = space
+                .chroma_space
+                .chromaticities(space.custom_chroma)
+                .unwrap_or(working_space_chroma);
+
+            if let Some(white_balance) = space.white_balance {
+                to_linear_transforms.push(Transform::MatrixTransform(matrix::to_4x4_f32(
+                    white_balance.matrix(chroma),
+                )));
+            }
+
+            config
+                .add_input_colorspace(
+                    space_name.clone(),
+                    Some(space.family_or_default().into()),
+                    if space.description.trim().is_empty() {
+                        None
+                    } else {
+                        Some(space.description.trim().into())
+                    },
+                    chroma,
+                    matrix::AdaptationMethod::Bradford,
+                    to_linear_transforms,
+                    space.gamut_mapping,
+                )
+                .map_err(|e| format!("Color space \"{}\": {}", space_name, e))?;
+            {
+                let added_space = config.colorspaces.last_mut().unwrap();
+                added_space.aliases = space.alias_list().iter().map(|a| escape_name(a)).collect();
+                added_space.encoding = space.encoding;
+            }
+
+            if space.inactive {
+                config.inactive_colorspaces.push(space_name.clone());
+            }
+
+            if space.include_as_display {
+                config.displays.push(Display {
+                    name: space_name.clone(),
+                    views: vec![("Standard".into(), space_name.clone())],
+                });
+                config.active_displays.push(space_name.clone());
+            }
+        }
+    }
+
+    // Add looks.
+    let look_count = ui_data.lock().looks.len();
+    for i in 0..look_count {
+        if let Some(look) = ui_data.lock().looks.get(i).map(|l| l.clone()) {
+            // Add text version to header comment.
+            config
+                .header_comment
+                .push_str(&look.to_string(Some(&export_path)));
+            config.header_comment.push_str("----\n");
+
+            // Actual export.
+            let mut transforms = Vec::new();
+            for (t_i, transform) in look.transforms.iter().enumerate() {
+                match *transform {
+                    LookTransformSpec::CDL {
+                        slope,
+                        offset,
+                        power,
+                        saturation,
+                    } => {
+                        transforms.push(Transform::CDLTransform {
+                            slope: slope,
+                            offset: offset,
+                            power: power,
+                            saturation: saturation,
+                            direction_inverse: false,
+                        });
+                    }
+                    LookTransformSpec::GradingPrimary {
+                        style,
+                        contrast,
+                        saturation,
+                        pivot,
+                    } => {
+                        transforms.push(Transform::GradingPrimaryTransform {
+                            style: style,
+                            contrast: contrast,
+                            saturation: saturation,
+                            pivot_contrast: pivot,
+                            direction_inverse: false,
+                        });
+                    }
+                    LookTransformSpec::File {
+                        ref lut_1d,
+                        ref lut_3d,
+                        ref path,
+                    } => {
+                        if let Some(src) = source_luts.place(&mut config, path)? {
+                            transforms.push(Transform::FileTransform {
+                                src: src,
+                                interpolation: if lut_3d.is_some() {
+                                    Interpolation::Tetrahedral
+                                } else {
+                                    Interpolation::Linear
+                                },
+                                direction_inverse: false,
+                            });
+                            continue;
+                        }
+
+                        let file_name = path
+                            .file_name()
+                            .map(|f| f.to_str())
+                            .flatten()
+                            .unwrap_or("lut.cube");
+
+                        if let Some(lut_1d) = lut_1d {
+                            let lut_path = if lut_3d.is_some() {
+                                output_dir
+                                    .join(format!("omkr_look{}_{}__shaper__{}", i, t_i, file_name))
+                            } else {
+                                output_dir.join(format!("omkr_look{}_{}__{}", i, t_i, file_name))
+                            };
+                            config
+                                .output_files
+                                .entry(lut_path.clone())
+                                .or_insert(OutputFile::Lut1D(lut_1d.clone()));
+                            transforms.push(Transform::FileTransform {
+                                src: lut_path.file_name().unwrap().to_string_lossy().into(),
+                                interpolation: Interpolation::Linear,
+                                direction_inverse: false,
+                            });
+                        }
+                        if let Some(lut_3d) = lut_3d {
+                            let lut_path =
+                                output_dir.join(format!("omkr_look{}_{}__{}", i, t_i, file_name));
+                            config
+                                .output_files
+                                .entry(lut_path.clone())
+                                .or_insert(OutputFile::Lut3D(lut_3d.clone()));
+                            transforms.push(Transform::FileTransform {
+                                src: lut_path.file_name().unwrap().to_string_lossy().into(),
+                                interpolation: Interpolation::Tetrahedral,
+                                direction_inverse: false,
+                            });
+                        }
+                    }
+                }
+            }
+
+            config.looks.push(Look {
+                name: escape_name(&look.name),
+                description: String::new(),
+                process_space: escape_name(&look.process_space),
+                transform: transforms,
+                inverse_transform: Vec::new(),
+            });
+        }
+    }
+
+    // Add named transforms.
+    let named_transform_count = ui_data.lock().named_transforms.len();
+    for i in 0..named_transform_count {
+        if let Some(named_transform) = ui_data.lock().named_transforms.get(i).map(|n| n.clone()) {
+            // Add text version to header comment.
+            config
+                .header_comment
+                .push_str(&named_transform.to_string(Some(&export_path)));
+            config.header_comment.push_str("----\n");
+
+            // Actual export.
+            let mut transforms = Vec::new();
+            match named_transform.source {
+                NamedTransformSource::Builtin(ref name) => {
+                    transforms.push(Transform::BuiltinTransform {
+                        name: name.trim().into(),
+                        direction_inverse: false,
+                    });
+                }
+                NamedTransformSource::File {
+                    ref lut_1d,
+                    ref lut_3d,
+                    ref path,
+                } => {
+                    if let Some(src) = source_luts.place(&mut config, path)? {
+                        transforms.push(Transform::FileTransform {
+                            src: src,
+                            interpolation: if lut_3d.is_some() {
+                                Interpolation::Tetrahedral
+                            } else {
+                                Interpolation::Linear
+                            },
+                            direction_inverse: false,
+                        });
+                    } else {
+                        let file_name = path
+                            .file_name()
+                            .map(|f| f.to_str())
+                            .flatten()
+                            .unwrap_or("lut.cube");
+
+                        if let Some(lut_1d) = lut_1d {
+                            let lut_path = if lut_3d.is_some() {
+                                output_dir.join(format!("omkr_nt{}__shaper__{}", i, file_name))
+                            } else {
+                                output_dir.join(format!("omkr_nt{}__{}", i, file_name))
+                            };
+                            config
+                                .output_files
+                                .entry(lut_path.clone())
+                                .or_insert(OutputFile::Lut1D(lut_1d.clone()));
+                            transforms.push(Transform::FileTransform {
+                                src: lut_path.file_name().unwrap().to_string_lossy().into(),
+                                interpolation: Interpolation::Linear,
+                                direction_inverse: false,
+                            });
+                        }
+                        if let Some(lut_3d) = lut_3d {
+                            let lut_path = output_dir.join(format!("omkr_nt{}__{}", i, file_name));
+                            config
+                                .output_files
+                                .entry(lut_path.clone())
+                                .or_insert(OutputFile::Lut3D(lut_3d.clone()));
+                            transforms.push(Transform::FileTransform {
+                                src: lut_path.file_name().unwrap().to_string_lossy().into(),
+                                interpolation: Interpolation::Tetrahedral,
+                                direction_inverse: false,
+                            });
+                        }
+                    }
+                }
+            }
+
+            // When the source defines the inverse direction, we let
+            // OCIO derive the forward direction from it.
+            let (transform, inverse_transform) = if named_transform.source_is_inverse {
+                (Vec::new(), transforms)
+            } else {
+                (transforms, Vec::new())
+            };
+            config.named_transforms.push(NamedTransform {
+                name: escape_name(&named_transform.name),
+                family: DEFAULT_FAMILY.into(),
+                transform: transform,
+                inverse_transform: inverse_transform,
+                ..NamedTransform::default()
+            });
+        }
+    }
+
+    // Add displays.
+    let display_count = ui_data.lock().displays.len();
+    for i in 0..display_count {
+        if let Some(display) = ui_data.lock().displays.get(i).map(|d| d.clone()) {
+            // Add text version to header comment.
+            config.header_comment.push_str(&display.to_string());
+            config.header_comment.push_str("----\n");
+
+            // Actual export.  Displays that share a name with one in
+            // the base config have their views merged into it.
+            let display_name = escape_name(&display.name);
+            let display_i = match config.displays.iter().position(|d| d.name == display_name) {
+                Some(display_i) => display_i,
+                None => {
+                    config.displays.push(Display {
+                        name: display_name.clone(),
+                        views: Vec::new(),
+                    });
+                    config.displays.len() - 1
+                }
+            };
+            for view in display.views.iter() {
+                let view_name = escape_name(&view.name);
+                let color_space = escape_name(&view.color_space);
+                let views = &mut config.displays[display_i].views;
+                if let Some(existing) = views.iter_mut().find(|(name, _)| *name == view_name) {
+                    existing.1 = color_space;
+                } else {
+                    views.push((view_name.clone(), color_space));
+                }
+                set_active(
+                    &mut config.active_views,
+                    &all_view_names(&config.displays),
+                    &view_name,
+                    view.active,
+                );
+            }
+            set_active(
+                &mut config.active_displays,
+                &all_display_names(&config.displays),
+                &display_name,
+                display.active,
+            );
+        }
+    }
+
+    // Role overrides.
+    let roles = ui_data.lock().roles.clone();
+    if !roles.is_empty() {
+        config.header_comment.push_str(&roles_to_string(&roles));
+        config.header_comment.push_str("----\n");
+    }
+    for (role, space) in roles.iter() {
+        let role = role.trim();
+        let space = escape_name(space);
+        match role {
+            "" => {}
+            "aces_interchange" => config.roles.aces_interchange = Some(space),
+            "cie_xyz_d65_interchange" => config.roles.cie_xyz_d65_interchange = Some(space),
+            "default" => config.roles.default = Some(space),
+            "data" => config.roles.data = Some(space),
+            _ => {
+                config.roles.other.insert(role.into(), space);
+            }
+        }
+    }
+
+    // File rules.
+    let file_rules = ui_data.lock().file_rules.clone();
+    if !file_rules.is_empty() {
+        config
+            .header_comment
+            .push_str(&file_rules_to_string(&file_rules));
+        config.header_comment.push_str("----\n");
+    }
+    for rule in file_rules.iter() {
+        let wildcard_if_empty = |text: &str| {
+            if text.trim().is_empty() {
+                String::from("*")
+            } else {
+                text.trim().into()
+            }
+        };
+        config.add_file_rule(FileRule {
+            name: escape_name(&rule.name),
+            colorspace: escape_name(&rule.color_space),
+            kind: FileRuleKind::Pattern {
+                pattern: wildcard_if_empty(&rule.pattern),
+                extension: wildcard_if_empty(&rule.extension),
+            },
+        });
+    }
+
+    // Anything that was added to the loaded config by hand.
+    ui_data.lock().hand_edits.apply(&mut config);
+
+    // Check for validation errors.
+    use ocio_gen::config::ValidationError::*;
+    match config.validate() {
+        Err(DuplicateColorSpace(name)) => {
+            return Err(format!(
+                "There is a duplicate color space in the config: \"{}\" \
+                                \nNote: this may be a conflict with the built-ins of the \
+                                config template, rather that two visible duplicates in \
+                                your own colorspace list.",
+                name
+            ));
+        }
+        Err(DuplicateDisplay(name)) => {
+            return Err(format!(
+                "There is a duplicate display in the config: \"{}\" \
+                                \nNote: this may be a conflict with the built-ins of the \
+                                config template, rather that two visible duplicates in \
+                                your own colorspace list.",
+                name
+            ));
+        }
+        Err(DuplicateRole(name)) => {
+            return Err(format!(
+                "There is a duplicate role in the config: \"{}\"",
+                name
+            ));
+        }
+        Err(DuplicateLook(name)) => {
+            return Err(format!(
+                "There is a duplicate look in the config: \"{}\"",
+                name
+            ));
+        }
+        Err(DuplicateRule(name)) => {
+            return Err(format!(
+                "There is a duplicate file or viewing rule in the config: \"{}\"",
+                name
+            ));
+        }
+        Err(FileRulesWithoutDefault) => {
+            return Err("The config's file rules don't end with a \"Default\" rule.".into());
+        }
+        Err(UndeclaredContextVariable(name)) => {
+            return Err(format!(
+                "The config uses context variable \"{}\" without declaring it in its environment.",
+                name
+            ));
+        }
+        Err(ReferenceToAbsentColorSpace(name)) => {
+            return Err(format!(
+                "There is a reference to a non-existent colorspace in the config: \"{}\"",
+                name
+            ));
+        }
+        Ok(()) => {}
+    }
+
+    Ok(config)
+}
+
+/// Places the source LUT files of the user's color spaces, looks, etc.
+/// in the config, for the `LutPaths` that use the source files as-is.
+struct SourceLuts<'a> {
+    lut_paths: &'a LutPaths,
+    export_path: &'a str,
+    placed: HashMap<PathBuf, PathBuf>, // Output path -> source path.
+}
+
+impl<'a> SourceLuts<'a> {
+    fn new(lut_paths: &'a LutPaths, export_path: &'a str) -> SourceLuts<'a> {
+        SourceLuts {
+            lut_paths: lut_paths,
+            export_path: export_path,
+            placed: HashMap::new(),
+        }
+    }
+
+    /// Adds the LUT file at `source_path` to the config, and returns the
+    /// `src` for file transforms to reference it by.  Returns `None` if
+    /// the LUT should instead be written from its loaded data.
+    fn place(
+        &mut self,
+        config: &mut ocio_gen::config::OCIOConfig,
+        source_path: &Path,
+    ) -> Result<Option<String>, String> {
+        use ocio_gen::config::OutputFile;
+
+        let (path, external) = match *self.lut_paths {
+            LutPaths::Copy => return Ok(None),
+            LutPaths::InPlace => (relative_path(source_path, Some(self.export_path)), true),
+            LutPaths::Directory(ref dir) => {
+                let file_name = source_path
+                    .file_name()
+                    .ok_or_else(|| format!("\"{}\" isn't a file.", source_path.display()))?;
+                (Path::new(dir.trim()).join(file_name), false)
+            }
+        };
+
+        // Different files can't share a name in the same directory.
+        let source = source_path
+            .canonicalize()
+            .unwrap_or_else(|_| source_path.into());
+        match self.placed.get(&path) {
+            Some(other) if *other != source => {
+                return Err(format!(
+                    "The LUT files \"{}\" and \"{}\" would both be exported as \"{}\".  Please rename one of them.",
+                    other.display(),
+                    source.display(),
+                    path.display(),
+                ));
+            }
+            Some(_) => {}
+            None => {
+                let data = std::fs::read(source_path).map_err(|e| {
+                    format!(
+                        "Unable to read LUT file \"{}\": {}",
+                        source_path.display(),
+                        e
+                    )
+                })?;
+                if external {
+                    config
+                        .external_files
+                        .insert(path.clone(), OutputFile::Raw(data));
+                } else {
+                    config
+                        .output_files
+                        .insert(path.clone(), OutputFile::Raw(data));
+                }
+                self.placed.insert(path.clone(), source);
+            }
+        }
+
+        // OCIO only looks for files in the search path, so it needs the
+        // config's own directory or the LUT directory in it.
+        let src = if external {
+            config.add_search_path(".");
+            path.to_string_lossy().replace('\\', "/")
+        } else {
+            let dir = path.parent().unwrap_or(Path::new("."));
+            config.add_search_path(&dir.to_string_lossy().replace('\\', "/"));
+            path.file_name().unwrap().to_string_lossy().into()
+        };
+
+        Ok(Some(src))
+    }
+}
+
+/// Generates the config for export, along with the directory to export
+/// it to.  Returns `None` (after logging why) if it can't be exported.
+fn prepare_export(
+    status: &Shared<job_queue::JobStatus>,
+    ui_data: &Shared<UIData>,
+) -> Option<(ocio_gen::config::OCIOConfig, String)> {
+    status
+        .lock_mut()
+        .set_progress("Generating config".into(), 0.0);
+
+    let export_path = ui_data.lock().export_path.clone();
+    if export_path.is_empty() {
+        status
+            .lock_mut()
+            .log_error("Failed to export: no config directory selected yet.".into());
+        return None;
+    }
+    // We ignore the result here because we'll encounter the same one later
+    // anyway, where it is handled properly.
+    let _ = lib::job_helpers::ensure_dir_exists(&export_path);
+
+    let base_preset = ui_data.lock().base_preset;
+    let imported_config_path = ui_data.lock().imported_config_path.clone();
+
+    // Exporting on top of an imported config would clobber it, and
+    // make it unusable as a base afterwards.
+    if base_preset == BasePreset::Imported {
+        let same_dir = match (
+            Path::new(&imported_config_path)
+                .parent()
+                .map(|p| p.canonicalize()),
+            Path::new(&export_path).canonicalize(),
+        ) {
+            (Some(Ok(a)), Ok(b)) => a == b,
+            _ => false,
+        };
+        if same_dir {
+            status.lock_mut().log_error("Failed to export: the config directory is the same as the imported base config's.  Please choose a different directory.".into());
+            return None;
+        }
+    }
+
+    let mut config = match generate_config(ui_data, &export_path) {
+        Ok(config) => config,
+        Err(e) => {
+            status
+                .lock_mut()
+                .log_error(format!("Failed to export: {}", e));
+            return None;
+        }
+    };
+
+    if ui_data.lock().v1_compatible {
+        status
+            .lock_mut()
+            .set_progress("Baking LUTs for OCIO v1".into(), 0.0);
+        for warning in ocio_gen::v1_compat::downgrade(&mut config) {
+            status.lock_mut().log_warning(warning);
+        }
+    }
+
+    // Color spaces that use the same LUT (e.g. several cameras with
+    // the same transfer function) needn't each get their own copy.
+    let merged_count = config.deduplicate_output_files();
+    if merged_count > 0 {
+        status.lock_mut().log_note(format!(
+            "Merged {} LUT file(s) that were identical to others.",
+            merged_count
+        ));
+    }
+
+    Some((config, export_path))
+}
+
+/// Writes a generated config to disk, and tells the user how to use it.
+///
+/// Returns whether it was successful.
+fn write_config(
+    status: &Shared<job_queue::JobStatus>,
+    config: &ocio_gen::config::OCIOConfig,
+    export_path: &str,
+) -> bool {
+    // Write it out to disk.
+    status
+        .lock_mut()
+        .set_progress("Writing config to disk".into(), 0.0);
+    if let Err(e) = config.write_to_directory(export_path) {
+        status
+            .lock_mut()
+            .log_error(format!("Failed to export: {}", e));
+        return false;
+    }
+
+    // Print help message about how to use the configuration.
+    let config_path = {
+        let tmp: PathBuf = export_path.into();
+        tmp.join("config.ocio")
+    };
+    if cfg!(target_family = "windows") {
+        status.lock_mut().log_note(format!("Export successful!  To use this configuration in your OCIO-enabled applications, create the following Windows environment variable:\n    Name:   OCIO\n    Value:   {}\nIf you're unsure how to create environment variables on Windows, step-by-step instructions can be found on the internet.", config_path.display()));
+    } else {
+        status.lock_mut().log_note(format!("Export successful!  To use this configuration in your OCIO-enabled applications, set the following environment variable:\n    OCIO={}", config_path.display()));
+    }
+
+    true
+}
+
+/// Loads an OCIO Maker project into `ui_data`.  `config_file_path` can
+/// either be a config exported by OCIO Maker, or a plain project spec
+/// file (see `read_project_chunks()`).
+///
+/// Returns whether it was successful.
+fn load_project(
+    status: &Shared<job_queue::JobStatus>,
+    ui_data: &Shared<UIData>,
+    config_file_path: &Path,
+) -> bool {
+    status.lock_mut().set_progress("Loading config".into(), 0.0);
+
+    let mut color_spaces = Vec::new();
+    let mut looks = Vec::new();
+    let mut displays = Vec::new();
+    let mut named_transforms = Vec::new();
+    let mut roles = Vec::new();
+    let mut file_rules = Vec::new();
+
+    // Parse.
+    let chunks = match read_project_chunks(config_file_path) {
+        Ok(chunks) => chunks,
+        Err(e) => {
+            status.lock_mut().log_error(e);
+            return false;
+        }
+    };
+    for chunk in chunks.iter() {
+        // Base config.
+        if chunk.starts_with("base:") {
+            if let Some((base, tail)) = chunk.split_once("\n") {
+                if let Some(base_preset) = BasePreset::from_str(base.split_once(":").unwrap().1) {
+                    ui_data.lock_mut().base_preset = base_preset;
+                    if let (color_space, Ok(_)) = ColorSpaceSpec::from_str::<&str>(tail, None) {
+                        ui_data.lock_mut().working_color_space = color_space;
+                    }
+                    ui_data.lock_mut().tone_map_params = tail
+                        .lines()
+                        .find_map(|l| l.strip_prefix("tone_map:"))
+                        .and_then(tone_map_params_from_str)
+                        .unwrap_or_default();
+                    ui_data.lock_mut().lut_paths = tail
+                        .lines()
+                        .find_map(|l| l.strip_prefix("lut_paths:"))
+                        .and_then(LutPaths::from_str)
+                        .unwrap_or(LutPaths::Copy);
+                    if let Some(path) = tail.lines().find_map(|l| l.strip_prefix("imported_path:"))
+                    {
+                        let path = match config_file_path.parent() {
+                            Some(dir) => dir.join(path.trim()),
+                            None => path.trim().into(),
+                        };
+                        ui_data.lock_mut().imported_config_path = path.to_string_lossy().into();
+                    }
+                } else {
+                    status.lock_mut().log_error(
+                        "Invalid configuration base.  Continuing to load with default.".into(),
+                    );
+                }
+            }
+        }
+        // Color space.
+        else if chunk.starts_with("color_space:") {
+            let (color_space, result) = ColorSpaceSpec::from_str(chunk, config_file_path.parent());
+            match result {
+                Ok(_) => {}
+                Err(ConfigLoadErr::FileUnloadable(s)) => {
+                    status.lock_mut().log_error(format!(
+                        "Unable to load LUT file: \"{}\".  Colorspace \"{}\" is incomplete.",
+                        s, color_space.name
+                    ));
+                }
+            };
+            color_spaces.push(color_space);
+        }
+        // Look.
+        else if chunk.starts_with("look:") {
+            let (look, result) = LookSpec::from_str(chunk, config_file_path.parent());
+            match result {
+                Ok(_) => {}
+                Err(ConfigLoadErr::FileUnloadable(s)) => {
+                    status.lock_mut().log_error(format!(
+                        "Unable to load LUT file: \"{}\".  Look \"{}\" is incomplete.",
+                        s, look.name
+                    ));
+                }
+            };
+            looks.push(look);
+        }
+        // Display.
+        else if chunk.starts_with("display:") {
+            displays.push(DisplaySpec::from_str(chunk));
+        }
+        // Named transform.
+        else if chunk.starts_with("named_transform:") {
+            let (named_transform, result) =
+                NamedTransformSpec::from_str(chunk, config_file_path.parent());
+            match result {
+                Ok(_) => {}
+                Err(ConfigLoadErr::FileUnloadable(s)) => {
+                    status.lock_mut().log_error(format!(
+                        "Unable to load LUT file: \"{}\".  Named transform \"{}\" is incomplete.",
+                        s, named_transform.name
+                    ));
+                }
+            };
+            named_transforms.push(named_transform);
+        }
+        // Role overrides.
+        else if chunk.starts_with("roles:") {
+            roles = roles_from_str(chunk);
+        }
+        // File rules.
+        else if chunk.starts_with("file_rules:") {
+            file_rules = file_rules_from_str(chunk);
+        }
+    }
+
+    // Set in-memory config to the same as the parsed one.
+    {
+        let mut ui_data = ui_data.lock_mut();
+        ui_data.color_spaces = color_spaces;
+        ui_data.selected_space_index = 0;
+        ui_data.looks = looks;
+        ui_data.selected_look_index = 0;
+        ui_data.displays = displays;
+        ui_data.selected_display_index = 0;
+        ui_data.named_transforms = named_transforms;
+        ui_data.selected_named_transform_index = 0;
+        ui_data.roles = roles;
+        ui_data.file_rules = file_rules;
+        ui_data.hand_edits = ocio_gen::hand_edits::HandEdits::default();
+        if let Some(parent) = config_file_path.parent().map(|p| p.to_string_lossy()) {
+            ui_data.export_path = parent.into();
+        }
+    }
+
+    // Find anything that was added to the config by hand since it was
+    // exported, so it's kept when exporting it again.
+    if config_file_path.extension().map(|e| e == "ocio") == Some(true) {
+        status
+            .lock_mut()
+            .set_progress("Checking for hand edits".into(), 0.5);
+        let export_path = ui_data.lock().export_path.clone();
+        match (
+            ocio_gen::import::read_config(config_file_path),
+            generate_config(ui_data, &export_path),
+        ) {
+            (Ok((edited, _)), Ok(generated)) => {
+                let hand_edits = ocio_gen::hand_edits::HandEdits::find(&edited, &generated);
+                if !hand_edits.is_empty() {
+                    status.lock_mut().log_note(format!(
+                        "The config has hand-added content that will be kept when exporting it: {} color space(s), {} look(s), {} named transform(s), {} view(s), and {} role(s).",
+                        hand_edits.colorspaces.len(),
+                        hand_edits.looks.len(),
+                        hand_edits.named_transforms.len(),
+                        hand_edits.views.len(),
+                        hand_edits.roles.len(),
+                    ));
+                }
+                ui_data.lock_mut().hand_edits = hand_edits;
+            }
+            _ => {
+                status.lock_mut().log_warning("Couldn't check the config for hand edits, so anything added to it by hand will be lost when exporting it.".into());
+            }
+        }
+    }
+
+    true
+}
+
+/// Reads the chunks of an OCIO Maker project.
+///
+/// Configs exported by OCIO Maker store the project in their header
+/// comment.  Plain project spec files use the same format without the
+/// comment markers, so that they can be written by hand (or by scripts)
+/// and exported with `--export`.  In those, lines starting with `#` are
+/// comments.  In both, chunks are separated by `----` lines, and paths
+/// are relative to the file.
+fn read_project_chunks(path: &Path) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path).map_err(|_| {
+        format!(
+            "Unable to access config file: \"{}\"",
+            path.to_string_lossy()
+        )
+    })?;
+    let commented = text.starts_with("# Generated by ETF OCIO Maker");
+    if !commented && path.extension().map(|e| e == "ocio") == Some(true) {
+        return Err(
+            "Failed to load configuration: is not an ETF OCIO Maker-generated config.".into(),
+        );
+    }
+
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let line = if commented {
+            match line.strip_prefix("#") {
+                Some(line) => line.trim(),
+                None => break, // End of the header comment.
+            }
+        } else if line.starts_with("#") {
+            continue;
+        } else {
+            line
+        };
+
+        if line == "----" {
+            chunks.push(std::mem::take(&mut chunk));
+        } else {
+            chunk.push_str(line);
+            chunk.push_str("\n");
+        }
+    }
+    if !chunk.trim().is_empty() {
+        chunks.push(chunk);
+    }
+
+    Ok(chunks)
+}
+
+/// Round-trip errors above this are reported as failures by the
+/// export verification.
+const MAX_ROUND_TRIP_ERROR: f64 = 1.0e-3;
+
+/// Reloads an exported config along with its LUT files, and checks
+/// that the given color spaces round-trip accurately.
+fn verify_export(status: &Shared<job_queue::JobStatus>, export_path: &str, colorspaces: &[String]) {
+    status
+        .lock_mut()
+        .set_progress("Verifying exported config".into(), 0.0);
+
+    let report = match ocio_gen::verify::verify_directory(export_path, colorspaces) {
+        Ok(report) => report,
+        Err(e) => {
+            status.lock_mut().log_error(format!(
+                "Verification failed: couldn't reload the exported config: {}",
+                e
+            ));
+            return;
+        }
+    };
+
+    for path in report.unreadable_files.iter() {
+        status.lock_mut().log_error(format!(
+            "Verification failed: the LUT file \"{}\" couldn't be read back.",
+            path.to_string_lossy()
+        ));
+    }
+
+    let mut failed = !report.unreadable_files.is_empty();
+    let mut lines = String::new();
+    for round_trip in report.round_trips.iter() {
+        let inaccurate = round_trip.max_error > MAX_ROUND_TRIP_ERROR;
+        failed |= inaccurate;
+        lines.push_str(&format!(
+            "\n    {}: max error {:.2e}{}",
+            round_trip.colorspace,
+            round_trip.max_error,
+            if round_trip.neutral_only {
+                " (neutral axis only, due to gamut clipping)"
+            } else {
+                ""
+            }
+        ));
+        if inaccurate {
+            let s = round_trip.worst_sample;
+            lines.push_str(&format!(" at [{}, {}, {}]", s[0], s[1], s[2]));
         }
     }
+    for (name, reason) in report.skipped.iter() {
+        lines.push_str(&format!("\n    {}: not checked ({})", name, reason));
+    }
+
+    if failed {
+        status.lock_mut().log_error(format!("Verification found problems with the exported config.  Color spaces that don't round-trip accurately usually have a broken or inverted LUT.  Results:{}", lines));
+    } else if !lines.is_empty() {
+        status
+            .lock_mut()
+            .log_note(format!("Verification passed.  Results:{}", lines));
+    }
 }
 
 /// The stuff the UI code needs access to for drawing and update.
@@ -92,12 +1277,215 @@ impl AppMain {
 /// amounts of time.
 pub struct UIData {
     selected_tab: Tabs,
+    gamut_graph: gamut_graph::GraphSettings,
+    transfer_graph: transfer_function_graph::GraphSettings,
 
     base_preset: BasePreset,
+    imported_config_path: String, // Only used with `BasePreset::Imported`.
     working_color_space: ColorSpaceSpec, // Main/reference/rendering/working color space.
-    color_spaces: Vec<ColorSpaceSpec>,
+    tone_map_params: ocio_gen::ToneMapParams, // Only used by presets with adjustable view transforms.
+    color_spaces: Vec<ColorSpaceSpec>,        // In the order they're exported in.
     selected_space_index: usize,
+    group_color_spaces: bool, // Whether to keep color spaces grouped by family.
+    looks: Vec<LookSpec>,
+    selected_look_index: usize,
+    displays: Vec<DisplaySpec>,
+    selected_display_index: usize,
+    named_transforms: Vec<NamedTransformSpec>,
+    selected_named_transform_index: usize,
+    roles: Vec<(String, String)>, // (role, color_space), overriding the base config's roles.
+    file_rules: Vec<FileRuleSpec>, // Tried after the base config's file rules.
     export_path: String,
+
+    // Names and aliases of the base config's color spaces, along with the
+    // base preset and imported config path they were fetched for.  Used
+    // for validation, and kept up to date by `AppMain::check_base_preset()`.
+    base_space_names: Option<(BasePreset, String, Vec<String>)>,
+
+    // A generated config waiting for the user to review its changes
+    // before it overwrites the one already in the export directory.
+    pending_export: Option<export_review::PendingExport>,
+
+    // Whether to check the exported config's color spaces by reloading
+    // it and round-tripping samples through them.
+    verify_export: bool,
+
+    // Whether to export an OCIO v1 config, for legacy applications.
+    v1_compatible: bool,
+
+    // Whether to add theatrical P3 displays to bases that lack them.
+    p3_displays: bool,
+
+    // Where the LUT files go in exported configs.
+    lut_paths: LutPaths,
+
+    // Content that was added by hand to the loaded config.
+    hand_edits: ocio_gen::hand_edits::HandEdits,
+
+    // A generated config that the user is picking a display/view chain
+    // from to bake into a LUT.
+    bake_lut: Option<bake_lut::BakeState>,
+}
+
+impl UIData {
+    fn new() -> UIData {
+        UIData {
+            selected_tab: Tabs::BaseConfig,
+            gamut_graph: gamut_graph::GraphSettings::default(),
+            transfer_graph: transfer_function_graph::GraphSettings::default(),
+
+            base_preset: BasePreset::Blender4_0,
+            imported_config_path: String::new(),
+            working_color_space: ColorSpaceSpec {
+                // Only the `chroma_space` and `custom_chroma` fields are
+                // actually used to define the working color space.
+                name: "".into(),
+                transfer_lut: None,
+                transfer_lut_3d: None,
+                chroma_space: ChromaSpace::Rec709,
+                custom_chroma: colorbox::chroma::REC709,
+                input_matrix: None,
+                gamut_mapping: GamutMapping::Clip,
+                white_balance: None,
+                include_as_display: false,
+                inactive: false,
+                family: String::new(),
+                aliases: String::new(),
+                encoding: None,
+                description: String::new(),
+            },
+            tone_map_params: ocio_gen::ToneMapParams::default(),
+            color_spaces: Vec::new(),
+            selected_space_index: 0,
+            group_color_spaces: false,
+            looks: Vec::new(),
+            selected_look_index: 0,
+            displays: Vec::new(),
+            selected_display_index: 0,
+            named_transforms: Vec::new(),
+            selected_named_transform_index: 0,
+            roles: Vec::new(),
+            file_rules: Vec::new(),
+            export_path: String::new(),
+            base_space_names: None,
+            pending_export: None,
+            verify_export: false,
+            v1_compatible: false,
+            p3_displays: false,
+            lut_paths: LutPaths::Copy,
+            hand_edits: ocio_gen::hand_edits::HandEdits::default(),
+            bake_lut: None,
+        }
+    }
+
+    /// The names of the user's color spaces, as they appear in the
+    /// exported config.
+    fn color_space_names(&self) -> Vec<String> {
+        self.color_spaces
+            .iter()
+            .map(|s| escape_name(&s.name))
+            .filter(|n| !n.is_empty())
+            .collect()
+    }
+
+    fn edit_state(&self) -> EditState {
+        EditState {
+            base_preset: self.base_preset,
+            imported_config_path: self.imported_config_path.clone(),
+            working_color_space: self.working_color_space.clone(),
+            tone_map_params: self.tone_map_params,
+            color_spaces: self.color_spaces.clone(),
+            selected_space_index: self.selected_space_index,
+            looks: self.looks.clone(),
+            selected_look_index: self.selected_look_index,
+            displays: self.displays.clone(),
+            selected_display_index: self.selected_display_index,
+            named_transforms: self.named_transforms.clone(),
+            selected_named_transform_index: self.selected_named_transform_index,
+            roles: self.roles.clone(),
+            file_rules: self.file_rules.clone(),
+        }
+    }
+
+    /// Whether the parts of the UI data covered by undo/redo are the
+    /// same as `state`, compared the same way as `EditState`'s
+    /// `PartialEq`.
+    ///
+    /// This avoids taking a (deep-copying) snapshot just to find that
+    /// nothing changed.
+    fn matches_edit_state(&self, state: &EditState) -> bool {
+        self.base_preset == state.base_preset
+            && self.imported_config_path == state.imported_config_path
+            && self.working_color_space == state.working_color_space
+            && self.tone_map_params == state.tone_map_params
+            && self.color_spaces == state.color_spaces
+            && self.looks == state.looks
+            && self.displays == state.displays
+            && self.named_transforms == state.named_transforms
+            && self.roles == state.roles
+            && self.file_rules == state.file_rules
+    }
+
+    fn restore_edit_state(&mut self, state: EditState) {
+        self.base_preset = state.base_preset;
+        self.imported_config_path = state.imported_config_path;
+        self.working_color_space = state.working_color_space;
+        self.tone_map_params = state.tone_map_params;
+        self.color_spaces = state.color_spaces;
+        self.selected_space_index = state
+            .selected_space_index
+            .min(self.color_spaces.len().saturating_sub(1));
+        self.looks = state.looks;
+        self.selected_look_index = state
+            .selected_look_index
+            .min(self.looks.len().saturating_sub(1));
+        self.displays = state.displays;
+        self.selected_display_index = state
+            .selected_display_index
+            .min(self.displays.len().saturating_sub(1));
+        self.named_transforms = state.named_transforms;
+        self.selected_named_transform_index = state
+            .selected_named_transform_index
+            .min(self.named_transforms.len().saturating_sub(1));
+        self.roles = state.roles;
+        self.file_rules = state.file_rules;
+    }
+}
+
+/// The parts of `UIData` covered by undo/redo.
+#[derive(Debug, Clone)]
+struct EditState {
+    base_preset: BasePreset,
+    imported_config_path: String,
+    working_color_space: ColorSpaceSpec,
+    tone_map_params: ocio_gen::ToneMapParams,
+    color_spaces: Vec<ColorSpaceSpec>,
+    selected_space_index: usize,
+    looks: Vec<LookSpec>,
+    selected_look_index: usize,
+    displays: Vec<DisplaySpec>,
+    selected_display_index: usize,
+    named_transforms: Vec<NamedTransformSpec>,
+    selected_named_transform_index: usize,
+    roles: Vec<(String, String)>,
+    file_rules: Vec<FileRuleSpec>,
+}
+
+impl PartialEq for EditState {
+    fn eq(&self, other: &Self) -> bool {
+        // The selected indices are deliberately left out, so that merely
+        // selecting a different item in a list isn't an undo step.
+        self.base_preset == other.base_preset
+            && self.imported_config_path == other.imported_config_path
+            && self.working_color_space == other.working_color_space
+            && self.tone_map_params == other.tone_map_params
+            && self.color_spaces == other.color_spaces
+            && self.looks == other.looks
+            && self.displays == other.displays
+            && self.named_transforms == other.named_transforms
+            && self.roles == other.roles
+            && self.file_rules == other.file_rules
+    }
 }
 
 impl eframe::App for AppMain {
@@ -106,78 +1494,511 @@ impl eframe::App for AppMain {
         // Don't need to do anything.
     }
 
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.preferences.last_directory = self.last_opened_directory.clone();
+        if let Err(e) = self.preferences.save(APP_NAME) {
+            self.job_queue
+                .log_error(format!("Unable to save preferences: {}", e));
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.preferences.update_window(ctx);
         let job_count = self.job_queue.job_count();
         let mut working_dir = self
             .last_opened_directory
             .clone()
             .unwrap_or_else(|| "".into());
+        self.lut_drop_target = None; // Set by the color space list.
 
         //----------------
         // GUI.
 
-        // Menu bar.
-        menu::menu_bar(ctx, self, &mut working_dir, job_count);
+        // Menu bar.
+        menu::menu_bar(ctx, self, &mut working_dir, job_count);
+
+        // Top bar, with tabs, config export, etc.
+        top_bar::top_bar(ctx, self);
+
+        // Status bar and log (footer).
+        egui_custom::status_bar(ctx, &self.job_queue);
+
+        // Validation problems (right side).
+        validation_panel::panel(ctx, self);
+
+        // Review of changes before overwriting a previous export.
+        export_review::window(ctx, self);
+
+        // Baking a display/view chain to a LUT.
+        bake_lut::window(ctx, self, &mut working_dir);
+
+        // Previewing a test image through the config.
+        soft_proof::window(ctx, self, &mut working_dir, job_count);
+
+        // Importing a folder of LUTs as color spaces.
+        lut_folder_import::window(ctx, self, job_count);
+
+        // Main UI.
+        let selected_tab = self.ui_data.lock().selected_tab; // Work around borrow checker.
+        match selected_tab {
+            Tabs::BaseConfig => base_config::ui(ctx, self),
+            Tabs::InputTransforms => input_transforms::ui(ctx, self, &mut working_dir, job_count),
+            Tabs::Looks => looks::ui(ctx, self, &mut working_dir, job_count),
+            Tabs::Displays => displays::ui(ctx, self, job_count),
+            Tabs::NamedTransforms => named_transforms::ui(ctx, self, &mut working_dir, job_count),
+            Tabs::Roles => roles::ui(ctx, self),
+            Tabs::FileRules => file_rules::ui(ctx, self),
+        }
+
+        self.last_opened_directory = Some(working_dir);
+
+        //----------------
+        // Undo/redo.
+
+        if job_count == 0 {
+            if let Some(action) = lib::undo::consume_shortcuts(ctx) {
+                self.apply_history_action(action);
+            }
+        }
+        if job_count == 0 && !lib::undo::is_interacting(ctx) {
+            let ui_data = self.ui_data.lock();
+            let unchanged = self
+                .undo_stack
+                .current()
+                .map(|state| ui_data.matches_edit_state(state))
+                .unwrap_or(false);
+            if !unchanged {
+                self.undo_stack.update(ui_data.edit_state());
+            }
+        }
+
+        //----------------
+        // Processing.
+
+        // Collect dropped files.
+        let dropped_file_list = ctx.input(|input| {
+            let file_list: Vec<PathBuf> = input
+                .raw
+                .dropped_files
+                .iter()
+                .map(|dropped_file| dropped_file.path.clone().unwrap())
+                .collect();
+            file_list
+        });
+
+        // Camera space files from LUT Maker become input color spaces,
+        // and LUTs are attached to the color space they're dropped on
+        // (or the selected one).
+        if job_count == 0 {
+            for path in dropped_file_list.iter() {
+                if lib::camera_space::is_sidecar_path(path) {
+                    self.import_camera_space(path);
+                } else if is_transfer_lut_path(path) {
+                    if self.ui_data.lock().selected_tab == Tabs::InputTransforms {
+                        let space_i = self
+                            .lut_drop_target
+                            .unwrap_or(self.ui_data.lock().selected_space_index);
+                        self.attach_transfer_lut(space_i, path);
+                    } else {
+                        self.job_queue.log_warning(format!(
+                            "Ignored dropped LUT \"{}\".  LUTs can only be dropped onto the Input Transforms tab.",
+                            path.to_string_lossy()
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl AppMain {
+    fn apply_history_action(&mut self, action: HistoryAction) {
+        let state = match action {
+            HistoryAction::Undo => self.undo_stack.undo(),
+            HistoryAction::Redo => self.undo_stack.redo(),
+        };
+        if let Some(state) = state {
+            let base_changed = {
+                let mut ui_data = self.ui_data.lock_mut();
+                let old_base = (ui_data.base_preset, ui_data.imported_config_path.clone());
+                ui_data.restore_edit_state(state);
+                old_base != (ui_data.base_preset, ui_data.imported_config_path.clone())
+            };
+            if base_changed {
+                self.check_base_preset();
+            }
+        }
+    }
+
+    fn remove_color_space(&self, space_i: usize) {
+        let ui_data = &mut *self.ui_data.lock_mut();
+
+        if space_i < ui_data.color_spaces.len() {
+            ui_data.color_spaces.remove(space_i);
+        }
+
+        if ui_data.selected_space_index > space_i {
+            ui_data.selected_space_index = ui_data.selected_space_index.saturating_sub(1);
+        }
+
+        ui_data.selected_space_index = ui_data
+            .color_spaces
+            .len()
+            .saturating_sub(1)
+            .min(ui_data.selected_space_index);
+    }
+
+    fn add_input_color_space(&self) {
+        let ui_data = &mut *self.ui_data.lock_mut();
+        let name = {
+            let mut new_name = "New Color Space".into();
+            for i in 1..200 {
+                let name = format!("{} {}", new_name, i);
+                let mut taken = false;
+                for space in ui_data.color_spaces.iter() {
+                    taken |= space.name == name;
+                }
+                if !taken {
+                    new_name = name;
+                    break;
+                }
+            }
+            new_name
+        };
+        ui_data.color_spaces.push(ColorSpaceSpec::with_name(&name));
+        ui_data.selected_space_index = ui_data.color_spaces.len() - 1;
+    }
+
+    /// Moves the color space at `from` to position `to`, keeping the
+    /// same color space selected.
+    ///
+    /// When color spaces are grouped by family, the moved color space
+    /// takes the family of the one it's moved onto.
+    fn move_color_space(&self, from: usize, to: usize) {
+        let ui_data = &mut *self.ui_data.lock_mut();
+        let len = ui_data.color_spaces.len();
+        if from >= len || to >= len || from == to {
+            return;
+        }
+
+        let mut space = ui_data.color_spaces.remove(from);
+        if ui_data.group_color_spaces {
+            let target_i = if to > from { to - 1 } else { to };
+            space.family = ui_data.color_spaces[target_i].family.clone();
+        }
+        ui_data.color_spaces.insert(to, space);
+
+        let selected = ui_data.selected_space_index;
+        ui_data.selected_space_index = if selected == from {
+            to
+        } else if from < selected && selected <= to {
+            selected - 1
+        } else if to <= selected && selected < from {
+            selected + 1
+        } else {
+            selected
+        };
+    }
+
+    /// Reorders the color spaces so that each family's color spaces
+    /// are together, with the families in order of first appearance.
+    /// The order within each family is kept.
+    fn group_color_spaces_by_family(&self) {
+        let ui_data = &mut *self.ui_data.lock_mut();
+
+        let order: Vec<usize> = {
+            let spaces = &ui_data.color_spaces;
+            let group_key = |i: usize| {
+                spaces
+                    .iter()
+                    .position(|s| s.family_or_default() == spaces[i].family_or_default())
+                    .unwrap()
+            };
+            let mut order: Vec<usize> = (0..spaces.len()).collect();
+            order.sort_by_key(|&i| group_key(i)); // Stable.
+            order
+        };
+        if order.iter().enumerate().all(|(i, &j)| i == j) {
+            return;
+        }
+
+        let mut old_spaces: Vec<Option<ColorSpaceSpec>> =
+            ui_data.color_spaces.drain(..).map(Some).collect();
+        ui_data.color_spaces = order
+            .iter()
+            .map(|&i| old_spaces[i].take().unwrap())
+            .collect();
+        if let Some(selected) = order
+            .iter()
+            .position(|&i| i == ui_data.selected_space_index)
+        {
+            ui_data.selected_space_index = selected;
+        }
+    }
+
+    /// Adds a copy of the color space at `space_i` right after it, with
+    /// a new unique name, and selects it.
+    fn duplicate_color_space(&self, space_i: usize) {
+        let ui_data = &mut *self.ui_data.lock_mut();
+        let mut space = match ui_data.color_spaces.get(space_i) {
+            Some(space) => space.clone(),
+            None => return,
+        };
+        space.name = {
+            let base_name = format!("{} Copy", space.name.trim());
+            let mut new_name = base_name.clone();
+            for i in 2..200 {
+                let mut taken = false;
+                for other in ui_data.color_spaces.iter() {
+                    taken |= other.name == new_name;
+                }
+                if !taken {
+                    break;
+                }
+                new_name = format!("{} {}", base_name, i);
+            }
+            new_name
+        };
+        ui_data.color_spaces.insert(space_i + 1, space);
+        ui_data.selected_space_index = space_i + 1;
+    }
+
+    /// Adds a copy of the library color space at `library_i` to the
+    /// config, and selects it.
+    fn add_library_color_space(&self, library_i: usize) {
+        let space = match self.library.lock().get(library_i) {
+            Some(space) => space.clone(),
+            None => return,
+        };
+        let ui_data = &mut *self.ui_data.lock_mut();
+        ui_data.color_spaces.push(space);
+        ui_data.selected_space_index = ui_data.color_spaces.len() - 1;
+    }
+
+    /// Adds an input color space from a camera space sidecar file
+    /// written by LUT Maker, and selects it.
+    fn import_camera_space(&self, path: &Path) {
+        let camera_space = match lib::camera_space::CameraSpace::read(path) {
+            Ok(camera_space) => camera_space,
+            Err(e) => {
+                self.job_queue.log_error(format!(
+                    "Unable to read camera space file \"{}\": {}.",
+                    path.to_string_lossy(),
+                    e
+                ));
+                return;
+            }
+        };
+        let lut = match lib::job_helpers::load_1d_lut(&camera_space.lut_path) {
+            Ok(lut) => lut,
+            Err(_) => {
+                self.job_queue.log_error(format!(
+                    "Unable to load the LUT \"{}\" of camera space file \"{}\".",
+                    camera_space.lut_path.to_string_lossy(),
+                    path.to_string_lossy()
+                ));
+                return;
+            }
+        };
+
+        let mut space = ColorSpaceSpec::with_name(&camera_space.name);
+        let use_inverse = camera_space.lut_direction == lib::camera_space::LutDirection::FromLinear;
+        space.transfer_lut = Some((lut, camera_space.lut_path.clone(), use_inverse));
+        if let Some((chroma_space, custom_chroma)) = camera_space.chroma_space() {
+            space.chroma_space = chroma_space;
+            space.custom_chroma = custom_chroma;
+        }
+
+        let ui_data = &mut *self.ui_data.lock_mut();
+        if ui_data.color_spaces.iter().any(|s| s.name == space.name) {
+            self.job_queue.log_warning(format!(
+                "A color space named \"{}\" already exists.  Please rename one of them.",
+                space.name
+            ));
+        }
+        ui_data.color_spaces.push(space);
+        ui_data.selected_space_index = ui_data.color_spaces.len() - 1;
+        ui_data.selected_tab = Tabs::InputTransforms;
+    }
+
+    /// Loads a 1D or 3D LUT as the transfer LUT of the color space at
+    /// `space_i`, replacing any it already has, and selects the space.
+    fn attach_transfer_lut(&self, space_i: usize, path: &Path) {
+        let ui_data = &mut *self.ui_data.lock_mut();
+        let space = match ui_data.color_spaces.get_mut(space_i) {
+            Some(space) => space,
+            None => {
+                self.job_queue.log_error(format!(
+                    "No color space to attach the LUT \"{}\" to.  Please add one first.",
+                    path.to_string_lossy()
+                ));
+                return;
+            }
+        };
+
+        // .cube files can be either 1D or 3D.
+        match lib::job_helpers::load_1d_lut(path) {
+            Ok(lut) => {
+                space.transfer_lut = Some((lut, path.into(), false));
+                space.transfer_lut_3d = None;
+            }
+            Err(_) => match lib::job_helpers::load_3d_lut(path) {
+                Ok((lut, shaper)) => {
+                    space.transfer_lut = None;
+                    space.transfer_lut_3d = Some((lut, shaper, path.into()));
+                }
+                Err(_) => {
+                    self.job_queue.log_error(format!(
+                        "Unable to load the dropped LUT \"{}\".",
+                        path.to_string_lossy()
+                    ));
+                    return;
+                }
+            },
+        }
+        ui_data.selected_space_index = space_i;
+    }
+
+    /// Adds the selected color space to the library, replacing any
+    /// library color space with the same name.
+    fn add_selected_to_library(&self) {
+        let space = {
+            let ui_data = self.ui_data.lock();
+            match ui_data.color_spaces.get(ui_data.selected_space_index) {
+                Some(space) => library::library_copy(space, Path::new(&ui_data.export_path)),
+                None => return,
+            }
+        };
+        {
+            let mut library = self.library.lock_mut();
+            if let Some(existing) = library.iter_mut().find(|s| s.name == space.name) {
+                *existing = space;
+            } else {
+                library.push(space);
+            }
+        }
+        library::save(&self.library, &self.job_queue);
+    }
+
+    fn remove_library_color_space(&self, library_i: usize) {
+        {
+            let mut library = self.library.lock_mut();
+            if library_i >= library.len() {
+                return;
+            }
+            library.remove(library_i);
+        }
+        library::save(&self.library, &self.job_queue);
+    }
+
+    fn remove_look(&self, look_i: usize) {
+        let ui_data = &mut *self.ui_data.lock_mut();
+
+        if look_i < ui_data.looks.len() {
+            ui_data.looks.remove(look_i);
+        }
+
+        if ui_data.selected_look_index > look_i {
+            ui_data.selected_look_index = ui_data.selected_look_index.saturating_sub(1);
+        }
+
+        ui_data.selected_look_index = ui_data
+            .looks
+            .len()
+            .saturating_sub(1)
+            .min(ui_data.selected_look_index);
+    }
 
-        // Top bar, with tabs, config export, etc.
-        top_bar::top_bar(ctx, self);
+    fn add_look(&self) {
+        let ui_data = &mut *self.ui_data.lock_mut();
+        let name = {
+            let mut new_name = "New Look".into();
+            for i in 1..200 {
+                let name = format!("{} {}", new_name, i);
+                let mut taken = false;
+                for look in ui_data.looks.iter() {
+                    taken |= look.name == name;
+                }
+                if !taken {
+                    new_name = name;
+                    break;
+                }
+            }
+            new_name
+        };
+        ui_data.looks.push(LookSpec::with_name(&name));
+        ui_data.selected_look_index = ui_data.looks.len() - 1;
+    }
 
-        // Status bar and log (footer).
-        egui_custom::status_bar(ctx, &self.job_queue);
+    fn remove_named_transform(&self, named_transform_i: usize) {
+        let ui_data = &mut *self.ui_data.lock_mut();
 
-        // Main UI.
-        let selected_tab = self.ui_data.lock().selected_tab; // Work around borrow checker.
-        match selected_tab {
-            Tabs::BaseConfig => base_config::ui(ctx, self),
-            Tabs::InputTransforms => input_transforms::ui(ctx, self, &mut working_dir, job_count),
+        if named_transform_i < ui_data.named_transforms.len() {
+            ui_data.named_transforms.remove(named_transform_i);
         }
 
-        self.last_opened_directory = Some(working_dir);
+        if ui_data.selected_named_transform_index > named_transform_i {
+            ui_data.selected_named_transform_index =
+                ui_data.selected_named_transform_index.saturating_sub(1);
+        }
 
-        //----------------
-        // Processing.
+        ui_data.selected_named_transform_index = ui_data
+            .named_transforms
+            .len()
+            .saturating_sub(1)
+            .min(ui_data.selected_named_transform_index);
+    }
 
-        // Collect dropped files.
-        let _dropped_file_list = ctx.input(|input| {
-            let file_list: Vec<PathBuf> = input
-                .raw
-                .dropped_files
-                .iter()
-                .map(|dropped_file| dropped_file.path.clone().unwrap())
-                .collect();
-            file_list
-        });
+    fn add_named_transform(&self) {
+        let ui_data = &mut *self.ui_data.lock_mut();
+        let name = {
+            let mut new_name = "New Named Transform".into();
+            for i in 1..200 {
+                let name = format!("{} {}", new_name, i);
+                let mut taken = false;
+                for named_transform in ui_data.named_transforms.iter() {
+                    taken |= named_transform.name == name;
+                }
+                if !taken {
+                    new_name = name;
+                    break;
+                }
+            }
+            new_name
+        };
+        ui_data
+            .named_transforms
+            .push(NamedTransformSpec::with_name(&name));
+        ui_data.selected_named_transform_index = ui_data.named_transforms.len() - 1;
     }
-}
 
-impl AppMain {
-    fn remove_color_space(&self, space_i: usize) {
+    fn remove_display(&self, display_i: usize) {
         let ui_data = &mut *self.ui_data.lock_mut();
 
-        if space_i < ui_data.color_spaces.len() {
-            ui_data.color_spaces.remove(space_i);
+        if display_i < ui_data.displays.len() {
+            ui_data.displays.remove(display_i);
         }
 
-        if ui_data.selected_space_index > space_i {
-            ui_data.selected_space_index = ui_data.selected_space_index.saturating_sub(1);
+        if ui_data.selected_display_index > display_i {
+            ui_data.selected_display_index = ui_data.selected_display_index.saturating_sub(1);
         }
 
-        ui_data.selected_space_index = ui_data
-            .color_spaces
+        ui_data.selected_display_index = ui_data
+            .displays
             .len()
             .saturating_sub(1)
-            .min(ui_data.selected_space_index);
+            .min(ui_data.selected_display_index);
     }
 
-    fn add_input_color_space(&self) {
+    fn add_display(&self) {
         let ui_data = &mut *self.ui_data.lock_mut();
         let name = {
-            let mut new_name = "New Color Space".into();
+            let mut new_name = "New Display".into();
             for i in 1..200 {
                 let name = format!("{} {}", new_name, i);
                 let mut taken = false;
-                for space in ui_data.color_spaces.iter() {
-                    taken |= space.name == name;
+                for display in ui_data.displays.iter() {
+                    taken |= display.name == name;
                 }
                 if !taken {
                     new_name = name;
@@ -186,289 +2007,248 @@ impl AppMain {
             }
             new_name
         };
-        ui_data.color_spaces.push(ColorSpaceSpec::with_name(&name));
-        ui_data.selected_space_index = ui_data.color_spaces.len() - 1;
+        ui_data.displays.push(DisplaySpec::with_name(&name));
+        ui_data.selected_display_index = ui_data.displays.len() - 1;
     }
 
-    fn export_config(&self) {
-        use colorbox::matrix;
-        use ocio_gen::config::*;
-
+    /// Checks the current color spaces, looks, and displays against the
+    /// selected base preset, logging a warning for each problem found.
+    fn check_base_preset(&self) {
         let ui_data = self.ui_data.clone_ref();
 
-        self.job_queue.add_job("Export Config", move |status| {
-            status
-                .lock_mut()
-                .set_progress("Generating config".into(), 0.0);
+        self.job_queue
+            .add_job("Check Base Config", Priority::Normal, move |status| {
+                status
+                    .lock_mut()
+                    .set_progress("Checking against base config".into(), 0.0);
+
+                let (base_preset, working_color_space, tone_map_params, imported_config_path) = {
+                    let ui_data = ui_data.lock();
+                    (
+                        ui_data.base_preset,
+                        ui_data.working_color_space.clone(),
+                        ui_data.tone_map_params,
+                        ui_data.imported_config_path.clone(),
+                    )
+                };
+                let config = match base_preset.make_config(
+                    &working_color_space,
+                    tone_map_params,
+                    &imported_config_path,
+                ) {
+                    Ok((config, _)) => config,
+                    Err(e) => {
+                        status.lock_mut().log_error(e);
+                        return;
+                    }
+                };
+                ui_data.lock_mut().base_space_names =
+                    Some((base_preset, imported_config_path, colorspace_names(&config)));
+
+                for problem in check_against_base(&config, &*ui_data.lock()) {
+                    status.lock_mut().log_warning(format!(
+                        "{}  This will need to be fixed before exporting with the \"{}\" base.",
+                        problem,
+                        base_preset.ui_text()
+                    ));
+                }
+            });
+    }
+
+    /// Reads an existing OCIO config and makes it the base config.
+    fn import_base_config(&self, config_file_path: &Path) {
+        let config_file_path: PathBuf = config_file_path.into();
+        let ui_data = self.ui_data.clone_ref();
 
-            let export_path = ui_data.lock().export_path.clone();
-            if export_path.is_empty() {
+        self.job_queue
+            .add_job("Import Base Config", Priority::Normal, move |status| {
                 status
                     .lock_mut()
-                    .log_error("Failed to export: no config directory selected yet.".into());
-                return;
-            }
-            // We ignore the result here because we'll encounter the same one later
-            // anyway, where it is handled properly.
-            let _ = lib::job_helpers::ensure_dir_exists(&export_path);
-
-            let base_preset = ui_data.lock().base_preset;
-
-            // Template config.
-            let (mut config, working_space_chroma) = match base_preset {
-                BasePreset::Custom => {
-                    let chroma = ui_data.lock()
-                        .working_color_space
-                        .chroma_space
-                        .chromaticities(
-                            ui_data.lock().working_color_space.custom_chroma
-                        ).unwrap_or(colorbox::chroma::REC709);
-                    let config = ocio_gen::minimal_config::make_minimal(
-                        chroma,
-                        colorbox::matrix::AdaptationMethod::Hunt,
-                    );
+                    .set_progress("Importing config".into(), 0.0);
 
-                    (config, chroma)
-                }
-                BasePreset::Blender3_0 => (
-                    ocio_gen::blender_3_config::make_blender_3_0(),
-                    ocio_gen::blender_3_config::REFERENCE_SPACE_CHROMA,
-                ),
-                BasePreset::Blender4_0 => (
-                    ocio_gen::blender_4_config::make_blender_4_0(),
-                    ocio_gen::blender_4_config::REFERENCE_SPACE_CHROMA,
-                ),
-                BasePreset::AcesLite => {
-                    let config = ocio_gen::minimal_config::make_minimal(
-                        colorbox::chroma::ACES_AP1,
-                        colorbox::matrix::AdaptationMethod::Hunt,
-                    );
+                let config = match ocio_gen::import::read_config(&config_file_path) {
+                    Ok((config, warnings)) => {
+                        for warning in warnings {
+                            status.lock_mut().log_warning(warning);
+                        }
+                        config
+                    }
+                    Err(e) => {
+                        status.lock_mut().log_error(format!(
+                            "Unable to import config \"{}\": {}",
+                            config_file_path.to_string_lossy(),
+                            e
+                        ));
+                        return;
+                    }
+                };
 
-                    (config, colorbox::chroma::ACES_AP1)
+                {
+                    let mut ui_data = ui_data.lock_mut();
+                    ui_data.base_preset = BasePreset::Imported;
+                    ui_data.imported_config_path = config_file_path.to_string_lossy().into();
+                    ui_data.base_space_names = Some((
+                        BasePreset::Imported,
+                        ui_data.imported_config_path.clone(),
+                        colorspace_names(&config),
+                    ));
                 }
-            };
 
-            // Initial header comment.
-            config.header_comment.push_str(&format!("Generated by ETF OCIO Maker v{}\n\n", VERSION));
-            config.header_comment.push_str("Do not edit this file by hand if you want to continue managing\n");
-            config.header_comment.push_str("this configuration with ETF OCIO Maker.\n\n");
-            config.header_comment.push_str("----\n");
-            config.header_comment.push_str(&format!("base: {}\n", base_preset.as_str()));
-            match base_preset {
-                BasePreset::Custom => {
-                    config.header_comment.push_str(&ui_data.lock().working_color_space.to_string::<&str>(None, true));
+                for problem in check_against_base(&config, &*ui_data.lock()) {
+                    status.lock_mut().log_warning(problem);
                 }
-                _ => {}
-            }
-            config.header_comment.push_str("----\n");
+            });
+    }
 
-            // Prep to add our own stuff.
-            let output_dir: &Path = "ocio_maker".as_ref();
-            config.search_path.insert(output_dir.into());
-            let space_count = ui_data.lock().color_spaces.len();
-
-            // Add color spaces.
-            for i in 0..space_count {
-                if let Some(space) = ui_data.lock().color_spaces.get(i).map(|s| s.clone()) {
-                    // Add text version to header comment.
-                    config.header_comment.push_str(&space.to_string(Some(&export_path), false));
-                    config.header_comment.push_str("----\n");
-
-                    // Actual export.
-                    let space_name = space
-                        .name
-                        .trim()
-                        .replace("\\", "\\\\")
-                        .replace("#", "\\#")
-                        .replace("\"", "\\\"")
-                        .replace("]", "\\]")
-                        .replace("}", "\\}");
-
-                    let lut_info = space.transfer_lut.map(|(lut, ref path, inverse)| {
-                        // Compute output path.
-                        let lut_path = output_dir.join(format!(
-                            "omkr_{}__{}",
-                            i,
-                            path.file_name()
-                                .map(|f| f.to_str())
-                                .flatten()
-                                .unwrap_or("lut.cube")
-                        ));
+    fn export_config(&self) {
+        let ui_data = self.ui_data.clone_ref();
 
-                        // Add LUT file to config if it's not already there.
-                        config.output_files
-                            .entry(lut_path.clone())
-                            .or_insert(
-                                OutputFile::Lut1D(lut.clone())
-                            );
+        self.job_queue.add_job("Export Config", Priority::High, move |status| {
+            let (config, export_path) = match prepare_export(status, &ui_data) {
+                Some(export) => export,
+                None => return,
+            };
 
-                        (
-                            lut_path,
-                            inverse,
-                        )
+            // If there's a previously exported config, let the user review
+            // what will change before overwriting it.
+            match ocio_gen::diff::diff_against_directory(&config, &export_path) {
+                Ok(Some(diff)) if !diff.is_empty() => {
+                    ui_data.lock_mut().pending_export = Some(export_review::PendingExport {
+                        config: config,
+                        diff: diff,
+                        export_path: export_path,
                     });
-
-                    config.add_input_colorspace(
-                        space_name.clone(),
-                        Some("Custom (OCIO Maker)".into()),
-                        None,
-                        space.chroma_space.chromaticities(space.custom_chroma).unwrap_or(working_space_chroma),
-                        matrix::AdaptationMethod::Bradford,
-                        lut_info.map(|(lut_path, inverse)| Transform::FileTransform {
-                            src: lut_path.file_name().unwrap().into(),
-                            interpolation: Interpolation::Linear,
-                            direction_inverse: inverse,
-                        }),
-                        true,
-                    );
-
-                    if space.include_as_display {
-                        config.displays.push(Display {
-                            name: space_name.clone(),
-                            views: vec![("Standard".into(), space_name.clone())],
-                        });
-                        config.active_displays.push(space_name.clone());
-                    }
-                }
-            }
-
-            // Check for validation errors.
-            use ocio_gen::config::ValidationError::*;
-            match config.validate() {
-                Err(DuplicateColorSpace(name)) => {
-                    status
-                        .lock_mut()
-                        .log_error(format!("There is a duplicate color space in the config: \"{}\" \
-                                            \nNote: this may be a conflict with the built-ins of the \
-                                            config template, rather that two visible duplicates in \
-                                            your own colorspace list.", name));
-                    return;
-                },
-                Err(DuplicateDisplay(name)) => {
-                    status
-                        .lock_mut()
-                        .log_error(format!("There is a duplicate display in the config: \"{}\" \
-                                            \nNote: this may be a conflict with the built-ins of the \
-                                            config template, rather that two visible duplicates in \
-                                            your own colorspace list.", name));
-                    return;
-                },
-                Err(DuplicateRole(name)) => {
-                    status
-                        .lock_mut()
-                        .log_error(format!("There is a duplicate role in the config: \"{}\"", name));
-                    return;
-                },
-                Err(DuplicateLook(name)) => {
-                    status
-                        .lock_mut()
-                        .log_error(format!("There is a duplicate look in the config: \"{}\"", name));
-                    return;
-                },
-                Err(ReferenceToAbsentColorSpace(name)) => {
-                    status
-                        .lock_mut()
-                        .log_error(format!("There is a reference to a non-existent colorspace in the config: \"{}\"", name));
                     return;
-                },
-                Ok(()) => {},
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    status.lock_mut().log_warning(format!("Couldn't compare against the config already in the export directory, so it will be overwritten without a summary of changes: {}", e));
+                }
             }
 
-            // Write it out to disk.
-            status
-                .lock_mut()
-                .set_progress("Writing config to disk".into(), 0.0);
-            config
-                .write_to_directory(export_path.clone())
-                .expect("Failed to write OCIO config");
-
-            // Print help message about how to use the configuration.
-            let config_path = {
-                let tmp: PathBuf = export_path.into();
-                tmp.join("config.ocio")
-            };
-            if cfg!(target_family = "windows") {
-                status.lock_mut().log_note(format!("Export successful!  To use this configuration in your OCIO-enabled applications, create the following Windows environment variable:\n    Name:   OCIO\n    Value:   {}\nIf you're unsure how to create environment variables on Windows, step-by-step instructions can be found on the internet.", config_path.display()));
-            } else {
-                status.lock_mut().log_note(format!("Export successful!  To use this configuration in your OCIO-enabled applications, set the following environment variable:\n    OCIO={}", config_path.display()));
+            if write_config(status, &config, &export_path) && ui_data.lock().verify_export {
+                let colorspaces = ui_data.lock().color_space_names();
+                verify_export(status, &export_path, &colorspaces);
             }
         });
     }
 
-    fn load_config(&self, config_file_path: &Path) {
-        let config_file_path: PathBuf = config_file_path.into();
+    /// Generates the config, and opens the window for baking one of its
+    /// display/view chains to a LUT.
+    fn open_bake_lut(&self) {
         let ui_data = self.ui_data.clone_ref();
 
-        self.job_queue.add_job("Load Config", move |status| {
-            status.lock_mut().set_progress("Loading config".into(), 0.0);
+        self.job_queue
+            .add_job("Bake LUT", Priority::High, move |status| {
+                status
+                    .lock_mut()
+                    .set_progress("Generating config".into(), 0.0);
+
+                let export_path = ui_data.lock().export_path.clone();
+                match generate_config(&ui_data, &export_path) {
+                    Ok(config) => {
+                        ui_data.lock_mut().bake_lut = Some(bake_lut::BakeState::new(config));
+                    }
+                    Err(e) => {
+                        status
+                            .lock_mut()
+                            .log_error(format!("Failed to bake LUT: {}", e));
+                    }
+                }
+            });
+    }
 
-            let mut color_spaces = Vec::new();
+    /// Generates the config, and opens the window for previewing a test
+    /// image through it.
+    fn open_soft_proof(&mut self) {
+        self.soft_proof.open = true;
+        self.soft_proof
+            .refresh_config(&self.ui_data, &self.job_queue);
+    }
 
-            // Parse.
-            if let Ok(file) = std::fs::File::open(&config_file_path) {
-                let file = BufReader::new(file);
+    fn bake_lut(&self, path: PathBuf) {
+        let state = match self.ui_data.lock().bake_lut {
+            Some(ref state) => state.clone(),
+            None => return,
+        };
 
-                let mut chunk = String::new();
-                for (num, line) in file.lines().take_while(|l| l.is_ok()).map(|l| l.unwrap()).enumerate() {
-                    if num == 0 && !line.starts_with("# Generated by ETF OCIO Maker") {
-                        status.lock_mut().log_error("Failed to load configuration: is not an ETF OCIO Maker-generated config.".into());
-                        return;
-                    } else if !line.trim().starts_with("#") {
-                        break;
-                    } else if line.trim() == "# ----" {
-                        // Main parsing code, that handles each chunk.
-
-                        // Base config.
-                        if chunk.starts_with("base:") {
-                            if let Some((base, tail)) = chunk.split_once("\n") {
-                                if let Some(base_preset) = BasePreset::from_str(base.split_once(":").unwrap().1) {
-                                    ui_data.lock_mut().base_preset = base_preset;
-                                    if let (color_space, Ok(_)) = ColorSpaceSpec::from_str::<&str>(tail, None) {
-                                        ui_data.lock_mut().working_color_space = color_space;
-                                    }
-                                } else {
-                                    status.lock_mut().log_error(
-                                        "Invalid configuration base.  Continuing to load with default.".into()
-                                    );
-                                }
-                            }
-                        }
-                        // Color space.
-                        else if chunk.starts_with("color_space:") {
-                            let (color_space, result) = ColorSpaceSpec::from_str(&chunk, config_file_path.parent());
-                            match result {
-                                Ok(_) => {},
-                                Err(ConfigLoadErr::FileUnloadable(s)) => {
-                                    status.lock_mut().log_error(format!(
-                                        "Unable to load LUT file: \"{}\".  Colorspace \"{}\" is incomplete.",
-                                        s, color_space.name
-                                    ));
-                                },
-                            };
-                            color_spaces.push(color_space);
-                        }
-                        chunk.clear();
-                    } else {
-                        chunk.push_str(&line[1..].trim());
-                        chunk.push_str("\n");
+        self.job_queue
+            .add_job("Bake LUT", Priority::High, move |status| {
+                status.lock_mut().set_progress("Baking LUT".into(), 0.0);
+                match bake_lut::bake(&state, &path) {
+                    Ok(()) => {
+                        status.lock_mut().log_note(format!(
+                            "Baked \"{}\" to \"{}\" / \"{}\" into \"{}\".",
+                            state.source,
+                            state.display,
+                            state.view,
+                            path.display()
+                        ));
+                    }
+                    Err(e) => {
+                        status
+                            .lock_mut()
+                            .log_error(format!("Failed to bake LUT: {}", e));
                     }
                 }
-            } else {
-                status.lock_mut().log_error(format!(
-                    "Unable to access config file: \"{}\"",
-                    config_file_path.to_string_lossy()
-                ));
+            });
+    }
+
+    fn write_config(&self, config: ocio_gen::config::OCIOConfig, export_path: String) {
+        let ui_data = self.ui_data.clone_ref();
+
+        self.job_queue
+            .add_job("Export Config", Priority::High, move |status| {
+                if write_config(status, &config, &export_path) && ui_data.lock().verify_export {
+                    let colorspaces = ui_data.lock().color_space_names();
+                    verify_export(status, &export_path, &colorspaces);
+                }
+            });
+    }
+
+    fn load_config(&self, config_file_path: &Path) {
+        let config_file_path: PathBuf = config_file_path.into();
+        let ui_data = self.ui_data.clone_ref();
+
+        self.job_queue.add_job("Load Config", Priority::Normal, move |status| {
+            if !load_project(status, &ui_data, &config_file_path) {
                 return;
             }
 
-            // Set in-memory config to the same as the parsed one.
-            {
-                let mut ui_data = ui_data.lock_mut();
-                ui_data.color_spaces = color_spaces;
-                ui_data.selected_space_index = 0;
-                if let Some(parent) = config_file_path.parent().map(|p| p.to_string_lossy()) {
-                    ui_data.export_path = parent.into();
+            // Older configs were commonly made with the Blender 3.0 base.
+            // We keep it so that they export the same as before, but point
+            // out the newer one.
+            let (base_preset, working_color_space, tone_map_params, imported_config_path) = {
+                let ui_data = ui_data.lock();
+                (
+                    ui_data.base_preset,
+                    ui_data.working_color_space.clone(),
+                    ui_data.tone_map_params,
+                    ui_data.imported_config_path.clone(),
+                )
+            };
+            if base_preset == BasePreset::Blender3_0 {
+                status.lock_mut().log_note(
+                    "This config uses the Blender 3.0 base.  The Blender 4.0 base, which includes AgX, can be selected in the Base Config tab.".into()
+                );
+            }
+
+            // Check for anything that won't export cleanly with the base.
+            status.lock_mut().set_progress("Checking against base config".into(), 0.9);
+            let config = match base_preset.make_config(&working_color_space, tone_map_params, &imported_config_path) {
+                Ok((config, _)) => config,
+                Err(e) => {
+                    status.lock_mut().log_error(e);
+                    return;
                 }
+            };
+            ui_data.lock_mut().base_space_names = Some((
+                base_preset,
+                imported_config_path,
+                colorspace_names(&config),
+            ));
+            for problem in check_against_base(&config, &*ui_data.lock()) {
+                status.lock_mut().log_warning(problem);
             }
         });
     }
@@ -482,6 +2262,8 @@ pub enum BasePreset {
     Blender3_0,
     Blender4_0,
     AcesLite,
+    Aces1_3,
+    Imported, // An existing config read from disk.
 }
 
 impl BasePreset {
@@ -491,6 +2273,8 @@ impl BasePreset {
             Self::Blender3_0 => "Blender 3.0",
             Self::Blender4_0 => "Blender 4.0",
             Self::AcesLite => "ACES Lite",
+            Self::Aces1_3 => "ACES 1.3",
+            Self::Imported => "Imported Config",
         }
     }
 
@@ -500,6 +2284,8 @@ impl BasePreset {
             Self::Blender3_0 => "Blender 3.0",
             Self::Blender4_0 => "Blender 4.0",
             Self::AcesLite => "ACES Lite",
+            Self::Aces1_3 => "ACES 1.3",
+            Self::Imported => "Imported",
         }
     }
 
@@ -509,9 +2295,183 @@ impl BasePreset {
             "Blender 3.0" => Some(Self::Blender3_0),
             "Blender 4.0" => Some(Self::Blender4_0),
             "ACES Lite" => Some(Self::AcesLite),
+            "ACES 1.3" => Some(Self::Aces1_3),
+            "Imported" => Some(Self::Imported),
             _ => None,
         }
     }
+
+    /// Whether the preset's tone mapping view transforms are generated,
+    /// and can therefore be adjusted with `ToneMapParams`.
+    pub fn has_adjustable_view_transforms(&self) -> bool {
+        match self {
+            Self::Custom | Self::AcesLite => true,
+            _ => false,
+        }
+    }
+}
+
+impl BasePreset {
+    /// Builds the template config for this preset, along with the
+    /// chromaticities of its reference space.
+    ///
+    /// `working_color_space` is only used by the `Custom` preset,
+    /// `tone_map_params` only by presets with adjustable view
+    /// transforms, and `imported_config_path` only by the `Imported`
+    /// preset.  Only the latter can fail, if the imported config can't be
+    /// read.
+    fn make_config(
+        &self,
+        working_color_space: &ColorSpaceSpec,
+        tone_map_params: ocio_gen::ToneMapParams,
+        imported_config_path: &str,
+    ) -> Result<
+        (
+            ocio_gen::config::OCIOConfig,
+            colorbox::chroma::Chromaticities,
+        ),
+        String,
+    > {
+        Ok(match *self {
+            BasePreset::Custom => {
+                let chroma = working_color_space
+                    .chroma_space
+                    .chromaticities(working_color_space.custom_chroma)
+                    .unwrap_or(colorbox::chroma::REC709);
+                let config = ocio_gen::minimal_config::make_minimal(
+                    chroma,
+                    colorbox::matrix::AdaptationMethod::Hunt,
+                    tone_map_params,
+                );
+
+                (config, chroma)
+            }
+            BasePreset::Blender3_0 => (
+                ocio_gen::blender_3_config::make_blender_3_0(),
+                ocio_gen::blender_3_config::REFERENCE_SPACE_CHROMA,
+            ),
+            BasePreset::Blender4_0 => (
+                ocio_gen::blender_4_config::make_blender_4_0(),
+                ocio_gen::blender_4_config::REFERENCE_SPACE_CHROMA,
+            ),
+            BasePreset::AcesLite => {
+                let config = ocio_gen::minimal_config::make_minimal(
+                    colorbox::chroma::ACES_AP1,
+                    colorbox::matrix::AdaptationMethod::Hunt,
+                    tone_map_params,
+                );
+
+                (config, colorbox::chroma::ACES_AP1)
+            }
+            BasePreset::Aces1_3 => (
+                ocio_gen::aces_config::make_aces_1_3(),
+                ocio_gen::aces_config::REFERENCE_SPACE_CHROMA,
+            ),
+            BasePreset::Imported => {
+                // Warnings were already reported at import time.
+                let (config, _) =
+                    ocio_gen::import::read_config(imported_config_path).map_err(|e| {
+                        format!(
+                            "Unable to read imported base config \"{}\": {}",
+                            imported_config_path, e
+                        )
+                    })?;
+                let chroma = config.reference_space_chroma;
+
+                (config, chroma)
+            }
+        })
+    }
+}
+
+/// Names and aliases of all color spaces in `config`.
+fn colorspace_names(config: &ocio_gen::config::OCIOConfig) -> Vec<String> {
+    let mut names = Vec::new();
+    for space in config.colorspaces.iter() {
+        names.push(space.name.clone());
+        names.extend(space.aliases.iter().cloned());
+    }
+    names
+}
+
+/// Checks the user's color spaces, looks, and displays against a base
+/// config, returning a description of each problem found.
+///
+/// This catches the things that break when switching base presets:
+/// color space names that clash with the base config, and looks or
+/// views that refer to color spaces the base config doesn't have.
+fn check_against_base(base_config: &ocio_gen::config::OCIOConfig, ui_data: &UIData) -> Vec<String> {
+    let mut problems = Vec::new();
+    let user_space_names: Vec<String> = ui_data
+        .color_spaces
+        .iter()
+        .map(|s| escape_name(&s.name))
+        .collect();
+    let exists = |name: &str| {
+        let name = escape_name(name);
+        base_config.has_colorspace(&name) || user_space_names.contains(&name)
+    };
+
+    for name in user_space_names.iter() {
+        if base_config.has_colorspace(name) {
+            problems.push(format!(
+                "Color space \"{}\" has the same name as one in the base config.",
+                name
+            ));
+        }
+    }
+    for space in ui_data.color_spaces.iter() {
+        for alias in space.alias_list().iter().map(|a| escape_name(a)) {
+            if base_config.has_colorspace(&alias) || user_space_names.contains(&alias) {
+                problems.push(format!(
+                    "Alias \"{}\" of color space \"{}\" is already the name of a color space.",
+                    alias,
+                    space.name.trim()
+                ));
+            }
+        }
+    }
+    for named_transform in ui_data.named_transforms.iter() {
+        let name = escape_name(&named_transform.name);
+        if base_config.has_colorspace(&name) || user_space_names.contains(&name) {
+            problems.push(format!(
+                "Named transform \"{}\" has the same name as a color space.",
+                name
+            ));
+        }
+    }
+    for look in ui_data.looks.iter() {
+        if !exists(&look.process_space) {
+            problems.push(format!(
+                "Look \"{}\" uses non-existent process space \"{}\".",
+                look.name.trim(),
+                look.process_space.trim()
+            ));
+        }
+    }
+    for (role, space) in ui_data.roles.iter() {
+        if !exists(space) {
+            problems.push(format!(
+                "Role \"{}\" is assigned non-existent color space \"{}\".",
+                role.trim(),
+                space.trim()
+            ));
+        }
+    }
+    for display in ui_data.displays.iter() {
+        for view in display.views.iter() {
+            if !exists(&view.color_space) {
+                problems.push(format!(
+                    "View \"{}\" of display \"{}\" uses non-existent color space \"{}\".",
+                    view.name.trim(),
+                    display.name.trim(),
+                    view.color_space.trim()
+                ));
+            }
+        }
+    }
+
+    problems
 }
 
 pub const BASE_PRESETS: &[BasePreset] = &[
@@ -519,21 +2479,115 @@ pub const BASE_PRESETS: &[BasePreset] = &[
     BasePreset::Blender3_0,
     BasePreset::Blender4_0,
     BasePreset::AcesLite,
+    BasePreset::Aces1_3,
 ];
 
+/// Where the LUT files of exported configs go.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LutPaths {
+    /// Copied into `ocio_maker/`, with generated names.
+    Copy,
+    /// Referenced where they are, relative to the config.
+    InPlace,
+    /// Copied into the given directory (relative to the config), with
+    /// their original names.
+    Directory(String),
+}
+
+impl LutPaths {
+    pub fn ui_text(&self) -> &'static str {
+        match *self {
+            LutPaths::Copy => "Copy to ocio_maker/",
+            LutPaths::InPlace => "Reference in Place",
+            LutPaths::Directory(_) => "Copy to Directory",
+        }
+    }
+
+    fn to_string(&self) -> String {
+        match *self {
+            LutPaths::Copy => "copy".into(),
+            LutPaths::InPlace => "in_place".into(),
+            LutPaths::Directory(ref dir) => format!("directory {}", dir),
+        }
+    }
+
+    fn from_str(text: &str) -> Option<Self> {
+        let text = text.trim();
+        match text {
+            "copy" => Some(LutPaths::Copy),
+            "in_place" => Some(LutPaths::InPlace),
+            _ => text
+                .strip_prefix("directory ")
+                .map(|dir| LutPaths::Directory(dir.trim().into())),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Tabs {
     InputTransforms,
     BaseConfig,
+    Looks,
+    Displays,
+    NamedTransforms,
+    Roles,
+    FileRules,
 }
 
 #[derive(Debug, Clone)]
 pub struct ColorSpaceSpec {
     name: String,
     transfer_lut: Option<(Lut1D, PathBuf, bool)>, // The bool is whether to do the inverse transform.
+    transfer_lut_3d: Option<(Lut3D, Option<Lut1D>, PathBuf)>, // (lut, shaper, path).  Used instead of `transfer_lut` if present.
     chroma_space: ChromaSpace,
     custom_chroma: colorbox::chroma::Chromaticities,
+    input_matrix: Option<[[f64; 4]; 3]>, // 3x4 matrix (4th column is offsets) from linear input RGB to `chroma_space`.
+    gamut_mapping: GamutMapping,         // For colors outside the reference gamut, and vice versa.
+    white_balance: Option<WhiteBalance>, // Applied in linear `chroma_space` RGB.
     include_as_display: bool,
+    inactive: bool, // Listed in the config's `inactive_colorspaces`.
+
+    // Metadata.
+    family: String,  // Empty means the default OCIO Maker family.
+    aliases: String, // Comma-separated.
+    encoding: Option<Encoding>,
+    description: String,
+}
+
+impl PartialEq for ColorSpaceSpec {
+    fn eq(&self, other: &Self) -> bool {
+        // LUTs are compared by their source file rather than contents,
+        // since they're always loaded from there.
+        let same_lut = match (&self.transfer_lut, &other.transfer_lut) {
+            (None, None) => true,
+            (Some((_, path1, inv1)), Some((_, path2, inv2))) => path1 == path2 && inv1 == inv2,
+            _ => false,
+        };
+        let same_lut_3d = match (&self.transfer_lut_3d, &other.transfer_lut_3d) {
+            (None, None) => true,
+            (Some((_, _, path1)), Some((_, _, path2))) => path1 == path2,
+            _ => false,
+        };
+        let (c1, c2) = (&self.custom_chroma, &other.custom_chroma);
+
+        self.name == other.name
+            && same_lut
+            && same_lut_3d
+            && self.chroma_space == other.chroma_space
+            && c1.r == c2.r
+            && c1.g == c2.g
+            && c1.b == c2.b
+            && c1.w == c2.w
+            && self.input_matrix == other.input_matrix
+            && self.gamut_mapping == other.gamut_mapping
+            && self.white_balance == other.white_balance
+            && self.include_as_display == other.include_as_display
+            && self.inactive == other.inactive
+            && self.family == other.family
+            && self.aliases == other.aliases
+            && self.encoding == other.encoding
+            && self.description == other.description
+    }
 }
 
 impl ColorSpaceSpec {
@@ -544,6 +2598,25 @@ impl ColorSpaceSpec {
         }
     }
 
+    /// The family the color space is exported with.
+    fn family_or_default(&self) -> &str {
+        if self.family.trim().is_empty() {
+            DEFAULT_FAMILY
+        } else {
+            self.family.trim()
+        }
+    }
+
+    /// The non-empty aliases, trimmed.
+    fn alias_list(&self) -> Vec<String> {
+        self.aliases
+            .split(',')
+            .map(|a| a.trim())
+            .filter(|a| !a.is_empty())
+            .map(|a| a.into())
+            .collect()
+    }
+
     /// If `base_path` is specified, then all paths will be written as relative to that path.
     fn to_string<P: AsRef<Path>>(&self, base_path: Option<P>, chroma_only: bool) -> String {
         let mut s = String::new();
@@ -554,6 +2627,34 @@ impl ColorSpaceSpec {
                 "include_as_display: {:?}\n",
                 self.include_as_display
             ));
+            if self.inactive {
+                s.push_str("inactive: true\n");
+            }
+            if self.gamut_mapping != GamutMapping::default() {
+                s.push_str(&format!("gamut_mapping: {}\n", self.gamut_mapping.as_str()));
+            }
+            if !self.family.trim().is_empty() {
+                s.push_str(&format!("family: {}\n", self.family.trim()));
+            }
+            if !self.alias_list().is_empty() {
+                s.push_str(&format!("aliases: {}\n", self.alias_list().join(", ")));
+            }
+            if let Some(encoding) = self.encoding {
+                s.push_str(&format!("encoding: {}\n", encoding.as_str()));
+            }
+            if let Some(white_balance) = self.white_balance {
+                s.push_str(&format!("white_balance: {}\n", white_balance.to_string()));
+            }
+            if !self.description.trim().is_empty() {
+                // Newlines are escaped to keep it on one line.
+                s.push_str(&format!(
+                    "description: {}\n",
+                    self.description
+                        .trim()
+                        .replace("\\", "\\\\")
+                        .replace("\n", "\\n")
+                ));
+            }
         }
 
         if self.chroma_space != ChromaSpace::None {
@@ -573,21 +2674,28 @@ impl ColorSpaceSpec {
             }
         }
 
+        if let Some(m) = self.input_matrix {
+            s.push_str("input_matrix:");
+            for n in m.iter().flatten() {
+                s.push_str(&format!(" {}", n));
+            }
+            s.push_str("\n");
+        }
+
         if !chroma_only {
             if let Some((_, ref path, use_inverse)) = self.transfer_lut {
-                // Convert path to be relative, if a base path is available.
-                let path = path.canonicalize().unwrap_or_else(|_| path.into());
-                let path = if let Some(base) = base_path {
-                    let base: &Path = base.as_ref();
-                    let base = base.canonicalize().unwrap_or_else(|_| base.into());
-                    pathdiff::diff_paths(&path, &base).unwrap_or_else(|| path)
-                } else {
-                    path
-                };
+                let path = relative_path(path, base_path.as_ref());
 
                 s.push_str(&format!("transfer_lut_path: {}\n", path.to_string_lossy()));
                 s.push_str(&format!("transfer_lut_use_inverse: {:?}\n", use_inverse));
             }
+            if let Some((_, _, ref path)) = self.transfer_lut_3d {
+                let path = relative_path(path, base_path.as_ref());
+                s.push_str(&format!(
+                    "transfer_lut_3d_path: {}\n",
+                    path.to_string_lossy()
+                ));
+            }
         }
 
         s
@@ -610,6 +2718,16 @@ impl ColorSpaceSpec {
                     "include_as_display" => {
                         color_space.include_as_display = if value == "true" { true } else { false }
                     }
+                    "inactive" => color_space.inactive = value == "true",
+                    "gamut_mapping" => {
+                        color_space.gamut_mapping =
+                            GamutMapping::from_str(value).unwrap_or_default()
+                    }
+                    "family" => color_space.family = value.into(),
+                    "aliases" => color_space.aliases = value.into(),
+                    "encoding" => color_space.encoding = Encoding::from_str(value),
+                    "white_balance" => color_space.white_balance = WhiteBalance::from_str(value),
+                    "description" => color_space.description = unescape_newlines(value),
                     "chroma_space" => {
                         color_space.chroma_space =
                             ChromaSpace::from_str(value).unwrap_or(ChromaSpace::None)
@@ -630,6 +2748,29 @@ impl ColorSpaceSpec {
                             }
                         }
                     }
+                    "input_matrix" => {
+                        let values: Vec<f64> = value
+                            .split_whitespace()
+                            .map(|v| v.parse::<f64>())
+                            .filter(|v| v.is_ok())
+                            .map(|v| v.unwrap())
+                            .collect();
+                        // Accept both 3x3 and 3x4 (with offsets) matrices.
+                        let cols = match values.len() {
+                            9 => 3,
+                            12 => 4,
+                            _ => 0,
+                        };
+                        if cols > 0 {
+                            let mut m = [[0.0f64; 4]; 3];
+                            for row in 0..3 {
+                                for col in 0..cols {
+                                    m[row][col] = values[row * cols + col];
+                                }
+                            }
+                            color_space.input_matrix = Some(m);
+                        }
+                    }
                     "transfer_lut_path" => {
                         let path: PathBuf = if let Some(ref base_path) = base_path {
                             base_path.as_ref().join(value)
@@ -641,6 +2782,19 @@ impl ColorSpaceSpec {
                             Err(_) => result = Err(ConfigLoadErr::FileUnloadable(value.into())),
                         }
                     }
+                    "transfer_lut_3d_path" => {
+                        let path: PathBuf = if let Some(ref base_path) = base_path {
+                            base_path.as_ref().join(value)
+                        } else {
+                            value.into()
+                        };
+                        match lib::job_helpers::load_3d_lut(path) {
+                            Ok((lut, shaper)) => {
+                                color_space.transfer_lut_3d = Some((lut, shaper, value.into()))
+                            }
+                            Err(_) => result = Err(ConfigLoadErr::FileUnloadable(value.into())),
+                        }
+                    }
                     "transfer_lut_use_inverse" => {
                         if let Some((_, _, ref mut use_inverse)) = color_space.transfer_lut {
                             *use_inverse = if value == "true" { true } else { false };
@@ -660,6 +2814,7 @@ impl Default for ColorSpaceSpec {
         ColorSpaceSpec {
             name: "".into(),
             transfer_lut: None,
+            transfer_lut_3d: None,
             chroma_space: ChromaSpace::None,
             custom_chroma: colorbox::chroma::Chromaticities {
                 // Default to Rec.2020, just to have a starting point.
@@ -668,181 +2823,710 @@ impl Default for ColorSpaceSpec {
                 b: (0.131, 0.046),
                 w: (0.3127, 0.3290),
             },
+            input_matrix: None,
+            gamut_mapping: GamutMapping::default(),
+            white_balance: None,
             include_as_display: false,
+            inactive: false,
+            family: String::new(),
+            aliases: String::new(),
+            encoding: None,
+            description: String::new(),
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum ChromaSpace {
-    None,
-    Custom,
-    Rec709,
-    Rec2020,
-    DciP3,
-    DisplayP3,
-    AcesAP0,
-    AcesAP1,
-    AdobeRGB,
-    AdobeWideGamutRGB,
-    ARRIWideGamut3,
-    ARRIWideGamut4,
-    BlackmagicWideGamutGen4,
-    CanonCinemaGamut,
-    DavinciWideGamut,
-    DJIDGamut,
-    EGamut,
-    PanasonicVGamut,
-    ProPhoto,
-    RedWideGamutRGB,
-    SGamut,
-    SGamut3Cine,
-}
-
-const CHROMA_SPACES: &[ChromaSpace] = &[
-    ChromaSpace::None,
-    ChromaSpace::Custom,
-    // Standardized.
-    ChromaSpace::AcesAP0,
-    ChromaSpace::AcesAP1,
-    ChromaSpace::Rec709,
-    ChromaSpace::Rec2020,
-    ChromaSpace::DciP3,
-    ChromaSpace::DisplayP3,
-    // Other.
-    ChromaSpace::AdobeRGB,
-    ChromaSpace::AdobeWideGamutRGB,
-    ChromaSpace::ARRIWideGamut3,
-    ChromaSpace::ARRIWideGamut4,
-    ChromaSpace::BlackmagicWideGamutGen4,
-    ChromaSpace::CanonCinemaGamut,
-    ChromaSpace::DavinciWideGamut,
-    ChromaSpace::DJIDGamut,
-    ChromaSpace::EGamut,
-    ChromaSpace::PanasonicVGamut,
-    ChromaSpace::ProPhoto,
-    ChromaSpace::RedWideGamutRGB,
-    ChromaSpace::SGamut,
-    ChromaSpace::SGamut3Cine,
-];
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookSpec {
+    name: String,
+    process_space: String,
+    transforms: Vec<LookTransformSpec>,
+}
 
-impl ChromaSpace {
-    fn chromaticities(
-        &self,
-        custom: colorbox::chroma::Chromaticities,
-    ) -> Option<colorbox::chroma::Chromaticities> {
-        match *self {
-            ChromaSpace::None => None,
-            ChromaSpace::Custom => Some(custom),
-            ChromaSpace::Rec709 => Some(colorbox::chroma::REC709),
-            ChromaSpace::Rec2020 => Some(colorbox::chroma::REC2020),
-            ChromaSpace::DciP3 => Some(colorbox::chroma::DCI_P3),
-            ChromaSpace::DisplayP3 => Some(colorbox::chroma::DISPLAY_P3),
-            ChromaSpace::AcesAP0 => Some(colorbox::chroma::ACES_AP0),
-            ChromaSpace::AcesAP1 => Some(colorbox::chroma::ACES_AP1),
-            ChromaSpace::AdobeRGB => Some(colorbox::chroma::ADOBE_RGB),
-            ChromaSpace::AdobeWideGamutRGB => Some(colorbox::chroma::ADOBE_WIDE_GAMUT_RGB),
-            ChromaSpace::ARRIWideGamut3 => Some(colorbox::chroma::ARRI_WIDE_GAMUT_3),
-            ChromaSpace::ARRIWideGamut4 => Some(colorbox::chroma::ARRI_WIDE_GAMUT_4),
-            ChromaSpace::BlackmagicWideGamutGen4 => {
-                Some(colorbox::chroma::blackmagic::BMD_WIDE_GAMUT_GEN4)
-            }
-            ChromaSpace::CanonCinemaGamut => Some(colorbox::chroma::CANON_CINEMA_GAMUT),
-            ChromaSpace::DavinciWideGamut => Some(colorbox::chroma::blackmagic::DAVINCI_WIDE_GAMUT),
-            ChromaSpace::DJIDGamut => Some(colorbox::chroma::DJI_D_GAMUT),
-            ChromaSpace::EGamut => Some(colorbox::chroma::E_GAMUT),
-            ChromaSpace::PanasonicVGamut => Some(colorbox::chroma::PANASONIC_V_GAMUT),
-            ChromaSpace::ProPhoto => Some(colorbox::chroma::PROPHOTO),
-            ChromaSpace::RedWideGamutRGB => Some(colorbox::chroma::RED_WIDE_GAMUT_RGB),
-            ChromaSpace::SGamut => Some(colorbox::chroma::sony::S_GAMUT),
-            ChromaSpace::SGamut3Cine => Some(colorbox::chroma::sony::S_GAMUT3_CINE),
-        }
-    }
-
-    fn ui_text(&self) -> &'static str {
-        match *self {
-            ChromaSpace::None => "None",
-            ChromaSpace::Custom => "Custom",
-            ChromaSpace::Rec709 => "Rec.709 / sRGB",
-            ChromaSpace::Rec2020 => "Rec.2020",
-            ChromaSpace::DciP3 => "DCI-P3",
-            ChromaSpace::DisplayP3 => "Display P3",
-            ChromaSpace::AcesAP0 => "ACES APO",
-            ChromaSpace::AcesAP1 => "ACES AP1",
-            ChromaSpace::AdobeRGB => "Adobe RGB",
-            ChromaSpace::AdobeWideGamutRGB => "Adobe Wide Gamut RGB",
-            ChromaSpace::ARRIWideGamut3 => "ARRI Wide Gamut 3 / Alexa Wide Gamut RGB",
-            ChromaSpace::ARRIWideGamut4 => "ARRI Wide Gamut 4",
-            ChromaSpace::BlackmagicWideGamutGen4 => "BMD Wide Gamut Gen4/Gen5",
-            ChromaSpace::CanonCinemaGamut => "Canon Cinema Gamut",
-            ChromaSpace::DavinciWideGamut => "DaVinci Wide Gamut",
-            ChromaSpace::DJIDGamut => "DJI D-Gamut",
-            ChromaSpace::EGamut => "FilmLight E-Gamut",
-            ChromaSpace::PanasonicVGamut => "Panasonic V-Gamut",
-            ChromaSpace::ProPhoto => "ProPhoto",
-            ChromaSpace::RedWideGamutRGB => "RED Wide Gamut RGB",
-            ChromaSpace::SGamut => "Sony S-Gamut / S-Gamut3",
-            ChromaSpace::SGamut3Cine => "Sony S-Gamut3.Cine",
+#[derive(Debug, Clone)]
+pub enum LookTransformSpec {
+    CDL {
+        slope: [f32; 3],
+        offset: [f32; 3],
+        power: [f32; 3],
+        saturation: f32,
+    },
+    GradingPrimary {
+        style: GradingStyle,
+        contrast: [f32; 3],
+        saturation: f32,
+        pivot: f32,
+    },
+    File {
+        lut_1d: Option<Lut1D>, // A 1D LUT, or the shaper of the 3D LUT.
+        lut_3d: Option<Lut3D>,
+        path: PathBuf,
+    },
+}
+
+impl PartialEq for LookTransformSpec {
+    fn eq(&self, other: &Self) -> bool {
+        use LookTransformSpec::*;
+        match (self, other) {
+            (
+                CDL {
+                    slope: s1,
+                    offset: o1,
+                    power: p1,
+                    saturation: sat1,
+                },
+                CDL {
+                    slope: s2,
+                    offset: o2,
+                    power: p2,
+                    saturation: sat2,
+                },
+            ) => s1 == s2 && o1 == o2 && p1 == p2 && sat1 == sat2,
+            (
+                GradingPrimary {
+                    style: st1,
+                    contrast: c1,
+                    saturation: sat1,
+                    pivot: p1,
+                },
+                GradingPrimary {
+                    style: st2,
+                    contrast: c2,
+                    saturation: sat2,
+                    pivot: p2,
+                },
+            ) => st1 == st2 && c1 == c2 && sat1 == sat2 && p1 == p2,
+            // LUTs are compared by their source file, like in `ColorSpaceSpec`.
+            (File { path: path1, .. }, File { path: path2, .. }) => path1 == path2,
+            _ => false,
         }
     }
+}
 
-    fn as_str(&self) -> &'static str {
+impl LookTransformSpec {
+    fn default_cdl() -> LookTransformSpec {
+        LookTransformSpec::CDL {
+            slope: [1.0; 3],
+            offset: [0.0; 3],
+            power: [1.0; 3],
+            saturation: 1.0,
+        }
+    }
+
+    fn default_grading_primary() -> LookTransformSpec {
+        LookTransformSpec::GradingPrimary {
+            style: GradingStyle::Log,
+            contrast: [1.0; 3],
+            saturation: 1.0,
+            pivot: -0.2,
+        }
+    }
+
+    /// Loads a .cube file as a look transform.  Both 1D and 3D LUTs are
+    /// supported.
+    fn load_file<P: AsRef<Path>>(path: P) -> Option<LookTransformSpec> {
+        let path: &Path = path.as_ref();
+        if let Ok((lut_3d, shaper)) = lib::job_helpers::load_3d_lut(path) {
+            Some(LookTransformSpec::File {
+                lut_1d: shaper,
+                lut_3d: Some(lut_3d),
+                path: path.into(),
+            })
+        } else if let Ok(lut_1d) = lib::job_helpers::load_1d_lut(path) {
+            Some(LookTransformSpec::File {
+                lut_1d: Some(lut_1d),
+                lut_3d: None,
+                path: path.into(),
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn ui_text(&self) -> &'static str {
         match *self {
-            ChromaSpace::None => "None",
-            ChromaSpace::Custom => "Custom",
-            ChromaSpace::Rec709 => "Rec709",
-            ChromaSpace::Rec2020 => "Rec2020",
-            ChromaSpace::DciP3 => "DciP3",
-            ChromaSpace::DisplayP3 => "DisplayP3",
-            ChromaSpace::AcesAP0 => "AcesAPO",
-            ChromaSpace::AcesAP1 => "AcesAP1",
-            ChromaSpace::AdobeRGB => "AdobeRGB",
-            ChromaSpace::AdobeWideGamutRGB => "AdobeWideGamutRGB",
-            ChromaSpace::ARRIWideGamut3 => "ARRIWideGamut3",
-            ChromaSpace::ARRIWideGamut4 => "ARRIWideGamut4",
-            ChromaSpace::BlackmagicWideGamutGen4 => "BlackmagicWideGamutGen4",
-            ChromaSpace::CanonCinemaGamut => "CanonCinemaGamut",
-            ChromaSpace::DavinciWideGamut => "DavinciWideGamut",
-            ChromaSpace::DJIDGamut => "DJIDGamut",
-            ChromaSpace::EGamut => "EGamut",
-            ChromaSpace::PanasonicVGamut => "PanasonicVGamut",
-            ChromaSpace::ProPhoto => "ProPhoto",
-            ChromaSpace::RedWideGamutRGB => "RedWideGamutRGB",
-            ChromaSpace::SGamut => "SGamut",
-            ChromaSpace::SGamut3Cine => "SGamut3Cine",
-        }
-    }
-
-    fn from_str(text: &str) -> Option<ChromaSpace> {
-        match text {
-            "None" => Some(ChromaSpace::None),
-            "Custom" => Some(ChromaSpace::Custom),
-            "Rec709" => Some(ChromaSpace::Rec709),
-            "Rec2020" => Some(ChromaSpace::Rec2020),
-            "DciP3" => Some(ChromaSpace::DciP3),
-            "DisplayP3" => Some(ChromaSpace::DisplayP3),
-            "AcesAPO" => Some(ChromaSpace::AcesAP0),
-            "AcesAP1" => Some(ChromaSpace::AcesAP1),
-            "AdobeRGB" => Some(ChromaSpace::AdobeRGB),
-            "AdobeWideGamutRGB" => Some(ChromaSpace::AdobeWideGamutRGB),
-            "ARRIWideGamut3" => Some(ChromaSpace::ARRIWideGamut3),
-            "ARRIWideGamut4" => Some(ChromaSpace::ARRIWideGamut4),
-            "BlackmagicWideGamutGen4" => Some(ChromaSpace::BlackmagicWideGamutGen4),
-            "CanonCinemaGamut" => Some(ChromaSpace::CanonCinemaGamut),
-            "DavinciWideGamut" => Some(ChromaSpace::DavinciWideGamut),
-            "DJIDGamut" => Some(ChromaSpace::DJIDGamut),
-            "EGamut" => Some(ChromaSpace::EGamut),
-            "PanasonicVGamut" => Some(ChromaSpace::PanasonicVGamut),
-            "ProPhoto" => Some(ChromaSpace::ProPhoto),
-            "RedWideGamutRGB" => Some(ChromaSpace::RedWideGamutRGB),
-            "SGamut" => Some(ChromaSpace::SGamut),
-            "SGamut3Cine" => Some(ChromaSpace::SGamut3Cine),
+            LookTransformSpec::CDL { .. } => "CDL",
+            LookTransformSpec::GradingPrimary { .. } => "Grading Primary",
+            LookTransformSpec::File { .. } => "LUT File",
+        }
+    }
+}
+
+impl LookSpec {
+    fn with_name(name: &str) -> LookSpec {
+        LookSpec {
+            name: name.into(),
+            process_space: String::new(),
+            transforms: Vec::new(),
+        }
+    }
+
+    /// If `base_path` is specified, then all paths will be written as relative to that path.
+    fn to_string<P: AsRef<Path>>(&self, base_path: Option<P>) -> String {
+        let mut s = String::new();
+
+        s.push_str(&format!("look: {}\n", self.name.trim()));
+        s.push_str(&format!("process_space: {}\n", self.process_space.trim()));
+        for transform in self.transforms.iter() {
+            match *transform {
+                LookTransformSpec::CDL {
+                    slope,
+                    offset,
+                    power,
+                    saturation,
+                } => s.push_str(&format!(
+                    "cdl: {} {} {} {} {} {} {} {} {} {}\n",
+                    slope[0],
+                    slope[1],
+                    slope[2],
+                    offset[0],
+                    offset[1],
+                    offset[2],
+                    power[0],
+                    power[1],
+                    power[2],
+                    saturation,
+                )),
+                LookTransformSpec::GradingPrimary {
+                    style,
+                    contrast,
+                    saturation,
+                    pivot,
+                } => s.push_str(&format!(
+                    "grading_primary: {} {} {} {} {} {}\n",
+                    style.as_str(),
+                    contrast[0],
+                    contrast[1],
+                    contrast[2],
+                    saturation,
+                    pivot,
+                )),
+                LookTransformSpec::File { ref path, .. } => {
+                    let path = relative_path(path, base_path.as_ref());
+                    s.push_str(&format!("lut_path: {}\n", path.to_string_lossy()));
+                }
+            }
+        }
+
+        s
+    }
+
+    /// If `base_path` is specified, then all relative paths will be interpretted in terms of it.
+    fn from_str<P: AsRef<Path>>(
+        text: &str,
+        base_path: Option<P>,
+    ) -> (LookSpec, Result<(), ConfigLoadErr>) {
+        let mut look = LookSpec::with_name("");
+        let mut result = Ok(());
+
+        for line in text.lines() {
+            if let Some((param, value)) = line.split_once(":") {
+                let param = param.trim();
+                let value = value.trim();
+                match param {
+                    "look" => look.name = value.into(),
+                    "process_space" => look.process_space = value.into(),
+                    "cdl" => {
+                        let values: Vec<f32> = value
+                            .split_whitespace()
+                            .map(|v| v.parse::<f32>())
+                            .filter(|v| v.is_ok())
+                            .map(|v| v.unwrap())
+                            .collect();
+                        if values.len() == 10 {
+                            look.transforms.push(LookTransformSpec::CDL {
+                                slope: [values[0], values[1], values[2]],
+                                offset: [values[3], values[4], values[5]],
+                                power: [values[6], values[7], values[8]],
+                                saturation: values[9],
+                            });
+                        }
+                    }
+                    "grading_primary" => {
+                        let mut parts = value.split_whitespace();
+                        let style = parts.next().map(|p| GradingStyle::from_str(p)).flatten();
+                        let values: Vec<f32> = parts
+                            .map(|v| v.parse::<f32>())
+                            .filter(|v| v.is_ok())
+                            .map(|v| v.unwrap())
+                            .collect();
+                        if let (Some(style), 5) = (style, values.len()) {
+                            look.transforms.push(LookTransformSpec::GradingPrimary {
+                                style: style,
+                                contrast: [values[0], values[1], values[2]],
+                                saturation: values[3],
+                                pivot: values[4],
+                            });
+                        }
+                    }
+                    "lut_path" => {
+                        let path: PathBuf = if let Some(ref base_path) = base_path {
+                            base_path.as_ref().join(value)
+                        } else {
+                            value.into()
+                        };
+                        match LookTransformSpec::load_file(path) {
+                            Some(LookTransformSpec::File { lut_1d, lut_3d, .. }) => {
+                                look.transforms.push(LookTransformSpec::File {
+                                    lut_1d: lut_1d,
+                                    lut_3d: lut_3d,
+                                    path: value.into(),
+                                })
+                            }
+                            _ => result = Err(ConfigLoadErr::FileUnloadable(value.into())),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        (look, result)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedTransformSpec {
+    name: String,
+    source: NamedTransformSource,
+    source_is_inverse: bool, // Whether the source defines the inverse direction.
+}
+
+#[derive(Debug, Clone)]
+pub enum NamedTransformSource {
+    Builtin(String), // Name of an OCIO built-in transform.
+    File {
+        lut_1d: Option<Lut1D>, // A 1D LUT, or the shaper of the 3D LUT.
+        lut_3d: Option<Lut3D>,
+        path: PathBuf,
+    },
+}
+
+impl PartialEq for NamedTransformSource {
+    fn eq(&self, other: &Self) -> bool {
+        use NamedTransformSource::*;
+        match (self, other) {
+            (Builtin(name1), Builtin(name2)) => name1 == name2,
+            // LUTs are compared by their source file, like in `ColorSpaceSpec`.
+            (File { path: path1, .. }, File { path: path2, .. }) => path1 == path2,
+            _ => false,
+        }
+    }
+}
+
+/// Some commonly useful OCIO built-in transforms, offered as presets in
+/// the UI.  Any built-in transform name can be typed in, however.
+pub const BUILTIN_TRANSFORMS: &[&str] = &[
+    "CURVE - LINEAR_to_ST-2084",
+    "CURVE - ST-2084_to_LINEAR",
+    "DISPLAY - CIE-XYZ-D65_to_sRGB",
+    "DISPLAY - CIE-XYZ-D65_to_REC.1886-REC.709",
+    "DISPLAY - CIE-XYZ-D65_to_REC.2100-PQ",
+    "ACEScct_to_ACES2065-1",
+    "ACEScc_to_ACES2065-1",
+    "ACEScg_to_ACES2065-1",
+    "ACES-LMT - ACES 1.3 Reference Gamut Compression",
+    "ARRI_ALEXA-LOGC-EI800-AWG_to_ACES2065-1",
+    "SONY_SLOG3-SGAMUT3_to_ACES2065-1",
+    "PANASONIC_VLOG-VGAMUT_to_ACES2065-1",
+    "CANON_CLOG3-CGAMUT_to_ACES2065-1",
+    "RED_LOG3G10-RWG_to_ACES2065-1",
+];
+
+impl NamedTransformSpec {
+    fn with_name(name: &str) -> NamedTransformSpec {
+        NamedTransformSpec {
+            name: name.into(),
+            source: NamedTransformSource::Builtin(BUILTIN_TRANSFORMS[0].into()),
+            source_is_inverse: false,
+        }
+    }
+
+    /// Loads a .cube or .spi1d file as a named transform source.  Both
+    /// 1D and 3D LUTs are supported.
+    fn load_file<P: AsRef<Path>>(path: P) -> Option<NamedTransformSource> {
+        match LookTransformSpec::load_file(path) {
+            Some(LookTransformSpec::File {
+                lut_1d,
+                lut_3d,
+                path,
+            }) => Some(NamedTransformSource::File {
+                lut_1d: lut_1d,
+                lut_3d: lut_3d,
+                path: path,
+            }),
             _ => None,
         }
     }
+
+    /// If `base_path` is specified, then all paths will be written as relative to that path.
+    fn to_string<P: AsRef<Path>>(&self, base_path: Option<P>) -> String {
+        let mut s = String::new();
+
+        s.push_str(&format!("named_transform: {}\n", self.name.trim()));
+        match self.source {
+            NamedTransformSource::Builtin(ref name) => {
+                s.push_str(&format!("builtin: {}\n", name.trim()));
+            }
+            NamedTransformSource::File { ref path, .. } => {
+                let path = relative_path(path, base_path.as_ref());
+                s.push_str(&format!("lut_path: {}\n", path.to_string_lossy()));
+            }
+        }
+        s.push_str(&format!(
+            "source_is_inverse: {:?}\n",
+            self.source_is_inverse
+        ));
+
+        s
+    }
+
+    /// If `base_path` is specified, then all relative paths will be interpretted in terms of it.
+    fn from_str<P: AsRef<Path>>(
+        text: &str,
+        base_path: Option<P>,
+    ) -> (NamedTransformSpec, Result<(), ConfigLoadErr>) {
+        let mut named_transform = NamedTransformSpec::with_name("");
+        let mut result = Ok(());
+
+        for line in text.lines() {
+            if let Some((param, value)) = line.split_once(":") {
+                let param = param.trim();
+                let value = value.trim();
+                match param {
+                    "named_transform" => named_transform.name = value.into(),
+                    "builtin" => {
+                        named_transform.source = NamedTransformSource::Builtin(value.into())
+                    }
+                    "lut_path" => {
+                        let path: PathBuf = if let Some(ref base_path) = base_path {
+                            base_path.as_ref().join(value)
+                        } else {
+                            value.into()
+                        };
+                        match NamedTransformSpec::load_file(path) {
+                            Some(NamedTransformSource::File { lut_1d, lut_3d, .. }) => {
+                                named_transform.source = NamedTransformSource::File {
+                                    lut_1d: lut_1d,
+                                    lut_3d: lut_3d,
+                                    path: value.into(),
+                                }
+                            }
+                            _ => result = Err(ConfigLoadErr::FileUnloadable(value.into())),
+                        }
+                    }
+                    "source_is_inverse" => named_transform.source_is_inverse = value == "true",
+                    _ => {}
+                }
+            }
+        }
+
+        (named_transform, result)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplaySpec {
+    name: String,
+    views: Vec<ViewSpec>,
+    active: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewSpec {
+    name: String,
+    color_space: String,
+    active: bool,
+}
+
+impl DisplaySpec {
+    fn with_name(name: &str) -> DisplaySpec {
+        DisplaySpec {
+            name: name.into(),
+            views: Vec::new(),
+            active: true,
+        }
+    }
+
+    fn to_string(&self) -> String {
+        let mut s = String::new();
+
+        s.push_str(&format!("display: {}\n", self.name.trim()));
+        s.push_str(&format!("display_active: {:?}\n", self.active));
+        for view in self.views.iter() {
+            s.push_str(&format!("view: {}\n", view.name.trim()));
+            s.push_str(&format!("view_color_space: {}\n", view.color_space.trim()));
+            s.push_str(&format!("view_active: {:?}\n", view.active));
+        }
+
+        s
+    }
+
+    fn from_str(text: &str) -> DisplaySpec {
+        let mut display = DisplaySpec::with_name("");
+
+        for line in text.lines() {
+            if let Some((param, value)) = line.split_once(":") {
+                let param = param.trim();
+                let value = value.trim();
+                match param {
+                    "display" => display.name = value.into(),
+                    "display_active" => display.active = value == "true",
+                    "view" => display.views.push(ViewSpec {
+                        name: value.into(),
+                        color_space: String::new(),
+                        active: true,
+                    }),
+                    "view_color_space" => {
+                        if let Some(view) = display.views.last_mut() {
+                            view.color_space = value.into();
+                        }
+                    }
+                    "view_active" => {
+                        if let Some(view) = display.views.last_mut() {
+                            view.active = value == "true";
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        display
+    }
+}
+
+/// Roles that can be overridden in the UI.  `reference` is deliberately
+/// absent, since all of the base config's color spaces are defined
+/// relative to it.
+pub const ROLES: &[&str] = &[
+    "scene_linear",
+    "rendering",
+    "compositing_linear",
+    "compositing_log",
+    "color_timing",
+    "color_picking",
+    "texture_paint",
+    "matte_paint",
+    "default",
+    "default_byte",
+    "default_float",
+    "data",
+    "aces_interchange",
+    "cie_xyz_d65_interchange",
+];
+
+fn roles_to_string(roles: &[(String, String)]) -> String {
+    let mut s = String::from("roles:\n");
+    for (role, space) in roles.iter() {
+        s.push_str(&format!("{}: {}\n", role.trim(), space.trim()));
+    }
+    s
+}
+
+fn roles_from_str(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(":"))
+        .map(|(role, space)| (role.trim().into(), space.trim().into()))
+        .collect()
+}
+
+/// Parses view transform adjustments, clamping them to the ranges
+/// offered in the UI.  Non-finite values are rejected.
+fn tone_map_params_from_str(text: &str) -> Option<ocio_gen::ToneMapParams> {
+    use base_config::{CONTRAST_RANGE, SATURATION_RANGE, SHOULDER_RANGE};
+
+    let values: Vec<f64> = text
+        .split_whitespace()
+        .map(|n| n.parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    if values.iter().any(|n| !n.is_finite()) {
+        return None;
+    }
+    let clamp =
+        |n: f64, range: std::ops::RangeInclusive<f64>| n.max(*range.start()).min(*range.end());
+    match values[..] {
+        [contrast, shoulder, saturation] => Some(ocio_gen::ToneMapParams {
+            contrast: clamp(contrast, CONTRAST_RANGE),
+            shoulder: clamp(shoulder, SHOULDER_RANGE),
+            saturation: clamp(saturation, SATURATION_RANGE),
+        }),
+        _ => None,
+    }
+}
+
+/// A file rule, assigning a color space to files whose paths match a
+/// pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRuleSpec {
+    name: String,
+    pattern: String,   // Glob pattern for the path without extension.
+    extension: String, // Glob pattern for the extension.
+    color_space: String,
+}
+
+fn file_rules_to_string(rules: &[FileRuleSpec]) -> String {
+    let mut s = String::from("file_rules:\n");
+    for rule in rules.iter() {
+        s.push_str(&format!(
+            "{} | {} | {} | {}\n",
+            rule.name.trim(),
+            rule.pattern.trim(),
+            rule.extension.trim(),
+            rule.color_space.trim()
+        ));
+    }
+    s
+}
+
+fn file_rules_from_str(text: &str) -> Vec<FileRuleSpec> {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('|').map(|f| f.trim()).collect();
+            match fields[..] {
+                [name, pattern, extension, color_space] => Some(FileRuleSpec {
+                    name: name.into(),
+                    pattern: pattern.into(),
+                    extension: extension.into(),
+                    color_space: color_space.into(),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Makes `name` active or inactive in an OCIO active displays/views list.
+///
+/// An empty list means everything is active, so it's populated from
+/// `all_names` before deactivating anything.
+fn set_active(active_list: &mut Vec<String>, all_names: &[String], name: &str, active: bool) {
+    let is_listed = active_list.iter().any(|n| n == name);
+    if active {
+        if !active_list.is_empty() && !is_listed {
+            active_list.push(name.into());
+        }
+    } else {
+        if active_list.is_empty() {
+            active_list.extend(all_names.iter().cloned());
+        }
+        active_list.retain(|n| n != name);
+    }
+}
+
+fn all_display_names(displays: &[ocio_gen::config::Display]) -> Vec<String> {
+    displays.iter().map(|d| d.name.clone()).collect()
+}
+
+fn all_view_names(displays: &[ocio_gen::config::Display]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for display in displays.iter() {
+        for (name, _) in display.views.iter() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+/// Reverses the newline escaping done when writing descriptions into the
+/// header comment.
+fn unescape_newlines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Escapes a user-provided name for use in the OCIO config.
+fn escape_name(name: &str) -> String {
+    name.trim()
+        .replace("\\", "\\\\")
+        .replace("#", "\\#")
+        .replace("\"", "\\\"")
+        .replace("]", "\\]")
+        .replace("}", "\\}")
+}
+
+/// Returns whether the path has the extension of a LUT format that
+/// color spaces can use as their transfer LUT.
+fn is_transfer_lut_path(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case("cube") || ext.eq_ignore_ascii_case("spi1d"),
+        None => false,
+    }
+}
+
+/// Converts `path` to be relative to `base_path`, if a base path is
+/// given.
+fn relative_path<P: AsRef<Path>>(path: &Path, base_path: Option<P>) -> PathBuf {
+    let path = path.canonicalize().unwrap_or_else(|_| path.into());
+    if let Some(base) = base_path {
+        let base: &Path = base.as_ref();
+        let base = base.canonicalize().unwrap_or_else(|_| base.into());
+        pathdiff::diff_paths(&path, &base).unwrap_or_else(|| path)
+    } else {
+        path
+    }
 }
 
 #[derive(Debug, Clone)]
 enum ConfigLoadErr {
     FileUnloadable(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tone_map_params_from_str_01() {
+        assert_eq!(
+            tone_map_params_from_str("1.5 0.5 0.0"),
+            Some(ocio_gen::ToneMapParams {
+                contrast: 1.5,
+                shoulder: 0.5,
+                saturation: 0.0,
+            })
+        );
+
+        // Out of range values are clamped to the UI's ranges.
+        assert_eq!(
+            tone_map_params_from_str("-1.0 100.0 -0.5"),
+            Some(ocio_gen::ToneMapParams {
+                contrast: 0.5,
+                shoulder: 4.0,
+                saturation: 0.0,
+            })
+        );
+        assert_eq!(
+            tone_map_params_from_str("0.0 0.0 1e300"),
+            Some(ocio_gen::ToneMapParams {
+                contrast: 0.5,
+                shoulder: 0.25,
+                saturation: 2.0,
+            })
+        );
+
+        // Non-finite and malformed values are rejected.
+        assert_eq!(tone_map_params_from_str("NaN 1.0 1.0"), None);
+        assert_eq!(tone_map_params_from_str("1.0 inf 1.0"), None);
+        assert_eq!(tone_map_params_from_str("1.0 1.0 -inf"), None);
+        assert_eq!(tone_map_params_from_str("1.0 1.0"), None);
+        assert_eq!(tone_map_params_from_str("1.0 one 1.0"), None);
+    }
+}
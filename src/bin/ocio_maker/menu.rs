@@ -1,6 +1,9 @@
 use std::path::PathBuf;
 
+use lib::undo::HistoryAction;
+
 use crate::egui::{self, vec2, Align, Color32, Context, Stroke};
+use crate::LutPaths;
 
 pub fn menu_bar(
     ctx: &Context,
@@ -18,6 +21,16 @@ pub fn menu_bar(
         d
     };
 
+    let import_base_config_dialog = {
+        let mut d = rfd::FileDialog::new()
+            .set_title("Import Base Config")
+            .add_filter("OCIO config", &["ocio"]);
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
+
     let select_export_directory_dialog = {
         let mut d = rfd::FileDialog::new().set_title("Select Export Directory");
         let export_path: PathBuf = app.ui_data.lock().export_path.clone().into();
@@ -51,11 +64,69 @@ pub fn menu_bar(
                             }
                         }
                     }
+                    if ui
+                        .add_enabled(
+                            job_count == 0,
+                            egui::widgets::Button::new("Import Base Config..."),
+                        )
+                        .on_hover_text("Use an existing OCIO config, such as a studio config, as the base to add to.")
+                        .clicked()
+                    {
+                        if let Some(path) = import_base_config_dialog.clone().pick_file() {
+                            app.import_base_config(&path);
+                            if let Some(parent) = path.parent().map(|p| p.into()) {
+                                *working_dir = parent;
+                            }
+                        }
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(job_count == 0, egui::widgets::Button::new("Bake LUT..."))
+                        .on_hover_text("Bake the transform from an input color space to a display/view into a 3D LUT.")
+                        .clicked()
+                    {
+                        app.open_bake_lut();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(job_count == 0, egui::widgets::Button::new("Soft Proof..."))
+                        .on_hover_text("Preview a test image through an input color space and display/view of the config, before exporting it.")
+                        .clicked()
+                    {
+                        app.open_soft_proof();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    ui.menu_button("Preferences", |ui| {
+                        app.preferences.ui(ui, crate::DEFAULT_WORKER_COUNT);
+                    });
                     ui.separator();
                     if ui.add(egui::widgets::Button::new("Quit")).clicked() {
                         ctx.send_viewport_cmd(egui::viewport::ViewportCommand::Close);
                     }
                 });
+                egui::menu::menu_button(ui, "Edit", |ui| {
+                    if ui
+                        .add_enabled(
+                            job_count == 0 && app.undo_stack.can_undo(),
+                            egui::widgets::Button::new("Undo").shortcut_text("Ctrl+Z"),
+                        )
+                        .clicked()
+                    {
+                        app.apply_history_action(HistoryAction::Undo);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            job_count == 0 && app.undo_stack.can_redo(),
+                            egui::widgets::Button::new("Redo").shortcut_text("Ctrl+Shift+Z"),
+                        )
+                        .clicked()
+                    {
+                        app.apply_history_action(HistoryAction::Redo);
+                        ui.close_menu();
+                    }
+                });
             });
 
             // Export UI.
@@ -68,6 +139,40 @@ pub fn menu_bar(
                 {
                     app.export_config();
                 }
+                ui.checkbox(&mut ui_data.p3_displays, "P3 Displays")
+                    .on_hover_text("Add DCI-P3 and P3-D65 theatrical displays (2.6 gamma, gamut clipped) to the config, if the base config doesn't already have them.");
+                ui.checkbox(&mut ui_data.v1_compatible, "OCIO v1")
+                    .on_hover_text("Export a config that OCIO v1 applications can read.  Transforms that only exist in OCIO v2 are baked into LUTs, and anything that can't be represented is left out and reported.");
+                ui.checkbox(&mut ui_data.verify_export, "Verify")
+                    .on_hover_text("After exporting, reload the config and its LUT files, and check that each color space round-trips (to the reference space and back) accurately.");
+                if let LutPaths::Directory(ref mut dir) = ui_data.lut_paths {
+                    ui.add(
+                        egui::widgets::TextEdit::singleline(dir)
+                            .id(egui::Id::new("LUT Directory"))
+                            .desired_width(64.0),
+                    )
+                    .on_hover_text("The directory to copy LUT files to, relative to the config directory.");
+                }
+                egui::ComboBox::from_id_source("LUT Paths")
+                    .selected_text(ui_data.lut_paths.ui_text())
+                    .show_ui(ui, |ui| {
+                        for lut_paths in vec![
+                            LutPaths::Copy,
+                            LutPaths::InPlace,
+                            LutPaths::Directory("luts".into()),
+                        ] {
+                            let selected = std::mem::discriminant(&ui_data.lut_paths)
+                                == std::mem::discriminant(&lut_paths);
+                            if ui.selectable_label(selected, lut_paths.ui_text()).clicked()
+                                && !selected
+                            {
+                                ui_data.lut_paths = lut_paths;
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Where the config's LUT files go.  \"Copy to ocio_maker/\" copies them with generated names.  \"Reference in Place\" uses the original files where they are, relative to the config.  \"Copy to Directory\" copies them with their original names.");
+                ui.label("LUTs:");
                 ui.add_space(16.0);
                 if ui.button("Browse...").clicked() {
                     if let Some(path) = select_export_directory_dialog.pick_folder() {
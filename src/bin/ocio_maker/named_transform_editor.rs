@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+
+use crate::egui::{self, Ui};
+use crate::{NamedTransformSource, NamedTransformSpec};
+
+pub fn editor(
+    ui: &mut Ui,
+    named_transform: &mut NamedTransformSpec,
+    named_transform_id: &str,
+    working_dir: &mut PathBuf,
+) -> Result<(), String> {
+    let load_lut_dialog = {
+        let mut d = rfd::FileDialog::new()
+            .set_title("Load LUT")
+            .add_filter("All Supported LUTs", &["spi1d", "cube"])
+            .add_filter("cube", &["cube"])
+            .add_filter("spi1d", &["spi1d"]);
+        if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+            d = d.set_directory(&working_dir);
+        }
+        d
+    };
+
+    ui.label("Named transforms are applied as-is, without going through the reference space.  Useful for e.g. \"curve only\" utility transforms.  Requires OCIO 2.0 or later.");
+    ui.add_space(8.0);
+
+    // Name.
+    ui.horizontal(|ui| {
+        ui.label("Name: ");
+        ui.add(
+            egui::widgets::TextEdit::singleline(&mut named_transform.name)
+                .id(egui::Id::new(named_transform_id)),
+        )
+        .on_hover_text("Named transforms share a namespace with color spaces, so this must not match any color space name.");
+    });
+    ui.add_space(8.0);
+
+    // Source.
+    let mut result = Ok(());
+    ui.horizontal(|ui| {
+        ui.label("Source: ");
+        let mut use_builtin = match named_transform.source {
+            NamedTransformSource::Builtin(_) => true,
+            NamedTransformSource::File { .. } => false,
+        };
+        let builtin_changed = ui.radio_value(&mut use_builtin, true, "Built-in").changed();
+        if builtin_changed {
+            named_transform.source =
+                NamedTransformSource::Builtin(crate::BUILTIN_TRANSFORMS[0].into());
+        }
+        if ui
+            .radio(!use_builtin, "LUT File...")
+            .on_hover_text("Load a 1D or 3D LUT to use as the transform.")
+            .clicked()
+        {
+            if let Some(path) = load_lut_dialog.clone().pick_file() {
+                if let Some(parent) = path.parent().map(|p| p.into()) {
+                    *working_dir = parent;
+                }
+
+                match NamedTransformSpec::load_file(&path) {
+                    Some(source) => named_transform.source = source,
+                    None => {
+                        result = Err(format!(
+                            "Unable to load LUT file \"{}\".",
+                            path.to_string_lossy()
+                        ));
+                    }
+                }
+            }
+        }
+    });
+    ui.indent("named_transform_source", |ui| {
+        match named_transform.source {
+            NamedTransformSource::Builtin(ref mut name) => {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::widgets::TextEdit::singleline(name)
+                            .desired_width(320.0)
+                            .id(egui::Id::new(format!("{}_builtin", named_transform_id))),
+                    )
+                    .on_hover_text("The style name of any OCIO built-in transform.");
+                    egui::ComboBox::from_id_source("Builtin Transform")
+                        .width(32.0)
+                        .selected_text("")
+                        .show_ui(ui, |ui| {
+                            for builtin in crate::BUILTIN_TRANSFORMS.iter() {
+                                ui.selectable_value(name, String::from(*builtin), *builtin);
+                            }
+                        });
+                });
+            }
+            NamedTransformSource::File {
+                ref lut_1d,
+                ref lut_3d,
+                ref path,
+            } => {
+                ui.horizontal(|ui| {
+                    ui.strong(if let Some(name) = path.file_name() {
+                        let tmp: String = name.to_string_lossy().into();
+                        tmp
+                    } else {
+                        "Unnamed LUT".into()
+                    });
+                    ui.label(match (lut_1d.is_some(), lut_3d.is_some()) {
+                        (true, true) => "(3D LUT with shaper)",
+                        (false, true) => "(3D LUT)",
+                        _ => "(1D LUT)",
+                    });
+                });
+            }
+        }
+    });
+    ui.add_space(8.0);
+
+    ui.checkbox(
+        &mut named_transform.source_is_inverse,
+        "Source Defines the Inverse Direction",
+    )
+    .on_hover_text("Enable if the source is e.g. a linear-to-log curve, but the named transform should be log-to-linear.");
+
+    result
+}
@@ -0,0 +1,52 @@
+use crate::egui::{self, Ui};
+
+pub fn list(ui: &mut Ui, app: &mut crate::AppMain, job_count: usize) {
+    let mut remove_i = None;
+    let mut add_named_transform = false;
+
+    ui.add_space(4.0);
+    ui.horizontal(|ui| {
+        add_named_transform |= ui.button("New  ➕").clicked();
+    });
+    ui.add_space(4.0);
+
+    egui::containers::ScrollArea::vertical()
+        .auto_shrink([true, false])
+        .show(ui, |ui| {
+            let ui_data = &mut *app.ui_data.lock_mut();
+
+            let mut named_transform_i = 0;
+            let mut selected_i = ui_data.selected_named_transform_index;
+
+            for named_transform in ui_data.named_transforms.iter() {
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(job_count == 0, egui::widgets::Button::new("🗙"))
+                        .clicked()
+                    {
+                        remove_i = Some(named_transform_i);
+                    }
+                    if ui
+                        .add(egui::widgets::SelectableLabel::new(
+                            named_transform_i == ui_data.selected_named_transform_index,
+                            &named_transform.name,
+                        ))
+                        .clicked()
+                    {
+                        selected_i = named_transform_i;
+                    }
+                });
+
+                named_transform_i += 1;
+            }
+
+            ui_data.selected_named_transform_index = selected_i;
+        });
+
+    if add_named_transform {
+        app.add_named_transform();
+    }
+    if let Some(named_transform_i) = remove_i {
+        app.remove_named_transform(named_transform_i);
+    }
+}
@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use crate::egui::{self, Context};
+
+pub fn ui(ctx: &Context, app: &mut crate::AppMain, working_dir: &mut PathBuf, job_count: usize) {
+    // Named transform list (left-side panel).
+    egui::containers::panel::SidePanel::left("named_transform_list")
+        .resizable(false)
+        .show(ctx, |ui| {
+            crate::named_transform_list::list(ui, app, job_count);
+        });
+
+    // Main area.
+    egui::containers::panel::CentralPanel::default().show(ctx, |ui| {
+        // Main UI area.
+        let selected_index = app.ui_data.lock().selected_named_transform_index;
+        if selected_index < app.ui_data.lock().named_transforms.len() {
+            let mut ui_data = app.ui_data.lock_mut();
+            let named_transform = &mut ui_data.named_transforms[selected_index];
+
+            if let Err(message) = crate::named_transform_editor::editor(
+                ui,
+                named_transform,
+                &format!("named_transform{}", selected_index),
+                working_dir,
+            ) {
+                app.job_queue.log_error(message);
+            };
+        }
+    });
+}
@@ -0,0 +1,77 @@
+use crate::egui::{self, Context};
+
+pub fn ui(ctx: &Context, app: &mut crate::AppMain) {
+    egui::containers::panel::CentralPanel::default().show(ctx, |ui| {
+        ui.label("Roles assigned here override those of the base config.  Any color space in the exported config can be used, including those of the base config.");
+        ui.add_space(8.0);
+
+        let ui_data = &mut *app.ui_data.lock_mut();
+        let space_names: Vec<String> = ui_data
+            .color_spaces
+            .iter()
+            .map(|s| s.name.trim().into())
+            .collect();
+
+        let mut remove = None;
+        egui::Grid::new("roles")
+            .min_col_width(4.0)
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label("Role");
+                ui.label("");
+                ui.label("Color Space");
+                ui.label("");
+                ui.end_row();
+
+                for (i, (role, space)) in ui_data.roles.iter_mut().enumerate() {
+                    if ui.button("🗙").clicked() {
+                        remove = Some(i);
+                    }
+
+                    ui.add(
+                        egui::widgets::TextEdit::singleline(role)
+                            .id(egui::Id::new(format!("role{}", i))),
+                    );
+                    egui::ComboBox::from_id_source(format!("role_name{}", i))
+                        .width(32.0)
+                        .selected_text("")
+                        .show_ui(ui, |ui| {
+                            for name in crate::ROLES.iter() {
+                                ui.selectable_value(role, String::from(*name), *name);
+                            }
+                        });
+
+                    ui.add(
+                        egui::widgets::TextEdit::singleline(space)
+                            .id(egui::Id::new(format!("role{}_space", i))),
+                    );
+                    if !space_names.is_empty() {
+                        egui::ComboBox::from_id_source(format!("role_space{}", i))
+                            .width(32.0)
+                            .selected_text("")
+                            .show_ui(ui, |ui| {
+                                for name in space_names.iter() {
+                                    ui.selectable_value(space, name.clone(), name);
+                                }
+                            });
+                    } else {
+                        ui.label("");
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = remove {
+            ui_data.roles.remove(i);
+        }
+        ui.add_space(4.0);
+
+        if ui.button("Add Role").clicked() {
+            // Default to the first role that isn't assigned yet.
+            let role = crate::ROLES
+                .iter()
+                .find(|r| !ui_data.roles.iter().any(|(role, _)| role == *r))
+                .unwrap_or(&crate::ROLES[0]);
+            ui_data.roles.push(((*role).into(), String::new()));
+        }
+    });
+}
@@ -0,0 +1,368 @@
+use std::path::PathBuf;
+
+use job_queue::{JobQueue, Priority};
+use ocio_gen::{config::OCIOConfig, eval::Evaluator};
+use shared_data::Shared;
+
+use crate::egui::{self, Context};
+
+const PREVIEW_WIDTH: usize = 512;
+const PREVIEW_MAX_DISPLAY_HEIGHT: f32 = 400.0;
+
+/// Shows a test image through an (input space -> display/view) chain of
+/// the config as it would currently be exported, for checking the
+/// config visually before exporting it.
+pub struct SoftProof {
+    pub open: bool,
+
+    config: Shared<Option<ProofConfig>>,
+    config_serial: usize, // Incremented each time the config is regenerated.
+    image: Shared<Option<ProofImage>>,
+
+    source: String,
+    display: String,
+    view: String,
+
+    texture: Option<egui::TextureHandle>,
+    last_settings: Option<ProofSettings>,
+    error: Option<String>,
+}
+
+struct ProofConfig {
+    serial: usize,
+    config: OCIOConfig,
+}
+
+/// The downscaled test image, with integer formats normalized to
+/// [0.0, 1.0] and float formats as-is.
+struct ProofImage {
+    path: PathBuf,
+    pixels: Vec<[f32; 3]>,
+    width: usize,
+    height: usize,
+}
+
+/// Everything the preview texture depends on, for detecting when it
+/// needs to be rebuilt.
+#[derive(Clone, PartialEq)]
+struct ProofSettings {
+    config_serial: usize,
+    image_path: PathBuf,
+    source: String,
+    display: String,
+    view: String,
+}
+
+impl SoftProof {
+    pub fn new() -> SoftProof {
+        SoftProof {
+            open: false,
+
+            config: Shared::new(None),
+            config_serial: 0,
+            image: Shared::new(None),
+
+            source: String::new(),
+            display: String::new(),
+            view: String::new(),
+
+            texture: None,
+            last_settings: None,
+            error: None,
+        }
+    }
+
+    /// Regenerates the config from the current UI data, so that the
+    /// preview reflects any edits made since it was last generated.
+    pub fn refresh_config(&mut self, ui_data: &Shared<crate::UIData>, job_queue: &JobQueue) {
+        self.config_serial += 1;
+        let serial = self.config_serial;
+        let ui_data = ui_data.clone_ref();
+        let config = self.config.clone_ref();
+
+        job_queue.add_job("Soft Proof", Priority::Normal, move |status| {
+            status
+                .lock_mut()
+                .set_progress("Generating config".into(), 0.0);
+
+            let export_path = ui_data.lock().export_path.clone();
+            match crate::generate_config(&ui_data, &export_path) {
+                Ok(new_config) => {
+                    *config.lock_mut() = Some(ProofConfig {
+                        serial: serial,
+                        config: new_config,
+                    });
+                }
+                Err(e) => {
+                    status.lock_mut().log_error(format!(
+                        "Failed to generate config for soft proofing: {}",
+                        e
+                    ));
+                }
+            }
+        });
+    }
+
+    fn load_image(&mut self, path: PathBuf, job_queue: &JobQueue) {
+        let image = self.image.clone_ref();
+
+        job_queue.add_job("Load Soft Proof Image", Priority::Normal, move |status| {
+            status
+                .lock_mut()
+                .set_progress(format!("Loading image: {}", path.to_string_lossy()), 0.0);
+
+            match lib::job_helpers::load_image(&path) {
+                Ok(img) => {
+                    let (pixels, width, height) =
+                        lib::job_helpers::make_linear_float_preview(&img.image, PREVIEW_WIDTH);
+                    *image.lock_mut() = Some(ProofImage {
+                        path: path,
+                        pixels: pixels,
+                        width: width,
+                        height: height,
+                    });
+                }
+                Err(_) => {
+                    status.lock_mut().log_error(format!(
+                        "Unable to load image for soft proofing: \"{}\".",
+                        path.to_string_lossy(),
+                    ));
+                }
+            }
+        });
+    }
+
+    /// Rebuilds the preview texture if anything it depends on has
+    /// changed.
+    fn update_texture(&mut self, ctx: &Context) {
+        let config_guard = self.config.lock();
+        let image_guard = self.image.lock();
+        let (config, image) = match (&*config_guard, &*image_guard) {
+            (Some(config), Some(image)) => (config, image),
+            _ => return,
+        };
+
+        let settings = ProofSettings {
+            config_serial: config.serial,
+            image_path: image.path.clone(),
+            source: self.source.clone(),
+            display: self.display.clone(),
+            view: self.view.clone(),
+        };
+        if self.last_settings.as_ref() == Some(&settings) {
+            return;
+        }
+        self.last_settings = Some(settings);
+
+        let evaluator = Evaluator::new(&config.config);
+        let result = evaluator
+            .view_processor(&self.source, &self.display, &self.view)
+            .and_then(|transforms| {
+                let mut bytes = Vec::with_capacity(image.pixels.len() * 3);
+                for pixel in image.pixels.iter() {
+                    let rgb = [pixel[0] as f64, pixel[1] as f64, pixel[2] as f64];
+                    for n in evaluator.eval(&transforms, rgb)?.iter() {
+                        bytes.push((n.max(0.0).min(1.0) * 255.0).round() as u8);
+                    }
+                }
+                Ok(bytes)
+            });
+
+        match result {
+            Ok(bytes) => {
+                let image = egui::ColorImage::from_rgb([image.width, image.height], &bytes);
+                match self.texture {
+                    Some(ref mut texture) => texture.set(image, egui::TextureOptions::LINEAR),
+                    None => {
+                        self.texture = Some(ctx.load_texture(
+                            "Soft Proof",
+                            image,
+                            egui::TextureOptions::LINEAR,
+                        ))
+                    }
+                }
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+}
+
+pub fn window(
+    ctx: &Context,
+    app: &mut crate::AppMain,
+    working_dir: &mut PathBuf,
+    job_count: usize,
+) {
+    if !app.soft_proof.open {
+        return;
+    }
+
+    let mut open = true;
+    let mut refresh = false;
+    let mut image_path = None;
+
+    // Make sure the selections refer to things that exist in the
+    // config, e.g. after it's been regenerated.
+    {
+        let proof = &mut app.soft_proof;
+        if let Some(ref config) = *proof.config.lock() {
+            let config = &config.config;
+            if !config.colorspaces.iter().any(|s| s.name == proof.source) {
+                proof.source = config
+                    .colorspaces
+                    .first()
+                    .map(|s| s.name.clone())
+                    .unwrap_or_else(String::new);
+            }
+            match config.displays.iter().find(|d| d.name == proof.display) {
+                Some(display) if display.views.iter().any(|v| v.0 == proof.view) => {}
+                Some(display) => {
+                    proof.view = display
+                        .views
+                        .first()
+                        .map(|v| v.0.clone())
+                        .unwrap_or_else(String::new);
+                }
+                None => {
+                    let display = config.displays.first();
+                    proof.display = display.map(|d| d.name.clone()).unwrap_or_else(String::new);
+                    proof.view = display
+                        .and_then(|d| d.views.first())
+                        .map(|v| v.0.clone())
+                        .unwrap_or_else(String::new);
+                }
+            }
+        }
+    }
+
+    app.soft_proof.update_texture(ctx);
+
+    egui::Window::new("Soft Proof")
+        .open(&mut open)
+        .resizable(true)
+        .default_width(PREVIEW_WIDTH as f32)
+        .show(ctx, |ui| {
+            let proof = &mut app.soft_proof;
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(job_count == 0, egui::widgets::Button::new("Load Image..."))
+                    .clicked()
+                {
+                    let mut d = rfd::FileDialog::new()
+                        .set_title("Load Test Image")
+                        .add_filter(
+                            "Image",
+                            &[
+                                "jpg", "JPG", "jpeg", "JPEG", "tiff", "TIFF", "tif", "TIF",
+                                "png", "PNG", "exr", "EXR", "hdr", "HDR",
+                            ],
+                        );
+                    if !working_dir.as_os_str().is_empty() && working_dir.is_dir() {
+                        d = d.set_directory(&working_dir);
+                    }
+                    image_path = d.pick_file();
+                }
+                if ui
+                    .add_enabled(job_count == 0, egui::widgets::Button::new("Refresh Config"))
+                    .on_hover_text("Regenerate the config from the current settings, to preview changes made since the soft proof was opened.")
+                    .clicked()
+                {
+                    refresh = true;
+                }
+            });
+            ui.add_space(4.0);
+
+            {
+                let (source, display, view) =
+                    (&mut proof.source, &mut proof.display, &mut proof.view);
+                let config_guard = proof.config.lock();
+                let config = match *config_guard {
+                    Some(ref config) => &config.config,
+                    None => {
+                        ui.label("Generating config...");
+                        return;
+                    }
+                };
+
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Input")
+                        .selected_text(source.as_str())
+                        .show_ui(ui, |ui| {
+                            for space in config.colorspaces.iter() {
+                                ui.selectable_value(
+                                    &mut *source,
+                                    space.name.clone(),
+                                    space.name.as_str(),
+                                );
+                            }
+                        });
+                    ui.add_space(16.0);
+                    let old_display = display.clone();
+                    egui::ComboBox::from_label("Display")
+                        .selected_text(display.as_str())
+                        .show_ui(ui, |ui| {
+                            for d in config.displays.iter() {
+                                ui.selectable_value(
+                                    &mut *display,
+                                    d.name.clone(),
+                                    d.name.as_str(),
+                                );
+                            }
+                        });
+                    let views: Vec<String> = config
+                        .displays
+                        .iter()
+                        .find(|d| d.name == *display)
+                        .map(|d| d.views.iter().map(|v| v.0.clone()).collect())
+                        .unwrap_or_else(Vec::new);
+                    if *display != old_display {
+                        *view = views.first().cloned().unwrap_or_else(String::new);
+                    }
+                    ui.add_space(16.0);
+                    egui::ComboBox::from_label("View")
+                        .selected_text(view.as_str())
+                        .show_ui(ui, |ui| {
+                            for v in views.iter() {
+                                ui.selectable_value(&mut *view, v.clone(), v.as_str());
+                            }
+                        });
+                });
+            }
+            ui.add_space(4.0);
+
+            if proof.image.lock().is_none() {
+                ui.label("Load a test image to preview it through the config.");
+                return;
+            }
+            if let Some(ref error) = proof.error {
+                ui.colored_label(
+                    egui::Color32::from_rgb(0xe0, 0x6c, 0x6c),
+                    format!("Can't preview this view: {}", error),
+                );
+                return;
+            }
+            if let Some(ref texture) = proof.texture {
+                let [width, height] = texture.size();
+                let display_scale = (ui.available_width() / width as f32)
+                    .min(PREVIEW_MAX_DISPLAY_HEIGHT / height as f32)
+                    .max(0.1);
+                ui.add(egui::Image::new(texture).fit_to_exact_size(egui::Vec2::new(
+                    width as f32 * display_scale,
+                    height as f32 * display_scale,
+                )));
+            }
+        });
+
+    if let Some(path) = image_path {
+        if let Some(parent) = path.parent().map(|p| p.into()) {
+            *working_dir = parent;
+        }
+        app.soft_proof.load_image(path, &app.job_queue);
+    }
+    if refresh {
+        app.soft_proof.refresh_config(&app.ui_data, &app.job_queue);
+    }
+    app.soft_proof.open = open;
+}
@@ -25,6 +25,39 @@ pub fn top_bar(ctx: &Context, app: &mut crate::AppMain) {
                     {
                         *selected_tab = Tabs::InputTransforms;
                     };
+                    if ui
+                        .selectable_label(*selected_tab == Tabs::Looks, "Looks")
+                        .clicked()
+                    {
+                        *selected_tab = Tabs::Looks;
+                    };
+                    if ui
+                        .selectable_label(*selected_tab == Tabs::Displays, "Displays")
+                        .clicked()
+                    {
+                        *selected_tab = Tabs::Displays;
+                    };
+                    if ui
+                        .selectable_label(
+                            *selected_tab == Tabs::NamedTransforms,
+                            "Named Transforms",
+                        )
+                        .clicked()
+                    {
+                        *selected_tab = Tabs::NamedTransforms;
+                    };
+                    if ui
+                        .selectable_label(*selected_tab == Tabs::Roles, "Roles")
+                        .clicked()
+                    {
+                        *selected_tab = Tabs::Roles;
+                    };
+                    if ui
+                        .selectable_label(*selected_tab == Tabs::FileRules, "File Rules")
+                        .clicked()
+                    {
+                        *selected_tab = Tabs::FileRules;
+                    };
                 });
             });
         });
@@ -1,14 +1,224 @@
 use std::iter::FromIterator;
 
-use crate::egui::Ui;
+use crate::egui::{self, Color32, Ui};
 
 use lib::colors::*;
+use lib::lut_modify::eval_lut_3d;
+
+/// Settings of the transfer function graph.  They're shared between
+/// color spaces, to make it easy to compare them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GraphSettings {
+    pub from_linear: bool,
+    pub stops: bool, // Show linear values in stops from 18% gray.
+    pub reference: Option<ReferenceCurve>,
+}
+
+impl Default for GraphSettings {
+    fn default() -> GraphSettings {
+        GraphSettings {
+            from_linear: false,
+            stops: false,
+            reference: None,
+        }
+    }
+}
+
+/// Official transfer functions to compare loaded LUTs against.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ReferenceCurve {
+    ArriLogC3,
+    ArriLogC4,
+    BlackmagicFilmGen5,
+    DavinciIntermediate,
+    CanonLog2,
+    CanonLog3,
+    DjiDlog,
+    FujifilmFlog,
+    NikonNlog,
+    PanasonicVlog,
+    RedLog3G10,
+    SonySlog3,
+    Rec709,
+    Srgb,
+}
+
+pub const REFERENCE_CURVES: &[ReferenceCurve] = &[
+    ReferenceCurve::ArriLogC3,
+    ReferenceCurve::ArriLogC4,
+    ReferenceCurve::BlackmagicFilmGen5,
+    ReferenceCurve::DavinciIntermediate,
+    ReferenceCurve::CanonLog2,
+    ReferenceCurve::CanonLog3,
+    ReferenceCurve::DjiDlog,
+    ReferenceCurve::FujifilmFlog,
+    ReferenceCurve::NikonNlog,
+    ReferenceCurve::PanasonicVlog,
+    ReferenceCurve::RedLog3G10,
+    ReferenceCurve::SonySlog3,
+    ReferenceCurve::Rec709,
+    ReferenceCurve::Srgb,
+];
+
+impl ReferenceCurve {
+    pub fn ui_text(&self) -> &'static str {
+        use ReferenceCurve::*;
+        match *self {
+            ArriLogC3 => "ARRI LogC3 (EI 800)",
+            ArriLogC4 => "ARRI LogC4",
+            BlackmagicFilmGen5 => "BMD Film Gen5",
+            DavinciIntermediate => "DaVinci Intermediate",
+            CanonLog2 => "Canon Log 2",
+            CanonLog3 => "Canon Log 3",
+            DjiDlog => "DJI D-Log",
+            FujifilmFlog => "Fujifilm F-Log",
+            NikonNlog => "Nikon N-Log",
+            PanasonicVlog => "Panasonic V-Log",
+            RedLog3G10 => "RED Log3G10",
+            SonySlog3 => "Sony S-Log3",
+            Rec709 => "Rec.709",
+            Srgb => "sRGB",
+        }
+    }
+
+    pub fn to_linear(&self, n: f32) -> f32 {
+        use colorbox::transfer_functions::*;
+        use ReferenceCurve::*;
+        match *self {
+            ArriLogC3 => arri::logc3::to_linear(n, true, arri::logc3::EI::Ei800),
+            ArriLogC4 => arri::logc4::to_linear(n),
+            BlackmagicFilmGen5 => blackmagic::film_gen5::to_linear(n),
+            DavinciIntermediate => blackmagic::davinci_intermediate::to_linear(n),
+            CanonLog2 => canon::log2::to_linear(n),
+            CanonLog3 => canon::log3::to_linear(n),
+            DjiDlog => dji::dlog::to_linear(n),
+            FujifilmFlog => fujifilm::flog::to_linear(n),
+            NikonNlog => nikon::nlog::to_linear(n),
+            PanasonicVlog => panasonic::vlog::to_linear(n),
+            RedLog3G10 => red::log3g10::to_linear(n),
+            SonySlog3 => sony::slog3::to_linear(n),
+            Rec709 => rec709::to_linear(n),
+            Srgb => srgb::to_linear(n),
+        }
+    }
+}
+
+pub fn graph(ui: &mut Ui, space: &crate::ColorSpaceSpec, settings: &mut GraphSettings) {
+    use egui_plot::{Legend, Line, LineStyle, Plot, PlotPoints};
 
-pub fn graph(ui: &mut Ui, space: &mut crate::ColorSpaceSpec) {
     // Visualize transfer function.
-    if let Some((ref lut, _, inverse)) = space.transfer_lut {
-        use egui_plot::{Line, Plot, PlotPoints};
+    let curves = to_linear_curves(space);
+    if curves.is_empty() {
+        return;
+    }
 
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut settings.from_linear, false, "To Linear");
+        ui.radio_value(&mut settings.from_linear, true, "From Linear");
+        ui.add_space(8.0);
+        ui.checkbox(&mut settings.stops, "Stops")
+            .on_hover_text("Show linear values in stops from 18% gray.");
+        ui.add_space(8.0);
+        ui.label("Compare with: ");
+        egui::ComboBox::from_id_source("transfer function reference")
+            .width(200.0)
+            .selected_text(settings.reference.map(|c| c.ui_text()).unwrap_or("None"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut settings.reference, None, "None");
+                for curve in REFERENCE_CURVES.iter() {
+                    ui.selectable_value(&mut settings.reference, Some(*curve), curve.ui_text());
+                }
+            });
+    });
+
+    // Maps an (encoded, linear) pair to its position in the plot, if it
+    // can be shown.
+    let settings = *settings;
+    let point = |encoded: f32, linear: f32| -> Option<[f64; 2]> {
+        let linear = if settings.stops {
+            if linear <= 0.0 {
+                return None;
+            }
+            (linear / 0.18).log2()
+        } else {
+            linear
+        };
+        Some(if settings.from_linear {
+            [linear as f64, encoded as f64]
+        } else {
+            [encoded as f64, linear as f64]
+        })
+    };
+
+    let mut plot = Plot::new("transfer function plot").legend(Legend::default());
+    plot = if settings.stops {
+        let names = ["Encoded", "Linear (stops from 18% gray)"];
+        let (x, y) = if settings.from_linear {
+            (names[1], names[0])
+        } else {
+            (names[0], names[1])
+        };
+        plot.x_axis_label(x).y_axis_label(y)
+    } else {
+        plot.data_aspect(1.0)
+    };
+    plot.show(ui, |plot| {
+        if let Some(reference) = settings.reference {
+            const SAMPLES: usize = 256;
+            plot.line(
+                Line::new(PlotPoints::from_iter((0..SAMPLES).filter_map(|i| {
+                    let n = i as f32 / (SAMPLES - 1) as f32;
+                    point(n, reference.to_linear(n))
+                })))
+                .color(GRAY)
+                .style(LineStyle::Dashed { length: 10.0 })
+                .name(reference.ui_text()),
+            );
+        }
+        for (color, curve) in curves.iter() {
+            plot.line(
+                Line::new(PlotPoints::from_iter(
+                    curve
+                        .iter()
+                        .filter_map(|&(encoded, linear)| point(encoded, linear)),
+                ))
+                .color(*color),
+            );
+        }
+    });
+}
+
+/// The to-linear curves of a color space's transfer LUT, as (encoded,
+/// linear) pairs, along with the color to draw them in.
+fn to_linear_curves(space: &crate::ColorSpaceSpec) -> Vec<(Color32, Vec<(f32, f32)>)> {
+    if let Some((ref lut, ref shaper, _)) = space.transfer_lut_3d {
+        // The neutral axis of the 3D LUT.
+        const SAMPLES: usize = 256;
+        let range = shaper.as_ref().map(|s| s.ranges[0]).unwrap_or(lut.range[0]);
+        let samples: Vec<(f32, [f32; 3])> = (0..SAMPLES)
+            .map(|i| {
+                let x = range.0 + ((range.1 - range.0) * i as f32 / (SAMPLES - 1) as f32);
+                let mut rgb = [x; 3];
+                if let Some(shaper) = shaper {
+                    for chan in 0..3 {
+                        rgb[chan] = shaper.look_up(rgb[chan], chan.min(shaper.tables.len() - 1));
+                    }
+                }
+                (x, eval_lut_3d(lut, rgb))
+            })
+            .collect();
+
+        [RED, GREEN, BLUE]
+            .iter()
+            .enumerate()
+            .map(|(chan, color)| {
+                (
+                    *color,
+                    samples.iter().map(|(x, rgb)| (*x, rgb[chan])).collect(),
+                )
+            })
+            .collect()
+    } else if let Some((ref lut, _, inverse)) = space.transfer_lut {
         let colors: &[_] = if lut.tables.len() == 1 {
             &[WHITE]
         } else if lut.tables.len() <= 4 {
@@ -16,26 +226,30 @@ pub fn graph(ui: &mut Ui, space: &mut crate::ColorSpaceSpec) {
         } else {
             unreachable!()
         };
-        Plot::new("transfer function plot")
-            .data_aspect(1.0)
-            .show(ui, |plot| {
-                for (component, table) in lut.tables.iter().enumerate() {
-                    let range = lut.ranges[component.min(lut.ranges.len() - 1)];
-                    plot.line(
-                        Line::new(PlotPoints::from_iter(
-                            table.iter().copied().enumerate().map(|(i, y)| {
-                                let a = i as f32 / (table.len() - 1).max(1) as f32;
-                                let x = range.0 + (a * (range.1 - range.0));
-                                if inverse {
-                                    [y as f64, x as f64]
-                                } else {
-                                    [x as f64, y as f64]
-                                }
-                            }),
-                        ))
-                        .color(colors[component]),
-                    );
-                }
-            });
+        lut.tables
+            .iter()
+            .enumerate()
+            .map(|(component, table)| {
+                let range = lut.ranges[component.min(lut.ranges.len() - 1)];
+                let curve = table
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .map(|(i, y)| {
+                        let a = i as f32 / (table.len() - 1).max(1) as f32;
+                        let x = range.0 + (a * (range.1 - range.0));
+                        // An inverse LUT maps linear to encoded.
+                        if inverse {
+                            (y, x)
+                        } else {
+                            (x, y)
+                        }
+                    })
+                    .collect();
+                (colors[component], curve)
+            })
+            .collect()
+    } else {
+        Vec::new()
     }
 }
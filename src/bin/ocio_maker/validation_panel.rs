@@ -0,0 +1,413 @@
+use std::path::{Path, PathBuf};
+
+use colorbox::{lut::Lut1D, matrix};
+
+use crate::egui::{self, Context};
+use crate::{escape_name, Tabs, UIData};
+
+/// A problem with the current configuration, that would either make
+/// export fail or produce a broken config.
+pub struct Problem {
+    pub message: String,
+    pub target: Target,
+}
+
+/// What a `Problem` is about, so the UI can jump to it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Target {
+    ColorSpace(usize),
+    Look(usize),
+    Display(usize),
+    NamedTransform(usize),
+    Roles,
+    FileRules,
+}
+
+pub fn panel(ctx: &Context, app: &mut crate::AppMain) {
+    let problems = find_problems(&*app.ui_data.lock());
+    if problems.is_empty() {
+        return;
+    }
+
+    egui::containers::panel::SidePanel::right("validation_panel")
+        .resizable(true)
+        .default_width(256.0)
+        .show(ctx, |ui| {
+            ui.add_space(4.0);
+            ui.strong(format!("⚠ Problems ({})", problems.len()));
+            ui.label("Click a problem to go to it.");
+            ui.add_space(4.0);
+            ui.separator();
+
+            egui::containers::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    for problem in problems.iter() {
+                        let clicked = ui
+                            .add(
+                                egui::widgets::Label::new(problem.message.as_str())
+                                    .sense(egui::Sense::click()),
+                            )
+                            .on_hover_cursor(egui::CursorIcon::PointingHand)
+                            .clicked();
+                        if clicked {
+                            go_to(&mut *app.ui_data.lock_mut(), problem.target);
+                        }
+                        ui.add_space(4.0);
+                    }
+                });
+        });
+}
+
+fn go_to(ui_data: &mut UIData, target: Target) {
+    match target {
+        Target::ColorSpace(i) => {
+            ui_data.selected_tab = Tabs::InputTransforms;
+            ui_data.selected_space_index = i;
+        }
+        Target::Look(i) => {
+            ui_data.selected_tab = Tabs::Looks;
+            ui_data.selected_look_index = i;
+        }
+        Target::Display(i) => {
+            ui_data.selected_tab = Tabs::Displays;
+            ui_data.selected_display_index = i;
+        }
+        Target::NamedTransform(i) => {
+            ui_data.selected_tab = Tabs::NamedTransforms;
+            ui_data.selected_named_transform_index = i;
+        }
+        Target::Roles => {
+            ui_data.selected_tab = Tabs::Roles;
+        }
+        Target::FileRules => {
+            ui_data.selected_tab = Tabs::FileRules;
+        }
+    }
+}
+
+/// Checks the current configuration for problems.
+///
+/// This is meant to be cheap enough to run every frame.  References to
+/// color spaces of the base config are only checked once the base
+/// config's color space names are known (see `UIData::base_space_names`).
+pub fn find_problems(ui_data: &UIData) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    let base_names = match ui_data.base_space_names {
+        Some((preset, ref path, ref names))
+            if preset == ui_data.base_preset && *path == ui_data.imported_config_path =>
+        {
+            Some(names)
+        }
+        _ => None,
+    };
+    let user_names: Vec<String> = ui_data
+        .color_spaces
+        .iter()
+        .map(|s| escape_name(&s.name))
+        .chain(ui_data.color_spaces.iter().flat_map(|s| {
+            s.alias_list()
+                .iter()
+                .map(|a| escape_name(a))
+                .collect::<Vec<_>>()
+        }))
+        .collect();
+    let exists = |name: &str| {
+        let name = escape_name(name);
+        user_names.contains(&name) || base_names.map(|n| n.contains(&name)).unwrap_or(false)
+    };
+    let check_refs = base_names.is_some();
+
+    // LUT paths loaded from a config are relative to it, which is also
+    // where it gets exported.
+    let export_path: PathBuf = ui_data.export_path.clone().into();
+    let file_missing = |path: &Path| {
+        let path = if path.is_relative() {
+            export_path.join(path)
+        } else {
+            path.into()
+        };
+        !path.is_file()
+    };
+
+    //----
+    // Color spaces and named transforms, which share a namespace.
+
+    let mut seen_names: Vec<String> = Vec::new();
+    for (i, space) in ui_data.color_spaces.iter().enumerate() {
+        let name = escape_name(&space.name);
+        if name.is_empty() {
+            problems.push(Problem {
+                message: format!("Color space #{} has no name.", i + 1),
+                target: Target::ColorSpace(i),
+            });
+        } else if seen_names.contains(&name) {
+            problems.push(Problem {
+                message: format!("There is more than one color space named \"{}\".", name),
+                target: Target::ColorSpace(i),
+            });
+        } else if base_names.map(|n| n.contains(&name)).unwrap_or(false) {
+            problems.push(Problem {
+                message: format!(
+                    "Color space \"{}\" has the same name as one in the base config.",
+                    name
+                ),
+                target: Target::ColorSpace(i),
+            });
+        }
+        seen_names.push(name.clone());
+
+        if let Some((ref lut, ref path, _)) = space.transfer_lut {
+            if file_missing(path) {
+                problems.push(Problem {
+                    message: format!(
+                        "The LUT file \"{}\" of color space \"{}\" no longer exists.",
+                        path.to_string_lossy(),
+                        name
+                    ),
+                    target: Target::ColorSpace(i),
+                });
+            }
+            if !is_monotonic(lut) {
+                problems.push(Problem {
+                    message: format!(
+                        "The LUT of color space \"{}\" isn't monotonic, so it can't be inverted accurately.",
+                        name
+                    ),
+                    target: Target::ColorSpace(i),
+                });
+            }
+        }
+        if let Some((_, _, ref path)) = space.transfer_lut_3d {
+            if file_missing(path) {
+                problems.push(Problem {
+                    message: format!(
+                        "The 3D LUT file \"{}\" of color space \"{}\" no longer exists.",
+                        path.to_string_lossy(),
+                        name
+                    ),
+                    target: Target::ColorSpace(i),
+                });
+            }
+        }
+        if let Some(m) = space.input_matrix {
+            let m3 = [
+                [m[0][0], m[0][1], m[0][2]],
+                [m[1][0], m[1][1], m[1][2]],
+                [m[2][0], m[2][1], m[2][2]],
+            ];
+            if matrix::invert(m3).is_none() {
+                problems.push(Problem {
+                    message: format!(
+                        "The input matrix of color space \"{}\" isn't invertible.",
+                        name
+                    ),
+                    target: Target::ColorSpace(i),
+                });
+            }
+        }
+    }
+    for (i, named_transform) in ui_data.named_transforms.iter().enumerate() {
+        let name = escape_name(&named_transform.name);
+        if name.is_empty() {
+            problems.push(Problem {
+                message: format!("Named transform #{} has no name.", i + 1),
+                target: Target::NamedTransform(i),
+            });
+        } else if seen_names.contains(&name)
+            || base_names.map(|n| n.contains(&name)).unwrap_or(false)
+        {
+            problems.push(Problem {
+                message: format!(
+                    "Named transform \"{}\" has the same name as another color space or named transform.",
+                    name
+                ),
+                target: Target::NamedTransform(i),
+            });
+        }
+        seen_names.push(name.clone());
+
+        if let crate::NamedTransformSource::File { ref path, .. } = named_transform.source {
+            if file_missing(path) {
+                problems.push(Problem {
+                    message: format!(
+                        "The LUT file \"{}\" of named transform \"{}\" no longer exists.",
+                        path.to_string_lossy(),
+                        name
+                    ),
+                    target: Target::NamedTransform(i),
+                });
+            }
+        }
+    }
+
+    //----
+    // Looks.
+
+    let mut seen_names: Vec<String> = Vec::new();
+    for (i, look) in ui_data.looks.iter().enumerate() {
+        let name = escape_name(&look.name);
+        if name.is_empty() {
+            problems.push(Problem {
+                message: format!("Look #{} has no name.", i + 1),
+                target: Target::Look(i),
+            });
+        } else if seen_names.contains(&name) {
+            problems.push(Problem {
+                message: format!("There is more than one look named \"{}\".", name),
+                target: Target::Look(i),
+            });
+        }
+        seen_names.push(name.clone());
+
+        if look.process_space.trim().is_empty() {
+            problems.push(Problem {
+                message: format!("Look \"{}\" has no process space.", name),
+                target: Target::Look(i),
+            });
+        } else if check_refs && !exists(&look.process_space) {
+            problems.push(Problem {
+                message: format!(
+                    "Look \"{}\" uses non-existent process space \"{}\".",
+                    name,
+                    look.process_space.trim()
+                ),
+                target: Target::Look(i),
+            });
+        }
+
+        for transform in look.transforms.iter() {
+            if let crate::LookTransformSpec::File { ref path, .. } = *transform {
+                if file_missing(path) {
+                    problems.push(Problem {
+                        message: format!(
+                            "The LUT file \"{}\" of look \"{}\" no longer exists.",
+                            path.to_string_lossy(),
+                            name
+                        ),
+                        target: Target::Look(i),
+                    });
+                }
+            }
+        }
+    }
+
+    //----
+    // Displays.
+
+    let mut seen_names: Vec<String> = Vec::new();
+    for (i, display) in ui_data.displays.iter().enumerate() {
+        let name = escape_name(&display.name);
+        if name.is_empty() {
+            problems.push(Problem {
+                message: format!("Display #{} has no name.", i + 1),
+                target: Target::Display(i),
+            });
+        } else if seen_names.contains(&name) {
+            problems.push(Problem {
+                message: format!("There is more than one display named \"{}\".", name),
+                target: Target::Display(i),
+            });
+        }
+        seen_names.push(name.clone());
+
+        for view in display.views.iter() {
+            if view.color_space.trim().is_empty() {
+                problems.push(Problem {
+                    message: format!(
+                        "View \"{}\" of display \"{}\" has no color space.",
+                        view.name.trim(),
+                        name
+                    ),
+                    target: Target::Display(i),
+                });
+            } else if check_refs && !exists(&view.color_space) {
+                problems.push(Problem {
+                    message: format!(
+                        "View \"{}\" of display \"{}\" uses non-existent color space \"{}\".",
+                        view.name.trim(),
+                        name,
+                        view.color_space.trim()
+                    ),
+                    target: Target::Display(i),
+                });
+            }
+        }
+    }
+
+    //----
+    // Roles.
+
+    let mut seen_roles: Vec<&str> = Vec::new();
+    for (role, space) in ui_data.roles.iter() {
+        let role = role.trim();
+        if seen_roles.contains(&role) {
+            problems.push(Problem {
+                message: format!("Role \"{}\" is assigned more than once.", role),
+                target: Target::Roles,
+            });
+        }
+        seen_roles.push(role);
+
+        if check_refs && !exists(space) {
+            problems.push(Problem {
+                message: format!(
+                    "Role \"{}\" is assigned non-existent color space \"{}\".",
+                    role,
+                    space.trim()
+                ),
+                target: Target::Roles,
+            });
+        }
+    }
+
+    //----
+    // File rules.
+
+    let mut seen_rules: Vec<String> = Vec::new();
+    for (i, rule) in ui_data.file_rules.iter().enumerate() {
+        let name = escape_name(&rule.name);
+        if name.is_empty() {
+            problems.push(Problem {
+                message: format!("File rule #{} has no name.", i + 1),
+                target: Target::FileRules,
+            });
+        } else if seen_rules.contains(&name) || name == "Default" {
+            problems.push(Problem {
+                message: format!("There is more than one file rule named \"{}\".", name),
+                target: Target::FileRules,
+            });
+        }
+        seen_rules.push(name.clone());
+
+        // File rules can also use roles.
+        let space = rule.color_space.trim();
+        let is_role =
+            crate::ROLES.contains(&space) || ui_data.roles.iter().any(|r| r.0.trim() == space);
+        if space.is_empty() {
+            problems.push(Problem {
+                message: format!("File rule \"{}\" has no color space.", name),
+                target: Target::FileRules,
+            });
+        } else if check_refs && !is_role && !exists(space) {
+            problems.push(Problem {
+                message: format!(
+                    "File rule \"{}\" uses non-existent color space \"{}\".",
+                    name, space
+                ),
+                target: Target::FileRules,
+            });
+        }
+    }
+
+    problems
+}
+
+/// Whether each channel of a 1D LUT is either non-decreasing or
+/// non-increasing.
+fn is_monotonic(lut: &Lut1D) -> bool {
+    lut.tables.iter().all(|table| {
+        table.windows(2).all(|w| w[0] <= w[1]) || table.windows(2).all(|w| w[0] >= w[1])
+    })
+}
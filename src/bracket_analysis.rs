@@ -0,0 +1,629 @@
+//! Analysis of bracketed exposure image sets, shared between LUT
+//! Maker and the camera report tool.
+
+use std::path::{Path, PathBuf};
+
+use sensor_analysis::{
+    estimate_sensor_floor_ceiling,
+    frame_rejection::{find_rejected_frames, Rejection},
+    ExposureMapping, Histogram,
+};
+
+use crate::{FloatEncoding, ImageInfo};
+
+/// The image formats that image sets are loaded from.
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "tif", "tiff", "webp", "png"];
+
+/// Utility function to get histograms into the right order for processing.
+///
+/// Excludes the histograms of images without exposure data.
+pub fn bracket_images_to_histogram_sets(
+    image_sets: &[Vec<([Histogram; 3], ImageInfo)>],
+) -> Vec<[Vec<(Histogram, f32)>; 3]> {
+    let mut histogram_sets: Vec<[Vec<(Histogram, f32)>; 3]> = Vec::new();
+    for images in image_sets.iter() {
+        let mut histograms = [Vec::new(), Vec::new(), Vec::new()];
+        for src_img in images.iter() {
+            for chan in 0..3 {
+                if let Some(exposure) = src_img.1.exposure {
+                    histograms[chan].push((src_img.0[chan].clone(), exposure));
+                }
+            }
+        }
+
+        histogram_sets.push(histograms);
+    }
+    histogram_sets
+}
+
+/// Whether the image sets have images and they're all monochrome, in
+/// which case only one channel needs to be estimated.
+pub fn image_sets_are_monochrome(image_sets: &[Vec<([Histogram; 3], ImageInfo)>]) -> bool {
+    image_sets.iter().any(|set| !set.is_empty())
+        && image_sets
+            .iter()
+            .flatten()
+            .all(|(histograms, _)| sensor_analysis::is_monochrome(histograms))
+}
+
+pub fn exposure_mappings(
+    histogram_sets: &[[Vec<(Histogram, f32)>; 3]],
+    floor: [f32; 3],
+    ceiling: [f32; 3],
+) -> [Vec<ExposureMapping>; 3] {
+    let mut mappings = [Vec::new(), Vec::new(), Vec::new()];
+
+    for histograms in histogram_sets.iter() {
+        for chan in 0..histograms.len() {
+            for i in 0..histograms[chan].len() {
+                // Find the histogram with closest to 2x the exposure of this one.
+                const TARGET_RATIO: f32 = 2.0;
+                let mut other_hist_i = i;
+                let mut best_ratio: f32 = -std::f32::INFINITY;
+                for j in (i + 1)..histograms[chan].len() {
+                    let ratio = histograms[chan][j].1 / histograms[chan][i].1;
+                    if (ratio - TARGET_RATIO).abs() > (best_ratio - TARGET_RATIO).abs() {
+                        break;
+                    }
+                    other_hist_i = j;
+                    best_ratio = ratio;
+                }
+
+                // Compute and add the exposure mapping.
+                if other_hist_i > i {
+                    mappings[chan].push(ExposureMapping::from_histograms(
+                        &histograms[chan][i].0,
+                        &histograms[chan][other_hist_i].0,
+                        histograms[chan][i].1,
+                        histograms[chan][other_hist_i].1,
+                        floor[chan],
+                        ceiling[chan],
+                    ));
+                }
+            }
+        }
+    }
+
+    mappings
+}
+
+/// Loads all images in a directory as an image set, sorted by exposure.
+pub fn load_image_set<F: FnMut(String)>(
+    dir: &Path,
+    float_encoding: &mut Option<FloatEncoding>,
+    note: &mut F,
+) -> Result<Vec<([Histogram; 3], ImageInfo)>, String> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| {
+            format!(
+                "unable to read directory \"{}\": {}",
+                dir.to_string_lossy(),
+                e
+            )
+        })?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .map(|ext| IMAGE_EXTENSIONS.contains(&&*ext.to_string_lossy().to_lowercase()))
+                .unwrap_or(false)
+        })
+        .collect();
+    paths.sort();
+
+    let mut set: Vec<([Histogram; 3], ImageInfo)> = Vec::new();
+    for path in paths.iter() {
+        let img = match crate::job_helpers::load_image(path) {
+            Ok(img) => img,
+            Err(_) => {
+                note(format!(
+                    "Warning: unable to load image \"{}\", skipping.",
+                    path.to_string_lossy()
+                ));
+                continue;
+            }
+        };
+        if let Some((_, first)) = set.first() {
+            if first.width != img.info.width || first.height != img.info.height {
+                note(format!(
+                    "Warning: image has a different resolution than the others in its set, skipping: \"{}\".",
+                    path.to_string_lossy()
+                ));
+                continue;
+            }
+        }
+        if img.info.exposure.is_none() {
+            note(format!(
+                "Warning: image lacks exposure Exif data, and will be excluded from estimation: \"{}\".",
+                path.to_string_lossy()
+            ));
+        }
+
+        if img.image.data.is_float() && float_encoding.is_none() {
+            *float_encoding = FloatEncoding::auto(&img.image);
+            if let Some(encoding) = float_encoding {
+                note(format!("Float image histograms: {}", encoding.ui_text()));
+            }
+        }
+        let histograms =
+            crate::job_helpers::compute_image_histograms(&img, None, float_encoding.as_ref());
+        set.push((histograms, img.info));
+    }
+    set.sort_unstable_by(|a, b| a.1.exposure.partial_cmp(&b.1.exposure).unwrap());
+
+    Ok(set)
+}
+
+/// Which of an image's capture settings differ from the rest of its
+/// bracket set.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SettingsMismatch {
+    pub iso: bool,
+    pub aperture: bool,
+}
+
+impl SettingsMismatch {
+    pub fn any(&self) -> bool {
+        self.iso || self.aperture
+    }
+}
+
+/// The most common ISO and f-stop of a bracket set.
+fn common_settings(infos: &[&ImageInfo]) -> (Option<u32>, Option<(u32, u32)>) {
+    fn most_common<T: Copy, K: PartialEq, F: Fn(T) -> K>(
+        values: impl Iterator<Item = T>,
+        key: F,
+    ) -> Option<T> {
+        let mut counts: Vec<(T, usize)> = Vec::new();
+        for value in values {
+            match counts.iter_mut().find(|(v, _)| key(*v) == key(value)) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((value, 1)),
+            }
+        }
+        // Ties go to the first value, so the result doesn't depend on
+        // anything but the set's order.
+        let max_count = counts.iter().map(|(_, count)| *count).max()?;
+        counts
+            .iter()
+            .find(|(_, count)| *count == max_count)
+            .map(|(v, _)| *v)
+    }
+
+    (
+        most_common(infos.iter().filter_map(|info| info.iso), |iso| iso),
+        // Compared at a tenth of a stop, since the same f-stop can be
+        // written as different ratios.
+        most_common(infos.iter().filter_map(|info| info.fstop), |f| {
+            (fstop_value(f).log2() * 20.0).round() as i32
+        }),
+    )
+}
+
+fn fstop_value(fstop: (u32, u32)) -> f64 {
+    fstop.0 as f64 / fstop.1 as f64
+}
+
+/// Finds the images of a bracket set whose ISO or aperture differ from
+/// the set's most common values.
+///
+/// Bracketing is meant to only vary the shutter speed.  Changing the
+/// aperture changes depth of field and vignetting, and changing the ISO
+/// may not scale the image by exactly the nominal amount, both of which
+/// break the assumption that only the exposure differs between images.
+/// Images missing the metadata are flagged if others in the set have it,
+/// since their exposures can't be compensated consistently.
+pub fn find_settings_mismatches(infos: &[&ImageInfo]) -> Vec<SettingsMismatch> {
+    let (iso, fstop) = common_settings(infos);
+    let same_fstop = |a: (u32, u32), b: (u32, u32)| {
+        ((fstop_value(a) / fstop_value(b)).log2() * 20.0).round() == 0.0
+    };
+
+    infos
+        .iter()
+        .map(|info| SettingsMismatch {
+            iso: iso.is_some() && info.iso != iso,
+            aperture: match (info.fstop, fstop) {
+                (Some(a), Some(b)) => !same_fstop(a, b),
+                (None, Some(_)) => true,
+                _ => false,
+            },
+        })
+        .collect()
+}
+
+/// Computes the exposures of a bracket set from the images' shutter
+/// speeds, ISOs, and f-stops.
+///
+/// If `compensate` is true, the exposures account for differences in
+/// ISO and aperture, with missing values taken to be the set's most
+/// common ones.  Otherwise they're computed from the shutter speed
+/// alone.  Either way they're on the same scale as the exposures from
+/// `job_helpers::load_image()` for images with the set's most common
+/// settings.
+///
+/// Images without a shutter speed (e.g. with manually entered
+/// exposures) are `None`, and should keep their existing exposures.
+pub fn bracket_exposures(infos: &[&ImageInfo], compensate: bool) -> Vec<Option<f32>> {
+    let (common_iso, common_fstop) = common_settings(infos);
+
+    infos
+        .iter()
+        .map(|info| {
+            let (num, denom) = info.exposure_time?;
+            let (iso, fstop) = if compensate {
+                (info.iso.or(common_iso), info.fstop.or(common_fstop))
+            } else {
+                (common_iso, common_fstop)
+            };
+
+            let mut exposure = num as f64 / denom as f64;
+            if let Some(iso) = iso {
+                exposure *= iso as f64;
+            }
+            if let Some(fstop) = fstop {
+                exposure /= fstop_value(fstop) * fstop_value(fstop);
+            }
+            Some(exposure as f32)
+        })
+        .collect()
+}
+
+/// Warns about images in a bracket set with mismatched ISO or aperture,
+/// and compensates the set's exposures for them.  The set is re-sorted
+/// by exposure.
+pub fn compensate_bracket_set<F: FnMut(String)>(
+    set: &mut Vec<([Histogram; 3], ImageInfo)>,
+    note: &mut F,
+) {
+    let infos: Vec<&ImageInfo> = set.iter().map(|(_, info)| info).collect();
+    let mismatches = find_settings_mismatches(&infos);
+    let exposures = bracket_exposures(&infos, true);
+
+    for ((_, info), mismatch) in set.iter().zip(mismatches.iter()) {
+        if mismatch.any() {
+            note(format!(
+                "Warning: image's {} differs from the others in its set, which may throw off estimation: \"{}\".",
+                match (mismatch.iso, mismatch.aperture) {
+                    (true, true) => "ISO and aperture",
+                    (true, false) => "ISO",
+                    _ => "aperture",
+                },
+                info.full_filepath
+            ));
+        }
+    }
+    for ((_, info), exposure) in set.iter_mut().zip(exposures.iter()) {
+        if let Some(exposure) = exposure {
+            info.exposure = Some(*exposure);
+        }
+    }
+    set.sort_by(|a, b| a.1.exposure.partial_cmp(&b.1.exposure).unwrap());
+}
+
+/// Finds the images of a bracketed set that are duplicates of other
+/// images in it, or whose brightness doesn't fit their exposure.
+///
+/// Images without exposures aren't checked.  The indices of duplicates
+/// are indices into `set`.
+pub fn find_rejected_images(set: &[(&[Histogram; 3], &ImageInfo)]) -> Vec<Option<Rejection>> {
+    let indices: Vec<usize> = (0..set.len())
+        .filter(|i| set[*i].1.exposure.is_some())
+        .collect();
+    let frames: Vec<(&[Histogram], f32)> = indices
+        .iter()
+        .map(|i| (&set[*i].0[..], set[*i].1.exposure.unwrap()))
+        .collect();
+
+    let mut rejections = vec![None; set.len()];
+    for (i, rejection) in indices.iter().zip(find_rejected_frames(&frames)) {
+        rejections[*i] = rejection.map(|r| match r {
+            Rejection::Duplicate(j) => Rejection::Duplicate(indices[j]),
+            r => r,
+        });
+    }
+    rejections
+}
+
+/// Warns about the images of a bracketed set that are duplicates or
+/// outliers, and removes them if `remove` is true.
+pub fn reject_bracket_images<F: FnMut(String)>(
+    set: &mut Vec<([Histogram; 3], ImageInfo)>,
+    remove: bool,
+    note: &mut F,
+) {
+    let rejections = {
+        let images: Vec<_> = set.iter().map(|(h, info)| (h, info)).collect();
+        find_rejected_images(&images)
+    };
+    for (i, rejection) in rejections.iter().enumerate() {
+        let reason = match rejection {
+            Some(Rejection::Duplicate(j)) => {
+                format!("is a duplicate of \"{}\"", set[*j].1.full_filepath)
+            }
+            Some(Rejection::Outlier) => "doesn't fit its exposure (did the scene change?)".into(),
+            None => continue,
+        };
+        note(format!(
+            "Warning: image {}{}: \"{}\".",
+            reason,
+            if remove { ", so it was excluded" } else { "" },
+            set[i].1.full_filepath
+        ));
+    }
+    if remove {
+        for i in (0..set.len()).rev() {
+            if rejections[i].is_some() {
+                set.remove(i);
+            }
+        }
+    }
+}
+
+/// Estimates the floor as the average of the dark images, computed in
+/// linear space.
+pub fn dark_floor<F1: Fn(f32) -> f32, F2: Fn(f32) -> f32>(
+    dark_images: &[([Histogram; 3], ImageInfo)],
+    to_linear: F1,
+    from_linear: F2,
+) -> [f32; 3] {
+    let mut sum = [0.0f64; 3];
+    let mut sample_count = [0usize; 3];
+    for (histograms, _) in dark_images.iter() {
+        for chan in 0..3 {
+            let norm = 1.0 / (histograms[chan].buckets.len() - 1) as f32;
+            for (i, bucket_population) in histograms[chan].buckets.iter().enumerate() {
+                sum[chan] += to_linear(i as f32 * norm) as f64 * (*bucket_population as f64);
+                sample_count[chan] += *bucket_population;
+            }
+        }
+    }
+
+    let mut floor = [0.0f32; 3];
+    for chan in 0..3 {
+        let n = from_linear((sum[chan] / sample_count[chan].max(1) as f64) as f32);
+        floor[chan] = n.max(0.0).min(1.0);
+    }
+    floor
+}
+
+/// Estimates the floor and ceiling from the bracketed image sets.
+pub fn bracket_floor_ceiling(
+    histogram_sets: &[[Vec<(Histogram, f32)>; 3]],
+) -> ([f32; 3], [f32; 3]) {
+    let mut floor: [Option<f32>; 3] = [None; 3];
+    let mut ceiling: [Option<f32>; 3] = [None; 3];
+    for histograms in histogram_sets.iter() {
+        for chan in 0..3 {
+            if histograms[chan].len() < 2 {
+                continue;
+            }
+            let norm = 1.0 / (histograms[chan][0].0.buckets.len() - 1) as f32;
+            if let Some((f, c)) = estimate_sensor_floor_ceiling(&histograms[chan]) {
+                floor[chan] = Some(floor[chan].map_or(f * norm, |n| n.min(f * norm)));
+                ceiling[chan] = Some(ceiling[chan].map_or(c * norm, |n| n.max(c * norm)));
+            }
+        }
+    }
+
+    (
+        [
+            floor[0].unwrap_or(0.0),
+            floor[1].unwrap_or(0.0),
+            floor[2].unwrap_or(0.0),
+        ],
+        [
+            ceiling[0].unwrap_or(1.0),
+            ceiling[1].unwrap_or(1.0),
+            ceiling[2].unwrap_or(1.0),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(
+        exposure_time: Option<(u32, u32)>,
+        iso: Option<u32>,
+        fstop: Option<(u32, u32)>,
+    ) -> ImageInfo {
+        ImageInfo {
+            filename: String::new(),
+            full_filepath: String::new(),
+            width: 1,
+            height: 1,
+            exposure: None,
+            exposure_time,
+            fstop,
+            iso,
+            camera: None,
+        }
+    }
+
+    fn mismatch(iso: bool, aperture: bool) -> SettingsMismatch {
+        SettingsMismatch { iso, aperture }
+    }
+
+    #[test]
+    fn find_settings_mismatches_01() {
+        let infos = [
+            info(Some((1, 100)), Some(100), Some((4, 1))),
+            info(Some((1, 50)), Some(100), Some((4, 1))),
+            info(Some((1, 25)), Some(400), Some((4, 1))),
+            info(Some((1, 12)), Some(100), Some((8, 1))),
+        ];
+        let infos: Vec<&ImageInfo> = infos.iter().collect();
+        assert_eq!(
+            find_settings_mismatches(&infos),
+            vec![
+                mismatch(false, false),
+                mismatch(false, false),
+                mismatch(true, false),
+                mismatch(false, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_settings_mismatches_02() {
+        // The same f-stop written as different ratios, and a missing
+        // f-stop when the rest of the set has one.
+        let infos = [
+            info(Some((1, 100)), Some(100), Some((14, 5))),
+            info(Some((1, 50)), Some(100), Some((28, 10))),
+            info(Some((1, 25)), Some(100), None),
+        ];
+        let infos: Vec<&ImageInfo> = infos.iter().collect();
+        assert_eq!(
+            find_settings_mismatches(&infos),
+            vec![
+                mismatch(false, false),
+                mismatch(false, false),
+                mismatch(false, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_settings_mismatches_03() {
+        // Empty and single-image sets.
+        assert_eq!(find_settings_mismatches(&[]), vec![]);
+        let single = info(Some((1, 100)), Some(100), Some((4, 1)));
+        assert_eq!(
+            find_settings_mismatches(&[&single]),
+            vec![mismatch(false, false)]
+        );
+
+        // A set without any ISO or f-stop metadata.
+        let infos = [info(Some((1, 100)), None, None), info(None, None, None)];
+        let infos: Vec<&ImageInfo> = infos.iter().collect();
+        assert_eq!(
+            find_settings_mismatches(&infos),
+            vec![mismatch(false, false), mismatch(false, false)]
+        );
+    }
+
+    #[test]
+    fn bracket_exposures_01() {
+        let infos = [
+            info(Some((1, 100)), Some(100), Some((4, 1))),
+            info(Some((1, 50)), Some(100), Some((4, 1))),
+            info(Some((1, 50)), Some(200), Some((4, 1))),
+            info(Some((1, 50)), Some(100), Some((8, 1))),
+            info(Some((1, 50)), None, None),
+            info(None, Some(100), Some((4, 1))),
+        ];
+        let infos: Vec<&ImageInfo> = infos.iter().collect();
+
+        // Shutter speed alone, at the set's most common settings.
+        assert_eq!(
+            bracket_exposures(&infos, false),
+            vec![
+                Some(0.0625),
+                Some(0.125),
+                Some(0.125),
+                Some(0.125),
+                Some(0.125),
+                None,
+            ]
+        );
+
+        // Compensated for ISO and aperture, with missing settings taken
+        // to be the set's most common ones.
+        assert_eq!(
+            bracket_exposures(&infos, true),
+            vec![
+                Some(0.0625),
+                Some(0.125),
+                Some(0.25),
+                Some(0.03125),
+                Some(0.125),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn bracket_exposures_02() {
+        // Empty and single-image sets.
+        assert_eq!(bracket_exposures(&[], true), vec![]);
+        let single = info(Some((1, 100)), Some(100), Some((4, 1)));
+        assert_eq!(bracket_exposures(&[&single], true), vec![Some(0.0625)]);
+
+        // All-identical exposures stay identical.
+        let infos = vec![info(Some((1, 60)), Some(800), Some((2, 1))); 4];
+        let infos: Vec<&ImageInfo> = infos.iter().collect();
+        for compensate in [false, true].iter() {
+            let exposures = bracket_exposures(&infos, *compensate);
+            assert_eq!(exposures.len(), 4);
+            assert!(exposures.iter().all(|e| *e == exposures[0]));
+            assert!((exposures[0].unwrap() - (800.0 / 60.0 / 4.0)).abs() < 0.0001);
+        }
+    }
+
+    /// Histograms of a synthetic scene of evenly spread linear values,
+    /// captured at `brightness` with a linear response that clips at one.
+    fn scene_histograms(brightness: f32) -> [Histogram; 3] {
+        let mut histogram = Histogram {
+            total_samples: 0,
+            buckets: vec![0; 256],
+        };
+        for i in 0..1000 {
+            let value = (i as f32 / 1000.0 * brightness).min(1.0);
+            histogram.buckets[(value * 255.0).round() as usize] += 1;
+            histogram.total_samples += 1;
+        }
+        [histogram.clone(), histogram.clone(), histogram]
+    }
+
+    fn exposed(exposure: Option<f32>) -> ImageInfo {
+        ImageInfo {
+            exposure,
+            ..info(None, None, None)
+        }
+    }
+
+    #[test]
+    fn find_rejected_images_01() {
+        // Duplicate indices refer to the whole set, including the images
+        // without exposures, which aren't checked.
+        let images = [
+            (scene_histograms(1.0), exposed(None)),
+            (scene_histograms(0.5), exposed(Some(0.5))),
+            (scene_histograms(1.0), exposed(None)),
+            (scene_histograms(1.0), exposed(Some(1.0))),
+            (scene_histograms(16.0), exposed(Some(2.0))),
+            (scene_histograms(1.0), exposed(Some(1.0))),
+            (scene_histograms(4.0), exposed(Some(4.0))),
+            (scene_histograms(8.0), exposed(Some(8.0))),
+        ];
+        let set: Vec<_> = images.iter().map(|(h, info)| (h, info)).collect();
+        assert_eq!(
+            find_rejected_images(&set),
+            vec![
+                None,
+                None,
+                None,
+                None,
+                Some(Rejection::Outlier),
+                Some(Rejection::Duplicate(3)),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn find_rejected_images_02() {
+        // Empty and single-image sets, and sets without exposures.
+        assert_eq!(find_rejected_images(&[]), vec![]);
+        let image = (scene_histograms(1.0), exposed(Some(1.0)));
+        assert_eq!(find_rejected_images(&[(&image.0, &image.1)]), vec![None]);
+        let image = (scene_histograms(1.0), exposed(None));
+        assert_eq!(
+            find_rejected_images(&[(&image.0, &image.1), (&image.0, &image.1)]),
+            vec![None, None]
+        );
+    }
+}
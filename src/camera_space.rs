@@ -0,0 +1,204 @@
+//! A small JSON sidecar file that describes a camera color space, for
+//! handing camera profiles from LUT Maker over to OCIO Maker.
+//!
+//! LUT Maker writes one next to exported LUT pairs, and dropping it
+//! onto OCIO Maker's color space list adds a color space with the
+//! right LUT, direction, and chromaticities.  An example:
+//!
+//! ```json
+//! {
+//!   "format_version": 1,
+//!   "name": "Sony ILME-FX3",
+//!   "lut_path": "sony_ilme_fx3_to_linear.cube",
+//!   "lut_direction": "to_linear",
+//!   "chroma_space": "SGamut3Cine",
+//!   "chromaticities": {
+//!     "r": [0.766, 0.275],
+//!     "g": [0.225, 0.8],
+//!     "b": [0.089, -0.087],
+//!     "w": [0.3127, 0.329]
+//!   }
+//! }
+//! ```
+//!
+//! `lut_path` is relative to the sidecar file's directory.  The
+//! chromaticities are optional, and `chroma_space` (one of the
+//! `ChromaSpace::as_str()` names) is informational: readers should
+//! prefer the named space when they know it, and otherwise use the
+//! chromaticities as a custom gamut.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::chroma_space::ChromaSpace;
+
+/// The extension of camera space sidecar files, including the
+/// `.camera_space` part that sets them apart from other JSON files.
+pub const SIDECAR_EXTENSION: &str = "camera_space.json";
+
+/// The sidecar format version that this code writes.  Newer versions
+/// are rejected when reading.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraSpace {
+    format_version: u32,
+
+    /// Name of the color space, usually the camera's.
+    pub name: String,
+
+    /// The 1D LUT of the transfer function.  Absolute after reading,
+    /// and written relative to the sidecar file.
+    pub lut_path: PathBuf,
+    pub lut_direction: LutDirection,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chroma_space: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chromaticities: Option<Chromaticities>,
+}
+
+/// Which way the LUT transforms.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LutDirection {
+    ToLinear,
+    FromLinear,
+}
+
+/// CIE xy chromaticities of the primaries and white point.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chromaticities {
+    pub r: [f64; 2],
+    pub g: [f64; 2],
+    pub b: [f64; 2],
+    pub w: [f64; 2],
+}
+
+impl CameraSpace {
+    pub fn new(name: &str, lut_path: &Path, lut_direction: LutDirection) -> CameraSpace {
+        CameraSpace {
+            format_version: FORMAT_VERSION,
+            name: name.trim().into(),
+            lut_path: lut_path.into(),
+            lut_direction: lut_direction,
+            chroma_space: None,
+            chromaticities: None,
+        }
+    }
+
+    /// Sets the gamut from a named chroma space.  `ChromaSpace::None`
+    /// clears it.
+    pub fn set_chroma_space(
+        &mut self,
+        chroma_space: ChromaSpace,
+        custom: colorbox::chroma::Chromaticities,
+    ) {
+        match chroma_space.chromaticities(custom) {
+            Some(chroma) => {
+                self.chroma_space = Some(chroma_space.as_str().into());
+                self.chromaticities = Some(chroma.into());
+            }
+            None => {
+                self.chroma_space = None;
+                self.chromaticities = None;
+            }
+        }
+    }
+
+    /// The gamut as `(chroma_space, custom_chromaticities)`, the way
+    /// OCIO Maker stores it.
+    ///
+    /// Named chroma spaces that aren't known fall back to the
+    /// chromaticities as a custom space.
+    pub fn chroma_space(&self) -> Option<(ChromaSpace, colorbox::chroma::Chromaticities)> {
+        let named = self
+            .chroma_space
+            .as_deref()
+            .and_then(ChromaSpace::from_str)
+            .filter(|space| *space != ChromaSpace::None && *space != ChromaSpace::Custom);
+        let chroma = self.chromaticities.map(|c| c.into());
+
+        match (named, chroma) {
+            (Some(space), chroma) => Some((
+                space,
+                chroma.unwrap_or_else(|| space.chromaticities(colorbox::chroma::REC709).unwrap()),
+            )),
+            (None, Some(chroma)) => Some((ChromaSpace::Custom, chroma)),
+            (None, None) => None,
+        }
+    }
+
+    /// Reads a sidecar file, making `lut_path` absolute.
+    pub fn read(path: &Path) -> std::io::Result<CameraSpace> {
+        use std::io::{Error, ErrorKind};
+
+        let text = std::fs::read_to_string(path)?;
+        let mut space: CameraSpace =
+            serde_json::from_str(&text).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if space.format_version > FORMAT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "camera space format version {} is newer than this version supports ({})",
+                    space.format_version, FORMAT_VERSION
+                ),
+            ));
+        }
+
+        if space.lut_path.is_relative() {
+            if let Some(dir) = path.parent() {
+                space.lut_path = dir.join(&space.lut_path);
+            }
+        }
+
+        Ok(space)
+    }
+
+    /// Writes the sidecar file, with `lut_path` relative to it when
+    /// possible.
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let mut space = self.clone();
+        space.format_version = FORMAT_VERSION;
+        if let Some(dir) = path.parent() {
+            if let Some(relative) = pathdiff::diff_paths(&space.lut_path, dir) {
+                space.lut_path = relative;
+            }
+        }
+
+        let text = serde_json::to_string_pretty(&space)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, text + "\n")
+    }
+}
+
+/// Returns whether the path looks like a camera space sidecar file.
+pub fn is_sidecar_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.to_lowercase().ends_with(SIDECAR_EXTENSION))
+        .unwrap_or(false)
+}
+
+impl From<colorbox::chroma::Chromaticities> for Chromaticities {
+    fn from(c: colorbox::chroma::Chromaticities) -> Chromaticities {
+        Chromaticities {
+            r: [c.r.0, c.r.1],
+            g: [c.g.0, c.g.1],
+            b: [c.b.0, c.b.1],
+            w: [c.w.0, c.w.1],
+        }
+    }
+}
+
+impl From<Chromaticities> for colorbox::chroma::Chromaticities {
+    fn from(c: Chromaticities) -> colorbox::chroma::Chromaticities {
+        colorbox::chroma::Chromaticities {
+            r: (c.r[0], c.r[1]),
+            g: (c.g[0], c.g[1]),
+            b: (c.b[0], c.b[1]),
+            w: (c.w[0], c.w[1]),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+/// The named color gamuts (chromaticities) available for selection in
+/// the UI.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChromaSpace {
+    None,
+    Custom,
+    Rec709,
+    Rec2020,
+    DciP3,
+    DisplayP3,
+    AcesAP0,
+    AcesAP1,
+    AdobeRGB,
+    AdobeWideGamutRGB,
+    ARRIWideGamut3,
+    ARRIWideGamut4,
+    BlackmagicWideGamutGen4,
+    CanonCinemaGamut,
+    DavinciWideGamut,
+    DJIDGamut,
+    EGamut,
+    PanasonicVGamut,
+    ProPhoto,
+    RedWideGamutRGB,
+    SGamut,
+    SGamut3Cine,
+}
+
+pub const CHROMA_SPACES: &[ChromaSpace] = &[
+    ChromaSpace::None,
+    ChromaSpace::Custom,
+    // Standardized.
+    ChromaSpace::AcesAP0,
+    ChromaSpace::AcesAP1,
+    ChromaSpace::Rec709,
+    ChromaSpace::Rec2020,
+    ChromaSpace::DciP3,
+    ChromaSpace::DisplayP3,
+    // Other.
+    ChromaSpace::AdobeRGB,
+    ChromaSpace::AdobeWideGamutRGB,
+    ChromaSpace::ARRIWideGamut3,
+    ChromaSpace::ARRIWideGamut4,
+    ChromaSpace::BlackmagicWideGamutGen4,
+    ChromaSpace::CanonCinemaGamut,
+    ChromaSpace::DavinciWideGamut,
+    ChromaSpace::DJIDGamut,
+    ChromaSpace::EGamut,
+    ChromaSpace::PanasonicVGamut,
+    ChromaSpace::ProPhoto,
+    ChromaSpace::RedWideGamutRGB,
+    ChromaSpace::SGamut,
+    ChromaSpace::SGamut3Cine,
+];
+
+impl From<camera_db::Gamut> for ChromaSpace {
+    fn from(gamut: camera_db::Gamut) -> ChromaSpace {
+        match gamut {
+            camera_db::Gamut::CanonCinemaGamut => ChromaSpace::CanonCinemaGamut,
+            camera_db::Gamut::PanasonicVGamut => ChromaSpace::PanasonicVGamut,
+            camera_db::Gamut::SGamut3Cine => ChromaSpace::SGamut3Cine,
+        }
+    }
+}
+
+impl ChromaSpace {
+    pub fn chromaticities(
+        &self,
+        custom: colorbox::chroma::Chromaticities,
+    ) -> Option<colorbox::chroma::Chromaticities> {
+        match *self {
+            ChromaSpace::None => None,
+            ChromaSpace::Custom => Some(custom),
+            ChromaSpace::Rec709 => Some(colorbox::chroma::REC709),
+            ChromaSpace::Rec2020 => Some(colorbox::chroma::REC2020),
+            ChromaSpace::DciP3 => Some(colorbox::chroma::DCI_P3),
+            ChromaSpace::DisplayP3 => Some(colorbox::chroma::DISPLAY_P3),
+            ChromaSpace::AcesAP0 => Some(colorbox::chroma::ACES_AP0),
+            ChromaSpace::AcesAP1 => Some(colorbox::chroma::ACES_AP1),
+            ChromaSpace::AdobeRGB => Some(colorbox::chroma::ADOBE_RGB),
+            ChromaSpace::AdobeWideGamutRGB => Some(colorbox::chroma::ADOBE_WIDE_GAMUT_RGB),
+            ChromaSpace::ARRIWideGamut3 => Some(colorbox::chroma::ARRI_WIDE_GAMUT_3),
+            ChromaSpace::ARRIWideGamut4 => Some(colorbox::chroma::ARRI_WIDE_GAMUT_4),
+            ChromaSpace::BlackmagicWideGamutGen4 => {
+                Some(colorbox::chroma::blackmagic::BMD_WIDE_GAMUT_GEN4)
+            }
+            ChromaSpace::CanonCinemaGamut => Some(colorbox::chroma::CANON_CINEMA_GAMUT),
+            ChromaSpace::DavinciWideGamut => Some(colorbox::chroma::blackmagic::DAVINCI_WIDE_GAMUT),
+            ChromaSpace::DJIDGamut => Some(colorbox::chroma::DJI_D_GAMUT),
+            ChromaSpace::EGamut => Some(colorbox::chroma::E_GAMUT),
+            ChromaSpace::PanasonicVGamut => Some(colorbox::chroma::PANASONIC_V_GAMUT),
+            ChromaSpace::ProPhoto => Some(colorbox::chroma::PROPHOTO),
+            ChromaSpace::RedWideGamutRGB => Some(colorbox::chroma::RED_WIDE_GAMUT_RGB),
+            ChromaSpace::SGamut => Some(colorbox::chroma::sony::S_GAMUT),
+            ChromaSpace::SGamut3Cine => Some(colorbox::chroma::sony::S_GAMUT3_CINE),
+        }
+    }
+
+    pub fn ui_text(&self) -> &'static str {
+        match *self {
+            ChromaSpace::None => "None",
+            ChromaSpace::Custom => "Custom",
+            ChromaSpace::Rec709 => "Rec.709 / sRGB",
+            ChromaSpace::Rec2020 => "Rec.2020",
+            ChromaSpace::DciP3 => "DCI-P3",
+            ChromaSpace::DisplayP3 => "Display P3",
+            ChromaSpace::AcesAP0 => "ACES APO",
+            ChromaSpace::AcesAP1 => "ACES AP1",
+            ChromaSpace::AdobeRGB => "Adobe RGB",
+            ChromaSpace::AdobeWideGamutRGB => "Adobe Wide Gamut RGB",
+            ChromaSpace::ARRIWideGamut3 => "ARRI Wide Gamut 3 / Alexa Wide Gamut RGB",
+            ChromaSpace::ARRIWideGamut4 => "ARRI Wide Gamut 4",
+            ChromaSpace::BlackmagicWideGamutGen4 => "BMD Wide Gamut Gen4/Gen5",
+            ChromaSpace::CanonCinemaGamut => "Canon Cinema Gamut",
+            ChromaSpace::DavinciWideGamut => "DaVinci Wide Gamut",
+            ChromaSpace::DJIDGamut => "DJI D-Gamut",
+            ChromaSpace::EGamut => "FilmLight E-Gamut",
+            ChromaSpace::PanasonicVGamut => "Panasonic V-Gamut",
+            ChromaSpace::ProPhoto => "ProPhoto",
+            ChromaSpace::RedWideGamutRGB => "RED Wide Gamut RGB",
+            ChromaSpace::SGamut => "Sony S-Gamut / S-Gamut3",
+            ChromaSpace::SGamut3Cine => "Sony S-Gamut3.Cine",
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ChromaSpace::None => "None",
+            ChromaSpace::Custom => "Custom",
+            ChromaSpace::Rec709 => "Rec709",
+            ChromaSpace::Rec2020 => "Rec2020",
+            ChromaSpace::DciP3 => "DciP3",
+            ChromaSpace::DisplayP3 => "DisplayP3",
+            ChromaSpace::AcesAP0 => "AcesAPO",
+            ChromaSpace::AcesAP1 => "AcesAP1",
+            ChromaSpace::AdobeRGB => "AdobeRGB",
+            ChromaSpace::AdobeWideGamutRGB => "AdobeWideGamutRGB",
+            ChromaSpace::ARRIWideGamut3 => "ARRIWideGamut3",
+            ChromaSpace::ARRIWideGamut4 => "ARRIWideGamut4",
+            ChromaSpace::BlackmagicWideGamutGen4 => "BlackmagicWideGamutGen4",
+            ChromaSpace::CanonCinemaGamut => "CanonCinemaGamut",
+            ChromaSpace::DavinciWideGamut => "DavinciWideGamut",
+            ChromaSpace::DJIDGamut => "DJIDGamut",
+            ChromaSpace::EGamut => "EGamut",
+            ChromaSpace::PanasonicVGamut => "PanasonicVGamut",
+            ChromaSpace::ProPhoto => "ProPhoto",
+            ChromaSpace::RedWideGamutRGB => "RedWideGamutRGB",
+            ChromaSpace::SGamut => "SGamut",
+            ChromaSpace::SGamut3Cine => "SGamut3Cine",
+        }
+    }
+
+    pub fn from_str(text: &str) -> Option<ChromaSpace> {
+        match text {
+            "None" => Some(ChromaSpace::None),
+            "Custom" => Some(ChromaSpace::Custom),
+            "Rec709" => Some(ChromaSpace::Rec709),
+            "Rec2020" => Some(ChromaSpace::Rec2020),
+            "DciP3" => Some(ChromaSpace::DciP3),
+            "DisplayP3" => Some(ChromaSpace::DisplayP3),
+            "AcesAPO" => Some(ChromaSpace::AcesAP0),
+            "AcesAP1" => Some(ChromaSpace::AcesAP1),
+            "AdobeRGB" => Some(ChromaSpace::AdobeRGB),
+            "AdobeWideGamutRGB" => Some(ChromaSpace::AdobeWideGamutRGB),
+            "ARRIWideGamut3" => Some(ChromaSpace::ARRIWideGamut3),
+            "ARRIWideGamut4" => Some(ChromaSpace::ARRIWideGamut4),
+            "BlackmagicWideGamutGen4" => Some(ChromaSpace::BlackmagicWideGamutGen4),
+            "CanonCinemaGamut" => Some(ChromaSpace::CanonCinemaGamut),
+            "DavinciWideGamut" => Some(ChromaSpace::DavinciWideGamut),
+            "DJIDGamut" => Some(ChromaSpace::DJIDGamut),
+            "EGamut" => Some(ChromaSpace::EGamut),
+            "PanasonicVGamut" => Some(ChromaSpace::PanasonicVGamut),
+            "ProPhoto" => Some(ChromaSpace::ProPhoto),
+            "RedWideGamutRGB" => Some(ChromaSpace::RedWideGamutRGB),
+            "SGamut" => Some(ChromaSpace::SGamut),
+            "SGamut3Cine" => Some(ChromaSpace::SGamut3Cine),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,831 @@
+//! Color management of image previews for the user's monitor.
+//!
+//! Previews are generated as sRGB, which is what egui assumes the
+//! monitor to be.  On monitors with other gamuts (e.g. the Display P3
+//! screens of many laptops) that makes them look more saturated than
+//! they should, so the previews are converted to the monitor's color
+//! space before being uploaded as textures.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use colorbox::matrix::{self, AdaptationMethod, Matrix};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// Samples per axis of the LUT that conversions are baked into.
+const LUT_RESOLUTION: usize = 33;
+
+/// The ICC profile connection space white point.
+const D50: (f64, f64) = (0.34567, 0.35850);
+
+/// The color space of the user's monitor.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DisplayProfile {
+    #[default]
+    Srgb,
+    DisplayP3,
+
+    /// An ICC profile file.  Only matrix/TRC profiles are supported,
+    /// which covers the profiles created by most calibration tools.
+    Icc(PathBuf),
+
+    /// A display/view of an OCIO config.  `source` is the config's color
+    /// space for sRGB display output, which previews are converted from.
+    Ocio {
+        config: PathBuf,
+        source: String,
+        display: String,
+        view: String,
+    },
+}
+
+impl DisplayProfile {
+    fn ui_text(&self) -> &'static str {
+        match *self {
+            DisplayProfile::Srgb => "sRGB",
+            DisplayProfile::DisplayP3 => "Display P3",
+            DisplayProfile::Icc(_) => "ICC Profile",
+            DisplayProfile::Ocio { .. } => "OCIO Display",
+        }
+    }
+
+    /// Builds the conversion from sRGB to this profile.
+    fn lut(&self) -> Result<Option<Lut>, String> {
+        match *self {
+            DisplayProfile::Srgb => Ok(None),
+
+            DisplayProfile::DisplayP3 => {
+                let mat = matrix::compose(&[
+                    matrix::rgb_to_xyz_matrix(colorbox::chroma::REC709),
+                    matrix::xyz_to_rgb_matrix(colorbox::chroma::DISPLAY_P3),
+                ]);
+                // Display P3 uses the sRGB transfer function.
+                Ok(Some(Lut::bake(|rgb| {
+                    let linear = rgb.map(srgb_to_linear);
+                    Ok(matrix::transform_color(linear, mat).map(linear_to_srgb))
+                })?))
+            }
+
+            DisplayProfile::Icc(ref path) => {
+                let data = std::fs::read(path).map_err(|e| {
+                    format!(
+                        "unable to read ICC profile \"{}\": {}",
+                        path.to_string_lossy(),
+                        e
+                    )
+                })?;
+                let profile = IccProfile::parse(&data).map_err(|e| {
+                    format!(
+                        "unable to use ICC profile \"{}\": {}",
+                        path.to_string_lossy(),
+                        e
+                    )
+                })?;
+
+                // ICC profiles are relative to a D50 white point.
+                let from_srgb = matrix::compose(&[
+                    matrix::rgb_to_xyz_matrix(colorbox::chroma::REC709),
+                    matrix::xyz_chromatic_adaptation_matrix(
+                        colorbox::chroma::REC709.w,
+                        D50,
+                        AdaptationMethod::Bradford,
+                    ),
+                    profile.from_xyz,
+                ]);
+                Ok(Some(Lut::bake(|rgb| {
+                    let linear = matrix::transform_color(rgb.map(srgb_to_linear), from_srgb);
+                    Ok([
+                        profile.trcs[0].inverse(linear[0]),
+                        profile.trcs[1].inverse(linear[1]),
+                        profile.trcs[2].inverse(linear[2]),
+                    ])
+                })?))
+            }
+
+            DisplayProfile::Ocio {
+                ref config,
+                ref source,
+                ref display,
+                ref view,
+            } => {
+                let (config, _) = ocio_gen::import::read_config(config).map_err(|e| {
+                    format!(
+                        "unable to load OCIO config \"{}\": {}",
+                        config.to_string_lossy(),
+                        e
+                    )
+                })?;
+                let evaluator = ocio_gen::eval::Evaluator::new(&config);
+                let transforms = evaluator.view_processor(source, display, view)?;
+                Ok(Some(Lut::bake(|rgb| evaluator.eval(&transforms, rgb))?))
+            }
+        }
+    }
+}
+
+/// A ready-to-use conversion from sRGB to a display profile.  Cheap to
+/// clone, for passing to jobs.
+#[derive(Clone)]
+pub struct DisplayTransform {
+    profile: DisplayProfile,
+    lut: Option<Arc<Lut>>, // `None` for sRGB.
+}
+
+impl DisplayTransform {
+    /// The transform for sRGB monitors, which leaves previews as-is.
+    pub fn srgb() -> DisplayTransform {
+        DisplayTransform {
+            profile: DisplayProfile::Srgb,
+            lut: None,
+        }
+    }
+
+    /// Creates the transform for `profile`.
+    ///
+    /// If the profile can't be used, an sRGB transform is returned
+    /// along with an explanation.  It still reports `profile` as its
+    /// profile, so that it isn't retried over and over.
+    pub fn new(profile: &DisplayProfile) -> (DisplayTransform, Option<String>) {
+        let (lut, error) = match profile.lut() {
+            Ok(lut) => (lut, None),
+            Err(e) => (None, Some(e)),
+        };
+        (
+            DisplayTransform {
+                profile: profile.clone(),
+                lut: lut.map(Arc::new),
+            },
+            error,
+        )
+    }
+
+    /// The profile the transform was created for.
+    pub fn profile(&self) -> &DisplayProfile {
+        &self.profile
+    }
+
+    /// Converts 8-bit sRGB pixels in place.  `channels` is the number of
+    /// channels per pixel (3 or 4).  Any alpha channel is left as-is.
+    pub fn apply(&self, pixels: &mut [u8], channels: usize) {
+        let lut = match self.lut {
+            Some(ref lut) => lut,
+            None => return,
+        };
+        assert!(channels >= 3);
+        for pixel in pixels.chunks_exact_mut(channels) {
+            let rgb = lut.eval([pixel[0], pixel[1], pixel[2]]);
+            pixel[..3].copy_from_slice(&rgb);
+        }
+    }
+}
+
+impl PartialEq for DisplayTransform {
+    fn eq(&self, other: &DisplayTransform) -> bool {
+        self.profile == other.profile
+    }
+}
+
+impl std::fmt::Debug for DisplayTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DisplayTransform")
+            .field(&self.profile)
+            .finish()
+    }
+}
+
+/// UI for choosing a display profile.  Returns whether it was changed.
+pub fn profile_ui(ui: &mut egui::Ui, profile: &mut DisplayProfile) -> bool {
+    let old_profile = profile.clone();
+
+    ui.horizontal(|ui| {
+        ui.label("Display profile:");
+        egui::ComboBox::from_id_source("Display Profile")
+            .selected_text(profile.ui_text())
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(*profile == DisplayProfile::Srgb, "sRGB")
+                    .clicked()
+                {
+                    *profile = DisplayProfile::Srgb;
+                }
+                if ui
+                    .selectable_label(*profile == DisplayProfile::DisplayP3, "Display P3")
+                    .clicked()
+                {
+                    *profile = DisplayProfile::DisplayP3;
+                }
+                if ui
+                    .selectable_label(matches!(profile, DisplayProfile::Icc(_)), "ICC Profile...")
+                    .clicked()
+                {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_title("Select ICC Profile")
+                        .add_filter("ICC Profile", &["icc", "ICC", "icm", "ICM"])
+                        .pick_file()
+                    {
+                        *profile = DisplayProfile::Icc(path);
+                    }
+                }
+                if ui
+                    .selectable_label(
+                        matches!(profile, DisplayProfile::Ocio { .. }),
+                        "OCIO Display...",
+                    )
+                    .clicked()
+                {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_title("Select OCIO Config")
+                        .add_filter("OCIO Config", &["ocio", "OCIO"])
+                        .pick_file()
+                    {
+                        *profile = default_ocio_profile(&path);
+                    }
+                }
+            });
+    })
+    .response
+    .on_hover_text("The color space of your monitor.  Previews are converted to it so that they look right on wide-gamut monitors.  Thumbnails of already-loaded images keep their old colors.");
+
+    match *profile {
+        DisplayProfile::Icc(ref path) => {
+            ui.label(path.to_string_lossy());
+        }
+        DisplayProfile::Ocio {
+            ref config,
+            ref mut source,
+            ref mut display,
+            ref mut view,
+        } => {
+            ui.label(config.to_string_lossy());
+            if let Ok(names) = config_names(ui.ctx(), config) {
+                combo_box(ui, "Previews are", &names.color_spaces, source);
+                combo_box(
+                    ui,
+                    "Display",
+                    &names
+                        .displays
+                        .iter()
+                        .map(|d| d.0.clone())
+                        .collect::<Vec<_>>(),
+                    display,
+                );
+                if let Some((_, views)) = names.displays.iter().find(|d| d.0 == *display) {
+                    combo_box(ui, "View", views, view);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    *profile != old_profile
+}
+
+fn combo_box(ui: &mut egui::Ui, label: &str, items: &[String], selected: &mut String) {
+    egui::ComboBox::from_label(label)
+        .selected_text(selected.as_str())
+        .show_ui(ui, |ui| {
+            for item in items.iter() {
+                ui.selectable_value(selected, item.clone(), item);
+            }
+        });
+}
+
+/// The names in an OCIO config needed for choosing a display/view.
+struct ConfigNames {
+    color_spaces: Vec<String>,
+    displays: Vec<(String, Vec<String>)>, // (display, views)
+}
+
+/// Reads the names in a config, caching them in egui's memory so the
+/// config isn't read every frame.
+fn config_names(ctx: &egui::Context, path: &Path) -> Result<Arc<ConfigNames>, String> {
+    let id = egui::Id::new(("lib::display_profile::config_names", path));
+    if let Some(names) = ctx.data_mut(|d| d.get_temp::<Result<Arc<ConfigNames>, String>>(id)) {
+        return names;
+    }
+
+    let names = ocio_gen::import::read_config(path)
+        .map(|(config, _)| {
+            Arc::new(ConfigNames {
+                color_spaces: config.colorspaces.iter().map(|s| s.name.clone()).collect(),
+                displays: config
+                    .displays
+                    .iter()
+                    .map(|d| {
+                        (
+                            d.name.clone(),
+                            d.views.iter().map(|v| v.0.clone()).collect(),
+                        )
+                    })
+                    .collect(),
+            })
+        })
+        .map_err(|e| e.to_string());
+    ctx.data_mut(|d| d.insert_temp(id, names.clone()));
+    names
+}
+
+/// An OCIO profile for `config`, with a guess at the sRGB color space
+/// and the first display and view.
+fn default_ocio_profile(config: &Path) -> DisplayProfile {
+    let (mut source, mut display, mut view) = (String::new(), String::new(), String::new());
+    if let Ok((config, _)) = ocio_gen::import::read_config(config) {
+        if let Some(space) = config
+            .colorspaces
+            .iter()
+            .find(|s| s.name.to_lowercase().contains("srgb"))
+        {
+            source = space.name.clone();
+        }
+        if let Some(d) = config.displays.first() {
+            display = d.name.clone();
+            if let Some(v) = d.views.first() {
+                view = v.0.clone();
+            }
+        }
+    }
+    DisplayProfile::Ocio {
+        config: config.into(),
+        source: source,
+        display: display,
+        view: view,
+    }
+}
+
+//-------------------------------------------------------------
+
+/// A 3D LUT from sRGB-encoded values to display-encoded values.
+struct Lut {
+    table: Vec<[f32; 3]>, // Red changes fastest.
+}
+
+impl Lut {
+    fn bake<F>(f: F) -> Result<Lut, String>
+    where
+        F: Fn([f64; 3]) -> Result<[f64; 3], String>,
+    {
+        let res = LUT_RESOLUTION;
+        let n = |i: usize| i as f64 / (res - 1) as f64;
+        let mut table = Vec::with_capacity(res * res * res);
+        for i in 0..(res * res * res) {
+            let out = f([n(i % res), n((i / res) % res), n(i / (res * res))])?;
+            table.push([out[0] as f32, out[1] as f32, out[2] as f32]);
+        }
+        Ok(Lut { table: table })
+    }
+
+    /// Trilinearly interpolated lookup.
+    fn eval(&self, rgb: [u8; 3]) -> [u8; 3] {
+        let res = LUT_RESOLUTION;
+        let mut i0 = [0usize; 3];
+        let mut t = [0.0f32; 3];
+        for chan in 0..3 {
+            let x = rgb[chan] as f32 / 255.0 * (res - 1) as f32;
+            i0[chan] = (x as usize).min(res - 2);
+            t[chan] = x - i0[chan] as f32;
+        }
+
+        let mut out = [0.0f32; 3];
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = 0;
+            let mut stride = 1;
+            for chan in 0..3 {
+                let bit = (corner >> chan) & 1;
+                weight *= if bit == 1 { t[chan] } else { 1.0 - t[chan] };
+                index += (i0[chan] + bit) * stride;
+                stride *= res;
+            }
+            for (n, value) in out.iter_mut().zip(self.table[index].iter()) {
+                *n += value * weight;
+            }
+        }
+
+        out.map(|n| (n.max(0.0).min(1.0) * 255.0).round() as u8)
+    }
+}
+
+fn srgb_to_linear(n: f64) -> f64 {
+    if n <= 0.04045 {
+        n / 12.92
+    } else {
+        ((n + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(n: f64) -> f64 {
+    if n <= 0.0031308 {
+        n * 12.92
+    } else {
+        (1.055 * n.powf(1.0 / 2.4)) - 0.055
+    }
+}
+
+//-------------------------------------------------------------
+
+/// The parts of a matrix/TRC ICC profile needed for converting to it.
+struct IccProfile {
+    from_xyz: Matrix, // From D50 XYZ to linear device RGB.
+    trcs: [ToneCurve; 3],
+}
+
+/// An ICC tone reproduction curve, mapping device values to linear.
+enum ToneCurve {
+    Table(Vec<f64>),
+    Parametric(u16, [f64; 7]), // (function type, parameters)
+}
+
+impl IccProfile {
+    fn parse(data: &[u8]) -> Result<IccProfile, String> {
+        let u16_at = |i: usize| -> Result<u16, String> {
+            data.get(i..(i + 2))
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .ok_or_else(|| "the file is truncated".to_string())
+        };
+        let u32_at = |i: usize| -> Result<u32, String> {
+            data.get(i..(i + 4))
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+                .ok_or_else(|| "the file is truncated".to_string())
+        };
+        let s15_fixed16_at =
+            |i: usize| -> Result<f64, String> { Ok(u32_at(i)? as i32 as f64 / 65536.0) };
+
+        if data.get(36..40) != Some(b"acsp") {
+            return Err("the file is not an ICC profile".into());
+        }
+        if data.get(16..20) != Some(b"RGB ") {
+            return Err("only RGB profiles are supported".into());
+        }
+
+        // Find the tags.  The tag count comes from the file, so check it
+        // against the file's size before looping over it.
+        let tag_count = u32_at(128)? as usize;
+        if tag_count.saturating_mul(12) > data.len().saturating_sub(132) {
+            return Err("the file is truncated".into());
+        }
+        let tag = |signature: &[u8]| -> Result<usize, String> {
+            for i in 0..tag_count {
+                let entry = 132 + i * 12;
+                if data.get(entry..(entry + 4)) == Some(signature) {
+                    return Ok(u32_at(entry + 4)? as usize);
+                }
+            }
+            Err(format!(
+                "the profile has no \"{}\" tag, and only matrix/TRC profiles are supported",
+                String::from_utf8_lossy(signature).trim()
+            ))
+        };
+        let xyz = |signature: &[u8]| -> Result<[f64; 3], String> {
+            let offset = tag(signature)?;
+            Ok([
+                s15_fixed16_at(offset + 8)?,
+                s15_fixed16_at(offset + 12)?,
+                s15_fixed16_at(offset + 16)?,
+            ])
+        };
+        let trc = |signature: &[u8]| -> Result<ToneCurve, String> {
+            let offset = tag(signature)?;
+            match data.get(offset..(offset + 4)) {
+                Some(b"curv") => {
+                    let count = u32_at(offset + 8)? as usize;
+                    match count {
+                        0 => Ok(ToneCurve::Parametric(
+                            0,
+                            [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                        )),
+                        1 => {
+                            let gamma = u16_at(offset + 12)? as f64 / 256.0;
+                            Ok(ToneCurve::Parametric(
+                                0,
+                                [gamma, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                            ))
+                        }
+                        _ => (0..count)
+                            .map(|i| Ok(u16_at(offset + 12 + i * 2)? as f64 / 65535.0))
+                            .collect::<Result<Vec<_>, String>>()
+                            .map(ToneCurve::Table),
+                    }
+                }
+                Some(b"para") => {
+                    let function = u16_at(offset + 8)?;
+                    let param_count = match function {
+                        0 => 1,
+                        1 => 3,
+                        2 => 4,
+                        3 => 5,
+                        4 => 7,
+                        _ => return Err("unknown parametric curve type".into()),
+                    };
+                    let mut params = [0.0; 7];
+                    for (i, param) in params.iter_mut().enumerate().take(param_count) {
+                        *param = s15_fixed16_at(offset + 12 + i * 4)?;
+                    }
+                    Ok(ToneCurve::Parametric(function, params))
+                }
+                _ => Err("unsupported tone curve type".into()),
+            }
+        };
+
+        let (r, g, b) = (xyz(b"rXYZ")?, xyz(b"gXYZ")?, xyz(b"bXYZ")?);
+        let to_xyz = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+        Ok(IccProfile {
+            from_xyz: matrix::invert(to_xyz).ok_or("the profile's matrix isn't invertible")?,
+            trcs: [trc(b"rTRC")?, trc(b"gTRC")?, trc(b"bTRC")?],
+        })
+    }
+}
+
+impl ToneCurve {
+    fn eval(&self, x: f64) -> f64 {
+        match *self {
+            ToneCurve::Table(ref table) => {
+                if table.len() < 2 {
+                    return x;
+                }
+                let x = x.max(0.0).min(1.0) * (table.len() - 1) as f64;
+                let i = (x as usize).min(table.len() - 2);
+                let t = x - i as f64;
+                table[i] * (1.0 - t) + table[i + 1] * t
+            }
+            ToneCurve::Parametric(function, [g, a, b, c, d, e, f]) => {
+                let x = x.max(0.0);
+                match function {
+                    0 => x.powf(g),
+                    1 if x >= -b / a => (a * x + b).powf(g),
+                    1 => 0.0,
+                    2 if x >= -b / a => (a * x + b).powf(g) + c,
+                    2 => c,
+                    3 if x >= d => (a * x + b).powf(g),
+                    3 => c * x,
+                    4 if x >= d => (a * x + b).powf(g) + e,
+                    _ => c * x + f,
+                }
+            }
+        }
+    }
+
+    /// Maps a linear value back to a device value, by bisection.  Tone
+    /// curves are assumed to be increasing.
+    fn inverse(&self, y: f64) -> f64 {
+        let (mut low, mut high) = (0.0, 1.0);
+        if y <= self.eval(low) {
+            return low;
+        }
+        if y >= self.eval(high) {
+            return high;
+        }
+        for _ in 0..24 {
+            let mid = (low + high) * 0.5;
+            if self.eval(mid) < y {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) * 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s15_fixed16(n: f64) -> [u8; 4] {
+        ((n * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    fn curv(entries: &[u16]) -> Vec<u8> {
+        let mut data = b"curv\0\0\0\0".to_vec();
+        data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        for n in entries.iter() {
+            data.extend_from_slice(&n.to_be_bytes());
+        }
+        data
+    }
+
+    fn para(function: u16, params: &[f64]) -> Vec<u8> {
+        let mut data = b"para\0\0\0\0".to_vec();
+        data.extend_from_slice(&function.to_be_bytes());
+        data.extend_from_slice(&[0, 0]);
+        for n in params.iter() {
+            data.extend_from_slice(&s15_fixed16(*n));
+        }
+        data
+    }
+
+    /// Builds a matrix/TRC profile with the primaries at the XYZ axes,
+    /// and the same tone curve tag for all three channels.
+    fn profile(trc: &[u8]) -> Vec<u8> {
+        let mut tags: Vec<(&[u8], Vec<u8>)> = Vec::new();
+        for (signature, xyz) in [
+            (b"rXYZ", [1.0, 0.0, 0.0]),
+            (b"gXYZ", [0.0, 1.0, 0.0]),
+            (b"bXYZ", [0.0, 0.0, 1.0]),
+        ]
+        .iter()
+        {
+            let mut data = b"XYZ \0\0\0\0".to_vec();
+            for n in xyz.iter() {
+                data.extend_from_slice(&s15_fixed16(*n));
+            }
+            tags.push((&signature[..], data));
+        }
+        for signature in [b"rTRC", b"gTRC", b"bTRC"].iter() {
+            tags.push((&signature[..], trc.to_vec()));
+        }
+
+        let mut data = vec![0u8; 128];
+        data[16..20].copy_from_slice(b"RGB ");
+        data[36..40].copy_from_slice(b"acsp");
+        data.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+        let mut offset = 132 + (tags.len() * 12);
+        for (signature, tag_data) in tags.iter() {
+            data.extend_from_slice(signature);
+            data.extend_from_slice(&(offset as u32).to_be_bytes());
+            data.extend_from_slice(&(tag_data.len() as u32).to_be_bytes());
+            offset += tag_data.len();
+        }
+        for (_, tag_data) in tags.iter() {
+            data.extend_from_slice(tag_data);
+        }
+        data
+    }
+
+    fn parse_err(data: &[u8]) -> String {
+        match IccProfile::parse(data) {
+            Ok(_) => panic!("parsing should have failed"),
+            Err(e) => e,
+        }
+    }
+
+    fn trc(data: &[u8]) -> ToneCurve {
+        let profile = IccProfile::parse(&profile(data)).unwrap();
+        assert_eq!(
+            profile.from_xyz,
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+        );
+        let [r, _, _] = profile.trcs;
+        r
+    }
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1.0e-4, "{} != {}", a, b);
+    }
+
+    /// Checks that `inverse()` undoes `eval()` across the curve.
+    fn assert_round_trip(curve: &ToneCurve) {
+        for i in 0..=10 {
+            let x = i as f64 / 10.0;
+            assert_near(curve.inverse(curve.eval(x)), x);
+        }
+    }
+
+    #[test]
+    fn parse_bad_files() {
+        assert_eq!(parse_err(b""), "the file is not an ICC profile");
+
+        let mut data = profile(&curv(&[]));
+        data[16..20].copy_from_slice(b"CMYK");
+        assert_eq!(parse_err(&data), "only RGB profiles are supported");
+
+        // Cut off in the header, the tag table, and the tag data.
+        let data = profile(&curv(&[]));
+        assert_eq!(parse_err(&data[..130]), "the file is truncated");
+        assert_eq!(parse_err(&data[..140]), "the file is truncated");
+        assert_eq!(
+            parse_err(&data[..(data.len() - 1)]),
+            "the file is truncated"
+        );
+
+        // A tag count that can't fit in the file.
+        let mut data = profile(&curv(&[]));
+        data[128..132].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(parse_err(&data), "the file is truncated");
+
+        // Missing tags.
+        let mut data = profile(&curv(&[]));
+        data[128..132].copy_from_slice(&3u32.to_be_bytes());
+        assert_eq!(
+            parse_err(&data),
+            "the profile has no \"rTRC\" tag, and only matrix/TRC profiles are supported"
+        );
+
+        assert_eq!(
+            parse_err(&profile(&para(5, &[1.0]))),
+            "unknown parametric curve type"
+        );
+        assert_eq!(
+            parse_err(&profile(b"mft2\0\0\0\0")),
+            "unsupported tone curve type"
+        );
+    }
+
+    #[test]
+    fn curv_trcs() {
+        // No entries is the identity.
+        let curve = trc(&curv(&[]));
+        assert_near(curve.eval(0.3), 0.3);
+        assert_round_trip(&curve);
+
+        // One entry is a gamma, in 8.8 fixed point.
+        let curve = trc(&curv(&[0x0233]));
+        assert_near(curve.eval(0.5), 0.5f64.powf(563.0 / 256.0));
+        assert_round_trip(&curve);
+
+        // More entries are a table.
+        let curve = trc(&curv(&[0, 16384, 65535]));
+        assert_near(curve.eval(0.25), 8192.0 / 65535.0);
+        assert_near(curve.eval(0.5), 16384.0 / 65535.0);
+        assert_near(curve.eval(1.5), 1.0);
+        assert_round_trip(&curve);
+    }
+
+    #[test]
+    fn para_trcs() {
+        // Type 0: x^g.
+        let curve = trc(&para(0, &[2.2]));
+        assert_near(curve.eval(0.5), 0.5f64.powf(2.2));
+        assert_round_trip(&curve);
+
+        // Type 1: (ax + b)^g above -b/a, zero below.
+        let curve = trc(&para(1, &[2.0, 1.25, -0.25]));
+        assert_near(curve.eval(0.1), 0.0);
+        assert_near(curve.eval(0.6), 0.25);
+
+        // Type 2: like type 1, plus c.
+        let curve = trc(&para(2, &[2.0, 1.25, -0.25, 0.125]));
+        assert_near(curve.eval(0.1), 0.125);
+        assert_near(curve.eval(0.6), 0.375);
+
+        // Type 3 is the sRGB curve.
+        let srgb = [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045];
+        let curve = trc(&para(3, &srgb));
+        for x in [0.02, 0.04, 0.5, 0.9].iter() {
+            assert_near(curve.eval(*x), srgb_to_linear(*x));
+        }
+        assert_round_trip(&curve);
+
+        // Type 4: like type 3, plus offsets on both segments.
+        let curve = trc(&para(4, &[1.0, 0.5, 0.0, 0.25, 0.5, 0.5, 0.125]));
+        assert_near(curve.eval(0.4), 0.225);
+        assert_near(curve.eval(0.6), 0.8);
+    }
+
+    #[test]
+    fn written_icc_profiles() {
+        // A to-linear curve that goes above one, which the writer scales
+        // into the colorants.
+        let table: Vec<f32> = (0..1024)
+            .map(|i| {
+                let x = i as f32 / 1023.0;
+                x * x * 2.0
+            })
+            .collect();
+        let mut data = Vec::new();
+        crate::lut_formats::write_icc(
+            &mut data,
+            (0.0, 1.0),
+            [&table, &table, &table],
+            colorbox::chroma::REC709,
+            None,
+            &crate::lut_formats::LutMetadata {
+                name: "Test".into(),
+                notes: vec!["Generated for a test".into()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize,
+            data.len()
+        );
+
+        let profile = IccProfile::parse(&data).unwrap();
+        for curve in profile.trcs.iter() {
+            assert_near(curve.eval(0.5), 0.25);
+            assert_near(curve.eval(1.0), 1.0);
+        }
+
+        // Device white maps to twice the D50 white.
+        let to_xyz = matrix::invert(profile.from_xyz).unwrap();
+        let xyz = matrix::transform_color([1.0, 1.0, 1.0], to_xyz);
+        for (a, b) in xyz.iter().zip([0.9642, 1.0, 0.8249].iter()) {
+            assert!((a - (b * 2.0)).abs() < 1.0e-3, "{:?}", xyz);
+        }
+    }
+
+    #[test]
+    fn lut_identity() {
+        let lut = Lut::bake(Ok).unwrap();
+        for r in (0..=255).step_by(15) {
+            for g in (0..=255).step_by(51) {
+                for b in [0u8, 1, 127, 128, 254, 255].iter() {
+                    assert_eq!(lut.eval([r as u8, g as u8, *b]), [r as u8, g as u8, *b]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,418 @@
+//! Reading Exif data from image files, including Exif that isn't where
+//! the Exif reader looks for it.
+//!
+//! Some converters store Exif in a private TIFF tag (e.g. in Photoshop
+//! image resources) rather than in an Exif IFD, and some PNG writers
+//! store it in text chunks, or in eXIf chunks with a JPEG-style "Exif"
+//! header.  Those are searched for when the file has no readable Exif
+//! exposure data in the standard places.
+
+use std::{convert::TryInto, fs::File, io::BufReader, path::Path};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// TIFF tag of Photoshop image resources.
+const TIFF_TAG_PHOTOSHOP: u16 = 34377;
+/// Photoshop image resource ID of Exif data.
+const PHOTOSHOP_RESOURCE_EXIF: u16 = 0x0422;
+
+/// Reads the Exif data of an image file, if any.
+pub fn read_exif(path: &Path) -> Option<exif::Exif> {
+    let standard = File::open(path).ok().and_then(|file| {
+        exif::Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+            .ok()
+    });
+    if standard.as_ref().map(has_exposure).unwrap_or(false) {
+        return standard;
+    }
+
+    let data = std::fs::read(path).ok()?;
+    let blocks = if data.starts_with(PNG_SIGNATURE) {
+        png_exif_blocks(&data)
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        tiff_exif_blocks(&data)
+    } else {
+        Vec::new()
+    };
+
+    blocks
+        .into_iter()
+        .filter_map(|block| {
+            let block = block.strip_prefix(EXIF_HEADER).unwrap_or(&block).to_vec();
+            exif::Reader::new().read_raw(block).ok()
+        })
+        .find(has_exposure)
+        .or(standard)
+}
+
+fn has_exposure(exif: &exif::Exif) -> bool {
+    exif.get_field(exif::Tag::ExposureTime, exif::In::PRIMARY)
+        .is_some()
+}
+
+/// Finds Exif blocks stored in the chunks of a PNG file.
+fn png_exif_blocks(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut blocks = Vec::new();
+
+    let mut i = PNG_SIGNATURE.len();
+    while i + 8 <= data.len() {
+        let len = u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]) as usize;
+        let chunk_type = &data[(i + 4)..(i + 8)];
+        let chunk = match data.get((i + 8)..(i + 8 + len)) {
+            Some(chunk) => chunk,
+            None => break,
+        };
+
+        match chunk_type {
+            b"eXIf" => blocks.push(chunk.to_vec()),
+            b"tEXt" => {
+                if let Some((keyword, text)) = split_at_nul(chunk) {
+                    blocks.extend(raw_profile(keyword, text));
+                }
+            }
+            b"iTXt" => {
+                // Only uncompressed text is supported.
+                if let Some((keyword, rest)) = split_at_nul(chunk) {
+                    if rest.first() == Some(&0) && rest.len() > 2 {
+                        let text = split_at_nul(&rest[2..])
+                            .and_then(|(_, rest)| split_at_nul(rest))
+                            .map(|(_, text)| text);
+                        if let Some(text) = text {
+                            blocks.extend(raw_profile(keyword, text));
+                        }
+                    }
+                }
+            }
+            b"IEND" => break,
+            _ => {}
+        }
+
+        i += 12 + len;
+    }
+
+    blocks
+}
+
+fn split_at_nul(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let i = data.iter().position(|b| *b == 0)?;
+    Some((&data[..i], &data[(i + 1)..]))
+}
+
+/// Decodes an ImageMagick-style hex encoded raw profile, if `keyword`
+/// is one that holds Exif data.
+///
+/// The text is a profile name line, a length line, and then the hex
+/// encoded data.
+fn raw_profile(keyword: &[u8], text: &[u8]) -> Option<Vec<u8>> {
+    if keyword != b"Raw profile type exif" && keyword != b"Raw profile type APP1" {
+        return None;
+    }
+
+    let text = std::str::from_utf8(text).ok()?;
+    let mut lines = text.trim_start().splitn(3, '\n');
+    let _name = lines.next()?;
+    let len: usize = lines.next()?.trim().parse().ok()?;
+    let hex: Vec<u8> = lines
+        .next()?
+        .bytes()
+        .filter(|b| b.is_ascii_hexdigit())
+        .collect();
+
+    let bytes: Vec<u8> = hex
+        .chunks_exact(2)
+        .filter_map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect();
+    if bytes.len() < len {
+        return None;
+    }
+    Some(bytes[..len].to_vec())
+}
+
+/// Finds Exif blocks stored in private tags of the first IFD of a TIFF
+/// file.
+fn tiff_exif_blocks(data: &[u8]) -> Vec<Vec<u8>> {
+    let big_endian = data.starts_with(b"MM");
+    let u16_at = |i: usize| -> Option<u16> {
+        let b = data.get(i..(i + 2))?;
+        Some(if big_endian {
+            u16::from_be_bytes([b[0], b[1]])
+        } else {
+            u16::from_le_bytes([b[0], b[1]])
+        })
+    };
+    let u32_at = |i: usize| -> Option<u32> {
+        let b = data.get(i..(i + 4))?;
+        Some(if big_endian {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        })
+    };
+
+    let mut blocks = Vec::new();
+    let ifd = match u32_at(4) {
+        Some(offset) => offset as usize,
+        None => return blocks,
+    };
+    let entry_count = u16_at(ifd).unwrap_or(0) as usize;
+    for entry in 0..entry_count {
+        let e = ifd + 2 + entry * 12;
+        let (tag, field_type, count) = match (u16_at(e), u16_at(e + 2), u32_at(e + 4)) {
+            (Some(tag), Some(field_type), Some(count)) => (tag, field_type, count as usize),
+            _ => break,
+        };
+
+        // Only byte and undefined fields can hold embedded data.
+        if (field_type != 1 && field_type != 7) || count <= 4 {
+            continue;
+        }
+        let value = match u32_at(e + 8).and_then(|offset| {
+            let offset = offset as usize;
+            data.get(offset..(offset.checked_add(count)?))
+        }) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        if tag == TIFF_TAG_PHOTOSHOP {
+            if let Some(block) = photoshop_exif(value) {
+                blocks.push(block.to_vec());
+            }
+        } else if value.starts_with(EXIF_HEADER)
+            || value.starts_with(b"II*\0")
+            || value.starts_with(b"MM\0*")
+        {
+            blocks.push(value.to_vec());
+        }
+    }
+
+    blocks
+}
+
+/// Finds the Exif resource in Photoshop image resources.
+fn photoshop_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut i = 0;
+    while i + 12 <= data.len() {
+        if &data[i..(i + 4)] != b"8BIM" {
+            return None;
+        }
+        let id = u16::from_be_bytes([data[i + 4], data[i + 5]]);
+
+        // Pascal string name, padded to an even length.
+        let name_len = data[i + 6] as usize;
+        let mut j = i + 6 + 1 + name_len;
+        j += j % 2;
+
+        let size = u32::from_be_bytes(data.get(j..(j + 4))?.try_into().ok()?) as usize;
+        let resource = data.get((j + 4)..(j + 4 + size))?;
+        if id == PHOTOSHOP_RESOURCE_EXIF {
+            return Some(resource);
+        }
+
+        i = j + 4 + size + size % 2;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `RAW_PROFILE_DATA` as the hex encoded text of a raw profile, as
+    /// written by ImageMagick.
+    const RAW_PROFILE: &[u8] = b"\nexif\n      10\n4578696600004d4d\n002a\n";
+    const RAW_PROFILE_DATA: &[u8] = b"Exif\0\0MM\0*";
+
+    fn png(chunks: &[(&[u8], &[u8])]) -> Vec<u8> {
+        let mut data = PNG_SIGNATURE.to_vec();
+        for (chunk_type, chunk) in chunks.iter() {
+            data.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            data.extend_from_slice(chunk_type);
+            data.extend_from_slice(chunk);
+            data.extend_from_slice(&[0; 4]); // CRC, which isn't checked.
+        }
+        data
+    }
+
+    /// A little-endian TIFF with one IFD of `(tag, type, value)`
+    /// entries, with the values stored after the IFD.
+    fn tiff(entries: &[(u16, u16, &[u8])]) -> Vec<u8> {
+        let mut data = b"II*\0".to_vec();
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        let mut offset = 8 + 2 + (entries.len() * 12) + 4;
+        for (tag, field_type, value) in entries.iter() {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&field_type.to_le_bytes());
+            data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += value.len();
+        }
+        data.extend_from_slice(&0u32.to_le_bytes());
+        for (_, _, value) in entries.iter() {
+            data.extend_from_slice(value);
+        }
+        data
+    }
+
+    fn photoshop_resource(id: u16, name: &[u8], resource: &[u8]) -> Vec<u8> {
+        let mut data = b"8BIM".to_vec();
+        data.extend_from_slice(&id.to_be_bytes());
+        data.push(name.len() as u8);
+        data.extend_from_slice(name);
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        data.extend_from_slice(&(resource.len() as u32).to_be_bytes());
+        data.extend_from_slice(resource);
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        data
+    }
+
+    #[test]
+    fn raw_profile_01() {
+        assert_eq!(
+            raw_profile(b"Raw profile type exif", RAW_PROFILE),
+            Some(RAW_PROFILE_DATA.to_vec())
+        );
+        assert_eq!(
+            raw_profile(b"Raw profile type APP1", RAW_PROFILE),
+            Some(RAW_PROFILE_DATA.to_vec())
+        );
+        assert_eq!(raw_profile(b"Raw profile type iptc", RAW_PROFILE), None);
+
+        // Extra data is ignored, and missing data is an error.
+        assert_eq!(
+            raw_profile(b"Raw profile type exif", b"\nexif\n4\n4578696600004d4d\n"),
+            Some(b"Exif".to_vec())
+        );
+        assert_eq!(
+            raw_profile(b"Raw profile type exif", b"\nexif\n12\n4578696600004d4d\n"),
+            None
+        );
+        assert_eq!(raw_profile(b"Raw profile type exif", b"\nexif\n"), None);
+    }
+
+    #[test]
+    fn png_exif_blocks_01() {
+        let mut itxt = b"Raw profile type exif\0\0\0en\0\0".to_vec();
+        itxt.extend_from_slice(RAW_PROFILE);
+        let mut compressed_itxt = b"Raw profile type exif\0\x01\0en\0\0".to_vec();
+        compressed_itxt.extend_from_slice(RAW_PROFILE);
+        let mut text = b"Raw profile type exif\0".to_vec();
+        text.extend_from_slice(RAW_PROFILE);
+
+        let data = png(&[
+            (b"IHDR", &[0; 13]),
+            (b"eXIf", b"MM\0*"),
+            (b"tEXt", &text),
+            (b"tEXt", b"Comment\0hello"),
+            (b"iTXt", &itxt),
+            (b"iTXt", &compressed_itxt),
+            (b"IEND", b""),
+            (b"eXIf", b"II*\0"),
+        ]);
+        assert_eq!(
+            png_exif_blocks(&data),
+            vec![
+                b"MM\0*".to_vec(),
+                RAW_PROFILE_DATA.to_vec(),
+                RAW_PROFILE_DATA.to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn png_exif_blocks_truncated() {
+        // A chunk cut off at the end of the file is ignored, along with
+        // an incomplete chunk header.
+        let data = png(&[(b"eXIf", b"MM\0*"), (b"eXIf", b"II*\0")]);
+        assert_eq!(
+            png_exif_blocks(&data[..(data.len() - 6)]),
+            vec![b"MM\0*".to_vec()]
+        );
+        assert_eq!(
+            png_exif_blocks(&data[..(data.len() - 14)]),
+            vec![b"MM\0*".to_vec()]
+        );
+
+        // So is one whose length runs past the end of the file.
+        let mut data = png(&[(b"eXIf", b"MM\0*")]);
+        data[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(png_exif_blocks(&data), Vec::<Vec<u8>>::new());
+    }
+
+    #[test]
+    fn photoshop_exif_01() {
+        // Names of every length parity, and odd-sized resources, which
+        // are both padded to an even length.
+        for name in [&b""[..], b"a", b"ab"].iter() {
+            let mut data = photoshop_resource(0x0404, name, b"iptc!");
+            data.extend(photoshop_resource(PHOTOSHOP_RESOURCE_EXIF, name, b"MM\0*"));
+            assert_eq!(photoshop_exif(&data), Some(&b"MM\0*"[..]));
+        }
+
+        let data = photoshop_resource(0x0404, b"", b"iptc");
+        assert_eq!(photoshop_exif(&data), None);
+
+        // Bad signatures and sizes.
+        let mut data = photoshop_resource(PHOTOSHOP_RESOURCE_EXIF, b"", b"MM\0*");
+        data[0..4].copy_from_slice(b"8BPS");
+        assert_eq!(photoshop_exif(&data), None);
+        let mut data = photoshop_resource(PHOTOSHOP_RESOURCE_EXIF, b"", b"MM\0*");
+        data[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(photoshop_exif(&data), None);
+        let data = photoshop_resource(PHOTOSHOP_RESOURCE_EXIF, b"name", b"MM\0*");
+        assert_eq!(photoshop_exif(&data[..(data.len() - 1)]), None);
+    }
+
+    #[test]
+    fn tiff_exif_blocks_01() {
+        let photoshop = photoshop_resource(PHOTOSHOP_RESOURCE_EXIF, b"", b"MM\0*\0\0\0\x08");
+        let data = tiff(&[
+            (256, 3, &[1, 0]),
+            (700, 1, b"Exif\0\0II*\0"),
+            (701, 7, b"not exif"),
+            (702, 3, b"Exif\0\0II*\0"),
+            (TIFF_TAG_PHOTOSHOP, 7, &photoshop),
+        ]);
+        assert_eq!(
+            tiff_exif_blocks(&data),
+            vec![b"Exif\0\0II*\0".to_vec(), b"MM\0*\0\0\0\x08".to_vec()]
+        );
+    }
+
+    #[test]
+    fn tiff_exif_blocks_truncated() {
+        let data = tiff(&[(700, 1, b"Exif\0\0II*\0"), (701, 1, b"Exif\0\0MM\0*")]);
+
+        // Values that run past the end of the file are skipped.
+        assert_eq!(
+            tiff_exif_blocks(&data[..(data.len() - 1)]),
+            vec![b"Exif\0\0II*\0".to_vec()]
+        );
+
+        // A bad count is skipped.
+        let mut bad_count = data.clone();
+        bad_count[14..18].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            tiff_exif_blocks(&bad_count),
+            vec![b"Exif\0\0MM\0*".to_vec()]
+        );
+
+        // An entry count larger than the IFD, or an IFD outside of the
+        // file, stops the search.
+        let mut bad_entry_count = data.clone();
+        bad_entry_count[8..10].copy_from_slice(&1000u16.to_le_bytes());
+        assert_eq!(tiff_exif_blocks(&bad_entry_count).len(), 2);
+        assert_eq!(tiff_exif_blocks(&data[..20]), Vec::<Vec<u8>>::new());
+        let mut bad_ifd = data;
+        bad_ifd[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(tiff_exif_blocks(&bad_ifd), Vec::<Vec<u8>>::new());
+        assert_eq!(tiff_exif_blocks(b"II*\0"), Vec::<Vec<u8>>::new());
+    }
+}
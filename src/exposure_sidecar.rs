@@ -0,0 +1,309 @@
+//! Reading exposure metadata from sidecar files, for images without
+//! Exif data (e.g. scans, or images that have been transcoded).
+//!
+//! Two kinds of sidecar files are supported:
+//!
+//! - XMP files next to the image, named either after the image without
+//!   its extension (`IMG_0001.xmp`) or with it (`IMG_0001.tif.xmp`).
+//! - A CSV file named `exposures.csv` or `exposures.txt` in the image's
+//!   directory, covering all the images in it.  Each line is
+//!   `filename, shutter, fstop, iso`, where the shutter speed is in
+//!   seconds (either decimal or a fraction like `1/60`) and any of the
+//!   values can be left empty if unknown.  Lines starting with `#` are
+//!   ignored.
+
+use std::path::Path;
+
+/// File names of the per-directory CSV sidecar files, in order of
+/// preference.
+const CSV_FILENAMES: &[&str] = &["exposures.csv", "exposures.txt"];
+
+/// Exposure metadata from a sidecar file.  Ratios are `(numerator,
+/// denominator)`, the same as in `ImageInfo`.
+#[derive(Debug, Clone, Default)]
+pub struct SidecarMetadata {
+    pub exposure_time: Option<(u32, u32)>,
+    pub fstop: Option<(u32, u32)>,
+    pub iso: Option<u32>,
+    pub camera: Option<(String, String)>, // (make, model)
+}
+
+/// Reads the sidecar exposure metadata of an image, if any.
+///
+/// XMP sidecars take precedence over the CSV sidecar, but values
+/// missing from the former are filled in from the latter.
+pub fn read_sidecar_metadata(image_path: &Path) -> Option<SidecarMetadata> {
+    let xmp = read_xmp_sidecar(image_path);
+    let csv = read_csv_sidecar(image_path);
+
+    match (xmp, csv) {
+        (Some(xmp), Some(csv)) => Some(SidecarMetadata {
+            exposure_time: xmp.exposure_time.or(csv.exposure_time),
+            fstop: xmp.fstop.or(csv.fstop),
+            iso: xmp.iso.or(csv.iso),
+            camera: xmp.camera.or(csv.camera),
+        }),
+        (xmp, csv) => xmp.or(csv),
+    }
+}
+
+fn read_xmp_sidecar(image_path: &Path) -> Option<SidecarMetadata> {
+    let with_extension = {
+        let mut path = image_path.as_os_str().to_os_string();
+        path.push(".xmp");
+        path
+    };
+    let text = std::fs::read_to_string(image_path.with_extension("xmp"))
+        .or_else(|_| std::fs::read_to_string(with_extension))
+        .ok()?;
+
+    let iso = xmp_property(&text, "exif:ISOSpeedRatings")
+        .or_else(|| xmp_property(&text, "exifEX:PhotographicSensitivity"))
+        .and_then(|n| n.parse::<u32>().ok())
+        .filter(|n| *n != 0);
+    let camera = match (
+        xmp_property(&text, "tiff:Make"),
+        xmp_property(&text, "tiff:Model"),
+    ) {
+        (Some(make), Some(model)) => Some((make, model)),
+        _ => None,
+    };
+
+    Some(SidecarMetadata {
+        exposure_time: xmp_property(&text, "exif:ExposureTime").and_then(|n| parse_ratio(&n)),
+        fstop: xmp_property(&text, "exif:FNumber").and_then(|n| parse_fstop(&n)),
+        iso: iso,
+        camera: camera,
+    })
+}
+
+/// Finds the value of an XMP property, written either as an attribute
+/// (`name="value"`) or as an element (`<name>value</name>`).  For
+/// elements, any nested tags (e.g. of an `rdf:Seq`) are skipped and the
+/// first value is returned.
+fn xmp_property(text: &str, name: &str) -> Option<String> {
+    // Attribute.
+    let attribute = format!("{}=", name);
+    if let Some(i) = text.find(&attribute) {
+        let rest = &text[(i + attribute.len())..];
+        let quote = rest.chars().next()?;
+        if quote == '"' || quote == '\'' {
+            let value = &rest[1..];
+            let end = value.find(quote)?;
+            return Some(value[..end].trim().to_string());
+        }
+    }
+
+    // Element.
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = text.find(&open)? + open.len();
+    let end = start + text[start..].find(&close)?;
+    text[start..end]
+        .split(&['<', '>'][..])
+        .enumerate()
+        // Text between tags is at even indices.
+        .filter(|(i, _)| i % 2 == 0)
+        .map(|(_, s)| s.trim())
+        .find(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+fn read_csv_sidecar(image_path: &Path) -> Option<SidecarMetadata> {
+    let filename = image_path.file_name()?.to_string_lossy().to_string();
+    let dir = image_path.parent()?;
+    let text = CSV_FILENAMES
+        .iter()
+        .find_map(|name| std::fs::read_to_string(dir.join(name)).ok())?;
+
+    let row = text
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.split(',').map(|s| s.trim()).collect::<Vec<_>>())
+        .find(|fields| fields[0].eq_ignore_ascii_case(&filename))?;
+    let field = |i: usize| row.get(i).copied().filter(|s| !s.is_empty());
+
+    Some(SidecarMetadata {
+        exposure_time: field(1).and_then(parse_ratio),
+        fstop: field(2).and_then(parse_fstop),
+        iso: field(3)
+            .and_then(|n| n.parse::<u32>().ok())
+            .filter(|n| *n != 0),
+        camera: None,
+    })
+}
+
+/// Parses an f-stop, with or without an "f/" prefix.
+fn parse_fstop(text: &str) -> Option<(u32, u32)> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("f/")
+        .or_else(|| text.strip_prefix("F/"))
+        .or_else(|| text.strip_prefix('f'))
+        .or_else(|| text.strip_prefix('F'))
+        .unwrap_or(text);
+    parse_ratio(text)
+}
+
+/// Parses a positive number, either as a fraction like `1/60` or as a
+/// decimal like `0.5`, into a ratio.
+fn parse_ratio(text: &str) -> Option<(u32, u32)> {
+    let text = text.trim().trim_end_matches('s').trim();
+
+    // Exact fractions.
+    if let Some((num, denom)) = text.split_once('/') {
+        if let (Ok(num), Ok(denom)) = (num.trim().parse::<u32>(), denom.trim().parse::<u32>()) {
+            return if num != 0 && denom != 0 {
+                Some((num, denom))
+            } else {
+                None
+            };
+        }
+    }
+
+    // Decimals, and fractions of decimals.
+    let n = if let Some((num, denom)) = text.split_once('/') {
+        num.trim().parse::<f64>().ok()? / denom.trim().parse::<f64>().ok()?
+    } else {
+        text.parse::<f64>().ok()?
+    };
+    if !n.is_finite() || n <= 0.0 {
+        return None;
+    }
+    let denom = if n < 1.0 { 1_000_000 } else { 1000 };
+    let num = (n * denom as f64).round();
+    if num < 1.0 || num > u32::MAX as f64 {
+        return None;
+    }
+    let (num, denom) = (num as u32, denom as u32);
+    let divisor = gcd(num, denom);
+    Some((num / divisor, denom / divisor))
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ratio_01() {
+        assert_eq!(parse_ratio("1/60"), Some((1, 60)));
+        assert_eq!(parse_ratio(" 1 / 60 s"), Some((1, 60)));
+        assert_eq!(parse_ratio("2/4"), Some((2, 4)));
+        assert_eq!(parse_ratio("0.5s"), Some((1, 2)));
+        assert_eq!(parse_ratio("0.5"), Some((1, 2)));
+        assert_eq!(parse_ratio("30"), Some((30, 1)));
+        assert_eq!(parse_ratio("2.5"), Some((5, 2)));
+        assert_eq!(parse_ratio("1/2.5"), Some((2, 5)));
+
+        assert_eq!(parse_ratio(""), None);
+        assert_eq!(parse_ratio("0"), None);
+        assert_eq!(parse_ratio("-1"), None);
+        assert_eq!(parse_ratio("0/60"), None);
+        assert_eq!(parse_ratio("1/0"), None);
+        assert_eq!(parse_ratio("1/0.0"), None);
+        assert_eq!(parse_ratio("fast"), None);
+    }
+
+    #[test]
+    fn parse_fstop_01() {
+        assert_eq!(parse_fstop("f/2.8"), Some((14, 5)));
+        assert_eq!(parse_fstop("F/8"), Some((8, 1)));
+        assert_eq!(parse_fstop("f4"), Some((4, 1)));
+        assert_eq!(parse_fstop("28/10"), Some((28, 10)));
+        assert_eq!(parse_fstop(" 1.4 "), Some((7, 5)));
+        assert_eq!(parse_fstop("f/"), None);
+    }
+
+    #[test]
+    fn xmp_property_01() {
+        let attributes = r#"<rdf:Description
+            exif:ExposureTime="1/60"
+            exif:FNumber='28/10'
+            tiff:Make=" Canon ">"#;
+        assert_eq!(
+            xmp_property(attributes, "exif:ExposureTime"),
+            Some("1/60".into())
+        );
+        assert_eq!(
+            xmp_property(attributes, "exif:FNumber"),
+            Some("28/10".into())
+        );
+        assert_eq!(xmp_property(attributes, "tiff:Make"), Some("Canon".into()));
+        assert_eq!(xmp_property(attributes, "tiff:Model"), None);
+
+        let elements = r#"<rdf:Description>
+            <exif:ExposureTime>1/60</exif:ExposureTime>
+            <exif:ISOSpeedRatings>
+                <rdf:Seq>
+                    <rdf:li>400</rdf:li>
+                    <rdf:li>800</rdf:li>
+                </rdf:Seq>
+            </exif:ISOSpeedRatings>
+            <exif:FNumber></exif:FNumber>
+        </rdf:Description>"#;
+        assert_eq!(
+            xmp_property(elements, "exif:ExposureTime"),
+            Some("1/60".into())
+        );
+        assert_eq!(
+            xmp_property(elements, "exif:ISOSpeedRatings"),
+            Some("400".into())
+        );
+        assert_eq!(xmp_property(elements, "exif:FNumber"), None);
+        assert_eq!(xmp_property(elements, "exif:ISO"), None);
+    }
+
+    #[test]
+    fn read_sidecar_metadata_01() {
+        let dir = std::env::temp_dir().join(format!("exposure_sidecar_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("exposures.csv"),
+            "# filename, shutter, fstop, iso\n\
+             \n\
+             a.tif, 1/60, f/2.8, 100\n\
+             B.TIF, , 8,\n\
+             c.tif, 0.5s\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("b.xmp"),
+            r#"<rdf:Description exif:ExposureTime="1/125" tiff:Make="Canon" tiff:Model="EOS"/>"#,
+        )
+        .unwrap();
+
+        let a = read_sidecar_metadata(&dir.join("a.tif")).unwrap();
+        assert_eq!(a.exposure_time, Some((1, 60)));
+        assert_eq!(a.fstop, Some((14, 5)));
+        assert_eq!(a.iso, Some(100));
+        assert_eq!(a.camera, None);
+
+        // Empty fields are unknown, and the XMP sidecar fills in what
+        // it can.
+        let b = read_sidecar_metadata(&dir.join("b.tif")).unwrap();
+        assert_eq!(b.exposure_time, Some((1, 125)));
+        assert_eq!(b.fstop, Some((8, 1)));
+        assert_eq!(b.iso, None);
+        assert_eq!(b.camera, Some(("Canon".into(), "EOS".into())));
+
+        // Missing fields are unknown too.
+        let c = read_sidecar_metadata(&dir.join("c.tif")).unwrap();
+        assert_eq!(c.exposure_time, Some((1, 2)));
+        assert_eq!(c.fstop, None);
+        assert_eq!(c.iso, None);
+
+        assert!(read_sidecar_metadata(&dir.join("d.tif")).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,586 @@
+//! A small expression language for user-supplied transfer functions.
+//!
+//! Formulas are written in terms of a single variable `x`, e.g.
+//! `x^2.4` or `(10^((x - 0.6) / 0.25) - 0.01) / 1.01`, and support:
+//!
+//! - The operators `+`, `-`, `*`, `/`, and `^` (power, right
+//!   associative), with the usual precedence, and unary minus.
+//! - Comparisons `<`, `<=`, `>`, and `>=`, which evaluate to 1.0 or 0.0.
+//! - The functions `ln`, `log` (base 10), `log10`, `log2`, `exp`,
+//!   `exp2`, `pow(a, b)`, `sqrt`, `abs`, `min(a, b)`, `max(a, b)`, and
+//!   `if(condition, a, b)`, which is `a` where the condition is
+//!   non-zero and `b` otherwise.  The latter is useful for the linear
+//!   toe segments that many camera log curves have.
+//! - The constants `pi` and `e`.
+//!
+//! Evaluation is done in double precision.
+
+#[derive(Debug, Clone)]
+pub struct Formula {
+    text: String,
+    root: Node,
+}
+
+impl PartialEq for Formula {
+    fn eq(&self, other: &Formula) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for Formula {}
+
+impl Default for Formula {
+    fn default() -> Formula {
+        Formula::parse("x").unwrap()
+    }
+}
+
+impl Formula {
+    /// Parses a formula.
+    ///
+    /// On failure, returns a message describing the problem and where
+    /// in the text it is.
+    pub fn parse(text: &str) -> Result<Formula, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            i: 0,
+        };
+        let root = parser.expression()?;
+        if parser.i < tokens.len() {
+            return Err(format!(
+                "unexpected {} at position {}",
+                tokens[parser.i].0.ui_text(),
+                tokens[parser.i].1 + 1
+            ));
+        }
+
+        Ok(Formula {
+            text: text.into(),
+            root: root,
+        })
+    }
+
+    /// The text the formula was parsed from.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn eval(&self, x: f32) -> f32 {
+        self.root.eval(x as f64) as f32
+    }
+}
+
+//-------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f64),
+    X,
+    Neg(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Function {
+    Ln,
+    Log10,
+    Log2,
+    Exp,
+    Exp2,
+    Pow,
+    Sqrt,
+    Abs,
+    Min,
+    Max,
+    If,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        use Function::*;
+        Some(match name {
+            "ln" => Ln,
+            "log" | "log10" => Log10,
+            "log2" => Log2,
+            "exp" => Exp,
+            "exp2" => Exp2,
+            "pow" => Pow,
+            "sqrt" => Sqrt,
+            "abs" => Abs,
+            "min" => Min,
+            "max" => Max,
+            "if" => If,
+            _ => return None,
+        })
+    }
+
+    fn arg_count(&self) -> usize {
+        use Function::*;
+        match *self {
+            Ln | Log10 | Log2 | Exp | Exp2 | Sqrt | Abs => 1,
+            Pow | Min | Max => 2,
+            If => 3,
+        }
+    }
+}
+
+impl Node {
+    fn eval(&self, x: f64) -> f64 {
+        match *self {
+            Node::Number(n) => n,
+            Node::X => x,
+            Node::Neg(ref a) => -a.eval(x),
+            Node::Binary(op, ref a, ref b) => {
+                let a = a.eval(x);
+                let b = b.eval(x);
+                let from_bool = |v: bool| if v { 1.0 } else { 0.0 };
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Pow => a.powf(b),
+                    BinaryOp::Less => from_bool(a < b),
+                    BinaryOp::LessEqual => from_bool(a <= b),
+                    BinaryOp::Greater => from_bool(a > b),
+                    BinaryOp::GreaterEqual => from_bool(a >= b),
+                }
+            }
+            Node::Call(function, ref args) => {
+                use Function::*;
+                // Only evaluate the taken branch of conditionals.
+                if function == If {
+                    return if args[0].eval(x) != 0.0 {
+                        args[1].eval(x)
+                    } else {
+                        args[2].eval(x)
+                    };
+                }
+                let a = args[0].eval(x);
+                match function {
+                    Ln => a.ln(),
+                    Log10 => a.log10(),
+                    Log2 => a.log2(),
+                    Exp => a.exp(),
+                    Exp2 => a.exp2(),
+                    Pow => a.powf(args[1].eval(x)),
+                    Sqrt => a.sqrt(),
+                    Abs => a.abs(),
+                    Min => a.min(args[1].eval(x)),
+                    Max => a.max(args[1].eval(x)),
+                    If => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+//-------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Op(&'static str),
+    OpenParen,
+    CloseParen,
+    Comma,
+}
+
+impl Token {
+    fn ui_text(&self) -> String {
+        match *self {
+            Token::Number(n) => format!("number {}", n),
+            Token::Identifier(ref name) => format!("\"{}\"", name),
+            Token::Op(op) => format!("\"{}\"", op),
+            Token::OpenParen => "\"(\"".into(),
+            Token::CloseParen => "\")\"".into(),
+            Token::Comma => "\",\"".into(),
+        }
+    }
+}
+
+/// Splits the text into tokens, each paired with its character
+/// position.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    const OPS: &[&str] = &["<=", ">=", "+", "-", "*", "/", "^", "<", ">"];
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    'outer: while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // Exponent, e.g. "1.5e-3".
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let number_text: String = chars[start..i].iter().collect();
+            match number_text.parse::<f64>() {
+                Ok(n) => tokens.push((Token::Number(n), start)),
+                Err(_) => {
+                    return Err(format!(
+                        "invalid number \"{}\" at position {}",
+                        number_text,
+                        start + 1
+                    ))
+                }
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            tokens.push((Token::Identifier(name.to_lowercase()), start));
+        } else if c == '(' {
+            tokens.push((Token::OpenParen, start));
+            i += 1;
+        } else if c == ')' {
+            tokens.push((Token::CloseParen, start));
+            i += 1;
+        } else if c == ',' {
+            tokens.push((Token::Comma, start));
+            i += 1;
+        } else {
+            for op in OPS.iter() {
+                let len = op.chars().count();
+                if chars[i..].iter().take(len).copied().eq(op.chars()) {
+                    tokens.push((Token::Op(op), start));
+                    i += len;
+                    continue 'outer;
+                }
+            }
+            return Err(format!("unexpected \"{}\" at position {}", c, start + 1));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens.
+struct Parser<'a> {
+    tokens: &'a [(Token, usize)],
+    i: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.i).map(|t| &t.0)
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) => Some(*op),
+            _ => None,
+        }
+    }
+
+    /// Error for an unexpected token (or end of text) at the current
+    /// position.
+    fn unexpected(&self, expected: &str) -> String {
+        match self.tokens.get(self.i) {
+            Some((token, pos)) => format!(
+                "expected {} at position {}, found {}",
+                expected,
+                pos + 1,
+                token.ui_text()
+            ),
+            None => format!("expected {} at end of formula", expected),
+        }
+    }
+
+    fn expect(&mut self, token: Token, expected: &str) -> Result<(), String> {
+        if self.peek() == Some(&token) {
+            self.i += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(expected))
+        }
+    }
+
+    fn expression(&mut self) -> Result<Node, String> {
+        let a = self.additive()?;
+        let op = match self.peek_op() {
+            Some("<") => BinaryOp::Less,
+            Some("<=") => BinaryOp::LessEqual,
+            Some(">") => BinaryOp::Greater,
+            Some(">=") => BinaryOp::GreaterEqual,
+            _ => return Ok(a),
+        };
+        self.i += 1;
+        let b = self.additive()?;
+        Ok(Node::Binary(op, Box::new(a), Box::new(b)))
+    }
+
+    fn additive(&mut self) -> Result<Node, String> {
+        let mut a = self.term()?;
+        loop {
+            let op = match self.peek_op() {
+                Some("+") => BinaryOp::Add,
+                Some("-") => BinaryOp::Sub,
+                _ => return Ok(a),
+            };
+            self.i += 1;
+            let b = self.term()?;
+            a = Node::Binary(op, Box::new(a), Box::new(b));
+        }
+    }
+
+    fn term(&mut self) -> Result<Node, String> {
+        let mut a = self.unary()?;
+        loop {
+            let op = match self.peek_op() {
+                Some("*") => BinaryOp::Mul,
+                Some("/") => BinaryOp::Div,
+                _ => return Ok(a),
+            };
+            self.i += 1;
+            let b = self.unary()?;
+            a = Node::Binary(op, Box::new(a), Box::new(b));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        match self.peek_op() {
+            Some("-") => {
+                self.i += 1;
+                Ok(Node::Neg(Box::new(self.unary()?)))
+            }
+            Some("+") => {
+                self.i += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Node, String> {
+        let base = self.primary()?;
+        if self.peek_op() == Some("^") {
+            self.i += 1;
+            // Right associative, and binds tighter than unary minus on
+            // its left but not on its right: -2^-2 == -(2^(-2)).
+            let exponent = self.unary()?;
+            Ok(Node::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        let (token, pos) = match self.tokens.get(self.i) {
+            Some((token, pos)) => (token.clone(), *pos),
+            None => return Err(self.unexpected("a value")),
+        };
+        match token {
+            Token::Number(n) => {
+                self.i += 1;
+                Ok(Node::Number(n))
+            }
+            Token::OpenParen => {
+                self.i += 1;
+                let node = self.expression()?;
+                self.expect(Token::CloseParen, "\")\"")?;
+                Ok(node)
+            }
+            Token::Identifier(name) => {
+                self.i += 1;
+                match name.as_str() {
+                    "x" => return Ok(Node::X),
+                    "pi" => return Ok(Node::Number(std::f64::consts::PI)),
+                    "e" => return Ok(Node::Number(std::f64::consts::E)),
+                    _ => {}
+                }
+                let function = Function::from_name(&name)
+                    .ok_or_else(|| format!("unknown name \"{}\" at position {}", name, pos + 1))?;
+
+                self.expect(Token::OpenParen, "\"(\"")?;
+                let mut args = vec![self.expression()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.i += 1;
+                    args.push(self.expression()?);
+                }
+                self.expect(Token::CloseParen, "\")\"")?;
+
+                if args.len() != function.arg_count() {
+                    return Err(format!(
+                        "\"{}\" at position {} takes {} argument{}, but was given {}",
+                        name,
+                        pos + 1,
+                        function.arg_count(),
+                        if function.arg_count() == 1 { "" } else { "s" },
+                        args.len()
+                    ));
+                }
+
+                Ok(Node::Call(function, args))
+            }
+            _ => Err(self.unexpected("a value")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, x: f32) -> f32 {
+        Formula::parse(text).unwrap().eval(x)
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1.0e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn precedence() {
+        assert_near(eval("1 + 2 * 3", 0.0), 7.0);
+        assert_near(eval("(1 + 2) * 3", 0.0), 9.0);
+        assert_near(eval("8 / 4 / 2", 0.0), 1.0);
+        assert_near(eval("2 - 3 - 4", 0.0), -5.0);
+        assert_near(eval("2 * x^2", 3.0), 18.0);
+
+        // Unary minus binds looser than "^" on its left, but is part of
+        // the exponent on its right.
+        assert_near(eval("-2^2", 0.0), -4.0);
+        assert_near(eval("-2^-2", 0.0), -0.25);
+        assert_near(eval("2^-x", 2.0), 0.25);
+        assert_near(eval("--x", 3.0), 3.0);
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        assert_near(eval("2^3^2", 0.0), 512.0);
+        assert_near(eval("(2^3)^2", 0.0), 64.0);
+    }
+
+    #[test]
+    fn numbers_and_constants() {
+        assert_near(eval("1.5e-3", 0.0), 0.0015);
+        assert_near(eval("1.5E+3", 0.0), 1500.0);
+        assert_near(eval("2e2", 0.0), 200.0);
+        assert_near(eval(".5", 0.0), 0.5);
+
+        // "e" not followed by digits is the constant, not an exponent.
+        assert_near(eval("e", 0.0), std::f32::consts::E);
+        assert_near(eval("2*e", 0.0), 2.0 * std::f32::consts::E);
+        assert_eq!(
+            Formula::parse("2e").unwrap_err(),
+            "unexpected \"e\" at position 2"
+        );
+        assert_near(eval("PI", 0.0), std::f32::consts::PI);
+    }
+
+    #[test]
+    fn functions() {
+        assert_near(eval("log(100)", 0.0), 2.0);
+        assert_near(eval("log2(x)", 8.0), 3.0);
+        assert_near(eval("pow(x, 2)", 3.0), 9.0);
+        assert_near(eval("min(x, 1) + max(x, 1)", 0.5), 1.5);
+        assert_near(eval("if(x < 0.5, 0, 1)", 0.25), 0.0);
+        assert_near(eval("if(x < 0.5, 0, 1)", 0.75), 1.0);
+
+        // Only the taken branch is evaluated, so the other one can be
+        // out of its domain.
+        assert_near(eval("if(x > 0, ln(x), 0)", 0.0), 0.0);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_near(eval("x < 1", 0.5), 1.0);
+        assert_near(eval("x >= 1", 0.5), 0.0);
+        assert_near(eval("x + 1 <= 2 * x", 1.0), 1.0);
+
+        // Comparisons don't chain.
+        assert_eq!(
+            Formula::parse("0 < x < 1").unwrap_err(),
+            "unexpected \"<\" at position 7"
+        );
+    }
+
+    #[test]
+    fn argument_count_errors() {
+        assert_eq!(
+            Formula::parse("pow(x)").unwrap_err(),
+            "\"pow\" at position 1 takes 2 arguments, but was given 1"
+        );
+        assert_eq!(
+            Formula::parse("x + sqrt(x, 2)").unwrap_err(),
+            "\"sqrt\" at position 5 takes 1 argument, but was given 2"
+        );
+        assert_eq!(
+            Formula::parse("if(x, 1)").unwrap_err(),
+            "\"if\" at position 1 takes 3 arguments, but was given 2"
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(
+            Formula::parse("x + $").unwrap_err(),
+            "unexpected \"$\" at position 5"
+        );
+        assert_eq!(
+            Formula::parse("1..2").unwrap_err(),
+            "invalid number \"1..2\" at position 1"
+        );
+        assert_eq!(
+            Formula::parse("2 * y").unwrap_err(),
+            "unknown name \"y\" at position 5"
+        );
+        assert_eq!(
+            Formula::parse("(x + 1").unwrap_err(),
+            "expected \")\" at end of formula"
+        );
+        assert_eq!(
+            Formula::parse("x * * 2").unwrap_err(),
+            "expected a value at position 5, found \"*\""
+        );
+        assert_eq!(
+            Formula::parse("ln x").unwrap_err(),
+            "expected \"(\" at position 4, found \"x\""
+        );
+        assert_eq!(
+            Formula::parse("x 2").unwrap_err(),
+            "unexpected number 2 at position 3"
+        );
+        assert_eq!(
+            Formula::parse("").unwrap_err(),
+            "expected a value at end of formula"
+        );
+    }
+}
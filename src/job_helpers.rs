@@ -1,10 +1,13 @@
 use std::{fs::File, io::BufReader, path::Path};
 
-use colorbox::{formats, lut::Lut1D};
+use colorbox::{
+    formats,
+    lut::{Lut1D, Lut3D},
+};
 
 use sensor_analysis::Histogram;
 
-use crate::{ImageInfo, SourceImage};
+use crate::{FloatEncoding, ImageInfo, SampleMask, SourceImage};
 use image_fmt::ImageBuf;
 
 pub fn load_image(path: &Path) -> Result<SourceImage, image_fmt::ReadError> {
@@ -15,20 +18,21 @@ pub fn load_image(path: &Path) -> Result<SourceImage, image_fmt::ReadError> {
         img
     };
 
-    // Get exposure metadata from EXIF data.
-    let (exposure_time, fstop, sensitivity) = {
+    // Get exposure metadata from EXIF data, wherever in the file it is,
+    // or failing that from sidecar files.
+    let (exposure_time, fstop, sensitivity, camera) = {
         let mut exposure_time = None;
         let mut fstop = None;
         let mut sensitivity = None;
+        let mut camera = None;
 
-        let mut file = std::io::BufReader::new(std::fs::File::open(&path)?);
-        if let Ok(img_exif) = exif::Reader::new().read_from_container(&mut file) {
+        if let Some(img_exif) = crate::embedded_exif::read_exif(path) {
             if let Some(&exif::Value::Rational(ref n)) = img_exif
                 .get_field(exif::Tag::ExposureTime, exif::In::PRIMARY)
                 .map(|n| &n.value)
             {
                 if n[0].num != 0 && n[0].denom != 0 {
-                    exposure_time = Some(n[0]);
+                    exposure_time = Some((n[0].num, n[0].denom));
                 }
             }
             if let Some(&exif::Value::Rational(ref n)) = img_exif
@@ -36,7 +40,7 @@ pub fn load_image(path: &Path) -> Result<SourceImage, image_fmt::ReadError> {
                 .map(|n| &n.value)
             {
                 if n[0].num != 0 && n[0].denom != 0 {
-                    fstop = Some(n[0]);
+                    fstop = Some((n[0].num, n[0].denom));
                 }
             }
             if let Some(Some(n)) = img_exif
@@ -47,19 +51,50 @@ pub fn load_image(path: &Path) -> Result<SourceImage, image_fmt::ReadError> {
                     sensitivity = Some(n);
                 }
             }
+
+            let get_text = |tag| -> Option<String> {
+                if let Some(&exif::Value::Ascii(ref v)) =
+                    img_exif.get_field(tag, exif::In::PRIMARY).map(|n| &n.value)
+                {
+                    v.get(0).map(|s| {
+                        String::from_utf8_lossy(s)
+                            .trim_end_matches('\0')
+                            .trim()
+                            .to_string()
+                    })
+                } else {
+                    None
+                }
+            };
+            if let (Some(make), Some(model)) =
+                (get_text(exif::Tag::Make), get_text(exif::Tag::Model))
+            {
+                camera = Some((make, model));
+            }
         }
 
-        (exposure_time, fstop, sensitivity)
+        // Fill in anything missing from sidecar files.
+        if exposure_time.is_none() || fstop.is_none() || sensitivity.is_none() {
+            if let Some(sidecar) = crate::exposure_sidecar::read_sidecar_metadata(path) {
+                exposure_time = exposure_time.or(sidecar.exposure_time);
+                fstop = fstop.or(sidecar.fstop);
+                sensitivity = sensitivity.or(sidecar.iso);
+                camera = camera.or(sidecar.camera);
+            }
+        }
+
+        (exposure_time, fstop, sensitivity, camera)
     };
 
     // Calculate over-all exposure.
+    let ratio = |(num, denom): (u32, u32)| num as f64 / denom as f64;
     let total_exposure = match (exposure_time, fstop, sensitivity) {
         (Some(exp), Some(fst), Some(sns)) => {
-            Some((sns as f64 * exp.to_f64() / (fst.to_f64() * fst.to_f64())) as f32)
+            Some((sns as f64 * ratio(exp) / (ratio(fst) * ratio(fst))) as f32)
         }
-        (Some(exp), None, Some(sns)) => Some((sns as f64 * exp.to_f64()) as f32),
-        (Some(exp), Some(fst), None) => Some((exp.to_f64() / (fst.to_f64() * fst.to_f64())) as f32),
-        (Some(exp), None, None) => Some(exp.to_f64() as f32),
+        (Some(exp), None, Some(sns)) => Some((sns as f64 * ratio(exp)) as f32),
+        (Some(exp), Some(fst), None) => Some((ratio(exp) / (ratio(fst) * ratio(fst))) as f32),
+        (Some(exp), None, None) => Some(ratio(exp) as f32),
         _ => None,
     };
 
@@ -75,9 +110,10 @@ pub fn load_image(path: &Path) -> Result<SourceImage, image_fmt::ReadError> {
         height: img.height(),
         exposure: total_exposure,
 
-        exposure_time: exposure_time.map(|n| (n.num, n.denom)),
-        fstop: fstop.map(|n| (n.num, n.denom)),
+        exposure_time: exposure_time,
+        fstop: fstop,
         iso: sensitivity,
+        camera: camera,
     };
 
     // Add image to our list of source images.
@@ -87,6 +123,92 @@ pub fn load_image(path: &Path) -> Result<SourceImage, image_fmt::ReadError> {
     })
 }
 
+/// File extensions recognized as video files.
+pub const VIDEO_EXTENSIONS: &[&str] = &[
+    "mov", "MOV", "mp4", "MP4", "mxf", "MXF", "mkv", "MKV", "avi", "AVI",
+];
+
+pub fn is_video_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| VIDEO_EXTENSIONS.contains(&e))
+        .unwrap_or(false)
+}
+
+/// Extracts frames from a video file at the given interval (in seconds)
+/// into `out_dir` as 16-bit PNG files, using the `ffmpeg` executable.
+///
+/// Returns the paths of the extracted frames, in order.
+#[cfg(feature = "ffmpeg")]
+pub fn extract_video_frames(
+    path: &Path,
+    interval: f32,
+    out_dir: &Path,
+) -> std::io::Result<Vec<std::path::PathBuf>> {
+    ensure_dir_exists(out_dir)?;
+
+    let output = std::process::Command::new("ffmpeg")
+        .arg("-nostdin")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(path)
+        .arg("-vf")
+        .arg(format!("fps=1/{}", interval.max(0.001)))
+        .arg("-pix_fmt")
+        .arg("rgb48be")
+        .arg(out_dir.join("frame_%05d.png"))
+        .output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    let mut frames: Vec<_> = std::fs::read_dir(out_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().map(|e| e == "png").unwrap_or(false))
+        .collect();
+    frames.sort();
+
+    Ok(frames)
+}
+
+/// Reads per-frame exposure values from a video's sidecar file, if any.
+///
+/// The sidecar is a text file next to the video with the same name plus
+/// an `.exposures` extension (e.g. `clip.mov.exposures`).  Each
+/// non-empty line that doesn't start with `#` is the relative exposure
+/// of the corresponding extracted frame, either as a decimal number or
+/// as a fraction like `1/60`.
+pub fn read_video_exposure_sidecar(path: &Path) -> Option<Vec<Option<f32>>> {
+    let mut sidecar_path = path.as_os_str().to_os_string();
+    sidecar_path.push(".exposures");
+    let text = std::fs::read_to_string(sidecar_path).ok()?;
+
+    let parse = |s: &str| -> Option<f32> {
+        let n = if let Some((num, denom)) = s.split_once('/') {
+            num.trim().parse::<f32>().ok()? / denom.trim().parse::<f32>().ok()?
+        } else {
+            s.parse::<f32>().ok()?
+        };
+        if n.is_finite() && n > 0.0 {
+            Some(n)
+        } else {
+            None
+        }
+    };
+
+    Some(
+        text.lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(parse)
+            .collect(),
+    )
+}
+
 pub fn make_image_preview(
     img: &SourceImage,
     max_width: Option<usize>,
@@ -142,34 +264,245 @@ pub fn make_image_preview(
     )
 }
 
-pub fn compute_image_histograms(src_img: &SourceImage) -> [Histogram; 3] {
+/// Makes a downscaled copy of an image with floating point RGB values
+/// in [0.0, 1.0], for previewing transforms on.
+///
+/// Unlike `make_image_preview()`, this retains the full precision of
+/// high-bit-depth images.  Downscaling is done with a box filter.
+///
+/// Float images are mapped to [0.0, 1.0] with `float_encoding`, or with
+/// an encoding auto-ranged to the image if it's `None`.
+pub fn make_float_preview(
+    img: &SourceImage,
+    max_width: usize,
+    float_encoding: Option<&FloatEncoding>,
+) -> (Vec<[f32; 3]>, usize, usize) {
+    let encoding = float_encoding
+        .copied()
+        .or_else(|| FloatEncoding::auto(&img.image));
+    downscale_float(&img.image, max_width, |v| match encoding {
+        Some(ref encoding) => encoding.encode(v),
+        None => v,
+    })
+}
+
+/// Like `make_float_preview()`, but float images keep their values
+/// as-is instead of being mapped to [0.0, 1.0].
+pub fn make_linear_float_preview(
+    img: &image_fmt::Image,
+    max_width: usize,
+) -> (Vec<[f32; 3]>, usize, usize) {
+    downscale_float(img, max_width, |v| v)
+}
+
+/// Box-filters an image down to at most `max_width` pixels wide, with
+/// integer formats normalized to [0.0, 1.0] and float values passed
+/// through `map_float`.
+fn downscale_float<F: Fn(f32) -> f32>(
+    img: &image_fmt::Image,
+    max_width: usize,
+    map_float: F,
+) -> (Vec<[f32; 3]>, usize, usize) {
+    let (width, height) = img.dimensions;
+    let factor = ((width + max_width - 1) / max_width).max(1);
+    let (new_width, new_height) = (width / factor, height / factor);
+
+    let (values, norm): (Vec<f32>, f32) = match img.data {
+        ImageBuf::Rgb8(ref buf) => (buf.iter().map(|v| *v as f32).collect(), 1.0 / 255.0),
+        ImageBuf::Rgb16(ref buf) => (buf.iter().map(|v| *v as f32).collect(), 1.0 / 65535.0),
+        ImageBuf::RgbF32(ref buf) => (buf.iter().map(|v| map_float(*v)).collect(), 1.0),
+        _ => panic!(),
+    };
+
+    let block_norm = norm / (factor * factor) as f32;
+    let mut pixels = Vec::with_capacity(new_width * new_height);
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let mut sum = [0.0f32; 3];
+            for by in 0..factor {
+                for bx in 0..factor {
+                    let i = (((y * factor + by) * width) + (x * factor + bx)) * 3;
+                    for chan in 0..3 {
+                        sum[chan] += values[i + chan];
+                    }
+                }
+            }
+            pixels.push([
+                sum[0] * block_norm,
+                sum[1] * block_norm,
+                sum[2] * block_norm,
+            ]);
+        }
+    }
+
+    (pixels, new_width, new_height)
+}
+
+/// Computes the per-channel histograms of an image.
+///
+/// If a mask is given, only the pixels within it are counted.
+///
+/// Float images are bucketed according to `float_encoding`, or with an
+/// encoding auto-ranged to the image if it's `None`.  Images that are
+/// compared with each other (e.g. a bracketed set) need to use the same
+/// encoding.
+pub fn compute_image_histograms(
+    src_img: &SourceImage,
+    mask: Option<&SampleMask>,
+    float_encoding: Option<&FloatEncoding>,
+) -> [Histogram; 3] {
+    compute_image_histograms_with_progress(src_img, mask, float_encoding, |_| true).unwrap()
+}
+
+/// Like `compute_image_histograms()`, but periodically calls `progress`
+/// with the fraction of the image processed so far.
+///
+/// If `progress` returns false, computation is stopped and `None` is
+/// returned.  This is for keeping cancellation responsive on large
+/// images.
+pub fn compute_image_histograms_with_progress<F: FnMut(f32) -> bool>(
+    src_img: &SourceImage,
+    mask: Option<&SampleMask>,
+    float_encoding: Option<&FloatEncoding>,
+    mut progress: F,
+) -> Option<[Histogram; 3]> {
+    let (width, height) = src_img.image.dimensions;
+
+    match src_img.image.data {
+        ImageBuf::Rgb8(ref buf) => accumulate_histograms(
+            buf,
+            1 << 8,
+            |n| n as usize,
+            (width, height),
+            mask,
+            &mut progress,
+        ),
+
+        ImageBuf::Rgb16(ref buf) => accumulate_histograms(
+            buf,
+            1 << 16,
+            |n| n as usize,
+            (width, height),
+            mask,
+            &mut progress,
+        ),
+
+        ImageBuf::RgbF32(ref buf) => {
+            let bucket_count = 1 << 16;
+            let encoding = float_encoding
+                .copied()
+                .or_else(|| FloatEncoding::auto(&src_img.image))
+                .unwrap();
+            accumulate_histograms(
+                buf,
+                bucket_count,
+                |n| (encoding.encode(n) * (bucket_count - 1) as f32).round() as usize,
+                (width, height),
+                mask,
+                &mut progress,
+            )
+        }
+
+        _ => panic!(),
+    }
+}
+
+fn accumulate_histograms<T: Copy, B: Fn(T) -> usize, F: FnMut(f32) -> bool>(
+    buf: &[T],
+    bucket_count: usize,
+    to_bucket: B,
+    (width, height): (usize, usize),
+    mask: Option<&SampleMask>,
+    progress: &mut F,
+) -> Option<[Histogram; 3]> {
+    const ROWS_PER_UPDATE: usize = 64;
+
     let mut histograms = [
         Histogram::default(),
         Histogram::default(),
         Histogram::default(),
     ];
+    for histogram in histograms.iter_mut() {
+        histogram.buckets = vec![0; bucket_count];
+    }
 
-    match src_img.image.data {
-        ImageBuf::Rgb8(ref buf) => {
-            let bucket_count = 1 << 8;
+    for y in 0..height {
+        if y % ROWS_PER_UPDATE == 0 && !progress(y as f32 / height as f32) {
+            return None;
+        }
+        for x in 0..width {
+            if !mask
+                .map(|m| m.includes(x, y, width, height))
+                .unwrap_or(true)
+            {
+                continue;
+            }
+            let i = ((y * width) + x) * 3;
             for chan in 0..3 {
-                histograms[chan] =
-                    Histogram::from_iter(buf.chunks(3).map(|c| c[chan]), bucket_count);
+                histograms[chan].buckets[to_bucket(buf[i + chan])] += 1;
+                histograms[chan].total_samples += 1;
             }
         }
+    }
 
-        ImageBuf::Rgb16(ref buf) => {
-            let bucket_count = 1 << 16;
-            for chan in 0..3 {
-                histograms[chan] =
-                    Histogram::from_iter(buf.chunks(3).map(|c| c[chan]), bucket_count);
-            }
+    Some(histograms)
+}
+
+/// A rough measure of an image's brightness, for ordering images by
+/// exposure when their exposures are unknown.
+///
+/// It's the mean of a sparse sample of the image's pixels, with integer
+/// images normalized to [0.0, 1.0].
+pub fn estimate_brightness(src_img: &SourceImage) -> f32 {
+    fn mean<T: Copy, F: Fn(T) -> f32>(data: &[T], channels: usize, to_f32: F) -> f32 {
+        const SAMPLE_COUNT: usize = 4096;
+        let pixel_count = data.len() / channels;
+        let step = (pixel_count / SAMPLE_COUNT).max(1);
+        let mut sum = 0.0f64;
+        let mut count = 0usize;
+        for p in data.chunks(channels).step_by(step) {
+            sum += p[..3].iter().map(|n| to_f32(*n) as f64).sum::<f64>() / 3.0;
+            count += 1;
         }
+        if count == 0 {
+            0.0
+        } else {
+            (sum / count as f64) as f32
+        }
+    }
 
-        _ => panic!(),
+    match src_img.image.data {
+        ImageBuf::Rgb8(ref d) => mean(d, 3, |n| n as f32 / 255.0),
+        ImageBuf::Rgb16(ref d) => mean(d, 3, |n| n as f32 / 65535.0),
+        ImageBuf::Rgba8(ref d) => mean(d, 4, |n| n as f32 / 255.0),
+        ImageBuf::Rgba16(ref d) => mean(d, 4, |n| n as f32 / 65535.0),
+        ImageBuf::RgbF32(ref d) => mean(d, 3, |n| n),
+        ImageBuf::RgbaF32(ref d) => mean(d, 4, |n| n),
+    }
+}
+
+/// Loads a mask image for restricting analysis to part of an image.
+///
+/// Pixels brighter than mid-gray are included in the mask.  Empty
+/// (zero width or height) images are an error.
+pub fn load_sample_mask(path: &Path) -> Result<SampleMask, image_fmt::ReadError> {
+    let img = image_fmt::load(BufReader::new(File::open(&path)?))?;
+    let (width, height) = img.dimensions;
+    if width == 0 || height == 0 {
+        return Err(image_fmt::ReadError::IO(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "the mask image is empty",
+        )));
     }
+    let included = match img.data.to_rgb().to_8_bit() {
+        ImageBuf::Rgb8(buf) => buf
+            .chunks(3)
+            .map(|c| (c[0] as u32 + c[1] as u32 + c[2] as u32) > (128 * 3))
+            .collect(),
+        _ => unreachable!(),
+    };
 
-    histograms
+    Ok(SampleMask::Image(included, width, height))
 }
 
 pub fn load_1d_lut<P: AsRef<Path>>(path: P) -> Result<Lut1D, formats::ReadError> {
@@ -199,6 +532,24 @@ pub fn load_1d_lut<P: AsRef<Path>>(path: P) -> Result<Lut1D, formats::ReadError>
     }
 }
 
+/// Loads a 3D LUT, along with its 1D shaper LUT if the file has one.
+///
+/// Currently only .cube files are supported.  Resolve-style .cube files
+/// can contain a shaper, and Iridas-style files never do.
+pub fn load_3d_lut<P: AsRef<Path>>(path: P) -> Result<(Lut3D, Option<Lut1D>), formats::ReadError> {
+    let path: &Path = path.as_ref();
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+
+    match path.extension().map(|e| e.to_str()) {
+        Some(Some("cube")) => match formats::cube_resolve::read(&mut file)? {
+            (shaper, Some(lut)) => Ok((lut, shaper)),
+            (_, None) => Err(formats::ReadError::FormatErr),
+        },
+
+        _ => Err(formats::ReadError::FormatErr),
+    }
+}
+
 /// Ensures that a directory path exists and that we have permission to
 /// write to it.  If it doesn't exists, this will attempt to create it.
 ///
@@ -0,0 +1,117 @@
+//! Lens projections, which map between a ray's angle from the optical
+//! axis and its distance from the center of the image.
+//!
+//! Rectilinear lenses keep straight lines straight, but fisheye lenses
+//! use one of several other mappings to fit a much wider view.  Anything
+//! that works with distances from the image center in terms of the
+//! scene (vignetting, stitching) needs to know which one a lens uses.
+
+use std::f64::consts::PI;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LensProjection {
+    Rectilinear,
+    FisheyeEquidistant,
+    FisheyeEquisolid,
+    FisheyeStereographic,
+    FisheyeOrthographic,
+}
+
+pub const LENS_PROJECTIONS: &[LensProjection] = &[
+    LensProjection::Rectilinear,
+    LensProjection::FisheyeEquidistant,
+    LensProjection::FisheyeEquisolid,
+    LensProjection::FisheyeStereographic,
+    LensProjection::FisheyeOrthographic,
+];
+
+impl LensProjection {
+    pub fn is_fisheye(&self) -> bool {
+        *self != LensProjection::Rectilinear
+    }
+
+    /// The distance from the image center of a ray at `angle` radians
+    /// from the optical axis, in units of the focal length.
+    pub fn radius(&self, angle: f64) -> f64 {
+        match *self {
+            LensProjection::Rectilinear => angle.tan(),
+            LensProjection::FisheyeEquidistant => angle,
+            LensProjection::FisheyeEquisolid => 2.0 * (angle * 0.5).sin(),
+            LensProjection::FisheyeStereographic => 2.0 * (angle * 0.5).tan(),
+            LensProjection::FisheyeOrthographic => angle.sin(),
+        }
+    }
+
+    /// The inverse of `radius()`: the angle from the optical axis, in
+    /// radians, of the ray at `radius` focal lengths from the image
+    /// center.
+    ///
+    /// Returns `None` for radii that the projection can't reach.
+    pub fn angle(&self, radius: f64) -> Option<f64> {
+        if radius < 0.0 {
+            return None;
+        }
+        match *self {
+            LensProjection::Rectilinear => Some(radius.atan()),
+            LensProjection::FisheyeEquidistant => Some(radius),
+            LensProjection::FisheyeEquisolid => {
+                if radius <= 2.0 {
+                    Some(2.0 * (radius * 0.5).asin())
+                } else {
+                    None
+                }
+            }
+            LensProjection::FisheyeStereographic => Some(2.0 * (radius * 0.5).atan()),
+            LensProjection::FisheyeOrthographic => {
+                if radius <= 1.0 {
+                    Some(radius.asin())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// The largest angle from the optical axis, in radians, that the
+    /// projection can represent.
+    pub fn max_angle(&self) -> f64 {
+        match *self {
+            LensProjection::Rectilinear => PI * 0.5,
+            LensProjection::FisheyeEquidistant => PI,
+            LensProjection::FisheyeEquisolid => PI,
+            LensProjection::FisheyeStereographic => PI,
+            LensProjection::FisheyeOrthographic => PI * 0.5,
+        }
+    }
+
+    pub fn ui_text(&self) -> &'static str {
+        match *self {
+            LensProjection::Rectilinear => "Rectilinear",
+            LensProjection::FisheyeEquidistant => "Fisheye (equidistant)",
+            LensProjection::FisheyeEquisolid => "Fisheye (equisolid)",
+            LensProjection::FisheyeStereographic => "Fisheye (stereographic)",
+            LensProjection::FisheyeOrthographic => "Fisheye (orthographic)",
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LensProjection::Rectilinear => "rectilinear",
+            LensProjection::FisheyeEquidistant => "fisheye_equidistant",
+            LensProjection::FisheyeEquisolid => "fisheye_equisolid",
+            LensProjection::FisheyeStereographic => "fisheye_stereographic",
+            LensProjection::FisheyeOrthographic => "fisheye_orthographic",
+        }
+    }
+
+    pub fn from_str(text: &str) -> Option<LensProjection> {
+        match text {
+            "rectilinear" => Some(LensProjection::Rectilinear),
+            "fisheye_equidistant" => Some(LensProjection::FisheyeEquidistant),
+            "fisheye_equisolid" => Some(LensProjection::FisheyeEquisolid),
+            "fisheye_stereographic" => Some(LensProjection::FisheyeStereographic),
+            "fisheye_orthographic" => Some(LensProjection::FisheyeOrthographic),
+            _ => None,
+        }
+    }
+}
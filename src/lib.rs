@@ -1,4 +1,19 @@
+pub mod bracket_analysis;
+pub mod camera_space;
+pub mod chroma_space;
+pub mod display_profile;
+pub mod embedded_exif;
+pub mod exposure_sidecar;
+pub mod formula;
 pub mod job_helpers;
+pub mod lens_projection;
+pub mod lut_formats;
+pub mod lut_modify;
+pub mod preferences;
+pub mod transfer_function;
+pub mod undo;
+pub mod user_dirs;
+pub mod white_balance;
 
 pub use image_fmt::ImageBuf;
 
@@ -20,6 +35,140 @@ pub struct ImageInfo {
     pub exposure_time: Option<(u32, u32)>, // Ratio.
     pub fstop: Option<(u32, u32)>,         // Ratio.
     pub iso: Option<u32>,
+    pub camera: Option<(String, String)>, // (make, model)
+}
+
+/// A region of an image to restrict analysis to.
+#[derive(Debug, Clone)]
+pub enum SampleMask {
+    /// A rectangle in normalized [0.0, 1.0] image coordinates, as
+    /// `(min_x, min_y, max_x, max_y)`.
+    Rect(f32, f32, f32, f32),
+
+    /// A mask image as `(included, width, height)`, where only pixels
+    /// marked as included are sampled.  It's stretched to fit the
+    /// dimensions of the image being analyzed.
+    Image(Vec<bool>, usize, usize),
+}
+
+impl SampleMask {
+    /// Returns whether the pixel at `(x, y)` of an image with the given
+    /// dimensions should be sampled.
+    pub fn includes(&self, x: usize, y: usize, width: usize, height: usize) -> bool {
+        match *self {
+            SampleMask::Rect(min_x, min_y, max_x, max_y) => {
+                let nx = (x as f32 + 0.5) / width as f32;
+                let ny = (y as f32 + 0.5) / height as f32;
+                nx >= min_x && nx <= max_x && ny >= min_y && ny <= max_y
+            }
+            SampleMask::Image(_, 0, _) | SampleMask::Image(_, _, 0) => false,
+            SampleMask::Image(ref included, mask_width, mask_height) => {
+                let mx = (x * mask_width / width).min(mask_width - 1);
+                let my = (y * mask_height / height).min(mask_height - 1);
+                included[my * mask_width + mx]
+            }
+        }
+    }
+}
+
+/// How the values of floating point images are mapped into [0.0, 1.0]
+/// for histograms and previews.
+///
+/// Unlike integer images, float images are unbounded, so the range is
+/// determined from the image data.  Images with a wide dynamic range
+/// are mapped in the log2 domain, which also makes the estimated curve
+/// usable as a shaper for them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FloatEncoding {
+    pub log: bool,
+    pub min: f32, // In stops (log2) if `log` is true.
+    pub max: f32,
+}
+
+impl FloatEncoding {
+    /// Stops of headroom added on either side of an auto-ranged log
+    /// encoding, so that other exposures of the same scene fit as well.
+    const LOG_HEADROOM: f32 = 8.0;
+
+    /// Images with a dynamic range of more than this many stops are
+    /// encoded in the log domain.
+    const LOG_THRESHOLD: f32 = 12.0;
+
+    /// Determines an encoding that covers the values of the given image.
+    ///
+    /// Returns `None` if the image isn't a float image.
+    pub fn auto(image: &image_fmt::Image) -> Option<FloatEncoding> {
+        let data = match image.data {
+            ImageBuf::RgbF32(ref data) | ImageBuf::RgbaF32(ref data) => data,
+            _ => return None,
+        };
+
+        let mut max = 0.0f32;
+        let mut min_positive = std::f32::INFINITY;
+        for &n in data.iter().filter(|n| n.is_finite()) {
+            max = max.max(n);
+            if n > 0.0 {
+                min_positive = min_positive.min(n);
+            }
+        }
+        if max <= 0.0 {
+            return Some(FloatEncoding {
+                log: false,
+                min: 0.0,
+                max: 1.0,
+            });
+        }
+
+        let min_positive = min_positive.max(max * (2.0f32).powf(-24.0));
+        if max > 1.0 || (max / min_positive).log2() > Self::LOG_THRESHOLD {
+            Some(FloatEncoding {
+                log: true,
+                min: min_positive.log2().floor() - Self::LOG_HEADROOM,
+                max: max.log2().ceil() + Self::LOG_HEADROOM,
+            })
+        } else {
+            Some(FloatEncoding {
+                log: false,
+                min: 0.0,
+                max: 1.0,
+            })
+        }
+    }
+
+    /// Maps a float image value to [0.0, 1.0], clamping values outside
+    /// the encoding's range.
+    pub fn encode(&self, n: f32) -> f32 {
+        let x = if self.log {
+            if n <= 0.0 {
+                return 0.0;
+            }
+            n.log2()
+        } else {
+            n
+        };
+        ((x - self.min) / (self.max - self.min)).max(0.0).min(1.0)
+    }
+
+    /// The inverse of `encode()`.
+    pub fn decode(&self, n: f32) -> f32 {
+        let x = self.min + (n * (self.max - self.min));
+        if self.log {
+            (2.0f32).powf(x)
+        } else {
+            x
+        }
+    }
+
+    pub fn ui_text(&self) -> String {
+        if self.log {
+            format!(
+                "log2, {:+.0} to {:+.0} stops (relative to 1.0)",
+                self.min, self.max
+            )
+        } else {
+            format!("linear, {} to {}", self.min, self.max)
+        }
+    }
 }
 
 pub mod colors {
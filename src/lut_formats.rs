@@ -0,0 +1,883 @@
+//! Writers for LUT formats that colorbox doesn't provide.
+//!
+//! The 1D writers take per-channel tables that evenly sample the given
+//! input range, and the 3D writers take a function to sample.  All of
+//! them take a `LutMetadata`, which is recorded in whatever way the
+//! format supports.  Cameras are picky about what they'll load, so the
+//! camera formats only record the LUT's name.
+//!
+//! Applications clamp input outside of a LUT's range in every format
+//! here except CLF, which can describe what the LUT does there.  To get
+//! something other than clamping from the other 1D formats, write tables
+//! that are already extended (see `ExtrapolatedLut::extended()`).  The 3D
+//! formats and ICC profiles are only defined over their range.
+
+use std::io::{Result, Write};
+
+use colorbox::{
+    chroma::Chromaticities,
+    matrix::{self, AdaptationMethod, Matrix},
+};
+use ocio_gen::lut_extrapolation::{ExtrapolatedLut, Extrapolation};
+use sensor_analysis::utils::lerp_slice;
+
+/// The ICC profile connection space white point, as chromaticities
+/// and as the XYZ value that profiles store.
+const ICC_D50: (f64, f64) = (0.34567, 0.35850);
+const ICC_D50_XYZ: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// The largest finite half float value.
+const HALF_MAX: f32 = 65504.0;
+
+/// Descriptive information about a LUT, for recording in its file.
+///
+/// Formats with a name or title field store `name` there, and the rest
+/// as comment lines (see `lines()`).  Formats without a name field get
+/// the name as the first comment line.
+#[derive(Debug, Clone, Default)]
+pub struct LutMetadata {
+    /// The LUT's name, as shown by applications that show one.
+    pub name: String,
+    /// The software that generated the LUT, e.g. "ETF LUT Maker v0.4.0".
+    pub software: String,
+    /// The camera that the LUT is for, if any.
+    pub camera: Option<String>,
+    /// Any further notes, one per line.
+    pub notes: Vec<String>,
+}
+
+impl LutMetadata {
+    /// Everything except the name, as text lines.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.software.is_empty() {
+            lines.push(format!("Generated by {}", self.software));
+        }
+        if let Some(camera) = &self.camera {
+            lines.push(format!("Camera: {}", camera));
+        }
+        lines.extend(self.notes.iter().cloned());
+        lines
+    }
+
+    /// Everything including the name, as text lines.
+    fn lines_with_name(&self) -> Vec<String> {
+        let mut lines = self.lines();
+        if !self.name.is_empty() {
+            lines.insert(0, format!("Name: {}", self.name));
+        }
+        lines
+    }
+}
+
+/// Writes an Academy/ASC Common LUT Format (CLF) file from a LUT with
+/// a single range.
+///
+/// If the input range isn't [0.0, 1.0], a `Range` node is added ahead of
+/// the LUT to map it into that range.  Optional 3x3 matrices can be
+/// applied before and/or after the LUT.
+///
+/// CLF has no setting for out-of-range input, so with an extrapolation
+/// other than clamping the LUT is instead written as a `halfDomain` table,
+/// which has an entry for every half float value and so covers the whole
+/// range that the extrapolation applies to.
+pub fn write_clf_1d<W: Write>(
+    out: &mut W,
+    lut: &ExtrapolatedLut,
+    pre_matrix: Option<[[f64; 3]; 3]>,
+    post_matrix: Option<[[f64; 3]; 3]>,
+    metadata: &LutMetadata,
+) -> Result<()> {
+    assert!(lut.lut.ranges.len() == 1 && lut.lut.tables.len() == 3);
+    let range = lut.lut.ranges[0];
+    let tables = [
+        &lut.lut.tables[0][..],
+        &lut.lut.tables[1][..],
+        &lut.lut.tables[2][..],
+    ];
+    let len = tables[0].len();
+    assert!(tables.iter().all(|t| t.len() == len));
+    let half_domain = lut.extrapolation != Extrapolation::Clamp;
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<ProcessList compCLFversion="3" id="{}" name="{}">"#,
+        xml_escape(&metadata.name),
+        xml_escape(&metadata.name)
+    )?;
+    for line in metadata.lines().iter() {
+        writeln!(out, "    <Description>{}</Description>", xml_escape(line))?;
+    }
+
+    if let Some(matrix) = pre_matrix {
+        // Half-domain tables take half float input.
+        write_clf_matrix(out, &matrix, if half_domain { "16f" } else { "32f" })?;
+    }
+
+    if half_domain {
+        writeln!(
+            out,
+            r#"    <LUT1D inBitDepth="16f" outBitDepth="32f" halfDomain="true">"#
+        )?;
+        writeln!(out, r#"        <Array dim="65536 3">"#)?;
+        for bits in 0..=u16::MAX {
+            // Infinities get the value at the largest finite input, and
+            // NaNs zero.
+            let x = half_to_f32(bits);
+            let out_rgb = if x.is_nan() {
+                [0.0; 3]
+            } else {
+                let x = x.max(-HALF_MAX).min(HALF_MAX);
+                [lut.look_up(0, x), lut.look_up(1, x), lut.look_up(2, x)]
+            };
+            writeln!(
+                out,
+                "            {} {} {}",
+                out_rgb[0], out_rgb[1], out_rgb[2]
+            )?;
+        }
+        writeln!(out, "        </Array>")?;
+        writeln!(out, "    </LUT1D>")?;
+    } else {
+        write_clf_table(out, range, tables)?;
+    }
+
+    if let Some(matrix) = post_matrix {
+        write_clf_matrix(out, &matrix, "32f")?;
+    }
+
+    writeln!(out, "</ProcessList>")?;
+
+    Ok(())
+}
+
+/// Writes the `Range` (if needed) and `LUT1D` nodes of a LUT that
+/// clamps at its range.
+fn write_clf_table<W: Write>(out: &mut W, range: (f32, f32), tables: [&[f32]; 3]) -> Result<()> {
+    let len = tables[0].len();
+    if range != (0.0, 1.0) {
+        writeln!(out, r#"    <Range inBitDepth="32f" outBitDepth="32f">"#)?;
+        writeln!(out, "        <minInValue>{}</minInValue>", range.0)?;
+        writeln!(out, "        <maxInValue>{}</maxInValue>", range.1)?;
+        writeln!(out, "        <minOutValue>0.0</minOutValue>")?;
+        writeln!(out, "        <maxOutValue>1.0</maxOutValue>")?;
+        writeln!(out, "    </Range>")?;
+    }
+
+    writeln!(out, r#"    <LUT1D inBitDepth="32f" outBitDepth="32f">"#)?;
+    writeln!(out, r#"        <Array dim="{} 3">"#, len)?;
+    for i in 0..len {
+        writeln!(
+            out,
+            "            {} {} {}",
+            tables[0][i], tables[1][i], tables[2][i]
+        )?;
+    }
+    writeln!(out, "        </Array>")?;
+    writeln!(out, "    </LUT1D>")?;
+
+    Ok(())
+}
+
+fn write_clf_matrix<W: Write>(
+    out: &mut W,
+    matrix: &[[f64; 3]; 3],
+    out_bit_depth: &str,
+) -> Result<()> {
+    writeln!(
+        out,
+        r#"    <Matrix inBitDepth="32f" outBitDepth="{}">"#,
+        out_bit_depth
+    )?;
+    writeln!(out, r#"        <Array dim="3 3">"#)?;
+    for row in matrix.iter() {
+        writeln!(out, "            {} {} {}", row[0], row[1], row[2])?;
+    }
+    writeln!(out, "        </Array>")?;
+    writeln!(out, "    </Matrix>")?;
+    Ok(())
+}
+
+/// Writes an Iridas/Resolve `.cube` 3D LUT file, sampling `f` over
+/// the given input range on all axes.
+pub fn write_cube_3d<W: Write, F: Fn([f32; 3]) -> [f32; 3]>(
+    out: &mut W,
+    range: (f32, f32),
+    cube_size: usize,
+    f: F,
+    metadata: &LutMetadata,
+) -> Result<()> {
+    assert!(cube_size >= 2);
+
+    write_cube_header(out, metadata)?;
+    writeln!(out, "LUT_3D_SIZE {}", cube_size)?;
+    writeln!(out, "DOMAIN_MIN {0} {0} {0}", range.0)?;
+    writeln!(out, "DOMAIN_MAX {0} {0} {0}", range.1)?;
+
+    // Red changes fastest.
+    let x = |i: usize| range.0 + ((range.1 - range.0) * i as f32 / (cube_size - 1) as f32);
+    for b in 0..cube_size {
+        for g in 0..cube_size {
+            for r in 0..cube_size {
+                let out_rgb = f([x(r), x(g), x(b)]);
+                writeln!(out, "{} {} {}", out_rgb[0], out_rgb[1], out_rgb[2])?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the metadata comment lines that start a `.cube` file.
+///
+/// This is also for the 1D `.cube` files that colorbox writes, which
+/// don't otherwise get any metadata.
+pub fn write_cube_header<W: Write>(out: &mut W, metadata: &LutMetadata) -> Result<()> {
+    for line in metadata.lines_with_name().iter() {
+        writeln!(out, "# {}", line)?;
+    }
+    Ok(())
+}
+
+/// Writes a Cinespace (CSP) 1D LUT file.
+///
+/// The input range is handled by the format's per-channel pre-LUT.  If
+/// `shaper_points` is greater than two, the pre-LUT is additionally
+/// spaced logarithmically over the range with that many points, which
+/// gives better precision in the shadows for LUTs with a wide input
+/// range (e.g. from-linear LUTs).
+pub fn write_csp_1d<W: Write>(
+    out: &mut W,
+    range: (f32, f32),
+    tables: [&[f32]; 3],
+    shaper_points: usize,
+    metadata: &LutMetadata,
+) -> Result<()> {
+    let len = tables[0].len();
+    assert!(tables.iter().all(|t| t.len() == len));
+
+    writeln!(out, "CSPLUTV100")?;
+    writeln!(out, "1D")?;
+    writeln!(out)?;
+    let lines = metadata.lines_with_name();
+    if !lines.is_empty() {
+        writeln!(out, "BEGIN METADATA")?;
+        for line in lines.iter() {
+            writeln!(out, "{}", line)?;
+        }
+        writeln!(out, "END METADATA")?;
+        writeln!(out)?;
+    }
+
+    // Pre-LUT, mapping the input range to [0.0, 1.0].
+    let shaper = shaper_samples(range, shaper_points);
+    for _ in 0..3 {
+        writeln!(out, "{}", shaper.len())?;
+        let inputs: Vec<String> = shaper.iter().map(|(x, _)| format!("{}", x)).collect();
+        let outputs: Vec<String> = shaper.iter().map(|(_, y)| format!("{}", y)).collect();
+        writeln!(out, "{}", inputs.join(" "))?;
+        writeln!(out, "{}", outputs.join(" "))?;
+    }
+    writeln!(out)?;
+
+    // The LUT itself, indexed by the pre-LUT output.
+    let table_len = if shaper.len() > 2 { len.max(1024) } else { len };
+    writeln!(out, "{}", table_len)?;
+    for i in 0..table_len {
+        let t = i as f32 / (table_len - 1) as f32;
+        let x = shaper_to_input(range, shaper.len(), t);
+        let n = (x - range.0) / (range.1 - range.0);
+        writeln!(
+            out,
+            "{} {} {}",
+            lerp_slice(tables[0], n),
+            lerp_slice(tables[1], n),
+            lerp_slice(tables[2], n)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes a version 4 ICC matrix/TRC display profile, for applications
+/// that color manage with ICC profiles rather than LUTs.
+///
+/// The tables are the to-linear direction, sampled over [0.0, 1.0] as
+/// the profile's tone curves.  (ICC profiles are used in both
+/// directions, so there's no from-linear form.)  The linear values are
+/// taken to have the primaries and white point of `chroma`, after
+/// applying `gamut_matrix` if given.
+///
+/// Tone curves can't go below zero or above one, so negative values are
+/// clipped and values above one are scaled down, with the scale moved
+/// into the colorants.  The name is stored as the profile description,
+/// and the rest of the metadata in the copyright tag, which is the only
+/// free-form text that applications commonly show.
+pub fn write_icc<W: Write>(
+    out: &mut W,
+    range: (f32, f32),
+    tables: [&[f32]; 3],
+    chroma: Chromaticities,
+    gamut_matrix: Option<Matrix>,
+    metadata: &LutMetadata,
+) -> Result<()> {
+    let len = tables[0].len();
+    assert!(tables.iter().all(|t| t.len() == len));
+
+    // Resample the tables over [0.0, 1.0], and find the scale that
+    // brings them into the range of a tone curve.
+    let curves: Vec<Vec<f32>> = tables
+        .iter()
+        .map(|table| {
+            (0..len)
+                .map(|i| {
+                    let x = i as f32 / (len - 1) as f32;
+                    let n = (x - range.0) / (range.1 - range.0);
+                    lerp_slice(table, n.max(0.0).min(1.0)).max(0.0)
+                })
+                .collect()
+        })
+        .collect();
+    let max = curves.iter().flatten().fold(0.0f32, |a, b| a.max(*b));
+    let scale = if max > 1.0 { max } else { 1.0 };
+
+    // ICC profiles are relative to a D50 white point.
+    let adaptation =
+        matrix::xyz_chromatic_adaptation_matrix(chroma.w, ICC_D50, AdaptationMethod::Bradford);
+    let mut to_xyz = matrix::compose(&[
+        gamut_matrix.unwrap_or([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+        matrix::rgb_to_xyz_matrix(chroma),
+        adaptation,
+    ]);
+    for row in to_xyz.iter_mut() {
+        for n in row.iter_mut() {
+            *n *= scale as f64;
+        }
+    }
+
+    // Tag data, in the order written.
+    let mut tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", icc_mluc(&metadata.name)),
+        (b"cprt", icc_mluc(&metadata.lines().join("\n"))),
+        (b"wtpt", icc_xyz(ICC_D50_XYZ)),
+        (b"chad", {
+            let mut data = b"sf32\0\0\0\0".to_vec();
+            for n in adaptation.iter().flatten() {
+                data.extend_from_slice(&icc_s15_fixed16(*n));
+            }
+            data
+        }),
+    ];
+    for (chan, signature) in [b"rXYZ", b"gXYZ", b"bXYZ"].iter().enumerate() {
+        tags.push((
+            *signature,
+            icc_xyz([to_xyz[0][chan], to_xyz[1][chan], to_xyz[2][chan]]),
+        ));
+    }
+    for (curve, signature) in curves.iter().zip([b"rTRC", b"gTRC", b"bTRC"].iter()) {
+        let mut data = b"curv\0\0\0\0".to_vec();
+        data.extend_from_slice(&(curve.len() as u32).to_be_bytes());
+        for n in curve.iter() {
+            let n = (n / scale).max(0.0).min(1.0);
+            data.extend_from_slice(&((n * 65535.0).round() as u16).to_be_bytes());
+        }
+        tags.push((*signature, data));
+    }
+    for (_, data) in tags.iter_mut() {
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+    }
+
+    // Header.
+    let size = 128 + 4 + (tags.len() * 12) + tags.iter().map(|t| t.1.len()).sum::<usize>();
+    let mut header = Vec::with_capacity(128);
+    header.extend_from_slice(&(size as u32).to_be_bytes());
+    header.extend_from_slice(&[0; 4]); // Preferred CMM.
+    header.extend_from_slice(&[4, 0x30, 0, 0]); // Version 4.3.
+    header.extend_from_slice(b"mntr");
+    header.extend_from_slice(b"RGB ");
+    header.extend_from_slice(b"XYZ ");
+    for n in icc_date_time(std::time::SystemTime::now()).iter() {
+        header.extend_from_slice(&n.to_be_bytes());
+    }
+    header.extend_from_slice(b"acsp");
+    header.extend_from_slice(&[0; 4 * 4 + 8]); // Platform, flags, device, and attributes.
+    header.extend_from_slice(&[0; 4]); // Perceptual intent.
+    for n in ICC_D50_XYZ.iter() {
+        header.extend_from_slice(&icc_s15_fixed16(*n));
+    }
+    header.extend_from_slice(&[0; 4 + 16 + 28]); // Creator, profile ID, and reserved.
+    debug_assert_eq!(header.len(), 128);
+    out.write_all(&header)?;
+
+    // Tag table and tag data.
+    out.write_all(&(tags.len() as u32).to_be_bytes())?;
+    let mut offset = 128 + 4 + (tags.len() * 12);
+    for (signature, data) in tags.iter() {
+        out.write_all(&signature[..])?;
+        out.write_all(&(offset as u32).to_be_bytes())?;
+        out.write_all(&(data.len() as u32).to_be_bytes())?;
+        offset += data.len();
+    }
+    for (_, data) in tags.iter() {
+        out.write_all(data)?;
+    }
+
+    Ok(())
+}
+
+/// Writes an Autodesk Lustre/Flame `.3dl` 3D LUT file from 1D tables.
+///
+/// Since the format is 3D-only, the 1D tables are baked into a cube
+/// of the given size.  See `write_3dl()`.
+pub fn write_3dl_from_1d<W: Write>(
+    out: &mut W,
+    range: (f32, f32),
+    tables: [&[f32]; 3],
+    cube_size: usize,
+    metadata: &LutMetadata,
+) -> Result<()> {
+    write_3dl(
+        out,
+        cube_size,
+        |rgb| {
+            let mut out = [0.0f32; 3];
+            for chan in 0..3 {
+                let n = (rgb[chan] - range.0) / (range.1 - range.0);
+                out[chan] = lerp_slice(tables[chan], n.max(0.0).min(1.0));
+            }
+            out
+        },
+        metadata,
+    )
+}
+
+/// Writes an Autodesk Lustre/Flame `.3dl` 3D LUT file, with 10-bit
+/// input and 12-bit output, sampling `f` over [0.0, 1.0] on all axes.
+///
+/// The format has no way to specify an input range, and its output is
+/// integer, so outputs beyond [0.0, 1.0] are clipped.
+pub fn write_3dl<W: Write, F: Fn([f32; 3]) -> [f32; 3]>(
+    out: &mut W,
+    cube_size: usize,
+    f: F,
+    metadata: &LutMetadata,
+) -> Result<()> {
+    assert!(cube_size >= 2);
+
+    for line in metadata.lines_with_name().iter() {
+        writeln!(out, "# {}", line)?;
+    }
+
+    // Input mesh.
+    let mesh: Vec<String> = (0..cube_size)
+        .map(|i| format!("{}", (i * 1023 + (cube_size - 1) / 2) / (cube_size - 1)))
+        .collect();
+    writeln!(out, "{}", mesh.join(" "))?;
+
+    // Output values, with blue changing fastest.
+    let x = |i: usize| i as f32 / (cube_size - 1) as f32;
+    let to_int = |n: f32| (n.max(0.0).min(1.0) * 4095.0).round() as u32;
+    for r in 0..cube_size {
+        for g in 0..cube_size {
+            for b in 0..cube_size {
+                let rgb = f([x(r), x(g), x(b)]);
+                writeln!(
+                    out,
+                    "{} {} {}",
+                    to_int(rgb[0]),
+                    to_int(rgb[1]),
+                    to_int(rgb[2])
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a Panasonic VariCam Look Table (`.vlt`) file, as loaded by
+/// Panasonic cameras, sampling `f` over [0.0, 1.0] on all axes.
+///
+/// The format is a 17-point 3D LUT with 10-bit integer output, so
+/// outputs beyond [0.0, 1.0] are clipped.  The metadata's name is
+/// stored in the file's header.
+pub fn write_vlt<W: Write, F: Fn([f32; 3]) -> [f32; 3]>(
+    out: &mut W,
+    f: F,
+    metadata: &LutMetadata,
+) -> Result<()> {
+    const VLT_SIZE: usize = 17;
+
+    writeln!(out, "# panasonic vlt file version 1.0")?;
+    writeln!(
+        out,
+        "# source vlt file \"{}\"",
+        metadata.name.replace('"', "")
+    )?;
+    writeln!(out, "LUT_3D_SIZE {}", VLT_SIZE)?;
+    writeln!(out)?;
+
+    // Red changes fastest.
+    let x = |i: usize| i as f32 / (VLT_SIZE - 1) as f32;
+    let to_int = |n: f32| (n.max(0.0).min(1.0) * 1023.0).round() as u32;
+    for b in 0..VLT_SIZE {
+        for g in 0..VLT_SIZE {
+            for r in 0..VLT_SIZE {
+                let rgb = f([x(r), x(g), x(b)]);
+                writeln!(
+                    out,
+                    "{} {} {}",
+                    to_int(rgb[0]),
+                    to_int(rgb[1]),
+                    to_int(rgb[2])
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a `.cube` 3D LUT file in the restricted form that Sony
+/// cameras load as user (monitoring) LUTs, sampling `f` over
+/// [0.0, 1.0] on all axes.
+///
+/// The cameras only accept 17 or 33-point cubes over the default
+/// domain, without comments, and with outputs in [0.0, 1.0], so outputs
+/// beyond that are clipped.  The metadata's name is stored in the
+/// file's `TITLE` line, which the cameras show as the LUT's name.
+pub fn write_sony_cube<W: Write, F: Fn([f32; 3]) -> [f32; 3]>(
+    out: &mut W,
+    cube_size: usize,
+    f: F,
+    metadata: &LutMetadata,
+) -> Result<()> {
+    assert!(cube_size == 17 || cube_size == 33);
+
+    writeln!(out, "TITLE \"{}\"", metadata.name.replace('"', ""))?;
+    writeln!(out, "LUT_3D_SIZE {}", cube_size)?;
+
+    // Red changes fastest.
+    let x = |i: usize| i as f32 / (cube_size - 1) as f32;
+    let clip = |n: f32| n.max(0.0).min(1.0);
+    for b in 0..cube_size {
+        for g in 0..cube_size {
+            for r in 0..cube_size {
+                let rgb = f([x(r), x(g), x(b)]);
+                writeln!(
+                    out,
+                    "{:.6} {:.6} {:.6}",
+                    clip(rgb[0]),
+                    clip(rgb[1]),
+                    clip(rgb[2])
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+//-------------------------------------------------------------
+
+/// The precision that LUT output values are quantized to when writing,
+/// for devices that load LUTs at a fixed precision (e.g. the LUT slots
+/// of cameras and monitors).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Quantization {
+    /// Full 32-bit float precision.
+    None,
+    /// 16-bit half float precision.
+    Half,
+    /// Integer code values with the given number of bits, covering
+    /// [0.0, 1.0].  Values outside of that range are clipped.
+    Integer(u32),
+}
+
+impl Quantization {
+    pub fn ui_text(&self) -> String {
+        match *self {
+            Quantization::None => "None".into(),
+            Quantization::Half => "Half float".into(),
+            Quantization::Integer(bits) => format!("{}-bit integer", bits),
+        }
+    }
+
+    /// Rounds a value to the nearest representable value.
+    pub fn quantize(&self, n: f32) -> f32 {
+        match *self {
+            Quantization::None => n,
+            Quantization::Half => round_to_half(n),
+            Quantization::Integer(bits) => {
+                let max = ((1u64 << bits.max(1).min(32)) - 1) as f64;
+                ((n.max(0.0).min(1.0) as f64 * max).round() / max) as f32
+            }
+        }
+    }
+}
+
+/// Quantizes the values of a 1D table in place.
+///
+/// When `dither` is true, the rounding error of each entry is carried
+/// over to the next, so that the table's average slope is preserved
+/// instead of running in flat steps.  Either way, the table stays
+/// monotonic wherever it was.
+pub fn quantize_table(table: &mut [f32], quantization: Quantization, dither: bool) {
+    if quantization == Quantization::None {
+        return;
+    }
+
+    let mut error = 0.0;
+    let mut prev: Option<(f32, f32)> = None; // (original, quantized)
+    for n in table.iter_mut() {
+        let target = *n + error;
+        let mut q = quantization.quantize(target);
+        if let Some((prev_n, prev_q)) = prev {
+            if *n >= prev_n {
+                q = q.max(prev_q);
+            } else {
+                q = q.min(prev_q);
+            }
+        }
+        if dither {
+            error = target - q;
+        }
+        prev = Some((*n, q));
+        *n = q;
+    }
+}
+
+/// Rounds a value to the nearest half float value, clipping to the
+/// largest finite one.
+fn round_to_half(n: f32) -> f32 {
+    if !n.is_finite() {
+        return n;
+    }
+    if n.abs() >= HALF_MAX {
+        return HALF_MAX.copysign(n);
+    }
+
+    // Half floats have 10 mantissa bits, and are subnormal below 2^-14.
+    let exponent = (((n.to_bits() >> 23) & 0xff) as i32 - 127).max(-14);
+    let ulp = (2.0f32).powi(exponent - 10);
+    (n / ulp).round() * ulp
+}
+
+/// The value of a half float, from its bits.
+fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * (2.0f32).powi(-24),
+        0x1f if mantissa == 0.0 => sign * std::f32::INFINITY,
+        0x1f => std::f32::NAN,
+        _ => sign * (1.0 + (mantissa / 1024.0)) * (2.0f32).powi(exponent - 15),
+    }
+}
+
+//-------------------------------------------------------------
+
+/// `(input, output)` pairs of a pre-LUT mapping `range` to [0.0, 1.0].
+fn shaper_samples(range: (f32, f32), points: usize) -> Vec<(f32, f32)> {
+    if points <= 2 {
+        return vec![(range.0, 0.0), (range.1, 1.0)];
+    }
+
+    (0..points)
+        .map(|i| {
+            let t = i as f32 / (points - 1) as f32;
+            (shaper_to_input(range, points, t), t)
+        })
+        .collect()
+}
+
+/// Maps a shaper output value in [0.0, 1.0] back to the input range.
+///
+/// With more than two shaper points, the spacing is logarithmic, with
+/// the input spanning `LOG_SHAPER_STOPS` stops below the top of the
+/// range.
+fn shaper_to_input(range: (f32, f32), points: usize, t: f32) -> f32 {
+    const LOG_SHAPER_STOPS: f32 = 16.0;
+    if points <= 2 {
+        range.0 + ((range.1 - range.0) * t)
+    } else {
+        let span = range.1 - range.0;
+        let offset = span * (2.0f32).powf(-LOG_SHAPER_STOPS);
+        let log_min = offset.log2();
+        let log_max = (span + offset).log2();
+        range.0 + (2.0f32).powf(log_min + ((log_max - log_min) * t)) - offset
+    }
+}
+
+fn icc_s15_fixed16(n: f64) -> [u8; 4] {
+    ((n * 65536.0).round() as i32).to_be_bytes()
+}
+
+/// An ICC `XYZType` tag.
+fn icc_xyz(xyz: [f64; 3]) -> Vec<u8> {
+    let mut data = b"XYZ \0\0\0\0".to_vec();
+    for n in xyz.iter() {
+        data.extend_from_slice(&icc_s15_fixed16(*n));
+    }
+    data
+}
+
+/// An ICC `multiLocalizedUnicodeType` tag with a single English string.
+fn icc_mluc(text: &str) -> Vec<u8> {
+    let utf16: Vec<u8> = text.encode_utf16().flat_map(|c| c.to_be_bytes()).collect();
+    let mut data = b"mluc\0\0\0\0".to_vec();
+    data.extend_from_slice(&1u32.to_be_bytes()); // Record count.
+    data.extend_from_slice(&12u32.to_be_bytes()); // Record size.
+    data.extend_from_slice(b"enUS");
+    data.extend_from_slice(&(utf16.len() as u32).to_be_bytes());
+    data.extend_from_slice(&28u32.to_be_bytes()); // Offset of the string.
+    data.extend_from_slice(&utf16);
+    data
+}
+
+/// The UTC `(year, month, day, hour, minute, second)` of `time`, as
+/// stored in ICC headers.
+fn icc_date_time(time: std::time::SystemTime) -> [u16; 6] {
+    let secs = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // Civil date from days since 1970-01-01, in the proleptic Gregorian
+    // calendar.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - (era * 146097);
+    let yoe = (doe - (doe / 1460) + (doe / 36524) - (doe / 146096)) / 365;
+    let doy = doe - ((365 * yoe) + (yoe / 4) - (yoe / 100));
+    let mp = ((5 * doy) + 2) / 153;
+    let day = doy - (((153 * mp) + 2) / 5) + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + (era * 400) + if month <= 2 { 1 } else { 0 };
+
+    [
+        year as u16,
+        month as u16,
+        day as u16,
+        (secs / 3600) as u16,
+        ((secs / 60) % 60) as u16,
+        (secs % 60) as u16,
+    ]
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colorbox::lut::Lut1D;
+
+    #[test]
+    fn half_to_f32_01() {
+        assert_eq!(half_to_f32(0x0000), 0.0);
+        assert_eq!(half_to_f32(0x0001), (2.0f32).powi(-24));
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x7bff), HALF_MAX);
+        assert_eq!(half_to_f32(0xfc00), -std::f32::INFINITY);
+        assert!(half_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn clf_half_domain() {
+        let lut = |extrapolation| {
+            ExtrapolatedLut::new(
+                Lut1D {
+                    ranges: vec![(0.0, 1.0)],
+                    tables: vec![vec![0.0, 0.5, 1.0]; 3],
+                },
+                extrapolation,
+            )
+        };
+        let write = |lut: &ExtrapolatedLut| {
+            let mut out = Vec::new();
+            let metadata = LutMetadata {
+                name: "Test".into(),
+                ..LutMetadata::default()
+            };
+            write_clf_1d(&mut out, lut, None, None, &metadata).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let clamped = write(&lut(Extrapolation::Clamp));
+        assert!(!clamped.contains("halfDomain"));
+        assert!(clamped.contains(r#"<Array dim="3 3">"#));
+
+        // 2.0 is half 0x4000, and extrapolates linearly.
+        let extended = write(&lut(Extrapolation::LinearExtend));
+        assert!(extended.contains(r#"halfDomain="true""#));
+        let entries: Vec<&str> = extended
+            .lines()
+            .skip_while(|l| !l.contains("<Array"))
+            .skip(1)
+            .take(65536)
+            .collect();
+        assert_eq!(entries[0x4000].trim(), "2 2 2");
+        assert_eq!(entries[0xc000].trim(), "-2 -2 -2");
+    }
+
+    #[test]
+    fn metadata_in_all_formats() {
+        let metadata = LutMetadata {
+            name: "Test LUT".into(),
+            software: "Test Suite".into(),
+            camera: Some("Test Camera".into()),
+            notes: vec!["A note".into()],
+        };
+        let table = [0.0, 0.5, 1.0];
+        let tables = [&table[..], &table[..], &table[..]];
+        let f = |rgb| rgb;
+        let written = |write: &dyn Fn(&mut Vec<u8>) -> Result<()>| {
+            let mut out = Vec::new();
+            write(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let text_formats = [
+            written(&|out| {
+                let lut = ExtrapolatedLut::new(
+                    Lut1D {
+                        ranges: vec![(0.0, 1.0)],
+                        tables: vec![table.to_vec(); 3],
+                    },
+                    Extrapolation::Clamp,
+                );
+                write_clf_1d(out, &lut, None, None, &metadata)
+            }),
+            written(&|out| write_cube_3d(out, (0.0, 1.0), 2, f, &metadata)),
+            written(&|out| write_csp_1d(out, (0.0, 1.0), tables, 2, &metadata)),
+            written(&|out| write_3dl(out, 2, f, &metadata)),
+        ];
+        for text in text_formats.iter() {
+            assert!(text.contains("Test LUT"));
+            assert!(text.contains("Generated by Test Suite"));
+            assert!(text.contains("Camera: Test Camera"));
+            assert!(text.contains("A note"));
+        }
+
+        // The camera formats only get the name.
+        let camera_formats = [
+            written(&|out| write_vlt(out, f, &metadata)),
+            written(&|out| write_sony_cube(out, 17, f, &metadata)),
+        ];
+        for text in camera_formats.iter() {
+            assert!(text.contains("\"Test LUT\""));
+            assert!(!text.contains("Test Camera"));
+        }
+    }
+}
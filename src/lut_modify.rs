@@ -0,0 +1,311 @@
+//! Adjustments made on top of loaded LUTs in LUT Maker's Modify mode.
+//!
+//! These are shared by the preview and the exporters, so that exported
+//! LUTs match what was previewed.
+
+use std::path::PathBuf;
+
+use colorbox::lut::{Lut1D, Lut3D};
+
+/// Simple trims on the output of a LUT.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Trims {
+    pub exposure: f32, // In stops.
+    pub offset: f32,
+    pub gamma: f32,
+    pub contrast: f32, // Power around the pivot.
+    pub pivot: f32,
+    pub floor: f32,   // Output value that 0.0 is mapped to.
+    pub ceiling: f32, // Output value that 1.0 is mapped to.
+}
+
+impl Trims {
+    pub fn new() -> Trims {
+        Trims {
+            exposure: 0.0,
+            offset: 0.0,
+            gamma: 1.0,
+            contrast: 1.0,
+            pivot: 0.18,
+            floor: 0.0,
+            ceiling: 1.0,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.exposure == 0.0
+            && self.offset == 0.0
+            && self.gamma == 1.0
+            && self.contrast == 1.0
+            && self.floor == 0.0
+            && self.ceiling == 1.0
+    }
+
+    /// Applies the trims to a single output value.
+    ///
+    /// The trims are applied in order: exposure, offset, gamma (as
+    /// `n^(1/gamma)`, so higher values brighten), contrast, and finally
+    /// the output is remapped from [0.0, 1.0] to [floor, ceiling].
+    /// Gamma and contrast leave values at or below zero unchanged.
+    ///
+    /// As long as `gamma` and `contrast` are positive and `ceiling` is
+    /// greater than `floor`, this is monotonically increasing, so
+    /// trimmed curves can still be inverted.
+    pub fn apply(&self, n: f32) -> f32 {
+        let n = (n * 2.0f32.powf(self.exposure)) + self.offset;
+        let n = if n > 0.0 && self.gamma != 1.0 && self.gamma > 0.0 {
+            n.powf(1.0 / self.gamma)
+        } else {
+            n
+        };
+        let n = if n > 0.0 && self.contrast != 1.0 && self.pivot > 0.0 {
+            self.pivot * (n / self.pivot).powf(self.contrast)
+        } else {
+            n
+        };
+        self.floor + (n * (self.ceiling - self.floor))
+    }
+}
+
+/// A loaded 3D LUT, with optional 1D curves applied before and after
+/// it.
+///
+/// The full stack is: pre curve, the LUT's own shaper (if any), the 3D
+/// LUT, trims, and then the post curve.
+#[derive(Debug, Clone)]
+pub struct Lut3DStack {
+    pub lut: Lut3D,
+    pub shaper: Option<Lut1D>,
+    pub path: PathBuf,
+    pub pre_curve: Option<(Lut1D, PathBuf)>,
+    pub post_curve: Option<(Lut1D, PathBuf)>,
+}
+
+impl Lut3DStack {
+    pub fn new(lut: Lut3D, shaper: Option<Lut1D>, path: PathBuf) -> Lut3DStack {
+        Lut3DStack {
+            lut: lut,
+            shaper: shaper,
+            path: path,
+            pre_curve: None,
+            post_curve: None,
+        }
+    }
+
+    /// The input range of the whole stack.
+    pub fn input_range(&self) -> (f32, f32) {
+        if let Some((ref curve, _)) = self.pre_curve {
+            curve.ranges[0]
+        } else if let Some(ref shaper) = self.shaper {
+            shaper.ranges[0]
+        } else {
+            self.lut.range[0]
+        }
+    }
+
+    /// Evaluates the stack for an RGB value.
+    pub fn eval(&self, rgb: [f32; 3], trims: &Trims) -> [f32; 3] {
+        let mut rgb = rgb;
+        if let Some((ref curve, _)) = self.pre_curve {
+            rgb = look_up_rgb(curve, rgb);
+        }
+        if let Some(ref shaper) = self.shaper {
+            rgb = look_up_rgb(shaper, rgb);
+        }
+        rgb = eval_lut_3d(&self.lut, rgb);
+        for n in rgb.iter_mut() {
+            *n = trims.apply(*n);
+        }
+        if let Some((ref curve, _)) = self.post_curve {
+            rgb = look_up_rgb(curve, rgb);
+        }
+        rgb
+    }
+}
+
+/// Looks up an RGB value in a 1D LUT, using the first table for all
+/// channels if it only has one.
+fn look_up_rgb(lut: &Lut1D, rgb: [f32; 3]) -> [f32; 3] {
+    let last = lut.tables.len() - 1;
+    [
+        lut.look_up(rgb[0], 0),
+        lut.look_up(rgb[1], 1.min(last)),
+        lut.look_up(rgb[2], 2.min(last)),
+    ]
+}
+
+/// Trilinearly interpolates a 3D LUT.
+pub fn eval_lut_3d(lut: &Lut3D, rgb: [f32; 3]) -> [f32; 3] {
+    let res = lut.resolution;
+
+    // Cell index and fraction on each axis.
+    let mut i0 = [0usize; 3];
+    let mut t = [0.0f32; 3];
+    for axis in 0..3 {
+        let (min, max) = lut.range[axis];
+        let n = ((rgb[axis] - min) / (max - min)).max(0.0).min(1.0) * (res[axis] - 1) as f32;
+        i0[axis] = (n as usize).min(res[axis].saturating_sub(2));
+        t[axis] = n - i0[axis] as f32;
+    }
+
+    // Red changes fastest in the tables.
+    let index = |r: usize, g: usize, b: usize| r + (g * res[0]) + (b * res[0] * res[1]);
+
+    let mut out = [0.0f32; 3];
+    for corner in 0..8 {
+        let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+        let mut weight = 1.0;
+        for axis in 0..3 {
+            weight *= if offset[axis] == 1 {
+                t[axis]
+            } else {
+                1.0 - t[axis]
+            };
+        }
+        let i = index(
+            (i0[0] + offset[0]).min(res[0] - 1),
+            (i0[1] + offset[1]).min(res[1] - 1),
+            (i0[2] + offset[2]).min(res[2] - 1),
+        );
+        for chan in 0..3 {
+            out[chan] += lut.tables[chan][i] * weight;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1.0e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn trims_identity() {
+        let t = Trims::new();
+        assert!(t.is_identity());
+        for n in [-1.0, 0.0, 0.18, 0.5, 1.0, 4.0].iter() {
+            assert_eq!(t.apply(*n), *n);
+        }
+
+        // The pivot alone doesn't change anything.
+        let t = Trims {
+            pivot: 0.5,
+            ..Trims::new()
+        };
+        assert!(t.is_identity());
+        assert_eq!(t.apply(0.25), 0.25);
+    }
+
+    #[test]
+    fn trims_individually() {
+        let t = Trims {
+            exposure: 1.0,
+            ..Trims::new()
+        };
+        assert_near(t.apply(0.25), 0.5);
+        let t = Trims {
+            exposure: -2.0,
+            ..Trims::new()
+        };
+        assert_near(t.apply(1.0), 0.25);
+
+        let t = Trims {
+            offset: 0.1,
+            ..Trims::new()
+        };
+        assert_near(t.apply(0.25), 0.35);
+
+        let t = Trims {
+            gamma: 2.0,
+            ..Trims::new()
+        };
+        assert_near(t.apply(0.25), 0.5);
+        assert_near(t.apply(1.0), 1.0);
+
+        // Contrast leaves the pivot in place, and scales distances from
+        // it in log space.
+        let t = Trims {
+            contrast: 2.0,
+            pivot: 0.25,
+            ..Trims::new()
+        };
+        assert_near(t.apply(0.25), 0.25);
+        assert_near(t.apply(0.5), 1.0);
+        assert_near(t.apply(0.125), 0.0625);
+
+        let t = Trims {
+            floor: 0.1,
+            ceiling: 0.9,
+            ..Trims::new()
+        };
+        assert_near(t.apply(0.0), 0.1);
+        assert_near(t.apply(0.5), 0.5);
+        assert_near(t.apply(1.0), 0.9);
+    }
+
+    #[test]
+    fn trims_order() {
+        // Exposure before offset.
+        let t = Trims {
+            exposure: 1.0,
+            offset: 0.1,
+            ..Trims::new()
+        };
+        assert_near(t.apply(0.25), 0.6);
+
+        // Offset before gamma, and gamma before contrast.
+        let t = Trims {
+            offset: 0.15,
+            gamma: 2.0,
+            contrast: 2.0,
+            pivot: 0.5,
+            ..Trims::new()
+        };
+        assert_near(t.apply(0.1), 0.5);
+    }
+
+    #[test]
+    fn trims_at_or_below_zero() {
+        // Gamma and contrast leave values at or below zero alone, since
+        // they're undefined there.
+        let t = Trims {
+            gamma: 2.0,
+            contrast: 2.0,
+            ..Trims::new()
+        };
+        assert_eq!(t.apply(0.0), 0.0);
+        assert_eq!(t.apply(-0.5), -0.5);
+
+        // Likewise for a pivot at zero, which contrast can't work
+        // around.
+        let t = Trims {
+            contrast: 2.0,
+            pivot: 0.0,
+            ..Trims::new()
+        };
+        assert_eq!(t.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn trims_are_monotonic() {
+        let t = Trims {
+            exposure: 0.5,
+            offset: -0.05,
+            gamma: 0.8,
+            contrast: 1.5,
+            pivot: 0.18,
+            floor: 0.05,
+            ceiling: 0.95,
+        };
+        let mut prev = t.apply(-1.0);
+        for i in 1..=2000 {
+            let n = t.apply(-1.0 + i as f32 * 0.001);
+            assert!(n > prev, "{} <= {}", n, prev);
+            prev = n;
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Application preferences that persist across sessions.
+//!
+//! Each app has its own preferences file in the user's config
+//! directory.  The apps share the same set of preferences, but not all
+//! of them apply to every app.
+
+use std::path::PathBuf;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::display_profile::DisplayProfile;
+
+const PREFERENCES_FILE_NAME: &str = "preferences.yaml";
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// The directory that file dialogs start in.
+    pub last_directory: Option<PathBuf>,
+
+    /// The size of the main window, in points.
+    pub window_size: Option<[f32; 2]>,
+
+    /// The number of worker threads for jobs.  Zero means the app's
+    /// default.  Takes effect on the next launch.
+    pub worker_count: usize,
+
+    /// The selected export format, as its file extension, for apps
+    /// that export in more than one format.
+    pub export_format: Option<String>,
+
+    /// The color space of the user's monitor, for previews.
+    pub display_profile: DisplayProfile,
+}
+
+impl Preferences {
+    /// Loads the preferences of the app `app_name` (e.g. "lut_maker").
+    ///
+    /// Returns the default preferences if none have been saved yet.
+    pub fn load(app_name: &str) -> std::io::Result<Preferences> {
+        let path = match crate::user_dirs::config_path(app_name, PREFERENCES_FILE_NAME) {
+            Some(path) if path.is_file() => path,
+            _ => return Ok(Preferences::default()),
+        };
+        let text = std::fs::read_to_string(&path)?;
+        serde_yaml::from_str(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Saves the preferences of the app `app_name`.
+    pub fn save(&self, app_name: &str) -> std::io::Result<()> {
+        let path =
+            crate::user_dirs::config_path(app_name, PREFERENCES_FILE_NAME).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "no user config directory to save preferences in",
+                )
+            })?;
+        let text = serde_yaml::to_string(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, text)
+    }
+
+    /// The number of worker threads to use, given the app's default.
+    pub fn worker_count(&self, default: usize) -> usize {
+        if self.worker_count == 0 {
+            default
+        } else {
+            self.worker_count
+        }
+    }
+
+    /// Applies the saved window size, if any, to a viewport.
+    pub fn apply_to_viewport(&self, viewport: egui::ViewportBuilder) -> egui::ViewportBuilder {
+        match self.window_size {
+            Some(size) => viewport.with_inner_size(size),
+            None => viewport,
+        }
+    }
+
+    /// Records the current state of the window, for saving.  Call once
+    /// per frame.
+    pub fn update_window(&mut self, ctx: &egui::Context) {
+        if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
+            self.window_size = Some([rect.width(), rect.height()]);
+        }
+    }
+
+    /// UI for the preferences that are edited directly, rather than
+    /// being remembered from use.  `default_worker_count` is the app's
+    /// default number of worker threads.
+    pub fn ui(&mut self, ui: &mut egui::Ui, default_worker_count: usize) {
+        ui.horizontal(|ui| {
+            ui.label("Worker threads:");
+            let mut count = self.worker_count(default_worker_count);
+            if ui
+                .add(egui::widgets::DragValue::new(&mut count).clamp_range(1..=64))
+                .on_hover_text("Takes effect the next time the app is started.")
+                .changed()
+            {
+                self.worker_count = count;
+            }
+        });
+    }
+}
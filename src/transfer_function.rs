@@ -0,0 +1,463 @@
+//! The built-in transfer functions (e.g. sRGB or camera log curves),
+//! with optional sensor floor and ceiling adjustments.
+
+use crate::formula::Formula;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TransferFunction {
+    pub id: TransferFunctionID,
+    pub arri_logc3_ei: colorbox::transfer_functions::arri::logc3::EI,
+
+    /// Whether HLG and PQ are decoded to scene or display light.  HLG
+    /// signals are scene-referred and PQ signals display-referred, so
+    /// the other intent for each goes through the Rec.2100 OOTF.
+    pub hlg_intent: HdrIntent,
+    pub pq_intent: HdrIntent,
+
+    /// Nominal peak luminance of the HLG display, in cd/m².  Determines
+    /// the system gamma of the HLG OOTF.
+    pub hlg_peak_luminance: u32,
+
+    /// The encoded-to-linear formula used by `TransferFunctionID::Custom`.
+    pub custom_formula: Formula,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TransferFunctionID {
+    Linear,
+    ARRILogC3,
+    ARRILogC4,
+    BlackmagicFilmGen5,
+    DavinciIntermediate,
+    CanonLog1,
+    CanonLog2,
+    CanonLog3,
+    DJIDlog,
+    FujifilmFlog,
+    HLG,
+    NikonNlog,
+    PanasonicVlog,
+    PQ,
+    Rec709,
+    RedLog3G10,
+    SonySlog1,
+    SonySlog2,
+    SonySlog3,
+    sRGB,
+
+    /// A user-supplied formula.  Not part of `TRANSFER_FUNCTION_IDS`,
+    /// since it isn't a fixed function to compare against.
+    Custom,
+}
+
+/// Whether a transfer function's linear values are scene light (as seen
+/// by the camera) or display light (as emitted by the display).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HdrIntent {
+    Scene,
+    Display,
+}
+
+impl HdrIntent {
+    pub fn ui_text(&self) -> &'static str {
+        match *self {
+            HdrIntent::Scene => "Scene light",
+            HdrIntent::Display => "Display light",
+        }
+    }
+}
+
+pub const TRANSFER_FUNCTION_IDS: &[TransferFunctionID] = &[
+    TransferFunctionID::Linear,
+    TransferFunctionID::sRGB,
+    TransferFunctionID::Rec709,
+    TransferFunctionID::HLG,
+    TransferFunctionID::PQ,
+    TransferFunctionID::ARRILogC3,
+    TransferFunctionID::ARRILogC4,
+    TransferFunctionID::BlackmagicFilmGen5,
+    TransferFunctionID::DavinciIntermediate,
+    TransferFunctionID::CanonLog1,
+    TransferFunctionID::CanonLog2,
+    TransferFunctionID::CanonLog3,
+    TransferFunctionID::DJIDlog,
+    TransferFunctionID::FujifilmFlog,
+    TransferFunctionID::NikonNlog,
+    TransferFunctionID::PanasonicVlog,
+    TransferFunctionID::RedLog3G10,
+    TransferFunctionID::SonySlog1,
+    TransferFunctionID::SonySlog2,
+    TransferFunctionID::SonySlog3,
+];
+
+impl Default for TransferFunction {
+    fn default() -> TransferFunction {
+        TransferFunction {
+            id: TransferFunctionID::sRGB,
+            arri_logc3_ei: colorbox::transfer_functions::arri::logc3::EI::Ei800,
+            hlg_intent: HdrIntent::Scene,
+            pq_intent: HdrIntent::Display,
+            hlg_peak_luminance: 1000,
+            custom_formula: Formula::default(),
+        }
+    }
+}
+
+impl TransferFunction {
+    pub fn to_linear_fc(
+        &self,
+        n: f32,
+        floor: Option<f32>,
+        ceil: Option<f32>,
+        normalize: bool,
+    ) -> f32 {
+        let (nonlinear_black, nonlinear_max, _, linear_top, _) = self.constants();
+        let out_floor = self.to_linear(floor.unwrap_or(nonlinear_black));
+        let out_ceil = self.to_linear(ceil.unwrap_or(nonlinear_max));
+
+        let mut out = self.to_linear(n);
+        out = (out - out_floor) / (out_ceil - out_floor);
+        if !normalize {
+            out *= linear_top;
+        }
+
+        out
+    }
+
+    pub fn from_linear_fc(
+        &self,
+        mut n: f32,
+        floor: Option<f32>,
+        ceil: Option<f32>,
+        normalize: bool,
+    ) -> f32 {
+        let (nonlinear_black, nonlinear_max, _, linear_top, _) = self.constants();
+        let in_floor = self.to_linear(floor.unwrap_or(nonlinear_black));
+        let in_ceil = self.to_linear(ceil.unwrap_or(nonlinear_max));
+
+        if !normalize {
+            n /= linear_top;
+        }
+        n = in_floor + (n * (in_ceil - in_floor));
+
+        self.from_linear(n)
+    }
+
+    pub fn to_linear(&self, n: f32) -> f32 {
+        use colorbox::transfer_functions::*;
+        use TransferFunctionID::*;
+        match self.id {
+            Linear => n,
+
+            ARRILogC3 => arri::logc3::to_linear(n, true, self.arri_logc3_ei),
+            ARRILogC4 => arri::logc4::to_linear(n),
+            BlackmagicFilmGen5 => blackmagic::film_gen5::to_linear(n),
+            DavinciIntermediate => blackmagic::davinci_intermediate::to_linear(n),
+            CanonLog1 => canon::log1::to_linear(n),
+            CanonLog2 => canon::log2::to_linear(n),
+            CanonLog3 => canon::log3::to_linear(n),
+            DJIDlog => dji::dlog::to_linear(n),
+            FujifilmFlog => fujifilm::flog::to_linear(n),
+            HLG => match self.hlg_intent {
+                // Inverse OETF.
+                HdrIntent::Scene => rec2100_hlg::to_linear(n),
+                // EOTF, i.e. inverse OETF followed by the OOTF.
+                HdrIntent::Display => {
+                    let peak = self.hlg_peak_luminance as f32;
+                    peak * rec2100_hlg::to_linear(n)
+                        .max(0.0)
+                        .powf(self.hlg_system_gamma())
+                }
+            },
+            NikonNlog => nikon::nlog::to_linear(n),
+            PanasonicVlog => panasonic::vlog::to_linear(n),
+            PQ => match self.pq_intent {
+                // EOTF.
+                HdrIntent::Display => rec2100_pq::to_linear(n),
+                // EOTF followed by the inverse OOTF.
+                HdrIntent::Scene => pq_inverse_ootf(rec2100_pq::to_linear(n)),
+            },
+            Rec709 => rec709::to_linear(n),
+            RedLog3G10 => red::log3g10::to_linear(n),
+            SonySlog1 => sony::slog1::to_linear(n),
+            SonySlog2 => sony::slog2::to_linear(n),
+            SonySlog3 => sony::slog3::to_linear(n),
+            sRGB => srgb::to_linear(n),
+            Custom => self.custom_formula.eval(n),
+        }
+    }
+
+    pub fn from_linear(&self, n: f32) -> f32 {
+        use colorbox::transfer_functions::*;
+        use TransferFunctionID::*;
+        match self.id {
+            Linear => n,
+
+            ARRILogC3 => arri::logc3::from_linear(n, true, self.arri_logc3_ei),
+            ARRILogC4 => arri::logc4::from_linear(n),
+            BlackmagicFilmGen5 => blackmagic::film_gen5::from_linear(n),
+            DavinciIntermediate => blackmagic::davinci_intermediate::from_linear(n),
+            CanonLog1 => canon::log1::from_linear(n),
+            CanonLog2 => canon::log2::from_linear(n),
+            CanonLog3 => canon::log3::from_linear(n),
+            DJIDlog => dji::dlog::from_linear(n),
+            FujifilmFlog => fujifilm::flog::from_linear(n),
+            HLG => match self.hlg_intent {
+                HdrIntent::Scene => rec2100_hlg::from_linear(n),
+                HdrIntent::Display => {
+                    let peak = self.hlg_peak_luminance as f32;
+                    rec2100_hlg::from_linear(
+                        (n / peak).max(0.0).powf(1.0 / self.hlg_system_gamma()),
+                    )
+                }
+            },
+            NikonNlog => nikon::nlog::from_linear(n),
+            PanasonicVlog => panasonic::vlog::from_linear(n),
+            PQ => match self.pq_intent {
+                HdrIntent::Display => rec2100_pq::from_linear(n),
+                HdrIntent::Scene => rec2100_pq::from_linear(pq_ootf(n)),
+            },
+            Rec709 => rec709::from_linear(n),
+            RedLog3G10 => red::log3g10::from_linear(n),
+            SonySlog1 => sony::slog1::from_linear(n),
+            SonySlog2 => sony::slog2::from_linear(n),
+            SonySlog3 => sony::slog3::from_linear(n),
+            sRGB => srgb::from_linear(n),
+            Custom => self.custom_from_linear(n),
+        }
+    }
+
+    /// The system gamma of the HLG OOTF for the nominal peak luminance,
+    /// from Rec.2100 (with the extended formula from BT.2390 outside of
+    /// 400 to 2000 cd/m²).
+    pub fn hlg_system_gamma(&self) -> f32 {
+        let peak = (self.hlg_peak_luminance as f32).max(1.0);
+        if (400.0..=2000.0).contains(&peak) {
+            1.2 + (0.42 * (peak / 1000.0).log10())
+        } else {
+            1.2 * 1.111f32.powf((peak / 1000.0).log2())
+        }
+    }
+
+    /// Inverts the custom formula numerically.
+    ///
+    /// The formula is assumed to be increasing over the encoded range
+    /// [0.0, 1.0], and linear values outside of the range it maps to are
+    /// clamped to the ends of it.
+    fn custom_from_linear(&self, n: f32) -> f32 {
+        let (mut low, mut high) = (0.0f32, 1.0f32);
+        if n <= self.custom_formula.eval(low) {
+            return low;
+        }
+        if n >= self.custom_formula.eval(high) {
+            return high;
+        }
+        for _ in 0..32 {
+            let mid = (low + high) * 0.5;
+            if self.custom_formula.eval(mid) < n {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) * 0.5
+    }
+
+    /// Returns (NONLINEAR_BLACK, NONLINEAR_MAX, LINEAR_MIN, LINEAR_MAX,
+    /// LINEAR_SATURATE) for the transfer function.
+    ///
+    /// - NONLINEAR_BLACK is the non-linear value of linear = 0.0.
+    /// - NONLINEAR_MAX is the maximum nonlinear value that should be
+    ///   reportable by a camera sensor.  Usually 1.0, but some transfer
+    ///   functions are weird.
+    /// - LINEAR_MIN/MAX are the linear values when the encoded value is
+    ///   0.0 and 1.0.
+    /// - LINEAR_SATURATE is the linear value when the encoded value is
+    ///   NONLINEAR_MAX.  Usually the same as LINEAR_MAX, but some
+    ///   transfer functions are weird.
+    #[inline(always)]
+    pub fn constants(&self) -> (f32, f32, f32, f32, f32) {
+        use colorbox::transfer_functions::*;
+        use TransferFunctionID::*;
+        match self.id {
+            Linear => (0.0, 1.0, 0.0, 1.0, 1.0),
+
+            ARRILogC3 => {
+                use arri::logc3::*;
+                (
+                    from_linear(0.0, true, self.arri_logc3_ei),
+                    1.0,
+                    to_linear(0.0, true, self.arri_logc3_ei),
+                    to_linear(1.0, true, self.arri_logc3_ei),
+                    to_linear(1.0, true, self.arri_logc3_ei),
+                )
+            }
+            ARRILogC4 => {
+                use arri::logc4::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            BlackmagicFilmGen5 => {
+                use blackmagic::film_gen5::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            DavinciIntermediate => {
+                use blackmagic::davinci_intermediate::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            CanonLog1 => {
+                use canon::log1::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            CanonLog2 => {
+                use canon::log2::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            CanonLog3 => {
+                use canon::log3::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            DJIDlog => {
+                use dji::dlog::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            FujifilmFlog => {
+                use fujifilm::flog::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            HLG => match self.hlg_intent {
+                HdrIntent::Scene => (0.0, 1.0, 0.0, 1.0, 1.0),
+                HdrIntent::Display => {
+                    let peak = self.hlg_peak_luminance as f32;
+                    (0.0, 1.0, 0.0, peak, peak)
+                }
+            },
+            NikonNlog => {
+                use nikon::nlog::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            PanasonicVlog => {
+                use panasonic::vlog::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            PQ => match self.pq_intent {
+                HdrIntent::Display => (
+                    0.0,
+                    1.0,
+                    0.0,
+                    rec2100_pq::LUMINANCE_MAX,
+                    rec2100_pq::LUMINANCE_MAX,
+                ),
+                HdrIntent::Scene => {
+                    let linear_max = pq_inverse_ootf(rec2100_pq::LUMINANCE_MAX);
+                    (0.0, 1.0, 0.0, linear_max, linear_max)
+                }
+            },
+            Rec709 => (0.0, 1.0, 0.0, 1.0, 1.0),
+            RedLog3G10 => {
+                use red::log3g10::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            SonySlog1 => {
+                use sony::slog1::*;
+                (
+                    NONLINEAR_BLACK,
+                    NONLINEAR_SATURATION,
+                    LINEAR_MIN,
+                    LINEAR_MAX,
+                    self.to_linear(NONLINEAR_SATURATION),
+                )
+            }
+            SonySlog2 => {
+                use sony::slog2::*;
+                (
+                    NONLINEAR_BLACK,
+                    NONLINEAR_SATURATION,
+                    LINEAR_MIN,
+                    LINEAR_MAX,
+                    self.to_linear(NONLINEAR_SATURATION),
+                )
+            }
+            SonySlog3 => {
+                use sony::slog3::*;
+                (NONLINEAR_BLACK, 1.0, LINEAR_MIN, LINEAR_MAX, LINEAR_MAX)
+            }
+            sRGB => (0.0, 1.0, 0.0, 1.0, 1.0),
+            Custom => {
+                let linear_min = self.to_linear(0.0);
+                let linear_max = self.to_linear(1.0);
+                (
+                    self.from_linear(0.0),
+                    1.0,
+                    linear_min,
+                    linear_max,
+                    linear_max,
+                )
+            }
+        }
+    }
+}
+
+/// The reference PQ OOTF from Rec.2100: the Rec.709 OETF followed by
+/// the BT.1886 EOTF, mapping scene light to display light in cd/m².
+fn pq_ootf(n: f32) -> f32 {
+    let e = 59.5208 * n.max(0.0);
+    let e = if e <= 0.018 {
+        4.5 * e
+    } else {
+        (1.099 * e.powf(0.45)) - 0.099
+    };
+    100.0 * e.powf(2.4)
+}
+
+/// The inverse of `pq_ootf()`.
+fn pq_inverse_ootf(n: f32) -> f32 {
+    let e = (n.max(0.0) / 100.0).powf(1.0 / 2.4);
+    let e = if e <= 4.5 * 0.018 {
+        e / 4.5
+    } else {
+        ((e + 0.099) / 1.099).powf(1.0 / 0.45)
+    };
+    e / 59.5208
+}
+
+impl From<camera_db::TransferFunction> for TransferFunctionID {
+    fn from(function: camera_db::TransferFunction) -> TransferFunctionID {
+        match function {
+            camera_db::TransferFunction::CanonLog2 => TransferFunctionID::CanonLog2,
+            camera_db::TransferFunction::CanonLog3 => TransferFunctionID::CanonLog3,
+            camera_db::TransferFunction::PanasonicVlog => TransferFunctionID::PanasonicVlog,
+            camera_db::TransferFunction::SonySlog3 => TransferFunctionID::SonySlog3,
+        }
+    }
+}
+
+impl TransferFunctionID {
+    pub fn ui_text(&self) -> &'static str {
+        use TransferFunctionID::*;
+        match *self {
+            Linear => "Linear",
+
+            ARRILogC3 => "ARRI LogC3 / ALEXA LogC v3",
+            ARRILogC4 => "ARRI LogC4",
+            BlackmagicFilmGen5 => "BMD Film Gen5",
+            DavinciIntermediate => "DaVinci Intermediate",
+            CanonLog1 => "Canon Log",
+            CanonLog2 => "Canon Log 2",
+            CanonLog3 => "Canon Log 3",
+            DJIDlog => "DJI D-Log",
+            FujifilmFlog => "Fujifilm F-Log",
+            HLG => "Rec.2100 - HLG",
+            NikonNlog => "Nikon N-Log",
+            PanasonicVlog => "Panasonic V-Log",
+            PQ => "Rec.2100 - PQ",
+            Rec709 => "Rec.709",
+            RedLog3G10 => "RED Log3G10",
+            SonySlog1 => "Sony S-Log",
+            SonySlog2 => "Sony S-Log2",
+            SonySlog3 => "Sony S-Log3",
+            sRGB => "sRGB",
+            Custom => "Custom formula",
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use eframe::egui;
+
+/// Undo/redo history for an app's editable state.
+///
+/// Rather than wrapping every individual mutation, the app hands the
+/// stack a snapshot of its editable state each frame via `update()`.
+/// Whenever that differs from the last recorded state, the previous
+/// state becomes an undo step.
+///
+/// Apps should skip calling `update()` while the user is still in the
+/// middle of an edit (see `is_interacting()`), so that e.g. dragging a
+/// slider becomes a single undo step rather than one per frame.
+pub struct UndoStack<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    current: Option<T>,
+    max_steps: usize,
+}
+
+impl<T: Clone + PartialEq> UndoStack<T> {
+    pub fn new(max_steps: usize) -> UndoStack<T> {
+        UndoStack {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            current: None,
+            max_steps: max_steps,
+        }
+    }
+
+    /// The most recently recorded state, if any.
+    pub fn current(&self) -> Option<&T> {
+        self.current.as_ref()
+    }
+
+    /// Records `state` as a new undo step if it differs from the most
+    /// recently recorded state.
+    pub fn update(&mut self, state: T) {
+        match self.current {
+            None => self.current = Some(state),
+            Some(ref current) => {
+                if *current != state {
+                    let previous = self.current.replace(state).unwrap();
+                    self.undo.push(previous);
+                    if self.undo.len() > self.max_steps {
+                        self.undo.remove(0);
+                    }
+                    self.redo.clear();
+                }
+            }
+        }
+    }
+
+    /// Steps back in the history, returning the state to restore.
+    pub fn undo(&mut self) -> Option<T> {
+        let state = self.undo.pop()?;
+        if let Some(current) = self.current.replace(state.clone()) {
+            self.redo.push(current);
+        }
+        Some(state)
+    }
+
+    /// Steps forward in the history, returning the state to restore.
+    pub fn redo(&mut self) -> Option<T> {
+        let state = self.redo.pop()?;
+        if let Some(current) = self.current.replace(state.clone()) {
+            self.undo.push(current);
+        }
+        Some(state)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Discards all history.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.current = None;
+    }
+}
+
+/// Whether the user is in the middle of an edit that shouldn't be
+/// split into multiple undo steps.
+pub fn is_interacting(ctx: &egui::Context) -> bool {
+    ctx.input(|input| input.pointer.any_down()) || ctx.memory(|mem| mem.focus().is_some())
+}
+
+/// Undo/redo requested via keyboard shortcut this frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HistoryAction {
+    Undo,
+    Redo,
+}
+
+/// Checks for the undo (Ctrl+Z) and redo (Ctrl+Shift+Z or Ctrl+Y)
+/// shortcuts.
+///
+/// Text fields have their own undo, so this does nothing while one of
+/// them has focus.
+pub fn consume_shortcuts(ctx: &egui::Context) -> Option<HistoryAction> {
+    use egui::{Key, KeyboardShortcut, Modifiers};
+
+    if ctx.memory(|mem| mem.focus().is_some()) {
+        return None;
+    }
+
+    ctx.input_mut(|input| {
+        if input.consume_shortcut(&KeyboardShortcut::new(
+            Modifiers::COMMAND | Modifiers::SHIFT,
+            Key::Z,
+        )) || input.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Y))
+        {
+            Some(HistoryAction::Redo)
+        } else if input.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Z)) {
+            Some(HistoryAction::Undo)
+        } else {
+            None
+        }
+    })
+}
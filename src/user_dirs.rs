@@ -0,0 +1,50 @@
+//! Per-user directories for application data and settings.
+
+use std::path::PathBuf;
+
+fn env_path(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var).map(PathBuf::from)
+}
+
+/// The platform's per-user data directory, or `None` if there isn't
+/// one.
+pub fn data_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        env_path("LOCALAPPDATA").or_else(|| env_path("APPDATA"))
+    } else if cfg!(target_os = "macos") {
+        Some(env_path("HOME")?.join("Library/Application Support"))
+    } else {
+        env_path("XDG_DATA_HOME").or_else(|| env_path("HOME").map(|home| home.join(".local/share")))
+    }
+}
+
+/// The platform's per-user config directory, or `None` if there isn't
+/// one.
+pub fn config_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        env_path("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        Some(env_path("HOME")?.join("Library/Application Support"))
+    } else {
+        env_path("XDG_CONFIG_HOME").or_else(|| env_path("HOME").map(|home| home.join(".config")))
+    }
+}
+
+/// Path of the session log file of the application `app_name` (e.g.
+/// "lut_maker").
+pub fn log_path(app_name: &str) -> Option<PathBuf> {
+    Some(
+        data_dir()?
+            .join(format!("etf_{}", app_name))
+            .join("session.log"),
+    )
+}
+
+/// Path of the settings file `file_name` of the application `app_name`.
+pub fn config_path(app_name: &str, file_name: &str) -> Option<PathBuf> {
+    Some(
+        config_dir()?
+            .join(format!("etf_{}", app_name))
+            .join(file_name),
+    )
+}
@@ -0,0 +1,131 @@
+use colorbox::{
+    chroma::Chromaticities,
+    matrix::{self, AdaptationMethod, Matrix},
+};
+
+/// Common light sources, as (name, CIE xy chromaticity), for quickly
+/// picking the illuminant footage was shot under.
+pub const ILLUMINANTS: &[(&str, (f64, f64))] = &[
+    ("A (Tungsten)", (0.44757, 0.40745)),
+    ("D50", (0.34567, 0.35850)),
+    ("D55", (0.33242, 0.34743)),
+    ("D65", (0.31271, 0.32902)),
+    ("D75", (0.29902, 0.31485)),
+    ("F2 (Cool White Fluorescent)", (0.37208, 0.37529)),
+    ("F11 (Narrow Band Fluorescent)", (0.38052, 0.37713)),
+];
+
+/// A white balance adjustment, for footage shot under lighting other
+/// than its color space's white point.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WhiteBalance {
+    /// The CIE xy chromaticity of the light the footage was shot under.
+    Illuminant(f64, f64),
+
+    /// The color temperature (in Kelvin) and tint of the light the
+    /// footage was shot under.  Tint is in thousandths of Duv, with
+    /// positive values toward magenta, i.e. below the Planckian locus.
+    Temperature(f64, f64),
+}
+
+impl WhiteBalance {
+    /// The CIE xy chromaticity of the light the footage was shot under.
+    pub fn source_white(&self) -> (f64, f64) {
+        match *self {
+            WhiteBalance::Illuminant(x, y) => (x, y),
+            WhiteBalance::Temperature(temperature, tint) => temperature_to_xy(temperature, tint),
+        }
+    }
+
+    /// A matrix in the linear RGB space of `chroma` that maps the source
+    /// white to `chroma`'s white point, using Bradford adaptation.
+    pub fn matrix(&self, chroma: Chromaticities) -> Matrix {
+        matrix::compose(&[
+            matrix::rgb_to_xyz_matrix(chroma),
+            matrix::xyz_chromatic_adaptation_matrix(
+                self.source_white(),
+                chroma.w,
+                AdaptationMethod::Bradford,
+            ),
+            matrix::xyz_to_rgb_matrix(chroma),
+        ])
+    }
+
+    pub fn to_string(&self) -> String {
+        match *self {
+            WhiteBalance::Illuminant(x, y) => format!("illuminant {} {}", x, y),
+            WhiteBalance::Temperature(temperature, tint) => {
+                format!("temperature {} {}", temperature, tint)
+            }
+        }
+    }
+
+    pub fn from_str(text: &str) -> Option<WhiteBalance> {
+        let mut parts = text.split_whitespace();
+        let kind = parts.next()?;
+        let a = parts.next()?.parse::<f64>().ok()?;
+        let b = parts.next()?.parse::<f64>().ok()?;
+        match kind {
+            "illuminant" => Some(WhiteBalance::Illuminant(a, b)),
+            "temperature" => Some(WhiteBalance::Temperature(a, b)),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a color temperature (in Kelvin) and tint to CIE xy.
+///
+/// Uses the cubic spline approximation of the Planckian locus by Kim et
+/// al., which is valid from 1667K to 25000K.  Temperatures outside that
+/// range are clamped to it.  `tint` is as in `WhiteBalance::Temperature`.
+pub fn temperature_to_xy(temperature: f64, tint: f64) -> (f64, f64) {
+    let (u, v) = xy_to_uv(planckian_xy(temperature));
+    if tint == 0.0 {
+        return uv_to_xy((u, v));
+    }
+
+    // Offset perpendicular to the locus, in CIE 1960 UCS.
+    let (u2, v2) = xy_to_uv(planckian_xy(temperature + 1.0));
+    let (du, dv) = (u2 - u, v2 - v);
+    let len = (du * du + dv * dv).sqrt();
+    if len == 0.0 {
+        // At the end of the valid range, so no direction to go by.
+        return uv_to_xy((u, v));
+    }
+    let duv = -tint / 1000.0;
+    uv_to_xy((u + dv / len * duv, v - du / len * duv))
+}
+
+fn planckian_xy(temperature: f64) -> (f64, f64) {
+    let t = temperature.max(1667.0).min(25000.0);
+    let (t2, t3) = (t * t, t * t * t);
+
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t3 + 2.1070379e6 / t2 + 0.2226347e3 / t + 0.240390
+    };
+
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.1063814 * x3 - 1.34811020 * x2 + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x3 - 1.37418593 * x2 + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x3 - 5.87338670 * x2 + 3.75112997 * x - 0.37001483
+    };
+
+    (x, y)
+}
+
+/// CIE xy to CIE 1960 UCS uv.
+fn xy_to_uv((x, y): (f64, f64)) -> (f64, f64) {
+    let d = -2.0 * x + 12.0 * y + 3.0;
+    (4.0 * x / d, 6.0 * y / d)
+}
+
+/// CIE 1960 UCS uv to CIE xy.
+fn uv_to_xy((u, v): (f64, f64)) -> (f64, f64) {
+    let d = 2.0 * u - 8.0 * v + 4.0;
+    (3.0 * u / d, 2.0 * v / d)
+}
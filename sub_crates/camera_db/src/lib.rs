@@ -0,0 +1,255 @@
+//! Known properties of specific camera models, keyed by the make and
+//! model strings that the cameras write into their Exif data.
+//!
+//! This is static data shared by the tools, e.g. to pre-select a
+//! camera's transfer function and gamut, or to sanity-check estimated
+//! sensor floors.  The black levels are the nominal code values that
+//! the cameras record pure black at in their log modes.
+//!
+//! The transfer functions and gamuts are given as this crate's own
+//! enums, so that it has no dependencies.  Their variant names match
+//! the corresponding built-in transfer functions and gamuts of the
+//! tools.
+
+#[derive(Debug, Copy, Clone)]
+pub struct CameraInfo {
+    pub make: &'static str,
+    pub model: &'static str,
+
+    /// The camera's log curve.
+    pub transfer_function: TransferFunction,
+    /// The gamut the camera usually records its log curve in.
+    pub gamut: Gamut,
+
+    /// Bit depth that `black_level` is specified in.
+    pub bit_depth: u32,
+    /// Code value of pure black.
+    pub black_level: u32,
+
+    /// The native ISOs of the sensor in the camera's log mode, lowest
+    /// first.  Dual gain sensors have two.  Empty if not known.
+    pub native_iso: &'static [u32],
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TransferFunction {
+    CanonLog2,
+    CanonLog3,
+    PanasonicVlog,
+    SonySlog3,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Gamut {
+    CanonCinemaGamut,
+    PanasonicVGamut,
+    SGamut3Cine,
+}
+
+impl CameraInfo {
+    /// The camera's make and model, for display in the UI.
+    pub fn ui_text(&self) -> String {
+        // Some makers repeat the make in the model string.
+        if self
+            .model
+            .to_lowercase()
+            .starts_with(&self.make.to_lowercase())
+        {
+            self.model.into()
+        } else {
+            format!("{} {}", self.make, self.model)
+        }
+    }
+
+    /// The black level normalized to [0.0, 1.0].
+    pub fn black_level_normalized(&self) -> f32 {
+        self.black_level as f32 / ((1u32 << self.bit_depth) - 1) as f32
+    }
+
+    /// Returns whether an estimated (normalized) sensor floor differs
+    /// wildly from this camera's known black level.
+    pub fn floor_diverges(&self, floor: f32) -> bool {
+        let expected = self.black_level_normalized();
+        (floor - expected).abs() > (expected * 0.5).max(0.01)
+    }
+}
+
+/// Finds a camera by its make and model, as given in e.g. Exif data.
+///
+/// Matching is case insensitive and ignores surrounding whitespace.
+pub fn find_camera(make: &str, model: &str) -> Option<&'static CameraInfo> {
+    let make = make.trim();
+    let model = model.trim();
+    CAMERAS
+        .iter()
+        .find(|c| c.make.eq_ignore_ascii_case(make) && c.model.eq_ignore_ascii_case(model))
+}
+
+pub const CAMERAS: &[CameraInfo] = &[
+    // Sony.  S-Log3 records black at 95/1023.
+    CameraInfo {
+        make: "SONY",
+        model: "ILCE-7SM3",
+        transfer_function: TransferFunction::SonySlog3,
+        gamut: Gamut::SGamut3Cine,
+        bit_depth: 10,
+        black_level: 95,
+        native_iso: &[640, 12800],
+    },
+    CameraInfo {
+        make: "SONY",
+        model: "ILCE-7M4",
+        transfer_function: TransferFunction::SonySlog3,
+        gamut: Gamut::SGamut3Cine,
+        bit_depth: 10,
+        black_level: 95,
+        native_iso: &[],
+    },
+    CameraInfo {
+        make: "SONY",
+        model: "ILCE-1",
+        transfer_function: TransferFunction::SonySlog3,
+        gamut: Gamut::SGamut3Cine,
+        bit_depth: 10,
+        black_level: 95,
+        native_iso: &[],
+    },
+    CameraInfo {
+        make: "SONY",
+        model: "ILME-FX3",
+        transfer_function: TransferFunction::SonySlog3,
+        gamut: Gamut::SGamut3Cine,
+        bit_depth: 10,
+        black_level: 95,
+        native_iso: &[800, 12800],
+    },
+    CameraInfo {
+        make: "SONY",
+        model: "ILME-FX30",
+        transfer_function: TransferFunction::SonySlog3,
+        gamut: Gamut::SGamut3Cine,
+        bit_depth: 10,
+        black_level: 95,
+        native_iso: &[800, 2500],
+    },
+    CameraInfo {
+        make: "SONY",
+        model: "ILME-FX6",
+        transfer_function: TransferFunction::SonySlog3,
+        gamut: Gamut::SGamut3Cine,
+        bit_depth: 10,
+        black_level: 95,
+        native_iso: &[800, 12800],
+    },
+    // Panasonic.  V-Log records black at 128/1023.
+    CameraInfo {
+        make: "Panasonic",
+        model: "DC-GH5",
+        transfer_function: TransferFunction::PanasonicVlog,
+        gamut: Gamut::PanasonicVGamut,
+        bit_depth: 10,
+        black_level: 128,
+        native_iso: &[400],
+    },
+    CameraInfo {
+        make: "Panasonic",
+        model: "DC-GH6",
+        transfer_function: TransferFunction::PanasonicVlog,
+        gamut: Gamut::PanasonicVGamut,
+        bit_depth: 10,
+        black_level: 128,
+        native_iso: &[],
+    },
+    CameraInfo {
+        make: "Panasonic",
+        model: "DC-S5",
+        transfer_function: TransferFunction::PanasonicVlog,
+        gamut: Gamut::PanasonicVGamut,
+        bit_depth: 10,
+        black_level: 128,
+        native_iso: &[640, 4000],
+    },
+    CameraInfo {
+        make: "Panasonic",
+        model: "DC-S5M2",
+        transfer_function: TransferFunction::PanasonicVlog,
+        gamut: Gamut::PanasonicVGamut,
+        bit_depth: 10,
+        black_level: 128,
+        native_iso: &[640, 5000],
+    },
+    // Canon.  Canon Log 2 records black at 95/1023, and Canon Log 3 at
+    // 128/1023.
+    CameraInfo {
+        make: "Canon",
+        model: "Canon EOS R5",
+        transfer_function: TransferFunction::CanonLog3,
+        gamut: Gamut::CanonCinemaGamut,
+        bit_depth: 10,
+        black_level: 128,
+        native_iso: &[],
+    },
+    CameraInfo {
+        make: "Canon",
+        model: "Canon EOS C70",
+        transfer_function: TransferFunction::CanonLog2,
+        gamut: Gamut::CanonCinemaGamut,
+        bit_depth: 10,
+        black_level: 95,
+        native_iso: &[800],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_camera_ignores_case_and_whitespace() {
+        let camera = find_camera(" sony", "ilce-7sm3 ").unwrap();
+        assert_eq!(camera.model, "ILCE-7SM3");
+        assert_eq!(camera.transfer_function, TransferFunction::SonySlog3);
+        assert!(find_camera("SONY", "ILCE-7S").is_none());
+    }
+
+    #[test]
+    fn ui_text_doesnt_repeat_make() {
+        assert_eq!(
+            find_camera("Canon", "Canon EOS R5").unwrap().ui_text(),
+            "Canon EOS R5"
+        );
+        assert_eq!(
+            find_camera("SONY", "ILME-FX3").unwrap().ui_text(),
+            "SONY ILME-FX3"
+        );
+    }
+
+    #[test]
+    fn black_levels_match_log_curves() {
+        // One camera per log curve.
+        let black_level = |make, model| find_camera(make, model).unwrap().black_level;
+        assert_eq!(black_level("SONY", "ILCE-7SM3"), 95);
+        assert_eq!(black_level("Panasonic", "DC-GH5"), 128);
+        assert_eq!(black_level("Canon", "Canon EOS C70"), 95);
+        assert_eq!(black_level("Canon", "Canon EOS R5"), 128);
+
+        for camera in CAMERAS.iter() {
+            let expected = match camera.transfer_function {
+                TransferFunction::CanonLog2 | TransferFunction::SonySlog3 => 95,
+                TransferFunction::CanonLog3 | TransferFunction::PanasonicVlog => 128,
+            };
+            assert_eq!((camera.bit_depth, camera.black_level), (10, expected));
+        }
+    }
+
+    #[test]
+    fn cameras_are_unique_and_valid() {
+        for (i, a) in CAMERAS.iter().enumerate() {
+            assert!(a.black_level < (1 << a.bit_depth));
+            assert!(a.native_iso.windows(2).all(|w| w[0] < w[1]));
+            for b in &CAMERAS[(i + 1)..] {
+                assert!(!(a.make == b.make && a.model == b.model));
+            }
+        }
+    }
+}
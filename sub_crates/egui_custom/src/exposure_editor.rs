@@ -0,0 +1,175 @@
+//! A window for entering the exposures of images that lack exposure
+//! metadata.
+//!
+//! Like the image list, the editor only reports what the user entered.
+//! The app applies the exposures to its own data.
+
+use eframe::egui::{self, widgets::DragValue, Context};
+
+/// An image in the exposure editor.
+pub struct ExposureEditorItem {
+    pub name: String,
+
+    /// The image's exposure in stops, if it's known from its metadata.
+    /// Known exposures are shown for reference, but can't be edited.
+    pub known_ev: Option<f32>,
+
+    /// How bright the image is, on any scale (e.g. its median value).
+    /// Only used to order the guessed exposures.
+    pub brightness: f32,
+}
+
+/// Editable exposures for a list of images.
+///
+/// The exposures of images without known exposures start out guessed,
+/// by assuming that the images are evenly spaced in exposure and
+/// ordering them by brightness.
+pub struct ExposureEditor {
+    title: String,
+    items: Vec<ExposureEditorItem>,
+    evs: Vec<f32>, // Entered exposures in stops, parallel to `items`.
+    cadence: f32,  // Guessed spacing between images, in stops.
+}
+
+impl ExposureEditor {
+    pub fn new(title: &str, items: Vec<ExposureEditorItem>) -> ExposureEditor {
+        let mut editor = ExposureEditor {
+            title: title.into(),
+            evs: items
+                .iter()
+                .map(|item| item.known_ev.unwrap_or(0.0))
+                .collect(),
+            items: items,
+            cadence: 1.0,
+        };
+        editor.guess();
+        editor
+    }
+
+    /// Fills in the exposures of the images without known exposures,
+    /// from darkest to brightest, spaced `cadence` stops apart.
+    pub fn guess(&mut self) {
+        let mut order: Vec<usize> = (0..self.items.len())
+            .filter(|&i| self.items[i].known_ev.is_none())
+            .collect();
+        order.sort_by(|&a, &b| {
+            self.items[a]
+                .brightness
+                .partial_cmp(&self.items[b].brightness)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        for (n, &i) in order.iter().enumerate() {
+            self.evs[i] = n as f32 * self.cadence;
+        }
+    }
+
+    /// Shows the editor window.
+    ///
+    /// Returns `Some` when the window is closed: with the entered
+    /// exposures in stops, as `(item index, exposure)`, if the user
+    /// applied them, and with nothing if the user canceled.
+    pub fn show(&mut self, ctx: &Context) -> Option<Vec<(usize, f32)>> {
+        let mut result = None;
+
+        egui::Window::new(self.title.as_str())
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("These images lack the metadata needed to compute their exposures.  Enter each image's exposure in stops, relative to the others.");
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    ui.label("Spacing:");
+                    ui.add(
+                        DragValue::new(&mut self.cadence)
+                            .clamp_range(0.1..=8.0)
+                            .speed(0.05)
+                            .max_decimals(2)
+                            .suffix(" stops"),
+                    );
+                    if ui
+                        .button("Guess")
+                        .on_hover_text("Space the images evenly by the given number of stops, from darkest to brightest.")
+                        .clicked()
+                    {
+                        self.guess();
+                    }
+                });
+                ui.add_space(8.0);
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("exposure_editor_grid")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Image");
+                                ui.strong("Exposure (stops)");
+                                ui.end_row();
+
+                                for (item, ev) in self.items.iter().zip(self.evs.iter_mut()) {
+                                    ui.label(&item.name);
+                                    if let Some(known_ev) = item.known_ev {
+                                        ui.label(format!("{:.2} (from metadata)", known_ev));
+                                    } else {
+                                        ui.add(
+                                            DragValue::new(ev)
+                                                .speed(0.05)
+                                                .max_decimals(2),
+                                        );
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Apply").clicked() {
+                        result = Some(
+                            self.items
+                                .iter()
+                                .zip(self.evs.iter())
+                                .enumerate()
+                                .filter(|(_, (item, _))| item.known_ev.is_none())
+                                .map(|(i, (_, ev))| (i, *ev))
+                                .collect(),
+                        );
+                    }
+                    if ui.button("Cancel").clicked() {
+                        result = Some(Vec::new());
+                    }
+                });
+            });
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guess_01() {
+        let item = |known_ev: Option<f32>, brightness: f32| ExposureEditorItem {
+            name: String::new(),
+            known_ev: known_ev,
+            brightness: brightness,
+        };
+        let mut editor = ExposureEditor::new(
+            "",
+            vec![
+                item(None, 0.5),
+                item(None, 0.2),
+                item(Some(-3.0), 0.1),
+                item(None, 0.8),
+            ],
+        );
+        assert_eq!(editor.evs, vec![1.0, 0.0, -3.0, 2.0]);
+
+        editor.cadence = 2.0;
+        editor.guess();
+        assert_eq!(editor.evs, vec![2.0, 0.0, -3.0, 4.0]);
+    }
+}
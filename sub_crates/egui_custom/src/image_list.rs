@@ -0,0 +1,412 @@
+//! A list of image thumbnails, optionally grouped into named sets.
+//!
+//! The widget only draws the list and reports what the user did.  The
+//! images themselves are owned by the app, which applies the returned
+//! edits to its own data.
+
+use eframe::egui::{
+    self,
+    widgets::{Button, ImageButton, Label, TextEdit},
+    Color32, TextureHandle, Ui,
+};
+
+/// An image in the list.
+pub struct ImageListItem<'a> {
+    pub thumbnail: &'a TextureHandle,
+
+    /// Short notes about problems with the image, shown next to it.
+    pub badges: Vec<ImageBadge>,
+}
+
+/// A note shown next to an image, e.g. that its Exif data is missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageBadge {
+    pub text: String,
+    pub hover_text: String,
+    pub color: Color32,
+}
+
+impl ImageBadge {
+    pub fn warning(text: &str, hover_text: &str) -> ImageBadge {
+        ImageBadge {
+            text: format!("⚠ {}", text),
+            hover_text: hover_text.into(),
+            color: Color32::from_rgb(220, 220, 20),
+        }
+    }
+}
+
+/// Which images are selected, as (set index, image index).  Lists that
+/// aren't grouped into sets have a single set at index zero.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImageListSelection {
+    /// The most recently clicked image, which is the one shown in
+    /// previews etc.
+    pub current: (usize, usize),
+
+    /// Further selected images, when more than one is selected.
+    pub others: Vec<(usize, usize)>,
+}
+
+impl ImageListSelection {
+    pub fn is_selected(&self, index: (usize, usize)) -> bool {
+        self.current == index || self.others.contains(&index)
+    }
+
+    /// All selected images, sorted.
+    pub fn all(&self) -> Vec<(usize, usize)> {
+        let mut all = self.others.clone();
+        all.push(self.current);
+        all.sort_unstable();
+        all.dedup();
+        all
+    }
+
+    /// Selects only `index`.
+    pub fn select(&mut self, index: (usize, usize)) {
+        self.current = index;
+        self.others.clear();
+    }
+
+    /// Updates the selection for the removal of the image at `index`,
+    /// so that it keeps referring to the same images.
+    pub fn image_removed(&mut self, index: (usize, usize)) {
+        let adjust = |i: (usize, usize)| -> Option<(usize, usize)> {
+            if i.0 != index.0 || i.1 < index.1 {
+                Some(i)
+            } else if i.1 > index.1 {
+                Some((i.0, i.1 - 1))
+            } else {
+                None
+            }
+        };
+        self.others = self.others.iter().filter_map(|&i| adjust(i)).collect();
+        self.current = adjust(self.current).unwrap_or((index.0, index.1.saturating_sub(1)));
+    }
+
+    /// Toggles `index` in a multi-selection, as for a ctrl-click.
+    fn toggle(&mut self, index: (usize, usize)) {
+        if let Some(i) = self.others.iter().position(|&i| i == index) {
+            self.others.remove(i);
+        } else if self.current == index {
+            if let Some(i) = self.others.pop() {
+                self.current = i;
+            }
+        } else {
+            self.others.push(self.current);
+            self.current = index;
+        }
+    }
+
+    /// Extends the selection to all images between the current one and
+    /// `index` in the same set, as for a shift-click.
+    fn extend_to(&mut self, index: (usize, usize)) {
+        if index.0 != self.current.0 {
+            self.select(index);
+            return;
+        }
+        let (a, b) = (self.current.1.min(index.1), self.current.1.max(index.1));
+        for i in a..=b {
+            if !self.is_selected((index.0, i)) {
+                self.others.push((index.0, i));
+            }
+        }
+        self.others.retain(|&i| i != index);
+        self.others.push(self.current);
+        self.current = index;
+    }
+}
+
+/// A change to the list requested by the user, for the app to apply.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageListEdit {
+    /// Remove these images, in descending order so that they can be
+    /// removed one after another.
+    RemoveImages(Vec<(usize, usize)>),
+    RemoveSet(usize),
+    MoveSet {
+        from: usize,
+        to: usize,
+    },
+    /// Move an image to `to`, as (set index, image index) once it's
+    /// been removed from `from`.  This may be within the same set.
+    MoveImage {
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+}
+
+pub struct ImageListResponse {
+    /// Whether the user changed the selection.
+    pub selection_changed: bool,
+
+    pub edit: Option<ImageListEdit>,
+}
+
+/// Something being dragged in the image list.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum DragItem {
+    Set(usize),          // Set index.
+    Image(usize, usize), // (set index, image index)
+}
+
+/// A list of image thumbnails with selection and removal, and, when
+/// grouped into sets, set names and drag-and-drop reordering of sets and
+/// images.
+///
+/// Click to select an image, ctrl-click to add or remove an image from
+/// the selection, and shift-click to select a range.
+pub struct ImageListWidget<'a> {
+    sets: &'a [Vec<ImageListItem<'a>>],
+    set_names: Option<&'a mut Vec<String>>,
+    enabled: bool,
+}
+
+impl<'a> ImageListWidget<'a> {
+    /// Creates a list of the images in `sets`.  Lists that aren't
+    /// grouped into sets should pass a single set.
+    pub fn new(sets: &'a [Vec<ImageListItem<'a>>]) -> ImageListWidget<'a> {
+        ImageListWidget {
+            sets: sets,
+            set_names: None,
+            enabled: true,
+        }
+    }
+
+    /// Shows the images grouped into sets, with editable set names.
+    /// Sets can then be reordered and removed, and images moved within
+    /// and between them.  Empty names are shown as "Image Set N".
+    pub fn sets(mut self, set_names: &'a mut Vec<String>) -> Self {
+        self.set_names = Some(set_names);
+        self
+    }
+
+    /// Whether the list can be edited.  Selection is always possible.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn show(self, ui: &mut Ui, selection: &mut ImageListSelection) -> ImageListResponse {
+        let ImageListWidget {
+            sets,
+            mut set_names,
+            enabled,
+        } = self;
+        let use_sets = set_names.is_some();
+
+        let mut selection_changed = false;
+        let mut edit = None;
+        let mut dropped: Option<(DragItem, usize)> = None; // (item, target set index)
+        let mut dropped_at: Option<(DragItem, (usize, usize))> = None; // (item, insertion point)
+
+        let selected = selection.all();
+        if enabled
+            && selected.len() > 1
+            && ui
+                .add(Button::new(format!("🗙 Remove {} Selected", selected.len())))
+                .clicked()
+        {
+            edit = Some(ImageListEdit::RemoveImages(
+                selected.iter().rev().copied().collect(),
+            ));
+        }
+
+        egui::containers::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for (set_i, set) in sets.iter().enumerate() {
+                    let mut set_contents = |ui: &mut Ui| {
+                        if let Some(set_names) = &mut set_names {
+                            ui.horizontal(|ui| {
+                                drag_handle(ui, enabled, DragItem::Set(set_i));
+                                if let Some(name) = set_names.get_mut(set_i) {
+                                    ui.add_enabled(
+                                        enabled,
+                                        TextEdit::singleline(name)
+                                            .hint_text(format!("Image Set {}", set_i + 1))
+                                            .desired_width(140.0),
+                                    );
+                                }
+                                if ui.add_enabled(enabled, Button::new("🗙")).clicked() {
+                                    edit = Some(ImageListEdit::RemoveSet(set_i));
+                                }
+                            });
+                            ui.add_space(4.0);
+                        }
+                        for (img_i, item) in set.iter().enumerate() {
+                            let index = (set_i, img_i);
+                            let row = ui.horizontal(|ui| {
+                                if use_sets {
+                                    drag_handle(ui, enabled, DragItem::Image(set_i, img_i));
+                                }
+                                if ui
+                                    .add(
+                                        ImageButton::new(item.thumbnail)
+                                            .selected(selection.is_selected(index)),
+                                    )
+                                    .clicked()
+                                {
+                                    let modifiers = ui.input(|i| i.modifiers);
+                                    if modifiers.shift {
+                                        selection.extend_to(index);
+                                    } else if modifiers.command {
+                                        selection.toggle(index);
+                                    } else {
+                                        selection.select(index);
+                                    }
+                                    selection_changed = true;
+                                }
+                                if ui.add_enabled(enabled, Button::new("🗙")).clicked() {
+                                    edit = Some(ImageListEdit::RemoveImages(vec![index]));
+                                }
+                                for badge in item.badges.iter() {
+                                    ui.add(Label::new(
+                                        egui::RichText::new(&badge.text).color(badge.color),
+                                    ))
+                                    .on_hover_text(&badge.hover_text);
+                                }
+                            });
+
+                            // Dropping an image onto another inserts it
+                            // before or after, depending on which half it's
+                            // dropped on.
+                            if use_sets && enabled {
+                                let row = row.response;
+                                let after = ui
+                                    .input(|i| i.pointer.interact_pos())
+                                    .map(|pos| pos.y > row.rect.center().y)
+                                    .unwrap_or(false);
+                                if let Some(DragItem::Image(..)) =
+                                    row.dnd_hover_payload::<DragItem>().as_deref()
+                                {
+                                    let y = if after {
+                                        row.rect.bottom()
+                                    } else {
+                                        row.rect.top()
+                                    };
+                                    ui.painter().hline(
+                                        row.rect.x_range(),
+                                        y,
+                                        ui.visuals().selection.stroke,
+                                    );
+                                }
+                                if let Some(item) = row.dnd_release_payload::<DragItem>() {
+                                    dropped_at = Some((*item, (set_i, img_i + after as usize)));
+                                }
+                            }
+                        }
+                    };
+
+                    ui.add_space(if use_sets { 16.0 } else { 4.0 });
+                    if use_sets {
+                        let (_, payload) = ui.dnd_drop_zone::<DragItem>(
+                            egui::Frame::default().inner_margin(4.0),
+                            set_contents,
+                        );
+                        if let Some(item) = payload {
+                            dropped = Some((*item, set_i));
+                        }
+                    } else {
+                        set_contents(ui);
+                    }
+                }
+            });
+
+        if enabled {
+            match dropped {
+                Some((DragItem::Set(from), to)) if from != to => {
+                    edit = Some(ImageListEdit::MoveSet { from: from, to: to });
+                }
+                Some((DragItem::Image(set_i, img_i), to)) => {
+                    // Onto an image, or else the end of the set.
+                    let insert_at = match dropped_at {
+                        Some((DragItem::Image(..), at)) => at,
+                        _ => (to, sets[to].len()),
+                    };
+                    if let Some(to) = move_destination((set_i, img_i), insert_at) {
+                        edit = Some(ImageListEdit::MoveImage {
+                            from: (set_i, img_i),
+                            to: to,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        ImageListResponse {
+            selection_changed: selection_changed,
+            edit: edit,
+        }
+    }
+}
+
+/// Where an image ends up when it's moved from `from` to the insertion
+/// point `insert_at`, after its removal shifts the images behind it.
+/// `None` if that's where it already is.
+fn move_destination(from: (usize, usize), insert_at: (usize, usize)) -> Option<(usize, usize)> {
+    let to = if from.0 == insert_at.0 && from.1 < insert_at.1 {
+        (insert_at.0, insert_at.1 - 1)
+    } else {
+        insert_at
+    };
+    if to == from {
+        None
+    } else {
+        Some(to)
+    }
+}
+
+/// A handle for dragging an image or image set to a new place.
+fn drag_handle(ui: &mut Ui, enabled: bool, item: DragItem) {
+    if !enabled {
+        ui.add_enabled(false, Label::new("☰"));
+        return;
+    }
+    let id = ui.make_persistent_id(("Image List Drag", format!("{:?}", item)));
+    ui.dnd_drag_source(id, item, |ui| {
+        ui.label("☰");
+    })
+    .response
+    .on_hover_text("Drag to move");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_01() {
+        let mut selection = ImageListSelection::default();
+        selection.select((0, 1));
+        selection.toggle((0, 3));
+        selection.toggle((1, 0));
+        assert_eq!(selection.all(), vec![(0, 1), (0, 3), (1, 0)]);
+
+        selection.toggle((0, 3));
+        assert_eq!(selection.all(), vec![(0, 1), (1, 0)]);
+
+        selection.select((0, 1));
+        selection.extend_to((0, 4));
+        assert_eq!(selection.current, (0, 4));
+        assert_eq!(selection.all(), vec![(0, 1), (0, 2), (0, 3), (0, 4)]);
+
+        selection.image_removed((0, 2));
+        assert_eq!(selection.current, (0, 3));
+        assert_eq!(selection.all(), vec![(0, 1), (0, 2), (0, 3)]);
+    }
+
+    #[test]
+    fn move_destination_01() {
+        // Within a set.
+        assert_eq!(move_destination((0, 1), (0, 4)), Some((0, 3)));
+        assert_eq!(move_destination((0, 3), (0, 1)), Some((0, 1)));
+        assert_eq!(move_destination((0, 1), (0, 1)), None);
+        assert_eq!(move_destination((0, 1), (0, 2)), None);
+
+        // Between sets.
+        assert_eq!(move_destination((0, 1), (1, 0)), Some((1, 0)));
+        assert_eq!(move_destination((1, 2), (0, 5)), Some((0, 5)));
+    }
+}
@@ -1,7 +1,17 @@
 //! Custom widgets etc. for egui.
 
+mod exposure_editor;
+mod image_list;
+mod log_panel;
+mod lut_3d_view;
 mod status_bar;
 
+pub use exposure_editor::{ExposureEditor, ExposureEditorItem};
+pub use image_list::{
+    ImageBadge, ImageListEdit, ImageListItem, ImageListResponse, ImageListSelection,
+    ImageListWidget,
+};
+pub use lut_3d_view::Lut3DView;
 pub use status_bar::status_bar;
 
 #[cfg(test)]
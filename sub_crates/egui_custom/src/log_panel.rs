@@ -0,0 +1,169 @@
+//! An expandable panel showing the whole job queue log, with filtering
+//! by level and text search.
+//!
+//! Its state is kept in egui's memory, so apps don't need to store
+//! anything for it.
+
+use eframe::egui::{
+    self,
+    containers::ScrollArea,
+    widgets::{Button, Label, TextEdit},
+    Rgba, RichText,
+};
+use job_queue::{JobQueue, LogEntry, LogLevel};
+
+#[derive(Debug, Clone)]
+struct LogPanelState {
+    open: bool,
+    show_errors: bool,
+    show_warnings: bool,
+    show_notes: bool,
+    search: String,
+}
+
+impl Default for LogPanelState {
+    fn default() -> LogPanelState {
+        LogPanelState {
+            open: false,
+            show_errors: true,
+            show_warnings: true,
+            show_notes: true,
+            search: String::new(),
+        }
+    }
+}
+
+impl LogPanelState {
+    fn shows(&self, entry: &LogEntry) -> bool {
+        let level = match entry.level {
+            LogLevel::Error => self.show_errors,
+            LogLevel::Warning => self.show_warnings,
+            LogLevel::Note => self.show_notes,
+        };
+        level
+            && (self.search.is_empty()
+                || entry
+                    .message
+                    .to_lowercase()
+                    .contains(&self.search.to_lowercase()))
+    }
+}
+
+fn state_id() -> egui::Id {
+    egui::Id::new("egui_custom::log_panel")
+}
+
+fn load_state(ctx: &egui::Context) -> LogPanelState {
+    ctx.data_mut(|d| {
+        d.get_temp_mut_or_default::<LogPanelState>(state_id())
+            .clone()
+    })
+}
+
+fn store_state(ctx: &egui::Context, state: LogPanelState) {
+    ctx.data_mut(|d| d.insert_temp(state_id(), state));
+}
+
+/// A button that opens and closes the log panel.
+pub(crate) fn toggle_button(ui: &mut egui::Ui) {
+    let mut state = load_state(ui.ctx());
+    if ui
+        .add(Button::new("📜  Log").selected(state.open))
+        .on_hover_text("Show the whole log, with filtering and search.")
+        .clicked()
+    {
+        state.open = !state.open;
+        store_state(ui.ctx(), state);
+    }
+}
+
+/// Draws the log panel, if it's open.  It's placed above any bottom
+/// panels drawn before it, such as the status bar.
+pub(crate) fn log_panel(ctx: &egui::Context, job_queue: &JobQueue) {
+    let mut state = load_state(ctx);
+    if !state.open {
+        return;
+    }
+
+    // Oldest first.
+    let entries: Vec<LogEntry> = (0..job_queue.log_count())
+        .rev()
+        .filter_map(|i| job_queue.get_log_entry(i))
+        .collect();
+
+    egui::containers::panel::TopBottomPanel::bottom("log_panel")
+        .resizable(true)
+        .default_height(250.0)
+        .show(ctx, |ui| {
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                let count = |level| entries.iter().filter(|e| e.level == level).count();
+                ui.checkbox(
+                    &mut state.show_errors,
+                    format!("Errors ({})", count(LogLevel::Error)),
+                );
+                ui.checkbox(
+                    &mut state.show_warnings,
+                    format!("Warnings ({})", count(LogLevel::Warning)),
+                );
+                ui.checkbox(
+                    &mut state.show_notes,
+                    format!("Notes ({})", count(LogLevel::Note)),
+                );
+                ui.add_space(8.0);
+                ui.add(
+                    TextEdit::singleline(&mut state.search)
+                        .hint_text("Search")
+                        .desired_width(200.0),
+                );
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.add(Button::new("🗙")).on_hover_text("Close").clicked() {
+                        state.open = false;
+                    }
+                    if ui.add(Button::new("Clear")).clicked() {
+                        job_queue.clear_log();
+                    }
+                    if ui
+                        .add(Button::new("Copy"))
+                        .on_hover_text("Copy the shown entries.")
+                        .clicked()
+                    {
+                        let mut text = String::new();
+                        for entry in entries.iter().filter(|e| state.shows(e)) {
+                            text.push_str(&entry.to_text());
+                            text.push('\n');
+                        }
+                        ui.output_mut(|output| output.copied_text = text);
+                    }
+                });
+            });
+            ui.separator();
+
+            ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for (i, entry) in entries.iter().enumerate() {
+                        if !state.shows(entry) {
+                            continue;
+                        }
+                        let text = RichText::new(entry.to_line()).monospace();
+                        ui.add(Label::new(match entry.level {
+                            LogLevel::Error => text.color(Rgba::from_rgb(1.0, 0.2, 0.1)),
+                            LogLevel::Warning => text.color(Rgba::from_rgb(0.6, 0.6, 0.05)),
+                            LogLevel::Note => text,
+                        }));
+                        if let Some(details) = &entry.details {
+                            egui::CollapsingHeader::new("Details")
+                                .id_source(("log entry details", i))
+                                .show(ui, |ui| {
+                                    ui.label(RichText::new(details).monospace().small());
+                                });
+                        }
+                    }
+                });
+        });
+
+    store_state(ctx, state);
+}
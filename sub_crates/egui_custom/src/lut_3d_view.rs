@@ -0,0 +1,337 @@
+//! A viewer for 3D LUTs, for spotting clipping and hue twists.
+//!
+//! The LUT is shown either as the RGB cube deformed by it, or as a
+//! hue/saturation slice of constant value.  Clipping shows up as
+//! lattice points piling up on the faces of the cube or the rim of the
+//! slice, and hue twists as bent spokes in the slice.
+
+use eframe::egui::{self, vec2, Color32, Pos2, Sense, Shape, Stroke, Ui};
+
+/// Maximum lattice points per axis drawn in the cube view.
+const MAX_CUBE_POINTS: usize = 9;
+
+/// The number of hue spokes and saturation steps in the slice view.
+const SLICE_HUES: usize = 12;
+const SLICE_SAT_STEPS: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ViewMode {
+    Cube,
+    Slice,
+}
+
+/// View settings, kept in egui's memory between frames.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct ViewState {
+    mode: ViewMode,
+    yaw: f32,   // Radians.
+    pitch: f32, // Radians.
+    value: f32, // Value (as in HSV) of the slice.
+}
+
+impl Default for ViewState {
+    fn default() -> ViewState {
+        // Looking down the neutral axis at an angle, so it's vertical.
+        ViewState {
+            mode: ViewMode::Cube,
+            yaw: std::f32::consts::FRAC_PI_4,
+            pitch: (2.0f32).sqrt().atan(),
+            value: 0.75,
+        }
+    }
+}
+
+/// A 3D LUT viewer widget.
+///
+/// The LUT is given as per-channel tables with red changing fastest,
+/// over an input domain that's treated as [0.0, 1.0] on all axes.
+pub struct Lut3DView<'a> {
+    id_source: egui::Id,
+    resolution: [usize; 3],
+    tables: [&'a [f32]; 3],
+}
+
+impl<'a> Lut3DView<'a> {
+    pub fn new(
+        id_source: impl std::hash::Hash,
+        resolution: [usize; 3],
+        tables: [&'a [f32]; 3],
+    ) -> Lut3DView<'a> {
+        let len = resolution[0] * resolution[1] * resolution[2];
+        assert!(resolution.iter().all(|r| *r >= 2));
+        assert!(tables.iter().all(|t| t.len() == len));
+        Lut3DView {
+            id_source: egui::Id::new(id_source),
+            resolution: resolution,
+            tables: tables,
+        }
+    }
+
+    pub fn show(&self, ui: &mut Ui) -> egui::Response {
+        let id = ui.make_persistent_id(self.id_source);
+        let mut state = ui.data_mut(|d| *d.get_temp_mut_or_default::<ViewState>(id));
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut state.mode, ViewMode::Cube, "Cube")
+                .on_hover_text("The RGB cube deformed by the LUT.  Drag to rotate.");
+            ui.selectable_value(&mut state.mode, ViewMode::Slice, "Hue/Sat Slice")
+                .on_hover_text("Hue around and saturation outward, at a constant input value.  Spokes are lines of constant input hue, and rings of constant input saturation.");
+            if state.mode == ViewMode::Slice {
+                ui.add(egui::widgets::Slider::new(&mut state.value, 0.0..=1.0).text("Value"));
+            }
+        });
+
+        let size = ui.available_width().min(320.0);
+        let (response, painter) = ui.allocate_painter(vec2(size, size), Sense::drag());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, Color32::from_gray(24));
+
+        match state.mode {
+            ViewMode::Cube => {
+                let delta = response.drag_delta();
+                state.yaw += delta.x * 0.01;
+                state.pitch = (state.pitch + delta.y * 0.01)
+                    .max(-std::f32::consts::PI)
+                    .min(std::f32::consts::PI);
+                self.paint_cube(&painter, rect, &state);
+            }
+            ViewMode::Slice => self.paint_slice(&painter, rect, &state),
+        }
+
+        ui.data_mut(|d| d.insert_temp(id, state));
+        response
+    }
+
+    fn paint_cube(&self, painter: &egui::Painter, rect: egui::Rect, state: &ViewState) {
+        // Scale so that the output fits, with the unit cube as the
+        // minimum.
+        let (mut min, mut max) = (0.0f32, 1.0f32);
+        for table in self.tables.iter() {
+            for n in table.iter() {
+                if n.is_finite() {
+                    min = min.min(*n);
+                    max = max.max(*n);
+                }
+            }
+        }
+        let center = (min + max) * 0.5;
+        let scale = rect.width() * 0.5 / ((max - min) * 0.5 * (3.0f32).sqrt()).max(1.0e-6);
+        let project = |rgb: [f32; 3]| -> Pos2 {
+            let p = [rgb[0] - center, rgb[1] - center, rgb[2] - center];
+            let (sy, cy) = state.yaw.sin_cos();
+            let (sp, cp) = state.pitch.sin_cos();
+            // Yaw around blue, then pitch around the screen's x axis.
+            let x = p[0] * cy - p[1] * sy;
+            let y = p[0] * sy + p[1] * cy;
+            let z = y * sp + p[2] * cp;
+            rect.center() + vec2(x, -z) * scale * 0.95
+        };
+
+        // The undeformed unit cube, for reference.
+        let corner = |i: usize| [(i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32];
+        let reference = Stroke::new(1.0, Color32::from_gray(72));
+        for i in 0..8 {
+            for axis in 0..3 {
+                if i & (1 << axis) == 0 {
+                    let j = i | (1 << axis);
+                    painter.line_segment([project(corner(i)), project(corner(j))], reference);
+                }
+            }
+        }
+
+        // The deformed cube's edges.
+        let steps = 32;
+        for i in 0..8 {
+            for axis in 0..3 {
+                if i & (1 << axis) == 0 {
+                    let points: Vec<Pos2> = (0..=steps)
+                        .map(|s| {
+                            let mut rgb = corner(i);
+                            rgb[axis] = s as f32 / steps as f32;
+                            project(self.eval(rgb))
+                        })
+                        .collect();
+                    painter.add(Shape::line(
+                        points,
+                        Stroke::new(1.5, Color32::from_gray(200)),
+                    ));
+                }
+            }
+        }
+
+        // Lattice points, colored by their input color.
+        let points = self
+            .resolution
+            .iter()
+            .fold(MAX_CUBE_POINTS, |a, r| a.min(*r));
+        let n = |i: usize| i as f32 / (points - 1) as f32;
+        for b in 0..points {
+            for g in 0..points {
+                for r in 0..points {
+                    let rgb = [n(r), n(g), n(b)];
+                    painter.circle_filled(project(self.eval(rgb)), 2.0, to_color32(rgb));
+                }
+            }
+        }
+    }
+
+    fn paint_slice(&self, painter: &egui::Painter, rect: egui::Rect, state: &ViewState) {
+        let radius = rect.width() * 0.5 * 0.95 / 1.25;
+        let plot = |hue: f32, sat: f32| -> Pos2 {
+            let r = sat.max(0.0).min(1.25) * radius;
+            let angle = hue * std::f32::consts::PI * 2.0;
+            rect.center() + vec2(angle.cos(), -angle.sin()) * r
+        };
+        let slice_point = |hue: f32, sat: f32| -> Pos2 {
+            let (out_hue, out_sat, _) = rgb_to_hsv(self.eval(hsv_to_rgb(hue, sat, state.value)));
+            plot(out_hue, out_sat)
+        };
+
+        // The undeformed slice, for reference.
+        let reference = Stroke::new(1.0, Color32::from_gray(72));
+        painter.circle_stroke(rect.center(), radius, reference);
+        for h in 0..SLICE_HUES {
+            let hue = h as f32 / SLICE_HUES as f32;
+            painter.line_segment([rect.center(), plot(hue, 1.0)], reference);
+        }
+
+        // Rings of constant input saturation.
+        let ring_steps = 96;
+        for ring in 1..=4 {
+            let sat = ring as f32 / 4.0;
+            let points: Vec<Pos2> = (0..=ring_steps)
+                .map(|i| slice_point(i as f32 / ring_steps as f32, sat))
+                .collect();
+            painter.add(Shape::line(
+                points,
+                Stroke::new(1.0, Color32::from_gray(160)),
+            ));
+        }
+
+        // Spokes of constant input hue, colored by their input color.
+        for h in 0..SLICE_HUES {
+            let hue = h as f32 / SLICE_HUES as f32;
+            let points: Vec<Pos2> = (0..=SLICE_SAT_STEPS)
+                .map(|i| slice_point(hue, i as f32 / SLICE_SAT_STEPS as f32))
+                .collect();
+            let end = *points.last().unwrap();
+            let color = to_color32(hsv_to_rgb(hue, 1.0, 1.0));
+            painter.add(Shape::line(points, Stroke::new(2.0, color)));
+            painter.circle_filled(end, 3.0, color);
+        }
+
+        painter.text(
+            rect.left_top() + vec2(4.0, 4.0),
+            egui::Align2::LEFT_TOP,
+            format!("Value {:.2}", state.value),
+            egui::FontId::proportional(12.0),
+            Color32::from_gray(160),
+        );
+    }
+
+    /// Trilinear lookup, with `rgb` clamped to [0.0, 1.0].
+    fn eval(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let res = self.resolution;
+
+        let mut i0 = [0usize; 3];
+        let mut t = [0.0f32; 3];
+        for axis in 0..3 {
+            let n = rgb[axis].max(0.0).min(1.0) * (res[axis] - 1) as f32;
+            i0[axis] = (n as usize).min(res[axis] - 2);
+            t[axis] = n - i0[axis] as f32;
+        }
+
+        // Red changes fastest in the tables.
+        let index = |r: usize, g: usize, b: usize| r + (g * res[0]) + (b * res[0] * res[1]);
+
+        let mut out = [0.0f32; 3];
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let mut weight = 1.0;
+            for axis in 0..3 {
+                weight *= if offset[axis] == 1 {
+                    t[axis]
+                } else {
+                    1.0 - t[axis]
+                };
+            }
+            let i = index(i0[0] + offset[0], i0[1] + offset[1], i0[2] + offset[2]);
+            for chan in 0..3 {
+                out[chan] += self.tables[chan][i] * weight;
+            }
+        }
+
+        out
+    }
+}
+
+fn to_color32(rgb: [f32; 3]) -> Color32 {
+    let to_u8 = |n: f32| (n.max(0.0).min(1.0) * 255.0).round() as u8;
+    Color32::from_rgb(to_u8(rgb[0]), to_u8(rgb[1]), to_u8(rgb[2]))
+}
+
+/// Hue in [0.0, 1.0), saturation and value to RGB.
+fn hsv_to_rgb(hue: f32, sat: f32, value: f32) -> [f32; 3] {
+    let h = hue.rem_euclid(1.0) * 6.0;
+    let channel = |offset: f32| {
+        let k = (offset + h).rem_euclid(6.0);
+        value - value * sat * k.min(4.0 - k).max(0.0).min(1.0)
+    };
+    [channel(5.0), channel(3.0), channel(1.0)]
+}
+
+/// RGB to hue in [0.0, 1.0), saturation, and value.
+///
+/// Saturation is relative to the value, and can exceed 1.0 for colors
+/// with negative channels.  Grays have a hue of zero.
+fn rgb_to_hsv(rgb: [f32; 3]) -> (f32, f32, f32) {
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    let min = rgb[0].min(rgb[1]).min(rgb[2]);
+    let chroma = max - min;
+    if chroma <= 0.0 || max <= 0.0 {
+        return (0.0, 0.0, max);
+    }
+
+    let h = if max == rgb[0] {
+        (rgb[1] - rgb[2]) / chroma
+    } else if max == rgb[1] {
+        (rgb[2] - rgb[0]) / chroma + 2.0
+    } else {
+        (rgb[0] - rgb[1]) / chroma + 4.0
+    };
+    ((h / 6.0).rem_euclid(1.0), chroma / max, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_identity() {
+        let res = 3;
+        let mut tables = vec![Vec::new(), Vec::new(), Vec::new()];
+        for i in 0..(res * res * res) {
+            tables[0].push((i % res) as f32 / (res - 1) as f32);
+            tables[1].push(((i / res) % res) as f32 / (res - 1) as f32);
+            tables[2].push((i / (res * res)) as f32 / (res - 1) as f32);
+        }
+        let view = Lut3DView::new("", [res; 3], [&tables[0], &tables[1], &tables[2]]);
+
+        for rgb in [[0.0, 0.0, 0.0], [0.25, 0.5, 0.75], [1.0, 0.1, 0.9]].iter() {
+            let out = view.eval(*rgb);
+            for i in 0..3 {
+                assert!((out[i] - rgb[i]).abs() < 1.0e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn hsv_round_trip() {
+        for hsv in [(0.0, 1.0, 1.0), (0.3, 0.5, 0.8), (0.95, 0.25, 0.5)].iter() {
+            let (h, s, v) = rgb_to_hsv(hsv_to_rgb(hsv.0, hsv.1, hsv.2));
+            assert!((h - hsv.0).abs() < 1.0e-5);
+            assert!((s - hsv.1).abs() < 1.0e-5);
+            assert!((v - hsv.2).abs() < 1.0e-5);
+        }
+    }
+}
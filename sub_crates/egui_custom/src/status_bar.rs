@@ -19,8 +19,9 @@ pub fn status_bar(ctx: &egui::Context, job_queue: &JobQueue) {
         if log_count > 0 {
             for i in 0..log_count {
                 let log_i = (log_count - 1) - i;
-                if let Some((message, level)) = job_queue.get_log(log_i) {
-                    match level {
+                if let Some(entry) = job_queue.get_log_entry(log_i) {
+                    let message = &entry.message;
+                    match entry.level {
                         LogLevel::Error => {
                             error_count += 1;
                             log_string.push_str(&format!("{}:  ERROR: {}", i + 1, message));
@@ -34,6 +35,11 @@ pub fn status_bar(ctx: &egui::Context, job_queue: &JobQueue) {
                             log_string.push_str(&format!("{}:  {}", i + 1, message));
                         }
                     }
+                    if entry.details.is_some() {
+                        log_string.push_str(
+                            "\n    (Use \"Copy Log\" or \"Save Log…\" to get the backtrace.)",
+                        );
+                    }
                     if log_i > 0 {
                         log_string.push_str("\n\n")
                     }
@@ -53,31 +59,74 @@ pub fn status_bar(ctx: &egui::Context, job_queue: &JobQueue) {
             ui.add_space(6.0);
         }
 
-        // Draw progress bar for any in-progress jobs.
+        // Draw progress bars for any in-progress jobs, one per job, with
+        // a second, indented bar for the current sub-step if any.
         let jobs_are_canceling = job_queue.is_canceling();
-        if let Some((text, ratio)) = job_queue.progress() {
-            ui.horizontal(|ui| {
-                if ui
-                    .add_enabled(!jobs_are_canceling, Button::new("Cancel"))
-                    .clicked()
-                {
-                    job_queue.cancel_all_jobs();
+        let job_progress = job_queue.job_progress();
+        if !job_progress.is_empty() {
+            for (i, (_, progress)) in job_progress.iter().enumerate() {
+                let mut text = progress.text.clone();
+                if let (Some((done, total)), None) = (progress.items, &progress.sub_step) {
+                    text.push_str(&format!(" ({}/{})", done, total));
                 }
-                ui.add(
-                    ProgressBar::new(ratio)
-                        .text(if jobs_are_canceling {
-                            "Canceling..."
-                        } else {
-                            &text
-                        })
-                        .animate(true),
-                );
-            });
+                if let Some(eta) = progress.eta {
+                    text.push_str(&format!("  -  {} left", format_duration(eta)));
+                }
+
+                ui.horizontal(|ui| {
+                    if i == 0
+                        && ui
+                            .add_enabled(!jobs_are_canceling, Button::new("Cancel"))
+                            .clicked()
+                    {
+                        job_queue.cancel_all_jobs();
+                    }
+                    ui.add(
+                        ProgressBar::new(progress.ratio)
+                            .text(if jobs_are_canceling {
+                                "Canceling..."
+                            } else {
+                                &text
+                            })
+                            .animate(true),
+                    );
+                });
+
+                if let Some((ref sub_text, sub_ratio)) = progress.sub_step {
+                    let mut sub_text = sub_text.clone();
+                    if let Some((done, total)) = progress.items {
+                        sub_text.push_str(&format!(" ({}/{})", done, total));
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add_space(24.0);
+                        ui.add(ProgressBar::new(sub_ratio).text(sub_text));
+                    });
+                }
+            }
         } else if log_count > 0 {
             ui.with_layout(egui::Layout::right_to_left(Align::Max), |ui| {
                 if ui.add(Button::new("🗙  Clear Log")).clicked() {
                     job_queue.clear_log();
                 }
+                crate::log_panel::toggle_button(ui);
+                if ui.add(Button::new("Save Log…")).clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_title("Save Log")
+                        .set_file_name("log.txt")
+                        .save_file()
+                    {
+                        if let Err(e) = std::fs::write(&path, job_queue.log_text()) {
+                            job_queue.log_error(format!(
+                                "Unable to save log \"{}\": {}",
+                                path.to_string_lossy(),
+                                e
+                            ));
+                        }
+                    }
+                }
+                if ui.add(Button::new("Copy Log")).clicked() {
+                    ctx.output_mut(|output| output.copied_text = job_queue.log_text());
+                }
 
                 ui.add_space(6.0);
 
@@ -99,4 +148,18 @@ pub fn status_bar(ctx: &egui::Context, job_queue: &JobQueue) {
             });
         }
     });
+
+    crate::log_panel::log_panel(ctx, job_queue);
+}
+
+/// Formats a duration as e.g. "1h 5m", "3m 20s", or "45s".
+fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs.max(1))
+    }
 }
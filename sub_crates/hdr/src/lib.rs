@@ -2,27 +2,304 @@ mod trifloat;
 
 use std::io::Write;
 
+/// Options for writing Radiance .hdr files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdrOptions {
+    /// Multiplier applied to the pixel values before writing.
+    ///
+    /// It's recorded in the header as `EXPOSURE`, following the
+    /// Radiance convention, so that readers that honor it can recover
+    /// the original (e.g. absolute) values by dividing by it.
+    pub exposure: f32,
+
+    /// The software that wrote the file, recorded as `SOFTWARE`.
+    pub software: Option<String>,
+
+    /// The camera the image was captured with, recorded as `CAMERA`.
+    /// This isn't a standard Radiance variable, but readers ignore
+    /// variables they don't know.
+    pub camera: Option<String>,
+
+    /// The lens projection of the image (e.g. "fisheye_equisolid"),
+    /// recorded as `PROJECTION`.  Also not a standard Radiance
+    /// variable.
+    pub projection: Option<String>,
+
+    /// Run-length encode the pixels, which makes files smaller.
+    /// Otherwise they're written flat, which readers also support.
+    ///
+    /// Images narrower than 8 or wider than 32767 pixels can't be
+    /// run-length encoded, and are always written flat.
+    pub run_length_encoding: bool,
+}
+
+impl Default for HdrOptions {
+    fn default() -> HdrOptions {
+        HdrOptions {
+            exposure: 1.0,
+            software: None,
+            camera: None,
+            projection: None,
+            run_length_encoding: false,
+        }
+    }
+}
+
+/// Writes a Radiance .hdr file, with `exposure` applied to the pixels
+/// and recorded in the header.
+///
+/// Equivalent to `write_hdr_with_options()` with only the exposure set.
 pub fn write_hdr<W: Write>(
     out: &mut W,
     image: &[[f32; 3]],
     width: usize,
     height: usize,
     exposure: f32,
+) -> std::io::Result<()> {
+    write_hdr_with_options(
+        out,
+        image,
+        width,
+        height,
+        &HdrOptions {
+            exposure: exposure,
+            ..HdrOptions::default()
+        },
+    )
+}
+
+/// Writes a Radiance .hdr file.
+pub fn write_hdr_with_options<W: Write>(
+    out: &mut W,
+    image: &[[f32; 3]],
+    width: usize,
+    height: usize,
+    options: &HdrOptions,
 ) -> std::io::Result<()> {
     assert_eq!(image.len(), width * height);
 
     out.write_all(b"#?RADIANCE\n")?;
+    if let Some(ref software) = options.software {
+        out.write_all(format!("SOFTWARE={}\n", header_value(software)).as_bytes())?;
+    }
+    if let Some(ref camera) = options.camera {
+        out.write_all(format!("CAMERA={}\n", header_value(camera)).as_bytes())?;
+    }
+    if let Some(ref projection) = options.projection {
+        out.write_all(format!("PROJECTION={}\n", header_value(projection)).as_bytes())?;
+    }
+    if options.exposure != 1.0 {
+        out.write_all(format!("EXPOSURE={:e}\n", options.exposure).as_bytes())?;
+    }
     out.write_all(b"FORMAT=32-bit_rle_rgbe\n\n")?;
     out.write_all(format!("-Y {} +X {}\n", height, width).as_bytes())?;
-    for pixel in image.iter() {
-        let pixel_adjusted = [
-            pixel[0] * exposure,
-            pixel[1] * exposure,
-            pixel[2] * exposure,
-        ];
-        out.write_all(&trifloat::encode(pixel_adjusted))?;
+
+    let exposure = options.exposure;
+    let rle = options.run_length_encoding && (8..=0x7fff).contains(&width);
+    let mut scanline = Vec::with_capacity(width);
+    let mut encoded = Vec::new();
+    for row in image.chunks(width.max(1)) {
+        scanline.clear();
+        scanline.extend(row.iter().map(|pixel| {
+            trifloat::encode([
+                pixel[0] * exposure,
+                pixel[1] * exposure,
+                pixel[2] * exposure,
+            ])
+        }));
+
+        encoded.clear();
+        if rle {
+            encode_scanline_rle(&scanline, &mut encoded);
+        } else {
+            for pixel in scanline.iter() {
+                encoded.extend_from_slice(pixel);
+            }
+        }
+        out.write_all(&encoded)?;
     }
     out.flush()?;
 
     Ok(())
 }
+
+/// Encodes a scanline with Radiance's run-length encoding: a marker
+/// with the scanline's width, followed by each of the four components
+/// in turn as runs of a repeated byte and spans of literal bytes.
+fn encode_scanline_rle(scanline: &[[u8; 4]], out: &mut Vec<u8>) {
+    const MIN_RUN: usize = 4; // Shorter runs are cheaper as literals.
+    const MAX_RUN: usize = 127;
+    const MAX_LITERAL: usize = 128;
+
+    // The length of the run of identical bytes at the start of `data`.
+    let run_length = |data: &[u8]| {
+        data.iter()
+            .take(MAX_RUN)
+            .take_while(|byte| **byte == data[0])
+            .count()
+    };
+
+    let width = scanline.len();
+    out.extend_from_slice(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+    let mut component = Vec::with_capacity(width);
+    for chan in 0..4 {
+        component.clear();
+        component.extend(scanline.iter().map(|pixel| pixel[chan]));
+
+        let mut i = 0;
+        while i < width {
+            let run = run_length(&component[i..]);
+            if run >= MIN_RUN {
+                out.push(128 + run as u8);
+                out.push(component[i]);
+                i += run;
+            } else {
+                let mut end = i + 1;
+                while end < width
+                    && end - i < MAX_LITERAL
+                    && run_length(&component[end..]) < MIN_RUN
+                {
+                    end += 1;
+                }
+                out.push((end - i) as u8);
+                out.extend_from_slice(&component[i..end]);
+                i = end;
+            }
+        }
+    }
+}
+
+/// Header values are one line each, so newlines are replaced.
+fn header_value(text: &str) -> String {
+    text.trim().replace(['\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(options: &HdrOptions) -> String {
+        let mut out = Vec::new();
+        write_hdr_with_options(&mut out, &[[1.0, 0.5, 0.25]], 1, 1, options).unwrap();
+        let text = String::from_utf8_lossy(&out).into_owned();
+        text[..text.find("\n\n").unwrap()].into()
+    }
+
+    #[test]
+    fn default_header() {
+        assert_eq!(
+            header(&HdrOptions::default()),
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe"
+        );
+    }
+
+    #[test]
+    fn exposure_is_recorded() {
+        let text = header(&HdrOptions {
+            exposure: 4.0,
+            ..HdrOptions::default()
+        });
+        let line = text.lines().find(|l| l.starts_with("EXPOSURE=")).unwrap();
+        assert_eq!(line["EXPOSURE=".len()..].parse::<f32>().unwrap(), 4.0);
+    }
+
+    #[test]
+    fn metadata_lines() {
+        let text = header(&HdrOptions {
+            exposure: 1.0,
+            software: Some("HDRI Merge v0.4.0".into()),
+            camera: Some("SONY\nILCE-7SM3".into()),
+            projection: Some("fisheye_equisolid".into()),
+            run_length_encoding: false,
+        });
+        assert!(text.lines().any(|l| l == "SOFTWARE=HDRI Merge v0.4.0"));
+        assert!(text.lines().any(|l| l == "CAMERA=SONY ILCE-7SM3"));
+        assert!(text.lines().any(|l| l == "PROJECTION=fisheye_equisolid"));
+    }
+
+    /// Decodes the pixels of a file written by `write_hdr_with_options()`,
+    /// whether run-length encoded or not.
+    fn decode_pixels(data: &[u8], width: usize, height: usize) -> Vec<[u8; 4]> {
+        let header_end = data.windows(2).position(|w| w == b"\n\n").unwrap() + 2;
+        let mut i = header_end + data[header_end..].iter().position(|b| *b == b'\n').unwrap() + 1;
+
+        let mut pixels = Vec::new();
+        for _ in 0..height {
+            if data[i..(i + 2)] == [2, 2] {
+                assert_eq!(data[i + 2] as usize * 256 + data[i + 3] as usize, width);
+                i += 4;
+                let mut components = [Vec::new(), Vec::new(), Vec::new(), Vec::new()];
+                for component in components.iter_mut() {
+                    while component.len() < width {
+                        let count = data[i] as usize;
+                        if count > 128 {
+                            component.resize(component.len() + count - 128, data[i + 1]);
+                            i += 2;
+                        } else {
+                            component.extend_from_slice(&data[(i + 1)..(i + 1 + count)]);
+                            i += 1 + count;
+                        }
+                    }
+                }
+                let c = &components;
+                pixels.extend((0..width).map(|x| [c[0][x], c[1][x], c[2][x], c[3][x]]));
+            } else {
+                for _ in 0..width {
+                    pixels.push([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+                    i += 4;
+                }
+            }
+        }
+        assert_eq!(i, data.len());
+        pixels
+    }
+
+    #[test]
+    fn run_length_encoding() {
+        let (width, height) = (300, 3);
+        let image: Vec<[f32; 3]> = (0..(width * height))
+            .map(|i| {
+                // Long runs, short runs, and noise.
+                let x = i % width;
+                if x < 100 {
+                    [1.0, 0.5, 0.25]
+                } else if x < 200 {
+                    [(x / 3) as f32, 1.0, 1.0]
+                } else {
+                    [(x * 7919 % 61) as f32 * 0.1, x as f32, 0.0]
+                }
+            })
+            .collect();
+
+        let mut flat = Vec::new();
+        let mut rle = Vec::new();
+        let options = HdrOptions::default();
+        write_hdr_with_options(&mut flat, &image, width, height, &options).unwrap();
+        write_hdr_with_options(
+            &mut rle,
+            &image,
+            width,
+            height,
+            &HdrOptions {
+                run_length_encoding: true,
+                ..options
+            },
+        )
+        .unwrap();
+
+        assert!(rle.len() < flat.len());
+        assert_eq!(
+            decode_pixels(&rle, width, height),
+            decode_pixels(&flat, width, height)
+        );
+    }
+
+    #[test]
+    fn exposure_is_applied() {
+        let mut a = Vec::new();
+        let mut b = Vec::new();
+        write_hdr(&mut a, &[[0.5, 0.5, 0.5]], 1, 1, 2.0).unwrap();
+        write_hdr(&mut b, &[[1.0, 1.0, 1.0]], 1, 1, 1.0).unwrap();
+        assert_eq!(a[(a.len() - 4)..], b[(b.len() - 4)..]);
+    }
+}
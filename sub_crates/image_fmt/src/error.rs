@@ -24,6 +24,30 @@ impl std::fmt::Display for ReadError {
     }
 }
 
+#[derive(Debug)]
+pub enum WriteError {
+    IO(std::io::Error),
+    Encoder(String),
+}
+
+impl std::error::Error for WriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WriteError::IO(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::IO(e) => e.fmt(f),
+            WriteError::Encoder(e) => write!(f, "Encoder: {}", e),
+        }
+    }
+}
+
 //-------------------------------------------------------------
 // From impls.
 
@@ -73,3 +97,27 @@ impl From<jpeg_decoder::Error> for ReadError {
         }
     }
 }
+
+impl From<std::io::Error> for WriteError {
+    fn from(other: std::io::Error) -> Self {
+        Self::IO(other)
+    }
+}
+
+impl From<tiff::TiffError> for WriteError {
+    fn from(other: tiff::TiffError) -> Self {
+        match other {
+            tiff::TiffError::IoError(e) => Self::IO(e),
+            e => Self::Encoder(e.to_string()),
+        }
+    }
+}
+
+impl From<png::EncodingError> for WriteError {
+    fn from(other: png::EncodingError) -> Self {
+        match other {
+            png::EncodingError::IoError(e) => Self::IO(e),
+            e => Self::Encoder(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! A minimal OpenEXR writer: single-part scanline images, with 16-bit
+//! (half) or 32-bit float channels, uncompressed or with RLE or ZIP
+//! compression.
+
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::{error::WriteError, Image, ImageBuf};
+
+const MAGIC: u32 = 20000630;
+const VERSION: u32 = 2; // Single-part scanline file, no flags.
+const PIXEL_TYPE_HALF: i32 = 1;
+const PIXEL_TYPE_FLOAT: i32 = 2;
+const INCREASING_Y: u8 = 0;
+
+/// The compression methods that EXR files can be written with.  All
+/// of them are lossless.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ExrCompression {
+    None,
+    Rle,
+    Zip,
+}
+
+pub const EXR_COMPRESSIONS: &[ExrCompression] = &[
+    ExrCompression::None,
+    ExrCompression::Rle,
+    ExrCompression::Zip,
+];
+
+impl ExrCompression {
+    pub fn ui_text(&self) -> &'static str {
+        match *self {
+            ExrCompression::None => "None",
+            ExrCompression::Rle => "RLE",
+            ExrCompression::Zip => "ZIP",
+        }
+    }
+
+    /// The value of the `compression` header attribute.
+    fn code(&self) -> u8 {
+        match *self {
+            ExrCompression::None => 0,
+            ExrCompression::Rle => 1,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    /// The number of scanlines compressed together in each block.
+    fn lines_per_block(&self) -> usize {
+        match *self {
+            ExrCompression::None | ExrCompression::Rle => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+/// Options for writing EXR files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExrOptions {
+    /// Write 16-bit (half) floats instead of 32-bit floats.  Values
+    /// beyond the range of half floats become infinity.
+    pub half_float: bool,
+
+    pub compression: ExrCompression,
+
+    /// Additional string attributes to write in the header, as
+    /// `(name, value)`.
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Default for ExrOptions {
+    fn default() -> ExrOptions {
+        ExrOptions {
+            half_float: false,
+            compression: ExrCompression::None,
+            attributes: Vec::new(),
+        }
+    }
+}
+
+/// Writes float images as-is.  Integer images are normalized to
+/// [0.0, 1.0].
+pub fn write<W: Write + Seek>(
+    mut writer: W,
+    image: &Image,
+    options: &ExrOptions,
+) -> Result<(), WriteError> {
+    let float_data;
+    let (pixels, channel_count) = match image.data {
+        ImageBuf::RgbF32(ref d) => (d, 3),
+        ImageBuf::RgbaF32(ref d) => (d, 4),
+        ref data => {
+            float_data = data.clone().to_f32();
+            match float_data {
+                ImageBuf::RgbF32(ref d) => (d, 3),
+                ImageBuf::RgbaF32(ref d) => (d, 4),
+                _ => unreachable!(),
+            }
+        }
+    };
+    let (width, height) = (image.width(), image.height());
+    let (pixel_type, sample_size) = if options.half_float {
+        (PIXEL_TYPE_HALF, 2)
+    } else {
+        (PIXEL_TYPE_FLOAT, 4)
+    };
+
+    // Channels are stored in alphabetical order, as (name, index in the
+    // pixel data).
+    let channels: &[(&str, usize)] = if channel_count == 4 {
+        &[("A", 3), ("B", 2), ("G", 1), ("R", 0)]
+    } else {
+        &[("B", 2), ("G", 1), ("R", 0)]
+    };
+
+    // Header.
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC.to_le_bytes());
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    {
+        let mut chlist = Vec::new();
+        for (name, _) in channels.iter() {
+            chlist.extend_from_slice(name.as_bytes());
+            chlist.push(0);
+            chlist.extend_from_slice(&pixel_type.to_le_bytes());
+            chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved.
+            chlist.extend_from_slice(&1i32.to_le_bytes()); // x sampling.
+            chlist.extend_from_slice(&1i32.to_le_bytes()); // y sampling.
+        }
+        chlist.push(0);
+        attribute(&mut header, "channels", "chlist", &chlist);
+    }
+    attribute(
+        &mut header,
+        "compression",
+        "compression",
+        &[options.compression.code()],
+    );
+    let window: Vec<u8> = [0i32, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|n| n.to_le_bytes())
+        .collect();
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[INCREASING_Y]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    for (name, value) in options.attributes.iter() {
+        attribute(&mut header, name, "string", value.as_bytes());
+    }
+    header.push(0);
+
+    // The offset table comes before the blocks, but compressed block
+    // sizes aren't known until they're written.  So it's written as a
+    // placeholder first, and filled in at the end.
+    let start = writer.stream_position()?;
+    let lines_per_block = options.compression.lines_per_block();
+    let block_count = (height + lines_per_block - 1) / lines_per_block;
+    writer.write_all(&header)?;
+    writer.write_all(&vec![0u8; block_count * 8])?;
+
+    // Blocks.
+    let mut offsets = Vec::with_capacity(block_count);
+    let mut raw = Vec::new();
+    let mut block = Vec::new();
+    for block_i in 0..block_count {
+        let y_start = block_i * lines_per_block;
+        let y_end = (y_start + lines_per_block).min(height);
+
+        // Within a block, each scanline stores its channels one after
+        // another.
+        raw.clear();
+        for y in y_start..y_end {
+            let row = &pixels[(y * width * channel_count)..((y + 1) * width * channel_count)];
+            for &(_, chan) in channels.iter() {
+                for pixel in row.chunks_exact(channel_count) {
+                    if options.half_float {
+                        raw.extend_from_slice(&f32_to_half(pixel[chan]).to_le_bytes());
+                    } else {
+                        raw.extend_from_slice(&pixel[chan].to_le_bytes());
+                    }
+                }
+            }
+        }
+        debug_assert_eq!(
+            raw.len(),
+            (y_end - y_start) * width * channels.len() * sample_size
+        );
+
+        block.clear();
+        compress(&raw, options.compression, &mut block)?;
+
+        offsets.push(writer.stream_position()? - start);
+        writer.write_all(&(y_start as i32).to_le_bytes())?;
+        writer.write_all(&(block.len() as i32).to_le_bytes())?;
+        writer.write_all(&block)?;
+    }
+
+    // Fill in the offset table.
+    let end = writer.stream_position()?;
+    writer.seek(SeekFrom::Start(start + header.len() as u64))?;
+    for offset in offsets.iter() {
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+    writer.seek(SeekFrom::Start(end))?;
+
+    Ok(())
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, type_name: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(type_name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/// Compresses a block's data into `out`.
+///
+/// As the EXR spec requires, the uncompressed data is stored instead
+/// when compression doesn't make it smaller.
+fn compress(raw: &[u8], compression: ExrCompression, out: &mut Vec<u8>) -> std::io::Result<()> {
+    match compression {
+        ExrCompression::None => {}
+        ExrCompression::Rle => rle_compress(&predict(raw), out),
+        ExrCompression::Zip => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(&mut *out, flate2::Compression::default());
+            encoder.write_all(&predict(raw))?;
+            encoder.finish()?;
+        }
+    }
+    if compression == ExrCompression::None || out.len() >= raw.len() {
+        out.clear();
+        out.extend_from_slice(raw);
+    }
+    Ok(())
+}
+
+/// The preprocessing that both RLE and ZIP compression do first:
+/// interleave the low and high bytes into separate halves, and then
+/// store the difference of each byte from the previous one.
+fn predict(raw: &[u8]) -> Vec<u8> {
+    let half = (raw.len() + 1) / 2;
+    let mut data = vec![0u8; raw.len()];
+    for (i, byte) in raw.iter().enumerate() {
+        if i % 2 == 0 {
+            data[i / 2] = *byte;
+        } else {
+            data[half + i / 2] = *byte;
+        }
+    }
+
+    let mut prev = data.first().copied().unwrap_or(0);
+    for byte in data.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(prev).wrapping_add(128);
+        prev = current;
+    }
+    data
+}
+
+/// EXR's run-length encoding: a negative count followed by that many
+/// literal bytes, or a count `n` followed by a byte repeated `n + 1`
+/// times.
+fn rle_compress(data: &[u8], out: &mut Vec<u8>) {
+    const MIN_RUN: usize = 3;
+    const MAX_RUN: usize = 127;
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut run = 1;
+        while i + run < data.len() && data[i + run] == data[i] && run < MAX_RUN + 1 {
+            run += 1;
+        }
+
+        if run >= MIN_RUN {
+            out.push((run - 1) as u8);
+            out.push(data[i]);
+            i += run;
+        } else {
+            // Literal bytes up to the next run.
+            let mut end = i + 1;
+            while end < data.len()
+                && end - i < MAX_RUN
+                && !(end + 2 < data.len()
+                    && data[end] == data[end + 1]
+                    && data[end] == data[end + 2])
+            {
+                end += 1;
+            }
+            out.push((-((end - i) as i32)) as u8);
+            out.extend_from_slice(&data[i..end]);
+            i = end;
+        }
+    }
+}
+
+/// Converts to the bits of a 16-bit float, rounding to nearest even.
+fn f32_to_half(n: f32) -> u16 {
+    let bits = n.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    // Infinity and NaN.
+    if exponent == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        // Too big, so infinity.
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        // Subnormal or zero.
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let truncated = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let rounded = if remainder > halfway || (remainder == halfway && truncated & 1 == 1) {
+            truncated + 1
+        } else {
+            truncated
+        };
+        sign | rounded as u16
+    } else {
+        // Normal.  Rounding can carry into the exponent, which is
+        // still correct, up to and including overflowing to infinity.
+        let mut half = ((exponent as u32) << 10) | (mantissa >> 13);
+        let remainder = mantissa & 0x1fff;
+        if remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1) {
+            half += 1;
+        }
+        sign | half as u16
+    }
+}
@@ -1,11 +1,14 @@
 mod error;
+mod exr_fmt;
 mod jpeg_fmt;
+mod pfm_fmt;
 mod png_fmt;
 mod tiff_fmt;
 
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
 
-pub use error::ReadError;
+pub use error::{ReadError, WriteError};
+pub use exr_fmt::{ExrCompression, ExrOptions, EXR_COMPRESSIONS};
 
 #[derive(Debug, Clone)]
 pub struct Image {
@@ -93,14 +96,29 @@ pub enum ImageBuf {
 
     /// 16-bit unsigned RGBA channels.
     Rgba16(Vec<u16>),
+
+    /// 32-bit floating point RGB channels.  Unlike the integer formats,
+    /// these are unbounded.
+    RgbF32(Vec<f32>),
+
+    /// 32-bit floating point RGBA channels.
+    RgbaF32(Vec<f32>),
 }
 
 impl ImageBuf {
+    pub fn is_float(&self) -> bool {
+        use ImageBuf::*;
+        match *self {
+            RgbF32(_) | RgbaF32(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn to_rgb(self) -> Self {
         use ImageBuf::*;
 
         match self {
-            Rgb8(_) | Rgb16(_) => self,
+            Rgb8(_) | Rgb16(_) | RgbF32(_) => self,
 
             Rgba8(mut data) => {
                 let mut from_i = 4;
@@ -137,6 +155,24 @@ impl ImageBuf {
 
                 Rgb16(data)
             }
+
+            RgbaF32(mut data) => {
+                let mut from_i = 4;
+                let mut to_i = 3;
+
+                while from_i < data.len() {
+                    for _ in 0..3 {
+                        data[to_i] = data[from_i];
+                        from_i += 1;
+                        to_i += 1;
+                    }
+                    from_i += 1;
+                }
+
+                data.truncate(data.len() / 4 * 3);
+
+                RgbF32(data)
+            }
         }
     }
 
@@ -144,7 +180,7 @@ impl ImageBuf {
         use ImageBuf::*;
 
         match self {
-            Rgba8(_) | Rgba16(_) => self,
+            Rgba8(_) | Rgba16(_) | RgbaF32(_) => self,
 
             Rgb8(mut data) => {
                 let mut from_i = data.len() - 1;
@@ -181,15 +217,37 @@ impl ImageBuf {
 
                 Rgba16(data)
             }
+
+            RgbF32(mut data) => {
+                let mut from_i = data.len() - 1;
+                data.resize(data.len() / 3 * 4, 0.0);
+                let mut to_i = data.len() - 1;
+
+                while from_i > 2 {
+                    data[to_i] = 1.0;
+                    to_i -= 1;
+                    for _ in 0..3 {
+                        data[to_i] = data[from_i];
+                        from_i -= 1;
+                        to_i -= 1;
+                    }
+                }
+
+                RgbaF32(data)
+            }
         }
     }
 
+    /// Note: float values are clamped to [0.0, 1.0] when converting to
+    /// integer formats.
     pub fn to_8_bit(self) -> Self {
         use ImageBuf::*;
         match self {
             Rgb8(_) | Rgba8(_) => self,
             Rgb16(data) => Rgb8(data.iter().map(|&v| (v >> 8) as u8).collect()),
             Rgba16(data) => Rgba8(data.iter().map(|&v| (v >> 8) as u8).collect()),
+            RgbF32(data) => Rgb8(data.iter().map(|&v| float_to_int(v, 255.0) as u8).collect()),
+            RgbaF32(data) => Rgba8(data.iter().map(|&v| float_to_int(v, 255.0) as u8).collect()),
         }
     }
 
@@ -199,10 +257,37 @@ impl ImageBuf {
             Rgb16(_) | Rgba16(_) => self,
             Rgb8(data) => Rgb16(data.iter().map(|&v| (v as u16) << 8).collect()),
             Rgba8(data) => Rgba16(data.iter().map(|&v| (v as u16) << 8).collect()),
+            RgbF32(data) => Rgb16(
+                data.iter()
+                    .map(|&v| float_to_int(v, 65535.0) as u16)
+                    .collect(),
+            ),
+            RgbaF32(data) => Rgba16(
+                data.iter()
+                    .map(|&v| float_to_int(v, 65535.0) as u16)
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Note: integer values are normalized to [0.0, 1.0].
+    pub fn to_f32(self) -> Self {
+        use ImageBuf::*;
+        match self {
+            RgbF32(_) | RgbaF32(_) => self,
+            Rgb8(data) => RgbF32(data.iter().map(|&v| v as f32 / 255.0).collect()),
+            Rgba8(data) => RgbaF32(data.iter().map(|&v| v as f32 / 255.0).collect()),
+            Rgb16(data) => RgbF32(data.iter().map(|&v| v as f32 / 65535.0).collect()),
+            Rgba16(data) => RgbaF32(data.iter().map(|&v| v as f32 / 65535.0).collect()),
         }
     }
 }
 
+fn float_to_int(v: f32, max: f32) -> u32 {
+    // Note: NaN maps to zero.
+    (v.max(0.0).min(1.0) * max).round() as u32
+}
+
 pub fn load<R: Read + Seek>(mut reader: R) -> Result<Image, ReadError> {
     // Try tiff.
     match tiff_fmt::load(&mut reader) {
@@ -234,3 +319,52 @@ pub fn load<R: Read + Seek>(mut reader: R) -> Result<Image, ReadError> {
     // No formats matched.
     return Err(ReadError::UnknownFormat);
 }
+
+/// The file formats that images can be written in.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WriteFormat {
+    Png,
+    Tiff,
+    Exr,
+    Pfm,
+}
+
+impl WriteFormat {
+    pub fn ext(&self) -> &'static str {
+        match *self {
+            WriteFormat::Png => "png",
+            WriteFormat::Tiff => "tiff",
+            WriteFormat::Exr => "exr",
+            WriteFormat::Pfm => "pfm",
+        }
+    }
+}
+
+/// Writes an image in the given format.
+///
+/// The image's channels and bit depth are kept where the format
+/// supports them.  PNG writes float images as 16-bit, EXR writes
+/// everything as 32-bit float, and PFM writes RGB 32-bit float.
+///
+/// Use `write_exr()` for more control over EXR files.
+pub fn write<W: Write + Seek>(
+    writer: W,
+    image: &Image,
+    format: WriteFormat,
+) -> Result<(), WriteError> {
+    match format {
+        WriteFormat::Png => png_fmt::write(writer, image),
+        WriteFormat::Tiff => tiff_fmt::write(writer, image),
+        WriteFormat::Exr => exr_fmt::write(writer, image, &ExrOptions::default()),
+        WriteFormat::Pfm => pfm_fmt::write(writer, image),
+    }
+}
+
+/// Writes an EXR file with the given options.
+pub fn write_exr<W: Write + Seek>(
+    writer: W,
+    image: &Image,
+    options: &ExrOptions,
+) -> Result<(), WriteError> {
+    exr_fmt::write(writer, image, options)
+}
@@ -0,0 +1,43 @@
+//! A Portable Float Map (PFM) writer.  PFM is an uncompressed 32-bit
+//! float RGB format that many HDR tools can read.
+
+use std::io::Write;
+
+use crate::{error::WriteError, Image, ImageBuf};
+
+/// Writes float images as-is.  Integer images are normalized to
+/// [0.0, 1.0], and alpha is dropped since PFM doesn't support it.
+pub fn write<W: Write>(mut writer: W, image: &Image) -> Result<(), WriteError> {
+    let float_data;
+    let (pixels, channel_count) = match image.data {
+        ImageBuf::RgbF32(ref d) => (d, 3),
+        ImageBuf::RgbaF32(ref d) => (d, 4),
+        ref data => {
+            float_data = data.clone().to_f32();
+            match float_data {
+                ImageBuf::RgbF32(ref d) => (d, 3),
+                ImageBuf::RgbaF32(ref d) => (d, 4),
+                _ => unreachable!(),
+            }
+        }
+    };
+    let (width, height) = (image.width(), image.height());
+
+    // A negative scale means little-endian.
+    writer.write_all(format!("PF\n{} {}\n-1.0\n", width, height).as_bytes())?;
+
+    // Scanlines are stored bottom to top.
+    let mut line = Vec::with_capacity(width * 3 * 4);
+    for y in (0..height).rev() {
+        line.clear();
+        let row = &pixels[(y * width * channel_count)..((y + 1) * width * channel_count)];
+        for pixel in row.chunks_exact(channel_count) {
+            for n in pixel[..3].iter() {
+                line.extend_from_slice(&n.to_le_bytes());
+            }
+        }
+        writer.write_all(&line)?;
+    }
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
-use crate::{error::ReadError, Image, ImageBuf};
+use crate::{
+    error::{ReadError, WriteError},
+    Image, ImageBuf,
+};
 
 pub fn load<R: Read>(mut reader: R) -> Result<Image, ReadError> {
     let decoder = png::Decoder::new_with_limits(
@@ -107,3 +110,38 @@ pub fn load<R: Read>(mut reader: R) -> Result<Image, ReadError> {
         _ => return Err(ReadError::UnsupportedFeature),
     };
 }
+
+/// Writes 8 or 16-bit images as-is.  Float images are written as
+/// 16-bit.
+pub fn write<W: Write>(writer: W, image: &Image) -> Result<(), WriteError> {
+    let mut encoder = png::Encoder::new(writer, image.width() as u32, image.height() as u32);
+
+    use ImageBuf::*;
+    let float_data;
+    let data = match image.data {
+        RgbF32(_) | RgbaF32(_) => {
+            float_data = image.data.clone().to_16_bit();
+            &float_data
+        }
+        ref data => data,
+    };
+    let (color_type, bit_depth, bytes) = match *data {
+        Rgb8(ref d) => (png::ColorType::Rgb, png::BitDepth::Eight, d.clone()),
+        Rgba8(ref d) => (png::ColorType::Rgba, png::BitDepth::Eight, d.clone()),
+        Rgb16(ref d) => (png::ColorType::Rgb, png::BitDepth::Sixteen, be_bytes(d)),
+        Rgba16(ref d) => (png::ColorType::Rgba, png::BitDepth::Sixteen, be_bytes(d)),
+        RgbF32(_) | RgbaF32(_) => unreachable!(),
+    };
+    encoder.set_color(color_type);
+    encoder.set_depth(bit_depth);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&bytes)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+fn be_bytes(data: &[u16]) -> Vec<u8> {
+    data.iter().flat_map(|v| v.to_be_bytes()).collect()
+}
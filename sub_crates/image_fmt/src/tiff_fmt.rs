@@ -1,8 +1,11 @@
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
 
-use tiff::{decoder::DecodingResult, ColorType};
+use tiff::{decoder::DecodingResult, encoder::colortype, ColorType};
 
-use crate::{error::ReadError, Image, ImageBuf};
+use crate::{
+    error::{ReadError, WriteError},
+    Image, ImageBuf,
+};
 
 pub fn load<R: Read + Seek>(mut reader: R) -> Result<Image, ReadError> {
     let mut decoder =
@@ -33,6 +36,13 @@ pub fn load<R: Read + Seek>(mut reader: R) -> Result<Image, ReadError> {
                 data: ImageBuf::Rgb16(pixel_data),
             })
         }
+        (ColorType::RGB(_), DecodingResult::F32(pixel_data)) => {
+            assert_eq!(pixel_data.len(), pixel_count * 3);
+            Ok(Image {
+                dimensions: dimensions,
+                data: ImageBuf::RgbF32(pixel_data),
+            })
+        }
 
         //-------
         // RGBA.
@@ -50,6 +60,13 @@ pub fn load<R: Read + Seek>(mut reader: R) -> Result<Image, ReadError> {
                 data: ImageBuf::Rgba16(pixel_data),
             })
         }
+        (ColorType::RGBA(_), DecodingResult::F32(pixel_data)) => {
+            assert_eq!(pixel_data.len(), pixel_count * 4);
+            Ok(Image {
+                dimensions: dimensions,
+                data: ImageBuf::RgbaF32(pixel_data),
+            })
+        }
 
         //------------
         // Grayscale.
@@ -67,6 +84,13 @@ pub fn load<R: Read + Seek>(mut reader: R) -> Result<Image, ReadError> {
                 data: ImageBuf::Rgb16(pixel_data.iter().map(|&c| [c, c, c]).flatten().collect()),
             })
         }
+        (ColorType::Gray(_), DecodingResult::F32(pixel_data)) => {
+            assert_eq!(pixel_data.len(), pixel_count);
+            Ok(Image {
+                dimensions: dimensions,
+                data: ImageBuf::RgbF32(pixel_data.iter().map(|&c| [c, c, c]).flatten().collect()),
+            })
+        }
 
         //--------------------
         // Grayscale + alpha.
@@ -100,3 +124,21 @@ pub fn load<R: Read + Seek>(mut reader: R) -> Result<Image, ReadError> {
         _ => Err(ReadError::UnsupportedFeature),
     };
 }
+
+/// Writes images as-is, in any of the supported bit depths.
+pub fn write<W: Write + Seek>(mut writer: W, image: &Image) -> Result<(), WriteError> {
+    let mut encoder = tiff::encoder::TiffEncoder::new(&mut writer)?;
+    let (width, height) = (image.width() as u32, image.height() as u32);
+
+    use ImageBuf::*;
+    match image.data {
+        Rgb8(ref d) => encoder.write_image::<colortype::RGB8>(width, height, d)?,
+        Rgb16(ref d) => encoder.write_image::<colortype::RGB16>(width, height, d)?,
+        Rgba8(ref d) => encoder.write_image::<colortype::RGBA8>(width, height, d)?,
+        Rgba16(ref d) => encoder.write_image::<colortype::RGBA16>(width, height, d)?,
+        RgbF32(ref d) => encoder.write_image::<colortype::RGB32Float>(width, height, d)?,
+        RgbaF32(ref d) => encoder.write_image::<colortype::RGBA32Float>(width, height, d)?,
+    }
+
+    Ok(())
+}
@@ -1,23 +1,65 @@
-use std::collections::VecDeque;
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::{Arc, Once},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use scheduled_thread_pool::{JobHandle, ScheduledThreadPool};
+use scheduled_thread_pool::ScheduledThreadPool;
 use shared_data::Shared;
 
-/// A job queue that uses a single thread to process jobs.
+/// A job queue that processes jobs on a pool of worker threads.
+///
+/// Pending jobs are started highest priority first, and in the order
+/// they were added within a priority.  Jobs added with `add_job()` are
+/// exclusive: they run alone, and don't start ahead of earlier jobs or
+/// let later jobs start ahead of them, regardless of priority, so they
+/// can depend on everything added before them being finished.  The
+/// exception is low priority jobs (e.g. preview updates), which nothing
+/// may depend on and which higher priority jobs overtake.  Jobs added
+/// with `add_parallel_job()` can run alongside other parallel jobs, up
+/// to the number of workers.
+///
+/// Dependencies between jobs can also be given explicitly with
+/// `add_job_after()`, in which case canceling a job also cancels the
+/// jobs that depend on it.
 pub struct JobQueue {
-    runner: ScheduledThreadPool,
-    job_status: Shared<JobStatus>,
+    runner: Arc<ScheduledThreadPool>,
+    scheduler: Shared<Scheduler>,
+    common: Shared<Common>,
 }
 
 impl JobQueue {
+    /// Creates a job queue with a single worker, which runs all jobs
+    /// one at a time.
     pub fn new() -> JobQueue {
+        JobQueue::with_worker_count(1)
+    }
+
+    /// Creates a job queue with `worker_count` workers, for running
+    /// parallel jobs concurrently.
+    pub fn with_worker_count(worker_count: usize) -> JobQueue {
+        install_panic_hook();
+        let worker_count = worker_count.max(1);
         JobQueue {
-            runner: ScheduledThreadPool::new(1),
-            job_status: Shared::new(JobStatus {
-                jobs: VecDeque::new(),
-                job_progress: None,
+            runner: Arc::new(ScheduledThreadPool::new(worker_count)),
+            scheduler: Shared::new(Scheduler {
+                worker_count: worker_count,
+                pending: VecDeque::new(),
+                running: Vec::new(),
+                next_id: 0,
+                outcomes: HashMap::new(),
+                callbacks: Vec::new(),
+                finished_callbacks: Vec::new(),
+            }),
+            common: Shared::new(Common {
                 log: VecDeque::new(),
-                do_cancel: false,
+                log_file: None,
                 update_fn: None,
             }),
         }
@@ -32,157 +74,640 @@ impl JobQueue {
     /// - A job finishes.
     /// - Cancelation is requested.
     pub fn set_update_fn<F: Fn() + Send + 'static>(&mut self, cleanup_function: F) {
-        self.job_status.lock_mut().update_fn = Some(Box::new(cleanup_function));
+        self.common.lock_mut().update_fn = Some(Box::new(cleanup_function));
     }
 
-    pub fn add_job<F>(&self, name: &str, job: F)
+    /// Adds an exclusive job, which waits for all earlier jobs to
+    /// finish and runs alone.  If it's of higher priority, it may start
+    /// ahead of earlier low priority jobs.
+    pub fn add_job<F>(&self, name: &str, priority: Priority, job: F) -> JobId
     where
         F: FnOnce(&Shared<JobStatus>) + Send + 'static,
     {
-        let job_name1 = name.to_string();
-        let job_name2 = name.to_string();
-        let mut job_status = self.job_status.lock_mut();
-
-        // Add the job.
-        let local_job_status = self.job_status.clone_ref();
-        job_status.jobs.push_back((
-            self.runner.execute(move || {
-                let job_status = local_job_status;
-
-                if let Some(update_fn) = &job_status.lock().update_fn {
-                    update_fn();
-                }
+        self.push_job(name, priority, true, Vec::new(), Box::new(job))
+    }
 
-                // Actually run the job.
-                // TODO: this use of `AssertUndwindSafe` is a workaround
-                // for the way `egui::Context` works, because we pass it
-                // around frequently.
-                if let Err(_) =
-                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job(&job_status)))
-                {
-                    job_status
-                        .lock_mut()
-                        .log_error(format!("ERROR: job \"{}\" panicked!", job_name1));
-                }
+    /// Adds an exclusive job that depends on the jobs in `after`.
+    ///
+    /// It won't start until they've all finished, regardless of
+    /// priority, and is canceled if any of them are canceled or panic.
+    /// That includes jobs that have already finished that way, or whose
+    /// outcome has been released.
+    pub fn add_job_after<F>(&self, after: &[JobId], name: &str, priority: Priority, job: F) -> JobId
+    where
+        F: FnOnce(&Shared<JobStatus>) + Send + 'static,
+    {
+        self.push_job(name, priority, true, after.to_vec(), Box::new(job))
+    }
 
-                // Cleanup.
-                let mut job_status = job_status.lock_mut();
-                job_status.jobs.pop_front(); // This job.
-                job_status.do_cancel = false;
-                job_status.clear_progress();
-                if let Some(update_fn) = &job_status.update_fn {
-                    update_fn();
-                }
-            }),
-            job_name2,
-        ));
+    /// Adds a job that can run concurrently with other parallel jobs.
+    ///
+    /// It still waits for earlier exclusive jobs to finish, and later
+    /// exclusive jobs wait for it.
+    pub fn add_parallel_job<F>(&self, name: &str, priority: Priority, job: F) -> JobId
+    where
+        F: FnOnce(&Shared<JobStatus>) + Send + 'static,
+    {
+        self.push_job(name, priority, false, Vec::new(), Box::new(job))
+    }
+
+    fn push_job(
+        &self,
+        name: &str,
+        priority: Priority,
+        exclusive: bool,
+        after: Vec<JobId>,
+        job: Job,
+    ) -> JobId {
+        let id = {
+            let mut scheduler = self.scheduler.lock_mut();
+
+            // Supersede older low priority jobs of the same name.
+            if priority == Priority::Low {
+                scheduler.remove_pending(|job| job.priority == Priority::Low && job.name == name);
+                scheduler.cancel_running(|job| job.priority == Priority::Low && job.name == name);
+            }
+
+            let id = JobId(scheduler.next_id);
+            scheduler.next_id += 1;
+
+            // A dependency that has already finished without completing
+            // (or whose outcome isn't known) cancels the job right away.
+            let failed_dependency = after.iter().any(|dep| {
+                !scheduler.is_unfinished(*dep)
+                    && scheduler.outcomes.get(dep) != Some(&JobOutcome::Completed)
+            });
+            if failed_dependency {
+                scheduler.finish(id, JobOutcome::Canceled);
+            } else {
+                scheduler.pending.push_back(PendingJob {
+                    id: id,
+                    name: name.to_string(),
+                    priority: priority,
+                    exclusive: exclusive,
+                    after: after,
+                    job: job,
+                });
+            }
+            id
+        };
+        run_finished_callbacks(&self.scheduler);
+        dispatch(&self.runner, &self.scheduler, &self.common);
+        id
+    }
+
+    /// Whether the job has finished, either by running or by being
+    /// canceled before it could run.
+    pub fn is_finished(&self, id: JobId) -> bool {
+        let scheduler = self.scheduler.lock();
+        id.0 < scheduler.next_id && !scheduler.is_unfinished(id)
+    }
+
+    /// How the job finished, or `None` if it hasn't yet or if its
+    /// outcome has been released with `release()`.
+    pub fn outcome(&self, id: JobId) -> Option<JobOutcome> {
+        self.scheduler.lock().outcomes.get(&id).copied()
+    }
+
+    /// Forgets the outcome of a finished job.
+    ///
+    /// Outcomes are kept until released, so that `outcome()` and
+    /// `on_finish()` work no matter how soon the job finishes.  They're
+    /// small, so this only matters for queues that run a great many
+    /// jobs.  Unfinished jobs are unaffected.
+    pub fn release(&self, id: JobId) {
+        self.scheduler.lock_mut().outcomes.remove(&id);
+    }
+
+    /// Cancels the job, along with the jobs that depend on it.
+    ///
+    /// If it's running, it's marked for cancelation.
+    pub fn cancel_job(&self, id: JobId) {
+        {
+            let mut scheduler = self.scheduler.lock_mut();
+            scheduler.remove_pending(|job| job.id == id);
+            scheduler.cancel_running(|job| job.id == id);
+        }
+        run_finished_callbacks(&self.scheduler);
+        self.common.lock().notify();
+    }
+
+    /// Sets a function to be called with the job's outcome once it
+    /// finishes, e.g. to start something that isn't a job itself.
+    ///
+    /// It's called from whichever thread finishes the job, or
+    /// immediately if the job has already finished.  It must not block
+    /// waiting on the queue.
+    ///
+    /// Returns false, dropping the callback without calling it, if the
+    /// job's outcome has been released or the id isn't from this queue.
+    pub fn on_finish<F>(&self, id: JobId, callback: F) -> bool
+    where
+        F: FnOnce(JobOutcome) + Send + 'static,
+    {
+        let outcome = {
+            let mut scheduler = self.scheduler.lock_mut();
+            if scheduler.is_unfinished(id) {
+                scheduler.callbacks.push((id, Box::new(callback)));
+                return true;
+            }
+            match scheduler.outcomes.get(&id) {
+                Some(outcome) => *outcome,
+                None => return false,
+            }
+        };
+        callback(outcome);
+        true
+    }
+
+    /// The progress of the earliest-started running job that's
+    /// reporting progress.
+    pub fn progress(&self) -> Option<Progress> {
+        self.job_progress()
+            .into_iter()
+            .next()
+            .map(|(_, progress)| progress)
     }
 
-    pub fn progress(&self) -> Option<(String, f32)> {
-        self.job_status.lock().job_progress.clone()
+    /// The progress of all running jobs that are reporting progress,
+    /// as (job name, progress), in the order the jobs started.
+    pub fn job_progress(&self) -> Vec<(String, Progress)> {
+        self.scheduler
+            .lock()
+            .running
+            .iter()
+            .filter_map(|job| {
+                let status = job.status.lock();
+                let mut progress = status.progress.clone()?;
+                progress.eta = estimate_remaining(status.started.elapsed(), progress.ratio);
+                Some((job.name.clone(), progress))
+            })
+            .collect()
     }
 
+    /// The number of running and pending jobs.
     pub fn job_count(&self) -> usize {
-        self.job_status.lock().jobs.len()
+        let scheduler = self.scheduler.lock();
+        scheduler.running.len() + scheduler.pending.len()
     }
 
     pub fn cancel_all_jobs(&self) {
-        let mut job_status = self.job_status.lock_mut();
-        if !job_status.jobs.is_empty() {
+        {
+            let mut scheduler = self.scheduler.lock_mut();
+
             // Cancel all not-currently-running jobs.
-            while job_status.jobs.len() > 1 {
-                job_status.jobs.pop_back().unwrap().0.cancel()
-            }
+            scheduler.remove_pending(|_| true);
 
-            // Mark currently running job for cancelation.
-            job_status.do_cancel = true;
-        }
-        if let Some(update_fn) = &job_status.update_fn {
-            update_fn();
+            // Mark currently running jobs for cancelation.
+            scheduler.cancel_running(|_| true);
         }
+        run_finished_callbacks(&self.scheduler);
+        self.common.lock().notify();
     }
 
     /// Cancel all jobs that aren't currently running.
     pub fn cancel_pending_jobs(&self) {
-        let mut job_status = self.job_status.lock_mut();
-
-        // Cancel all not-currently-running jobs.
-        while job_status.jobs.len() > 1 {
-            job_status.jobs.pop_back().unwrap().0.cancel();
-        }
-        if let Some(update_fn) = &job_status.update_fn {
-            update_fn();
-        }
+        self.scheduler.lock_mut().remove_pending(|_| true);
+        run_finished_callbacks(&self.scheduler);
+        self.common.lock().notify();
     }
 
     pub fn cancel_jobs_with_name(&self, name: &str) {
-        let mut job_status = self.job_status.lock_mut();
-        if !job_status.jobs.is_empty() {
+        {
+            let mut scheduler = self.scheduler.lock_mut();
+
             // Cancel all not-currently-running jobs with name.
-            for i in 0..(job_status.jobs.len() - 1) {
-                let job_i = job_status.jobs.len() - 1 - i;
-                if job_status.jobs[job_i].1 == name {
-                    job_status.jobs.remove(job_i).unwrap().0.cancel();
-                }
-            }
+            scheduler.remove_pending(|job| job.name == name);
 
-            // Mark currently running job for cancelation if its name matches.
-            if job_status.jobs[0].1 == name {
-                job_status.do_cancel = true;
-            }
-        }
-        if let Some(update_fn) = &job_status.update_fn {
-            update_fn();
+            // Mark currently running jobs for cancelation if their name matches.
+            scheduler.cancel_running(|job| job.name == name);
         }
+        run_finished_callbacks(&self.scheduler);
+        self.common.lock().notify();
     }
 
     /// Cancel all jobs that aren't currently running that match the given name.
     pub fn cancel_pending_jobs_with_name(&self, name: &str) {
-        let mut job_status = self.job_status.lock_mut();
-        if job_status.jobs.len() > 1 {
-            // Cancel all not-currently-running jobs with name.
-            for i in 0..(job_status.jobs.len() - 1) {
-                let job_i = job_status.jobs.len() - 1 - i;
-                if job_status.jobs[job_i].1 == name {
-                    job_status.jobs.remove(job_i).unwrap().0.cancel();
-                }
-            }
-        }
-        if let Some(update_fn) = &job_status.update_fn {
-            update_fn();
+        self.scheduler
+            .lock_mut()
+            .remove_pending(|job| job.name == name);
+        run_finished_callbacks(&self.scheduler);
+        self.common.lock().notify();
+    }
+
+    /// Blocks until all jobs have finished.  For running jobs without
+    /// a UI, e.g. from the command line.
+    pub fn wait_for_all_jobs(&self) {
+        while self.job_count() > 0 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
         }
     }
 
+    /// Whether any running job has been marked for cancelation.
     pub fn is_canceling(&self) -> bool {
-        self.job_status.lock().do_cancel
+        self.scheduler
+            .lock()
+            .running
+            .iter()
+            .any(|job| job.status.lock().do_cancel)
     }
 
     pub fn log_count(&self) -> usize {
-        self.job_status.lock().log.len()
+        self.common.lock().log.len()
     }
 
     /// Index zero is the most recent error.
     pub fn get_log(&self, index: usize) -> Option<(String, LogLevel)> {
-        self.job_status.lock().log.get(index).map(|l| l.clone())
+        self.common
+            .lock()
+            .log
+            .get(index)
+            .map(|e| (e.message.clone(), e.level))
+    }
+
+    /// Like `get_log()`, but also includes when the entry was logged.
+    pub fn get_log_entry(&self, index: usize) -> Option<LogEntry> {
+        self.common.lock().log.get(index).cloned()
+    }
+
+    /// The whole log as text, oldest entry first, with one timestamped
+    /// line per entry followed by its details, if any.  For copying
+    /// into bug reports and the like.
+    pub fn log_text(&self) -> String {
+        let mut text = String::new();
+        for entry in self.common.lock().log.iter().rev() {
+            text.push_str(&entry.to_text());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Additionally writes all log entries from now on to the file at
+    /// `path`, so they survive the application closing or crashing.
+    ///
+    /// Entries are appended to the file, and it's rotated (to
+    /// "`path`.1", "`path`.2") when it grows beyond a limit, so it
+    /// can't grow without bound across sessions.
+    pub fn set_log_file(&mut self, path: &Path) -> std::io::Result<()> {
+        const MAX_LOG_FILE_SIZE: u64 = 1 << 20;
+        const ROTATED_LOG_FILES: usize = 2;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        if std::fs::metadata(path).map(|m| m.len()).unwrap_or(0) > MAX_LOG_FILE_SIZE {
+            let rotated = |n: usize| {
+                let mut p = path.as_os_str().to_owned();
+                p.push(format!(".{}", n));
+                p
+            };
+            let _ = std::fs::remove_file(rotated(ROTATED_LOG_FILES));
+            for n in (1..ROTATED_LOG_FILES).rev() {
+                let _ = std::fs::rename(rotated(n), rotated(n + 1));
+            }
+            std::fs::rename(path, rotated(1))?;
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(
+            file,
+            "---- Session started {} ----",
+            format_time(SystemTime::now())
+        )?;
+        self.common.lock_mut().log_file = Some(file);
+
+        Ok(())
     }
 
     pub fn clear_log(&self) {
-        self.job_status.lock_mut().log.clear()
+        self.common.lock_mut().log.clear()
     }
 
     /// Convenience function for logging errors outside of a job.
     pub fn log_error(&self, message: String) {
-        self.job_status.lock_mut().log_error(message);
+        self.common.lock_mut().log(message, LogLevel::Error);
     }
 
     /// Convenience function for logging warnings outside of a job.
     pub fn log_warning(&self, message: String) {
-        self.job_status.lock_mut().log_warning(message);
+        self.common.lock_mut().log(message, LogLevel::Warning);
     }
 
     /// Convenience function for logging notes outside of a job.
     pub fn log_note(&self, message: String) {
-        self.job_status.lock_mut().log_note(message);
+        self.common.lock_mut().log(message, LogLevel::Note);
+    }
+}
+
+/// How urgently a job should be run.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Priority {
+    /// For jobs that refresh previews and the like.  Adding a low
+    /// priority job supersedes any low priority jobs with the same name:
+    /// pending ones are removed, and running ones are marked for
+    /// cancelation.  Since higher priority jobs start ahead of them,
+    /// nothing should depend on them.
+    Low,
+    Normal,
+    /// For jobs the user is waiting on, such as exports.
+    High,
+}
+
+/// Identifies a job added to a `JobQueue`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct JobId(usize);
+
+/// How a job finished.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum JobOutcome {
+    Completed,
+    /// Either removed before it started, or marked for cancelation
+    /// while running.
+    Canceled,
+    Panicked,
+}
+
+type Job = Box<dyn FnOnce(&Shared<JobStatus>) + Send + 'static>;
+type FinishCallback = Box<dyn FnOnce(JobOutcome) + Send + 'static>;
+
+struct PendingJob {
+    id: JobId,
+    name: String,
+    priority: Priority,
+    exclusive: bool,
+    after: Vec<JobId>, // Jobs this job depends on.
+    job: Job,
+}
+
+struct RunningJob {
+    id: JobId,
+    name: String,
+    priority: Priority,
+    exclusive: bool,
+    status: Shared<JobStatus>,
+}
+
+/// The jobs of a queue.
+///
+/// To avoid deadlocks, locks are always taken in the order scheduler,
+/// job status, common.
+struct Scheduler {
+    worker_count: usize,
+    pending: VecDeque<PendingJob>,
+    running: Vec<RunningJob>,
+    next_id: usize,
+    outcomes: HashMap<JobId, JobOutcome>, // Of finished jobs, until released.
+    callbacks: Vec<(JobId, FinishCallback)>,
+
+    // Callbacks of finished jobs, to be called once the scheduler is
+    // unlocked.
+    finished_callbacks: Vec<(FinishCallback, JobOutcome)>,
+}
+
+impl Scheduler {
+    /// Records the outcome of a job that's been removed from the queue.
+    fn finish(&mut self, id: JobId, outcome: JobOutcome) {
+        self.outcomes.insert(id, outcome);
+        let mut i = 0;
+        while i < self.callbacks.len() {
+            if self.callbacks[i].0 == id {
+                let (_, callback) = self.callbacks.remove(i);
+                self.finished_callbacks.push((callback, outcome));
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Removes the pending jobs that match `pred`, along with the
+    /// pending jobs that depend on them, directly or indirectly.
+    fn remove_pending<F: Fn(&PendingJob) -> bool>(&mut self, pred: F) {
+        let mut removed = Vec::new();
+        self.pending.retain(|job| {
+            if pred(job) {
+                removed.push(job.id);
+                false
+            } else {
+                true
+            }
+        });
+        for id in removed.iter() {
+            self.finish(*id, JobOutcome::Canceled);
+        }
+        self.remove_dependents(removed);
+    }
+
+    /// Marks the running jobs that match `pred` for cancelation, and
+    /// removes the pending jobs that depend on them.
+    fn cancel_running<F: Fn(&RunningJob) -> bool>(&mut self, pred: F) {
+        let mut canceled = Vec::new();
+        for job in self.running.iter().filter(|job| pred(job)) {
+            job.status.lock_mut().do_cancel = true;
+            canceled.push(job.id);
+        }
+        self.remove_dependents(canceled);
+    }
+
+    /// Removes the pending jobs that depend on the jobs in `ids`,
+    /// directly or indirectly.
+    fn remove_dependents(&mut self, mut ids: Vec<JobId>) {
+        while !ids.is_empty() {
+            let mut removed = Vec::new();
+            self.pending.retain(|job| {
+                if job.after.iter().any(|id| ids.contains(id)) {
+                    removed.push(job.id);
+                    false
+                } else {
+                    true
+                }
+            });
+            for id in removed.iter() {
+                self.finish(*id, JobOutcome::Canceled);
+            }
+            ids = removed;
+        }
+    }
+
+    /// Whether the job is pending or running.
+    fn is_unfinished(&self, id: JobId) -> bool {
+        self.pending.iter().any(|job| job.id == id) || self.running.iter().any(|job| job.id == id)
+    }
+
+    /// Whether the job's dependencies have all finished.
+    fn is_ready(&self, job: &PendingJob) -> bool {
+        job.after.iter().all(|id| !self.is_unfinished(*id))
+    }
+}
+
+/// Calls the callbacks of jobs that have finished since the last call.
+fn run_finished_callbacks(scheduler: &Shared<Scheduler>) {
+    let callbacks = std::mem::take(&mut scheduler.lock_mut().finished_callbacks);
+    for (callback, outcome) in callbacks {
+        callback(outcome);
+    }
+}
+
+/// State shared between a queue and all of its jobs.
+struct Common {
+    log: VecDeque<LogEntry>,
+    log_file: Option<File>,
+    update_fn: Option<Box<dyn Fn() + Send + 'static>>,
+}
+
+impl Common {
+    fn notify(&self) {
+        if let Some(update_fn) = &self.update_fn {
+            update_fn();
+        }
+    }
+
+    fn log(&mut self, message: String, level: LogLevel) {
+        self.log_with_details(message, level, None);
+    }
+
+    fn log_with_details(&mut self, message: String, level: LogLevel, details: Option<String>) {
+        let entry = LogEntry {
+            message: message,
+            level: level,
+            time: SystemTime::now(),
+            details: details,
+        };
+
+        // Failing to write the log file shouldn't take anything else
+        // down with it, so it's just dropped.
+        if let Some(file) = &mut self.log_file {
+            if writeln!(file, "{}", entry.to_text()).is_err() {
+                self.log_file = None;
+            }
+        }
+
+        self.log.push_front(entry);
+        self.notify();
+    }
+}
+
+/// Starts as many pending jobs as can currently run.
+fn dispatch(
+    runner: &Arc<ScheduledThreadPool>,
+    scheduler: &Shared<Scheduler>,
+    common: &Shared<Common>,
+) {
+    let mut sched = scheduler.lock_mut();
+    loop {
+        // The earliest added of the highest priority jobs that are
+        // ready.  Lower priority jobs don't start ahead of it, even if
+        // they could.
+        //
+        // Priority only reorders independent jobs, though: a job can't
+        // start ahead of an earlier job that isn't low priority if
+        // either of them is exclusive, since it may depend on that job.
+        let first_job = sched
+            .pending
+            .iter()
+            .position(|job| job.priority != Priority::Low)
+            .unwrap_or(sched.pending.len());
+        let first_exclusive_job = sched
+            .pending
+            .iter()
+            .position(|job| job.exclusive && job.priority != Priority::Low)
+            .unwrap_or(sched.pending.len());
+        let next = (0..sched.pending.len())
+            .filter(|i| {
+                *i <= first_exclusive_job && (*i <= first_job || !sched.pending[*i].exclusive)
+            })
+            .filter(|i| sched.is_ready(&sched.pending[*i]))
+            .fold(None, |best: Option<usize>, i| match best {
+                Some(b) if sched.pending[b].priority >= sched.pending[i].priority => Some(b),
+                _ => Some(i),
+            });
+
+        let can_start = match next.map(|i| &sched.pending[i]) {
+            None => false,
+            Some(job) if job.exclusive => sched.running.is_empty(),
+            Some(_) => {
+                sched.running.len() < sched.worker_count
+                    && !sched.running.iter().any(|job| job.exclusive)
+            }
+        };
+        if !can_start {
+            break;
+        }
+
+        let PendingJob {
+            id,
+            name,
+            priority,
+            exclusive,
+            job,
+            ..
+        } = sched.pending.remove(next.unwrap()).unwrap();
+        let status = Shared::new(JobStatus {
+            progress: None,
+            started: Instant::now(),
+            do_cancel: false,
+            common: common.clone_ref(),
+        });
+        sched.running.push(RunningJob {
+            id: id,
+            name: name.clone(),
+            priority: priority,
+            exclusive: exclusive,
+            status: status.clone_ref(),
+        });
+
+        let runner2 = runner.clone();
+        let scheduler = scheduler.clone_ref();
+        let common = common.clone_ref();
+        runner.execute(move || {
+            common.lock().notify();
+
+            // Actually run the job.
+            // TODO: this use of `AssertUndwindSafe` is a workaround
+            // for the way `egui::Context` works, because we pass it
+            // around frequently.
+            LAST_PANIC.with(|last| last.borrow_mut().take());
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job(&status)));
+            let panicked = result.is_err();
+            if let Err(payload) = result {
+                let (location, backtrace) = LAST_PANIC
+                    .with(|last| last.borrow_mut().take())
+                    .map(|p| (p.location, Some(p.backtrace)))
+                    .unwrap_or((None, None));
+                let mut message = format!("Job \"{}\" panicked", name);
+                if let Some(location) = location {
+                    message.push_str(&format!(" at {}", location));
+                }
+                message.push_str(&format!(": {}", panic_message(&*payload)));
+                common
+                    .lock_mut()
+                    .log_with_details(message, LogLevel::Error, backtrace);
+            }
+
+            // Cleanup.  Jobs that depend on this one only run if it
+            // finished normally.
+            {
+                let outcome = if panicked {
+                    JobOutcome::Panicked
+                } else if status.lock().is_canceled() {
+                    JobOutcome::Canceled
+                } else {
+                    JobOutcome::Completed
+                };
+                let mut scheduler = scheduler.lock_mut();
+                scheduler.running.retain(|job| job.id != id);
+                scheduler.finish(id, outcome);
+                if outcome != JobOutcome::Completed {
+                    scheduler.remove_dependents(vec![id]);
+                }
+            }
+            run_finished_callbacks(&scheduler);
+            dispatch(&runner2, &scheduler, &common);
+            common.lock().notify();
+        });
     }
 }
 
@@ -193,12 +718,156 @@ pub enum LogLevel {
     Note,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub message: String,
+    pub level: LogLevel,
+    pub time: SystemTime,
+
+    /// Further multi-line information, such as the backtrace of a
+    /// panic.
+    pub details: Option<String>,
+}
+
+impl LogEntry {
+    /// The entry as a single line of text, e.g.
+    /// "[2024-03-01 14:05:09 UTC] ERROR: Unable to load file".
+    pub fn to_line(&self) -> String {
+        let level = match self.level {
+            LogLevel::Error => "ERROR: ",
+            LogLevel::Warning => "WARNING: ",
+            LogLevel::Note => "",
+        };
+        format!("[{}] {}{}", format_time(self.time), level, self.message)
+    }
+
+    /// Like `to_line()`, but followed by the entry's details, if any,
+    /// indented on the lines after it.
+    pub fn to_text(&self) -> String {
+        let mut text = self.to_line();
+        if let Some(details) = &self.details {
+            for line in details.lines() {
+                text.push_str("\n    ");
+                text.push_str(line);
+            }
+        }
+        text
+    }
+}
+
+/// What's known about a panic at the time it happens, which is the only
+/// time its backtrace can be captured.
+struct CapturedPanic {
+    location: Option<String>,
+    backtrace: String,
+}
+
+thread_local! {
+    /// The most recent panic on this thread.
+    static LAST_PANIC: RefCell<Option<CapturedPanic>> = const { RefCell::new(None) };
+}
+
+/// Installs a panic hook that records the location and backtrace of
+/// panics for the job panic handler, before calling the previous hook.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let captured = CapturedPanic {
+                location: info
+                    .location()
+                    .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+                backtrace: Backtrace::force_capture().to_string(),
+            };
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(captured));
+            previous_hook(info);
+        }));
+    });
+}
+
+/// The message of a panic, from its payload.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
+
+/// Formats a time as "YYYY-MM-DD HH:MM:SS UTC".
+fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // Days since the epoch to a civil date, from Howard Hinnant's
+    // `civil_from_days()`.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+/// The progress of a running job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// What the job is doing.
+    pub text: String,
+
+    /// How far along the whole job is, from 0.0 to 1.0.
+    pub ratio: f32,
+
+    /// The current sub-step, as (text, ratio), for jobs made up of
+    /// several steps.  The ratio is of the sub-step itself.
+    pub sub_step: Option<(String, f32)>,
+
+    /// (completed, total) items of the current step or sub-step, for
+    /// jobs that process a list of things.
+    pub items: Option<(usize, usize)>,
+
+    /// Estimated time remaining, extrapolated from how long the job has
+    /// taken so far and its overall ratio.  `None` until there's enough
+    /// to go on.
+    pub eta: Option<Duration>,
+}
+
+/// Estimates the time remaining of a job from its elapsed time and
+/// overall progress ratio.
+fn estimate_remaining(elapsed: Duration, ratio: f32) -> Option<Duration> {
+    // Too early to tell, or done.
+    if elapsed < Duration::from_secs(2) || !(0.01..1.0).contains(&ratio) {
+        return None;
+    }
+    Some(elapsed.mul_f64((1.0 - ratio as f64) / ratio as f64))
+}
+
+/// The status of a single job, which the job uses to report progress,
+/// log messages, and check for cancelation.
 pub struct JobStatus {
-    jobs: VecDeque<(JobHandle, String)>, // (handle, name)
-    job_progress: Option<(String, f32)>,
-    log: VecDeque<(String, LogLevel)>,
+    progress: Option<Progress>,
+    started: Instant,
     do_cancel: bool,
-    update_fn: Option<Box<dyn Fn() + Send + 'static>>,
+    common: Shared<Common>,
 }
 
 impl JobStatus {
@@ -206,38 +875,504 @@ impl JobStatus {
         self.do_cancel
     }
 
+    /// Sets the job's current step and overall progress ratio.
+    ///
+    /// Changing the text starts a new step, which clears the sub-step
+    /// and item count.
     pub fn set_progress(&mut self, text: String, ratio: f32) {
-        self.job_progress = Some((text, ratio));
-        if let Some(update_fn) = &self.update_fn {
-            update_fn();
+        match self.progress {
+            Some(ref mut progress) if progress.text == text => {
+                progress.ratio = ratio;
+            }
+            _ => {
+                self.progress = Some(Progress {
+                    text: text,
+                    ratio: ratio,
+                    sub_step: None,
+                    items: None,
+                    eta: None,
+                });
+            }
         }
+        self.common.lock().notify();
     }
 
-    pub fn clear_progress(&mut self) {
-        self.job_progress = None;
-        if let Some(update_fn) = &self.update_fn {
-            update_fn();
+    /// Sets the current sub-step of the job's current step, and how far
+    /// along the sub-step is.
+    ///
+    /// Changing the text starts a new sub-step, which clears the item
+    /// count.  Does nothing if no step has been set.
+    pub fn set_sub_progress(&mut self, text: String, ratio: f32) {
+        if let Some(ref mut progress) = self.progress {
+            if progress.sub_step.as_ref().map(|(t, _)| t != &text) != Some(false) {
+                progress.items = None;
+            }
+            progress.sub_step = Some((text, ratio));
+            self.common.lock().notify();
         }
     }
 
-    pub fn log_error(&mut self, message: String) {
-        self.log.push_front((message, LogLevel::Error));
-        if let Some(update_fn) = &self.update_fn {
-            update_fn();
+    /// Sets how many items of the current step or sub-step are done.
+    /// Does nothing if no step has been set.
+    pub fn set_item_count(&mut self, completed: usize, total: usize) {
+        if let Some(ref mut progress) = self.progress {
+            progress.items = Some((completed, total));
+            self.common.lock().notify();
         }
     }
 
+    pub fn clear_progress(&mut self) {
+        self.progress = None;
+        self.common.lock().notify();
+    }
+
+    pub fn log_error(&mut self, message: String) {
+        self.common.lock_mut().log(message, LogLevel::Error);
+    }
+
     pub fn log_warning(&mut self, message: String) {
-        self.log.push_front((message, LogLevel::Warning));
-        if let Some(update_fn) = &self.update_fn {
-            update_fn();
-        }
+        self.common.lock_mut().log(message, LogLevel::Warning);
     }
 
     pub fn log_note(&mut self, message: String) {
-        self.log.push_front((message, LogLevel::Note));
-        if let Some(update_fn) = &self.update_fn {
-            update_fn();
+        self.common.lock_mut().log(message, LogLevel::Note);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Runs a job for each entry of `exclusive`, and returns the most
+    /// jobs that were ever running at once.
+    fn max_concurrency(queue: &JobQueue, exclusive: &[bool]) -> usize {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+        for &exclusive in exclusive.iter() {
+            let running = running.clone();
+            let max = max.clone();
+            let job = move |_: &Shared<JobStatus>| {
+                let n = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max.fetch_max(n, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                running.fetch_sub(1, Ordering::SeqCst);
+            };
+            if exclusive {
+                queue.add_job("Test", Priority::Normal, job);
+            } else {
+                queue.add_parallel_job("Test", Priority::Normal, job);
+            }
+        }
+        queue.wait_for_all_jobs();
+        max.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn parallel_jobs_01() {
+        let queue = JobQueue::with_worker_count(3);
+        assert_eq!(max_concurrency(&queue, &[false, false, false]), 3);
+    }
+
+    #[test]
+    fn exclusive_jobs_01() {
+        let queue = JobQueue::with_worker_count(3);
+        assert_eq!(max_concurrency(&queue, &[false, true, false, true]), 1);
+    }
+
+    #[test]
+    fn progress_and_cancel_01() {
+        let queue = JobQueue::with_worker_count(2);
+        for name in ["A", "B"].iter() {
+            queue.add_parallel_job(name, Priority::Normal, |status| {
+                status.lock_mut().set_progress("Working".into(), 0.5);
+                while !status.lock().is_canceled() {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+                status.lock_mut().log_note("Canceled".into());
+            });
+        }
+        while queue.job_progress().len() < 2 {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        queue.cancel_jobs_with_name("A");
+        while queue.job_count() > 1 {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(queue.job_progress()[0].0, "B");
+
+        queue.cancel_all_jobs();
+        queue.wait_for_all_jobs();
+        assert_eq!(queue.log_count(), 2);
+    }
+
+    #[test]
+    fn priority_01() {
+        let queue = JobQueue::new();
+        let order = Shared::new(Vec::new());
+
+        // Keep the worker busy until the rest are added.
+        let (start, wait) = std::sync::mpsc::channel::<()>();
+        queue.add_job("Wait", Priority::Normal, move |_| {
+            let _ = wait.recv();
+        });
+        for (name, priority, exclusive) in [
+            ("Preview", Priority::Low, false),
+            ("Normal", Priority::Normal, true),
+            ("Preview", Priority::Low, false),
+            ("Export", Priority::High, true),
+            ("Parallel", Priority::High, false),
+        ]
+        .iter()
+        {
+            let order = order.clone_ref();
+            let job = move |_: &Shared<JobStatus>| order.lock_mut().push(*name);
+            if *exclusive {
+                queue.add_job(name, *priority, job);
+            } else {
+                queue.add_parallel_job(name, *priority, job);
+            }
+        }
+        start.send(()).unwrap();
+        queue.wait_for_all_jobs();
+
+        // The export overtakes the preview, but not the earlier normal
+        // priority job it may depend on.
+        assert_eq!(
+            *order.lock(),
+            vec!["Normal", "Export", "Parallel", "Preview"]
+        );
+    }
+
+    #[test]
+    fn progress_steps_01() {
+        let queue = JobQueue::new();
+        let progress = Shared::new(Vec::new());
+        let progress2 = progress.clone_ref();
+        queue.add_job("Steps", Priority::Normal, move |status| {
+            let mut status = status.lock_mut();
+            let record = |status: &JobStatus| progress2.lock_mut().push(status.progress.clone());
+
+            status.set_progress("Step 1".into(), 0.0);
+            status.set_sub_progress("Sub-step".into(), 0.5);
+            status.set_item_count(1, 2);
+            record(&status);
+            status.set_progress("Step 1".into(), 0.25);
+            record(&status);
+            status.set_sub_progress("Another sub-step".into(), 0.0);
+            record(&status);
+            status.set_progress("Step 2".into(), 0.5);
+            record(&status);
+        });
+        queue.wait_for_all_jobs();
+
+        let progress: Vec<_> = progress.lock().iter().map(|p| p.clone().unwrap()).collect();
+        assert_eq!(progress[0].sub_step, Some(("Sub-step".into(), 0.5)));
+        assert_eq!(progress[0].items, Some((1, 2)));
+        assert_eq!(progress[1].ratio, 0.25);
+        assert_eq!(progress[1].items, Some((1, 2)));
+        assert_eq!(progress[2].items, None);
+        assert_eq!(progress[3].sub_step, None);
+    }
+
+    #[test]
+    fn estimate_remaining_01() {
+        assert_eq!(estimate_remaining(Duration::from_secs(1), 0.5), None);
+        assert_eq!(estimate_remaining(Duration::from_secs(10), 0.0), None);
+        assert_eq!(
+            estimate_remaining(Duration::from_secs(10), 0.25),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn panic_01() {
+        let queue = JobQueue::new();
+        queue.add_job("Test", Priority::Normal, |_| {
+            panic!("Read-only file system");
+        });
+        queue.wait_for_all_jobs();
+
+        let entry = queue.get_log_entry(0).unwrap();
+        assert_eq!(entry.level, LogLevel::Error);
+        assert!(entry.message.starts_with("Job \"Test\" panicked at "));
+        assert!(entry.message.ends_with(": Read-only file system"));
+        assert!(entry.details.is_some());
+    }
+
+    #[test]
+    fn format_time_01() {
+        assert_eq!(format_time(UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+        assert_eq!(
+            format_time(UNIX_EPOCH + Duration::from_secs(1709301909)),
+            "2024-03-01 14:05:09 UTC"
+        );
+    }
+
+    #[test]
+    fn log_file_01() {
+        let path = std::env::temp_dir().join(format!("job_queue_log_{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut queue = JobQueue::new();
+        queue.set_log_file(&path).unwrap();
+        queue.add_job("Test", Priority::Normal, |status| {
+            status.lock_mut().log_warning("From a job".into());
+        });
+        queue.wait_for_all_jobs();
+        queue.log_error("Outside a job".into());
+        drop(queue);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("---- Session started"));
+        assert!(lines[1].ends_with("] WARNING: From a job"));
+        assert!(lines[2].ends_with("] ERROR: Outside a job"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn dependencies_01() {
+        let queue = JobQueue::new();
+        let order = Shared::new(Vec::new());
+        let push = |name: &'static str| {
+            let order = order.clone_ref();
+            move |_: &Shared<JobStatus>| order.lock_mut().push(name)
+        };
+
+        // Keep the worker busy while the rest are added.
+        let first = queue.add_job("First", Priority::Normal, |_| {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        });
+        let parent = queue.add_job("Parent", Priority::Normal, push("Parent"));
+        queue.add_job_after(&[first, parent], "Child", Priority::High, push("Child"));
+        queue.add_job("Other", Priority::Normal, push("Other"));
+        queue.wait_for_all_jobs();
+
+        // The child runs ahead of "Other" because of its priority, but
+        // not ahead of its parent.
+        assert_eq!(*order.lock(), vec!["Parent", "Child", "Other"]);
+    }
+
+    #[test]
+    fn dependencies_02() {
+        let queue = JobQueue::new();
+        let order = Shared::new(Vec::new());
+        let push = |name: &'static str| {
+            let order = order.clone_ref();
+            move |_: &Shared<JobStatus>| order.lock_mut().push(name)
+        };
+
+        let parent = queue.add_job("Parent", Priority::Normal, |status| {
+            while !status.lock().is_canceled() {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+        });
+        let child = queue.add_job_after(&[parent], "Child", Priority::Normal, push("Child"));
+        queue.add_job_after(&[child], "Grandchild", Priority::Normal, push("Grandchild"));
+        queue.add_job("Other", Priority::Normal, push("Other"));
+
+        queue.cancel_jobs_with_name("Parent");
+        queue.wait_for_all_jobs();
+
+        assert_eq!(*order.lock(), vec!["Other"]);
+    }
+
+    #[test]
+    fn dependencies_03() {
+        // Dependencies that have already finished without completing.
+        let queue = JobQueue::new();
+        let ran = Shared::new(Vec::new());
+        let push = |name: &'static str| {
+            let ran = ran.clone_ref();
+            move |_: &Shared<JobStatus>| ran.lock_mut().push(name)
+        };
+
+        let gate = Shared::new(false);
+        let waiting = queue.add_job("Waiting", Priority::Normal, {
+            let gate = gate.clone_ref();
+            move |_| {
+                while !*gate.lock() {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+            }
+        });
+        let canceled = queue.add_job("Canceled", Priority::Normal, push("Canceled"));
+        queue.cancel_job(canceled);
+        *gate.lock_mut() = true;
+        let panics = queue.add_job("Panics", Priority::Normal, |_| panic!());
+        let completes = queue.add_job("Completes", Priority::Normal, push("Completes"));
+        queue.wait_for_all_jobs();
+        while !queue.is_finished(panics) || !queue.is_finished(completes) {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let released = queue.add_job("Released", Priority::Normal, push("Released"));
+        queue.wait_for_all_jobs();
+        while !queue.is_finished(released) {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        queue.release(released);
+
+        let children = [
+            queue.add_job_after(&[canceled], "Child", Priority::Normal, push("Child")),
+            queue.add_job_after(
+                &[completes, panics],
+                "Child",
+                Priority::Normal,
+                push("Child"),
+            ),
+            queue.add_job_after(&[released], "Child", Priority::Normal, push("Child")),
+            queue.add_job_after(
+                &[JobId(usize::MAX)],
+                "Child",
+                Priority::Normal,
+                push("Child"),
+            ),
+        ];
+        for child in children.iter() {
+            assert!(queue.is_finished(*child));
+            assert_eq!(queue.outcome(*child), Some(JobOutcome::Canceled));
+        }
+
+        // Only completed dependencies let the job run.
+        let child = queue.add_job_after(
+            &[waiting, completes],
+            "Child",
+            Priority::Normal,
+            push("Child"),
+        );
+        queue.wait_for_all_jobs();
+        while !queue.is_finished(child) {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(queue.outcome(child), Some(JobOutcome::Completed));
+        assert_eq!(*ran.lock(), vec!["Completes", "Released", "Child"]);
+    }
+
+    #[test]
+    fn outcomes_01() {
+        let queue = JobQueue::new();
+        let outcomes = Shared::new(Vec::new());
+        let record = |name: &'static str| {
+            let outcomes = outcomes.clone_ref();
+            move |outcome| outcomes.lock_mut().push((name, outcome))
+        };
+
+        let waiting = queue.add_job("Waiting", Priority::Normal, |status| {
+            while !status.lock().is_canceled() {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+        });
+        let pending = queue.add_job("Pending", Priority::Normal, |_| {});
+        let panics = queue.add_job("Panics", Priority::Normal, |_| panic!());
+        let completes = queue.add_job("Completes", Priority::Normal, |_| {});
+        queue.on_finish(waiting, record("Waiting"));
+        queue.on_finish(pending, record("Pending"));
+        queue.on_finish(panics, record("Panics"));
+        queue.on_finish(completes, record("Completes"));
+        assert!(!queue.is_finished(pending));
+
+        queue.cancel_job(pending);
+        assert!(queue.is_finished(pending));
+        queue.cancel_job(waiting);
+
+        // Callbacks are called just after jobs leave the queue.
+        queue.wait_for_all_jobs();
+        while outcomes.lock().len() < 4 {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        assert_eq!(
+            *outcomes.lock(),
+            vec![
+                ("Pending", JobOutcome::Canceled),
+                ("Waiting", JobOutcome::Canceled),
+                ("Panics", JobOutcome::Panicked),
+                ("Completes", JobOutcome::Completed),
+            ]
+        );
+
+        // Already finished.
+        assert!(queue.on_finish(completes, record("Again")));
+        assert_eq!(
+            outcomes.lock().last(),
+            Some(&("Again", JobOutcome::Completed))
+        );
+
+        // Released.
+        queue.release(completes);
+        assert!(queue.is_finished(completes));
+        assert_eq!(queue.outcome(completes), None);
+        assert!(!queue.on_finish(completes, record("Released")));
+        assert_eq!(outcomes.lock().len(), 5);
+    }
+
+    #[test]
+    fn outcomes_02() {
+        let queue = JobQueue::new();
+        let (gate, ran) = (Shared::new(false), Shared::new(false));
+
+        let first = queue.add_job("First", Priority::Normal, |_| {});
+        let waiting = queue.add_job("Waiting", Priority::Normal, {
+            let gate = gate.clone_ref();
+            move |_| {
+                while !*gate.lock() {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+            }
+        });
+        let child = queue.add_job_after(&[first, waiting], "Child", Priority::Normal, {
+            let ran = ran.clone_ref();
+            move |_| *ran.lock_mut() = true
+        });
+
+        while !queue.is_finished(first) {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(queue.outcome(first), Some(JobOutcome::Completed));
+
+        *gate.lock_mut() = true;
+        queue.wait_for_all_jobs();
+        assert!(*ran.lock());
+        for id in [first, waiting, child].iter() {
+            assert!(queue.is_finished(*id));
+            assert_eq!(queue.outcome(*id), Some(JobOutcome::Completed));
+            queue.release(*id);
+            assert_eq!(queue.outcome(*id), None);
+        }
+        assert!(queue.scheduler.lock().outcomes.is_empty());
+
+        // Ids that weren't issued by this queue.
+        let foreign = JobId(usize::MAX);
+        assert!(!queue.is_finished(foreign));
+        assert_eq!(queue.outcome(foreign), None);
+        assert!(!queue.on_finish(foreign, |_| panic!()));
+    }
+
+    #[test]
+    fn outcomes_03() {
+        // Callbacks set after the job has already finished are still
+        // called, no matter how quickly it finished.
+        let queue = JobQueue::new();
+        let outcomes = Shared::new(Vec::new());
+
+        let completes = queue.add_job("Completes", Priority::Normal, |_| {});
+        let panics = queue.add_parallel_job("Panics", Priority::Normal, |_| panic!());
+        queue.wait_for_all_jobs();
+        while !queue.is_finished(completes) || !queue.is_finished(panics) {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        for id in [completes, panics].iter() {
+            let outcomes = outcomes.clone_ref();
+            assert!(queue.on_finish(*id, move |outcome| outcomes.lock_mut().push(outcome)));
         }
+        assert_eq!(
+            *outcomes.lock(),
+            vec![JobOutcome::Completed, JobOutcome::Panicked]
+        );
     }
 }
@@ -0,0 +1,331 @@
+use crate::{config::*, gamut_map::GamutMapping};
+
+use colorbox::{chroma, matrix};
+
+pub const REFERENCE_SPACE_CHROMA: chroma::Chromaticities = chroma::ACES_AP0;
+
+/// Builds a config that follows the conventions of the ACES 1.3 CG
+/// config: ACES2065-1 reference, the standard ACES working spaces, and
+/// populated interchange roles.
+///
+/// The ACES output transforms are done with OCIO's built-in transforms,
+/// and therefore require OCIO 2.1 or later.
+pub fn make_aces_1_3() -> OCIOConfig {
+    let adaptation_method = matrix::AdaptationMethod::Bradford;
+    let ap0_to_xyz_d65 = matrix::compose(&[
+        matrix::rgb_to_xyz_matrix(REFERENCE_SPACE_CHROMA),
+        matrix::xyz_chromatic_adaptation_matrix(
+            REFERENCE_SPACE_CHROMA.w,
+            chroma::illuminant::D65,
+            adaptation_method,
+        ),
+    ]);
+
+    //----
+
+    let mut config = OCIOConfig::default();
+
+    config.reference_space_chroma = REFERENCE_SPACE_CHROMA;
+
+    config.name = Some("ACES 1.3 (customized)".into());
+    config.description =
+        Some("Customized config following the conventions of the ACES 1.3 CG config.".into());
+
+    config.roles.reference = Some("ACES2065-1".into());
+    config.roles.aces_interchange = Some("ACES2065-1".into());
+    config.roles.cie_xyz_d65_interchange = Some("CIE-XYZ-D65".into());
+
+    config.roles.default = Some("ACES2065-1".into());
+    config.roles.data = Some("Raw".into());
+    config.roles.other = [
+        ("scene_linear".into(), "ACEScg".into()),
+        ("rendering".into(), "ACEScg".into()),
+        ("compositing_linear".into(), "ACEScg".into()),
+        ("compositing_log".into(), "ACEScct".into()),
+        ("color_timing".into(), "ACEScct".into()),
+        ("texture_paint".into(), "ACEScct".into()),
+        ("matte_paint".into(), "ACEScct".into()),
+        ("color_picking".into(), "sRGB - Texture".into()),
+    ]
+    .into();
+
+    //---------------------------------------------------------
+    // Displays
+
+    config.displays.push(Display {
+        name: "sRGB - Display".into(),
+        views: vec![
+            (
+                "ACES 1.0 - SDR Video".into(),
+                "sRGB - Display (ACES 1.0 SDR-video)".into(),
+            ),
+            (
+                "Un-tone-mapped".into(),
+                "sRGB - Display (Un-tone-mapped)".into(),
+            ),
+            ("Raw".into(), "Raw".into()),
+        ],
+    });
+    config.displays.push(Display {
+        name: "Rec.1886 Rec.709 - Display".into(),
+        views: vec![
+            (
+                "ACES 1.0 - SDR Video".into(),
+                "Rec.1886 Rec.709 - Display (ACES 1.0 SDR-video)".into(),
+            ),
+            (
+                "Un-tone-mapped".into(),
+                "Rec.1886 Rec.709 - Display (Un-tone-mapped)".into(),
+            ),
+            ("Raw".into(), "Raw".into()),
+        ],
+    });
+    config.displays.push(Display {
+        name: "P3-D65 - Display".into(),
+        views: vec![
+            (
+                "ACES 1.0 - SDR Cinema".into(),
+                "P3-D65 - Display (ACES 1.0 SDR-cinema)".into(),
+            ),
+            (
+                "Un-tone-mapped".into(),
+                "P3-D65 - Display (Un-tone-mapped)".into(),
+            ),
+            ("Raw".into(), "Raw".into()),
+        ],
+    });
+    config.displays.push(Display {
+        name: "Rec.2100-PQ - Display".into(),
+        views: vec![
+            (
+                "ACES 1.1 - HDR Video (1000 nits)".into(),
+                "Rec.2100-PQ - Display (ACES 1.1 HDR-video 1000 nits)".into(),
+            ),
+            ("Raw".into(), "Raw".into()),
+        ],
+    });
+
+    config.active_displays = vec![
+        "sRGB - Display".into(),
+        "Rec.1886 Rec.709 - Display".into(),
+        "P3-D65 - Display".into(),
+        "Rec.2100-PQ - Display".into(),
+    ];
+    config.active_views = vec![
+        "ACES 1.0 - SDR Video".into(),
+        "ACES 1.0 - SDR Cinema".into(),
+        "ACES 1.1 - HDR Video (1000 nits)".into(),
+        "Un-tone-mapped".into(),
+        "Raw".into(),
+    ];
+
+    //---------------------------------------------------------
+    // Display color spaces.
+
+    // (name, ACES output transform, display encoding)
+    let display_spaces = [
+        (
+            "sRGB - Display (ACES 1.0 SDR-video)",
+            Some("ACES-OUTPUT - ACES2065-1_to_CIE-XYZ-D65 - SDR-VIDEO_1.0"),
+            "DISPLAY - CIE-XYZ-D65_to_sRGB",
+        ),
+        (
+            "sRGB - Display (Un-tone-mapped)",
+            None,
+            "DISPLAY - CIE-XYZ-D65_to_sRGB",
+        ),
+        (
+            "Rec.1886 Rec.709 - Display (ACES 1.0 SDR-video)",
+            Some("ACES-OUTPUT - ACES2065-1_to_CIE-XYZ-D65 - SDR-VIDEO_1.0"),
+            "DISPLAY - CIE-XYZ-D65_to_REC.1886-REC.709",
+        ),
+        (
+            "Rec.1886 Rec.709 - Display (Un-tone-mapped)",
+            None,
+            "DISPLAY - CIE-XYZ-D65_to_REC.1886-REC.709",
+        ),
+        (
+            "P3-D65 - Display (ACES 1.0 SDR-cinema)",
+            Some("ACES-OUTPUT - ACES2065-1_to_CIE-XYZ-D65 - SDR-CINEMA_1.0"),
+            "DISPLAY - CIE-XYZ-D65_to_G2.6-P3-D65",
+        ),
+        (
+            "P3-D65 - Display (Un-tone-mapped)",
+            None,
+            "DISPLAY - CIE-XYZ-D65_to_G2.6-P3-D65",
+        ),
+        (
+            "Rec.2100-PQ - Display (ACES 1.1 HDR-video 1000 nits)",
+            Some("ACES-OUTPUT - ACES2065-1_to_CIE-XYZ-D65 - HDR-VIDEO-1000nit-15nit_1.1"),
+            "DISPLAY - CIE-XYZ-D65_to_REC.2100-PQ",
+        ),
+    ];
+    for (name, output_transform, display_transform) in display_spaces.iter() {
+        let mut from_reference = Vec::new();
+        if let Some(output_transform) = output_transform {
+            from_reference.push(Transform::BuiltinTransform {
+                name: (*output_transform).into(),
+                direction_inverse: false,
+            });
+        } else {
+            from_reference.push(Transform::MatrixTransform(matrix::to_4x4_f32(
+                ap0_to_xyz_d65,
+            )));
+        }
+        from_reference.push(Transform::BuiltinTransform {
+            name: (*display_transform).into(),
+            direction_inverse: false,
+        });
+
+        config.colorspaces.push(ColorSpace {
+            name: (*name).into(),
+            family: "Display".into(),
+            bitdepth: Some(BitDepth::F32),
+            isdata: Some(false),
+            from_reference: from_reference,
+            ..ColorSpace::default()
+        });
+    }
+
+    //---------------------------------------------------------
+    // Input color spaces.
+
+    config.colorspaces.push(ColorSpace {
+        name: "ACES2065-1".into(),
+        description: "The Academy Color Encoding System reference color space".into(),
+        aliases: vec![
+            "aces2065_1".into(),
+            "ACES - ACES2065-1".into(),
+            "lin_ap0".into(),
+        ],
+        family: "ACES".into(),
+        bitdepth: Some(BitDepth::F32),
+        isdata: Some(false),
+        ..ColorSpace::default()
+    });
+
+    // (name, aliases, description, built-in transform to ACES2065-1)
+    let aces_spaces = [
+        (
+            "ACEScg",
+            ["ACES - ACEScg", "lin_ap1"],
+            "ACEScg working space",
+            "ACEScg_to_ACES2065-1",
+        ),
+        (
+            "ACEScct",
+            ["ACES - ACEScct", "acescct_ap1"],
+            "ACEScct to ACES2065-1",
+            "ACEScct_to_ACES2065-1",
+        ),
+        (
+            "ACEScc",
+            ["ACES - ACEScc", "acescc_ap1"],
+            "ACEScc to ACES2065-1",
+            "ACEScc_to_ACES2065-1",
+        ),
+    ];
+    for (name, aliases, description, to_reference) in aces_spaces.iter() {
+        config.colorspaces.push(ColorSpace {
+            name: (*name).into(),
+            description: (*description).into(),
+            aliases: aliases.iter().map(|a| (*a).into()).collect(),
+            family: "ACES".into(),
+            bitdepth: Some(BitDepth::F32),
+            isdata: Some(false),
+            to_reference: vec![Transform::BuiltinTransform {
+                name: (*to_reference).into(),
+                direction_inverse: false,
+            }],
+            ..ColorSpace::default()
+        });
+    }
+
+    config.colorspaces.push(ColorSpace {
+        name: "CIE-XYZ-D65".into(),
+        description: "The CIE XYZ (1931) color space, with a D65 white point".into(),
+        aliases: vec!["cie_xyz_d65".into()],
+        family: "Utility".into(),
+        bitdepth: Some(BitDepth::F32),
+        isdata: Some(false),
+        from_reference: vec![Transform::MatrixTransform(matrix::to_4x4_f32(
+            ap0_to_xyz_d65,
+        ))],
+        ..ColorSpace::default()
+    });
+
+    // (name, aliases, chromaticities)
+    let linear_spaces = [
+        (
+            "Linear Rec.709 (sRGB)",
+            ["lin_rec709_srgb", "Utility - Linear - Rec.709"],
+            chroma::REC709,
+        ),
+        (
+            "Linear P3-D65",
+            ["lin_p3d65", "Utility - Linear - P3-D65"],
+            chroma::DISPLAY_P3,
+        ),
+        (
+            "Linear Rec.2020",
+            ["lin_rec2020", "Utility - Linear - Rec.2020"],
+            chroma::REC2020,
+        ),
+    ];
+    for (name, aliases, chroma) in linear_spaces.iter() {
+        config
+            .add_input_colorspace(
+                (*name).into(),
+                Some("Utility".into()),
+                None,
+                *chroma,
+                adaptation_method,
+                Vec::new(),
+                GamutMapping::None,
+            )
+            .unwrap();
+        config.colorspaces.last_mut().unwrap().aliases =
+            aliases.iter().map(|a| (*a).into()).collect();
+    }
+
+    config
+        .add_input_colorspace(
+            "sRGB - Texture".into(),
+            Some("Utility".into()),
+            Some("sRGB encoded Rec.709 primaries, as commonly used for textures".into()),
+            chroma::REC709,
+            adaptation_method,
+            vec![Transform::ExponentWithLinearTransform {
+                gamma: 2.4,
+                offset: 0.055,
+                direction_inverse: true,
+            }],
+            GamutMapping::None,
+        )
+        .unwrap();
+    config.colorspaces.last_mut().unwrap().aliases =
+        vec!["srgb_tx".into(), "Utility - sRGB - Texture".into()];
+
+    config.colorspaces.push(ColorSpace {
+        name: "Raw".into(),
+        description: "The utility \"Raw\" color space, for non-color data".into(),
+        aliases: vec!["Utility - Raw".into()],
+        family: "Utility".into(),
+        bitdepth: Some(BitDepth::F32),
+        isdata: Some(true),
+        ..ColorSpace::default()
+    });
+
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_aces_1_3_test() {
+        let config = make_aces_1_3();
+        assert_eq!(config.validate(), Ok(()));
+    }
+}
@@ -2,8 +2,6 @@
 //!
 //! Aims to match Blender's AgX implementation.
 
-use std::path::PathBuf;
-
 use colorbox::{
     chroma::{self, Chromaticities},
     lut::Lut3D,
@@ -11,12 +9,15 @@ use colorbox::{
     transforms::ocio::{hsv_to_rgb, rgb_to_hsv},
 };
 
-use crate::config::{Allocation, Interpolation, Transform};
+use crate::{
+    config::{Allocation, Interpolation, Transform},
+    tone_map::ToneMapParams,
+};
 
 const LUT_GAMMA: f64 = 2.4;
 const HEADROOM_STOPS: f64 = 8.5;
 
-pub fn make_agx_rec709() -> AgX {
+pub fn make_agx_rec709(params: ToneMapParams) -> AgX {
     const MID_GRAY: f64 = 0.18;
     const NORMALIZED_LOG2_MINIMUM: f64 = -10.0;
     const NORMALIZED_LOG2_MAXIMUM: f64 = 6.5;
@@ -29,9 +30,9 @@ pub fn make_agx_rec709() -> AgX {
 
         curve::Sigmoid::new(
             [x_pivot, y_pivot],
-            2.4,
+            2.4 * params.contrast,
             [0.0; 2],
-            [1.5; 2],
+            [1.5, 1.5 * params.shoulder],
             [[0.0; 2], [1.0; 2]],
         )
     };
@@ -51,11 +52,12 @@ pub fn make_agx_rec709() -> AgX {
         [0.2658180370250449, 0.59846986045365, 0.1357121025213052],
         Some(0.08),
         40.0,
+        params.saturation,
         37,
     )
 }
 
-pub fn make_agx_rec2020() -> AgX {
+pub fn make_agx_rec2020(params: ToneMapParams) -> AgX {
     const MID_GRAY: f64 = 0.18;
     const NORMALIZED_LOG2_MINIMUM: f64 = -10.0;
     const NORMALIZED_LOG2_MAXIMUM: f64 = 6.5;
@@ -68,9 +70,9 @@ pub fn make_agx_rec2020() -> AgX {
 
         curve::Sigmoid::new(
             [x_pivot, y_pivot],
-            2.4,
+            2.4 * params.contrast,
             [0.0; 2],
-            [1.5; 2],
+            [1.5, 1.5 * params.shoulder],
             [[0.0; 2], [1.0; 2]],
         )
     };
@@ -90,11 +92,12 @@ pub fn make_agx_rec2020() -> AgX {
         [0.2658180370250449, 0.59846986045365, 0.1357121025213052],
         None,
         40.0,
+        params.saturation,
         37,
     )
 }
 
-pub fn make_agx_display_p3() -> AgX {
+pub fn make_agx_display_p3(params: ToneMapParams) -> AgX {
     const MID_GRAY: f64 = 0.18;
     const NORMALIZED_LOG2_MINIMUM: f64 = -10.0;
     const NORMALIZED_LOG2_MAXIMUM: f64 = 6.5;
@@ -107,9 +110,9 @@ pub fn make_agx_display_p3() -> AgX {
 
         curve::Sigmoid::new(
             [x_pivot, y_pivot],
-            2.4,
+            2.4 * params.contrast,
             [0.0; 2],
-            [1.5; 2],
+            [1.5, 1.5 * params.shoulder],
             [[0.0; 2], [1.0; 2]],
         )
     };
@@ -129,6 +132,7 @@ pub fn make_agx_display_p3() -> AgX {
         [0.2658180370250449, 0.59846986045365, 0.1357121025213052],
         Some(0.08),
         40.0,
+        params.saturation,
         37,
     )
 }
@@ -149,6 +153,7 @@ pub struct AgX {
     sigmoid: curve::Sigmoid,
 
     mix_percent: f64,
+    saturation: f64,
 
     res_3d: usize,
 }
@@ -169,6 +174,7 @@ impl AgX {
         luminance_coeffs: [f64; 3],
         luminance_blend_power: Option<f64>,
         mix_percent: f64,
+        saturation: f64,
         res_3d: usize,
     ) -> Self {
         let input_to_working_matrix = matrix::compose(&[
@@ -219,6 +225,7 @@ impl AgX {
             luminance_blend_power: luminance_blend_power,
             sigmoid: sigmoid,
             mix_percent: mix_percent,
+            saturation: saturation,
             res_3d: res_3d,
         }
     }
@@ -268,6 +275,17 @@ impl AgX {
 
         let col = matrix::transform_color(col, self.outset_matrix);
 
+        // User saturation adjustment.
+        let col = {
+            let c = self.luminance_coeffs;
+            let gray = col[0] * c[0] + col[1] * c[1] + col[2] * c[2];
+            [
+                lerp(gray, col[0], self.saturation),
+                lerp(gray, col[1], self.saturation),
+                lerp(gray, col[2], self.saturation),
+            ]
+        };
+
         // Convert to output color space.
         let col = matrix::transform_color(col, self.working_to_output_matrix);
 
@@ -335,7 +353,7 @@ impl AgX {
             },
             // 3D LUT.
             Transform::FileTransform {
-                src: PathBuf::from(lut_3d_path),
+                src: lut_3d_path.into(),
                 interpolation: Interpolation::Tetrahedral,
                 direction_inverse: false,
             },
@@ -189,7 +189,10 @@ pub fn make_blender_4_0() -> OCIOConfig {
                 direction_inverse: false,
             },
             Transform::CDLTransform {
+                slope: [1.0; 3],
+                offset: [0.0; 3],
                 power: [1.0912; 3],
+                saturation: 1.0,
                 direction_inverse: false,
             },
         ],
@@ -341,7 +344,10 @@ pub fn make_blender_4_0() -> OCIOConfig {
                 direction_inverse: false,
             },
             Transform::CDLTransform {
+                slope: [1.0; 3],
+                offset: [0.0; 3],
                 power: [1.0912; 3],
+                saturation: 1.0,
                 direction_inverse: false,
             },
         ],
@@ -5,16 +5,18 @@ use std::{
 };
 
 use colorbox::{
-    chroma::Chromaticities,
+    chroma::{self, Chromaticities},
     lut::{Lut1D, Lut3D},
     matrix::{self, AdaptationMethod},
 };
 
-use crate::gamut_map;
+use crate::gamut_map::{self, GamutMapping};
 
 const GAMUT_DIR: &str = "gamut_handling";
+const STAGING_DIR: &str = ".ocio_gen_staging";
 pub const INPUT_GAMUT_CLIP_LUT_FILENAME: &str = "rgb_input_gamut_clip.cube";
 pub const OUTPUT_GAMUT_CLIP_LUT_FILENAME: &str = "rgb_output_gamut_clip.cube";
+pub const SOFT_COMPRESS_LUT_FILENAME: &str = "rgb_gamut_soft_compress.cube";
 
 #[derive(Debug, Clone)]
 pub struct OCIOConfig {
@@ -25,18 +27,26 @@ pub struct OCIOConfig {
     // Files to include.
     pub output_files: HashMap<PathBuf, OutputFile>,
 
+    // Files outside the config that it references, keyed by the path
+    // they're referenced by.  These aren't written with the config, but
+    // are used when evaluating it.
+    pub external_files: HashMap<PathBuf, OutputFile>,
+
     // Top-level comment at the start of the config file.
     pub header_comment: String,
 
     // Header fields.
+    pub profile_version: ProfileVersion,
     pub name: Option<String>,
     pub description: Option<String>,
-    pub search_path: HashSet<PathBuf>,
+    pub environment: Vec<(String, String)>, // (context variable, default value)
+    pub search_path: Vec<String>,           // Can use context variables.
 
     // Config sections.
     pub roles: Roles,
 
-    // pub file_rules: TODO.
+    pub file_rules: Vec<FileRule>, // If empty, not written to config.
+    pub viewing_rules: Vec<ViewingRule>,
     pub displays: Vec<Display>,
     pub active_displays: Vec<String>, // If empty, not written to config.
     pub active_views: Vec<String>,    // If empty, not written to config.
@@ -45,6 +55,8 @@ pub struct OCIOConfig {
 
     pub colorspaces: Vec<ColorSpace>,
     pub inactive_colorspaces: Vec<String>,
+
+    pub named_transforms: Vec<NamedTransform>,
 }
 
 impl Default for OCIOConfig {
@@ -53,19 +65,25 @@ impl Default for OCIOConfig {
             reference_space_chroma: colorbox::chroma::REC709,
 
             output_files: HashMap::new(),
+            external_files: HashMap::new(),
 
             header_comment: String::new(),
+            profile_version: ProfileVersion::V2_1,
             name: None,
             description: None,
-            search_path: HashSet::new(),
+            environment: Vec::new(),
+            search_path: Vec::new(),
 
             roles: Roles::default(),
+            file_rules: Vec::new(),
+            viewing_rules: Vec::new(),
             displays: Vec::new(),
             active_displays: Vec::new(),
             active_views: Vec::new(),
             looks: Vec::new(),
             colorspaces: Vec::new(),
             inactive_colorspaces: Vec::new(),
+            named_transforms: Vec::new(),
         }
     }
 }
@@ -75,98 +93,98 @@ impl OCIOConfig {
         OCIOConfig::default()
     }
 
-    pub fn write_to_directory<P: AsRef<Path>>(&self, dir_path: P) -> std::io::Result<()> {
+    /// Writes the config file and all output files to `dir_path`.
+    ///
+    /// Everything is first written to a staging directory inside
+    /// `dir_path` and then moved into place, so that failing partway
+    /// through doesn't leave a half-written config behind.  The config
+    /// file is moved last, so it never references files that aren't
+    /// there yet.
+    pub fn write_to_directory<P: AsRef<Path>>(&self, dir_path: P) -> Result<(), WriteError> {
         let dir_path: &Path = dir_path.as_ref();
+        let config_path: &Path = "config.ocio".as_ref();
+        let file_paths = || {
+            self.output_files
+                .keys()
+                .map(|p| p.as_path())
+                .chain(std::iter::once(config_path))
+        };
 
-        // First ensure all the directories we need exist.
-        crate::ensure_dir_exists(dir_path)?;
-        for (output_path, _) in self.output_files.iter() {
-            if let Some(path) = output_path.parent() {
-                crate::ensure_dir_exists(&dir_path.join(path))?;
+        // Pre-flight check: make sure the target directory exists and is
+        // writable, and that nothing we're about to replace is read-only.
+        crate::ensure_dir_exists(dir_path).map_err(|e| WriteError::new(dir_path, e))?;
+        let staging_dir = dir_path.join(STAGING_DIR);
+        prepare_staging_dir(dir_path, &staging_dir)?;
+        for path in file_paths() {
+            let path = dir_path.join(path);
+            if let Some(parent) = path.parent() {
+                if parent.exists() {
+                    crate::ensure_dir_exists(parent).map_err(|e| WriteError::new(parent, e))?;
+                }
             }
-        }
-        for path in self.search_path.iter() {
-            if path.is_relative() {
-                crate::ensure_dir_exists(&dir_path.join(path))?;
+            if let Ok(metadata) = std::fs::metadata(&path) {
+                if metadata.permissions().readonly() {
+                    return Err(WriteError::NotWritable(path));
+                }
             }
         }
 
-        // Write the output files.
-        for (output_path, output_file) in self.output_files.iter() {
-            let mut f = BufWriter::new(std::fs::File::create(&dir_path.join(output_path))?);
-            match output_file {
-                OutputFile::Raw(data) => f.write_all(&data)?,
-                OutputFile::Lut1D(lut) => {
-                    match output_path.extension().map(|e| e.to_str()).flatten() {
-                        Some("spi1d") => {
-                            if lut.ranges.len() > 1 {
-                                return Err(std::io::Error::new(
-                                    std::io::ErrorKind::Other,
-                                    ".spi1d files don't support per-channel input ranges",
-                                ));
-                            } else {
-                                let tables: Vec<&[f32]> =
-                                    lut.tables.iter().map(|t| t.as_ref()).collect();
-                                colorbox::formats::spi1d::write(
-                                    &mut f,
-                                    lut.ranges[0].0,
-                                    lut.ranges[0].1,
-                                    &tables,
-                                )?;
-                            }
-                        }
-
-                        Some("cube") => {
-                            let ranges = match lut.ranges.len() {
-                                1 => [lut.ranges[0], lut.ranges[0], lut.ranges[0]],
-                                2 => [lut.ranges[0], lut.ranges[1], lut.ranges[1]],
-                                _ => [lut.ranges[0], lut.ranges[1], lut.ranges[2]],
-                            };
-                            let tables = match lut.tables.len() {
-                                1 => [&lut.tables[0][..], &lut.tables[0][..], &lut.tables[0][..]],
-                                2 => [&lut.tables[0][..], &lut.tables[1][..], &lut.tables[1][..]],
-                                _ => [&lut.tables[0][..], &lut.tables[1][..], &lut.tables[2][..]],
-                            };
-                            colorbox::formats::cube_iridas::write_1d(&mut f, ranges, tables)?;
-                        }
-
-                        _ => {
-                            return Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
-                                "Unsupported LUT output file format",
-                            ))
-                        }
-                    }
+        // Write everything to the staging directory.
+        let result = self.write_files(&staging_dir, dir_path).and_then(|_| {
+            // Move everything into place.
+            for path in file_paths() {
+                let target = dir_path.join(path);
+                if let Some(parent) = target.parent() {
+                    crate::ensure_dir_exists(parent).map_err(|e| WriteError::new(parent, e))?;
                 }
-                OutputFile::Lut3D(lut) => {
-                    match output_path.extension().map(|e| e.to_str()).flatten() {
-                        Some("cube") => colorbox::formats::cube_iridas::write_3d(
-                            &mut f,
-                            lut.range,
-                            lut.resolution[0],
-                            [&lut.tables[0], &lut.tables[1], &lut.tables[2]],
-                        )?,
-
-                        _ => {
-                            return Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
-                                "Unsupported LUT output file format",
-                            ))
-                        }
-                    }
+                std::fs::rename(staging_dir.join(path), &target)
+                    .map_err(|e| WriteError::new(&target, e))?;
+            }
+            for path in self.search_path.iter() {
+                // Directories that depend on context variables are up to
+                // the user to create.
+                if Path::new(path).is_relative() && context_variables(path).is_empty() {
+                    let path = dir_path.join(path);
+                    crate::ensure_dir_exists(&path).map_err(|e| WriteError::new(&path, e))?;
                 }
             }
+            Ok(())
+        });
+        let _ = std::fs::remove_dir_all(&staging_dir);
+
+        result
+    }
+
+    /// Writes the config file and all output files to `staging_dir`.
+    ///
+    /// Errors are reported with the corresponding paths in `dir_path`, since
+    /// that's where the files are ultimately going.
+    fn write_files(&self, staging_dir: &Path, dir_path: &Path) -> Result<(), WriteError> {
+        crate::ensure_dir_exists(staging_dir).map_err(|e| WriteError::new(dir_path, e))?;
+
+        // Output files.
+        for (output_path, output_file) in self.output_files.iter() {
+            let err = |e| WriteError::new(&dir_path.join(output_path), e);
+            if let Some(parent) = output_path.parent() {
+                crate::ensure_dir_exists(&staging_dir.join(parent)).map_err(err)?;
+            }
+            let mut f =
+                BufWriter::new(std::fs::File::create(&staging_dir.join(output_path)).map_err(err)?);
+            output_file.write(output_path, &mut f).map_err(err)?;
+            f.flush().map_err(err)?;
         }
 
-        // Write the config file.
-        self.write_config_file(BufWriter::new(std::fs::File::create(
-            dir_path.join("config.ocio"),
-        )?))?;
+        // Config file.
+        let err = |e| WriteError::new(&dir_path.join("config.ocio"), e);
+        let mut f =
+            BufWriter::new(std::fs::File::create(staging_dir.join("config.ocio")).map_err(err)?);
+        self.write_config_file(&mut f).map_err(err)?;
+        f.flush().map_err(err)?;
 
         Ok(())
     }
 
-    fn write_config_file<W: std::io::Write>(&self, mut file: W) -> std::io::Result<()> {
+    pub(crate) fn write_config_file<W: std::io::Write>(&self, mut file: W) -> std::io::Result<()> {
         // Header comment.
         if !self.header_comment.trim().is_empty() {
             for line in self.header_comment.lines() {
@@ -176,28 +194,36 @@ impl OCIOConfig {
         }
 
         // Header.
-        file.write_all(b"ocio_profile_version: 2.1\n\n")?;
-        if let Some(name) = &self.name {
+        let v2 = self.profile_version != ProfileVersion::V1;
+        file.write_all(
+            format!(
+                "ocio_profile_version: {}\n\n",
+                self.profile_version.as_str()
+            )
+            .as_bytes(),
+        )?;
+        if let (Some(name), true) = (&self.name, v2) {
             file.write_all(format!("name: {}\n", name).as_bytes())?;
         }
         if let Some(description) = &self.description {
             file.write_all(
                 format!(
                     "description: |\n  {}\n",
-                    description.trim().replace("\n", "  \n")
+                    description.trim().replace("\n", "\n  ")
                 )
                 .as_bytes(),
             )?;
         }
-        if !self.search_path.is_empty() {
-            file.write_all(b"search_path: \"")?;
-            for (i, path) in self.search_path.iter().enumerate() {
-                if i != 0 {
-                    file.write_all(b":")?;
-                }
-                file.write_all(path.to_string_lossy().as_bytes())?
+        if v2 && !self.environment.is_empty() {
+            file.write_all(b"environment:\n")?;
+            for (variable, default) in self.environment.iter() {
+                file.write_all(format!("  {}: {}\n", variable, quoted(default)).as_bytes())?;
             }
-            file.write_all(b"\"\n")?;
+        }
+        if !self.search_path.is_empty() {
+            file.write_all(
+                format!("search_path: {}\n", quoted(&self.search_path.join(":"))).as_bytes(),
+            )?;
         }
         file.write_all(b"strictparsing: true\n")?;
         file.write_all(b"\n")?;
@@ -224,6 +250,55 @@ impl OCIOConfig {
         }
         file.write_all(b"\n")?;
 
+        // File rules.
+        if v2 && !self.file_rules.is_empty() {
+            file.write_all(b"file_rules:\n")?;
+            for rule in self.file_rules.iter() {
+                let fields = match rule.kind {
+                    FileRuleKind::Pattern {
+                        ref pattern,
+                        ref extension,
+                    } => format!(
+                        "colorspace: {}, pattern: {}, extension: {}",
+                        rule.colorspace,
+                        quoted(pattern),
+                        quoted(extension)
+                    ),
+                    FileRuleKind::Regex(ref regex) => {
+                        format!("colorspace: {}, regex: {}", rule.colorspace, quoted(regex))
+                    }
+                    FileRuleKind::ColorSpaceNamePathSearch => String::new(),
+                    FileRuleKind::Default => format!("colorspace: {}", rule.colorspace),
+                };
+                if fields.is_empty() {
+                    file.write_all(format!("  - !<Rule> {{ name: {} }}\n", rule.name).as_bytes())?;
+                } else {
+                    file.write_all(
+                        format!("  - !<Rule> {{ name: {}, {} }}\n", rule.name, fields).as_bytes(),
+                    )?;
+                }
+            }
+            file.write_all(b"\n")?;
+        }
+
+        // Viewing rules.
+        if v2 && !self.viewing_rules.is_empty() {
+            file.write_all(b"viewing_rules:\n")?;
+            for rule in self.viewing_rules.iter() {
+                let mut text = format!("  - !<Rule> {{ name: {}", rule.name);
+                if !rule.colorspaces.is_empty() {
+                    text.push_str(&format!(", colorspaces: [{}]", rule.colorspaces.join(", ")));
+                }
+                if !rule.encodings.is_empty() {
+                    let encodings: Vec<&str> = rule.encodings.iter().map(|e| e.as_str()).collect();
+                    text.push_str(&format!(", encodings: [{}]", encodings.join(", ")));
+                }
+                text.push_str(" }\n");
+                file.write_all(text.as_bytes())?;
+            }
+            file.write_all(b"\n")?;
+        }
+
         // Displays and views.
         file.write_all(b"displays:\n")?;
         for display in self.displays.iter() {
@@ -264,7 +339,7 @@ impl OCIOConfig {
         }
 
         // Inactive colorspaces.
-        if !self.inactive_colorspaces.is_empty() {
+        if v2 && !self.inactive_colorspaces.is_empty() {
             file.write_all(b"inactive_colorspaces: [")?;
             for (i, space) in self.inactive_colorspaces.iter().enumerate() {
                 if i != 0 {
@@ -295,12 +370,59 @@ impl OCIOConfig {
             }
         }
 
+        // Named transforms.
+        if v2 && !self.named_transforms.is_empty() {
+            file.write_all(b"named_transforms:\n")?;
+            for named_transform in self.named_transforms.iter() {
+                file.write_all(b"  - !<NamedTransform>\n")?;
+                file.write_all(format!("    name: {}\n", named_transform.name).as_bytes())?;
+                if !named_transform.aliases.is_empty() {
+                    file.write_all(
+                        format!("    aliases: [{}]\n", named_transform.aliases.join(", "))
+                            .as_bytes(),
+                    )?;
+                }
+                if !named_transform.description.is_empty() {
+                    file.write_all(
+                        format!(
+                            "    description: |\n      {}\n",
+                            named_transform.description.trim().replace("\n", "\n      ")
+                        )
+                        .as_bytes(),
+                    )?;
+                }
+                if !named_transform.family.is_empty() {
+                    file.write_all(format!("    family: {}\n", named_transform.family).as_bytes())?;
+                }
+                if let Some(encoding) = named_transform.encoding {
+                    file.write_all(format!("    encoding: {}\n", encoding.as_str()).as_bytes())?;
+                }
+                if !named_transform.transform.is_empty() {
+                    write_transform_yaml(
+                        &mut file,
+                        4,
+                        "transform",
+                        &named_transform.transform[..],
+                    )?;
+                }
+                if !named_transform.inverse_transform.is_empty() {
+                    write_transform_yaml(
+                        &mut file,
+                        4,
+                        "inverse_transform",
+                        &named_transform.inverse_transform[..],
+                    )?;
+                }
+                file.write_all(b"\n")?;
+            }
+        }
+
         // Color spaces.
         file.write_all(b"colorspaces:\n")?;
         for colorspace in self.colorspaces.iter() {
             file.write_all(b"  - !<ColorSpace>\n")?;
             file.write_all(format!("    name: {}\n", colorspace.name).as_bytes())?;
-            if !colorspace.aliases.is_empty() {
+            if v2 && !colorspace.aliases.is_empty() {
                 file.write_all(b"    aliases: [")?;
                 for (i, alias) in colorspace.aliases.iter().enumerate() {
                     if i != 0 {
@@ -314,7 +436,7 @@ impl OCIOConfig {
                 file.write_all(
                     format!(
                         "    description: |\n      {}\n",
-                        colorspace.description.trim().replace("\n", "      \n")
+                        colorspace.description.trim().replace("\n", "\n      ")
                     )
                     .as_bytes(),
                 )?;
@@ -327,7 +449,7 @@ impl OCIOConfig {
                     format!("    equalitygroup: {}\n", colorspace.equalitygroup).as_bytes(),
                 )?;
             }
-            if let Some(encoding) = colorspace.encoding {
+            if let (Some(encoding), true) = (colorspace.encoding, v2) {
                 file.write_all(format!("    encoding: {}\n", encoding.as_str()).as_bytes())?;
             }
             if let Some(bitdepth) = colorspace.bitdepth {
@@ -353,6 +475,257 @@ impl OCIOConfig {
         Ok(())
     }
 
+    /// Adds a directory to the end of the search path, if it isn't
+    /// already in it.
+    pub fn add_search_path(&mut self, path: &str) {
+        if !self.search_path.iter().any(|p| p == path) {
+            self.search_path.push(path.into());
+        }
+    }
+
+    /// Declares a context variable in the environment, if it isn't
+    /// already declared.
+    ///
+    /// Returns the variable's default value, which is the existing one
+    /// if it was already declared.
+    pub fn declare_context_variable(&mut self, variable: &str, default: &str) -> String {
+        match self.environment.iter().find(|(v, _)| v == variable) {
+            Some((_, existing)) => existing.clone(),
+            None => {
+                self.environment.push((variable.into(), default.into()));
+                default.into()
+            }
+        }
+    }
+
+    /// Replaces the context variables in `text` with their default
+    /// values, e.g. for finding the files that a path refers to when the
+    /// config is used without any context set.
+    ///
+    /// Undeclared variables are replaced with nothing.
+    pub fn expand_context_defaults(&self, text: &str) -> String {
+        replace_context_variables(text, |name| {
+            self.environment
+                .iter()
+                .find(|(v, _)| v == name)
+                .map(|(_, default)| default.clone())
+                .unwrap_or_else(String::new)
+        })
+    }
+
+    /// Adds a look that applies a per-shot grade LUT, chosen by a context
+    /// variable.
+    ///
+    /// For example, with `dir` "grades" and `variable` "SHOT", the look
+    /// loads "grades/$SHOT.cube".  The variable is declared with the
+    /// default value "identity" (unless already declared), and an
+    /// identity LUT is written for the default, so that the look does
+    /// nothing when no shot is set.
+    pub fn add_per_shot_look(
+        &mut self,
+        name: String,
+        process_space: String,
+        dir: &str,
+        variable: &str,
+    ) {
+        let default = self.declare_context_variable(variable, "identity");
+        self.add_search_path(dir);
+        if !default.is_empty() && context_variables(&default).is_empty() {
+            let identity = Lut3D {
+                range: [(0.0, 1.0); 3],
+                resolution: [2; 3],
+                tables: (0..3)
+                    .map(|chan| {
+                        (0..8)
+                            .map(|i| ((i >> chan) & 1) as f32)
+                            .collect::<Vec<f32>>()
+                    })
+                    .collect(),
+            };
+            self.output_files
+                .entry(Path::new(dir).join(format!("{}.cube", default)))
+                .or_insert(OutputFile::Lut3D(identity));
+        }
+
+        self.looks.push(Look {
+            name: name,
+            description: format!("Per-shot grade, from \"{}/${}.cube\".", dir, variable),
+            process_space: process_space,
+            transform: vec![Transform::FileTransform {
+                src: format!("${{{}}}.cube", variable),
+                interpolation: Interpolation::Tetrahedral,
+                direction_inverse: false,
+            }],
+            inverse_transform: Vec::new(),
+        });
+    }
+
+    /// Adds a file rule, keeping the `Default` rule last.
+    ///
+    /// If there isn't a `Default` rule yet, one is added that uses the
+    /// `default` role, or failing that the reference space.
+    pub fn add_file_rule(&mut self, rule: FileRule) {
+        let default_index = self
+            .file_rules
+            .iter()
+            .position(|r| r.kind == FileRuleKind::Default);
+        match default_index {
+            Some(i) => self.file_rules.insert(i, rule),
+            None => {
+                let default_space = if self.roles.default.is_some() {
+                    "default".into()
+                } else {
+                    self.roles.reference.clone().unwrap_or_default()
+                };
+                self.file_rules.push(rule);
+                self.file_rules.push(FileRule {
+                    name: "Default".into(),
+                    colorspace: default_space,
+                    kind: FileRuleKind::Default,
+                });
+            }
+        }
+    }
+
+    /// Returns the names of all context variables used in the search
+    /// path and by file transforms.
+    pub fn context_variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        let texts = self
+            .search_path
+            .iter()
+            .chain(self.all_transforms().filter_map(|t| match *t {
+                Transform::FileTransform { ref src, .. } => Some(src),
+                _ => None,
+            }));
+        for text in texts {
+            for variable in context_variables(text) {
+                if !variables.contains(&variable) {
+                    variables.push(variable);
+                }
+            }
+        }
+        variables
+    }
+
+    /// Merges output files that have identical contents into a single
+    /// file, and points the file transforms that used the duplicates at
+    /// it instead.
+    ///
+    /// A duplicate is only merged if the remaining file can be found
+    /// via the search path under its own name.  Returns the number of
+    /// files that were removed.
+    pub fn deduplicate_output_files(&mut self) -> usize {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        // Group files by the hash of their contents as they'd be
+        // written, keeping the first (by path) of each group.
+        let mut paths: Vec<PathBuf> = self.output_files.keys().cloned().collect();
+        paths.sort();
+        let mut originals: HashMap<u64, Vec<(PathBuf, Vec<u8>)>> = HashMap::new();
+        let mut duplicate_of: HashMap<PathBuf, PathBuf> = HashMap::new();
+        for path in paths {
+            let mut data = Vec::new();
+            if self.output_files[&path].write(&path, &mut data).is_err() {
+                continue;
+            }
+            let mut hasher = DefaultHasher::new();
+            path.extension().hash(&mut hasher);
+            data.hash(&mut hasher);
+            let candidates = originals.entry(hasher.finish()).or_insert_with(Vec::new);
+            match candidates
+                .iter()
+                .find(|(p, d)| p.extension() == path.extension() && *d == data)
+            {
+                Some((original, _)) => {
+                    duplicate_of.insert(path, original.clone());
+                }
+                None => candidates.push((path, data)),
+            }
+        }
+        if duplicate_of.is_empty() {
+            return 0;
+        }
+
+        // Transforms carried through verbatim may refer to any file, so
+        // duplicates they mention are left alone.
+        let mut still_used: HashSet<PathBuf> = duplicate_of
+            .keys()
+            .filter(|path| {
+                let name = path.file_name().unwrap().to_string_lossy();
+                self.all_transforms().any(|t| match *t {
+                    Transform::Raw(ref text) => text.contains(name.as_ref()),
+                    _ => false,
+                })
+            })
+            .cloned()
+            .collect();
+
+        // Point file transforms at the remaining files.
+        let (search_path, output_files) = (&self.search_path, &self.output_files);
+        let chains = self
+            .colorspaces
+            .iter_mut()
+            .flat_map(|s| vec![&mut s.to_reference, &mut s.from_reference])
+            .chain(
+                self.looks
+                    .iter_mut()
+                    .flat_map(|l| vec![&mut l.transform, &mut l.inverse_transform]),
+            )
+            .chain(
+                self.named_transforms
+                    .iter_mut()
+                    .flat_map(|t| vec![&mut t.transform, &mut t.inverse_transform]),
+            );
+        for chain in chains {
+            for transform in chain.iter_mut() {
+                let src = match *transform {
+                    Transform::FileTransform { ref mut src, .. } => src,
+                    _ => continue,
+                };
+                let duplicate = match find_output_file(search_path, output_files, src) {
+                    Some(path) if duplicate_of.contains_key(path) => path,
+                    _ => continue,
+                };
+                let original = &duplicate_of[duplicate];
+                let new_src: String = original.file_name().unwrap().to_string_lossy().into();
+                if find_output_file(search_path, output_files, &new_src) == Some(original) {
+                    *src = new_src;
+                } else {
+                    still_used.insert(duplicate.clone());
+                }
+            }
+        }
+
+        let mut removed = 0;
+        for path in duplicate_of.keys() {
+            if !still_used.contains(path) {
+                self.output_files.remove(path);
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    /// Iterates over the transforms of all color spaces, looks, and
+    /// named transforms.
+    fn all_transforms(&self) -> impl Iterator<Item = &Transform> {
+        self.colorspaces
+            .iter()
+            .flat_map(|s| s.to_reference.iter().chain(s.from_reference.iter()))
+            .chain(
+                self.looks
+                    .iter()
+                    .flat_map(|l| l.transform.iter().chain(l.inverse_transform.iter())),
+            )
+            .chain(
+                self.named_transforms
+                    .iter()
+                    .flat_map(|t| t.transform.iter().chain(t.inverse_transform.iter())),
+            )
+    }
+
     pub fn has_colorspace(&self, name: &str) -> bool {
         for colorspace in self.colorspaces.iter() {
             if colorspace.name == name {
@@ -371,7 +744,8 @@ impl OCIOConfig {
     ///
     /// This is not 100% thorough by any means.
     pub fn validate(&self) -> Result<(), ValidationError> {
-        // Check for duplicate color space names.
+        // Check for duplicate color space names.  Named transforms share
+        // the same namespace as color spaces.
         {
             let mut colorspaces = HashSet::new();
             for colorspace in self.colorspaces.iter() {
@@ -381,6 +755,13 @@ impl OCIOConfig {
                     ));
                 }
             }
+            for named_transform in self.named_transforms.iter() {
+                if !colorspaces.insert(named_transform.name.as_str()) {
+                    return Err(ValidationError::DuplicateColorSpace(
+                        named_transform.name.clone(),
+                    ));
+                }
+            }
         }
 
         // Check for duplicate role names.
@@ -414,6 +795,25 @@ impl OCIOConfig {
             }
         }
 
+        // Check the file and viewing rules.
+        if !self.file_rules.is_empty() {
+            let mut rules = HashSet::new();
+            for rule in self.file_rules.iter() {
+                if !rules.insert(rule.name.as_str()) {
+                    return Err(ValidationError::DuplicateRule(rule.name.clone()));
+                }
+            }
+            if self.file_rules.last().map(|r| &r.kind) != Some(&FileRuleKind::Default) {
+                return Err(ValidationError::FileRulesWithoutDefault);
+            }
+        }
+        let mut rules = HashSet::new();
+        for rule in self.viewing_rules.iter() {
+            if !rules.insert(rule.name.as_str()) {
+                return Err(ValidationError::DuplicateRule(rule.name.clone()));
+            }
+        }
+
         // Check for references to non-existent color spaces.
         // TODO: check inside views and color spaces themselves.
         if let Some(ref space) = self.roles.reference {
@@ -458,6 +858,41 @@ impl OCIOConfig {
                 return Err(ValidationError::ReferenceToAbsentColorSpace(space.clone()));
             }
         }
+        for look in self.looks.iter() {
+            if !self.has_colorspace(look.process_space.as_str()) {
+                return Err(ValidationError::ReferenceToAbsentColorSpace(
+                    look.process_space.clone(),
+                ));
+            }
+        }
+        // File rules can also refer to roles.
+        let roles = self.roles.as_map();
+        for rule in self.file_rules.iter() {
+            if rule.kind != FileRuleKind::ColorSpaceNamePathSearch
+                && !self.has_colorspace(rule.colorspace.as_str())
+                && !roles.contains_key(&rule.colorspace)
+            {
+                return Err(ValidationError::ReferenceToAbsentColorSpace(
+                    rule.colorspace.clone(),
+                ));
+            }
+        }
+        for rule in self.viewing_rules.iter() {
+            for space in rule.colorspaces.iter() {
+                if !self.has_colorspace(space.as_str()) {
+                    return Err(ValidationError::ReferenceToAbsentColorSpace(space.clone()));
+                }
+            }
+        }
+
+        // Check that all used context variables are declared, since
+        // OCIO only looks up declared variables once there's an
+        // environment section.
+        for variable in self.context_variables() {
+            if !self.environment.iter().any(|(v, _)| *v == variable) {
+                return Err(ValidationError::UndeclaredContextVariable(variable));
+            }
+        }
 
         Ok(())
     }
@@ -465,6 +900,20 @@ impl OCIOConfig {
     //---------------------------------------------------------
     // Convenience functions to help build configs more easily.
 
+    /// Adds an input color space with the given chromaticities.
+    ///
+    /// `to_linear_transforms` are applied in order to linearize the
+    /// color space's values, before the conversion to the reference
+    /// gamut.  They're inverted in reverse order for the from-reference
+    /// direction.
+    ///
+    /// `gamut_mapping` is applied after converting to the reference
+    /// gamut (and likewise to this space's gamut in the from-reference
+    /// direction), unless the destination gamut contains the source gamut.
+    ///
+    /// Returns an error if the color space can't be converted from the
+    /// reference space, e.g. because `to_linear_transforms` contains a
+    /// singular matrix.
     pub fn add_input_colorspace(
         &mut self,
         name: String,
@@ -472,14 +921,11 @@ impl OCIOConfig {
         description: Option<String>,
         chromaticities: Chromaticities,
         whitepoint_adaptation_method: AdaptationMethod,
-        to_linear_transform: Option<Transform>,
-        use_gamut_clipping: bool,
-    ) {
+        to_linear_transforms: Vec<Transform>,
+        gamut_mapping: GamutMapping,
+    ) -> Result<(), String> {
         // Build to-reference transforms.
-        let mut to_reference_transforms = Vec::new();
-        if let Some(ref to_linear) = to_linear_transform {
-            to_reference_transforms.push(to_linear.clone());
-        }
+        let mut to_reference_transforms = to_linear_transforms.clone();
         to_reference_transforms.push(Transform::MatrixTransform(matrix::to_4x4_f32(
             matrix::compose(&[
                 matrix::rgb_to_xyz_matrix(chromaticities),
@@ -491,9 +937,8 @@ impl OCIOConfig {
                 matrix::xyz_to_rgb_matrix(self.reference_space_chroma),
             ]),
         )));
-        if use_gamut_clipping && !gamut_is_within_gamut(chromaticities, self.reference_space_chroma)
-        {
-            to_reference_transforms.extend_from_slice(&gamut_map::hsv_gamut_clip());
+        if !gamut_is_within_gamut(chromaticities, self.reference_space_chroma) {
+            to_reference_transforms.extend(self.gamut_mapping_transforms(gamut_mapping));
         }
 
         // Build from-reference transforms.
@@ -508,14 +953,13 @@ impl OCIOConfig {
                 ),
                 matrix::xyz_to_rgb_matrix(self.reference_space_chroma),
             ]))
-            .unwrap(),
+            .ok_or_else(|| String::from("the chromaticities don't form a valid gamut"))?,
         )));
-        if let Some(to_linear) = to_linear_transform {
-            from_reference_transforms.push(to_linear.invert());
+        for t in to_linear_transforms.into_iter().rev() {
+            from_reference_transforms.push(t.try_invert()?);
         }
-        if use_gamut_clipping && !gamut_is_within_gamut(self.reference_space_chroma, chromaticities)
-        {
-            from_reference_transforms.extend_from_slice(&gamut_map::hsv_gamut_clip());
+        if !gamut_is_within_gamut(self.reference_space_chroma, chromaticities) {
+            from_reference_transforms.extend(self.gamut_mapping_transforms(gamut_mapping));
         }
 
         // Add the colorspace.
@@ -529,6 +973,8 @@ impl OCIOConfig {
             from_reference: from_reference_transforms,
             ..ColorSpace::default()
         });
+
+        Ok(())
     }
 
     /// Adds a display color space with basic gamut clipping.
@@ -597,6 +1043,76 @@ impl OCIOConfig {
         });
     }
 
+    /// Adds theatrical P3 displays, with 2.6 gamma and gamut clipping:
+    /// "DCI-P3" with the DCI white point, and "P3-D65" with a D65 white
+    /// point.
+    ///
+    /// Displays that already exist in the config are left alone.  The
+    /// "Raw" view uses the same color space as other displays' "Raw"
+    /// views, falling back to the `data` role's color space.
+    pub fn add_p3_displays(&mut self, whitepoint_adaptation_method: AdaptationMethod) {
+        for (display_name, chromaticities) in
+            [("DCI-P3", chroma::DCI_P3), ("P3-D65", chroma::DISPLAY_P3)]
+        {
+            if self.displays.iter().any(|d| d.name == display_name) {
+                continue;
+            }
+
+            let space_name = format!("{} Gamut Clipped", display_name);
+            self.add_display_colorspace(
+                space_name.clone(),
+                None,
+                chromaticities,
+                whitepoint_adaptation_method,
+                vec![],
+                Transform::ExponentTransform(2.6, 2.6, 2.6, 1.0).invert(),
+                true,
+            );
+
+            let mut views = vec![("Standard".into(), space_name)];
+            let raw_space = self
+                .displays
+                .iter()
+                .flat_map(|d| d.views.iter())
+                .find(|(view, _)| view == "Raw")
+                .map(|(_, space)| space.clone())
+                .or_else(|| self.roles.data.clone());
+            if let Some(raw_space) = raw_space {
+                views.push(("Raw".into(), raw_space));
+            }
+            self.displays.push(Display {
+                name: display_name.into(),
+                views: views,
+            });
+            if !self.active_displays.is_empty() {
+                self.active_displays.push(display_name.into());
+            }
+        }
+    }
+
+    /// The transforms for `gamut_mapping`, generating any LUTs they
+    /// need.
+    fn gamut_mapping_transforms(&mut self, gamut_mapping: GamutMapping) -> Vec<Transform> {
+        match gamut_mapping {
+            GamutMapping::None => Vec::new(),
+            GamutMapping::Clip => gamut_map::hsv_gamut_clip(),
+            GamutMapping::AcesCompress => vec![gamut_map::aces_gamut_compress_transform()],
+            GamutMapping::SoftCompress => {
+                // Luminance of the reference space, since that's what
+                // the compression preserves.
+                let luminance_weights = matrix::rgb_to_xyz_matrix(self.reference_space_chroma)[1];
+
+                self.add_search_path(GAMUT_DIR);
+                self.output_files
+                    .entry(Path::new(GAMUT_DIR).join(SOFT_COMPRESS_LUT_FILENAME))
+                    .or_insert_with(|| {
+                        OutputFile::Lut3D(gamut_map::make_soft_compress_lut(luminance_weights))
+                    });
+                gamut_map::hsv_soft_compress(SOFT_COMPRESS_LUT_FILENAME)
+            }
+        }
+    }
+
     /// Creates and adds the default gamut clipping luts, if
     /// they haven't been already.
     pub fn generate_gamut_clipping_luts(&mut self) {
@@ -609,7 +1125,7 @@ impl OCIOConfig {
         let input_luminance_weights = [1.0 / 13.0, 11.0 / 13.0, 1.0 / 13.0];
         let output_luminance_weights = [1.0 / 6.0, 4.0 / 6.0, 1.0 / 6.0];
 
-        self.search_path.insert(GAMUT_DIR.into());
+        self.add_search_path(GAMUT_DIR);
 
         let res = 3 * 4;
         let upper = (3 * (1i64 << 32)) as f64;
@@ -657,13 +1173,109 @@ impl OCIOConfig {
     }
 }
 
+/// An error encountered while writing a config to disk.
+#[derive(Debug)]
+pub enum WriteError {
+    /// The path exists, but we don't have permission to write to it.
+    NotWritable(PathBuf),
+    /// Any other IO error, along with the path it happened at.
+    IoErr(PathBuf, std::io::Error),
+}
+
+impl WriteError {
+    fn new(path: &Path, e: std::io::Error) -> WriteError {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            WriteError::NotWritable(path.into())
+        } else {
+            WriteError::IoErr(path.into(), e)
+        }
+    }
+}
+
+impl std::error::Error for WriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WriteError::NotWritable(_) => None,
+            WriteError::IoErr(_, ref e) => Some(e),
+        }
+    }
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::NotWritable(path) => write!(
+                f,
+                "no permission to write to \"{}\".  Please choose a different location.",
+                path.display()
+            ),
+            WriteError::IoErr(path, e) => write!(f, "couldn't write \"{}\": {}", path.display(), e),
+        }
+    }
+}
+
+/// Replaces a single file with `contents`, staging it the same way as
+/// `OCIOConfig::write_to_directory()`: it's written to a staging
+/// directory next to it and then moved into place, so that failing
+/// partway through leaves the original file untouched.
+pub fn write_file_staged(path: &Path, contents: &[u8]) -> Result<(), WriteError> {
+    let dir_path = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| WriteError::new(path, std::io::ErrorKind::InvalidInput.into()))?;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.permissions().readonly() {
+            return Err(WriteError::NotWritable(path.into()));
+        }
+    }
+
+    let staging_dir = dir_path.join(STAGING_DIR);
+    prepare_staging_dir(dir_path, &staging_dir)?;
+    let staged_path = staging_dir.join(file_name);
+    let result = crate::ensure_dir_exists(&staging_dir)
+        .and_then(|_| {
+            let mut f = std::fs::File::create(&staged_path)?;
+            f.write_all(contents)?;
+            f.sync_all()
+        })
+        .map_err(|e| WriteError::new(path, e))
+        .and_then(|_| std::fs::rename(&staged_path, path).map_err(|e| WriteError::new(path, e)));
+    let _ = std::fs::remove_dir_all(&staging_dir);
+
+    result
+}
+
+/// Clears out any staging directory left over from an earlier failed
+/// write, and checks that `dir_path` is writable by creating and
+/// removing the staging directory.
+///
+/// Permission bits don't tell the whole story (e.g. ACLs or ownership),
+/// so actually creating something is the only reliable check, and doing
+/// it up front means a read-only directory doesn't fail partway through
+/// staging.
+fn prepare_staging_dir(dir_path: &Path, staging_dir: &Path) -> Result<(), WriteError> {
+    if staging_dir.exists() {
+        // Left over from an earlier failed write.
+        std::fs::remove_dir_all(staging_dir).map_err(|e| WriteError::new(staging_dir, e))?;
+    }
+    std::fs::create_dir(staging_dir)
+        .and_then(|_| std::fs::remove_dir(staging_dir))
+        .map_err(|_| WriteError::NotWritable(dir_path.into()))
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ValidationError {
     DuplicateColorSpace(String),
     DuplicateDisplay(String),
     DuplicateRole(String),
     DuplicateLook(String),
+    DuplicateRule(String),
+    FileRulesWithoutDefault,
     ReferenceToAbsentColorSpace(String),
+    UndeclaredContextVariable(String),
 }
 
 /// Specifies what color spaces to use for various purposes.
@@ -700,6 +1312,26 @@ pub struct Roles {
     pub other: HashMap<String, String>, // role_name -> colorspace_name
 }
 
+impl Roles {
+    /// All of the assigned roles, as role name -> color space name.
+    pub fn as_map(&self) -> HashMap<String, String> {
+        let mut map = self.other.clone();
+        let named = [
+            ("reference", &self.reference),
+            ("aces_interchange", &self.aces_interchange),
+            ("cie_xyz_d65_interchange", &self.cie_xyz_d65_interchange),
+            ("default", &self.default),
+            ("data", &self.data),
+        ];
+        for (role, space) in named.iter() {
+            if let Some(space) = space {
+                map.insert((*role).into(), space.clone());
+            }
+        }
+        map
+    }
+}
+
 impl Default for Roles {
     fn default() -> Roles {
         Roles {
@@ -713,6 +1345,41 @@ impl Default for Roles {
     }
 }
 
+/// An OCIO v2 file rule, for assigning color spaces to files based on
+/// their paths.
+///
+/// Rules are tried in order, and the last one must be the `Default`
+/// rule.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FileRule {
+    pub name: String,
+    pub colorspace: String, // Color space or role.  Unused by `ColorSpaceNamePathSearch`.
+    pub kind: FileRuleKind,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FileRuleKind {
+    /// Glob patterns for the file path (minus extension) and extension.
+    Pattern {
+        pattern: String,
+        extension: String,
+    },
+    Regex(String),
+    /// Uses the color space whose name appears last in the file path.
+    ColorSpaceNamePathSearch,
+    /// Used when no other rule matches.
+    Default,
+}
+
+/// An OCIO v2 viewing rule, which views can refer to in order to only
+/// be offered for certain color spaces or encodings.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ViewingRule {
+    pub name: String,
+    pub colorspaces: Vec<String>,
+    pub encodings: Vec<Encoding>,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Display {
     pub name: String,
@@ -763,6 +1430,38 @@ impl Default for ColorSpace {
     }
 }
 
+/// An OCIO v2 named transform: a transform that's applied as-is,
+/// without going through the reference space (e.g. a "curve only"
+/// utility transform).
+///
+/// At least one of `transform` and `inverse_transform` needs to be
+/// filled in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedTransform {
+    pub name: String,
+    pub description: String,
+    pub aliases: Vec<String>,
+    pub family: String,
+    pub encoding: Option<Encoding>,
+
+    pub transform: Vec<Transform>,
+    pub inverse_transform: Vec<Transform>,
+}
+
+impl Default for NamedTransform {
+    fn default() -> NamedTransform {
+        NamedTransform {
+            name: String::new(),
+            description: String::new(),
+            aliases: Vec::new(),
+            family: String::new(),
+            encoding: None,
+            transform: Vec::new(),
+            inverse_transform: Vec::new(),
+        }
+    }
+}
+
 /// A color transform.
 ///
 /// `GroupTransform` is not represented here, as all places
@@ -772,7 +1471,7 @@ impl Default for ColorSpace {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Transform {
     FileTransform {
-        src: PathBuf,
+        src: String, // Can use context variables.
         interpolation: Interpolation,
         direction_inverse: bool, // Whether to apply it in reverse.
     },
@@ -837,14 +1536,20 @@ pub enum Transform {
         direction_inverse: bool,
     },
     CDLTransform {
-        // TODO: the other fields.
+        // TODO: the style field.
         // style: CDLStyle,
-        // slope: [f32; 3],
-        // offset: [f32; 3],
+        slope: [f32; 3],
+        offset: [f32; 3],
         power: [f32; 3],
-        // sat: f32,
+        saturation: f32, // "sat" in the config.
         direction_inverse: bool,
     },
+    /// A transform that's written to the config verbatim, as single-line
+    /// flow-style YAML (e.g. `!<LogCameraTransform> { base: 2 }`).
+    ///
+    /// Used to carry through transforms from imported configs that
+    /// aren't otherwise supported.
+    Raw(String),
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -862,6 +1567,15 @@ impl GradingStyle {
             GradingStyle::Video => "video",
         }
     }
+
+    pub fn from_str(text: &str) -> Option<GradingStyle> {
+        match text.trim() {
+            "linear" => Some(GradingStyle::Linear),
+            "log" => Some(GradingStyle::Log),
+            "video" => Some(GradingStyle::Video),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -878,10 +1592,42 @@ pub struct Tone {
 //     NoClamp, // "noclamp" in the config.
 // }
 
+/// Inverts a 4x4 matrix whose last row is `[0, 0, 0, 1]`, i.e. a 3x3
+/// matrix plus offsets.
+///
+/// Returns an error if the 3x3 portion isn't invertible.
+fn invert_affine_4x4(m: [f32; 16]) -> Result<[f32; 16], String> {
+    let inv = matrix::invert([
+        [m[0] as f64, m[1] as f64, m[2] as f64],
+        [m[4] as f64, m[5] as f64, m[6] as f64],
+        [m[8] as f64, m[9] as f64, m[10] as f64],
+    ])
+    .ok_or_else(|| String::from("the MatrixTransform's matrix isn't invertible"))?;
+    let offset = [m[3] as f64, m[7] as f64, m[11] as f64];
+    let inv_offset = matrix::transform_color(offset, inv);
+
+    let mut out = matrix::to_4x4_f32(inv);
+    out[3] = -inv_offset[0] as f32;
+    out[7] = -inv_offset[1] as f32;
+    out[11] = -inv_offset[2] as f32;
+    Ok(out)
+}
+
 impl Transform {
+    /// Returns the inverse transform.
+    ///
+    /// Panics if the transform can't be inverted, so transforms that
+    /// come from users or files should use `try_invert()` instead.
     pub fn invert(self) -> Self {
+        self.try_invert()
+            .unwrap_or_else(|e| panic!("Transform can't be inverted: {}", e))
+    }
+
+    /// Returns the inverse transform, or an error if it can't be
+    /// inverted: raw transforms, and matrices that are singular.
+    pub fn try_invert(self) -> Result<Self, String> {
         use Transform::*;
-        match self {
+        Ok(match self {
             FileTransform {
                 src,
                 interpolation,
@@ -894,7 +1640,7 @@ impl Transform {
 
             ColorSpaceTransform { src, dst } => ColorSpaceTransform { src: dst, dst: src },
 
-            MatrixTransform(_) => todo!(),
+            MatrixTransform(m) => MatrixTransform(invert_affine_4x4(m)?),
 
             BuiltinTransform {
                 name,
@@ -982,14 +1728,87 @@ impl Transform {
             },
 
             CDLTransform {
+                slope,
+                offset,
                 power,
+                saturation,
                 direction_inverse,
             } => CDLTransform {
+                slope: slope,
+                offset: offset,
                 power: power,
-                direction_inverse: direction_inverse,
+                saturation: saturation,
+                direction_inverse: !direction_inverse,
             },
+
+            Raw(text) => return Err(format!("raw transforms cannot be inverted: {}", text)),
+        })
+    }
+}
+
+/// Finds the output file that a file transform's `src` refers to, via
+/// the search path.
+pub(crate) fn find_output_file<'a>(
+    search_path: &[String],
+    output_files: &'a HashMap<PathBuf, OutputFile>,
+    src: &str,
+) -> Option<&'a PathBuf> {
+    search_path
+        .iter()
+        .map(|dir| Path::new(dir).join(src))
+        .chain(std::iter::once(PathBuf::from(src)))
+        .find_map(|path| output_files.get_key_value(&path).map(|(k, _)| k))
+}
+
+/// Double-quotes a string for YAML, e.g. for glob patterns, which would
+/// otherwise be misread.
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+/// Returns the names of the context variables (e.g. `$SHOT` or
+/// `${SHOT}`) used in `text`, in order of first use.
+pub fn context_variables(text: &str) -> Vec<String> {
+    let mut variables = Vec::new();
+    replace_context_variables(text, |name| {
+        if !variables.iter().any(|v| v == name) {
+            variables.push(name.into());
+        }
+        String::new()
+    });
+    variables
+}
+
+/// Replaces each context variable in `text` with what `f` returns for
+/// its name.
+fn replace_context_variables<F: FnMut(&str) -> String>(text: &str, mut f: F) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        let after = &rest[(i + 1)..];
+        let (name, len) = if after.starts_with('{') {
+            match after.find('}') {
+                Some(end) => (&after[1..end], end + 1),
+                None => ("", 0),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+        if name.is_empty() {
+            // Not a variable, just a dollar sign.
+            out.push('$');
+            rest = after;
+        } else {
+            out.push_str(&f(name));
+            rest = &after[len..];
         }
     }
+    out.push_str(rest);
+    out
 }
 
 pub fn write_transform_yaml<W: std::io::Write>(
@@ -1008,7 +1827,11 @@ pub fn write_transform_yaml<W: std::io::Write>(
         } => {
             format!(
                 "!<FileTransform> {{ src: {}, interpolation: {}{} }}",
-                src.to_string_lossy(),
+                if src.contains('$') {
+                    quoted(src)
+                } else {
+                    src.clone()
+                },
                 interpolation.as_str(),
                 if direction_inverse {
                     ", direction: inverse"
@@ -1126,11 +1949,11 @@ pub fn write_transform_yaml<W: std::io::Write>(
             format!(
                 "!<FixedFunctionTransform> {{ style: ACES_GamutComp13, params: [{}, {}, {}, {}, {}, {}, {}]{} }}",
                 limit[0],
-                limit[0],
-                limit[0],
-                threshhold[0],
-                threshhold[0],
+                limit[1],
+                limit[2],
                 threshhold[0],
+                threshhold[1],
+                threshhold[2],
                 power,
                 if direction_inverse {
                     ", direction: inverse"
@@ -1244,21 +2067,34 @@ pub fn write_transform_yaml<W: std::io::Write>(
         }
 
         &Transform::CDLTransform {
+            slope,
+            offset,
             power,
+            saturation,
             direction_inverse,
         } => {
-            format!(
-                "!<CDLTransform> {{ power: [{}, {}, {}]{} }}",
-                power[0],
-                power[1],
-                power[2],
-                if direction_inverse {
-                    ", direction: inverse"
-                } else {
-                    ""
-                },
-            )
+            // Only non-default values are written, to keep things tidy.
+            let mut fields = Vec::new();
+            if slope != [1.0; 3] {
+                fields.push(format!("slope: [{}, {}, {}]", slope[0], slope[1], slope[2]));
+            }
+            if offset != [0.0; 3] {
+                fields.push(format!(
+                    "offset: [{}, {}, {}]",
+                    offset[0], offset[1], offset[2]
+                ));
+            }
+            fields.push(format!("power: [{}, {}, {}]", power[0], power[1], power[2]));
+            if saturation != 1.0 {
+                fields.push(format!("sat: {}", saturation));
+            }
+            if direction_inverse {
+                fields.push("direction: inverse".into());
+            }
+            format!("!<CDLTransform> {{ {} }}", fields.join(", "))
         }
+
+        &Transform::Raw(ref text) => text.clone(),
     };
 
     if transforms.len() == 1 {
@@ -1276,6 +2112,27 @@ pub fn write_transform_yaml<W: std::io::Write>(
     Ok(())
 }
 
+/// The OCIO profile version a config is written as.
+///
+/// Sections and fields that don't exist in v1 (named transforms,
+/// inactive color spaces, aliases, etc.) are left out when writing a
+/// v1 config, but transforms are written as-is.  See `v1_compat` for
+/// making a config's transforms v1 compatible.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ProfileVersion {
+    V1,
+    V2_1,
+}
+
+impl ProfileVersion {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ProfileVersion::V1 => "1",
+            ProfileVersion::V2_1 => "2.1",
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Interpolation {
     Nearest,
@@ -1306,7 +2163,7 @@ pub enum Encoding {
 }
 
 impl Encoding {
-    fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &'static str {
         match *self {
             Encoding::SceneLinear => "scene-linear",
             Encoding::DisplayLinear => "display-linear",
@@ -1316,6 +2173,18 @@ impl Encoding {
             Encoding::Data => "data",
         }
     }
+
+    pub fn from_str(text: &str) -> Option<Encoding> {
+        match text.trim() {
+            "scene-linear" => Some(Encoding::SceneLinear),
+            "display-linear" => Some(Encoding::DisplayLinear),
+            "log" => Some(Encoding::Log),
+            "sdr-video" => Some(Encoding::SDRVideo),
+            "hdr-video" => Some(Encoding::HDRVideo),
+            "data" => Some(Encoding::Data),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -1346,6 +2215,20 @@ impl BitDepth {
             BitDepth::F32 => "32f",
         }
     }
+
+    pub fn from_str(text: &str) -> Option<BitDepth> {
+        match text.trim() {
+            "8ui" => Some(BitDepth::UI8),
+            "10ui" => Some(BitDepth::UI10),
+            "12ui" => Some(BitDepth::UI12),
+            "14ui" => Some(BitDepth::UI14),
+            "16ui" => Some(BitDepth::UI16),
+            "32ui" => Some(BitDepth::UI32),
+            "16f" => Some(BitDepth::F16),
+            "32f" => Some(BitDepth::F32),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -1370,6 +2253,77 @@ pub enum OutputFile {
     Lut3D(Lut3D),
 }
 
+impl OutputFile {
+    /// Writes the file in the format indicated by `output_path`'s
+    /// extension.
+    pub fn write<W: std::io::Write>(&self, output_path: &Path, mut f: W) -> std::io::Result<()> {
+        match *self {
+            OutputFile::Raw(ref data) => f.write_all(data)?,
+            OutputFile::Lut1D(ref lut) => {
+                match output_path.extension().map(|e| e.to_str()).flatten() {
+                    Some("spi1d") => {
+                        if lut.ranges.len() > 1 {
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::Other,
+                                ".spi1d files don't support per-channel input ranges",
+                            ));
+                        } else {
+                            let tables: Vec<&[f32]> =
+                                lut.tables.iter().map(|t| t.as_ref()).collect();
+                            colorbox::formats::spi1d::write(
+                                &mut f,
+                                lut.ranges[0].0,
+                                lut.ranges[0].1,
+                                &tables,
+                            )?;
+                        }
+                    }
+
+                    Some("cube") => {
+                        let ranges = match lut.ranges.len() {
+                            1 => [lut.ranges[0], lut.ranges[0], lut.ranges[0]],
+                            2 => [lut.ranges[0], lut.ranges[1], lut.ranges[1]],
+                            _ => [lut.ranges[0], lut.ranges[1], lut.ranges[2]],
+                        };
+                        let tables = match lut.tables.len() {
+                            1 => [&lut.tables[0][..], &lut.tables[0][..], &lut.tables[0][..]],
+                            2 => [&lut.tables[0][..], &lut.tables[1][..], &lut.tables[1][..]],
+                            _ => [&lut.tables[0][..], &lut.tables[1][..], &lut.tables[2][..]],
+                        };
+                        colorbox::formats::cube_iridas::write_1d(&mut f, ranges, tables)?;
+                    }
+
+                    _ => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            "Unsupported LUT output file format",
+                        ))
+                    }
+                }
+            }
+            OutputFile::Lut3D(ref lut) => {
+                match output_path.extension().map(|e| e.to_str()).flatten() {
+                    Some("cube") => colorbox::formats::cube_iridas::write_3d(
+                        &mut f,
+                        lut.range,
+                        lut.resolution[0],
+                        [&lut.tables[0], &lut.tables[1], &lut.tables[2]],
+                    )?,
+
+                    _ => {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            "Unsupported LUT output file format",
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// Returns true if `g1` is fully encompassed by `g2`.
 fn gamut_is_within_gamut(g1: Chromaticities, g2: Chromaticities) -> bool {
     fn sign(pa: (f64, f64), pb1: (f64, f64), pb2: (f64, f64)) -> f64 {
@@ -1583,6 +2537,20 @@ mod tests {
     use super::*;
     use colorbox::chroma;
 
+    #[test]
+    fn write_file_staged_01() {
+        let dir = std::env::temp_dir().join(format!("ocio_gen_staged_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.ocio");
+
+        std::fs::write(&path, "old").unwrap();
+        write_file_staged(&path, b"new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert!(!dir.join(STAGING_DIR).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn gamut_is_within_gamut_01() {
         assert_eq!(gamut_is_within_gamut(chroma::REC709, chroma::REC2020), true);
@@ -1596,4 +2564,101 @@ mod tests {
             true
         );
     }
+
+    #[test]
+    fn context_variables_01() {
+        assert_eq!(
+            context_variables("shots/$SEQ/${SHOT}_$SEQ.cube"),
+            vec!["SEQ".to_string(), "SHOT".to_string()]
+        );
+        assert_eq!(context_variables("costs $5 or $"), vec!["5".to_string()]);
+        assert_eq!(context_variables("luts"), Vec::<String>::new());
+
+        let mut config = OCIOConfig::default();
+        config.environment.push(("SHOT".into(), "sh010".into()));
+        assert_eq!(
+            config.expand_context_defaults("grades/${SHOT}.cube:$SEQ"),
+            "grades/sh010.cube:"
+        );
+    }
+
+    #[test]
+    fn add_per_shot_look_01() {
+        let mut config = OCIOConfig::default();
+        config.colorspaces.push(ColorSpace {
+            name: "Log".into(),
+            ..ColorSpace::default()
+        });
+        config.add_per_shot_look("Shot Grade".into(), "Log".into(), "grades", "SHOT");
+
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.search_path, vec!["grades"]);
+        assert!(config
+            .output_files
+            .contains_key(Path::new("grades/identity.cube")));
+
+        config.environment.clear();
+        assert_eq!(
+            config.validate(),
+            Err(ValidationError::UndeclaredContextVariable("SHOT".into()))
+        );
+    }
+
+    #[test]
+    fn deduplicate_output_files_01() {
+        let mut config = OCIOConfig::default();
+        config.add_search_path("luts");
+        for (name, data) in [("a", b"same"), ("b", b"same"), ("c", b"diff")].iter() {
+            config.output_files.insert(
+                Path::new("luts").join(format!("{}.spi1d", name)),
+                OutputFile::Raw(data.to_vec()),
+            );
+            config.colorspaces.push(ColorSpace {
+                name: name.to_string(),
+                to_reference: vec![Transform::FileTransform {
+                    src: format!("{}.spi1d", name),
+                    interpolation: Interpolation::Linear,
+                    direction_inverse: false,
+                }],
+                ..ColorSpace::default()
+            });
+        }
+
+        assert_eq!(config.deduplicate_output_files(), 1);
+        assert_eq!(config.output_files.len(), 2);
+        let srcs: Vec<&str> = config
+            .colorspaces
+            .iter()
+            .map(|s| match s.to_reference[0] {
+                Transform::FileTransform { ref src, .. } => src.as_str(),
+                _ => panic!(),
+            })
+            .collect();
+        assert_eq!(srcs, vec!["a.spi1d", "a.spi1d", "c.spi1d"]);
+    }
+
+    #[test]
+    fn add_p3_displays_01() {
+        let mut config = OCIOConfig::default();
+        config.displays.push(Display {
+            name: "DCI-P3".into(),
+            views: vec![("Raw".into(), "Raw".into())],
+        });
+        config.roles.data = Some("Non-Color".into());
+
+        config.add_p3_displays(AdaptationMethod::Bradford);
+        config.add_p3_displays(AdaptationMethod::Bradford);
+
+        assert_eq!(config.displays.len(), 2);
+        assert_eq!(config.displays[1].name, "P3-D65");
+        assert_eq!(
+            config.displays[1].views,
+            vec![
+                ("Standard".into(), "P3-D65 Gamut Clipped".into()),
+                ("Raw".into(), "Raw".into()),
+            ]
+        );
+        assert_eq!(config.colorspaces.len(), 1);
+        assert!(config.active_displays.is_empty());
+    }
 }
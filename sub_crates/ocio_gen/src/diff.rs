@@ -0,0 +1,232 @@
+//! Comparing a config against a previously written version of it.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::OCIOConfig,
+    import::{parse_config, read_config, ImportError},
+};
+
+/// The differences between a config on disk and one about to replace it.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigDiff {
+    pub added_colorspaces: Vec<String>,
+    pub removed_colorspaces: Vec<String>,
+    pub changed_colorspaces: Vec<String>,
+
+    /// (role, old color space, new color space).
+    pub role_changes: Vec<(String, Option<String>, Option<String>)>,
+
+    pub added_files: Vec<PathBuf>,
+    pub removed_files: Vec<PathBuf>,
+    /// (path, old checksum, new checksum).
+    pub changed_files: Vec<(PathBuf, u64, u64)>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_colorspaces.is_empty()
+            && self.removed_colorspaces.is_empty()
+            && self.changed_colorspaces.is_empty()
+            && self.role_changes.is_empty()
+            && self.added_files.is_empty()
+            && self.removed_files.is_empty()
+            && self.changed_files.is_empty()
+    }
+}
+
+impl std::fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes.");
+        }
+        for name in self.added_colorspaces.iter() {
+            writeln!(f, "+ color space \"{}\"", name)?;
+        }
+        for name in self.removed_colorspaces.iter() {
+            writeln!(f, "- color space \"{}\"", name)?;
+        }
+        for name in self.changed_colorspaces.iter() {
+            writeln!(f, "~ color space \"{}\"", name)?;
+        }
+        for (role, old, new) in self.role_changes.iter() {
+            writeln!(
+                f,
+                "~ role \"{}\": {} -> {}",
+                role,
+                old.as_deref().unwrap_or("(none)"),
+                new.as_deref().unwrap_or("(none)"),
+            )?;
+        }
+        for path in self.added_files.iter() {
+            writeln!(f, "+ file \"{}\"", path.to_string_lossy())?;
+        }
+        for path in self.removed_files.iter() {
+            writeln!(f, "- file \"{}\"", path.to_string_lossy())?;
+        }
+        for (path, old, new) in self.changed_files.iter() {
+            writeln!(
+                f,
+                "~ file \"{}\": {:016x} -> {:016x}",
+                path.to_string_lossy(),
+                old,
+                new
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares `config` against the config previously written to `dir_path`.
+///
+/// Returns `None` if there is no config in `dir_path` yet.
+pub fn diff_against_directory<P: AsRef<Path>>(
+    config: &OCIOConfig,
+    dir_path: P,
+) -> Result<Option<ConfigDiff>, ImportError> {
+    let dir_path: &Path = dir_path.as_ref();
+    let config_path = dir_path.join("config.ocio");
+    if !config_path.is_file() {
+        return Ok(None);
+    }
+
+    let (old_config, _) = read_config(&config_path)?;
+
+    // Round-trip the new config through the same parser as the old one,
+    // so that only real differences show up, not differences in how the
+    // transforms happen to be represented.
+    let new_config = {
+        let mut text = Vec::new();
+        config.write_config_file(&mut text)?;
+        parse_config(&String::from_utf8_lossy(&text))?.0
+    };
+
+    let mut diff = diff_configs(&old_config, &new_config);
+
+    // Files.  The new config's files are compared against whatever is
+    // currently on disk at the same path.
+    let mut new_paths: Vec<&PathBuf> = config.output_files.keys().collect();
+    new_paths.sort();
+    for path in new_paths {
+        let mut data = Vec::new();
+        config.output_files[path].write(path, &mut data)?;
+        match std::fs::read(dir_path.join(path)) {
+            Ok(old_data) => {
+                let (old_sum, new_sum) = (checksum(&old_data), checksum(&data));
+                if old_sum != new_sum {
+                    diff.changed_files.push((path.clone(), old_sum, new_sum));
+                }
+            }
+            Err(_) => diff.added_files.push(path.clone()),
+        }
+    }
+    let mut old_paths: Vec<&PathBuf> = old_config
+        .output_files
+        .keys()
+        .filter(|path| !config.output_files.contains_key(*path))
+        .collect();
+    old_paths.sort();
+    diff.removed_files
+        .extend(old_paths.into_iter().map(|p| p.clone()));
+
+    Ok(Some(diff))
+}
+
+/// Compares the color spaces and roles of two configs.
+pub fn diff_configs(old: &OCIOConfig, new: &OCIOConfig) -> ConfigDiff {
+    let mut diff = ConfigDiff::default();
+
+    // Color spaces.
+    for space in new.colorspaces.iter() {
+        match old.colorspaces.iter().find(|s| s.name == space.name) {
+            None => diff.added_colorspaces.push(space.name.clone()),
+            Some(old_space) if old_space != space => {
+                diff.changed_colorspaces.push(space.name.clone())
+            }
+            _ => {}
+        }
+    }
+    for space in old.colorspaces.iter() {
+        if !new.colorspaces.iter().any(|s| s.name == space.name) {
+            diff.removed_colorspaces.push(space.name.clone());
+        }
+    }
+
+    // Roles.
+    let old_roles = old.roles.as_map();
+    let new_roles = new.roles.as_map();
+    let mut role_names: Vec<&String> = old_roles
+        .keys()
+        .chain(new_roles.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    role_names.sort();
+    for role in role_names {
+        let old_space = old_roles.get(role);
+        let new_space = new_roles.get(role);
+        if old_space != new_space {
+            diff.role_changes.push((
+                role.clone(),
+                old_space.map(|s| s.clone()),
+                new_space.map(|s| s.clone()),
+            ));
+        }
+    }
+
+    diff
+}
+
+/// 64-bit FNV-1a hash.  Not cryptographic, just for spotting changes.
+fn checksum(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ColorSpace;
+
+    fn space(name: &str, family: &str) -> ColorSpace {
+        ColorSpace {
+            name: name.into(),
+            family: family.into(),
+            ..ColorSpace::default()
+        }
+    }
+
+    #[test]
+    fn diff_configs_01() {
+        let mut old = OCIOConfig::default();
+        old.colorspaces.push(space("A", ""));
+        old.colorspaces.push(space("B", ""));
+        old.roles.default = Some("A".into());
+
+        let mut new = OCIOConfig::default();
+        new.colorspaces.push(space("B", "Changed"));
+        new.colorspaces.push(space("C", ""));
+        new.roles.default = Some("C".into());
+        new.roles.other.insert("matte_paint".into(), "B".into());
+
+        let diff = diff_configs(&old, &new);
+        assert_eq!(diff.added_colorspaces, vec![String::from("C")]);
+        assert_eq!(diff.removed_colorspaces, vec![String::from("A")]);
+        assert_eq!(diff.changed_colorspaces, vec![String::from("B")]);
+        assert_eq!(
+            diff.role_changes,
+            vec![
+                ("default".into(), Some("A".into()), Some("C".into())),
+                ("matte_paint".into(), None, Some("B".into())),
+            ]
+        );
+        assert!(diff_configs(&new, &new).is_empty());
+    }
+}
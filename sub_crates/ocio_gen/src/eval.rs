@@ -0,0 +1,639 @@
+//! A CPU evaluator for (most of) the transforms of a config.
+//!
+//! This isn't a replacement for OCIO, and only aims to be accurate
+//! enough for checking configs and baking LUTs.  Transforms that aren't
+//! supported produce an error rather than a wrong result.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use colorbox::{
+    formats,
+    lut::{Lut1D, Lut3D},
+};
+
+use crate::{
+    config::{Allocation, ColorSpace, OCIOConfig, OutputFile, Transform},
+    lut_extrapolation::{self, Extrapolation},
+};
+
+/// How deeply `ColorSpaceTransform`s may nest before we give up.
+const MAX_DEPTH: usize = 16;
+
+enum LoadedLut {
+    Lut1D(Lut1D),
+    Lut3D(Option<Lut1D>, Lut3D), // (shaper, lut)
+}
+
+/// Evaluates the transforms of a config, using its output files for
+/// any `FileTransform`s.
+pub struct Evaluator<'a> {
+    config: &'a OCIOConfig,
+    luts: HashMap<PathBuf, LoadedLut>,
+
+    /// Output files that couldn't be read as LUTs.  Only an error if
+    /// they're actually used.
+    pub unreadable_files: Vec<PathBuf>,
+
+    /// How 1D LUTs (including the shapers of 3D LUTs) treat input
+    /// outside of their domain.  Defaults to clamping, like OCIO.
+    pub extrapolation: Extrapolation,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(config: &'a OCIOConfig) -> Evaluator<'a> {
+        let mut luts = HashMap::new();
+        let mut unreadable_files = Vec::new();
+
+        let mut files: Vec<(&PathBuf, &OutputFile)> = config
+            .output_files
+            .iter()
+            .chain(config.external_files.iter())
+            .collect();
+        files.sort_by(|a, b| a.0.cmp(b.0));
+        for (path, file) in files {
+            let lut = match *file {
+                OutputFile::Raw(ref data) => read_lut(path, data),
+                OutputFile::Lut1D(ref lut) => Some(LoadedLut::Lut1D(lut.clone())),
+                OutputFile::Lut3D(ref lut) => Some(LoadedLut::Lut3D(None, lut.clone())),
+            };
+            match lut {
+                Some(lut) => {
+                    luts.insert(path.clone(), lut);
+                }
+                None => unreadable_files.push(path.clone()),
+            }
+        }
+
+        Evaluator {
+            config: config,
+            luts: luts,
+            unreadable_files: unreadable_files,
+            extrapolation: Extrapolation::Clamp,
+        }
+    }
+
+    /// Finds a color space by name, alias, or role.
+    pub fn colorspace(&self, name: &str) -> Result<&'a ColorSpace, String> {
+        let name = self
+            .config
+            .roles
+            .as_map()
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.into());
+        self.config
+            .colorspaces
+            .iter()
+            .find(|s| s.name == name || s.aliases.contains(&name))
+            .ok_or_else(|| format!("color space \"{}\" doesn't exist", name))
+    }
+
+    /// The transforms from a color space to the reference space.
+    pub fn to_reference(&self, name: &str) -> Result<Vec<Transform>, String> {
+        let space = self.colorspace(name)?;
+        if space.to_reference.is_empty() {
+            inverted(&space.from_reference)
+        } else {
+            Ok(space.to_reference.clone())
+        }
+    }
+
+    /// The transforms from the reference space to a color space.
+    pub fn from_reference(&self, name: &str) -> Result<Vec<Transform>, String> {
+        let space = self.colorspace(name)?;
+        if space.from_reference.is_empty() {
+            inverted(&space.to_reference)
+        } else {
+            Ok(space.from_reference.clone())
+        }
+    }
+
+    /// The transforms from `src` to `dst`, which are color space names,
+    /// aliases, or roles.
+    pub fn processor(&self, src: &str, dst: &str) -> Result<Vec<Transform>, String> {
+        let src_space = self.colorspace(src)?;
+        let dst_space = self.colorspace(dst)?;
+        if src_space.name == dst_space.name
+            || src_space.isdata == Some(true)
+            || dst_space.isdata == Some(true)
+        {
+            return Ok(Vec::new());
+        }
+
+        let mut transforms = self.to_reference(src)?;
+        transforms.extend(self.from_reference(dst)?);
+        Ok(transforms)
+    }
+
+    /// The transforms from `src` to a display's view.
+    pub fn view_processor(
+        &self,
+        src: &str,
+        display: &str,
+        view: &str,
+    ) -> Result<Vec<Transform>, String> {
+        let display_def = self
+            .config
+            .displays
+            .iter()
+            .find(|d| d.name == display)
+            .ok_or_else(|| format!("display \"{}\" doesn't exist", display))?;
+        let (_, dst) = display_def
+            .views
+            .iter()
+            .find(|(name, _)| name == view)
+            .ok_or_else(|| format!("display \"{}\" doesn't have a view \"{}\"", display, view))?;
+        self.processor(src, dst)
+    }
+
+    /// Bakes a chain of transforms into a 3D LUT with `resolution`
+    /// samples per axis, covering `range` on all axes.
+    pub fn bake_lut_3d(
+        &self,
+        transforms: &[Transform],
+        resolution: usize,
+        range: (f32, f32),
+    ) -> Result<Lut3D, String> {
+        assert!(resolution >= 2);
+        let n = |i: usize| {
+            range.0 as f64 + (range.1 - range.0) as f64 * i as f64 / (resolution - 1) as f64
+        };
+
+        // Red changes fastest in the tables.
+        let mut tables = vec![Vec::new(), Vec::new(), Vec::new()];
+        for i in 0..(resolution * resolution * resolution) {
+            let rgb = [
+                n(i % resolution),
+                n((i / resolution) % resolution),
+                n(i / (resolution * resolution)),
+            ];
+            let out = self.eval(transforms, rgb)?;
+            for chan in 0..3 {
+                tables[chan].push(out[chan] as f32);
+            }
+        }
+
+        Ok(Lut3D {
+            range: [range; 3],
+            resolution: [resolution; 3],
+            tables: tables,
+        })
+    }
+
+    /// Applies a chain of transforms to an RGB color.
+    ///
+    /// Returns an explanation if any of the transforms aren't supported.
+    pub fn eval(&self, transforms: &[Transform], rgb: [f64; 3]) -> Result<[f64; 3], String> {
+        self.eval_nested(transforms, rgb, 0)
+    }
+
+    fn eval_nested(
+        &self,
+        transforms: &[Transform],
+        mut rgb: [f64; 3],
+        depth: usize,
+    ) -> Result<[f64; 3], String> {
+        for transform in transforms.iter() {
+            rgb = match *transform {
+                Transform::MatrixTransform(m) => {
+                    let m: Vec<f64> = m.iter().map(|n| *n as f64).collect();
+                    [
+                        m[0] * rgb[0] + m[1] * rgb[1] + m[2] * rgb[2] + m[3],
+                        m[4] * rgb[0] + m[5] * rgb[1] + m[6] * rgb[2] + m[7],
+                        m[8] * rgb[0] + m[9] * rgb[1] + m[10] * rgb[2] + m[11],
+                    ]
+                }
+
+                Transform::FileTransform {
+                    ref src,
+                    direction_inverse,
+                    ..
+                } => {
+                    let lut = self
+                        .lut(src)
+                        .ok_or_else(|| format!("LUT file \"{}\" couldn't be loaded", src))?;
+                    match (lut, direction_inverse) {
+                        (LoadedLut::Lut1D(lut), inverse) => {
+                            look_up_1d(lut, rgb, inverse, self.extrapolation)
+                        }
+                        (LoadedLut::Lut3D(shaper, lut), false) => {
+                            let rgb = match shaper {
+                                Some(shaper) => look_up_1d(shaper, rgb, false, self.extrapolation),
+                                None => rgb,
+                            };
+                            look_up_3d(lut, rgb)
+                        }
+                        (LoadedLut::Lut3D(..), true) => {
+                            return Err("inverting 3D LUTs isn't supported".into());
+                        }
+                    }
+                }
+
+                Transform::ColorSpaceTransform { ref src, ref dst } => {
+                    if depth >= MAX_DEPTH {
+                        return Err("color space transforms are nested too deeply".into());
+                    }
+                    self.eval_nested(&self.processor(src, dst)?, rgb, depth + 1)?
+                }
+
+                Transform::AllocationTransform {
+                    allocation,
+                    ref vars,
+                    direction_inverse,
+                } => {
+                    let min = vars.get(0).copied().unwrap_or(0.0);
+                    let max = vars.get(1).copied().unwrap_or(1.0);
+                    let offset = vars.get(2).copied().unwrap_or(0.0);
+                    let mut out = rgb;
+                    for n in out.iter_mut() {
+                        *n = match (allocation, direction_inverse) {
+                            (Allocation::Uniform, false) => (*n - min) / (max - min),
+                            (Allocation::Uniform, true) => min + *n * (max - min),
+                            (Allocation::Log2, false) => {
+                                ((*n + offset).max(std::f64::MIN_POSITIVE).log2() - min)
+                                    / (max - min)
+                            }
+                            (Allocation::Log2, true) => (min + *n * (max - min)).exp2() - offset,
+                        };
+                    }
+                    out
+                }
+
+                Transform::RangeTransform {
+                    range_in,
+                    range_out,
+                    clamp,
+                } => {
+                    let mut out = rgb;
+                    for n in out.iter_mut() {
+                        match (range_in, range_out) {
+                            ((Some(in_min), Some(in_max)), (Some(out_min), Some(out_max))) => {
+                                *n = out_min
+                                    + (*n - in_min) / (in_max - in_min) * (out_max - out_min);
+                            }
+                            ((Some(in_min), _), (Some(out_min), _)) => {
+                                *n += out_min - in_min;
+                            }
+                            ((_, Some(in_max)), (_, Some(out_max))) => {
+                                *n += out_max - in_max;
+                            }
+                            _ => {}
+                        }
+                        if clamp {
+                            if let Some(min) = range_out.0 {
+                                *n = n.max(min);
+                            }
+                            if let Some(max) = range_out.1 {
+                                *n = n.min(max);
+                            }
+                        }
+                    }
+                    out
+                }
+
+                Transform::ExponentTransform(r, g, b, _) => [
+                    rgb[0].max(0.0).powf(r),
+                    rgb[1].max(0.0).powf(g),
+                    rgb[2].max(0.0).powf(b),
+                ],
+
+                Transform::ExponentWithLinearTransform {
+                    gamma,
+                    offset,
+                    direction_inverse,
+                } => {
+                    // Linear segment below `x_break`, which matches the
+                    // power segment in both value and slope there.
+                    let x_break = offset / (gamma - 1.0);
+                    let slope = x_break / ((x_break + offset) / (1.0 + offset)).powf(gamma);
+                    let y_break = x_break / slope;
+                    let mut out = rgb;
+                    for n in out.iter_mut() {
+                        *n = match direction_inverse {
+                            false if *n <= x_break => *n / slope,
+                            false => ((*n + offset) / (1.0 + offset)).powf(gamma),
+                            true if *n <= y_break => *n * slope,
+                            true => n.powf(1.0 / gamma) * (1.0 + offset) - offset,
+                        };
+                    }
+                    out
+                }
+
+                Transform::CDLTransform {
+                    slope,
+                    offset,
+                    power,
+                    saturation,
+                    direction_inverse,
+                } => {
+                    // ASC CDL, which clamps to [0, 1].
+                    let luma = |c: [f64; 3]| 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2];
+                    let sat = |c: [f64; 3], s: f64| {
+                        let l = luma(c);
+                        [l + s * (c[0] - l), l + s * (c[1] - l), l + s * (c[2] - l)]
+                    };
+                    let mut out = rgb;
+                    if !direction_inverse {
+                        for i in 0..3 {
+                            out[i] = (out[i] * slope[i] as f64 + offset[i] as f64)
+                                .max(0.0)
+                                .min(1.0)
+                                .powf(power[i] as f64);
+                        }
+                        out = sat(out, saturation as f64);
+                        for n in out.iter_mut() {
+                            *n = n.max(0.0).min(1.0);
+                        }
+                    } else {
+                        for n in out.iter_mut() {
+                            *n = n.max(0.0).min(1.0);
+                        }
+                        out = sat(out, 1.0 / saturation as f64);
+                        for i in 0..3 {
+                            out[i] = (out[i].max(0.0).min(1.0).powf(1.0 / power[i] as f64)
+                                - offset[i] as f64)
+                                / slope[i] as f64;
+                            out[i] = out[i].max(0.0).min(1.0);
+                        }
+                    }
+                    out
+                }
+
+                Transform::ToHSV => colorbox::transforms::ocio::rgb_to_hsv(rgb),
+                Transform::FromHSV => colorbox::transforms::ocio::hsv_to_rgb(rgb),
+
+                Transform::ACESGamutMapTransform {
+                    threshhold,
+                    limit,
+                    power,
+                    direction_inverse,
+                } => crate::gamut_map::aces_gamut_compress(
+                    rgb,
+                    [
+                        threshhold[0] as f64,
+                        threshhold[1] as f64,
+                        threshhold[2] as f64,
+                    ],
+                    [limit[0] as f64, limit[1] as f64, limit[2] as f64],
+                    power as f64,
+                    direction_inverse,
+                ),
+
+                Transform::BuiltinTransform { .. } => return Err(unsupported("BuiltinTransform")),
+                Transform::GradingPrimaryTransform { .. } => {
+                    return Err(unsupported("GradingPrimaryTransform"))
+                }
+                Transform::GradingToneTransform { .. } => {
+                    return Err(unsupported("GradingToneTransform"))
+                }
+                Transform::Raw(_) => {
+                    return Err("transforms carried through verbatim aren't supported".into())
+                }
+            };
+        }
+
+        Ok(rgb)
+    }
+
+    /// Finds the LUT for a `FileTransform`'s source, via the search path.
+    ///
+    /// Context variables are replaced with their default values.
+    fn lut(&self, src: &str) -> Option<&LoadedLut> {
+        let src = self.config.expand_context_defaults(src);
+        self.config
+            .search_path
+            .iter()
+            .map(|dir| Path::new(&self.config.expand_context_defaults(dir)).join(&src))
+            .chain(std::iter::once(src.clone().into()))
+            .find_map(|path| self.luts.get(&path))
+    }
+}
+
+/// Inverts a chain of transforms, if possible.
+fn inverted(transforms: &[Transform]) -> Result<Vec<Transform>, String> {
+    if transforms.iter().any(|t| matches!(t, Transform::Raw(_))) {
+        return Err("transforms carried through verbatim can't be inverted".into());
+    }
+    transforms
+        .iter()
+        .rev()
+        .map(|t| t.clone().try_invert())
+        .collect()
+}
+
+fn unsupported(transform_name: &str) -> String {
+    format!("{} isn't supported", transform_name)
+}
+
+/// Reads a LUT file's contents, based on its extension.
+fn read_lut(path: &Path, data: &[u8]) -> Option<LoadedLut> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("spi1d") => formats::spi1d::read(&mut std::io::Cursor::new(data))
+            .ok()
+            .map(LoadedLut::Lut1D),
+        Some("cube") => {
+            // There are two different .cube formats, so we try both.
+            if let Ok(lut) = formats::cube_iridas::read_1d(&mut std::io::Cursor::new(data)) {
+                return Some(LoadedLut::Lut1D(lut));
+            }
+            match formats::cube_resolve::read(&mut std::io::Cursor::new(data)) {
+                Ok((shaper, Some(lut))) => Some(LoadedLut::Lut3D(shaper, lut)),
+                Ok((Some(lut), None)) => Some(LoadedLut::Lut1D(lut)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn look_up_1d(lut: &Lut1D, rgb: [f64; 3], inverse: bool, policy: Extrapolation) -> [f64; 3] {
+    let mut out = [0.0; 3];
+    for chan in 0..3 {
+        let table = chan.min(lut.tables.len() - 1);
+        out[chan] = if inverse {
+            lut_extrapolation::look_up_inv(lut, table, rgb[chan] as f32, policy)
+        } else {
+            lut_extrapolation::look_up(lut, table, rgb[chan] as f32, policy)
+        } as f64;
+    }
+    out
+}
+
+/// Trilinear interpolation, which is close enough to what OCIO does for
+/// our purposes.
+fn look_up_3d(lut: &Lut3D, rgb: [f64; 3]) -> [f64; 3] {
+    let res = lut.resolution;
+
+    // Cell index and fraction on each axis.
+    let mut i0 = [0usize; 3];
+    let mut t = [0.0f64; 3];
+    for axis in 0..3 {
+        let (min, max) = (lut.range[axis].0 as f64, lut.range[axis].1 as f64);
+        let n = ((rgb[axis] - min) / (max - min)).max(0.0).min(1.0) * (res[axis] - 1) as f64;
+        i0[axis] = (n as usize).min(res[axis].saturating_sub(2));
+        t[axis] = n - i0[axis] as f64;
+    }
+
+    // Red changes fastest in the tables.
+    let index = |r: usize, g: usize, b: usize| r + (g * res[0]) + (b * res[0] * res[1]);
+
+    let mut out = [0.0f64; 3];
+    for corner in 0..8 {
+        let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+        let mut weight = 1.0;
+        for axis in 0..3 {
+            weight *= if offset[axis] == 1 {
+                t[axis]
+            } else {
+                1.0 - t[axis]
+            };
+        }
+        let i = index(
+            (i0[0] + offset[0]).min(res[0] - 1),
+            (i0[1] + offset[1]).min(res[1] - 1),
+            (i0[2] + offset[2]).min(res[2] - 1),
+        );
+        for chan in 0..3 {
+            out[chan] += lut.tables[chan][i] as f64 * weight;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn look_up_3d_identity() {
+        let res = 3;
+        let mut tables = vec![Vec::new(), Vec::new(), Vec::new()];
+        for i in 0..(res * res * res) {
+            tables[0].push((i % res) as f32 / (res - 1) as f32);
+            tables[1].push(((i / res) % res) as f32 / (res - 1) as f32);
+            tables[2].push((i / (res * res)) as f32 / (res - 1) as f32);
+        }
+        let lut = Lut3D {
+            range: [(0.0, 1.0); 3],
+            resolution: [res; 3],
+            tables: tables,
+        };
+
+        for rgb in [[0.0, 0.0, 0.0], [0.25, 0.5, 0.75], [1.0, 0.1, 0.9]].iter() {
+            let out = look_up_3d(&lut, *rgb);
+            for i in 0..3 {
+                assert!((out[i] - rgb[i]).abs() < 1.0e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn eval_01() {
+        let config = OCIOConfig::default();
+        let evaluator = Evaluator::new(&config);
+        let transforms = [
+            Transform::MatrixTransform([
+                2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 0.5, 0.0, 0.0, 0.0, 1.0,
+            ]),
+            Transform::ExponentTransform(2.0, 2.0, 2.0, 1.0),
+        ];
+        let out = evaluator.eval(&transforms, [0.5, 1.0, 0.0]).unwrap();
+        assert_eq!(out, [1.0, 4.0, 0.25]);
+
+        let transforms = [Transform::BuiltinTransform {
+            name: "ACEScct_to_ACES2065-1".into(),
+            direction_inverse: false,
+        }];
+        assert!(evaluator.eval(&transforms, [0.5; 3]).is_err());
+    }
+
+    #[test]
+    fn bake_lut_3d_01() {
+        let config = OCIOConfig::default();
+        let evaluator = Evaluator::new(&config);
+        let transforms = [Transform::ExponentTransform(2.0, 2.0, 2.0, 1.0)];
+        let lut = evaluator.bake_lut_3d(&transforms, 5, (0.0, 1.0)).unwrap();
+
+        assert_eq!(lut.resolution, [5; 3]);
+        for rgb in [[0.0, 0.0, 0.0], [0.5, 0.25, 1.0], [1.0, 1.0, 0.75]].iter() {
+            let out = look_up_3d(&lut, *rgb);
+            for i in 0..3 {
+                assert!((out[i] - rgb[i] * rgb[i]).abs() < 1.0e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn eval_round_trips() {
+        let config = OCIOConfig::default();
+        let evaluator = Evaluator::new(&config);
+        let transforms = vec![
+            Transform::AllocationTransform {
+                allocation: Allocation::Log2,
+                vars: vec![-8.0, 6.0, 0.001],
+                direction_inverse: false,
+            },
+            Transform::ExponentWithLinearTransform {
+                gamma: 2.4,
+                offset: 0.055,
+                direction_inverse: true,
+            },
+            Transform::CDLTransform {
+                slope: [1.1, 1.0, 0.9],
+                offset: [0.01, 0.0, -0.01],
+                power: [1.0, 1.1, 0.9],
+                saturation: 1.2,
+                direction_inverse: false,
+            },
+        ];
+        let inverse = inverted(&transforms).unwrap();
+
+        for rgb in [[0.18, 0.18, 0.18], [0.5, 0.3, 0.2], [1.0, 2.0, 0.05]].iter() {
+            let out = evaluator
+                .eval(&inverse, evaluator.eval(&transforms, *rgb).unwrap())
+                .unwrap();
+            for i in 0..3 {
+                assert!((out[i] - rgb[i]).abs() < 1.0e-4, "{:?} != {:?}", out, rgb);
+            }
+        }
+    }
+
+    #[test]
+    fn eval_extrapolation() {
+        let mut config = OCIOConfig::default();
+        config.output_files.insert(
+            "curve.spi1d".into(),
+            OutputFile::Lut1D(Lut1D {
+                ranges: vec![(0.0, 1.0)],
+                tables: vec![vec![0.0, 0.25, 0.5, 1.0]],
+            }),
+        );
+        let transforms = [Transform::FileTransform {
+            src: "curve.spi1d".into(),
+            interpolation: crate::config::Interpolation::Linear,
+            direction_inverse: false,
+        }];
+        let mut evaluator = Evaluator::new(&config);
+
+        let out = evaluator.eval(&transforms, [-1.0, 0.5, 2.0]).unwrap();
+        assert_eq!(out[0], 0.0);
+        assert!((out[1] - 0.375).abs() < 1.0e-6);
+        assert_eq!(out[2], 1.0);
+
+        evaluator.extrapolation = Extrapolation::LinearExtend;
+        let out = evaluator.eval(&transforms, [-1.0, 0.5, 2.0]).unwrap();
+        assert!((out[0] - -0.75).abs() < 1.0e-6);
+        assert!((out[1] - 0.375).abs() < 1.0e-6);
+        assert!((out[2] - 2.5).abs() < 1.0e-6);
+
+        let inverse = inverted(&transforms).unwrap();
+        let back = evaluator.eval(&inverse, out).unwrap();
+        for (a, b) in back.iter().zip([-1.0, 0.5, 2.0].iter()) {
+            assert!((a - b).abs() < 1.0e-5, "{:?}", back);
+        }
+    }
+}
@@ -15,7 +15,73 @@ use colorbox::{
     transforms::rgb_gamut,
 };
 
-use crate::config::Transform;
+use crate::config::{Interpolation, Transform};
+
+/// How much `hsv_gamut_clip()` and `hsv_soft_compress()` desaturate
+/// colors before converting them to HSV.  See the former for why.
+const HSV_DESAT_FAC: f64 = 4.0;
+
+/// Threshold, limit, and power of the ACES Reference Gamut Compression
+/// (ACES 1.3), per cyan, magenta, and yellow.
+const ACES_RGC_THRESHOLD: [f64; 3] = [0.815, 0.803, 0.880];
+const ACES_RGC_LIMIT: [f64; 3] = [1.147, 1.264, 1.312];
+const ACES_RGC_POWER: f64 = 1.2;
+
+/// Threshold, limit, and power of `rgb_soft_compress()` as used by
+/// `GamutMapping::SoftCompress`.
+pub const SOFT_COMPRESS_THRESHOLD: f64 = 0.75;
+pub const SOFT_COMPRESS_LIMIT: f64 = 2.0;
+const SOFT_COMPRESS_POWER: f64 = 1.2;
+
+/// How out-of-gamut colors are handled when converting between gamuts.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GamutMapping {
+    /// Leave out-of-gamut colors as-is.
+    None,
+    /// Hard clip to the gamut, via `hsv_gamut_clip()`.
+    Clip,
+    /// The ACES 1.3 Reference Gamut Compression.
+    AcesCompress,
+    /// Luminance-preserving soft compression, via `hsv_soft_compress()`.
+    SoftCompress,
+}
+
+impl Default for GamutMapping {
+    fn default() -> GamutMapping {
+        GamutMapping::Clip
+    }
+}
+
+impl GamutMapping {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            GamutMapping::None => "none",
+            GamutMapping::Clip => "clip",
+            GamutMapping::AcesCompress => "aces_compress",
+            GamutMapping::SoftCompress => "soft_compress",
+        }
+    }
+
+    pub fn from_str(text: &str) -> Option<GamutMapping> {
+        match text.trim() {
+            "none" => Some(GamutMapping::None),
+            "clip" => Some(GamutMapping::Clip),
+            "aces_compress" => Some(GamutMapping::AcesCompress),
+            "soft_compress" => Some(GamutMapping::SoftCompress),
+            _ => None,
+        }
+    }
+
+    /// Human-readable name, for UI.
+    pub fn ui_text(&self) -> &'static str {
+        match *self {
+            GamutMapping::None => "None",
+            GamutMapping::Clip => "Clip",
+            GamutMapping::AcesCompress => "ACES Gamut Compress 1.3",
+            GamutMapping::SoftCompress => "Soft Compress",
+        }
+    }
+}
 
 /// A simple but reasonably robust approach that clips in RGB space.
 ///
@@ -65,6 +131,111 @@ pub fn rgb_clip(
     }
 }
 
+/// Luminance-preserving soft gamut compression.
+///
+/// Colors are pulled toward the achromatic color of the same luminance,
+/// by an amount that depends on how far the furthest-out channel is
+/// from it (relative to luminance, where 1.0 is at zero).  Distances
+/// below `threshold` are left alone, and `limit` is compressed to 1.0,
+/// i.e. the gamut boundary.  Colors with zero or negative luminance are
+/// clipped.
+pub fn rgb_soft_compress(
+    rgb: [f64; 3],
+    luminance_weights: [f64; 3],
+    threshold: f64,
+    limit: f64,
+) -> [f64; 3] {
+    let l = (rgb[0] * luminance_weights[0])
+        + (rgb[1] * luminance_weights[1])
+        + (rgb[2] * luminance_weights[2]);
+    if l <= 0.0 {
+        return rgb_clip(rgb, None, luminance_weights, 0.0);
+    }
+
+    let dist = rgb.iter().fold(0.0f64, |d, n| d.max((l - n) / l));
+    if dist <= threshold {
+        return rgb;
+    }
+
+    let compressed_dist =
+        compress_distance(dist, threshold, limit, SOFT_COMPRESS_POWER, false).min(1.0);
+    let fac = compressed_dist / dist;
+    [
+        l + (rgb[0] - l) * fac,
+        l + (rgb[1] - l) * fac,
+        l + (rgb[2] - l) * fac,
+    ]
+}
+
+/// The ACES 1.3 Reference Gamut Compression, matching OCIO's
+/// `ACES_GamutComp13` fixed function.
+///
+/// `threshold` and `limit` are per cyan, magenta, and yellow (i.e. the
+/// distance of the red, green, and blue channels from the achromatic
+/// axis).
+pub fn aces_gamut_compress(
+    rgb: [f64; 3],
+    threshold: [f64; 3],
+    limit: [f64; 3],
+    power: f64,
+    inverse: bool,
+) -> [f64; 3] {
+    // Achromatic axis.
+    let ach = rgb[0].max(rgb[1]).max(rgb[2]);
+    if ach == 0.0 {
+        return rgb;
+    }
+
+    let mut out = rgb;
+    for i in 0..3 {
+        let dist = (ach - rgb[i]) / ach.abs();
+        let compressed_dist = compress_distance(dist, threshold[i], limit[i], power, inverse);
+        out[i] = ach - compressed_dist * ach.abs();
+    }
+    out
+}
+
+/// The ACES 1.3 Reference Gamut Compression as an OCIO transform.
+pub fn aces_gamut_compress_transform() -> Transform {
+    Transform::ACESGamutMapTransform {
+        threshhold: [
+            ACES_RGC_THRESHOLD[0] as f32,
+            ACES_RGC_THRESHOLD[1] as f32,
+            ACES_RGC_THRESHOLD[2] as f32,
+        ],
+        limit: [
+            ACES_RGC_LIMIT[0] as f32,
+            ACES_RGC_LIMIT[1] as f32,
+            ACES_RGC_LIMIT[2] as f32,
+        ],
+        power: ACES_RGC_POWER as f32,
+        direction_inverse: false,
+    }
+}
+
+/// The compression curve of the ACES Reference Gamut Compression.
+///
+/// Distances below `threshold` are unchanged, and `limit` maps to 1.0.
+fn compress_distance(dist: f64, threshold: f64, limit: f64, power: f64, inverse: bool) -> f64 {
+    if dist < threshold {
+        return dist;
+    }
+
+    let scale = (limit - threshold)
+        / (((1.0 - threshold) / (limit - threshold)).powf(-power) - 1.0).powf(1.0 / power);
+    let norm_dist = (dist - threshold) / scale;
+    let p = norm_dist.powf(power);
+
+    if !inverse {
+        threshold + scale * norm_dist / (1.0 + p).powf(1.0 / power)
+    } else if dist > (threshold + scale) {
+        // Out of the invertible range.
+        dist
+    } else {
+        threshold + scale * (-(p / (p - 1.0))).powf(1.0 / power)
+    }
+}
+
 fn sat_matrix(sat: f64, weights: [f64; 3]) -> Matrix {
     let mat1 = [weights, weights, weights];
     let mat2 = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
@@ -97,7 +268,7 @@ pub fn hsv_gamut_clip() -> Vec<Transform> {
     // Sigh...
 
     const MAX_V: f64 = (1u64 << 24) as f64;
-    const DESAT_FAC: f64 = 4.0;
+    const DESAT_FAC: f64 = HSV_DESAT_FAC;
 
     let desat_mat = sat_matrix(1.0 / DESAT_FAC, [1.0; 3]);
     let desat_mat_inv = matrix::invert(desat_mat).unwrap();
@@ -128,3 +299,98 @@ pub fn hsv_gamut_clip() -> Vec<Transform> {
         Transform::MatrixTransform(matrix::to_4x4_f32(desat_mat_inv)),
     ]
 }
+
+/// Luminance-preserving soft gamut compression, via a LUT in HSV space
+/// generated by `make_soft_compress_lut()`.
+///
+/// Uses the same desaturation trick as `hsv_gamut_clip()`.
+pub fn hsv_soft_compress(lut_path: &str) -> Vec<Transform> {
+    let desat_mat = sat_matrix(1.0 / HSV_DESAT_FAC, [1.0; 3]);
+    let desat_mat_inv = matrix::invert(desat_mat).unwrap();
+
+    vec![
+        Transform::MatrixTransform(matrix::to_4x4_f32(desat_mat)),
+        Transform::ToHSV,
+        Transform::FileTransform {
+            src: lut_path.into(),
+            interpolation: Interpolation::Linear,
+            direction_inverse: false,
+        },
+        Transform::FromHSV,
+        Transform::MatrixTransform(matrix::to_4x4_f32(desat_mat_inv)),
+    ]
+}
+
+/// Generates the LUT used by `hsv_soft_compress()`.
+pub fn make_soft_compress_lut(luminance_weights: [f64; 3]) -> colorbox::lut::Lut3D {
+    use colorbox::transforms::ocio::{hsv_to_rgb, rgb_to_hsv};
+
+    let desat_mat = sat_matrix(1.0 / HSV_DESAT_FAC, [1.0; 3]);
+    let desat_mat_inv = matrix::invert(desat_mat).unwrap();
+
+    // The mapping scales linearly with value, so linear interpolation
+    // along the value axis is exact, and a huge range costs nothing.
+    let upper = (3 * (1i64 << 32)) as f64;
+
+    crate::hsv_lut::make_hsv_lut(33, (0.0, upper), 1.5, |(h, s, v)| {
+        let rgb = matrix::transform_color(hsv_to_rgb([h, s, v]), desat_mat_inv);
+        let rgb2 = rgb_soft_compress(
+            rgb,
+            luminance_weights,
+            SOFT_COMPRESS_THRESHOLD,
+            SOFT_COMPRESS_LIMIT,
+        );
+        let hsv2 = rgb_to_hsv(matrix::transform_color(rgb2, desat_mat));
+        (hsv2[0], hsv2[1], hsv2[2])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aces_gamut_compress_round_trip() {
+        let rgb = [0.5, -0.02, 0.1];
+        let compressed = aces_gamut_compress(
+            rgb,
+            ACES_RGC_THRESHOLD,
+            ACES_RGC_LIMIT,
+            ACES_RGC_POWER,
+            false,
+        );
+        assert!(compressed[1] > rgb[1]);
+        assert_eq!(compressed[0], rgb[0]);
+
+        let rgb2 = aces_gamut_compress(
+            compressed,
+            ACES_RGC_THRESHOLD,
+            ACES_RGC_LIMIT,
+            ACES_RGC_POWER,
+            true,
+        );
+        for i in 0..3 {
+            assert!((rgb[i] - rgb2[i]).abs() < 0.000_001, "{:?}", rgb2);
+        }
+    }
+
+    #[test]
+    fn rgb_soft_compress_01() {
+        let weights = [0.25, 0.5, 0.25];
+        let rgb = [2.0, -0.5, 0.25];
+        let compressed =
+            rgb_soft_compress(rgb, weights, SOFT_COMPRESS_THRESHOLD, SOFT_COMPRESS_LIMIT);
+
+        // Brought into gamut, with the same luminance.
+        assert!(compressed.iter().all(|n| *n >= 0.0), "{:?}", compressed);
+        let lum = |c: [f64; 3]| c[0] * weights[0] + c[1] * weights[1] + c[2] * weights[2];
+        assert!((lum(rgb) - lum(compressed)).abs() < 0.000_001);
+
+        // In-gamut colors below the threshold are untouched.
+        let rgb = [0.5, 0.4, 0.3];
+        assert_eq!(
+            rgb_soft_compress(rgb, weights, SOFT_COMPRESS_THRESHOLD, SOFT_COMPRESS_LIMIT),
+            rgb
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Preserving content that was added to a generated config by hand.
+//!
+//! Hand-added color spaces, looks, etc. aren't part of what a config is
+//! generated from, so they would be lost when regenerating it.  Instead,
+//! they're found by comparing the hand-edited config to a freshly
+//! generated one, and then added back to regenerated configs as-is.
+//!
+//! Hand edits *to* generated content aren't preserved.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    config::{find_output_file, ColorSpace, Display, Look, NamedTransform, OCIOConfig, OutputFile},
+    import::collect_file_sources,
+};
+
+/// Content of a hand-edited config that isn't in the generated config.
+#[derive(Debug, Clone, Default)]
+pub struct HandEdits {
+    pub colorspaces: Vec<ColorSpace>,
+    pub looks: Vec<Look>,
+    pub named_transforms: Vec<NamedTransform>,
+    pub views: Vec<(String, String, String)>, // (display, view, color space)
+    pub roles: Vec<(String, String)>,         // (role, color space)
+    pub search_path: Vec<String>,
+
+    // Files referenced by the above, keyed by their path relative to
+    // the config.
+    pub files: HashMap<PathBuf, OutputFile>,
+}
+
+impl HandEdits {
+    /// Finds what was added to `edited` by hand, given `generated`,
+    /// which is `edited` as it would be generated without the edits.
+    ///
+    /// `edited` should have its files loaded, as by `import::read_config()`.
+    pub fn find(edited: &OCIOConfig, generated: &OCIOConfig) -> HandEdits {
+        let mut edits = HandEdits::default();
+
+        for space in edited.colorspaces.iter() {
+            if !generated.colorspaces.iter().any(|s| s.name == space.name) {
+                edits.colorspaces.push(space.clone());
+            }
+        }
+        for look in edited.looks.iter() {
+            if !generated.looks.iter().any(|l| l.name == look.name) {
+                edits.looks.push(look.clone());
+            }
+        }
+        for named_transform in edited.named_transforms.iter() {
+            if !generated
+                .named_transforms
+                .iter()
+                .any(|n| n.name == named_transform.name)
+            {
+                edits.named_transforms.push(named_transform.clone());
+            }
+        }
+        for display in edited.displays.iter() {
+            let generated_display = generated.displays.iter().find(|d| d.name == display.name);
+            for (view, space) in display.views.iter() {
+                let is_generated = generated_display
+                    .map(|d| d.views.iter().any(|(v, _)| v == view))
+                    .unwrap_or(false);
+                if !is_generated {
+                    edits
+                        .views
+                        .push((display.name.clone(), view.clone(), space.clone()));
+                }
+            }
+        }
+
+        let generated_roles = generated.roles.as_map();
+        let mut roles: Vec<(String, String)> = edited
+            .roles
+            .as_map()
+            .into_iter()
+            .filter(|(role, _)| !generated_roles.contains_key(role))
+            .collect();
+        roles.sort();
+        edits.roles = roles;
+
+        for path in edited.search_path.iter() {
+            if !generated.search_path.contains(path) {
+                edits.search_path.push(path.clone());
+            }
+        }
+
+        // Files.
+        let mut sources = Vec::new();
+        for space in edits.colorspaces.iter() {
+            collect_file_sources(&space.to_reference, &mut sources);
+            collect_file_sources(&space.from_reference, &mut sources);
+        }
+        for look in edits.looks.iter() {
+            collect_file_sources(&look.transform, &mut sources);
+            collect_file_sources(&look.inverse_transform, &mut sources);
+        }
+        for named_transform in edits.named_transforms.iter() {
+            collect_file_sources(&named_transform.transform, &mut sources);
+            collect_file_sources(&named_transform.inverse_transform, &mut sources);
+        }
+        for src in sources.iter() {
+            let src = edited.expand_context_defaults(src);
+            if let Some(path) = find_output_file(&edited.search_path, &edited.output_files, &src) {
+                edits
+                    .files
+                    .insert(path.clone(), edited.output_files[path].clone());
+            }
+        }
+
+        edits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colorspaces.is_empty()
+            && self.looks.is_empty()
+            && self.named_transforms.is_empty()
+            && self.views.is_empty()
+            && self.roles.is_empty()
+            && self.search_path.is_empty()
+    }
+
+    /// Adds the hand edits to `config`.
+    ///
+    /// The config's own content takes precedence, so anything that
+    /// conflicts with it (e.g. a color space of the same name) is left
+    /// out.
+    pub fn apply(&self, config: &mut OCIOConfig) {
+        for space in self.colorspaces.iter() {
+            if !config.colorspaces.iter().any(|s| s.name == space.name) {
+                config.colorspaces.push(space.clone());
+            }
+        }
+        for look in self.looks.iter() {
+            if !config.looks.iter().any(|l| l.name == look.name) {
+                config.looks.push(look.clone());
+            }
+        }
+        for named_transform in self.named_transforms.iter() {
+            if !config
+                .named_transforms
+                .iter()
+                .any(|n| n.name == named_transform.name)
+            {
+                config.named_transforms.push(named_transform.clone());
+            }
+        }
+        for (display, view, space) in self.views.iter() {
+            let i = match config.displays.iter().position(|d| d.name == *display) {
+                Some(i) => i,
+                None => {
+                    config.displays.push(Display {
+                        name: display.clone(),
+                        views: Vec::new(),
+                    });
+                    if !config.active_displays.is_empty() {
+                        config.active_displays.push(display.clone());
+                    }
+                    config.displays.len() - 1
+                }
+            };
+            let views = &mut config.displays[i].views;
+            if !views.iter().any(|(v, _)| v == view) {
+                views.push((view.clone(), space.clone()));
+            }
+        }
+
+        let roles = &mut config.roles;
+        for (role, space) in self.roles.iter() {
+            let named_role = match role.as_str() {
+                "reference" => Some(&mut roles.reference),
+                "aces_interchange" => Some(&mut roles.aces_interchange),
+                "cie_xyz_d65_interchange" => Some(&mut roles.cie_xyz_d65_interchange),
+                "default" => Some(&mut roles.default),
+                "data" => Some(&mut roles.data),
+                _ => None,
+            };
+            match named_role {
+                Some(named_role) => {
+                    if named_role.is_none() {
+                        *named_role = Some(space.clone());
+                    }
+                }
+                None => {
+                    roles
+                        .other
+                        .entry(role.clone())
+                        .or_insert_with(|| space.clone());
+                }
+            }
+        }
+
+        for path in self.search_path.iter() {
+            config.add_search_path(path);
+        }
+        for (path, file) in self.files.iter() {
+            config
+                .output_files
+                .entry(path.clone())
+                .or_insert_with(|| file.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn space(name: &str) -> ColorSpace {
+        ColorSpace {
+            name: name.into(),
+            ..ColorSpace::default()
+        }
+    }
+
+    #[test]
+    fn find_and_apply_01() {
+        let mut generated = OCIOConfig::default();
+        generated.colorspaces.push(space("Linear"));
+        generated.displays.push(Display {
+            name: "sRGB".into(),
+            views: vec![("Standard".into(), "Linear".into())],
+        });
+        generated.roles.reference = Some("Linear".into());
+
+        let mut edited = generated.clone();
+        edited.colorspaces.push(ColorSpace {
+            name: "Hand Made".into(),
+            to_reference: vec![crate::config::Transform::FileTransform {
+                src: "hand.spi1d".into(),
+                interpolation: crate::config::Interpolation::Linear,
+                direction_inverse: false,
+            }],
+            ..ColorSpace::default()
+        });
+        edited.add_search_path("hand_luts");
+        edited.output_files.insert(
+            PathBuf::from("hand_luts/hand.spi1d"),
+            OutputFile::Raw(b"data".to_vec()),
+        );
+        edited.displays[0]
+            .views
+            .push(("Hand View".into(), "Hand Made".into()));
+        edited
+            .roles
+            .other
+            .insert("color_picking".into(), "Hand Made".into());
+        edited.roles.reference = Some("Hand Made".into()); // Not preserved.
+
+        let edits = HandEdits::find(&edited, &generated);
+        assert_eq!(edits.colorspaces.len(), 1);
+        assert_eq!(edits.views.len(), 1);
+        assert_eq!(
+            edits.roles,
+            vec![("color_picking".into(), "Hand Made".into())]
+        );
+        assert_eq!(edits.search_path, vec!["hand_luts"]);
+        assert!(edits
+            .files
+            .contains_key(&PathBuf::from("hand_luts/hand.spi1d")));
+
+        let mut regenerated = generated.clone();
+        edits.apply(&mut regenerated);
+        assert_eq!(regenerated.colorspaces.len(), 2);
+        assert_eq!(regenerated.displays[0].views.len(), 2);
+        assert_eq!(regenerated.roles.reference, Some("Linear".into()));
+        assert_eq!(regenerated.roles.other.len(), 1);
+        assert_eq!(regenerated.output_files.len(), 1);
+        assert!(regenerated.validate().is_ok());
+    }
+}
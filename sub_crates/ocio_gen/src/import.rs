@@ -0,0 +1,983 @@
+//! Reading of existing OCIO configs.
+//!
+//! Only a subset of OCIO is understood: color spaces, named transforms,
+//! roles, displays and their views, looks, and active/inactive lists.
+//! Transforms that aren't otherwise supported are carried through
+//! verbatim via `Transform::Raw`, so that they survive being re-exported.
+
+use std::path::{Path, PathBuf};
+
+use serde_yaml::{Mapping, Value};
+
+use colorbox::{chroma, matrix};
+
+use crate::config::*;
+
+#[derive(Debug)]
+pub enum ImportError {
+    IoErr(std::io::Error),
+    YamlErr(serde_yaml::Error),
+    NotAConfig,
+    InvalidTransform(String),
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::IoErr(ref e) => Some(e),
+            ImportError::YamlErr(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::IoErr(e) => e.fmt(f),
+            ImportError::YamlErr(e) => write!(f, "invalid YAML: {}", e),
+            ImportError::NotAConfig => write!(f, "the file is not an OCIO config."),
+            ImportError::InvalidTransform(e) => write!(f, "invalid transform: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for ImportError {
+    fn from(e: std::io::Error) -> Self {
+        ImportError::IoErr(e)
+    }
+}
+
+impl From<serde_yaml::Error> for ImportError {
+    fn from(e: serde_yaml::Error) -> Self {
+        ImportError::YamlErr(e)
+    }
+}
+
+/// Reads the OCIO config file at `path`.
+///
+/// Files referenced by `FileTransform`s are loaded from the config's
+/// search path and included as output files, so that the config can be
+/// re-exported elsewhere.
+///
+/// The reference space chromaticities are determined from the
+/// `cie_xyz_d65_interchange` or `aces_interchange` roles when possible.
+///
+/// Returns the config along with a list of warnings about anything that
+/// was skipped or couldn't be determined.
+pub fn read_config<P: AsRef<Path>>(path: P) -> Result<(OCIOConfig, Vec<String>), ImportError> {
+    let path: &Path = path.as_ref();
+    let text = std::fs::read_to_string(path)?;
+    let (mut config, mut warnings) = parse_config(&text)?;
+
+    // Load referenced files.
+    let config_dir = path.parent().unwrap_or("".as_ref());
+    let mut sources = Vec::new();
+    for space in config.colorspaces.iter() {
+        collect_file_sources(&space.to_reference, &mut sources);
+        collect_file_sources(&space.from_reference, &mut sources);
+    }
+    for look in config.looks.iter() {
+        collect_file_sources(&look.transform, &mut sources);
+        collect_file_sources(&look.inverse_transform, &mut sources);
+    }
+    for named_transform in config.named_transforms.iter() {
+        collect_file_sources(&named_transform.transform, &mut sources);
+        collect_file_sources(&named_transform.inverse_transform, &mut sources);
+    }
+    for src in sources.iter() {
+        // Files that depend on context variables are looked up with the
+        // variables' default values.
+        let expanded_src = config.expand_context_defaults(src);
+        let found = config
+            .search_path
+            .iter()
+            .map(|dir| Path::new(&config.expand_context_defaults(dir)).join(&expanded_src))
+            .chain(std::iter::once(PathBuf::from(&expanded_src)))
+            .find(|rel_path| config_dir.join(rel_path).is_file());
+        match found {
+            Some(rel_path) => {
+                let data = std::fs::read(config_dir.join(&rel_path))?;
+                config.output_files.insert(rel_path, OutputFile::Raw(data));
+            }
+            None => warnings.push(format!(
+                "Couldn't find file \"{}\" in the config's search path.",
+                src
+            )),
+        }
+    }
+
+    Ok((config, warnings))
+}
+
+/// Parses the text of an OCIO config file.
+///
+/// Same as `read_config()`, except that referenced files are not loaded.
+pub fn parse_config(text: &str) -> Result<(OCIOConfig, Vec<String>), ImportError> {
+    let root: Value = serde_yaml::from_str(&shorthand_tags(text))?;
+    let root = match root {
+        Value::Mapping(m) if m.contains_key("ocio_profile_version") => m,
+        _ => return Err(ImportError::NotAConfig),
+    };
+
+    let mut config = OCIOConfig::default();
+    let mut warnings = Vec::new();
+
+    //----
+    // Header.
+
+    config.name = get_string(&root, "name");
+    config.description = get_string(&root, "description").map(|d| d.trim().into());
+    if let Some(Value::Mapping(environment)) = root.get("environment") {
+        for (variable, default) in environment.iter() {
+            if let Some(variable) = as_string(variable) {
+                config
+                    .environment
+                    .push((variable, as_string(default).unwrap_or_default()));
+            }
+        }
+    }
+    match root.get("search_path") {
+        Some(Value::Sequence(paths)) => {
+            for path in paths.iter().filter_map(as_string) {
+                config.add_search_path(path.trim());
+            }
+        }
+        Some(v) => {
+            if let Some(paths) = as_string(v) {
+                for path in paths.split(':').filter(|p| !p.trim().is_empty()) {
+                    config.add_search_path(path.trim());
+                }
+            }
+        }
+        None => {}
+    }
+
+    //----
+    // Roles.
+
+    if let Some(Value::Mapping(roles)) = root.get("roles") {
+        for (role, space) in roles.iter() {
+            let (role, space) = match (as_string(role), as_string(space)) {
+                (Some(role), Some(space)) => (role, space),
+                _ => continue,
+            };
+            match role.as_str() {
+                "reference" => config.roles.reference = Some(space),
+                "aces_interchange" => config.roles.aces_interchange = Some(space),
+                "cie_xyz_d65_interchange" => config.roles.cie_xyz_d65_interchange = Some(space),
+                "default" => config.roles.default = Some(space),
+                "data" => config.roles.data = Some(space),
+                _ => {
+                    config.roles.other.insert(role, space);
+                }
+            }
+        }
+    }
+
+    //----
+    // File and viewing rules.
+
+    if let Some(Value::Sequence(rules)) = root.get("file_rules") {
+        for rule in rules.iter().filter_map(|v| untag(v).1) {
+            let name = get_string(rule, "name").unwrap_or_default();
+            let kind = if let Some(regex) = get_string(rule, "regex") {
+                FileRuleKind::Regex(regex)
+            } else if name == "Default" {
+                FileRuleKind::Default
+            } else if name == "ColorSpaceNamePathSearch" {
+                FileRuleKind::ColorSpaceNamePathSearch
+            } else {
+                FileRuleKind::Pattern {
+                    pattern: get_string(rule, "pattern").unwrap_or_else(|| "*".into()),
+                    extension: get_string(rule, "extension").unwrap_or_else(|| "*".into()),
+                }
+            };
+            config.file_rules.push(FileRule {
+                name: name,
+                colorspace: get_string(rule, "colorspace").unwrap_or_default(),
+                kind: kind,
+            });
+        }
+    }
+    if let Some(Value::Sequence(rules)) = root.get("viewing_rules") {
+        for rule in rules.iter().filter_map(|v| untag(v).1) {
+            config.viewing_rules.push(ViewingRule {
+                name: get_string(rule, "name").unwrap_or_default(),
+                colorspaces: get_string_list(rule, "colorspaces"),
+                encodings: get_string_list(rule, "encodings")
+                    .iter()
+                    .filter_map(|e| Encoding::from_str(e))
+                    .collect(),
+            });
+        }
+    }
+
+    //----
+    // Displays and views.
+
+    if let Some(Value::Mapping(displays)) = root.get("displays") {
+        for (name, views) in displays.iter() {
+            let name = match as_string(name) {
+                Some(name) => name,
+                None => continue,
+            };
+            let mut display = Display {
+                name: name.clone(),
+                views: Vec::new(),
+            };
+            if let Value::Sequence(views) = views {
+                for view in views.iter().filter_map(|v| untag(v).1) {
+                    match (get_string(view, "name"), get_string(view, "colorspace")) {
+                        (Some(view_name), Some(space)) => display.views.push((view_name, space)),
+                        (Some(view_name), None) => warnings.push(format!(
+                            "View \"{}\" of display \"{}\" uses a view transform, which isn't supported.  It was skipped.",
+                            view_name, name
+                        )),
+                        _ => {}
+                    }
+                }
+            }
+            config.displays.push(display);
+        }
+    }
+    config.active_displays = get_string_list(&root, "active_displays");
+    config.active_views = get_string_list(&root, "active_views");
+
+    //----
+    // Looks.
+
+    if let Some(Value::Sequence(looks)) = root.get("looks") {
+        for look in looks.iter().filter_map(|v| untag(v).1) {
+            config.looks.push(Look {
+                name: get_string(look, "name").unwrap_or_default(),
+                description: get_string(look, "description").unwrap_or_default(),
+                process_space: get_string(look, "process_space").unwrap_or_default(),
+                transform: look
+                    .get("transform")
+                    .map(parse_transforms)
+                    .transpose()?
+                    .unwrap_or_default(),
+                inverse_transform: look
+                    .get("inverse_transform")
+                    .map(parse_transforms)
+                    .transpose()?
+                    .unwrap_or_default(),
+            });
+        }
+    }
+
+    //----
+    // Color spaces.
+
+    if let Some(Value::Sequence(spaces)) = root.get("colorspaces") {
+        for space in spaces.iter().filter_map(|v| untag(v).1) {
+            // OCIO v2 configs use `to/from_scene_reference`, and v1
+            // configs use `to/from_reference`.
+            let to_reference = space
+                .get("to_scene_reference")
+                .or_else(|| space.get("to_reference"));
+            let from_reference = space
+                .get("from_scene_reference")
+                .or_else(|| space.get("from_reference"));
+
+            config.colorspaces.push(ColorSpace {
+                name: get_string(space, "name").unwrap_or_default(),
+                description: get_string(space, "description")
+                    .map(|d| d.trim().into())
+                    .unwrap_or_default(),
+                aliases: get_string_list(space, "aliases"),
+                family: get_string(space, "family").unwrap_or_default(),
+                equalitygroup: get_string(space, "equalitygroup").unwrap_or_default(),
+                encoding: get_string(space, "encoding").and_then(|e| Encoding::from_str(&e)),
+                bitdepth: get_string(space, "bitdepth").and_then(|b| BitDepth::from_str(&b)),
+                isdata: space.get("isdata").and_then(|v| v.as_bool()),
+                to_reference: to_reference
+                    .map(parse_transforms)
+                    .transpose()?
+                    .unwrap_or_default(),
+                from_reference: from_reference
+                    .map(parse_transforms)
+                    .transpose()?
+                    .unwrap_or_default(),
+            });
+        }
+    }
+    config.inactive_colorspaces = get_string_list(&root, "inactive_colorspaces");
+
+    //----
+    // Named transforms.
+
+    if let Some(Value::Sequence(named_transforms)) = root.get("named_transforms") {
+        for named_transform in named_transforms.iter().filter_map(|v| untag(v).1) {
+            config.named_transforms.push(NamedTransform {
+                name: get_string(named_transform, "name").unwrap_or_default(),
+                description: get_string(named_transform, "description")
+                    .map(|d| d.trim().into())
+                    .unwrap_or_default(),
+                aliases: get_string_list(named_transform, "aliases"),
+                family: get_string(named_transform, "family").unwrap_or_default(),
+                encoding: get_string(named_transform, "encoding")
+                    .and_then(|e| Encoding::from_str(&e)),
+                transform: named_transform
+                    .get("transform")
+                    .map(parse_transforms)
+                    .transpose()?
+                    .unwrap_or_default(),
+                inverse_transform: named_transform
+                    .get("inverse_transform")
+                    .map(parse_transforms)
+                    .transpose()?
+                    .unwrap_or_default(),
+            });
+        }
+    }
+
+    //----
+    // Context variables.
+
+    // Exported configs always declare the variables they use, since
+    // without an environment section OCIO can't tell which ones matter.
+    for variable in config.context_variables() {
+        if !config.environment.iter().any(|(v, _)| *v == variable) {
+            warnings.push(format!(
+                "Context variable \"{}\" isn't declared in the config's environment, so it was declared with an empty default value.",
+                variable
+            ));
+            config.declare_context_variable(&variable, "");
+        }
+    }
+
+    //----
+    // Unsupported sections.
+
+    for section in ["display_colorspaces", "view_transforms", "shared_views"].iter() {
+        if root.contains_key(*section) {
+            warnings.push(format!(
+                "The config's \"{}\" aren't supported, and were skipped.",
+                section
+            ));
+        }
+    }
+
+    //----
+    // Reference space.
+
+    match reference_space_chroma(&config) {
+        Some(chroma) => config.reference_space_chroma = chroma,
+        None => warnings.push(
+            "Couldn't determine the gamut of the config's reference space, so Rec.709 is assumed.  Custom color spaces may be incorrect.".into(),
+        ),
+    }
+
+    Ok((config, warnings))
+}
+
+//-------------------------------------------------------------
+// Transforms.
+
+/// Parses a single transform or a list of transforms, flattening any
+/// group transforms.
+///
+/// Transforms that can't be represented are kept as raw transforms,
+/// but ones that are invalid (e.g. inverting a singular matrix) are an
+/// error.
+fn parse_transforms(value: &Value) -> Result<Vec<Transform>, ImportError> {
+    let mut transforms = Vec::new();
+    match value {
+        Value::Sequence(values) => {
+            for v in values.iter() {
+                transforms.extend(parse_transforms(v)?);
+            }
+        }
+        _ => match untag(value) {
+            (Some(ref tag), Some(map)) if tag == "GroupTransform" => {
+                let children = match map.get("children") {
+                    Some(children) => parse_transforms(children)?,
+                    None => Vec::new(),
+                };
+                if is_inverse(map) {
+                    for child in children.into_iter().rev() {
+                        transforms.push(invert(child)?);
+                    }
+                } else {
+                    transforms.extend(children);
+                }
+            }
+            (Some(tag), Some(map)) => transforms.push(match parse_transform(&tag, map) {
+                Some(transform) => transform.map_err(ImportError::InvalidTransform)?,
+                None => Transform::Raw(to_flow_yaml(value)),
+            }),
+            _ => {}
+        },
+    }
+    Ok(transforms)
+}
+
+/// Parses the transforms that have an equivalent in `Transform`.
+/// Returns `None` for anything else, and an error for transforms that
+/// are invalid.
+fn parse_transform(tag: &str, map: &Mapping) -> Option<Result<Transform, String>> {
+    let inverse = is_inverse(map);
+    let transform = match tag {
+        "FileTransform" => Transform::FileTransform {
+            src: get_string(map, "src")?.into(),
+            interpolation: match get_string(map, "interpolation").as_deref() {
+                Some("nearest") => Interpolation::Nearest,
+                Some("best") => Interpolation::Best,
+                Some("tetrahedral") => Interpolation::Tetrahedral,
+                _ => Interpolation::Linear,
+            },
+            direction_inverse: inverse,
+        },
+
+        "ColorSpaceTransform" => {
+            let src = get_string(map, "src")?;
+            let dst = get_string(map, "dst")?;
+            if inverse {
+                Transform::ColorSpaceTransform { src: dst, dst: src }
+            } else {
+                Transform::ColorSpaceTransform { src: src, dst: dst }
+            }
+        }
+
+        "MatrixTransform" => {
+            let mut m = [
+                1.0f32, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+            ];
+            if let Some(values) = map.get("matrix") {
+                let values = get_numbers(values)?;
+                if values.len() != 16 {
+                    return None;
+                }
+                for (n, v) in m.iter_mut().zip(values.iter()) {
+                    *n = *v as f32;
+                }
+            }
+            // `Transform::MatrixTransform` stores the RGB offsets in the
+            // last column, so they can only be folded in when that column
+            // is otherwise unused.
+            if m[12..16] != [0.0, 0.0, 0.0, 1.0] {
+                return None;
+            }
+            if let Some(values) = map.get("offset") {
+                let values = get_numbers(values)?;
+                if values.len() != 4 || values[3] != 0.0 {
+                    return None;
+                }
+                m[3] += values[0] as f32;
+                m[7] += values[1] as f32;
+                m[11] += values[2] as f32;
+            }
+            if inverse {
+                match Transform::MatrixTransform(m).try_invert() {
+                    Ok(t) => t,
+                    Err(e) => return Some(Err(e)),
+                }
+            } else {
+                Transform::MatrixTransform(m)
+            }
+        }
+
+        "BuiltinTransform" => Transform::BuiltinTransform {
+            name: get_string(map, "style")?,
+            direction_inverse: inverse,
+        },
+
+        "ExponentTransform" => {
+            let value = map.get("value")?;
+            let e = match get_numbers(value) {
+                Some(values) if values.len() == 4 => [values[0], values[1], values[2], values[3]],
+                _ => {
+                    let n = value.as_f64()?;
+                    [n, n, n, 1.0]
+                }
+            };
+            let t = Transform::ExponentTransform(e[0], e[1], e[2], e[3]);
+            if inverse {
+                t.invert()
+            } else {
+                t
+            }
+        }
+
+        "ExponentWithLinearTransform" => {
+            // Only the uniform-across-channels case is supported.
+            let gamma = uniform_rgb(map.get("gamma")?)?;
+            let offset = uniform_rgb(map.get("offset")?)?;
+            Transform::ExponentWithLinearTransform {
+                gamma: gamma,
+                offset: offset,
+                direction_inverse: inverse,
+            }
+        }
+
+        "CDLTransform" => {
+            let rgb = |key: &str, default: f32| -> Option<[f32; 3]> {
+                match map.get(key) {
+                    Some(value) => {
+                        let values = get_numbers(value)?;
+                        if values.len() != 3 {
+                            return None;
+                        }
+                        Some([values[0] as f32, values[1] as f32, values[2] as f32])
+                    }
+                    None => Some([default; 3]),
+                }
+            };
+            Transform::CDLTransform {
+                slope: rgb("slope", 1.0)?,
+                offset: rgb("offset", 0.0)?,
+                power: rgb("power", 1.0)?,
+                saturation: match map.get("sat") {
+                    Some(v) => v.as_f64()? as f32,
+                    None => 1.0,
+                },
+                direction_inverse: inverse,
+            }
+        }
+
+        "RangeTransform" => {
+            let range_in = (
+                map.get("min_in_value").and_then(|v| v.as_f64()),
+                map.get("max_in_value").and_then(|v| v.as_f64()),
+            );
+            let range_out = (
+                map.get("min_out_value").and_then(|v| v.as_f64()),
+                map.get("max_out_value").and_then(|v| v.as_f64()),
+            );
+            let t = Transform::RangeTransform {
+                range_in: range_in,
+                range_out: range_out,
+                clamp: get_string(map, "style").as_deref() != Some("noClamp"),
+            };
+            if inverse {
+                t.invert()
+            } else {
+                t
+            }
+        }
+
+        "AllocationTransform" => Transform::AllocationTransform {
+            allocation: match get_string(map, "allocation").as_deref() {
+                Some("lg2") => Allocation::Log2,
+                _ => Allocation::Uniform,
+            },
+            vars: match map.get("vars") {
+                Some(vars) => get_numbers(vars)?,
+                None => Vec::new(),
+            },
+            direction_inverse: inverse,
+        },
+
+        "FixedFunctionTransform" => {
+            if get_string(map, "style").as_deref() != Some("RGB_TO_HSV") {
+                return None;
+            }
+            if inverse {
+                Transform::FromHSV
+            } else {
+                Transform::ToHSV
+            }
+        }
+
+        _ => return None,
+    };
+
+    Some(Ok(transform))
+}
+
+/// Inverts a parsed transform.  Raw transforms are inverted by flipping
+/// their `direction` field.
+fn invert(transform: Transform) -> Result<Transform, ImportError> {
+    Ok(match transform {
+        Transform::Raw(text) => {
+            let value: Value = match serde_yaml::from_str(&shorthand_tags(&text)) {
+                Ok(v) => v,
+                Err(_) => return Ok(Transform::Raw(text)),
+            };
+            match value {
+                Value::Tagged(mut tagged) => {
+                    if let Value::Mapping(ref mut map) = tagged.value {
+                        if is_inverse(map) {
+                            map.remove("direction");
+                        } else {
+                            map.insert("direction".into(), "inverse".into());
+                        }
+                    }
+                    Transform::Raw(to_flow_yaml(&Value::Tagged(tagged)))
+                }
+                _ => Transform::Raw(text),
+            }
+        }
+        _ => transform
+            .try_invert()
+            .map_err(ImportError::InvalidTransform)?,
+    })
+}
+
+fn is_inverse(map: &Mapping) -> bool {
+    get_string(map, "direction").as_deref() == Some("inverse")
+}
+
+pub(crate) fn collect_file_sources(transforms: &[Transform], sources: &mut Vec<String>) {
+    for t in transforms.iter() {
+        if let Transform::FileTransform { ref src, .. } = *t {
+            if !sources.contains(src) {
+                sources.push(src.clone());
+            }
+        }
+    }
+}
+
+//-------------------------------------------------------------
+// Reference space.
+
+/// Determines the chromaticities of the config's reference space, via
+/// one of the interchange roles.
+///
+/// The interchange color space must either be the reference space
+/// itself, or be related to it by matrices only.
+fn reference_space_chroma(config: &OCIOConfig) -> Option<chroma::Chromaticities> {
+    // (role, interchange space to XYZ matrix)
+    let interchanges = [
+        (
+            &config.roles.cie_xyz_d65_interchange,
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        ),
+        (
+            &config.roles.aces_interchange,
+            matrix::rgb_to_xyz_matrix(chroma::ACES_AP0),
+        ),
+    ];
+
+    for (name, interchange_to_xyz) in interchanges.iter() {
+        let space = match name {
+            Some(name) => match config
+                .colorspaces
+                .iter()
+                .find(|s| &s.name == name || s.aliases.contains(name))
+            {
+                Some(space) => space,
+                None => continue,
+            },
+            None => continue,
+        };
+
+        // Matrix from the interchange space to the reference space.
+        let to_reference = if !space.to_reference.is_empty() {
+            match compose_matrices(&space.to_reference) {
+                Some(m) => m,
+                None => continue,
+            }
+        } else {
+            match compose_matrices(&space.from_reference).and_then(matrix::invert) {
+                Some(m) => m,
+                None => continue,
+            }
+        };
+
+        let reference_to_xyz = matrix::compose(&[
+            match matrix::invert(to_reference) {
+                Some(m) => m,
+                None => continue,
+            },
+            *interchange_to_xyz,
+        ]);
+        let xy = |rgb: [f64; 3]| {
+            let xyz = matrix::transform_color(rgb, reference_to_xyz);
+            let sum = xyz[0] + xyz[1] + xyz[2];
+            (xyz[0] / sum, xyz[1] / sum)
+        };
+
+        return Some(chroma::Chromaticities {
+            r: xy([1.0, 0.0, 0.0]),
+            g: xy([0.0, 1.0, 0.0]),
+            b: xy([0.0, 0.0, 1.0]),
+            w: xy([1.0, 1.0, 1.0]),
+        });
+    }
+
+    None
+}
+
+/// Composes a list of matrix transforms into a single 3x3 matrix.
+///
+/// Returns `None` if any of the transforms aren't matrices.  An empty
+/// list results in an identity matrix.
+fn compose_matrices(transforms: &[Transform]) -> Option<matrix::Matrix> {
+    let mut matrices = vec![[[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]];
+    for t in transforms.iter() {
+        match *t {
+            Transform::MatrixTransform(m) if m[3] == 0.0 && m[7] == 0.0 && m[11] == 0.0 => matrices
+                .push([
+                    [m[0] as f64, m[1] as f64, m[2] as f64],
+                    [m[4] as f64, m[5] as f64, m[6] as f64],
+                    [m[8] as f64, m[9] as f64, m[10] as f64],
+                ]),
+            _ => return None,
+        }
+    }
+    Some(matrix::compose(&matrices))
+}
+
+//-------------------------------------------------------------
+// YAML helpers.
+
+/// Converts verbatim tags (e.g. `!<ColorSpace>`), as used by OCIO, to
+/// shorthand tags (e.g. `!ColorSpace`), since the former are discarded
+/// by the YAML parser.
+fn shorthand_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("!<") {
+        match rest[start..].find('>') {
+            Some(len) => {
+                out.push_str(&rest[..start]);
+                out.push('!');
+                out.push_str(&rest[(start + 2)..(start + len)]);
+                rest = &rest[(start + len + 1)..];
+            }
+            None => break,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Splits a value into its tag (if any, without the leading `!`) and
+/// mapping (if it is one).
+fn untag(value: &Value) -> (Option<String>, Option<&Mapping>) {
+    match value {
+        Value::Tagged(tagged) => (
+            Some(tagged.tag.to_string().trim_start_matches('!').into()),
+            tagged.value.as_mapping(),
+        ),
+        Value::Mapping(map) => (None, Some(map)),
+        _ => (None, None),
+    }
+}
+
+fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn get_string(map: &Mapping, key: &str) -> Option<String> {
+    map.get(key).and_then(as_string)
+}
+
+fn get_string_list(map: &Mapping, key: &str) -> Vec<String> {
+    match map.get(key) {
+        Some(Value::Sequence(values)) => values.iter().filter_map(as_string).collect(),
+        // OCIO also accepts comma-separated lists in a string.
+        Some(Value::String(s)) => s
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.into())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn get_numbers(value: &Value) -> Option<Vec<f64>> {
+    match value {
+        Value::Sequence(values) => values.iter().map(|v| v.as_f64()).collect(),
+        _ => None,
+    }
+}
+
+/// For fields that can be either a single number or an RGBA list, where
+/// the RGB values must all be the same.
+fn uniform_rgb(value: &Value) -> Option<f64> {
+    match get_numbers(value) {
+        Some(values) if values.len() >= 3 => {
+            if values[0] == values[1] && values[1] == values[2] {
+                Some(values[0])
+            } else {
+                None
+            }
+        }
+        _ => value.as_f64(),
+    }
+}
+
+/// Writes a value as single-line flow-style YAML, with OCIO-style
+/// verbatim tags.
+fn to_flow_yaml(value: &Value) -> String {
+    match value {
+        Value::Null => "~".into(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => {
+            let needs_quotes =
+                s.is_empty() || s.trim() != s || s.contains(|c| ":,[]{}#&*!|>'\"%@`".contains(c));
+            if needs_quotes {
+                format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+            } else {
+                s.clone()
+            }
+        }
+        Value::Sequence(values) => {
+            let items: Vec<String> = values.iter().map(to_flow_yaml).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Mapping(map) => {
+            let items: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{}: {}", to_flow_yaml(k), to_flow_yaml(v)))
+                .collect();
+            format!("{{ {} }}", items.join(", "))
+        }
+        Value::Tagged(tagged) => format!(
+            "!<{}> {}",
+            tagged.tag.to_string().trim_start_matches('!'),
+            to_flow_yaml(&tagged.value)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CONFIG: &str = r#"
+ocio_profile_version: 2.1
+
+environment:
+  SHOT: sh010
+
+search_path: "luts:grades/$SHOT:other"
+
+roles:
+  reference: Linear
+  cie_xyz_d65_interchange: XYZ
+  scene_linear: Linear
+
+file_rules:
+  - !<Rule> { name: LogC, colorspace: XYZ, pattern: "*LogC*", extension: "*" }
+  - !<Rule> { name: ColorSpaceNamePathSearch }
+  - !<Rule> { name: Default, colorspace: scene_linear }
+
+viewing_rules:
+  - !<Rule> { name: video, encodings: [sdr-video] }
+
+displays:
+  sRGB:
+    - !<View> { name: Standard, colorspace: sRGB }
+    - !<View> { name: Fancy, view_transform: Fancy, display_colorspace: <USE_DISPLAY_NAME> }
+
+named_transforms:
+  - !<NamedTransform>
+    name: sRGB Curve
+    encoding: sdr-video
+    inverse_transform: !<ExponentWithLinearTransform> { gamma: 2.4, offset: 0.055 }
+
+colorspaces:
+  - !<ColorSpace>
+    name: Linear
+    aliases: [lin]
+    isdata: false
+
+  - !<ColorSpace>
+    name: XYZ
+    from_scene_reference: !<MatrixTransform> { matrix: [0.4124, 0.3576, 0.1805, 0, 0.2126, 0.7152, 0.0722, 0, 0.0193, 0.1192, 0.9505, 0, 0, 0, 0, 1] }
+
+  - !<ColorSpace>
+    name: sRGB
+    to_scene_reference: !<GroupTransform>
+      children:
+        - !<FileTransform> { src: srgb.spi1d, interpolation: linear }
+        - !<LogCameraTransform> { base: 2, direction: inverse }
+"#;
+
+    #[test]
+    fn shorthand_tags_test() {
+        assert_eq!(
+            shorthand_tags("- !<View> { name: a }\n- !<Look> {}"),
+            "- !View { name: a }\n- !Look {}"
+        );
+    }
+
+    #[test]
+    fn parse_config_test() {
+        let (config, warnings) = parse_config(TEST_CONFIG).unwrap();
+
+        assert_eq!(config.roles.reference, Some("Linear".into()));
+        assert_eq!(
+            config.roles.other.get("scene_linear"),
+            Some(&"Linear".into())
+        );
+        assert_eq!(config.colorspaces.len(), 3);
+        assert_eq!(config.colorspaces[0].aliases, vec!["lin".to_string()]);
+        assert_eq!(config.displays[0].views.len(), 1);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(config.named_transforms.len(), 1);
+        assert_eq!(config.file_rules.len(), 3);
+        assert_eq!(
+            config.file_rules[0].kind,
+            FileRuleKind::Pattern {
+                pattern: "*LogC*".into(),
+                extension: "*".into(),
+            }
+        );
+        assert_eq!(config.file_rules[2].kind, FileRuleKind::Default);
+        assert_eq!(config.viewing_rules[0].encodings, vec![Encoding::SDRVideo]);
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.search_path, vec!["luts", "grades/$SHOT", "other"]);
+        assert_eq!(
+            config.environment,
+            vec![("SHOT".to_string(), "sh010".to_string())]
+        );
+        assert_eq!(
+            config.named_transforms[0].inverse_transform,
+            vec![Transform::ExponentWithLinearTransform {
+                gamma: 2.4,
+                offset: 0.055,
+                direction_inverse: false,
+            }]
+        );
+
+        // Unsupported transforms are kept as-is.
+        assert_eq!(config.colorspaces[2].to_reference.len(), 2);
+        assert_eq!(
+            config.colorspaces[2].to_reference[1],
+            Transform::Raw("!<LogCameraTransform> { base: 2, direction: inverse }".into())
+        );
+
+        // The reference space is roughly Rec.709.
+        let chroma = config.reference_space_chroma;
+        assert!((chroma.r.0 - 0.64).abs() < 0.001);
+        assert!((chroma.g.1 - 0.6).abs() < 0.001);
+        assert!((chroma.w.0 - 0.3127).abs() < 0.001);
+    }
+
+    #[test]
+    fn parse_config_singular_matrix() {
+        let text = "ocio_profile_version: 2
+colorspaces:
+  - !<ColorSpace>
+    name: Flat
+    to_scene_reference: !<MatrixTransform> { matrix: [1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1], direction: inverse }
+";
+        assert!(matches!(
+            parse_config(text),
+            Err(ImportError::InvalidTransform(_))
+        ));
+
+        // Also inside inverted group transforms.
+        let text = text.replace(
+            "!<MatrixTransform> { matrix: [1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1], direction: inverse }",
+            "!<GroupTransform> { children: [!<MatrixTransform> { matrix: [1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1] }], direction: inverse }",
+        );
+        assert!(matches!(
+            parse_config(&text),
+            Err(ImportError::InvalidTransform(_))
+        ));
+    }
+}
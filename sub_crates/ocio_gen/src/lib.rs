@@ -1,15 +1,25 @@
+pub mod aces_config;
 pub mod blender_3_config;
 pub mod blender_4_config;
 pub mod config;
+pub mod diff;
+pub mod eval;
+pub mod hand_edits;
+pub mod gamut_map;
+pub mod import;
+pub mod lut_extrapolation;
 pub mod minimal_config;
+pub mod v1_compat;
+pub mod verify;
 
 mod agx;
 mod bezier;
 mod data;
-mod gamut_map;
 mod hsv_lut;
 mod tone_map;
 
+pub use tone_map::ToneMapParams;
+
 /// Helper function to decompress in-memory xz-compressed data.
 fn decompress_xz(data: &[u8]) -> Vec<u8> {
     let mut decompressed_data = std::io::Cursor::new(Vec::new());
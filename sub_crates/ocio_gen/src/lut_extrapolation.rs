@@ -0,0 +1,334 @@
+//! Out-of-domain behavior of 1D LUTs.
+//!
+//! Applications disagree about what a 1D LUT does with input outside of
+//! its domain: most clamp, but some extend the end segments.  To make
+//! that explicit, `Extrapolation` names a policy, which the evaluator
+//! honors when looking up LUTs, and `extend_lut()` bakes into a LUT's
+//! table so that applications which clamp at the domain get the same
+//! result.  `ExtrapolatedLut` keeps a LUT together with its policy, for
+//! writers of formats that can express it.
+
+use colorbox::lut::Lut1D;
+
+/// What a 1D LUT gives for input outside of its domain.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Extrapolation {
+    /// Input outside of the domain gives the value at the nearest end
+    /// of the table.
+    Clamp,
+    /// The segment between the last two samples at each end of the
+    /// table is extended as a straight line.
+    LinearExtend,
+    /// Both ends are extended as straight lines with the average slope
+    /// of the whole table.
+    ConstantSlope,
+}
+
+pub const EXTRAPOLATIONS: &[Extrapolation] = &[
+    Extrapolation::Clamp,
+    Extrapolation::LinearExtend,
+    Extrapolation::ConstantSlope,
+];
+
+impl Default for Extrapolation {
+    fn default() -> Extrapolation {
+        Extrapolation::Clamp
+    }
+}
+
+impl Extrapolation {
+    /// The identifier used on the command line and in LUT metadata.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Extrapolation::Clamp => "clamp",
+            Extrapolation::LinearExtend => "linear",
+            Extrapolation::ConstantSlope => "slope",
+        }
+    }
+
+    pub fn from_str(text: &str) -> Option<Extrapolation> {
+        match text.trim() {
+            "clamp" => Some(Extrapolation::Clamp),
+            "linear" => Some(Extrapolation::LinearExtend),
+            "slope" => Some(Extrapolation::ConstantSlope),
+            _ => None,
+        }
+    }
+
+    /// Human-readable name, for UI.
+    pub fn ui_text(&self) -> &'static str {
+        match *self {
+            Extrapolation::Clamp => "Clamp",
+            Extrapolation::LinearExtend => "Linear extend",
+            Extrapolation::ConstantSlope => "Constant slope",
+        }
+    }
+
+    /// The straight lines that input below and above the domain follow,
+    /// as `(value at the domain end, slope)` pairs.  `None` means the
+    /// value is held constant.
+    fn end_lines(&self, range: (f32, f32), table: &[f32]) -> Option<[(f32, f32); 2]> {
+        let len = table.len();
+        if len < 2 || range.1 <= range.0 {
+            return None;
+        }
+        let (first, last) = (table[0], table[len - 1]);
+        match *self {
+            Extrapolation::Clamp => None,
+            Extrapolation::LinearExtend => {
+                let step = (range.1 - range.0) / (len - 1) as f32;
+                Some([
+                    (first, (table[1] - first) / step),
+                    (last, (last - table[len - 2]) / step),
+                ])
+            }
+            Extrapolation::ConstantSlope => {
+                let slope = (last - first) / (range.1 - range.0);
+                Some([(first, slope), (last, slope)])
+            }
+        }
+    }
+}
+
+/// A 1D LUT along with what it gives outside of its domain, which
+/// `Lut1D` itself has no notion of.
+#[derive(Debug, Clone)]
+pub struct ExtrapolatedLut {
+    pub lut: Lut1D,
+    pub extrapolation: Extrapolation,
+}
+
+impl ExtrapolatedLut {
+    pub fn new(lut: Lut1D, extrapolation: Extrapolation) -> ExtrapolatedLut {
+        ExtrapolatedLut {
+            lut: lut,
+            extrapolation: extrapolation,
+        }
+    }
+
+    /// See `look_up()`.
+    pub fn look_up(&self, table: usize, x: f32) -> f32 {
+        look_up(&self.lut, table, x, self.extrapolation)
+    }
+
+    /// See `look_up_inv()`.
+    pub fn look_up_inv(&self, table: usize, y: f32) -> f32 {
+        look_up_inv(&self.lut, table, y, self.extrapolation)
+    }
+
+    /// The LUT with the policy baked into its tables, for formats that
+    /// can't express it.  See `extend_lut()`.
+    pub fn extended(self, margin: f32) -> Lut1D {
+        extend_lut(self.lut, self.extrapolation, margin)
+    }
+}
+
+/// Looks up `x` in one of the LUT's tables, extrapolating outside of
+/// the table's domain according to `policy`.
+pub fn look_up(lut: &Lut1D, table: usize, x: f32, policy: Extrapolation) -> f32 {
+    let (range, values) = (
+        lut.ranges[table.min(lut.ranges.len() - 1)],
+        &lut.tables[table],
+    );
+    if x >= range.0 && x <= range.1 {
+        return lut.look_up(x, table);
+    }
+
+    match policy.end_lines(range, values) {
+        Some([(y, slope), _]) if x < range.0 => y + ((x - range.0) * slope),
+        Some([_, (y, slope)]) => y + ((x - range.1) * slope),
+        None => lut.look_up(x.max(range.0).min(range.1), table),
+    }
+}
+
+/// The inverse of `look_up()`: finds the input that gives `y`.
+///
+/// Outputs beyond the table's ends are mapped back through the
+/// extrapolated lines, which works for both increasing and decreasing
+/// tables.  With `Clamp` (or a flat end) they give the domain end.
+pub fn look_up_inv(lut: &Lut1D, table: usize, y: f32, policy: Extrapolation) -> f32 {
+    let (range, values) = (
+        lut.ranges[table.min(lut.ranges.len() - 1)],
+        &lut.tables[table],
+    );
+    let (first, last) = match (values.first(), values.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return range.0,
+    };
+    if y >= first.min(last) && y <= first.max(last) {
+        return lut.look_up_inv(y, table);
+    }
+
+    // Whether `y` is past the start of the table rather than its end.
+    let below = if last >= first { y < first } else { y > first };
+    let (end, (end_y, slope)) = match policy.end_lines(range, values) {
+        Some([low, _]) if below => (range.0, low),
+        Some([_, high]) => (range.1, high),
+        None if below => (range.0, (first, 0.0)),
+        None => (range.1, (last, 0.0)),
+    };
+    if slope == 0.0 {
+        end
+    } else {
+        end + ((y - end_y) / slope)
+    }
+}
+
+/// Returns a copy of the LUT with each table extended beyond its domain
+/// by `margin` (a fraction of the domain's width) on both sides,
+/// following `policy`.
+///
+/// The new samples have the same spacing as the existing ones, which
+/// are kept as-is, so the table grows in the same proportion.  With
+/// `Clamp` the LUT is returned unchanged, since that's what a clamping
+/// application does anyway.
+pub fn extend_lut(lut: Lut1D, policy: Extrapolation, margin: f32) -> Lut1D {
+    if policy == Extrapolation::Clamp || margin <= 0.0 {
+        return lut;
+    }
+
+    let mut ranges = Vec::with_capacity(lut.tables.len());
+    let mut tables = Vec::with_capacity(lut.tables.len());
+    for (i, table) in lut.tables.iter().enumerate() {
+        let range = lut.ranges[i.min(lut.ranges.len() - 1)];
+        let lines = match policy.end_lines(range, table) {
+            Some(lines) => lines,
+            None => {
+                ranges.push(range);
+                tables.push(table.clone());
+                continue;
+            }
+        };
+
+        let step = (range.1 - range.0) / (table.len() - 1) as f32;
+        let extra = (margin * (table.len() - 1) as f32).ceil() as usize;
+        let offset = step * extra as f32;
+        let mut extended = Vec::with_capacity(table.len() + (extra * 2));
+        extended.extend((0..extra).map(|j| {
+            let (y, slope) = lines[0];
+            y - (slope * step * (extra - j) as f32)
+        }));
+        extended.extend_from_slice(table);
+        extended.extend((1..=extra).map(|j| {
+            let (y, slope) = lines[1];
+            y + (slope * step * j as f32)
+        }));
+        ranges.push((range.0 - offset, range.1 + offset));
+        tables.push(extended);
+    }
+
+    Lut1D {
+        ranges: ranges,
+        tables: tables,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A curve with different slopes at its two ends: 0.0, 0.1, 0.3,
+    // 0.6, 1.0 over [0.0, 1.0].
+    fn curve() -> Lut1D {
+        Lut1D {
+            ranges: vec![(0.0, 1.0)],
+            tables: vec![vec![0.0, 0.1, 0.3, 0.6, 1.0]],
+        }
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1.0e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn look_up_out_of_range() {
+        let lut = curve();
+
+        assert_near(look_up(&lut, 0, -0.5, Extrapolation::Clamp), 0.0);
+        assert_near(look_up(&lut, 0, 1.5, Extrapolation::Clamp), 1.0);
+
+        // End segments have slopes of 0.4 and 1.6.
+        assert_near(look_up(&lut, 0, -0.5, Extrapolation::LinearExtend), -0.2);
+        assert_near(look_up(&lut, 0, 1.5, Extrapolation::LinearExtend), 1.8);
+
+        // The whole table has a slope of 1.0.
+        assert_near(look_up(&lut, 0, -0.5, Extrapolation::ConstantSlope), -0.5);
+        assert_near(look_up(&lut, 0, 1.5, Extrapolation::ConstantSlope), 1.5);
+    }
+
+    #[test]
+    fn look_up_in_range() {
+        let lut = curve();
+        for policy in EXTRAPOLATIONS.iter() {
+            assert_near(look_up(&lut, 0, 0.0, *policy), 0.0);
+            assert_near(look_up(&lut, 0, 0.625, *policy), 0.45);
+            assert_near(look_up(&lut, 0, 1.0, *policy), 1.0);
+        }
+    }
+
+    #[test]
+    fn look_up_inv_out_of_range() {
+        let lut = curve();
+        for policy in EXTRAPOLATIONS.iter() {
+            for x in [-0.5f32, -0.1, 1.2, 1.5].iter() {
+                let y = look_up(&lut, 0, *x, *policy);
+                let expected = if *policy == Extrapolation::Clamp {
+                    x.max(0.0).min(1.0)
+                } else {
+                    *x
+                };
+                assert_near(look_up_inv(&lut, 0, y, *policy), expected);
+            }
+        }
+
+        // Decreasing tables.
+        let lut = Lut1D {
+            ranges: vec![(0.0, 1.0)],
+            tables: vec![vec![1.0, 0.5, 0.0]],
+        };
+        assert_near(look_up_inv(&lut, 0, 1.5, Extrapolation::LinearExtend), -0.5);
+        assert_near(look_up_inv(&lut, 0, -0.5, Extrapolation::LinearExtend), 1.5);
+        assert_near(look_up_inv(&lut, 0, -0.5, Extrapolation::Clamp), 1.0);
+    }
+
+    #[test]
+    fn extend_lut_01() {
+        let lut = curve();
+        assert_eq!(
+            extend_lut(curve(), Extrapolation::Clamp, 0.5).tables,
+            lut.tables
+        );
+
+        for policy in [Extrapolation::LinearExtend, Extrapolation::ConstantSlope].iter() {
+            let extended = extend_lut(curve(), *policy, 0.5);
+            assert_eq!(extended.ranges, vec![(-0.5, 1.5)]);
+            assert_eq!(extended.tables[0].len(), 9);
+            assert_eq!(&extended.tables[0][2..7], &lut.tables[0][..]);
+
+            // A clamping application now gets the policy's result
+            // within the margin.
+            for x in [-0.5f32, -0.3, 0.4, 1.1, 1.5].iter() {
+                assert_near(
+                    look_up(&extended, 0, *x, Extrapolation::Clamp),
+                    look_up(&lut, 0, *x, *policy),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn extrapolated_lut_01() {
+        let lut = ExtrapolatedLut::new(curve(), Extrapolation::LinearExtend);
+        assert_near(lut.look_up(0, 1.5), 1.8);
+        assert_near(lut.look_up_inv(0, 1.8), 1.5);
+        assert_eq!(lut.extended(0.5).ranges, vec![(-0.5, 1.5)]);
+    }
+
+    #[test]
+    fn from_str_round_trip() {
+        for policy in EXTRAPOLATIONS.iter() {
+            assert_eq!(Extrapolation::from_str(policy.as_str()), Some(*policy));
+        }
+        assert_eq!(Extrapolation::from_str("wrap"), None);
+    }
+}
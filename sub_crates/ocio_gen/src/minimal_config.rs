@@ -1,21 +1,39 @@
 use crate::{
     agx::{make_agx_display_p3, make_agx_rec2020, make_agx_rec709},
     config::*,
-    tone_map::{ToneCurve, Tonemapper},
+    gamut_map::GamutMapping,
+    tone_map::{ToneCurve, ToneMapParams, Tonemapper},
 };
 
 use colorbox::{chroma, matrix};
 
+/// Luminance in nits that scene-linear 1.0 maps to in the tone mapped
+/// HDR views, per the reference white of ITU-R BT.2408.
+const HDR_REFERENCE_WHITE_NITS: f64 = 203.0;
+
+/// Peak luminance in nits of the tone mapped HDR views.
+const HDR_PEAK_NITS: f64 = 1000.0;
+
 /// Builds a config with just the bare basics.
+///
+/// `tone_map_params` adjusts the tone mapping view transforms.
 pub fn make_minimal(
     reference_space_chroma: chroma::Chromaticities,
     whitepoint_adaptation_method: matrix::AdaptationMethod,
+    tone_map_params: ToneMapParams,
 ) -> OCIOConfig {
-    let toney_neutral_sdr_curve = ToneCurve::new(1.0, 0.0, 1.3);
-    let toney_filmic_sdr_curve = ToneCurve::new(1.0, 0.4, 1.0);
+    let shoulder = tone_map_params.shoulder;
+    let toney_neutral_sdr_curve = ToneCurve::new(1.0, 0.0, 1.3 * shoulder);
+    let toney_filmic_sdr_curve = ToneCurve::new(1.0, 0.4, 1.0 * shoulder);
+
+    let toney_neutral_hdr_curve = ToneCurve::new(12.0, 0.0, 1.3 * shoulder);
+    let toney_filmic_hdr_curve = ToneCurve::new(12.0, 0.4, 1.0 * shoulder);
 
-    let toney_neutral_hdr_curve = ToneCurve::new(12.0, 0.0, 1.3);
-    let toney_filmic_hdr_curve = ToneCurve::new(12.0, 0.4, 1.0);
+    // Curves for Rec.2100 displays, which top out at the peak
+    // luminance relative to reference white.
+    let rec2100_ceiling = HDR_PEAK_NITS / HDR_REFERENCE_WHITE_NITS;
+    let toney_neutral_rec2100_curve = ToneCurve::new(rec2100_ceiling, 0.0, 1.3 * shoulder);
+    let toney_filmic_rec2100_curve = ToneCurve::new(rec2100_ceiling, 0.4, 1.0 * shoulder);
 
     // Tone mapping operators, used various places below.
     let toney_neutral_rec709 = Tonemapper::new(
@@ -25,7 +43,8 @@ pub fn make_minimal(
         0.5,
         0.2,
         0.2,
-    );
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
     let toney_filmic_rec709 = Tonemapper::new(
         Some(chroma::REC709),
         1.0,
@@ -33,7 +52,8 @@ pub fn make_minimal(
         0.5,
         0.2,
         0.2,
-    );
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
 
     let toney_neutral_rec709_hdr = Tonemapper::new(
         Some(chroma::REC709),
@@ -42,7 +62,8 @@ pub fn make_minimal(
         0.5,
         0.2,
         0.2,
-    );
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
     let toney_filmic_rec709_hdr = Tonemapper::new(
         Some(chroma::REC709),
         1.1,
@@ -50,7 +71,8 @@ pub fn make_minimal(
         0.5,
         0.2,
         0.2,
-    );
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
 
     let toney_neutral_rec2020 = Tonemapper::new(
         Some(chroma::REC2020),
@@ -59,7 +81,8 @@ pub fn make_minimal(
         0.5,
         0.2,
         0.2,
-    );
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
     let toney_filmic_rec2020 = Tonemapper::new(
         Some(chroma::REC2020),
         1.0,
@@ -67,7 +90,8 @@ pub fn make_minimal(
         0.5,
         0.2,
         0.2,
-    );
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
 
     let toney_neutral_rec2020_hdr = Tonemapper::new(
         Some(chroma::REC2020),
@@ -76,7 +100,8 @@ pub fn make_minimal(
         0.5,
         0.2,
         0.2,
-    );
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
     let toney_filmic_rec2020_hdr = Tonemapper::new(
         Some(chroma::REC2020),
         1.0,
@@ -84,12 +109,32 @@ pub fn make_minimal(
         0.5,
         0.2,
         0.2,
-    );
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
+
+    let toney_neutral_rec2100 = Tonemapper::new(
+        Some(chroma::REC2020),
+        1.0,
+        toney_neutral_rec2100_curve,
+        0.5,
+        0.2,
+        0.2,
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
+    let toney_filmic_rec2100 = Tonemapper::new(
+        Some(chroma::REC2020),
+        1.1,
+        toney_filmic_rec2100_curve,
+        0.5,
+        0.2,
+        0.2,
+    )
+    .with_adjustments(tone_map_params.contrast, tone_map_params.saturation);
 
     // AgX.
-    let agx_rec709 = make_agx_rec709();
-    let agx_rec2020 = make_agx_rec2020();
-    let agx_display_p3 = make_agx_display_p3();
+    let agx_rec709 = make_agx_rec709(tone_map_params);
+    let agx_rec2020 = make_agx_rec2020(tone_map_params);
+    let agx_display_p3 = make_agx_display_p3(tone_map_params);
 
     //---------------------------------------------------------
 
@@ -189,6 +234,14 @@ pub fn make_minimal(
                 "Standard".into(),
                 "Rec.2100 PQ 1000 nits Gamut Clipped".into(),
             ),
+            (
+                "Toney (Neutral) HDR".into(),
+                "Rec.2100 PQ 1000 nits Toney Neutral".into(),
+            ),
+            (
+                "Toney (Filmic) HDR".into(),
+                "Rec.2100 PQ 1000 nits Toney Filmic".into(),
+            ),
             ("Raw".into(), "Raw".into()),
         ],
     });
@@ -210,20 +263,19 @@ pub fn make_minimal(
         name: "Rec.2100 HLG".into(),
         views: vec![
             ("Standard".into(), "Rec.2100 HLG Gamut Clipped".into()),
+            (
+                "Toney (Neutral) HDR".into(),
+                "Rec.2100 HLG Toney Neutral".into(),
+            ),
+            (
+                "Toney (Filmic) HDR".into(),
+                "Rec.2100 HLG Toney Filmic".into(),
+            ),
             ("Raw".into(), "Raw".into()),
         ],
     });
     config.active_displays.push("Rec.2100 HLG".into());
 
-    config.displays.push(Display {
-        name: "DCI-P3".into(),
-        views: vec![
-            ("Standard".into(), "DCI-P3 Gamut Clipped".into()),
-            ("Raw".into(), "Raw".into()),
-        ],
-    });
-    config.active_displays.push("DCI-P3".into());
-
     config.displays.push(Display {
         name: "Display P3".into(),
         views: vec![
@@ -554,18 +606,70 @@ pub fn make_minimal(
         true,
     );
 
+    // Tone mapped views.  These put scene-linear 1.0 at reference
+    // white and roll off to the display's peak, with the tone mapper
+    // handling gamut mapping to Rec.2020.
+    for (name, tonemapper, lut_name) in [
+        ("Toney Neutral", &toney_neutral_rec2100, "toney_neutral"),
+        ("Toney Filmic", &toney_filmic_rec2100, "toney_filmic"),
+    ] {
+        let tone_map = tonemapper.tone_map_transforms(
+            &format!("omkr__{}_rec2100_curve_inv.spi1d", lut_name),
+            &format!("omkr__{}_rec2100_chroma.cube", lut_name),
+        );
+
+        // PQ, with peak at 1000 nits.
+        let mut transforms = tone_map.clone();
+        transforms.push(Transform::RangeTransform {
+            range_in: (Some(0.0), Some(rec2100_ceiling)),
+            range_out: (Some(0.0), Some(HDR_PEAK_NITS / 10000.0)),
+            clamp: true,
+        });
+        config.add_display_colorspace(
+            format!("Rec.2100 PQ 1000 nits {}", name),
+            None,
+            chroma::REC2020,
+            whitepoint_adaptation_method,
+            transforms,
+            Transform::FileTransform {
+                src: "pq_norm_to_linear.spi1d".into(),
+                interpolation: Interpolation::Linear,
+                direction_inverse: true,
+            },
+            false,
+        );
+
+        // HLG, with peak at signal 1.0.  The inverse of the (nominal
+        // 1000-nit) display's system gamma is applied so that
+        // reference white lands at 75% signal, as per BT.2408.
+        let mut transforms = tone_map;
+        transforms.extend([
+            Transform::RangeTransform {
+                range_in: (Some(0.0), Some(rec2100_ceiling)),
+                range_out: (Some(0.0), Some(1.0)),
+                clamp: true,
+            },
+            Transform::ExponentTransform(1.2, 1.2, 1.2, 1.0).invert(),
+        ]);
+        config.add_display_colorspace(
+            format!("Rec.2100 HLG {}", name),
+            None,
+            chroma::REC2020,
+            whitepoint_adaptation_method,
+            transforms,
+            Transform::FileTransform {
+                src: "hlg_to_linear.spi1d".into(),
+                interpolation: Interpolation::Linear,
+                direction_inverse: true,
+            },
+            false,
+        );
+    }
+
     //----------
-    // DCI-P3
+    // Theatrical P3 (DCI-P3 and P3-D65)
 
-    config.add_display_colorspace(
-        "DCI-P3 Gamut Clipped".into(),
-        None,
-        chroma::DCI_P3,
-        whitepoint_adaptation_method,
-        vec![],
-        Transform::ExponentTransform(2.6, 2.6, 2.6, 1.0).invert(),
-        true,
-    );
+    config.add_p3_displays(whitepoint_adaptation_method);
 
     //----------
     // Display P3
@@ -657,59 +761,69 @@ pub fn make_minimal(
         ..ColorSpace::default()
     });
 
-    config.add_input_colorspace(
-        "ACES".into(),
-        Some("linear".into()),
-        Some("ACES AP0 linear space".into()),
-        chroma::ACES_AP0,
-        whitepoint_adaptation_method,
-        None,
-        true,
-    );
+    config
+        .add_input_colorspace(
+            "ACES".into(),
+            Some("linear".into()),
+            Some("ACES AP0 linear space".into()),
+            chroma::ACES_AP0,
+            whitepoint_adaptation_method,
+            Vec::new(),
+            GamutMapping::Clip,
+        )
+        .unwrap();
 
-    config.add_input_colorspace(
-        "ACES cg".into(),
-        Some("linear".into()),
-        Some("ACES AP1 linear space".into()),
-        chroma::ACES_AP1,
-        whitepoint_adaptation_method,
-        None,
-        true,
-    );
+    config
+        .add_input_colorspace(
+            "ACES cg".into(),
+            Some("linear".into()),
+            Some("ACES AP1 linear space".into()),
+            chroma::ACES_AP1,
+            whitepoint_adaptation_method,
+            Vec::new(),
+            GamutMapping::Clip,
+        )
+        .unwrap();
 
-    config.add_input_colorspace(
-        "sRGB Linear".into(),
-        Some("linear".into()),
-        Some("Linear color space with sRGB/Rec.709 gamut".into()),
-        chroma::REC709,
-        whitepoint_adaptation_method,
-        None,
-        false,
-    );
+    config
+        .add_input_colorspace(
+            "sRGB Linear".into(),
+            Some("linear".into()),
+            Some("Linear color space with sRGB/Rec.709 gamut".into()),
+            chroma::REC709,
+            whitepoint_adaptation_method,
+            Vec::new(),
+            GamutMapping::None,
+        )
+        .unwrap();
 
-    config.add_input_colorspace(
-        "Rec.2020 Linear".into(),
-        Some("linear".into()),
-        Some("Linear color space with Rec.2020 gamut".into()),
-        chroma::REC2020,
-        whitepoint_adaptation_method,
-        None,
-        false,
-    );
+    config
+        .add_input_colorspace(
+            "Rec.2020 Linear".into(),
+            Some("linear".into()),
+            Some("Linear color space with Rec.2020 gamut".into()),
+            chroma::REC2020,
+            whitepoint_adaptation_method,
+            Vec::new(),
+            GamutMapping::None,
+        )
+        .unwrap();
 
-    config.add_input_colorspace(
-        "sRGB".into(),
-        None,
-        None,
-        chroma::REC709,
-        whitepoint_adaptation_method,
-        Some(Transform::ExponentWithLinearTransform {
-            gamma: 2.4,
-            offset: 0.055,
-            direction_inverse: true,
-        }),
-        false,
-    );
+    config
+        .add_input_colorspace(
+            "sRGB".into(),
+            None,
+            None,
+            chroma::REC709,
+            whitepoint_adaptation_method,
+            vec![Transform::ExponentWithLinearTransform {
+                gamma: 2.4,
+                offset: 0.055,
+                direction_inverse: true,
+            }],
+            GamutMapping::None,
+        )
+        .unwrap();
 
     //---------------------------------------------------------
     // Input color spaces abused to create OpenEXR output spaces.
@@ -799,6 +913,11 @@ pub fn make_minimal(
         let (_, toney_filmic_rec2020_3d) = toney_filmic_rec2020.generate_luts();
         let (_, toney_filmic_rec2020_hdr_3d) = toney_filmic_rec2020_hdr.generate_luts();
 
+        let (toney_neutral_rec2100_1d, toney_neutral_rec2100_3d) =
+            toney_neutral_rec2100.generate_luts();
+        let (toney_filmic_rec2100_1d, toney_filmic_rec2100_3d) =
+            toney_filmic_rec2100.generate_luts();
+
         let agx_rec709_3d = agx_rec709.generate_lut();
         let agx_rec2020_3d = agx_rec2020.generate_lut();
         let agx_display_p3_3d = agx_display_p3.generate_lut();
@@ -862,6 +981,23 @@ pub fn make_minimal(
                 "luts/omkr__agx_rec2020.cube".into(),
                 OutputFile::Lut3D(agx_rec2020_3d),
             ),
+            // Rec.2100
+            (
+                "luts/omkr__toney_neutral_rec2100_curve_inv.spi1d".into(),
+                OutputFile::Lut1D(toney_neutral_rec2100_1d),
+            ),
+            (
+                "luts/omkr__toney_neutral_rec2100_chroma.cube".into(),
+                OutputFile::Lut3D(toney_neutral_rec2100_3d),
+            ),
+            (
+                "luts/omkr__toney_filmic_rec2100_curve_inv.spi1d".into(),
+                OutputFile::Lut1D(toney_filmic_rec2100_1d),
+            ),
+            (
+                "luts/omkr__toney_filmic_rec2100_chroma.cube".into(),
+                OutputFile::Lut3D(toney_filmic_rec2100_3d),
+            ),
             // Display P3
             (
                 "luts/omkr__agx_display_p3.cube".into(),
@@ -16,6 +16,36 @@ use crate::config::{ExponentLUTMapper, Interpolation, Transform};
 /// can show up in footage from cameras.
 const PARENT_SPACE_RGB_CHROMA: [(f64, f64); 3] = [(0.9, 0.3), (-0.06, 1.04), (0.0, -0.12)];
 
+/// The scene-linear value that contrast adjustments pivot around.
+const MID_GRAY: f64 = 0.18;
+
+/// User adjustments to the tone mapping view transforms of generated
+/// configs.
+///
+/// Each is relative to the view transform's stock look, so 1.0 for all
+/// of them changes nothing.
+///
+/// - `contrast`: the steepness of the curve around middle gray.
+/// - `shoulder`: the softness of the roll-off into the highlights.
+///   Larger values retain more highlight detail.
+/// - `saturation`: the saturation of the tone mapped image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ToneMapParams {
+    pub contrast: f64,
+    pub shoulder: f64,
+    pub saturation: f64,
+}
+
+impl Default for ToneMapParams {
+    fn default() -> ToneMapParams {
+        ToneMapParams {
+            contrast: 1.0,
+            shoulder: 1.0,
+            saturation: 1.0,
+        }
+    }
+}
+
 /// A filmic(ish) tonemapping operator.
 ///
 /// - `exposure`: input exposure adjustment before applying the tone mapping.
@@ -36,10 +66,12 @@ const PARENT_SPACE_RGB_CHROMA: [(f64, f64); 3] = [(0.9, 0.3), (-0.06, 1.04), (0.
 #[derive(Debug, Copy, Clone)]
 pub struct Tonemapper {
     exposure: f64,
+    contrast: f64,
     tone_curve: ToneCurve,
     saturation_preservation: f64,
     gamut_compression: f64,
     blue_lightness: f64,
+    saturation: f64,
 
     inset_mat: Matrix,
     outset_mat: Matrix,
@@ -125,10 +157,12 @@ impl Tonemapper {
 
         Tonemapper {
             exposure: exposure,
+            contrast: 1.0,
             tone_curve: tone_curve,
             saturation_preservation: saturation_preservation,
             gamut_compression: gamut_compression,
             blue_lightness: blue_lightness,
+            saturation: 1.0,
 
             inset_mat: inset_mat,
             outset_mat: invert(inset_mat).unwrap(),
@@ -155,6 +189,21 @@ impl Tonemapper {
         }
     }
 
+    /// Returns the same tone mapper with contrast and saturation
+    /// adjustments, as in `ToneMapParams`.
+    ///
+    /// - `contrast`: a power applied around middle gray before the tone
+    ///   curve.  1.0 does nothing.
+    /// - `saturation`: scales the saturation of the tone mapped colors.
+    ///   1.0 does nothing, and 0.0 is grayscale.
+    pub fn with_adjustments(mut self, contrast: f64, saturation: f64) -> Self {
+        assert!(contrast > 0.0);
+        assert!(saturation >= 0.0);
+        self.contrast = contrast;
+        self.saturation = saturation;
+        self
+    }
+
     /// The main tone mapping function.
     ///
     /// Takes an input open-domain "scene linear" RGB value, and returns
@@ -299,13 +348,19 @@ impl Tonemapper {
             transform_color(oklab::to_xyz_d65(oklab_adjusted), self.from_xyz_mat)
         };
 
+        // User saturation adjustment.
+        let rgb_saturated = {
+            let gray_level = dot(rgb_hue_fixed, self.display_rgb_luma_weights);
+            vlerp([gray_level; 3], rgb_hue_fixed, self.saturation)
+        };
+
         // The hue adjustment can slightly push colors out of gamut again.  It's
         // not enough to be visually important, so we just do simple clamping
         // here to push the colors back in.
         [
-            rgb_hue_fixed[0].max(0.0).min(self.tone_curve.max_output()),
-            rgb_hue_fixed[1].max(0.0).min(self.tone_curve.max_output()),
-            rgb_hue_fixed[2].max(0.0).min(self.tone_curve.max_output()),
+            rgb_saturated[0].max(0.0).min(self.tone_curve.max_output()),
+            rgb_saturated[1].max(0.0).min(self.tone_curve.max_output()),
+            rgb_saturated[2].max(0.0).min(self.tone_curve.max_output()),
         ]
     }
 
@@ -394,7 +449,7 @@ impl Tonemapper {
             0.0
         } else {
             self.tone_curve
-                .eval(x * self.exposure)
+                .eval(apply_contrast(x * self.exposure, self.contrast))
                 .min(self.tone_curve.max_output())
         }
     }
@@ -410,7 +465,7 @@ impl Tonemapper {
             // f32).
             (f32::MAX / 2.0) as f64
         } else {
-            self.tone_curve.eval_inv(y) / self.exposure
+            apply_contrast(self.tone_curve.eval_inv(y), 1.0 / self.contrast) / self.exposure
         }
     }
 }
@@ -699,6 +754,16 @@ fn vscale(a: [f64; 3], scale: f64) -> [f64; 3] {
     [a[0] * scale, a[1] * scale, a[2] * scale]
 }
 
+/// Applies a contrast power around `MID_GRAY`.  The inverse is the same
+/// with `1.0 / contrast`.
+fn apply_contrast(x: f64, contrast: f64) -> f64 {
+    if x <= 0.0 || contrast == 1.0 {
+        x
+    } else {
+        MID_GRAY * (x / MID_GRAY).powf(contrast)
+    }
+}
+
 fn vlerp(a: [f64; 3], b: [f64; 3], t: f64) -> [f64; 3] {
     [
         lerp(a[0], b[0], t),
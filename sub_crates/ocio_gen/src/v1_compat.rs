@@ -0,0 +1,328 @@
+//! Downgrading configs to OCIO v1, for applications (older Nuke, game
+//! engines, etc.) that can't read v2 configs.
+//!
+//! Transform chains that use v2-only transforms are baked into 3D LUTs
+//! via `eval`.  Anything that can't be represented in v1 is reported.
+
+use std::path::{Path, PathBuf};
+
+use colorbox::lut::Lut3D;
+
+use crate::{
+    config::{
+        Allocation, Encoding, Interpolation, OCIOConfig, OutputFile, ProfileVersion, Transform,
+    },
+    eval::Evaluator,
+};
+
+/// Directory, relative to the config, that baked LUTs are written to.
+const BAKED_DIR: &str = "v1_baked";
+
+/// Samples per axis of baked 3D LUTs.
+const BAKED_RES: usize = 33;
+
+/// Range in stops of the log2 shaper used when baking chains that take
+/// linear input.  Same as the lg2 allocation of the ACES 1.x configs.
+const SHAPER_RANGE: (f64, f64) = (-12.473931188, 12.526068812);
+
+/// A transform chain in the config.
+#[derive(Debug, Copy, Clone)]
+enum Chain {
+    ToReference(usize), // Color space index.
+    FromReference(usize),
+    Look(usize), // Look index.
+    LookInverse(usize),
+}
+
+/// Converts `config` to be written as an OCIO v1 config.
+///
+/// Transform chains with v2-only transforms are replaced by baked 3D
+/// LUTs.  Color spaces and looks whose transforms can't be baked are
+/// removed, along with the roles and views that refer to them.
+///
+/// Returns warnings about everything that couldn't be represented.
+pub fn downgrade(config: &mut OCIOConfig) -> Vec<String> {
+    let mut warnings = Vec::new();
+    config.profile_version = ProfileVersion::V1;
+
+    // Bake.
+    let mut baked = Vec::new(); // (chain, transforms, lut path, lut)
+    let mut failed = Vec::new(); // (chain, reason)
+    {
+        let evaluator = Evaluator::new(config);
+        let mut chains = Vec::new();
+        for (i, space) in config.colorspaces.iter().enumerate() {
+            chains.push((Chain::ToReference(i), &space.to_reference));
+            chains.push((Chain::FromReference(i), &space.from_reference));
+        }
+        for (i, look) in config.looks.iter().enumerate() {
+            chains.push((Chain::Look(i), &look.transform));
+            chains.push((Chain::LookInverse(i), &look.inverse_transform));
+        }
+
+        for (chain, transforms) in chains {
+            if !transforms.iter().any(is_v2_only) {
+                continue;
+            }
+            let linear_input = match chain {
+                Chain::ToReference(i) => is_linear(&evaluator, &config.colorspaces[i].name),
+                Chain::FromReference(_) => config
+                    .roles
+                    .reference
+                    .as_ref()
+                    .map(|name| is_linear(&evaluator, name))
+                    .unwrap_or(true),
+                Chain::Look(i) | Chain::LookInverse(i) => {
+                    is_linear(&evaluator, &config.looks[i].process_space)
+                }
+            };
+            match bake(&evaluator, transforms, linear_input) {
+                Ok((mut prefix, lut)) => {
+                    let path = Path::new(BAKED_DIR).join(lut_file_name(config, chain));
+                    prefix.push(Transform::FileTransform {
+                        src: path.file_name().unwrap().to_string_lossy().into(),
+                        interpolation: Interpolation::Tetrahedral,
+                        direction_inverse: false,
+                    });
+                    baked.push((chain, prefix, path, lut));
+                }
+                Err(reason) => failed.push((chain, reason)),
+            }
+        }
+    }
+
+    if !baked.is_empty() {
+        config.add_search_path(BAKED_DIR);
+    }
+    for (chain, transforms, path, lut) in baked {
+        config.output_files.insert(path, OutputFile::Lut3D(lut));
+        match chain {
+            Chain::ToReference(i) => config.colorspaces[i].to_reference = transforms,
+            Chain::FromReference(i) => config.colorspaces[i].from_reference = transforms,
+            Chain::Look(i) => config.looks[i].transform = transforms,
+            Chain::LookInverse(i) => config.looks[i].inverse_transform = transforms,
+        }
+    }
+
+    // Remove what couldn't be baked.
+    let mut removed_spaces = Vec::new();
+    let mut removed_looks = Vec::new();
+    for (chain, reason) in failed {
+        match chain {
+            Chain::ToReference(i) | Chain::FromReference(i) => {
+                let name = config.colorspaces[i].name.clone();
+                if !removed_spaces.contains(&name) {
+                    warnings.push(format!(
+                        "Color space \"{}\" was left out, because its transforms can't be baked for OCIO v1: {}.",
+                        name, reason
+                    ));
+                    removed_spaces.push(name);
+                }
+            }
+            Chain::Look(i) | Chain::LookInverse(i) => {
+                let name = config.looks[i].name.clone();
+                if !removed_looks.contains(&name) {
+                    warnings.push(format!(
+                        "Look \"{}\" was left out, because its transforms can't be baked for OCIO v1: {}.",
+                        name, reason
+                    ));
+                    removed_looks.push(name);
+                }
+            }
+        }
+    }
+    config
+        .colorspaces
+        .retain(|s| !removed_spaces.contains(&s.name));
+    config.looks.retain(|l| !removed_looks.contains(&l.name));
+    for display in config.displays.iter_mut() {
+        let display_name = display.name.clone();
+        display.views.retain(|(view, space)| {
+            let keep = !removed_spaces.contains(space);
+            if !keep {
+                warnings.push(format!(
+                    "View \"{}\" of display \"{}\" was left out, because its color space was.",
+                    view, display_name
+                ));
+            }
+            keep
+        });
+    }
+    let roles = &mut config.roles;
+    for role in [
+        &mut roles.reference,
+        &mut roles.aces_interchange,
+        &mut roles.cie_xyz_d65_interchange,
+        &mut roles.default,
+        &mut roles.data,
+    ] {
+        if role.as_ref().map(|s| removed_spaces.contains(s)) == Some(true) {
+            *role = None;
+        }
+    }
+    roles
+        .other
+        .retain(|_, space| !removed_spaces.contains(space));
+
+    // Things that v1 configs can't express at all.
+    if !config.named_transforms.is_empty() {
+        warnings.push(format!(
+            "{} named transform(s) were left out, because OCIO v1 doesn't support named transforms.",
+            config.named_transforms.len()
+        ));
+    }
+    if !config.inactive_colorspaces.is_empty() {
+        warnings.push("The inactive color spaces list was left out, because OCIO v1 doesn't support it, so inactive color spaces will show up in applications' menus.".into());
+    }
+    if !config.file_rules.is_empty() || !config.viewing_rules.is_empty() {
+        warnings.push(
+            "File and viewing rules were left out, because OCIO v1 doesn't support them.".into(),
+        );
+    }
+    if !config.environment.is_empty() {
+        warnings.push("The environment section was left out, because OCIO v1 doesn't support it.  Context variables still work, but have no default values.".into());
+    }
+    if config.colorspaces.iter().any(|s| !s.aliases.is_empty()) {
+        warnings.push("Color space aliases were left out, because OCIO v1 doesn't support them.  Anything that refers to a color space by an alias will fail to find it.".into());
+    }
+    if config
+        .colorspaces
+        .iter()
+        .flat_map(|s| s.to_reference.iter().chain(s.from_reference.iter()))
+        .chain(config.looks.iter().flat_map(|l| l.transform.iter()))
+        .any(|t| matches!(t, Transform::Raw(_)))
+    {
+        warnings.push("Some transforms were carried through from the imported base config as-is, and may not be supported by OCIO v1.".into());
+    }
+
+    warnings
+}
+
+/// Whether a transform only exists in OCIO v2.
+fn is_v2_only(transform: &Transform) -> bool {
+    match *transform {
+        Transform::FileTransform { .. }
+        | Transform::ColorSpaceTransform { .. }
+        | Transform::MatrixTransform(_)
+        | Transform::AllocationTransform { .. }
+        | Transform::ExponentTransform(..)
+        | Transform::CDLTransform { .. }
+        | Transform::Raw(_) => false,
+
+        Transform::BuiltinTransform { .. }
+        | Transform::RangeTransform { .. }
+        | Transform::ExponentWithLinearTransform { .. }
+        | Transform::ToHSV
+        | Transform::FromHSV
+        | Transform::ACESGamutMapTransform { .. }
+        | Transform::GradingPrimaryTransform { .. }
+        | Transform::GradingToneTransform { .. } => true,
+    }
+}
+
+fn is_linear(evaluator: &Evaluator, colorspace: &str) -> bool {
+    match evaluator.colorspace(colorspace).map(|s| s.encoding) {
+        Ok(Some(Encoding::SceneLinear)) | Ok(Some(Encoding::DisplayLinear)) => true,
+        _ => false,
+    }
+}
+
+/// Bakes a transform chain into a 3D LUT.
+///
+/// Returns the transforms that need to go before the LUT (i.e. a
+/// shaper, for linear input) along with the LUT.
+fn bake(
+    evaluator: &Evaluator,
+    transforms: &[Transform],
+    linear_input: bool,
+) -> Result<(Vec<Transform>, Lut3D), String> {
+    if linear_input {
+        let shaper = Transform::AllocationTransform {
+            allocation: Allocation::Log2,
+            vars: vec![SHAPER_RANGE.0, SHAPER_RANGE.1],
+            direction_inverse: false,
+        };
+        let mut shaped = vec![shaper.clone().invert()];
+        shaped.extend(transforms.iter().cloned());
+        let lut = evaluator.bake_lut_3d(&shaped, BAKED_RES, (0.0, 1.0))?;
+        Ok((vec![shaper], lut))
+    } else {
+        let lut = evaluator.bake_lut_3d(transforms, BAKED_RES, (0.0, 1.0))?;
+        Ok((Vec::new(), lut))
+    }
+}
+
+fn lut_file_name(config: &OCIOConfig, chain: Chain) -> PathBuf {
+    let (name, suffix) = match chain {
+        Chain::ToReference(i) => (&config.colorspaces[i].name, "to_reference"),
+        Chain::FromReference(i) => (&config.colorspaces[i].name, "from_reference"),
+        Chain::Look(i) => (&config.looks[i].name, "look"),
+        Chain::LookInverse(i) => (&config.looks[i].name, "look_inverse"),
+    };
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("v1__{}__{}.cube", name, suffix).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ColorSpace;
+
+    #[test]
+    fn downgrade_01() {
+        let mut config = OCIOConfig::default();
+        config.colorspaces.push(ColorSpace {
+            name: "Linear".into(),
+            encoding: Some(Encoding::SceneLinear),
+            ..ColorSpace::default()
+        });
+        config.colorspaces.push(ColorSpace {
+            name: "sRGB Display".into(),
+            aliases: vec!["srgb".into()],
+            from_reference: vec![Transform::ExponentWithLinearTransform {
+                gamma: 2.4,
+                offset: 0.055,
+                direction_inverse: true,
+            }],
+            ..ColorSpace::default()
+        });
+        config.colorspaces.push(ColorSpace {
+            name: "ACEScct".into(),
+            to_reference: vec![Transform::BuiltinTransform {
+                name: "ACEScct_to_ACES2065-1".into(),
+                direction_inverse: false,
+            }],
+            ..ColorSpace::default()
+        });
+        config.roles.reference = Some("Linear".into());
+        config
+            .roles
+            .other
+            .insert("color_timing".into(), "ACEScct".into());
+
+        let warnings = downgrade(&mut config);
+
+        assert_eq!(config.profile_version, ProfileVersion::V1);
+        assert_eq!(config.colorspaces.len(), 2);
+        assert_eq!(config.roles.other.len(), 0);
+        assert_eq!(warnings.len(), 2); // ACEScct and the alias.
+
+        // The display transform was baked, with a shaper.
+        let transforms = &config.colorspaces[1].from_reference;
+        assert!(!transforms.iter().any(is_v2_only));
+        assert!(matches!(
+            transforms[0],
+            Transform::AllocationTransform { .. }
+        ));
+        let path = Path::new(BAKED_DIR).join("v1__sRGB_Display__from_reference.cube");
+        assert!(config.output_files.contains_key(&path));
+
+        // ...and still evaluates about the same.
+        let evaluator = Evaluator::new(&config);
+        let out = evaluator.eval(transforms, [0.18; 3]).unwrap();
+        assert!((out[0] - 0.4614).abs() < 0.01, "{:?}", out);
+    }
+}
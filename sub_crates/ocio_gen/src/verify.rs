@@ -0,0 +1,123 @@
+//! Sanity checking of a config written to disk, by evaluating its
+//! color spaces' transforms.
+//!
+//! This isn't a replacement for OCIO, just a way to catch broken or
+//! inverted LUT files before a config ships.  See `eval` for which
+//! transforms are supported.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    config::Transform,
+    eval::Evaluator,
+    import::{read_config, ImportError},
+};
+
+/// Number of samples per axis of the sample grid.
+const GRID_RES: usize = 9;
+
+/// Number of samples along the neutral axis.
+const NEUTRAL_RES: usize = 256;
+
+/// The round-trip error of a color space.
+#[derive(Debug, Clone)]
+pub struct RoundTrip {
+    pub colorspace: String,
+    /// The largest absolute difference of any channel between a sample
+    /// and its round-tripped result.  Infinite if a NaN was produced.
+    pub max_error: f64,
+    /// The sample that had the largest error.
+    pub worst_sample: [f64; 3],
+    /// Whether only the neutral axis was checked, because the color
+    /// space does gamut clipping, which isn't invertible.
+    pub neutral_only: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub round_trips: Vec<RoundTrip>,
+    /// (color space, reason) for color spaces that couldn't be checked.
+    pub skipped: Vec<(String, String)>,
+    /// Referenced files that couldn't be read back as LUTs.
+    pub unreadable_files: Vec<PathBuf>,
+}
+
+/// Reloads the config in `dir_path` along with all of its LUT files,
+/// and checks the round trip (to reference and back) of the given color
+/// spaces on a grid of samples in the [0, 1] range.
+pub fn verify_directory<P: AsRef<Path>>(
+    dir_path: P,
+    colorspaces: &[String],
+) -> Result<Report, ImportError> {
+    let (config, _) = read_config(dir_path.as_ref().join("config.ocio"))?;
+    let mut report = Report::default();
+
+    // Reload the LUT files.
+    let evaluator = Evaluator::new(&config);
+    report
+        .unreadable_files
+        .extend(evaluator.unreadable_files.iter().cloned());
+
+    // Check the round trips.
+    for name in colorspaces.iter() {
+        let (to_reference, from_reference) =
+            match (evaluator.to_reference(name), evaluator.from_reference(name)) {
+                (Ok(to), Ok(from)) => (to, from),
+                (Err(reason), _) | (_, Err(reason)) => {
+                    report.skipped.push((name.clone(), reason));
+                    continue;
+                }
+            };
+
+        let neutral_only = to_reference
+            .iter()
+            .chain(from_reference.iter())
+            .any(|t| *t == Transform::ToHSV);
+        let samples: Vec<[f64; 3]> = if neutral_only {
+            (0..NEUTRAL_RES)
+                .map(|i| [i as f64 / (NEUTRAL_RES - 1) as f64; 3])
+                .collect()
+        } else {
+            let n = |i: usize| i as f64 / (GRID_RES - 1) as f64;
+            (0..(GRID_RES * GRID_RES * GRID_RES))
+                .map(|i| {
+                    [
+                        n(i % GRID_RES),
+                        n((i / GRID_RES) % GRID_RES),
+                        n(i / (GRID_RES * GRID_RES)),
+                    ]
+                })
+                .collect()
+        };
+
+        let mut round_trip = RoundTrip {
+            colorspace: name.clone(),
+            max_error: 0.0,
+            worst_sample: [0.0; 3],
+            neutral_only: neutral_only,
+        };
+        let result = samples.iter().try_for_each(|sample| {
+            let rgb = evaluator.eval(&to_reference, *sample)?;
+            let rgb = evaluator.eval(&from_reference, rgb)?;
+            let error = (0..3).fold(0.0f64, |e, i| {
+                let d = (rgb[i] - sample[i]).abs();
+                if d.is_nan() {
+                    std::f64::INFINITY
+                } else {
+                    e.max(d)
+                }
+            });
+            if error > round_trip.max_error {
+                round_trip.max_error = error;
+                round_trip.worst_sample = *sample;
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => report.round_trips.push(round_trip),
+            Err(reason) => report.skipped.push((name.clone(), reason)),
+        }
+    }
+
+    Ok(report)
+}
@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use nanorand::{Pcg64, Rng};
 use rayon::prelude::*;
 
@@ -10,6 +12,51 @@ include!(concat!(env!("OUT_DIR"), "/emor.inc"));
 const EMOR_FACTOR_COUNT: usize = 6;
 const MIN_SLOPE: f32 = 0.005;
 
+/// How much a knee has to reduce the fit error, as a fraction of the
+/// error without it, to be considered real rather than noise.
+const KNEE_MIN_IMPROVEMENT: f32 = 0.1;
+
+/// Knees that change the slope by less than this many stops aren't
+/// distinguishable from the EMoR fit itself.
+const KNEE_MIN_SLOPE_CHANGE: f32 = 0.1;
+
+/// The number of rounds over which the improvement of the error is
+/// measured, for convergence detection.
+pub const CONVERGENCE_WINDOW: usize = 1000;
+
+/// A knee in a transfer function, as found in sensors with dual gain
+/// or dual slope responses: above the encoded value `position`, the
+/// slope of the to-linear curve is multiplied by `slope`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Knee {
+    pub position: f32,
+    pub slope: f32,
+}
+
+impl Knee {
+    /// Applies the knee to a to-linear curve, keeping its end points
+    /// in place.
+    pub fn apply(&self, curve: &mut [f32]) {
+        let first = curve[0];
+        let last = curve[curve.len() - 1];
+        let knee_y = lerp_slice(curve, self.position);
+        let norm = 1.0 / (curve.len() - 1) as f32;
+        for (i, y) in curve.iter_mut().enumerate() {
+            if (i as f32 * norm) > self.position {
+                *y = knee_y + ((*y - knee_y) * self.slope);
+            }
+        }
+
+        let new_last = curve[curve.len() - 1];
+        if new_last != first {
+            let scale = (last - first) / (new_last - first);
+            for y in curve.iter_mut() {
+                *y = first + ((*y - first) * scale);
+            }
+        }
+    }
+}
+
 pub struct EmorEstimator<'a> {
     mappings: &'a [ExposureMapping],
     factors: [f32; EMOR_FACTOR_COUNT],
@@ -18,14 +65,19 @@ pub struct EmorEstimator<'a> {
     best_err: f32,
     current_round: usize,
     rounds_without_change: usize,
+    window_start: (usize, f32), // (round, best error)
+    last_improvement: Option<f32>,
+    stuck: bool,
     step_size: f32,
+    smoothness: f32,
+    knee: Option<Knee>,
     rand: Pcg64,
 }
 
 impl<'a> EmorEstimator<'a> {
     pub fn new(mappings: &'a [ExposureMapping]) -> EmorEstimator<'a> {
         let initial_factors = [0.0f32; EMOR_FACTOR_COUNT];
-        let initial_err = calc_emor_error(mappings, &initial_factors);
+        let initial_err = calc_emor_error(mappings, &initial_factors, None);
         EmorEstimator {
             mappings: mappings,
             factors: initial_factors,
@@ -34,11 +86,70 @@ impl<'a> EmorEstimator<'a> {
             best_err: initial_err,
             current_round: 0,
             rounds_without_change: 0,
+            window_start: (0, initial_err),
+            last_improvement: None,
+            stuck: false,
             step_size: 1.0,
+            smoothness: 0.0,
+            knee: None,
             rand: Pcg64::new_seed(0xdd60c3b293895214c16fa8cdc70cc1c3),
         }
     }
 
+    /// Sets the strength of the regularization that penalizes
+    /// oscillations in the curvature of the fit.
+    ///
+    /// Higher values produce smoother curves at the expense of fit
+    /// accuracy.  Zero (the default) disables regularization.
+    pub fn with_smoothness(mut self, smoothness: f32) -> EmorEstimator<'a> {
+        self.smoothness = smoothness.max(0.0);
+        self.err = self.calc_error(&self.factors);
+        self.best_err = self.calc_error(&self.best_factors);
+        self.restart_convergence();
+        self
+    }
+
+    /// Sets a knee to fit the curve with, e.g. one found by
+    /// `find_knee()`.  Estimation continues from the current estimate.
+    pub fn set_knee(&mut self, knee: Option<Knee>) {
+        self.knee = knee;
+        self.err = self.calc_error(&self.factors);
+        self.best_err = self.calc_error(&self.best_factors);
+        self.restart_convergence();
+    }
+
+    /// Returns whether the estimate has converged, i.e. whether the
+    /// error improved by less than `threshold` (relative to the error)
+    /// over the last `CONVERGENCE_WINDOW` rounds.
+    pub fn has_converged(&self, threshold: f32) -> bool {
+        self.stuck || self.last_improvement.map(|n| n < threshold) == Some(true)
+    }
+
+    /// The relative improvement of the error over the last
+    /// `CONVERGENCE_WINDOW` rounds, if that many rounds have been done.
+    pub fn last_improvement(&self) -> Option<f32> {
+        self.last_improvement
+    }
+
+    pub fn rounds_done(&self) -> usize {
+        self.current_round
+    }
+
+    fn restart_convergence(&mut self) {
+        self.window_start = (self.current_round, self.best_err);
+        self.last_improvement = None;
+        self.stuck = false;
+    }
+
+    fn calc_error(&self, emor_factors: &[f32]) -> f32 {
+        let err = calc_emor_error(self.mappings, emor_factors, self.knee);
+        if self.smoothness > 0.0 {
+            err + (calc_curvature_oscillation(emor_factors) * self.smoothness)
+        } else {
+            err
+        }
+    }
+
     fn rand_0_1(&mut self) -> f32 {
         // Note: we divide by 4294967808 instead of 2^32 because the latter
         // leads to a [0.0, 1.0] mapping instead of [0.0, 1.0) due to floating
@@ -53,58 +164,99 @@ impl<'a> EmorEstimator<'a> {
     }
 
     pub fn do_rounds(&mut self, rounds: usize) {
-        // Use gradient descent to find the lowest error.
-        for _ in self.current_round..(self.current_round + rounds) {
-            self.current_round += 1;
-            let delta = 0.01 * self.step_size;
-            let delta_inv = 1.0 / delta;
-            let mut error_diffs = [0.0f32; EMOR_FACTOR_COUNT];
-            for i in 0..EMOR_FACTOR_COUNT {
-                let neg = self.rand_bool();
-                let delta = if neg { -delta } else { delta };
-                let delta_inv = if neg { -delta_inv } else { delta_inv };
-                let mut test_factors = self.factors;
-                test_factors[i] += delta;
-                error_diffs[i] = (calc_emor_error(self.mappings, &test_factors) - self.err)
-                    * delta_inv
-                    * self.rand_0_1();
+        for _ in 0..rounds {
+            if !self.do_round() {
+                break;
+            }
+        }
+    }
+
+    /// Like `do_rounds()`, but stops early once `time_limit` has
+    /// elapsed.
+    ///
+    /// This lets callers report progress and check for cancellation at
+    /// regular intervals, regardless of how expensive each round is.
+    /// Returns the number of rounds done.
+    pub fn do_rounds_for(&mut self, max_rounds: usize, time_limit: Duration) -> usize {
+        let start = Instant::now();
+        for round_i in 0..max_rounds {
+            if !self.do_round() {
+                return round_i + 1;
+            }
+            if start.elapsed() >= time_limit {
+                return round_i + 1;
             }
+        }
+        max_rounds
+    }
 
-            let mut diff_length = error_diffs.iter().fold(0.0f32, |a, b| a + (b * b)).sqrt();
+    /// Does a single round of estimation.  Returns false if the
+    /// estimation is stuck and further rounds won't help.
+    fn do_round(&mut self) -> bool {
+        // Use gradient descent to find the lowest error.
+        self.current_round += 1;
+        let delta = 0.01 * self.step_size;
+        let delta_inv = 1.0 / delta;
+        let mut error_diffs = [0.0f32; EMOR_FACTOR_COUNT];
+        for i in 0..EMOR_FACTOR_COUNT {
+            let neg = self.rand_bool();
+            let delta = if neg { -delta } else { delta };
+            let delta_inv = if neg { -delta_inv } else { delta_inv };
+            let mut test_factors = self.factors;
+            test_factors[i] += delta;
+            error_diffs[i] =
+                (self.calc_error(&test_factors) - self.err) * delta_inv * self.rand_0_1();
+        }
 
-            // Jostle it a bit if seems to be stuck.
-            if !(diff_length > 0.0) {
-                for i in 0..EMOR_FACTOR_COUNT {
-                    error_diffs[i] = self.rand_0_1() * delta;
-                }
-                diff_length = error_diffs.iter().fold(0.0f32, |a, b| a + (b * b)).sqrt();
+        let mut diff_length = error_diffs.iter().fold(0.0f32, |a, b| a + (b * b)).sqrt();
+
+        // Jostle it a bit if seems to be stuck.
+        if !(diff_length > 0.0) {
+            for i in 0..EMOR_FACTOR_COUNT {
+                error_diffs[i] = self.rand_0_1() * delta;
             }
+            diff_length = error_diffs.iter().fold(0.0f32, |a, b| a + (b * b)).sqrt();
+        }
 
-            if diff_length > 0.0 {
-                let diff_norm = 1.0 / diff_length;
-                for i in 0..EMOR_FACTOR_COUNT {
-                    self.factors[i] -= error_diffs[i] * diff_norm * self.step_size;
-                }
-                self.err = calc_emor_error(self.mappings, &self.factors);
-
-                if self.err.is_finite() && self.err < self.best_err {
-                    self.best_err = self.err;
-                    self.best_factors = self.factors;
-                    self.rounds_without_change = 0;
-                } else {
-                    self.rounds_without_change += 1;
-                }
-            } else {
-                break;
+        if diff_length > 0.0 {
+            let diff_norm = 1.0 / diff_length;
+            for i in 0..EMOR_FACTOR_COUNT {
+                self.factors[i] -= error_diffs[i] * diff_norm * self.step_size;
             }
+            self.err = self.calc_error(&self.factors);
 
-            if self.rounds_without_change >= 64 {
-                self.step_size *= 0.9090909;
+            if self.err.is_finite() && self.err < self.best_err {
+                self.best_err = self.err;
+                self.best_factors = self.factors;
                 self.rounds_without_change = 0;
-                self.factors = self.best_factors;
-                self.err = self.best_err;
+            } else {
+                self.rounds_without_change += 1;
             }
+        } else {
+            self.stuck = true;
+            return false;
         }
+
+        if self.rounds_without_change >= 64 {
+            self.step_size *= 0.9090909;
+            self.rounds_without_change = 0;
+            self.factors = self.best_factors;
+            self.err = self.best_err;
+        }
+
+        if self.current_round - self.window_start.0 >= CONVERGENCE_WINDOW {
+            let (_, start_err) = self.window_start;
+            let improvement = if start_err > 0.0 && start_err.is_finite() {
+                ((start_err - self.best_err) / start_err).max(0.0)
+            } else {
+                // Can't measure progress from an infinite error.
+                1.0
+            };
+            self.last_improvement = Some(improvement);
+            self.window_start = (self.current_round, self.best_err);
+        }
+
+        true
     }
 
     pub fn current_estimate(&self) -> ([f32; EMOR_FACTOR_COUNT], f32) {
@@ -153,6 +305,87 @@ pub fn estimate_inv_emor(mappings: &[ExposureMapping]) -> ([f32; EMOR_FACTOR_COU
     estimator.current_estimate()
 }
 
+/// Computes the error of each channel's mappings against a single
+/// shared fit, with an optional knee.
+///
+/// Useful for gauging how far the individual channels of a sensor
+/// diverge from a common transfer function.  Channels without any
+/// mappings get an error of zero.
+pub fn channel_errors(
+    channel_mappings: &[&[ExposureMapping]],
+    factors: &[f32],
+    knee: Option<Knee>,
+) -> Vec<f32> {
+    channel_mappings
+        .iter()
+        .map(|mappings| {
+            if mappings.is_empty() {
+                0.0
+            } else {
+                calc_emor_error(mappings, factors, knee)
+            }
+        })
+        .collect()
+}
+
+/// Searches for a knee that improves the fit of the given inverse EMoR
+/// factors to the mappings.
+///
+/// Returns the knee and the fit error with it, or `None` if no knee
+/// improves the fit enough to be considered real.
+pub fn find_knee(mappings: &[ExposureMapping], factors: &[f32]) -> Option<(Knee, f32)> {
+    let base_err = calc_emor_error(mappings, factors, None);
+    let err_of = |knee: Knee| calc_emor_error(mappings, factors, Some(knee));
+
+    // Coarse search over the whole range, with slopes in stops.
+    let mut best = (
+        Knee {
+            position: 0.5,
+            slope: 1.0,
+        },
+        base_err,
+    );
+    for p in 2..=18 {
+        for s in -8..=8 {
+            let knee = Knee {
+                position: p as f32 * 0.05,
+                slope: (s as f32 * 0.25).exp2(),
+            };
+            let err = err_of(knee);
+            if err.is_finite() && err < best.1 {
+                best = (knee, err);
+            }
+        }
+    }
+
+    // Refine around the best.
+    let mut step = (0.025f32, 0.125f32);
+    for _ in 0..6 {
+        let center = best.0;
+        for dp in -2..=2 {
+            for ds in -2..=2 {
+                let knee = Knee {
+                    position: (center.position + (dp as f32 * step.0)).max(0.1).min(0.9),
+                    slope: center.slope * (ds as f32 * step.1).exp2(),
+                };
+                let err = err_of(knee);
+                if err.is_finite() && err < best.1 {
+                    best = (knee, err);
+                }
+            }
+        }
+        step = (step.0 * 0.5, step.1 * 0.5);
+    }
+
+    if best.1 < (base_err * (1.0 - KNEE_MIN_IMPROVEMENT))
+        && best.0.slope.log2().abs() >= KNEE_MIN_SLOPE_CHANGE
+    {
+        Some(best)
+    } else {
+        None
+    }
+}
+
 pub fn inv_emor_factors_to_curve(
     factors: &[f32],
     sensor_floor: f32,
@@ -174,20 +407,68 @@ pub fn inv_emor_factors_to_curve(
 
     // Ensure monotonicity.
     let min_diff = MIN_SLOPE / curve.len() as f32;
+    make_monotonic(&mut curve, min_diff);
+
+    curve
+}
+
+/// Makes a curve strictly increasing, by raising each value to at
+/// least `min_diff` above the one before it.
+///
+/// Non-finite values (e.g. from a degenerate fit) are treated as too
+/// low, so the result is always finite.
+fn make_monotonic(curve: &mut [f32], min_diff: f32) {
+    if let Some(first) = curve.first_mut() {
+        if !first.is_finite() {
+            *first = 0.0;
+        }
+    }
     for i in 1..curve.len() {
-        if (curve[i] - curve[i - 1]) < min_diff {
-            curve[i] = curve[i - 1] + min_diff;
+        let min = curve[i - 1] + min_diff;
+        if !curve[i].is_finite() || curve[i] < min {
+            curve[i] = min;
         }
     }
+}
 
-    curve
+/// Measures how much the curvature of the curve oscillates, as the
+/// mean squared change in its second derivative over a coarse grid.
+///
+/// Natural transfer function curves have smoothly varying curvature,
+/// whereas over-fitting to noisy data tends to produce wavy curves.
+fn calc_curvature_oscillation(emor_factors: &[f32]) -> f32 {
+    const GRID_SIZE: usize = 33;
+    let h = 1.0 / (GRID_SIZE - 1) as f32;
+
+    let points: Vec<f32> = (0..GRID_SIZE)
+        .map(|i| eval_inv_emor(emor_factors, i as f32 * h))
+        .collect();
+    let second_derivatives: Vec<f32> = points
+        .windows(3)
+        .map(|w| (w[0] - (2.0 * w[1]) + w[2]) / (h * h))
+        .collect();
+
+    let sum: f32 = second_derivatives
+        .windows(2)
+        .map(|w| {
+            let d = w[1] - w[0];
+            d * d
+        })
+        .sum();
+
+    // The scale factor roughly normalizes this to the magnitude of
+    // the fit error for typical curves.
+    sum / (second_derivatives.len() - 1) as f32 * 0.0001
 }
 
-fn calc_emor_error(mappings: &[ExposureMapping], emor_factors: &[f32]) -> f32 {
+fn calc_emor_error(mappings: &[ExposureMapping], emor_factors: &[f32], knee: Option<Knee>) -> f32 {
     // Compute the curve.
     let mut transfer_curve: Vec<f32> = (0..INV_EMOR_TABLE[0].len())
         .map(|i| inv_emor_at_index(emor_factors, i))
         .collect();
+    if let Some(knee) = knee {
+        knee.apply(&mut transfer_curve);
+    }
     let min_diff = MIN_SLOPE / transfer_curve.len() as f32;
 
     // Penalize non-monotonic curves.
@@ -204,11 +485,7 @@ fn calc_emor_error(mappings: &[ExposureMapping], emor_factors: &[f32]) -> f32 {
     };
 
     // Make it monotonic by clamping.
-    for i in 1..transfer_curve.len() {
-        if (transfer_curve[i] - transfer_curve[i - 1]) < min_diff {
-            transfer_curve[i] = transfer_curve[i - 1] + min_diff;
-        }
-    }
+    make_monotonic(&mut transfer_curve, min_diff);
 
     // Mapping point errors.
     let (point_err_sum, point_err_weight) = mappings
@@ -309,3 +586,178 @@ fn calc_emor_error(mappings: &[ExposureMapping], emor_factors: &[f32]) -> f32 {
 
     (non_mono_err * 8192.0) + point_err
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Histogram;
+
+    /// Exposure mappings of a synthetic bracketed set, from a sensor
+    /// whose to-linear curve is the mean inverse EMoR curve with `knee`.
+    fn knee_mappings(knee: Option<Knee>, exposures: &[f32]) -> Vec<ExposureMapping> {
+        let mut curve = inv_emor_factors_to_curve(&[0.0; EMOR_FACTOR_COUNT], 0.0, 1.0);
+        if let Some(knee) = knee {
+            knee.apply(&mut curve);
+        }
+        let encode = |linear: f32| {
+            let i = curve.partition_point(|y| *y < linear).min(curve.len() - 1);
+            i as f32 / (curve.len() - 1) as f32
+        };
+
+        let histograms: Vec<Histogram> = exposures
+            .iter()
+            .map(|exposure| {
+                let mut histogram = Histogram {
+                    total_samples: 0,
+                    buckets: vec![0; 256],
+                };
+                for i in 0..10000 {
+                    let linear = (i as f32 / 10000.0 * exposure).min(1.0);
+                    histogram.buckets[(encode(linear) * 255.0).round() as usize] += 1;
+                    histogram.total_samples += 1;
+                }
+                histogram
+            })
+            .collect();
+
+        (1..exposures.len())
+            .map(|i| {
+                ExposureMapping::from_histograms(
+                    &histograms[i - 1],
+                    &histograms[i],
+                    exposures[i - 1],
+                    exposures[i],
+                    0.0,
+                    1.0,
+                )
+            })
+            .collect()
+    }
+
+    const BRACKET: [f32; 5] = [0.0625, 0.125, 0.25, 0.5, 1.0];
+
+    #[test]
+    fn find_knee_01() {
+        let knee = Knee {
+            position: 0.6,
+            slope: 4.0,
+        };
+        let mappings = knee_mappings(Some(knee), &BRACKET);
+        let (found, err) = find_knee(&mappings, &[0.0; EMOR_FACTOR_COUNT]).unwrap();
+        assert!((found.position - knee.position).abs() < 0.05);
+        assert!((found.slope.log2() - knee.slope.log2()).abs() < 0.25);
+        assert!(err < calc_emor_error(&mappings, &[0.0; EMOR_FACTOR_COUNT], None));
+    }
+
+    #[test]
+    fn find_knee_02() {
+        // No knee.
+        let mappings = knee_mappings(None, &BRACKET);
+        assert_eq!(find_knee(&mappings, &[0.0; EMOR_FACTOR_COUNT]), None);
+
+        // No mappings, e.g. from an empty or single-image set.
+        assert_eq!(find_knee(&[], &[0.0; EMOR_FACTOR_COUNT]), None);
+        assert!(knee_mappings(None, &BRACKET[..1]).is_empty());
+    }
+
+    #[test]
+    fn find_knee_03() {
+        // A knee at the edge of the search range.
+        let knee = Knee {
+            position: 0.9,
+            slope: 4.0,
+        };
+        let mappings = knee_mappings(Some(knee), &BRACKET);
+        let (found, _) = find_knee(&mappings, &[0.0; EMOR_FACTOR_COUNT]).unwrap();
+        assert!(found.position <= 0.9);
+        assert!((found.position - knee.position).abs() < 0.05);
+    }
+
+    #[test]
+    fn find_knee_04() {
+        // All-identical exposures, which give mappings without any
+        // exposure difference to fit.
+        let mappings = knee_mappings(None, &[0.5; 4]);
+        assert_eq!(mappings.len(), 3);
+        assert_eq!(find_knee(&mappings, &[0.0; EMOR_FACTOR_COUNT]), None);
+    }
+
+    #[test]
+    fn knee_apply_01() {
+        let mut curve: Vec<f32> = (0..=100).map(|i| i as f32 / 100.0).collect();
+        Knee {
+            position: 0.5,
+            slope: 3.0,
+        }
+        .apply(&mut curve);
+
+        // The end points stay in place, and the slope above the knee is
+        // three times the slope below it.
+        assert_eq!(curve[0], 0.0);
+        assert!((curve[100] - 1.0).abs() < 0.0001);
+        let below = curve[50] - curve[40];
+        let above = curve[60] - curve[50];
+        assert!((above / below - 3.0).abs() < 0.001);
+    }
+
+    fn assert_monotonic(curve: &[f32], min_diff: f32) {
+        assert!(curve.iter().all(|n| n.is_finite()));
+        for i in 1..curve.len() {
+            assert!(curve[i] - curve[i - 1] >= min_diff * 0.99);
+        }
+    }
+
+    #[test]
+    fn make_monotonic_01() {
+        let mut curve = vec![0.0, 0.2, 0.15, 0.4, 0.4, 0.3, 0.9, 0.85, 1.0];
+        make_monotonic(&mut curve, 0.001);
+        assert_monotonic(&curve, 0.001);
+
+        // Values that were already increasing are left alone.
+        assert_eq!(curve[0], 0.0);
+        assert_eq!(curve[1], 0.2);
+        assert_eq!(curve[3], 0.4);
+        assert_eq!(curve[6], 0.9);
+        assert_eq!(curve[8], 1.0);
+    }
+
+    #[test]
+    fn make_monotonic_02() {
+        let mut curve = vec![f32::NAN, 0.1, f32::INFINITY, 0.3, f32::NAN, -1.0, 1.0];
+        make_monotonic(&mut curve, 0.01);
+        assert_monotonic(&curve, 0.01);
+        assert_eq!(curve[0], 0.0);
+        assert_eq!(curve[6], 1.0);
+    }
+
+    #[test]
+    fn make_monotonic_03() {
+        // Noisy, non-monotonic input.
+        let mut rng = Pcg64::new_seed(42);
+        let mut curve: Vec<f32> = (0..1024)
+            .map(|i| {
+                let noise = rng.generate::<f32>() - 0.5;
+                (i as f32 / 1023.0) + noise * 0.1
+            })
+            .collect();
+        let min_diff = MIN_SLOPE / curve.len() as f32;
+        make_monotonic(&mut curve, min_diff);
+        assert_monotonic(&curve, min_diff);
+    }
+
+    #[test]
+    fn inv_emor_factors_to_curve_01() {
+        // Wild factors that produce a strongly non-monotonic curve.
+        let factors = [40.0, -35.0, 20.0, -30.0, 25.0, -10.0];
+        let curve = inv_emor_factors_to_curve(&factors, 0.0, 1.0);
+        assert_monotonic(&curve, MIN_SLOPE / curve.len() as f32);
+    }
+
+    #[test]
+    fn inv_emor_factors_to_curve_02() {
+        // A degenerate fit where floor and ceiling coincide.
+        let factors = [0.0; EMOR_FACTOR_COUNT];
+        let curve = inv_emor_factors_to_curve(&factors, 0.5, 0.5);
+        assert_monotonic(&curve, MIN_SLOPE / curve.len() as f32);
+    }
+}
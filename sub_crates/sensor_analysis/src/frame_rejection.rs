@@ -0,0 +1,245 @@
+//! Detection of frames in a bracketed set that would throw off
+//! estimation: duplicates of other frames, and outliers where the scene
+//! changed between frames (e.g. a flash fired or lights were switched
+//! on or off).
+//!
+//! Frames are compared by their cumulative histograms.  Since the same
+//! scene can only get brighter as the exposure increases, a frame with
+//! more exposure than another should have no more pixels below any
+//! given value.  Frames that break that are outliers.
+
+use crate::Histogram;
+
+/// The number of points the cumulative histograms are compared at.
+const CDF_POINTS: usize = 256;
+
+/// Frames whose cumulative histograms differ by at most this fraction
+/// of their pixels are duplicates.
+const DUPLICATE_THRESHOLD: f32 = 0.01;
+
+/// The fraction of pixels that can be darker in a frame with more
+/// exposure than in one with less before they're considered
+/// inconsistent.  Allows for noise and slight camera movement.
+const OUTLIER_THRESHOLD: f32 = 0.05;
+
+/// Frames closer than this exposure ratio aren't checked against each
+/// other for consistency, since noise can outweigh their difference.
+const MIN_EXPOSURE_RATIO: f32 = 1.2;
+
+/// Why a frame should be excluded from estimation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// The frame is nearly identical to the frame at the given index,
+    /// so it adds nothing but extra weight.  If their exposures differ,
+    /// one of them is also wrong.
+    Duplicate(usize),
+
+    /// The frame's brightness is inconsistent with its exposure
+    /// relative to the other frames.
+    Outlier,
+}
+
+/// Finds the frames of a bracketed set that should be excluded from
+/// estimation.
+///
+/// `frames` are the per-channel histograms and the exposure of each
+/// frame, in any order.  Returns whether and why each frame should be
+/// excluded.
+pub fn find_rejected_frames(frames: &[(&[Histogram], f32)]) -> Vec<Option<Rejection>> {
+    let cdfs: Vec<Vec<Vec<f32>>> = frames
+        .iter()
+        .map(|(histograms, _)| histograms.iter().map(cumulative).collect())
+        .collect();
+    let mut rejections = vec![None; frames.len()];
+
+    // Duplicates.  The later of each pair is rejected.
+    for i in 0..frames.len() {
+        for j in 0..i {
+            if rejections[j].is_none()
+                && max_over_channels(&cdfs[i], &cdfs[j], |a, b| (a - b).abs())
+                    <= DUPLICATE_THRESHOLD
+            {
+                rejections[i] = Some(Rejection::Duplicate(j));
+                break;
+            }
+        }
+    }
+
+    // Outliers.  The frame that's inconsistent with the most other
+    // frames is rejected, repeating until the rest are consistent.
+    // When two frames are only inconsistent with each other it's
+    // unclear which is wrong, so neither is rejected.
+    loop {
+        let mut counts = vec![(0usize, 0.0f32); frames.len()]; // (inconsistent frames, total inconsistency)
+        for i in 0..frames.len() {
+            for j in 0..frames.len() {
+                if rejections[i].is_some()
+                    || rejections[j].is_some()
+                    || frames[j].1 < frames[i].1 * MIN_EXPOSURE_RATIO
+                {
+                    continue;
+                }
+                // Pixels that are darker in `j`, despite its greater
+                // exposure.
+                let inconsistency = max_over_channels(&cdfs[j], &cdfs[i], |a, b| a - b);
+                if inconsistency > OUTLIER_THRESHOLD {
+                    for k in [i, j].iter() {
+                        counts[*k].0 += 1;
+                        counts[*k].1 += inconsistency;
+                    }
+                }
+            }
+        }
+
+        let worst = (0..frames.len())
+            .max_by(|a, b| {
+                counts[*a]
+                    .partial_cmp(&counts[*b])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .filter(|i| counts[*i].0 > 1);
+        match worst {
+            Some(i) => rejections[i] = Some(Rejection::Outlier),
+            None => break,
+        }
+    }
+
+    rejections
+}
+
+/// The normalized cumulative histogram, sampled at `CDF_POINTS`
+/// evenly spaced values.
+fn cumulative(histogram: &Histogram) -> Vec<f32> {
+    let norm = 1.0 / histogram.total_samples.max(1) as f32;
+    let len = histogram.buckets.len();
+    let mut sum = 0;
+    let mut bucket = 0;
+    (1..=CDF_POINTS)
+        .map(|i| {
+            let end = i * len / CDF_POINTS;
+            while bucket < end {
+                sum += histogram.buckets[bucket];
+                bucket += 1;
+            }
+            sum as f32 * norm
+        })
+        .collect()
+}
+
+/// The largest value of `f` over all points of all channels.
+fn max_over_channels<F: Fn(f32, f32) -> f32>(a: &[Vec<f32>], b: &[Vec<f32>], f: F) -> f32 {
+    a.iter()
+        .zip(b.iter())
+        .flat_map(|(a, b)| a.iter().zip(b.iter()))
+        .map(|(a, b)| f(*a, *b))
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A histogram of a synthetic scene of evenly spread linear values,
+    /// captured at `exposure` with a linear response that clips at one.
+    fn scene_histogram(exposure: f32) -> Histogram {
+        let mut histogram = Histogram {
+            total_samples: 0,
+            buckets: vec![0; 256],
+        };
+        for i in 0..1000 {
+            let value = (i as f32 / 1000.0 * exposure).min(1.0);
+            histogram.buckets[(value * 255.0).round() as usize] += 1;
+            histogram.total_samples += 1;
+        }
+        histogram
+    }
+
+    fn rejections(histograms: &[(Histogram, f32)]) -> Vec<Option<Rejection>> {
+        let frames: Vec<(&[Histogram], f32)> = histograms
+            .iter()
+            .map(|(h, e)| (std::slice::from_ref(h), *e))
+            .collect();
+        find_rejected_frames(&frames)
+    }
+
+    #[test]
+    fn consistent_set() {
+        let set: Vec<_> = [0.25, 0.5, 1.0, 2.0, 4.0]
+            .iter()
+            .map(|e| (scene_histogram(*e), *e))
+            .collect();
+        assert_eq!(rejections(&set), vec![None; 5]);
+    }
+
+    #[test]
+    fn empty_and_single_frame() {
+        assert_eq!(rejections(&[]), vec![]);
+        assert_eq!(rejections(&[(scene_histogram(1.0), 1.0)]), vec![None]);
+    }
+
+    #[test]
+    fn duplicates() {
+        let set = [
+            (scene_histogram(0.5), 0.5),
+            (scene_histogram(1.0), 1.0),
+            (scene_histogram(1.0), 1.0),
+            (scene_histogram(2.0), 2.0),
+            (scene_histogram(1.0), 1.0),
+        ];
+        assert_eq!(
+            rejections(&set),
+            vec![
+                None,
+                None,
+                Some(Rejection::Duplicate(1)),
+                None,
+                Some(Rejection::Duplicate(1)),
+            ]
+        );
+
+        // A set of identical frames keeps only the first.
+        let set = vec![(scene_histogram(1.0), 1.0); 3];
+        assert_eq!(
+            rejections(&set),
+            vec![
+                None,
+                Some(Rejection::Duplicate(0)),
+                Some(Rejection::Duplicate(0))
+            ]
+        );
+    }
+
+    #[test]
+    fn outliers() {
+        // The middle frame is as bright as if it had 8x the exposure,
+        // e.g. from a flash.
+        let set = [
+            (scene_histogram(0.25), 0.25),
+            (scene_histogram(0.5), 0.5),
+            (scene_histogram(8.0), 1.0),
+            (scene_histogram(2.0), 2.0),
+            (scene_histogram(4.0), 4.0),
+        ];
+        assert_eq!(
+            rejections(&set),
+            vec![None, None, Some(Rejection::Outlier), None, None]
+        );
+
+        // Two frames that are only inconsistent with each other are
+        // both kept, since it's unclear which is wrong.
+        let set = [(scene_histogram(2.0), 1.0), (scene_histogram(1.0), 2.0)];
+        assert_eq!(rejections(&set), vec![None, None]);
+    }
+
+    #[test]
+    fn identical_exposures() {
+        // Frames too close in exposure aren't checked against each
+        // other, even if their brightness differs.
+        let set = [
+            (scene_histogram(1.0), 1.0),
+            (scene_histogram(2.0), 1.0),
+            (scene_histogram(0.5), 1.0),
+        ];
+        assert_eq!(rejections(&set), vec![None; 3]);
+    }
+}
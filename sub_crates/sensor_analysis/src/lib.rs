@@ -2,7 +2,9 @@
 
 pub mod emor;
 mod exposure_mapping;
+pub mod frame_rejection;
 mod histogram;
+pub mod log_fit;
 pub mod utils;
 
 pub use histogram::Histogram;
@@ -12,6 +14,9 @@ pub use exposure_mapping::ExposureMapping;
 /// Estimate a transfer function to fit the given histogram-exposure
 /// pairs.
 ///
+/// `histograms` has the histogram-exposure pairs of each channel, and
+/// can have any number of channels (e.g. one for monochrome images).
+///
 /// The returned lookup table represents a mapping from linear luminance
 /// in [0.0, 1.0] to a non-linear encoding also in [0.0, 1.0].  For
 /// example, if the input exposure mappings are from sRGB images, the
@@ -22,7 +27,7 @@ pub use exposure_mapping::ExposureMapping;
 pub fn estimate_transfer_function(
     histograms: &[&[(Histogram, f32)]],
 ) -> (Vec<Vec<f32>>, Vec<(f32, f32)>, f32) {
-    if histograms[0].len() < 2 || histograms[1].len() < 2 || histograms[2].len() < 2 {
+    if histograms.is_empty() || histograms.iter().any(|h| h.len() < 2) {
         // We don't have enough histograms to infer anything from, so
         // just assume linear.
         return (
@@ -95,6 +100,14 @@ pub fn estimate_transfer_function(
     )
 }
 
+/// Whether the per-channel histograms of an image are all the same, as
+/// they are for monochrome images stored with three channels.
+pub fn is_monochrome(histograms: &[Histogram]) -> bool {
+    histograms.iter().all(|h| {
+        h.total_samples == histograms[0].total_samples && h.buckets == histograms[0].buckets
+    })
+}
+
 pub fn estimate_sensor_floor_ceiling(histograms: &[(Histogram, f32)]) -> Option<(f32, f32)> {
     assert!(histograms.len() > 1);
 
@@ -158,6 +171,45 @@ pub fn estimate_sensor_floor_ceiling(histograms: &[(Histogram, f32)]) -> Option<
     }
 }
 
+/// Computes per-channel gains for a transfer function that is shared
+/// between all channels.
+///
+/// `curve` is a to-linear lookup table, `floor` is the shared sensor
+/// floor, and `ceilings` are the per-channel sensor ceilings, all as
+/// encoded values in [0.0, 1.0].  The returned gains map each channel's
+/// ceiling to 1.0 after the shared floor has been subtracted.
+pub fn shared_curve_channel_gains(curve: &[f32], floor: f32, ceilings: &[f32]) -> Vec<f32> {
+    let out_floor = utils::lerp_slice(curve, floor);
+    ceilings
+        .iter()
+        .map(|ceiling| 1.0 / (utils::lerp_slice(curve, *ceiling) - out_floor))
+        .collect()
+}
+
+/// Computes the exposure scale that best maps the given
+/// `(encoded, linear)` anchor points through a to-linear function.
+///
+/// The fit is done in log space, so it minimizes the error in stops.
+/// Anchors that can't be fit (e.g. with non-positive linear values)
+/// are ignored, and if there are no usable anchors this returns 1.0.
+pub fn anchor_exposure_scale<F: Fn(f32) -> f32>(to_linear: F, anchors: &[(f32, f32)]) -> f32 {
+    let mut log_sum = 0.0f64;
+    let mut count = 0usize;
+    for (encoded, linear) in anchors.iter().copied() {
+        let estimated = to_linear(encoded);
+        if linear > 0.0 && estimated > 0.0 {
+            log_sum += (linear as f64 / estimated as f64).log2();
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        1.0
+    } else {
+        (log_sum / count as f64).exp2() as f32
+    }
+}
+
 /// Calculates the inverse of a transfer function lookup table.
 ///
 /// Assumes the slice represents a semi-monotonic function in the range
@@ -0,0 +1,216 @@
+//! Fitting of analytic log curves to transfer function tables, so
+//! that an estimated transfer function can be implemented without a
+//! lookup table (e.g. in shaders or camera firmware).
+
+/// The number of offsets tried before refining.
+const OFFSET_STEPS: usize = 128;
+
+/// The number of refinement iterations around the best offset.
+const REFINE_ITERATIONS: usize = 48;
+
+/// The range of offsets searched, relative to the linear range of the
+/// table.
+const OFFSET_RANGE: (f64, f64) = (1.0e-7, 1.0e2);
+
+/// A log curve, mapping linear `x` to encoded `a * log10(b * x + c) + d`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LogCurve {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+}
+
+impl LogCurve {
+    pub fn from_linear(&self, x: f32) -> f32 {
+        self.a * (self.b * x + self.c).log10() + self.d
+    }
+
+    pub fn to_linear(&self, y: f32) -> f32 {
+        (10.0f32.powf((y - self.d) / self.a) - self.c) / self.b
+    }
+}
+
+/// Fits a log curve to a to-linear table.
+///
+/// `to_linear` has the linear values of encoded values evenly spaced
+/// over [0.0, 1.0], and should be increasing.  The fit minimizes the
+/// error in the encoded domain.
+///
+/// Returns the curve and its RMS error in encoded values, or `None` if
+/// the table is too small or flat to fit, or has non-finite values.
+pub fn fit_log_curve(to_linear: &[f32]) -> Option<(LogCurve, f32)> {
+    if to_linear.len() < 4 || to_linear.iter().any(|x| !x.is_finite()) {
+        return None;
+    }
+    let min = to_linear.iter().fold(std::f32::INFINITY, |a, b| a.min(*b)) as f64;
+    let max = to_linear.iter().fold(-std::f32::INFINITY, |a, b| a.max(*b)) as f64;
+    if max <= min {
+        return None;
+    }
+
+    // With a fixed offset `k`, fitting `a * log10(x + k) + d` is a
+    // linear least squares problem, so only the offset needs to be
+    // searched for.  It's searched for on a log scale, relative to the
+    // smallest offset that keeps the log's argument positive.
+    let offset = |t: f64| -min + (max - min) * t;
+    let log_range = (OFFSET_RANGE.0.ln(), OFFSET_RANGE.1.ln());
+    let t_of = |i: f64| (log_range.0 + (log_range.1 - log_range.0) * i).exp();
+    let step = 1.0 / OFFSET_STEPS as f64;
+
+    let mut best = (0.0, std::f64::INFINITY);
+    for i in 0..=OFFSET_STEPS {
+        let err = fit_with_offset(to_linear, offset(t_of(i as f64 * step))).2;
+        if err < best.1 {
+            best = (i as f64 * step, err);
+        }
+    }
+
+    // Refine with a golden section search around the best offset.
+    let ratio = (5.0f64.sqrt() - 1.0) / 2.0;
+    let err_at = |i: f64| fit_with_offset(to_linear, offset(t_of(i))).2;
+    let mut lo = (best.0 - step).max(0.0);
+    let mut hi = (best.0 + step).min(1.0);
+    for _ in 0..REFINE_ITERATIONS {
+        let i1 = hi - (hi - lo) * ratio;
+        let i2 = lo + (hi - lo) * ratio;
+        if err_at(i1) < err_at(i2) {
+            hi = i2;
+        } else {
+            lo = i1;
+        }
+    }
+    let k = offset(t_of((lo + hi) * 0.5));
+    let (a, d, err) = fit_with_offset(to_linear, k);
+    if !err.is_finite() || a == 0.0 {
+        return None;
+    }
+
+    // Normalize so that `c` is 1 when possible, which is the more
+    // common way to write log curves:
+    //     log10(x + k) = log10(x / k + 1) + log10(k)
+    let curve = if k > 0.0 {
+        LogCurve {
+            a: a as f32,
+            b: (1.0 / k) as f32,
+            c: 1.0,
+            d: (d + a * k.log10()) as f32,
+        }
+    } else {
+        LogCurve {
+            a: a as f32,
+            b: 1.0,
+            c: k as f32,
+            d: d as f32,
+        }
+    };
+
+    Some((curve, err as f32))
+}
+
+/// Fits `a * log10(x + k) + d` to the table for a fixed `k`.
+///
+/// Returns `(a, d, rms_error)`.
+fn fit_with_offset(to_linear: &[f32], k: f64) -> (f64, f64, f64) {
+    let norm = 1.0 / (to_linear.len() - 1) as f64;
+    let n = to_linear.len() as f64;
+    let samples = || {
+        to_linear.iter().enumerate().map(move |(i, x)| {
+            (
+                (*x as f64 + k).max(std::f64::MIN_POSITIVE).log10(),
+                i as f64 * norm,
+            )
+        })
+    };
+
+    let (mut sum_u, mut sum_y, mut sum_uu, mut sum_uy) = (0.0, 0.0, 0.0, 0.0);
+    for (u, y) in samples() {
+        sum_u += u;
+        sum_y += y;
+        sum_uu += u * u;
+        sum_uy += u * y;
+    }
+    let denom = n * sum_uu - sum_u * sum_u;
+    if denom <= 0.0 {
+        return (0.0, 0.0, std::f64::INFINITY);
+    }
+    let a = (n * sum_uy - sum_u * sum_y) / denom;
+    let d = (sum_y - a * sum_u) / n;
+
+    let sq_err: f64 = samples().map(|(u, y)| (a * u + d - y).powi(2)).sum();
+    (a, d, (sq_err / n).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A to-linear table of the given curve.
+    fn table(curve: &LogCurve, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| curve.to_linear(i as f32 / (len - 1) as f32))
+            .collect()
+    }
+
+    fn assert_fits(curve: &LogCurve, fit: &LogCurve) {
+        for i in 0..=16 {
+            let y = i as f32 / 16.0;
+            let x = curve.to_linear(y);
+            assert!((fit.from_linear(x) - y).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let curve = LogCurve {
+            a: 0.25,
+            b: 50.0,
+            c: 1.0,
+            d: 0.1,
+        };
+        for i in 0..=16 {
+            let y = i as f32 / 16.0;
+            assert!((curve.from_linear(curve.to_linear(y)) - y).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn fit_log_curve_01() {
+        let curve = LogCurve {
+            a: 0.25,
+            b: 50.0,
+            c: 1.0,
+            d: 0.1,
+        };
+        let (fit, err) = fit_log_curve(&table(&curve, 1024)).unwrap();
+        assert!(err < 0.0001);
+        assert_fits(&curve, &fit);
+        assert_eq!(fit.c, 1.0);
+    }
+
+    #[test]
+    fn fit_log_curve_02() {
+        // A pure log curve, whose offset is at the low end of the
+        // searched range.
+        let curve = LogCurve {
+            a: 0.2,
+            b: 1.0,
+            c: 0.0,
+            d: 0.8,
+        };
+        let (fit, err) = fit_log_curve(&table(&curve, 1024)).unwrap();
+        assert!(err < 0.001);
+        assert_fits(&curve, &fit);
+    }
+
+    #[test]
+    fn fit_log_curve_03() {
+        // Tables that are too small, flat, or non-finite.
+        assert_eq!(fit_log_curve(&[]), None);
+        assert_eq!(fit_log_curve(&[0.5]), None);
+        assert_eq!(fit_log_curve(&[0.0, 0.5, 1.0]), None);
+        assert_eq!(fit_log_curve(&[0.5; 16]), None);
+        assert_eq!(fit_log_curve(&[0.0, 0.1, f32::NAN, 1.0]), None);
+        assert_eq!(fit_log_curve(&[0.0, 0.1, f32::INFINITY, 1.0]), None);
+    }
+}
@@ -1,13 +1,33 @@
-//! A simplified wrapper for Arc<RwLock<T>>.
+//! A simplified wrapper for Arc<RwLock<T>>, with change notification.
+//!
+//! Every mutable lock of the data counts as a change, whether or not
+//! the data was actually modified.  Changes can be watched for either
+//! by polling (see `watch()`) or with callbacks (see `subscribe()`).
 
-use std::sync::{Arc, RwLock};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
 
-pub struct Shared<T>(Arc<RwLock<T>>);
+pub struct Shared<T>(Arc<Inner<T>>);
+
+struct Inner<T> {
+    data: RwLock<T>,
+    version: AtomicU64,
+    subscribers: Mutex<Vec<Box<dyn Fn() + Send + Sync + 'static>>>,
+}
 
 impl<T> Shared<T> {
     #[inline(always)]
     pub fn new(inner: T) -> Shared<T> {
-        Shared(Arc::new(RwLock::new(inner)))
+        Shared(Arc::new(Inner {
+            data: RwLock::new(inner),
+            version: AtomicU64::new(0),
+            subscribers: Mutex::new(Vec::new()),
+        }))
     }
 
     /// Get a read-only lock on the data.
@@ -15,7 +35,7 @@ impl<T> Shared<T> {
     /// Will block until the lock is acquired.  Will panic on errors.
     #[inline(always)]
     pub fn lock(&self) -> std::sync::RwLockReadGuard<T> {
-        self.0.read().unwrap()
+        self.0.data.read().unwrap()
     }
 
     /// Attempt to get a read-only lock on the data.
@@ -24,15 +44,18 @@ impl<T> Shared<T> {
     /// if there is an error.  Does not block.
     #[inline(always)]
     pub fn try_lock(&self) -> Option<std::sync::RwLockReadGuard<T>> {
-        self.0.try_read().ok()
+        self.0.data.try_read().ok()
     }
 
     /// Get a mutable lock on the data.
     ///
     /// Will block until the lock is acquired.  Will panic on errors.
     #[inline(always)]
-    pub fn lock_mut(&self) -> std::sync::RwLockWriteGuard<T> {
-        self.0.write().unwrap()
+    pub fn lock_mut(&self) -> WriteGuard<T> {
+        WriteGuard {
+            guard: Some(self.0.data.write().unwrap()),
+            shared: &self.0,
+        }
     }
 
     /// Attempt to get a mutable lock on the data.
@@ -40,8 +63,11 @@ impl<T> Shared<T> {
     /// Will return `None` if the lock is not immediately available or
     /// if there is an error.  Does not block.
     #[inline(always)]
-    pub fn try_lock_mut(&self) -> Option<std::sync::RwLockWriteGuard<T>> {
-        self.0.try_write().ok()
+    pub fn try_lock_mut(&self) -> Option<WriteGuard<T>> {
+        self.0.data.try_write().ok().map(|guard| WriteGuard {
+            guard: Some(guard),
+            shared: &self.0,
+        })
     }
 
     /// Creates a clone of the shared data reference.
@@ -53,6 +79,31 @@ impl<T> Shared<T> {
     pub fn clone_ref(&self) -> Shared<T> {
         self.clone()
     }
+
+    /// The number of times the data has been mutably locked.
+    pub fn version(&self) -> u64 {
+        self.0.version.load(Ordering::Acquire)
+    }
+
+    /// Creates a watcher for polling whether the data has changed since
+    /// the watcher last checked.
+    pub fn watch(&self) -> Watch<T> {
+        Watch {
+            shared: self.clone_ref(),
+            seen_version: self.version(),
+        }
+    }
+
+    /// Registers a function to be called after every change to the
+    /// data, once the mutable lock is released.
+    ///
+    /// It's called on whichever thread made the change, so it should be
+    /// quick, e.g. requesting a UI repaint, and must not mutably lock
+    /// this same data.  It also shouldn't hold a reference to this same
+    /// `Shared`, since that keeps the data alive forever.
+    pub fn subscribe<F: Fn() + Send + Sync + 'static>(&self, f: F) {
+        self.0.subscribers.lock().unwrap().push(Box::new(f));
+    }
 }
 
 impl<T> Clone for Shared<T> {
@@ -71,6 +122,101 @@ impl<T> std::panic::RefUnwindSafe for Shared<T> {}
 
 impl<T: std::fmt::Debug> std::fmt::Debug for Shared<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Debug::fmt(&self.0, f)
+        std::fmt::Debug::fmt(&self.0.data, f)
+    }
+}
+
+//-------------------------------------------------------------
+
+/// A mutable lock on shared data.  Releasing it counts as a change to
+/// the data.
+pub struct WriteGuard<'a, T> {
+    guard: Option<std::sync::RwLockWriteGuard<'a, T>>, // Always `Some` until dropped.
+    shared: &'a Inner<T>,
+}
+
+impl<'a, T> Deref for WriteGuard<'a, T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        self.guard.as_ref().unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for WriteGuard<'a, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        self.guard.as_mut().unwrap()
+    }
+}
+
+impl<'a, T> Drop for WriteGuard<'a, T> {
+    fn drop(&mut self) {
+        // Release the lock first, so subscribers can access the data.
+        self.guard.take();
+
+        self.shared.version.fetch_add(1, Ordering::AcqRel);
+        for subscriber in self.shared.subscribers.lock().unwrap().iter() {
+            subscriber();
+        }
+    }
+}
+
+/// Polls shared data for changes.  Created with `Shared::watch()`.
+pub struct Watch<T> {
+    shared: Shared<T>,
+    seen_version: u64,
+}
+
+impl<T> Watch<T> {
+    /// Returns whether the data has changed since the last call (or
+    /// since the watcher was created), and marks it as seen.
+    pub fn changed(&mut self) -> bool {
+        let version = self.shared.version();
+        let changed = version != self.seen_version;
+        self.seen_version = version;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn watch_01() {
+        let shared = Shared::new(0);
+        let mut watch = shared.watch();
+        assert!(!watch.changed());
+
+        let _ = *shared.lock();
+        assert!(!watch.changed());
+
+        *shared.lock_mut() += 1;
+        *shared.lock_mut() += 1;
+        assert!(watch.changed());
+        assert!(!watch.changed());
+        assert_eq!(shared.version(), 2);
+    }
+
+    #[test]
+    fn subscribe_01() {
+        let shared = Shared::new(0);
+        let calls = Arc::new(AtomicUsize::new(0));
+        {
+            let calls = calls.clone();
+            shared.subscribe(move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        *shared.lock_mut() = 5;
+        if let Some(mut data) = shared.try_lock_mut() {
+            *data += 1;
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(*shared.lock(), 6);
     }
 }